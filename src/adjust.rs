@@ -0,0 +1,161 @@
+//! Exposure and color adjustments applied to an input before it is
+//! tessellated, so a dull photo can be brightened without an image editor.
+
+use image::RgbaImage;
+
+/// Adjustments of every pixel, applied in the order of the fields. The
+/// default leaves images unchanged.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Adjustments {
+    /// Added to every channel, as a fraction of full range from `-1` to `1`.
+    pub brightness: f64,
+    /// How much channels are spread away from mid gray, `1` for unchanged.
+    pub contrast: f64,
+    /// How much colors are spread away from their gray of the same luma, `0`
+    /// for grayscale and `1` for unchanged.
+    pub saturation: f64,
+    /// Channels are raised to the power of its inverse, so above `1`
+    /// brightens the midtones and below darkens them.
+    pub gamma: f64,
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            brightness: 0f64,
+            contrast: 1f64,
+            saturation: 1f64,
+            gamma: 1f64,
+        }
+    }
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        *self == Adjustments::default()
+    }
+
+    /// Adjusts the color channels of every pixel of `image`, leaving alpha.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        if self.is_identity() {
+            return;
+        }
+
+        for pixel in image.pixels_mut() {
+            let mut channels = [0, 1, 2].map(|channel| (pixel.0[channel] as f64) / 255f64);
+            for channel in &mut channels {
+                *channel = ((*channel + self.brightness - 0.5f64) * self.contrast) + 0.5f64;
+            }
+            // Rec. 709 luma of the gamma encoded channels.
+            let luma =
+                (0.2126f64 * channels[0]) + (0.7152f64 * channels[1]) + (0.0722f64 * channels[2]);
+            for channel in &mut channels {
+                *channel = luma + ((*channel - luma) * self.saturation);
+                *channel = channel.clamp(0f64, 1f64).powf(1f64 / self.gamma);
+            }
+            for (value, channel) in pixel.0.iter_mut().zip(channels) {
+                *value = (channel * 255f64).round() as u8;
+            }
+        }
+    }
+}
+
+/// The share of the darkest and of the brightest pixels [`auto_levels`]
+/// lets clip, so that a few specks do not hold back the stretch.
+const AUTO_LEVELS_CLIP: f64 = 0.005f64;
+
+/// The first of `values` past the first `clipped` pixels of `histogram`
+/// counted in their order.
+fn clipped_bound(
+    histogram: &[u64; 256],
+    values: impl Iterator<Item = usize>,
+    clipped: u64,
+) -> usize {
+    let mut seen = 0u64;
+    for value in values {
+        seen += histogram[value];
+        if seen > clipped {
+            return value;
+        }
+    }
+
+    0
+}
+
+/// Stretches every color channel of `image` so its darkest and brightest
+/// values, but for the outermost half percent, span the full range, the way
+/// the auto levels of image editors fix washed out scans and color casts.
+/// Transparent pixels are left out of the histograms.
+pub fn auto_levels(image: &mut RgbaImage) {
+    let mut histograms = [[0u64; 256]; 3];
+    let mut count = 0u64;
+    for pixel in image.pixels().filter(|pixel| pixel.0[3] > 0) {
+        for (histogram, value) in histograms.iter_mut().zip(pixel.0) {
+            histogram[value as usize] += 1;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return;
+    }
+
+    let clipped = ((count as f64) * AUTO_LEVELS_CLIP) as u64;
+    let levels = histograms.map(|histogram| {
+        let low = clipped_bound(&histogram, 0..256, clipped);
+        let high = clipped_bound(&histogram, (0..256).rev(), clipped);
+        let span = (high.max(low + 1) - low) as f64;
+
+        let mut table = [0u8; 256];
+        for (value, level) in table.iter_mut().enumerate() {
+            let stretched = ((value as f64) - (low as f64)) * (255f64 / span);
+            *level = stretched.round().clamp(0f64, 255f64) as u8;
+        }
+        table
+    });
+
+    for pixel in image.pixels_mut() {
+        for (value, table) in pixel.0.iter_mut().zip(&levels) {
+            *value = table[*value as usize];
+        }
+    }
+}
+
+/// Equalizes the histogram of the Rec. 709 luma of `image`, spreading its
+/// pixels evenly from black to white, and shifts the channels of every pixel
+/// by as much as its luma moved to keep its hue. Transparent pixels are left
+/// out of the histogram.
+pub fn equalize(image: &mut RgbaImage) {
+    let luma = |channels: [u8; 4]| {
+        ((0.2126f64 * (channels[0] as f64))
+            + (0.7152f64 * (channels[1] as f64))
+            + (0.0722f64 * (channels[2] as f64)))
+            .round() as usize
+    };
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels().filter(|pixel| pixel.0[3] > 0) {
+        histogram[luma(pixel.0).min(255)] += 1;
+    }
+
+    let mut cumulative = [0u64; 256];
+    let mut total = 0u64;
+    for (sum, count) in cumulative.iter_mut().zip(histogram) {
+        total += count;
+        *sum = total;
+    }
+    // The darkest luma present stays black.
+    let darkest = cumulative.iter().copied().find(|sum| *sum > 0).unwrap_or(0);
+    if total <= darkest {
+        return;
+    }
+    let mapping = cumulative.map(|sum| {
+        (((sum.saturating_sub(darkest)) as f64) * 255f64 / ((total - darkest) as f64)).round()
+    });
+
+    for pixel in image.pixels_mut() {
+        let value = luma(pixel.0).min(255);
+        let shift = mapping[value] - (value as f64);
+        for channel in pixel.0.iter_mut().take(3) {
+            *channel = ((*channel as f64) + shift).round().clamp(0f64, 255f64) as u8;
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::geometry::Point;
+use image::RgbaImage;
+
+/// Rec. 709 luma of every pixel, in `0..=1`, stored row by row.
+pub fn luminance(image: &RgbaImage) -> Vec<f64> {
+    image
+        .pixels()
+        .map(|pixel| {
+            let [red, green, blue, _] = pixel.0;
+            ((0.2126 * (red as f64)) + (0.7152 * (green as f64)) + (0.0722 * (blue as f64)))
+                / 255f64
+        })
+        .collect()
+}
+
+/// Horizontal and vertical Sobel derivatives of the luminance, stored row by
+/// row. Borders are handled by clamping coordinates.
+pub fn sobel(image: &RgbaImage) -> (Vec<f64>, Vec<f64>) {
+    let (width, height) = image.dimensions();
+    let luma = luminance(image);
+    let at = |x: i64, y: i64| -> f64 {
+        let x = x.clamp(0, (width as i64) - 1) as usize;
+        let y = y.clamp(0, (height as i64) - 1) as usize;
+        luma[(y * (width as usize)) + x]
+    };
+
+    let mut horizontal = Vec::with_capacity(luma.len());
+    let mut vertical = Vec::with_capacity(luma.len());
+    for y in 0..(height as i64) {
+        for x in 0..(width as i64) {
+            horizontal.push(
+                (at(x + 1, y - 1) + (2f64 * at(x + 1, y)) + at(x + 1, y + 1))
+                    - (at(x - 1, y - 1) + (2f64 * at(x - 1, y)) + at(x - 1, y + 1)),
+            );
+            vertical.push(
+                (at(x - 1, y + 1) + (2f64 * at(x, y + 1)) + at(x + 1, y + 1))
+                    - (at(x - 1, y - 1) + (2f64 * at(x, y - 1)) + at(x + 1, y - 1)),
+            );
+        }
+    }
+
+    (horizontal, vertical)
+}
+
+/// Sobel gradient magnitude normalized to `0..=1`, stored row by row.
+pub fn gradient_magnitude(image: &RgbaImage) -> Vec<f64> {
+    let (horizontal, vertical) = sobel(image);
+    let magnitude: Vec<f64> = horizontal
+        .iter()
+        .zip(&vertical)
+        .map(|(gx, gy)| ((gx * gx) + (gy * gy)).sqrt())
+        .collect();
+
+    let maximum = magnitude
+        .iter()
+        .fold(0f64, |maximum, value| maximum.max(*value));
+    if maximum > 0f64 {
+        magnitude.into_iter().map(|value| value / maximum).collect()
+    } else {
+        magnitude
+    }
+}
+
+/// Moves every point onto the closest pixel within `radius` whose
+/// [`gradient_magnitude`] reaches `threshold`, leaving points without such a
+/// pixel nearby where they are.
+pub fn snap_to_edges(
+    image: &RgbaImage,
+    points: Vec<Point>,
+    radius: u32,
+    threshold: f64,
+) -> Vec<Point> {
+    let (width, height) = image.dimensions();
+    let magnitude = gradient_magnitude(image);
+    let radius = radius as i64;
+
+    points
+        .into_iter()
+        .map(|point| {
+            let (center_x, center_y) = (point.x.round() as i64, point.y.round() as i64);
+            let mut closest: Option<(i64, i64, i64)> = None;
+            for y in (center_y - radius).max(0)..=(center_y + radius).min((height as i64) - 1) {
+                for x in (center_x - radius).max(0)..=(center_x + radius).min((width as i64) - 1) {
+                    let squared_distance = (x - center_x).pow(2) + (y - center_y).pow(2);
+                    let is_edge =
+                        magnitude[((y as usize) * (width as usize)) + (x as usize)] >= threshold;
+                    let is_closer = closest
+                        .map(|(_, _, closest_distance)| squared_distance < closest_distance)
+                        .unwrap_or(true);
+                    if is_edge && is_closer && (squared_distance <= radius * radius) {
+                        closest = Some((x, y, squared_distance));
+                    }
+                }
+            }
+
+            match closest {
+                None => point,
+                Some((x, y, _)) => Point {
+                    x: x as f64,
+                    y: y as f64,
+                },
+            }
+        })
+        .collect()
+}
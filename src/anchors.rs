@@ -0,0 +1,154 @@
+use crate::geometry::Point;
+use image::{GenericImageView, Rgba};
+
+#[derive(Clone)]
+pub struct Anchor {
+    pub point: Point,
+    pub color: Rgba<u8>,
+}
+
+/// How the color under a point between pixel centers is read.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSampling {
+    /// The pixel the point falls in.
+    #[default]
+    Nearest,
+    /// The four pixels around the point, weighted by how close their
+    /// centers are.
+    Bilinear,
+    /// The mean of the pixels whose centers lie within `radius` pixels of the
+    /// point, so one outlier pixel cannot color a whole cell.
+    Disc { radius: u32 },
+}
+
+impl ColorSampling {
+    pub fn from_name(name: &str) -> Option<ColorSampling> {
+        match name {
+            "nearest" => Some(ColorSampling::Nearest),
+            "bilinear" => Some(ColorSampling::Bilinear),
+            _ => None,
+        }
+    }
+}
+
+/// Color of `image` at `point`, with points on or past the edges read from
+/// the closest edge pixel. Bilinear and disc sampling blend premultiplied
+/// colors, so transparent pixels do not darken their neighbours.
+pub fn sample_color<I>(image: &I, point: &Point, sampling: ColorSampling) -> Rgba<u8>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    let (width, height) = image.dimensions();
+    let clamp = |value: f64, size: u32| (value.max(0f64) as u32).min(size.saturating_sub(1));
+    let radius = match sampling {
+        ColorSampling::Nearest => {
+            return image.get_pixel(clamp(point.x, width), clamp(point.y, height))
+        }
+        ColorSampling::Bilinear => None,
+        ColorSampling::Disc { radius } => Some(radius as f64),
+    };
+
+    // Pixel centers are at half coordinates.
+    let (x, y) = (point.x - 0.5f64, point.y - 0.5f64);
+    if let Some(radius) = radius {
+        // At least the pixel the point falls in, however small the disc.
+        let (inner_x, inner_y) = (clamp(point.x, width), clamp(point.y, height));
+        let mut pixels = vec![(inner_x, inner_y)];
+        let (left, right) = (clamp(x - radius, width), clamp(x + radius, width));
+        let (top, bottom) = (clamp(y - radius, height), clamp(y + radius, height));
+        for pixel_y in top..=bottom {
+            for pixel_x in left..=right {
+                let (dx, dy) = ((pixel_x as f64) - x, (pixel_y as f64) - y);
+                if ((dx * dx) + (dy * dy) <= radius * radius)
+                    && ((pixel_x, pixel_y) != (inner_x, inner_y))
+                {
+                    pixels.push((pixel_x, pixel_y));
+                }
+            }
+        }
+        let weight = 1f64 / (pixels.len() as f64);
+
+        return premultiplied_mean(
+            image,
+            pixels
+                .into_iter()
+                .map(|(pixel_x, pixel_y)| (pixel_x, pixel_y, weight)),
+        );
+    }
+    let (left, top) = (clamp(x.floor(), width), clamp(y.floor(), height));
+    let (right, bottom) = (
+        (left + 1).min(width.saturating_sub(1)),
+        (top + 1).min(height.saturating_sub(1)),
+    );
+    let (x_weight, y_weight) = (
+        (x - x.floor()).clamp(0f64, 1f64),
+        (y - y.floor()).clamp(0f64, 1f64),
+    );
+    let (x_weight, y_weight) = (
+        if x < 0f64 { 0f64 } else { x_weight },
+        if y < 0f64 { 0f64 } else { y_weight },
+    );
+
+    premultiplied_mean(
+        image,
+        [
+            (left, top, (1f64 - x_weight) * (1f64 - y_weight)),
+            (right, top, x_weight * (1f64 - y_weight)),
+            (left, bottom, (1f64 - x_weight) * y_weight),
+            (right, bottom, x_weight * y_weight),
+        ],
+    )
+}
+
+/// The mean of the pixels of `image` at `weights` summing to one, blended
+/// premultiplied so transparent pixels do not darken the others.
+fn premultiplied_mean<I>(image: &I, weights: impl IntoIterator<Item = (u32, u32, f64)>) -> Rgba<u8>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    let mut sum = [0f64; 4];
+    for (pixel_x, pixel_y, weight) in weights {
+        let [red, green, blue, alpha] = image.get_pixel(pixel_x, pixel_y).0;
+        let alpha = (alpha as f64) * weight;
+        sum[0] += (red as f64) * alpha;
+        sum[1] += (green as f64) * alpha;
+        sum[2] += (blue as f64) * alpha;
+        sum[3] += alpha;
+    }
+    if sum[3] <= 0f64 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let channel = |value: f64| value.round().clamp(0f64, 255f64) as u8;
+    Rgba([
+        channel(sum[0] / sum[3]),
+        channel(sum[1] / sum[3]),
+        channel(sum[2] / sum[3]),
+        channel(sum[3]),
+    ])
+}
+
+pub fn color_anchor_points<I>(input_image: &I, anchor_points: Vec<Point>) -> Vec<Anchor>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    sample_anchor_colors(input_image, anchor_points, ColorSampling::Nearest)
+}
+
+/// Anchors at `anchor_points` colored by [`sample_color`].
+pub fn sample_anchor_colors<I>(
+    input_image: &I,
+    anchor_points: Vec<Point>,
+    sampling: ColorSampling,
+) -> Vec<Anchor>
+where
+    I: GenericImageView<Pixel = Rgba<u8>>,
+{
+    anchor_points
+        .into_iter()
+        .map(|point| Anchor {
+            color: sample_color(input_image, &point, sampling),
+            point,
+        })
+        .collect()
+}
@@ -0,0 +1,404 @@
+use crate::anchors::{color_anchor_points, Anchor};
+use crate::compose::{fill_background, Background};
+use crate::geometry::{Bounds, Point};
+use crate::render::{
+    assign_projected, render_voronoi, scale_anchors, CellMap, RenderOptions, UNASSIGNED,
+};
+use crate::sampling::AnchorSampler;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::error::{EncodingError, ImageFormatHint, ParameterError, ParameterErrorKind};
+use image::{
+    imageops, AnimationDecoder, Delay, Frame, ImageError, ImageFormat, ImageResult, RgbaImage,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::io::{Read, Write};
+
+pub struct Animation {
+    pub duration: f64,
+    pub fps: u32,
+    pub zoom: f64,
+    pub focus: Point,
+    pub retessellate_every: Option<u32>,
+    /// Move every anchor up to this many pixels from its place on every
+    /// frame, for the wobble of hand-drawn "boiling" animation.
+    pub boil: Option<f64>,
+    /// How cells appear over the animation instead of all showing at once.
+    pub reveal: Option<CellReveal>,
+    pub easing: Easing,
+    /// What shows where cells have not appeared yet.
+    pub background: Background,
+}
+
+pub fn ken_burns_frame(
+    tessellated_image: &RgbaImage,
+    animation: &Animation,
+    progress: f64,
+) -> RgbaImage {
+    let (image_width, image_height) = tessellated_image.dimensions();
+
+    let scale = 1f64 + ((animation.zoom - 1f64) * progress);
+    let crop_width = ((image_width as f64) / scale).round().max(1f64);
+    let crop_height = ((image_height as f64) / scale).round().max(1f64);
+
+    let start_center = Point {
+        x: (image_width as f64) / 2f64,
+        y: (image_height as f64) / 2f64,
+    };
+    let end_center = Point {
+        x: animation.focus.x * (image_width as f64),
+        y: animation.focus.y * (image_height as f64),
+    };
+    let center = Point {
+        x: start_center.x + ((end_center.x - start_center.x) * progress),
+        y: start_center.y + ((end_center.y - start_center.y) * progress),
+    };
+
+    let left = (center.x - (crop_width / 2f64)).clamp(0f64, (image_width as f64) - crop_width);
+    let top = (center.y - (crop_height / 2f64)).clamp(0f64, (image_height as f64) - crop_height);
+
+    let cropped_image = imageops::crop_imm(
+        tessellated_image,
+        left as u32,
+        top as u32,
+        crop_width as u32,
+        crop_height as u32,
+    )
+    .to_image();
+
+    imageops::resize(
+        &cropped_image,
+        image_width,
+        image_height,
+        imageops::FilterType::Triangle,
+    )
+}
+
+/// How a [`CellReveal`] speeds up and slows down over the animation.
+#[derive(Clone, Copy)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// Eased progress of a `progress` from `0` to `1`.
+    pub fn apply(self, progress: f64) -> f64 {
+        let t = progress.clamp(0f64, 1f64);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1f64 - (1f64 - t).powi(3),
+            Easing::EaseInOut => t * t * (3f64 - (2f64 * t)),
+        }
+    }
+}
+
+/// How the cells of an animation appear.
+pub enum CellReveal {
+    /// In order of how far their anchor is from `origin`, in pixels of the
+    /// input, sweeping over the image like crystals growing.
+    Growth { origin: Point },
+    /// Every cell fading in from a random moment, over a random share of the
+    /// animation of up to `length`, drawn from `seed` so that repainting the
+    /// same cells keeps their timing.
+    FadeIn { length: f64, seed: u64 },
+}
+
+impl CellReveal {
+    /// When every one of `anchors` starts to appear and how long it takes,
+    /// as shares of the animation.
+    fn schedule(&self, anchors: &[Anchor]) -> Vec<(f64, f64)> {
+        match self {
+            CellReveal::Growth { origin } => {
+                let distances: Vec<f64> = anchors
+                    .iter()
+                    .map(|anchor| anchor.point.squared_distance_from(origin).sqrt())
+                    .collect();
+                let farthest = distances.iter().copied().fold(0f64, f64::max).max(1f64);
+
+                distances
+                    .into_iter()
+                    .map(|distance| (distance / farthest, 0f64))
+                    .collect()
+            }
+            CellReveal::FadeIn { length, seed } => {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                let length = length.clamp(0f64, 1f64);
+
+                anchors
+                    .iter()
+                    .map(|_| {
+                        let fade = rng.gen_range((length / 2f64)..=length);
+                        (rng.gen_range(0f64..=(1f64 - fade)), fade)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// How much of a cell appearing at `start` over `length` shows at eased
+/// `progress`.
+fn cell_opacity((start, length): (f64, f64), progress: f64) -> f64 {
+    if length > 0f64 {
+        ((progress - start) / length).clamp(0f64, 1f64)
+    } else if progress >= start {
+        1f64
+    } else {
+        0f64
+    }
+}
+
+/// A painted frame and, while cells are being revealed, which cell every
+/// pixel of it belongs to and when every cell appears.
+struct Painted {
+    image: RgbaImage,
+    reveal: Option<(CellMap, Vec<(f64, f64)>)>,
+}
+
+fn paint(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    animation: &Animation,
+    options: &RenderOptions,
+) -> Painted {
+    let image = render_voronoi(input_image, anchors, options);
+    let reveal = animation.reveal.as_ref().map(|reveal| {
+        (
+            painted_cells(input_image, anchors, &image, options),
+            reveal.schedule(anchors),
+        )
+    });
+
+    Painted { image, reveal }
+}
+
+/// Which anchor every pixel of `painting` belongs to, at the size it was
+/// painted at.
+fn painted_cells(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    painting: &RgbaImage,
+    options: &RenderOptions,
+) -> CellMap {
+    let (image_width, image_height) = input_image.dimensions();
+    let (width, height) = painting.dimensions();
+    let scale =
+        ((width as f64) / (image_width as f64)).max((height as f64) / (image_height as f64));
+
+    assign_projected(
+        &scale_anchors(anchors, (image_width, image_height), (width, height)),
+        width,
+        height,
+        ((options.minimum_distance as f64) * scale).ceil() as u32,
+        options,
+        None,
+    )
+}
+
+/// Lays the cells of `painting` over `background` at the opacity of their
+/// anchor in `opacities`, from `0` to `1`.
+fn reveal_cells(
+    input_image: &RgbaImage,
+    painting: &RgbaImage,
+    cell_map: &CellMap,
+    opacities: &[f64],
+    background: Background,
+) -> RgbaImage {
+    let mut layer = painting.clone();
+    for (pixel, label) in layer.pixels_mut().zip(&cell_map.labels) {
+        if *label != UNASSIGNED {
+            let opacity = opacities[*label as usize].clamp(0f64, 1f64);
+            pixel[3] = ((pixel[3] as f64) * opacity).round() as u8;
+        }
+    }
+
+    fill_background(input_image, layer, background)
+}
+
+pub fn animate(
+    input_image: &RgbaImage,
+    animation: &Animation,
+    anchor_points: Vec<Point>,
+    sampler: &dyn AnchorSampler,
+    rng: &mut dyn RngCore,
+    options: &RenderOptions,
+) -> Vec<RgbaImage> {
+    let (image_width, image_height) = input_image.dimensions();
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+
+    let total_frames = ((animation.duration * (animation.fps as f64)).round() as u32).max(1);
+
+    let mut anchors = color_anchor_points(input_image, anchor_points);
+    let mut painted = paint(input_image, &anchors, animation, options);
+
+    let mut frames = Vec::with_capacity(total_frames as usize);
+    for frame_index in 0..total_frames {
+        match animation.retessellate_every {
+            Some(interval) if (frame_index > 0) && (frame_index % interval == 0) => {
+                let anchor_points = sampler.sample(&bounds, rng);
+                anchors = color_anchor_points(input_image, anchor_points);
+                painted = paint(input_image, &anchors, animation, options);
+            }
+            _ => {}
+        }
+        if let Some(boil) = animation.boil {
+            let jittered = jitter_anchors(&anchors, boil, &bounds, rng);
+            painted = paint(input_image, &jittered, animation, options);
+        }
+
+        let progress = if total_frames > 1 {
+            (frame_index as f64) / ((total_frames - 1) as f64)
+        } else {
+            0f64
+        };
+        let frame = match &painted.reveal {
+            Some((cell_map, schedule)) => {
+                let eased = animation.easing.apply(progress);
+                let opacities: Vec<f64> = schedule
+                    .iter()
+                    .map(|timing| cell_opacity(*timing, eased))
+                    .collect();
+                reveal_cells(
+                    input_image,
+                    &painted.image,
+                    cell_map,
+                    &opacities,
+                    animation.background,
+                )
+            }
+            None => painted.image.clone(),
+        };
+        frames.push(ken_burns_frame(&frame, animation, progress));
+    }
+
+    frames
+}
+
+/// Moves every anchor a random offset of up to `distance` pixels along each
+/// axis, keeping it inside `bounds` and keeping its color.
+fn jitter_anchors(
+    anchors: &[Anchor],
+    distance: f64,
+    bounds: &Bounds,
+    rng: &mut dyn RngCore,
+) -> Vec<Anchor> {
+    let (width, height) = (bounds.width as f64, bounds.height as f64);
+
+    anchors
+        .iter()
+        .map(|anchor| Anchor {
+            point: Point {
+                x: (anchor.point.x + rng.gen_range(-distance..=distance)).clamp(0f64, width - 1f64),
+                y: (anchor.point.y + rng.gen_range(-distance..=distance))
+                    .clamp(0f64, height - 1f64),
+            },
+            color: anchor.color,
+        })
+        .collect()
+}
+
+pub fn encode_gif<W: Write>(frames: Vec<RgbaImage>, fps: u32, writer: W) -> ImageResult<()> {
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    encode_gif_frames(
+        frames.into_iter().map(|frame| (frame, delay)).collect(),
+        writer,
+    )
+}
+
+/// Encodes a looping GIF showing every frame for its own delay.
+pub fn encode_gif_frames<W: Write>(frames: Vec<(RgbaImage, Delay)>, writer: W) -> ImageResult<()> {
+    let mut encoder = GifEncoder::new(writer);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(
+        frames
+            .into_iter()
+            .map(|(frame, delay)| Frame::from_parts(frame, 0, 0, delay)),
+    )
+}
+
+/// APNG frame delay, in seconds as a fraction of 16 bit parts: exact where
+/// it fits, else rounded to milliseconds.
+fn apng_delay(delay: Delay) -> (u16, u16) {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    let (numerator, denominator) = (numerator as u64, (denominator as u64) * 1000);
+    let (mut a, mut b) = (numerator, denominator);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    let divisor = a.max(1);
+    let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+    if (numerator <= u16::MAX as u64) && (denominator <= u16::MAX as u64) {
+        return (numerator as u16, denominator as u16);
+    }
+
+    let milliseconds = ((numerator as f64) * 1000f64 / (denominator as f64)).round();
+    (milliseconds.min(u16::MAX as f64) as u16, 1000)
+}
+
+/// Encodes a looping APNG showing every frame for its own delay, keeping
+/// full color and alpha, which GIF cannot. Frames must share one size.
+pub fn encode_apng<W: Write>(
+    frames: &[(RgbaImage, Delay)],
+    compression: png::Compression,
+    writer: W,
+) -> ImageResult<()> {
+    let png_error = |error| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::Png),
+            error,
+        ))
+    };
+    let (width, height) = frames
+        .first()
+        .map_or((1, 1), |(frame, _)| frame.dimensions());
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(png_error)?;
+
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    for (frame, delay) in frames {
+        if frame.dimensions() != (width, height) {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        let (numerator, denominator) = apng_delay(*delay);
+        writer
+            .set_frame_delay(numerator, denominator)
+            .map_err(png_error)?;
+        writer.write_image_data(frame.as_raw()).map_err(png_error)?;
+    }
+
+    writer.finish().map_err(png_error)
+}
+
+/// Decodes every frame of a GIF, each composed onto the full canvas, with
+/// how long it is shown.
+pub fn decode_gif_frames<R: Read>(reader: R) -> ImageResult<Vec<(RgbaImage, Delay)>> {
+    GifDecoder::new(reader)?
+        .into_frames()
+        .map(|frame| frame.map(|frame| (frame.delay(), frame.into_buffer())))
+        .map(|frame| frame.map(|(delay, frame)| (frame, delay)))
+        .collect()
+}
@@ -0,0 +1,56 @@
+use crate::anchors::Anchor;
+use crate::geometry::{Bounds, Point};
+use crate::palette::Palette;
+use rand::{Rng, RngCore};
+
+/// Biases palette choices along a direction across the canvas.
+pub struct SpatialGradient {
+    /// Direction of the gradient in degrees, `0` running left to right.
+    pub angle: f64,
+    /// How far, as a fraction of the palette, a cell may stray from the color
+    /// its position along the gradient suggests.
+    pub jitter: f64,
+}
+
+/// Colors every anchor with a random entry of `palette`, optionally following
+/// a spatial gradient through the palette order.
+pub fn color_anchors_from_palette(
+    anchor_points: Vec<Point>,
+    bounds: &Bounds,
+    palette: &Palette,
+    gradient: Option<&SpatialGradient>,
+    rng: &mut dyn RngCore,
+) -> Vec<Anchor> {
+    let color_count = palette.colors.len();
+
+    anchor_points
+        .into_iter()
+        .map(|point| {
+            let index = match gradient {
+                None => rng.gen_range(0..color_count),
+                Some(gradient) => {
+                    let (sin, cos) = gradient.angle.to_radians().sin_cos();
+                    let width = bounds.width as f64;
+                    let height = bounds.height as f64;
+
+                    let extent = (width * cos.abs()) + (height * sin.abs());
+                    let projection =
+                        ((point.x - (width / 2f64)) * cos) + ((point.y - (height / 2f64)) * sin);
+                    let t = if extent > 0f64 {
+                        (projection / extent) + 0.5f64
+                    } else {
+                        0.5f64
+                    };
+                    let t = t + ((rng.gen::<f64>() - 0.5f64) * gradient.jitter);
+
+                    ((t.clamp(0f64, 1f64) * (color_count as f64)) as usize).min(color_count - 1)
+                }
+            };
+
+            Anchor {
+                point,
+                color: palette.colors[index],
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,60 @@
+//! Base64 of RFC 4648, for images shown in terminals and sent to the
+//! server as JSON.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` with the standard alphabet and `=` padding.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | ((*byte as u32) << (16 - (8 * index)))
+        });
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - (6 * index))) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Decodes `value` in the standard or the URL safe alphabet, padded or
+/// not and broken across lines or not, or `None` if it is not base64.
+pub fn decode(value: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity((value.len() / 4) * 3);
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+
+    for byte in value.bytes() {
+        let sextet = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' | b'\r' | b'\n' | b' ' => continue,
+            _ => return None,
+        };
+
+        accumulator = ((accumulator << 6) | (sextet as u32)) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((accumulator >> bits) as u8);
+        }
+    }
+
+    // A single character left over encodes less than a byte.
+    if bits >= 6 {
+        return None;
+    }
+
+    Some(decoded)
+}
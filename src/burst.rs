@@ -0,0 +1,116 @@
+//! Bursts of aligned exposures of one scene combined into a single input,
+//! so that the noise of every frame averages out, or so that every part of
+//! the scene comes from the frames exposing it best.
+
+use image::{Rgba, RgbaImage};
+
+/// How the exposures of a burst are combined.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BurstMode {
+    /// The mean of every pixel over the frames, for noisy low-light bursts.
+    Mean,
+    /// Exposure fusion: every pixel weighted by how well exposed, saturated
+    /// and detailed it is in each frame, for bracketed exposures.
+    Fuse,
+}
+
+impl BurstMode {
+    pub fn from_name(name: &str) -> Option<BurstMode> {
+        match name {
+            "mean" => Some(BurstMode::Mean),
+            "fuse" => Some(BurstMode::Fuse),
+            _ => None,
+        }
+    }
+}
+
+/// Spread of the well-exposedness weight around mid-grey, as a share of the
+/// channel range.
+const EXPOSURE_SPREAD: f64 = 0.2;
+/// Pixels the fusion weights are averaged over on every side, so frames
+/// blend instead of switching from one pixel to the next.
+const WEIGHT_RADIUS: i64 = 2;
+
+/// Combines `exposures`, all of the same size, into one image as `mode`
+/// asks.
+pub fn combine_exposures(exposures: &[RgbaImage], mode: BurstMode) -> RgbaImage {
+    let (width, height) = exposures[0].dimensions();
+    let weights: Vec<Vec<f64>> = match mode {
+        BurstMode::Mean => vec![vec![1f64; (width * height) as usize]; exposures.len()],
+        BurstMode::Fuse => exposures.iter().map(fusion_weights).collect(),
+    };
+
+    let mut combined = RgbaImage::new(width, height);
+    for (index, (x, y, pixel)) in combined.enumerate_pixels_mut().enumerate() {
+        let mut sums = [0f64; 4];
+        let mut total = 0f64;
+        for (exposure, weights) in exposures.iter().zip(&weights) {
+            let weight = weights[index];
+            for (sum, channel) in sums.iter_mut().zip(exposure.get_pixel(x, y).0) {
+                *sum += weight * (channel as f64);
+            }
+            total += weight;
+        }
+        *pixel = Rgba(sums.map(|sum| (sum / total.max(f64::MIN_POSITIVE)).round() as u8));
+    }
+
+    combined
+}
+
+/// The weight of every pixel of `exposure` in exposure fusion: the product
+/// of its local contrast, its saturation and how close it is to mid-grey,
+/// averaged over its neighbourhood.
+fn fusion_weights(exposure: &RgbaImage) -> Vec<f64> {
+    let (width, height) = (exposure.width() as i64, exposure.height() as i64);
+    let index =
+        |x: i64, y: i64| ((y.clamp(0, height - 1) * width) + x.clamp(0, width - 1)) as usize;
+    let channels: Vec<[f64; 3]> = exposure
+        .pixels()
+        .map(|pixel| [0, 1, 2].map(|channel| (pixel.0[channel] as f64) / 255f64))
+        .collect();
+    let grey: Vec<f64> = channels
+        .iter()
+        .map(|[red, green, blue]| (red + green + blue) / 3f64)
+        .collect();
+
+    // Never quite zero, so pixels badly exposed in every frame still average.
+    let weights: Vec<f64> = (0..(width * height))
+        .map(|pixel| {
+            let (x, y) = (pixel % width, pixel / width);
+            let contrast = ((4f64 * grey[index(x, y)])
+                - grey[index(x - 1, y)]
+                - grey[index(x + 1, y)]
+                - grey[index(x, y - 1)]
+                - grey[index(x, y + 1)])
+            .abs();
+            let [red, green, blue] = channels[pixel as usize];
+            let mean = grey[pixel as usize];
+            let saturation =
+                (((red - mean).powi(2) + (green - mean).powi(2) + (blue - mean).powi(2)) / 3f64)
+                    .sqrt();
+            let exposedness = [red, green, blue]
+                .map(|channel| {
+                    (-((channel - 0.5f64).powi(2)) / (2f64 * EXPOSURE_SPREAD * EXPOSURE_SPREAD))
+                        .exp()
+                })
+                .iter()
+                .product::<f64>();
+
+            ((contrast + 1e-3f64) * (saturation + 1e-3f64) * exposedness) + 1e-12f64
+        })
+        .collect();
+
+    let blur = |weights: &[f64], step: (i64, i64)| -> Vec<f64> {
+        (0..(width * height))
+            .map(|pixel| {
+                let (x, y) = (pixel % width, pixel / width);
+                (-WEIGHT_RADIUS..=WEIGHT_RADIUS)
+                    .map(|offset| weights[index(x + (offset * step.0), y + (offset * step.1))])
+                    .sum::<f64>()
+                    / (((2 * WEIGHT_RADIUS) + 1) as f64)
+            })
+            .collect()
+    };
+
+    blur(&blur(&weights, (1, 0)), (0, 1))
+}
@@ -0,0 +1,689 @@
+//! Anchor caches, so anchors placed once can be reused across runs.
+//!
+//! A cache starts with `VPAC`, a little endian `u16` version and `u16`
+//! flags, the `u32` width and height of the canvas the anchors were placed
+//! on, their largest `f64` minimum distance and the `u64` [`image_hash`] of
+//! the image they were placed on, all zero when unknown, and the `u64`
+//! number of anchors. Every anchor follows as its `f64` x and y, with the
+//! colors flag its RGBA color and with the radii flag its `f64` radius.
+//! Caches of the first version are the bare coordinates, and of the second
+//! lack the hash; both are still read.
+//!
+//! Caches named `.json` hold the same as an object with the optional
+//! `width`, `height`, `spacing` and `source_hash` and an `anchors` list of
+//! `x`, `y` and optional `color` and `radius`, so they can be written by
+//! hand.
+//!
+//! Caches named `.npy` are NumPy arrays of `float64` rows of `x` and `y`,
+//! followed by the red, green, blue and alpha from 0 to 255 when they have
+//! colors, so they can be made and plotted in Python. They record nothing
+//! about how the anchors were placed, and hold no radii.
+
+use crate::anchors::Anchor;
+use crate::geometry::Point;
+use crate::palette::{format_hex_color, parse_hex_color};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+const MAGIC: &[u8; 4] = b"VPAC";
+const VERSION: u16 = 3;
+const HAS_COLORS: u16 = 1;
+const HAS_RADII: u16 = 2;
+
+/// Anchors saved between runs, with what is known about how they were
+/// placed.
+#[derive(Clone, Default)]
+pub struct AnchorCache {
+    /// Size of the canvas the anchors were placed on.
+    pub canvas: Option<(u32, u32)>,
+    /// Largest minimum distance between the anchors.
+    pub spacing: Option<f64>,
+    /// [`image_hash`] of the image the anchors were placed on.
+    pub source_hash: Option<u64>,
+    pub points: Vec<Point>,
+    /// Color of every point, when they were saved with their colors.
+    pub colors: Option<Vec<Rgba<u8>>>,
+    /// Additive weight of every point in pixels, subtracted from the distance
+    /// to it by `--weighting additive`.
+    pub radii: Option<Vec<f64>>,
+}
+
+/// FNV-1a hash of the size and pixels of an image, to tell whether a cache
+/// was made for it whatever file format it was read from.
+pub fn image_hash(image: &RgbaImage) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let (width, height) = image.dimensions();
+    let size = [width.to_le_bytes(), height.to_le_bytes()].concat();
+    for byte in size.iter().chain(image.as_raw()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+impl AnchorCache {
+    /// Why the anchors may not fit an image of this `canvas` size and
+    /// [`image_hash`], or `None` if nothing known about them says so.
+    pub fn mismatch(&self, canvas: (u32, u32), source_hash: u64) -> Option<String> {
+        match (self.canvas, self.source_hash) {
+            (Some((width, height)), _) if (width, height) != canvas => Some(format!(
+                "were placed on a {}x{} image, not {}x{}",
+                width, height, canvas.0, canvas.1
+            )),
+            (_, Some(hash)) if hash != source_hash => {
+                Some(String::from("were placed on a different image"))
+            }
+            _ => None,
+        }
+    }
+
+    /// The points with their colors, if the cache has a color for every
+    /// point.
+    pub fn anchors(&self) -> Option<Vec<Anchor>> {
+        let colors = self.colors.as_ref()?;
+        if colors.len() != self.points.len() {
+            return None;
+        }
+
+        Some(
+            self.points
+                .iter()
+                .zip(colors)
+                .map(|(point, color)| Anchor {
+                    point: point.clone(),
+                    color: *color,
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Fills `buffer` from `reader`, failing with the byte `offset` the read
+/// started at and `what` was being read when the file ends early or cannot
+/// be read.
+fn read_record(
+    reader: &mut impl Read,
+    buffer: &mut [u8],
+    offset: u64,
+    what: &str,
+) -> io::Result<()> {
+    reader
+        .read_exact(buffer)
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::UnexpectedEof => io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("truncated at byte {}, in {}", offset, what),
+            ),
+            kind => io::Error::new(kind, format!("{} at byte {}, in {}", error, offset, what)),
+        })
+}
+
+/// Writes `bytes`, failing with the byte `offset` they start at and `what`
+/// was being written.
+fn write_record(writer: &mut impl Write, bytes: &[u8], offset: u64, what: &str) -> io::Result<()> {
+    writer.write_all(bytes).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("{} at byte {}, in {}", error, offset, what),
+        )
+    })
+}
+
+fn read_legacy_points(prefix: &[u8], reader: &mut impl Read) -> io::Result<Vec<Point>> {
+    let mut bytes = prefix.to_vec();
+    reader.read_to_end(&mut bytes)?;
+
+    let records = bytes.chunks_exact(16);
+    if !records.remainder().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "truncated at byte {}, in anchor {}",
+                bytes.len() - records.remainder().len(),
+                bytes.len() / 16
+            ),
+        ));
+    }
+
+    Ok(records
+        .map(|record| Point {
+            x: LittleEndian::read_f64(&record[0..8]),
+            y: LittleEndian::read_f64(&record[8..16]),
+        })
+        .collect())
+}
+
+/// A `.json` anchor cache.
+#[derive(Serialize, Deserialize)]
+struct AnchorFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spacing: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_hash: Option<u64>,
+    anchors: Vec<AnchorEntry>,
+}
+
+/// One anchor of an [`AnchorFile`].
+#[derive(Serialize, Deserialize)]
+struct AnchorEntry {
+    x: f64,
+    y: f64,
+    /// `#RRGGBB`, or `#RRGGBBAA` when not opaque.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    radius: Option<f64>,
+}
+
+fn is_json_cache(anchors_cache_path: &str) -> bool {
+    Path::new(anchors_cache_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Collects a value of every entry that has one, failing unless all or none
+/// of the entries have one.
+fn all_or_none<T>(values: Vec<Option<T>>, what: &str) -> io::Result<Option<Vec<T>>> {
+    let count = values.iter().filter(|value| value.is_some()).count();
+    if count == 0 {
+        return Ok(None);
+    }
+    if count < values.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("only {} of {} anchors have a {}", count, values.len(), what),
+        ));
+    }
+
+    Ok(Some(values.into_iter().flatten().collect()))
+}
+
+fn decode_json_cache(anchors_cache_path: &str) -> io::Result<AnchorCache> {
+    let file: AnchorFile = serde_json::from_reader(BufReader::new(File::open(anchors_cache_path)?))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let colors = file
+        .anchors
+        .iter()
+        .enumerate()
+        .map(|(index, anchor)| match &anchor.color {
+            None => Ok(None),
+            Some(color) => parse_hex_color(color).map(Some).ok_or(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "anchor {} has color `{}`, expected `#RRGGBB` or `#RRGGBBAA`",
+                    index, color
+                ),
+            )),
+        })
+        .collect::<io::Result<Vec<Option<Rgba<u8>>>>>()?;
+    let radii = file.anchors.iter().map(|anchor| anchor.radius).collect();
+
+    Ok(AnchorCache {
+        canvas: file.width.zip(file.height),
+        spacing: file.spacing,
+        source_hash: file.source_hash,
+        points: file
+            .anchors
+            .iter()
+            .map(|anchor| Point {
+                x: anchor.x,
+                y: anchor.y,
+            })
+            .collect(),
+        colors: all_or_none(colors, "color")?,
+        radii: all_or_none(radii, "radius")?,
+    })
+}
+
+fn write_json_cache(cache: &AnchorCache, anchors_cache_path: &str) -> io::Result<()> {
+    let file = AnchorFile {
+        width: cache.canvas.map(|(width, _)| width),
+        height: cache.canvas.map(|(_, height)| height),
+        spacing: cache.spacing,
+        source_hash: cache.source_hash,
+        anchors: cache
+            .points
+            .iter()
+            .enumerate()
+            .map(|(index, point)| AnchorEntry {
+                x: point.x,
+                y: point.y,
+                color: cache
+                    .colors
+                    .as_ref()
+                    .map(|colors| format_hex_color(colors[index])),
+                radius: cache.radii.as_ref().map(|radii| radii[index]),
+            })
+            .collect(),
+    };
+    let mut writer = BufWriter::new(File::create(anchors_cache_path)?);
+    serde_json::to_writer_pretty(&mut writer, &file).map_err(io::Error::other)?;
+
+    writer.flush()
+}
+
+fn is_npy_cache(anchors_cache_path: &str) -> bool {
+    Path::new(anchors_cache_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("npy"))
+}
+
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+fn invalid_npy(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The text after `key` in the Python dict literal of an `.npy` header.
+fn npy_header_value<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+    let quoted = format!("'{}'", key);
+    let start = header
+        .find(&quoted)
+        .ok_or(invalid_npy(format!("the header has no {}", quoted)))?;
+    let value = header[(start + quoted.len())..].trim_start();
+
+    Ok(value.strip_prefix(':').unwrap_or(value).trim_start())
+}
+
+fn decode_npy_cache(anchors_cache_path: &str) -> io::Result<AnchorCache> {
+    let mut bytes = Vec::new();
+    File::open(anchors_cache_path)?.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(NPY_MAGIC) || (bytes.len() < 10) {
+        return Err(invalid_npy(String::from("not a NumPy array")));
+    }
+    // Versions 2 and 3 give the header length in four bytes instead of two.
+    let (header_length, header_start) = match bytes[6] {
+        1 => (LittleEndian::read_u16(&bytes[8..10]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (LittleEndian::read_u32(&bytes[8..12]) as usize, 12),
+        version => {
+            return Err(invalid_npy(format!(
+                "unsupported NumPy format version {}",
+                version
+            )))
+        }
+    };
+    let data_start = header_start + header_length;
+    let header = bytes
+        .get(header_start..data_start)
+        .map(String::from_utf8_lossy)
+        .ok_or(invalid_npy(String::from("truncated in the header")))?;
+
+    let descr = npy_header_value(&header, "descr")?;
+    let (is_big_endian, width) = match descr.get(..5) {
+        Some("'<f8'") | Some("'=f8'") => (false, 8),
+        Some("'>f8'") => (true, 8),
+        Some("'<f4'") | Some("'=f4'") => (false, 4),
+        Some("'>f4'") => (true, 4),
+        _ => {
+            return Err(invalid_npy(format!(
+                "the array holds {}, expected float64 or float32",
+                descr.split(',').next().unwrap_or(descr)
+            )))
+        }
+    };
+    let fortran_order = npy_header_value(&header, "fortran_order")?.starts_with("True");
+    let shape = npy_header_value(&header, "shape")?;
+    let shape: Vec<usize> = shape
+        .strip_prefix('(')
+        .and_then(|shape| shape.split(')').next())
+        .ok_or(invalid_npy(String::from("the header has no shape")))?
+        .split(',')
+        .map(str::trim)
+        .filter(|length| !length.is_empty())
+        .map(|length| {
+            length
+                .parse::<usize>()
+                .map_err(|_| invalid_npy(format!("the shape has length `{}`", length)))
+        })
+        .collect::<io::Result<Vec<usize>>>()?;
+    let (count, columns) = match shape[..] {
+        [count, columns] if (columns == 2) || (columns == 6) => (count, columns),
+        _ => {
+            return Err(invalid_npy(format!(
+                "the array has shape {:?}, expected N×2 or N×6",
+                shape
+            )))
+        }
+    };
+
+    let data = &bytes[data_start..];
+    let length = count
+        .checked_mul(columns * width)
+        .ok_or(invalid_npy(format!(
+            "the array has {} anchors, too many to address",
+            count
+        )))?;
+    if data.len() < length {
+        return Err(invalid_npy(format!(
+            "truncated at byte {}, in anchor {}",
+            bytes.len(),
+            data.len() / (columns * width)
+        )));
+    }
+    let value = |row: usize, column: usize| {
+        let index = match fortran_order {
+            true => (column * count) + row,
+            false => (row * columns) + column,
+        };
+        let bytes = &data[(index * width)..((index + 1) * width)];
+        match (width, is_big_endian) {
+            (8, false) => LittleEndian::read_f64(bytes),
+            (8, true) => BigEndian::read_f64(bytes),
+            (_, false) => LittleEndian::read_f32(bytes) as f64,
+            (_, true) => BigEndian::read_f32(bytes) as f64,
+        }
+    };
+    let channel = |row: usize, column: usize| value(row, column).round().clamp(0f64, 255f64) as u8;
+
+    Ok(AnchorCache {
+        points: (0..count)
+            .map(|row| Point {
+                x: value(row, 0),
+                y: value(row, 1),
+            })
+            .collect(),
+        colors: (columns == 6).then(|| {
+            (0..count)
+                .map(|row| {
+                    Rgba([
+                        channel(row, 2),
+                        channel(row, 3),
+                        channel(row, 4),
+                        channel(row, 5),
+                    ])
+                })
+                .collect()
+        }),
+        ..AnchorCache::default()
+    })
+}
+
+fn write_npy_cache(cache: &AnchorCache, anchors_cache_path: &str) -> io::Result<()> {
+    if cache.radii.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "`.npy` anchors hold no radii, use `.json` or another name",
+        ));
+    }
+
+    let columns = match cache.colors {
+        Some(_) => 6,
+        None => 2,
+    };
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        cache.points.len(),
+        columns
+    );
+    // The data starts 64 byte aligned, after the header padded with spaces
+    // and ended by a newline.
+    let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - (unpadded % 64)) % 64));
+    header.push('\n');
+
+    let mut writer = BufWriter::new(File::create(anchors_cache_path)?);
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    let mut length = [0u8; 2];
+    LittleEndian::write_u16(&mut length, header.len() as u16);
+    writer.write_all(&length)?;
+    writer.write_all(header.as_bytes())?;
+
+    let mut value = [0u8; 8];
+    for (index, point) in cache.points.iter().enumerate() {
+        let color = cache.colors.as_ref().map(|colors| colors[index].0);
+        let channels = color.iter().flatten().map(|channel| *channel as f64);
+        for number in [point.x, point.y].into_iter().chain(channels) {
+            LittleEndian::write_f64(&mut value, number);
+            writer.write_all(&value)?;
+        }
+    }
+
+    writer.flush()
+}
+
+static KEEP_DECODED: AtomicBool = AtomicBool::new(false);
+
+/// Caches already decoded, by path, with the size and modification time of
+/// the file they were decoded from.
+type DecodedCaches = HashMap<String, (u64, SystemTime, AnchorCache)>;
+
+static DECODED: Mutex<Option<DecodedCaches>> = Mutex::new(None);
+
+/// Makes [`read_anchor_cache`] keep every cache it decodes in memory and hand
+/// it out again while the file is unchanged, for processes that render the
+/// same anchors over and over.
+pub fn keep_decoded_caches(enabled: bool) {
+    KEEP_DECODED.store(enabled, Ordering::Relaxed);
+}
+
+fn decoded_caches() -> MutexGuard<'static, Option<DecodedCaches>> {
+    match DECODED.lock() {
+        Ok(decoded) => decoded,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+pub fn read_anchor_cache(anchors_cache_path: &str) -> io::Result<AnchorCache> {
+    if !KEEP_DECODED.load(Ordering::Relaxed) {
+        return decode_anchor_cache(anchors_cache_path);
+    }
+
+    let metadata = fs::metadata(anchors_cache_path)?;
+    let state = (metadata.len(), metadata.modified()?);
+    if let Some((length, modified, cache)) = decoded_caches()
+        .get_or_insert_with(HashMap::new)
+        .get(anchors_cache_path)
+    {
+        if (*length, *modified) == state {
+            return Ok(cache.clone());
+        }
+    }
+
+    let cache = decode_anchor_cache(anchors_cache_path)?;
+    decoded_caches().get_or_insert_with(HashMap::new).insert(
+        anchors_cache_path.to_string(),
+        (state.0, state.1, cache.clone()),
+    );
+
+    Ok(cache)
+}
+
+fn decode_anchor_cache(anchors_cache_path: &str) -> io::Result<AnchorCache> {
+    if is_json_cache(anchors_cache_path) {
+        return decode_json_cache(anchors_cache_path);
+    }
+    if is_npy_cache(anchors_cache_path) {
+        return decode_npy_cache(anchors_cache_path);
+    }
+    let mut reader = BufReader::new(File::open(anchors_cache_path)?);
+
+    let mut magic = Vec::with_capacity(MAGIC.len());
+    (&mut reader)
+        .take(MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    if magic != MAGIC {
+        return Ok(AnchorCache {
+            points: read_legacy_points(&magic, &mut reader)?,
+            ..AnchorCache::default()
+        });
+    }
+
+    let mut header = [0u8; 36];
+    read_record(&mut reader, &mut header[0..4], 4, "the header")?;
+    let version = LittleEndian::read_u16(&header[0..2]);
+    let flags = LittleEndian::read_u16(&header[2..4]);
+    let header = match version {
+        2 => &mut header[..28],
+        VERSION => &mut header[..],
+        version => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported anchor cache version {}", version),
+            ))
+        }
+    };
+    read_record(&mut reader, &mut header[4..], 8, "the header")?;
+    let (width, height) = (
+        LittleEndian::read_u32(&header[4..8]),
+        LittleEndian::read_u32(&header[8..12]),
+    );
+    let spacing = LittleEndian::read_f64(&header[12..20]);
+    let source_hash = match version {
+        2 => 0,
+        _ => LittleEndian::read_u64(&header[20..28]),
+    };
+    let count = LittleEndian::read_u64(&header[(header.len() - 8)..]);
+
+    let has_colors = (flags & HAS_COLORS) != 0;
+    let has_radii = (flags & HAS_RADII) != 0;
+    let mut points = Vec::new();
+    let mut colors = Vec::new();
+    let mut radii = Vec::new();
+    let mut record = [0u8; 28];
+    let radius_offset = if has_colors { 20 } else { 16 };
+    let record = if has_radii {
+        &mut record[..(radius_offset + 8)]
+    } else {
+        &mut record[..radius_offset]
+    };
+    let first_record = (MAGIC.len() + header.len()) as u64;
+    for index in 0..count {
+        read_record(
+            &mut reader,
+            record,
+            first_record + index * (record.len() as u64),
+            &format!("anchor {} of {}", index, count),
+        )?;
+        points.push(Point {
+            x: LittleEndian::read_f64(&record[0..8]),
+            y: LittleEndian::read_f64(&record[8..16]),
+        });
+        if has_colors {
+            colors.push(Rgba([record[16], record[17], record[18], record[19]]));
+        }
+        if has_radii {
+            radii.push(LittleEndian::read_f64(
+                &record[radius_offset..(radius_offset + 8)],
+            ));
+        }
+    }
+
+    Ok(AnchorCache {
+        canvas: ((width > 0) && (height > 0)).then_some((width, height)),
+        spacing: (spacing > 0f64).then_some(spacing),
+        source_hash: (source_hash != 0).then_some(source_hash),
+        points,
+        colors: has_colors.then_some(colors),
+        radii: has_radii.then_some(radii),
+    })
+}
+
+pub fn write_anchor_cache(cache: &AnchorCache, anchors_cache_path: &str) -> io::Result<()> {
+    let colors = match &cache.colors {
+        Some(colors) if colors.len() != cache.points.len() => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "every anchor needs a color",
+            ))
+        }
+        colors => colors.as_ref(),
+    };
+    let radii = match &cache.radii {
+        Some(radii) if radii.len() != cache.points.len() => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "every anchor needs a radius",
+            ))
+        }
+        radii => radii.as_ref(),
+    };
+    if let Some(decoded) = decoded_caches().as_mut() {
+        decoded.remove(anchors_cache_path);
+    }
+    if is_json_cache(anchors_cache_path) {
+        return write_json_cache(cache, anchors_cache_path);
+    }
+    if is_npy_cache(anchors_cache_path) {
+        return write_npy_cache(cache, anchors_cache_path);
+    }
+    let mut writer = BufWriter::new(File::create(anchors_cache_path)?);
+
+    let mut header = [0u8; 36];
+    LittleEndian::write_u16(&mut header[0..2], VERSION);
+    let mut flags = 0;
+    if colors.is_some() {
+        flags |= HAS_COLORS;
+    }
+    if radii.is_some() {
+        flags |= HAS_RADII;
+    }
+    LittleEndian::write_u16(&mut header[2..4], flags);
+    let (width, height) = cache.canvas.unwrap_or((0, 0));
+    LittleEndian::write_u32(&mut header[4..8], width);
+    LittleEndian::write_u32(&mut header[8..12], height);
+    LittleEndian::write_f64(&mut header[12..20], cache.spacing.unwrap_or(0f64));
+    LittleEndian::write_u64(&mut header[20..28], cache.source_hash.unwrap_or(0));
+    LittleEndian::write_u64(&mut header[28..36], cache.points.len() as u64);
+    write_record(&mut writer, MAGIC, 0, "the header")?;
+    write_record(&mut writer, &header, MAGIC.len() as u64, "the header")?;
+
+    let radius_offset = if colors.is_some() { 20 } else { 16 };
+    let record_length = if radii.is_some() {
+        radius_offset + 8
+    } else {
+        radius_offset
+    };
+    let mut record = [0u8; 28];
+    for (index, point) in cache.points.iter().enumerate() {
+        LittleEndian::write_f64(&mut record[0..8], point.x);
+        LittleEndian::write_f64(&mut record[8..16], point.y);
+        if let Some(colors) = colors {
+            record[16..20].copy_from_slice(&colors[index].0);
+        }
+        if let Some(radii) = radii {
+            LittleEndian::write_f64(
+                &mut record[radius_offset..(radius_offset + 8)],
+                radii[index],
+            );
+        }
+        write_record(
+            &mut writer,
+            &record[..record_length],
+            (MAGIC.len() + header.len() + index * record_length) as u64,
+            &format!("anchor {} of {}", index, cache.points.len()),
+        )?;
+    }
+
+    writer.flush()
+}
+
+pub fn read_anchor_points_from_file(anchors_cache_path: &str) -> std::io::Result<Vec<Point>> {
+    read_anchor_cache(anchors_cache_path).map(|cache| cache.points)
+}
+
+pub fn write_anchor_points_to_file(
+    anchor_points: Vec<Point>,
+    anchors_cache_path: &str,
+) -> std::io::Result<()> {
+    write_anchor_cache(
+        &AnchorCache {
+            points: anchor_points,
+            ..AnchorCache::default()
+        },
+        anchors_cache_path,
+    )
+}
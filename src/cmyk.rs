@@ -0,0 +1,568 @@
+//! CMYK TIFF output for print workflows that reject RGB files, with colors
+//! separated through the table of an ICC output profile when one is given.
+
+use crate::color::{d50_lab, d50_xyz};
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn field(bytes: &[u8], offset: usize, length: usize) -> io::Result<&[u8]> {
+    bytes
+        .get(offset..(offset + length))
+        .ok_or_else(|| invalid_data("ICC profile is truncated"))
+}
+
+fn u8_at(bytes: &[u8], offset: usize) -> io::Result<u8> {
+    Ok(field(bytes, offset, 1)?[0])
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    let field = field(bytes, offset, 2)?;
+    Ok(u16::from_be_bytes([field[0], field[1]]))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    let field = field(bytes, offset, 4)?;
+    Ok(u32::from_be_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+/// An `s15Fixed16Number`.
+fn fixed_at(bytes: &[u8], offset: usize) -> io::Result<f64> {
+    Ok((u32_at(bytes, offset)? as i32 as f64) / 65536f64)
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| (row[0] * vector[0]) + (row[1] * vector[1]) + (row[2] * vector[2]))
+}
+
+/// A one dimensional curve from `0..=1` to `0..=1`.
+enum Curve {
+    /// Evenly spaced samples, linearly interpolated.
+    Table(Vec<f64>),
+    /// One of the ICC parametric functions, with `g a b c d e f`.
+    Parametric(u16, [f64; 7]),
+}
+
+impl Curve {
+    fn apply(&self, x: f64) -> f64 {
+        let x = x.clamp(0f64, 1f64);
+        let y = match self {
+            Curve::Table(samples) if samples.is_empty() => x,
+            Curve::Table(samples) => {
+                let position = x * ((samples.len() - 1) as f64);
+                let index = (position.floor() as usize).min(samples.len() - 1);
+                let next = (index + 1).min(samples.len() - 1);
+                let fraction = position - (index as f64);
+                samples[index] + ((samples[next] - samples[index]) * fraction)
+            }
+            Curve::Parametric(function, [g, a, b, c, d, e, f]) => {
+                let power = |base: f64| base.max(0f64).powf(*g);
+                match function {
+                    0 => power(x),
+                    1 if x >= -b / a => power((a * x) + b),
+                    1 => 0f64,
+                    2 if x >= -b / a => power((a * x) + b) + c,
+                    2 => *c,
+                    3 if x >= *d => power((a * x) + b),
+                    3 => c * x,
+                    _ if x >= *d => power((a * x) + b) + e,
+                    _ => (c * x) + f,
+                }
+            }
+        };
+
+        y.clamp(0f64, 1f64)
+    }
+}
+
+/// Reads a `curv` or `parametricCurve` at `offset`, with the offset just past
+/// it.
+fn parse_curve(bytes: &[u8], offset: usize) -> io::Result<(Curve, usize)> {
+    match field(bytes, offset, 4)? {
+        b"curv" => {
+            let count = u32_at(bytes, offset + 8)? as usize;
+            let curve = match count {
+                1 => {
+                    let gamma = (u16_at(bytes, offset + 12)? as f64) / 256f64;
+                    Curve::Parametric(0, [gamma, 1f64, 0f64, 0f64, 0f64, 0f64, 0f64])
+                }
+                count => Curve::Table(
+                    (0..count)
+                        .map(|index| {
+                            u16_at(bytes, offset + 12 + (index * 2))
+                                .map(|value| (value as f64) / 65535f64)
+                        })
+                        .collect::<io::Result<Vec<f64>>>()?,
+                ),
+            };
+            Ok((curve, offset + 12 + (count * 2)))
+        }
+        b"para" => {
+            let function = u16_at(bytes, offset + 8)?;
+            let count = match function {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err(invalid_data("Unknown ICC parametric curve")),
+            };
+            let mut parameters = [1f64, 1f64, 0f64, 0f64, 0f64, 0f64, 0f64];
+            for (index, parameter) in parameters.iter_mut().enumerate().take(count) {
+                *parameter = fixed_at(bytes, offset + 12 + (index * 4))?;
+            }
+            Ok((
+                Curve::Parametric(function, parameters),
+                offset + 12 + (count * 4),
+            ))
+        }
+        _ => Err(invalid_data("Unknown ICC curve type")),
+    }
+}
+
+/// Reads `count` curves one after another, every one starting on four
+/// bytes.
+fn parse_curves(bytes: &[u8], mut offset: usize, count: usize) -> io::Result<Vec<Curve>> {
+    let mut curves = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (curve, end) = parse_curve(bytes, offset)?;
+        curves.push(curve);
+        offset = (end + 3) & !3;
+    }
+
+    Ok(curves)
+}
+
+/// Reads `count` tables of `entries` samples of `width` bytes.
+fn parse_tables(
+    bytes: &[u8],
+    offset: usize,
+    count: usize,
+    entries: usize,
+    width: usize,
+) -> io::Result<Vec<Curve>> {
+    (0..count)
+        .map(|table| {
+            (0..entries)
+                .map(|entry| {
+                    sample_at(bytes, offset + (((table * entries) + entry) * width), width)
+                })
+                .collect::<io::Result<Vec<f64>>>()
+                .map(Curve::Table)
+        })
+        .collect()
+}
+
+fn sample_at(bytes: &[u8], offset: usize, width: usize) -> io::Result<f64> {
+    if width == 1 {
+        Ok((u8_at(bytes, offset)? as f64) / 255f64)
+    } else {
+        Ok((u16_at(bytes, offset)? as f64) / 65535f64)
+    }
+}
+
+/// A color lookup table, with the first input varying slowest.
+struct Clut {
+    grid: Vec<usize>,
+    outputs: usize,
+    values: Vec<f64>,
+}
+
+impl Clut {
+    fn parse(
+        bytes: &[u8],
+        offset: usize,
+        grid: Vec<usize>,
+        outputs: usize,
+        width: usize,
+    ) -> io::Result<Clut> {
+        if grid.iter().any(|points| *points < 2) {
+            return Err(invalid_data("ICC color lookup table is too small"));
+        }
+        let count = grid.iter().product::<usize>() * outputs;
+        let values = (0..count)
+            .map(|index| sample_at(bytes, offset + (index * width), width))
+            .collect::<io::Result<Vec<f64>>>()?;
+
+        Ok(Clut {
+            grid,
+            outputs,
+            values,
+        })
+    }
+
+    /// Multilinear interpolation between the grid points around `input`.
+    fn apply(&self, input: &[f64]) -> Vec<f64> {
+        let mut strides = vec![self.outputs; self.grid.len()];
+        for axis in (0..self.grid.len().saturating_sub(1)).rev() {
+            strides[axis] = strides[axis + 1] * self.grid[axis + 1];
+        }
+        let cells: Vec<(usize, f64)> = input
+            .iter()
+            .zip(&self.grid)
+            .map(|(value, points)| {
+                let position = value.clamp(0f64, 1f64) * ((points - 1) as f64);
+                let index = (position.floor() as usize).min(points - 2);
+                (index, position - (index as f64))
+            })
+            .collect();
+
+        let mut output = vec![0f64; self.outputs];
+        for corner in 0..(1usize << self.grid.len()) {
+            let mut weight = 1f64;
+            let mut base = 0;
+            for (axis, (index, fraction)) in cells.iter().enumerate() {
+                let is_upper = (corner >> axis) & 1 == 1;
+                weight *= if is_upper { *fraction } else { 1f64 - fraction };
+                base += (index + (is_upper as usize)) * strides[axis];
+            }
+            if weight == 0f64 {
+                continue;
+            }
+            for (channel, value) in output.iter_mut().enumerate() {
+                *value += weight * self.values[base + channel];
+            }
+        }
+
+        output
+    }
+}
+
+/// A PCS to device transform of an `lut8`, `lut16` or `lutBToA` tag.
+struct Lut {
+    /// Matrix of `lut8` and `lut16` tables, used with XYZ connection spaces.
+    input_matrix: Option<[[f64; 3]; 3]>,
+    b_curves: Vec<Curve>,
+    matrix: Option<([[f64; 3]; 3], [f64; 3])>,
+    m_curves: Vec<Curve>,
+    clut: Clut,
+    a_curves: Vec<Curve>,
+    /// Whether Lab is in the version 2 encoding of `lut16` tables.
+    is_legacy_lab: bool,
+}
+
+fn apply_curves(curves: &[Curve], values: &mut [f64]) {
+    for (value, curve) in values.iter_mut().zip(curves) {
+        *value = curve.apply(*value);
+    }
+}
+
+impl Lut {
+    fn parse(bytes: &[u8], offset: usize, is_xyz: bool) -> io::Result<Lut> {
+        let (inputs, outputs) = (
+            u8_at(bytes, offset + 8)? as usize,
+            u8_at(bytes, offset + 9)? as usize,
+        );
+        if (inputs != 3) || (outputs != 4) {
+            return Err(invalid_data(
+                "ICC profile does not convert three PCS channels to CMYK",
+            ));
+        }
+
+        let signature = field(bytes, offset, 4)?;
+        if signature == b"mBA " {
+            let part = |index: usize| -> io::Result<Option<usize>> {
+                let part_offset = u32_at(bytes, offset + 12 + (index * 4))? as usize;
+                Ok((part_offset != 0).then_some(offset + part_offset))
+            };
+            let b_curves = match part(0)? {
+                None => Vec::new(),
+                Some(curves) => parse_curves(bytes, curves, inputs)?,
+            };
+            let matrix = match part(1)? {
+                None => None,
+                Some(matrix) => {
+                    let mut values = [0f64; 12];
+                    for (index, value) in values.iter_mut().enumerate() {
+                        *value = fixed_at(bytes, matrix + (index * 4))?;
+                    }
+                    Some((
+                        [
+                            [values[0], values[1], values[2]],
+                            [values[3], values[4], values[5]],
+                            [values[6], values[7], values[8]],
+                        ],
+                        [values[9], values[10], values[11]],
+                    ))
+                }
+            };
+            let m_curves = match part(2)? {
+                None => Vec::new(),
+                Some(curves) => parse_curves(bytes, curves, inputs)?,
+            };
+            let clut = match part(3)? {
+                None => return Err(invalid_data("ICC profile has no color lookup table")),
+                Some(clut) => {
+                    let grid = (0..inputs)
+                        .map(|axis| u8_at(bytes, clut + axis).map(usize::from))
+                        .collect::<io::Result<Vec<usize>>>()?;
+                    let width = u8_at(bytes, clut + 16)? as usize;
+                    Clut::parse(bytes, clut + 20, grid, outputs, width.clamp(1, 2))?
+                }
+            };
+            let a_curves = match part(4)? {
+                None => Vec::new(),
+                Some(curves) => parse_curves(bytes, curves, outputs)?,
+            };
+
+            return Ok(Lut {
+                input_matrix: None,
+                b_curves,
+                matrix,
+                m_curves,
+                clut,
+                a_curves,
+                is_legacy_lab: false,
+            });
+        }
+
+        let (width, input_entries, output_entries, tables) = match signature {
+            b"mft1" => (1, 256, 256, offset + 48),
+            b"mft2" => (
+                2,
+                u16_at(bytes, offset + 48)? as usize,
+                u16_at(bytes, offset + 50)? as usize,
+                offset + 52,
+            ),
+            _ => return Err(invalid_data("Unknown ICC lookup table type")),
+        };
+        let grid = u8_at(bytes, offset + 10)? as usize;
+        let mut matrix = [[0f64; 3]; 3];
+        for (index, value) in matrix.iter_mut().flatten().enumerate() {
+            *value = fixed_at(bytes, offset + 12 + (index * 4))?;
+        }
+
+        let b_curves = parse_tables(bytes, tables, inputs, input_entries, width)?;
+        let clut_offset = tables + (inputs * input_entries * width);
+        let clut = Clut::parse(bytes, clut_offset, vec![grid; inputs], outputs, width)?;
+        let a_curves = parse_tables(
+            bytes,
+            clut_offset + (clut.values.len() * width),
+            outputs,
+            output_entries,
+            width,
+        )?;
+
+        Ok(Lut {
+            input_matrix: is_xyz.then_some(matrix),
+            b_curves,
+            matrix: None,
+            m_curves: Vec::new(),
+            clut,
+            a_curves,
+            is_legacy_lab: width == 2,
+        })
+    }
+
+    fn apply(&self, input: [f64; 3]) -> Vec<f64> {
+        let mut values = match &self.input_matrix {
+            None => input,
+            Some(matrix) => multiply(matrix, input),
+        };
+        apply_curves(&self.b_curves, &mut values);
+        if let Some((matrix, offset)) = &self.matrix {
+            let product = multiply(matrix, values);
+            values = std::array::from_fn(|axis| (product[axis] + offset[axis]).clamp(0f64, 1f64));
+        }
+        apply_curves(&self.m_curves, &mut values);
+        let mut output = self.clut.apply(&values);
+        apply_curves(&self.a_curves, &mut output);
+
+        output
+    }
+}
+
+/// An ICC output profile separating colors into CMYK.
+pub struct CmykProfile {
+    bytes: Vec<u8>,
+    is_xyz: bool,
+    lut: Lut,
+}
+
+impl CmykProfile {
+    /// Reads a CMYK output profile, separating colors with its perceptual
+    /// table, or failing that its colorimetric or saturation one.
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<CmykProfile> {
+        if field(&bytes, 16, 4)? != b"CMYK" {
+            return Err(invalid_data("ICC profile is not for a CMYK device"));
+        }
+        let is_xyz = match field(&bytes, 20, 4)? {
+            b"XYZ " => true,
+            b"Lab " => false,
+            _ => return Err(invalid_data("Unknown ICC connection space")),
+        };
+
+        let tag_count = u32_at(&bytes, 128)? as usize;
+        let tags = (0..tag_count)
+            .map(|index| {
+                let entry = 132 + (index * 12);
+                Ok((
+                    field(&bytes, entry, 4)?,
+                    u32_at(&bytes, entry + 4)? as usize,
+                ))
+            })
+            .collect::<io::Result<Vec<(&[u8], usize)>>>()?;
+        let offset = [b"B2A0", b"B2A1", b"B2A2"]
+            .iter()
+            .find_map(|signature| {
+                tags.iter()
+                    .find(|(tag, _)| tag == signature)
+                    .map(|(_, offset)| *offset)
+            })
+            .ok_or_else(|| invalid_data("ICC profile has no PCS to device table"))?;
+        let lut = Lut::parse(&bytes, offset, is_xyz)?;
+
+        Ok(CmykProfile { bytes, is_xyz, lut })
+    }
+
+    /// The raw profile, for embedding in images.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Ink of the sRGB `color`, each `0` for none to `255` for full.
+    pub fn separate(&self, color: Rgba<u8>) -> [u8; 4] {
+        let xyz = d50_xyz(color);
+        let input = if self.is_xyz {
+            xyz.map(|value| value * (32768f64 / 65535f64))
+        } else {
+            let [lightness, a, b] = d50_lab(xyz);
+            if self.lut.is_legacy_lab {
+                [
+                    (lightness / 100f64) * (65280f64 / 65535f64),
+                    ((a + 128f64) * 256f64) / 65535f64,
+                    ((b + 128f64) * 256f64) / 65535f64,
+                ]
+            } else {
+                [
+                    lightness / 100f64,
+                    (a + 128f64) / 255f64,
+                    (b + 128f64) / 255f64,
+                ]
+            }
+        };
+        let ink = self.lut.apply(input.map(|value| value.clamp(0f64, 1f64)));
+
+        std::array::from_fn(|channel| (ink[channel] * 255f64).round().clamp(0f64, 255f64) as u8)
+    }
+}
+
+/// Ink of the sRGB `color` by plain under color removal, for when there is
+/// no profile of the printing process: all the gray goes to black.
+pub fn separate_naive(color: Rgba<u8>) -> [u8; 4] {
+    let [red, green, blue, _] = color.0.map(|channel| (channel as f64) / 255f64);
+    let black = 1f64 - red.max(green).max(blue);
+    if black >= 1f64 {
+        return [0, 0, 0, 255];
+    }
+
+    let ink = |channel: f64| (((1f64 - channel - black) / (1f64 - black)) * 255f64).round() as u8;
+    [
+        ink(red),
+        ink(green),
+        ink(blue),
+        (black * 255f64).round() as u8,
+    ]
+}
+
+/// Writes `image` as an uncompressed CMYK TIFF at `dpi`, separated through
+/// `profile` and with it embedded, or by [`separate_naive`] without one.
+/// Transparent pixels are left as bare paper.
+pub fn write_cmyk_tiff<W: Write>(
+    image: &RgbaImage,
+    profile: Option<&CmykProfile>,
+    dpi: f64,
+    mut writer: W,
+) -> io::Result<()> {
+    let (width, height) = image.dimensions();
+    let icc = profile.map(CmykProfile::bytes).unwrap_or_default();
+    let entry_count = if icc.is_empty() { 14 } else { 15 };
+    let extra = 8 + 2 + (entry_count * 12) + 4;
+    let (bits, x_resolution, y_resolution, icc_offset) = (extra, extra + 8, extra + 16, extra + 24);
+    let pixel_offset = icc_offset + icc.len() + (icc.len() % 2);
+    let length = (width as u64) * (height as u64) * 4;
+    if (pixel_offset as u64) + length > (u32::MAX as u64) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "image is too large for a TIFF",
+        ));
+    }
+
+    let mut separations: HashMap<[u8; 4], [u8; 4]> = HashMap::new();
+    let mut pixels = Vec::with_capacity(length as usize);
+    for pixel in image.pixels() {
+        let ink = *separations.entry(pixel.0).or_insert_with(|| {
+            let [red, green, blue, alpha] = pixel.0;
+            let alpha = alpha as u32;
+            let blend =
+                |channel: u8| ((((channel as u32) * alpha) + (255 * (255 - alpha))) / 255) as u8;
+            let on_paper = Rgba([blend(red), blend(green), blend(blue), 255]);
+            match profile {
+                None => separate_naive(on_paper),
+                Some(profile) => profile.separate(on_paper),
+            }
+        });
+        pixels.extend(ink);
+    }
+
+    let resolution = (
+        (dpi * 100f64).round().clamp(1f64, u32::MAX as f64) as u32,
+        100u32,
+    );
+
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+    const RATIONAL: u16 = 5;
+    const UNDEFINED: u16 = 7;
+    let mut entries: Vec<(u16, u16, u32, u32)> = vec![
+        (256, LONG, 1, width),
+        (257, LONG, 1, height),
+        (258, SHORT, 4, bits as u32),
+        (259, SHORT, 1, 1),
+        // Separated, with the ink set below.
+        (262, SHORT, 1, 5),
+        (273, LONG, 1, pixel_offset as u32),
+        (277, SHORT, 1, 4),
+        (278, LONG, 1, height),
+        (279, LONG, 1, length as u32),
+        (282, RATIONAL, 1, x_resolution as u32),
+        (283, RATIONAL, 1, y_resolution as u32),
+        (284, SHORT, 1, 1),
+        // Inches.
+        (296, SHORT, 1, 2),
+        // CMYK.
+        (332, SHORT, 1, 1),
+    ];
+    if !icc.is_empty() {
+        entries.push((34675, UNDEFINED, icc.len() as u32, icc_offset as u32));
+    }
+
+    let mut header = Vec::with_capacity(pixel_offset);
+    header.extend(b"II*\0");
+    header.extend(8u32.to_le_bytes());
+    header.extend((entries.len() as u16).to_le_bytes());
+    for (tag, kind, count, value) in entries {
+        header.extend(tag.to_le_bytes());
+        header.extend(kind.to_le_bytes());
+        header.extend(count.to_le_bytes());
+        header.extend(value.to_le_bytes());
+    }
+    header.extend(0u32.to_le_bytes());
+    for _ in 0..4 {
+        header.extend(8u16.to_le_bytes());
+    }
+    for _ in 0..2 {
+        header.extend(resolution.0.to_le_bytes());
+        header.extend(resolution.1.to_le_bytes());
+    }
+    header.extend(icc);
+    header.resize(pixel_offset, 0);
+
+    writer.write_all(&header)?;
+    writer.write_all(&pixels)?;
+    writer.flush()
+}
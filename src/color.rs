@@ -0,0 +1,196 @@
+//! Color spaces for operations where how different colors look matters,
+//! such as averaging, clustering and matching colors.
+
+use image::Rgba;
+
+/// Space colors are converted to before they are averaged or compared.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// The stored sRGB channels, as `0` to `1`.
+    #[default]
+    Srgb,
+    /// sRGB with the gamma curve removed, so averages match mixing light.
+    Linear,
+    /// OKLab, where distances follow perceived color differences.
+    Oklab,
+    /// CIELAB under the D65 white point.
+    Cielab,
+}
+
+fn srgb_to_linear(channel: u8) -> f64 {
+    let channel = (channel as f64) / 255f64;
+    if channel <= 0.04045f64 {
+        channel / 12.92f64
+    } else {
+        ((channel + 0.055f64) / 1.055f64).powf(2.4f64)
+    }
+}
+
+fn linear_to_srgb(channel: f64) -> u8 {
+    let channel = channel.clamp(0f64, 1f64);
+    let encoded = if channel <= 0.0031308f64 {
+        channel * 12.92f64
+    } else {
+        (1.055f64 * channel.powf(1f64 / 2.4f64)) - 0.055f64
+    };
+
+    (encoded * 255f64).round().clamp(0f64, 255f64) as u8
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| (row[0] * vector[0]) + (row[1] * vector[1]) + (row[2] * vector[2]))
+}
+
+const LINEAR_TO_LMS: [[f64; 3]; 3] = [
+    [0.4122214708, 0.5363325363, 0.0514459929],
+    [0.2119034982, 0.6806995451, 0.1073969566],
+    [0.0883024619, 0.2817188376, 0.6299787005],
+];
+const LMS_TO_OKLAB: [[f64; 3]; 3] = [
+    [0.2104542553, 0.7936177850, -0.0040720468],
+    [1.9779984951, -2.4285922050, 0.4505937099],
+    [0.0259040371, 0.7827717662, -0.8086757660],
+];
+const OKLAB_TO_LMS: [[f64; 3]; 3] = [
+    [1f64, 0.3963377774, 0.2158037573],
+    [1f64, -0.1055613458, -0.0638541728],
+    [1f64, -0.0894841775, -1.2914855480],
+];
+const LMS_TO_LINEAR: [[f64; 3]; 3] = [
+    [4.0767416621, -3.3077115913, 0.2309699292],
+    [-1.2684380046, 2.6097574011, -0.3413193965],
+    [-0.0041960863, -0.7034186147, 1.7076147010],
+];
+
+const LINEAR_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+const XYZ_TO_LINEAR: [[f64; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+const D65_WHITE: [f64; 3] = [0.95047, 1f64, 1.08883];
+const LAB_EPSILON: f64 = 6f64 / 29f64;
+
+fn lab_compress(t: f64) -> f64 {
+    if t > LAB_EPSILON.powi(3) {
+        t.cbrt()
+    } else {
+        (t / (3f64 * LAB_EPSILON * LAB_EPSILON)) + (4f64 / 29f64)
+    }
+}
+
+fn lab_expand(t: f64) -> f64 {
+    if t > LAB_EPSILON {
+        t.powi(3)
+    } else {
+        3f64 * LAB_EPSILON * LAB_EPSILON * (t - (4f64 / 29f64))
+    }
+}
+
+impl ColorSpace {
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        match name {
+            "srgb" => Some(ColorSpace::Srgb),
+            "linear" => Some(ColorSpace::Linear),
+            "oklab" => Some(ColorSpace::Oklab),
+            "cielab" => Some(ColorSpace::Cielab),
+            _ => None,
+        }
+    }
+
+    /// Coordinates of the color channels of `color` in this space, ignoring
+    /// alpha.
+    pub fn encode(self, color: Rgba<u8>) -> [f64; 3] {
+        let [red, green, blue, _] = color.0;
+        if self == ColorSpace::Srgb {
+            return [red, green, blue].map(|channel| (channel as f64) / 255f64);
+        }
+
+        let linear = [red, green, blue].map(srgb_to_linear);
+        match self {
+            ColorSpace::Oklab => multiply(
+                &LMS_TO_OKLAB,
+                multiply(&LINEAR_TO_LMS, linear).map(f64::cbrt),
+            ),
+            ColorSpace::Cielab => {
+                let xyz = multiply(&LINEAR_TO_XYZ, linear);
+                let [x, y, z] =
+                    std::array::from_fn(|axis| lab_compress(xyz[axis] / D65_WHITE[axis]));
+                [(116f64 * y) - 16f64, 500f64 * (x - y), 200f64 * (y - z)]
+            }
+            _ => linear,
+        }
+    }
+
+    /// The sRGB channels of coordinates in this space, clamped to the gamut.
+    pub fn decode(self, coordinates: [f64; 3]) -> [u8; 3] {
+        let linear = match self {
+            ColorSpace::Srgb => {
+                return coordinates
+                    .map(|channel| (channel * 255f64).round().clamp(0f64, 255f64) as u8)
+            }
+            ColorSpace::Linear => coordinates,
+            ColorSpace::Oklab => multiply(
+                &LMS_TO_LINEAR,
+                multiply(&OKLAB_TO_LMS, coordinates).map(|channel| channel.powi(3)),
+            ),
+            ColorSpace::Cielab => {
+                let [lightness, a, b] = coordinates;
+                let y = (lightness + 16f64) / 116f64;
+                let compressed = [y + (a / 500f64), y, y - (b / 200f64)];
+                let xyz =
+                    std::array::from_fn(|axis| lab_expand(compressed[axis]) * D65_WHITE[axis]);
+                multiply(&XYZ_TO_LINEAR, xyz)
+            }
+        };
+
+        linear.map(linear_to_srgb)
+    }
+
+    /// Squared distance between two colors in this space, ignoring alpha.
+    pub fn squared_distance(self, from: Rgba<u8>, to: Rgba<u8>) -> f64 {
+        let (from, to) = (self.encode(from), self.encode(to));
+        from.iter()
+            .zip(&to)
+            .map(|(from, to)| (from - to) * (from - to))
+            .sum()
+    }
+
+    /// Size of the buckets colors are grouped into along every coordinate,
+    /// splitting the usual range of each into about 32 steps.
+    pub(crate) fn bucket_size(self) -> [f64; 3] {
+        match self {
+            ColorSpace::Srgb | ColorSpace::Linear => [1f64 / 32f64; 3],
+            ColorSpace::Oklab => [1f64 / 32f64, 0.025f64, 0.025f64],
+            ColorSpace::Cielab => [100f64 / 32f64, 8f64, 8f64],
+        }
+    }
+}
+
+/// Bradford adaptation of XYZ from the D65 white point to D50, the white of
+/// ICC profile connection spaces.
+const D65_TO_D50: [[f64; 3]; 3] = [
+    [1.0478112, 0.0228866, -0.0501270],
+    [0.0295424, 0.9904844, -0.0170491],
+    [-0.0092345, 0.0150436, 0.7521316],
+];
+const D50_WHITE: [f64; 3] = [0.9642, 1f64, 0.8249];
+
+/// CIE XYZ of the sRGB channels of `color` under the D50 white point.
+pub(crate) fn d50_xyz(color: Rgba<u8>) -> [f64; 3] {
+    let [red, green, blue, _] = color.0;
+    let linear = [red, green, blue].map(srgb_to_linear);
+
+    multiply(&D65_TO_D50, multiply(&LINEAR_TO_XYZ, linear))
+}
+
+/// CIELAB of D50 `xyz`.
+pub(crate) fn d50_lab(xyz: [f64; 3]) -> [f64; 3] {
+    let [x, y, z] = std::array::from_fn(|axis| lab_compress(xyz[axis] / D50_WHITE[axis]));
+
+    [(116f64 * y) - 16f64, 500f64 * (x - y), 200f64 * (y - z)]
+}
@@ -0,0 +1,392 @@
+use crate::anchors::Anchor;
+use crate::color::ColorSpace;
+use crate::palette::Palette;
+use image::{Rgba, RgbaImage};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// A single voronoi cell: its anchor and the pixels it owns.
+pub struct Cell<'a> {
+    pub index: usize,
+    pub anchor: &'a Anchor,
+    pub pixels: &'a [(u32, u32)],
+}
+
+/// Chooses the fill color of a cell from the pixels it covers in the source
+/// image.
+pub trait CellColorizer: Send + Sync {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8>;
+
+    /// The color space the pixels of a cell are averaged in, if their mean
+    /// is all this colorizer needs, so cells can be colored from
+    /// [`PixelSums`] without listing their pixels.
+    fn mean_space(&self) -> Option<ColorSpace> {
+        None
+    }
+
+    /// Chooses the fill color of a cell from the sums of its pixels in
+    /// [`CellColorizer::mean_space`].
+    fn colorize_sums(&self, anchor: &Anchor, sums: &PixelSums) -> Rgba<u8> {
+        sums.mean().unwrap_or(anchor.color)
+    }
+}
+
+/// Running sums of the pixels of a cell in a color space, added a few at a
+/// time.
+#[derive(Clone, Copy)]
+pub struct PixelSums {
+    color_space: ColorSpace,
+    count: u64,
+    /// The channels of the pixels, alpha last, summed as they are.
+    channels: [u64; 4],
+    /// The coordinates of the pixels in `color_space`, unless that is sRGB.
+    coordinates: [f64; 3],
+}
+
+impl PixelSums {
+    pub fn new(color_space: ColorSpace) -> PixelSums {
+        PixelSums {
+            color_space,
+            count: 0,
+            channels: [0; 4],
+            coordinates: [0f64; 3],
+        }
+    }
+
+    pub fn add(&mut self, pixel: Rgba<u8>) {
+        self.count += 1;
+        for (sum, channel) in self.channels.iter_mut().zip(pixel.0) {
+            *sum += channel as u64;
+        }
+        if self.color_space != ColorSpace::Srgb {
+            for (sum, coordinate) in self
+                .coordinates
+                .iter_mut()
+                .zip(self.color_space.encode(pixel))
+            {
+                *sum += coordinate;
+            }
+        }
+    }
+
+    /// The mean of the pixels added, taken per channel in the color space,
+    /// or `None` if there are none.
+    pub fn mean(&self) -> Option<Rgba<u8>> {
+        let count = self.count;
+        if count == 0 {
+            return None;
+        }
+        if self.color_space == ColorSpace::Srgb {
+            return Some(Rgba(
+                self.channels.map(|sum| ((sum + (count / 2)) / count) as u8),
+            ));
+        }
+
+        let count = count as f64;
+        let [red, green, blue] = self
+            .color_space
+            .decode(self.coordinates.map(|sum| sum / count));
+        Some(Rgba([
+            red,
+            green,
+            blue,
+            ((self.channels[3] as f64) / count).round() as u8,
+        ]))
+    }
+}
+
+/// Fills each cell with the color sampled at its anchor.
+pub struct AnchorColorizer;
+
+impl CellColorizer for AnchorColorizer {
+    fn colorize(&self, cell: &Cell, _source_image: &RgbaImage) -> Rgba<u8> {
+        cell.anchor.color
+    }
+
+    fn mean_space(&self) -> Option<ColorSpace> {
+        Some(ColorSpace::Srgb)
+    }
+
+    fn colorize_sums(&self, anchor: &Anchor, _sums: &PixelSums) -> Rgba<u8> {
+        anchor.color
+    }
+}
+
+/// Averages `pixels` of `source_image` in `color_space`, and their alpha.
+fn mean_in_space(
+    pixels: &[(u32, u32)],
+    source_image: &RgbaImage,
+    color_space: ColorSpace,
+) -> Option<Rgba<u8>> {
+    let mut sums = PixelSums::new(color_space);
+    for &(x, y) in pixels {
+        sums.add(*source_image.get_pixel(x, y));
+    }
+
+    sums.mean()
+}
+
+/// Fills each cell with the mean of the pixels it covers, taken per channel
+/// in `color_space`.
+#[derive(Default)]
+pub struct MeanColorizer {
+    pub color_space: ColorSpace,
+}
+
+impl CellColorizer for MeanColorizer {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        mean_in_space(cell.pixels, source_image, self.color_space).unwrap_or(cell.anchor.color)
+    }
+
+    fn mean_space(&self) -> Option<ColorSpace> {
+        Some(self.color_space)
+    }
+}
+
+/// Fills each cell with the per-channel median of the pixels it covers.
+pub struct MedianColorizer;
+
+impl CellColorizer for MedianColorizer {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        if cell.pixels.is_empty() {
+            return cell.anchor.color;
+        }
+
+        let mut histograms = [[0u32; 256]; 4];
+        for &(x, y) in cell.pixels {
+            let pixel = source_image.get_pixel(x, y);
+            for (histogram, channel) in histograms.iter_mut().zip(pixel.0) {
+                histogram[channel as usize] += 1;
+            }
+        }
+
+        let middle = (cell.pixels.len() as u32).div_ceil(2);
+        Rgba(histograms.map(|histogram| {
+            let mut seen = 0;
+            for (value, count) in histogram.iter().enumerate() {
+                seen += count;
+                if seen >= middle {
+                    return value as u8;
+                }
+            }
+            u8::MAX
+        }))
+    }
+}
+
+/// Fills each cell with its most frequent color after quantizing every channel
+/// to `32` levels, averaged over the pixels that fall into that bucket. Colors
+/// are bucketed and averaged in `color_space`.
+#[derive(Default)]
+pub struct DominantColorizer {
+    pub color_space: ColorSpace,
+}
+
+impl DominantColorizer {
+    fn colorize_in_space(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        let bucket_size = self.color_space.bucket_size();
+        let mut buckets: HashMap<[i64; 4], Vec<(u32, u32)>> = HashMap::new();
+        for &(x, y) in cell.pixels {
+            let pixel = *source_image.get_pixel(x, y);
+            let coordinates = self.color_space.encode(pixel);
+            let bucket = [
+                (coordinates[0] / bucket_size[0]).floor() as i64,
+                (coordinates[1] / bucket_size[1]).floor() as i64,
+                (coordinates[2] / bucket_size[2]).floor() as i64,
+                (pixel.0[3] >> 3) as i64,
+            ];
+            buckets.entry(bucket).or_default().push((x, y));
+        }
+
+        let pixels = buckets
+            .iter()
+            .max_by_key(|(bucket, pixels)| (pixels.len(), Reverse(**bucket)))
+            .map(|(_, pixels)| pixels.as_slice())
+            .unwrap_or(cell.pixels);
+        mean_in_space(pixels, source_image, self.color_space).unwrap_or(cell.anchor.color)
+    }
+}
+
+impl CellColorizer for DominantColorizer {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        if cell.pixels.is_empty() {
+            return cell.anchor.color;
+        }
+        if self.color_space != ColorSpace::Srgb {
+            return self.colorize_in_space(cell, source_image);
+        }
+
+        let bucket_of = |pixel: &Rgba<u8>| -> u32 {
+            pixel.0.iter().fold(0u32, |bucket, channel| {
+                (bucket << 5) | ((*channel as u32) >> 3)
+            })
+        };
+
+        let mut counts: HashMap<u32, (u32, [u64; 4])> = HashMap::new();
+        for &(x, y) in cell.pixels {
+            let pixel = source_image.get_pixel(x, y);
+            let (count, sums) = counts.entry(bucket_of(pixel)).or_insert((0, [0u64; 4]));
+            *count += 1;
+            for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                *sum += channel as u64;
+            }
+        }
+
+        let (count, sums) = counts
+            .iter()
+            .max_by_key(|(bucket, (count, _))| (*count, Reverse(**bucket)))
+            .map(|(_, entry)| *entry)
+            .unwrap_or((1, [0u64; 4]));
+        let count = count as u64;
+        Rgba(sums.map(|sum| ((sum + (count / 2)) / count) as u8))
+    }
+}
+
+/// Snaps the colors another colorizer chooses to the nearest entry of a
+/// palette, compared in `color_space`.
+pub struct PaletteColorizer<'a> {
+    pub colorizer: &'a dyn CellColorizer,
+    pub palette: &'a Palette,
+    pub color_space: ColorSpace,
+}
+
+impl CellColorizer for PaletteColorizer<'_> {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        self.palette.nearest(
+            self.colorizer.colorize(cell, source_image),
+            self.color_space,
+        )
+    }
+
+    fn mean_space(&self) -> Option<ColorSpace> {
+        self.colorizer.mean_space()
+    }
+
+    fn colorize_sums(&self, anchor: &Anchor, sums: &PixelSums) -> Rgba<u8> {
+        self.palette
+            .nearest(self.colorizer.colorize_sums(anchor, sums), self.color_space)
+    }
+}
+
+/// Replaces the colors another colorizer chooses by the point of a ramp of
+/// tones, such as a dark and a light one, at their OKLab lightness.
+pub struct ToneColorizer<'a> {
+    pub colorizer: &'a dyn CellColorizer,
+    /// The tones from the darkest to the lightest, blended as a gradient.
+    pub tones: &'a Palette,
+}
+
+impl ToneColorizer<'_> {
+    fn tone(&self, color: Rgba<u8>) -> Rgba<u8> {
+        let [lightness, _, _] = ColorSpace::Oklab.encode(color);
+        let mut tone = self.tones.gradient(lightness);
+        tone.0[3] = color.0[3];
+
+        tone
+    }
+}
+
+impl CellColorizer for ToneColorizer<'_> {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        self.tone(self.colorizer.colorize(cell, source_image))
+    }
+
+    fn mean_space(&self) -> Option<ColorSpace> {
+        self.colorizer.mean_space()
+    }
+
+    fn colorize_sums(&self, anchor: &Anchor, sums: &PixelSums) -> Rgba<u8> {
+        self.tone(self.colorizer.colorize_sums(anchor, sums))
+    }
+}
+
+/// Rounds every channel of the colors another colorizer chooses to one of
+/// `levels` evenly spaced values, so close colors fall into bold bands.
+pub struct PosterizeColorizer<'a> {
+    pub colorizer: &'a dyn CellColorizer,
+    pub levels: u32,
+}
+
+impl PosterizeColorizer<'_> {
+    fn posterize(&self, color: Rgba<u8>) -> Rgba<u8> {
+        let steps = (self.levels - 1) as f64;
+        let mut posterized = color;
+        for channel in &mut posterized.0[..3] {
+            let level = ((*channel as f64) * steps / 255f64).round();
+            *channel = (level * 255f64 / steps).round() as u8;
+        }
+
+        posterized
+    }
+}
+
+impl CellColorizer for PosterizeColorizer<'_> {
+    fn colorize(&self, cell: &Cell, source_image: &RgbaImage) -> Rgba<u8> {
+        self.posterize(self.colorizer.colorize(cell, source_image))
+    }
+
+    fn mean_space(&self) -> Option<ColorSpace> {
+        self.colorizer.mean_space()
+    }
+
+    fn colorize_sums(&self, anchor: &Anchor, sums: &PixelSums) -> Rgba<u8> {
+        self.posterize(self.colorizer.colorize_sums(anchor, sums))
+    }
+}
+
+/// Colorizers addressable by name, e.g. from the `--color-mode` CLI flag.
+pub struct ColorizerRegistry {
+    colorizers: Vec<(String, Box<dyn CellColorizer>)>,
+}
+
+impl ColorizerRegistry {
+    pub fn new() -> ColorizerRegistry {
+        ColorizerRegistry {
+            colorizers: Vec::new(),
+        }
+    }
+
+    /// A registry holding the colorizers shipped with the crate.
+    pub fn with_builtins() -> ColorizerRegistry {
+        ColorizerRegistry::with_color_space(ColorSpace::Srgb)
+    }
+
+    /// The builtin colorizers, averaging and clustering colors in
+    /// `color_space`.
+    pub fn with_color_space(color_space: ColorSpace) -> ColorizerRegistry {
+        let mut registry = ColorizerRegistry::new();
+        registry.register("anchor", Box::new(AnchorColorizer));
+        registry.register("mean", Box::new(MeanColorizer { color_space }));
+        registry.register("median", Box::new(MedianColorizer));
+        registry.register("dominant", Box::new(DominantColorizer { color_space }));
+
+        registry
+    }
+
+    /// Adds a colorizer, replacing any previously registered under `name`.
+    pub fn register(&mut self, name: &str, colorizer: Box<dyn CellColorizer>) {
+        self.colorizers
+            .retain(|(registered_name, _)| registered_name != name);
+        self.colorizers.push((name.to_string(), colorizer));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn CellColorizer> {
+        self.colorizers
+            .iter()
+            .find(|(registered_name, _)| registered_name == name)
+            .map(|(_, colorizer)| colorizer.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.colorizers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+impl Default for ColorizerRegistry {
+    fn default() -> Self {
+        ColorizerRegistry::with_builtins()
+    }
+}
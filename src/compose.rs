@@ -0,0 +1,200 @@
+//! Combining a painting with the image it was painted from.
+
+use image::{imageops, Rgba, RgbaImage};
+
+/// How the original and the painting share a comparison image.
+#[derive(Clone, Copy)]
+pub enum CompareLayout {
+    /// The original on the left and the painting on the right.
+    SideBySide,
+    /// The original left of a vertical divider at this fraction of the
+    /// width, and the painting right of it.
+    Split(f64),
+    /// The original above the diagonal from the bottom left to the top right
+    /// corner, and the painting below it.
+    Diagonal,
+}
+
+const DIVIDER_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+fn resize_to(image: &RgbaImage, (width, height): (u32, u32)) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        image.clone()
+    } else {
+        imageops::resize(image, width, height, imageops::FilterType::Triangle)
+    }
+}
+
+/// How the colors of the painting combine with the original below it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The painting covers the original.
+    Normal,
+    /// Darkens: the channels are multiplied.
+    Multiply,
+    /// Lightens: the inverted channels are multiplied.
+    Screen,
+    /// Multiplies dark and screens light parts of the original, raising
+    /// contrast.
+    Overlay,
+    /// A gentler overlay that never clips to black or white.
+    SoftLight,
+}
+
+impl BlendMode {
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        match name {
+            "normal" => Some(BlendMode::Normal),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "overlay" => Some(BlendMode::Overlay),
+            "soft-light" => Some(BlendMode::SoftLight),
+            _ => None,
+        }
+    }
+
+    /// Blends channel `above` of the painting onto `below` of the original,
+    /// both from `0` to `1`.
+    fn blend(self, below: f64, above: f64) -> f64 {
+        match self {
+            BlendMode::Normal => above,
+            BlendMode::Multiply => below * above,
+            BlendMode::Screen => 1f64 - ((1f64 - below) * (1f64 - above)),
+            BlendMode::Overlay => {
+                if below < 0.5f64 {
+                    2f64 * below * above
+                } else {
+                    1f64 - (2f64 * (1f64 - below) * (1f64 - above))
+                }
+            }
+            BlendMode::SoftLight => {
+                if above <= 0.5f64 {
+                    below - ((1f64 - (2f64 * above)) * below * (1f64 - below))
+                } else {
+                    let lifted = if below <= 0.25f64 {
+                        ((((16f64 * below) - 12f64) * below) + 4f64) * below
+                    } else {
+                        below.sqrt()
+                    };
+                    below + (((2f64 * above) - 1f64) * (lifted - below))
+                }
+            }
+        }
+    }
+}
+
+/// Lays `painting` over `original` with `mode` at `opacity`, from `0` (only
+/// the original) to `1` (fully blended), scaling the original to the size of
+/// the painting. Transparent parts of the painting show the original.
+pub fn overlay(
+    original: &RgbaImage,
+    painting: &RgbaImage,
+    opacity: f64,
+    mode: BlendMode,
+) -> RgbaImage {
+    let original = resize_to(original, painting.dimensions());
+    let opacity = opacity.clamp(0f64, 1f64);
+
+    RgbaImage::from_fn(painting.width(), painting.height(), |x, y| {
+        let (below, above) = (original.get_pixel(x, y).0, painting.get_pixel(x, y).0);
+        let coverage = opacity * ((above[3] as f64) / 255f64);
+        Rgba(std::array::from_fn(|channel| {
+            let below = (below[channel] as f64) / 255f64;
+            let blended = if channel == 3 {
+                1f64
+            } else {
+                mode.blend(below, (above[channel] as f64) / 255f64)
+            };
+            ((below + ((blended - below) * coverage)) * 255f64).round() as u8
+        }))
+    })
+}
+
+/// Keeps `original` untouched wherever `coverage` is `1`, fading into
+/// `painting` where it falls to `0`, scaling the original to the size of the
+/// painting. `coverage` runs row by row over the painting.
+pub fn preserve(original: &RgbaImage, painting: &RgbaImage, coverage: &[f64]) -> RgbaImage {
+    let original = resize_to(original, painting.dimensions());
+    let width = painting.width() as usize;
+
+    RgbaImage::from_fn(painting.width(), painting.height(), |x, y| {
+        let (kept, painted) = (original.get_pixel(x, y).0, painting.get_pixel(x, y).0);
+        let coverage = coverage[((y as usize) * width) + (x as usize)].clamp(0f64, 1f64);
+        Rgba(std::array::from_fn(|channel| {
+            let (kept, painted) = (kept[channel] as f64, painted[channel] as f64);
+            (painted + ((kept - painted) * coverage)).round() as u8
+        }))
+    })
+}
+
+/// What fills the parts of the canvas no cell covers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    /// Left transparent.
+    Transparent,
+    /// A solid color.
+    Color(Rgba<u8>),
+    /// The pixels of the original.
+    Original,
+}
+
+/// Lays `painting` over `background`, scaling the original to the size of
+/// the painting when it shows through.
+pub fn fill_background(
+    original: &RgbaImage,
+    painting: RgbaImage,
+    background: Background,
+) -> RgbaImage {
+    match background {
+        Background::Transparent => painting,
+        Background::Original => overlay(original, &painting, 1f64, BlendMode::Normal),
+        Background::Color(color) => {
+            let backdrop = RgbaImage::from_pixel(painting.width(), painting.height(), color);
+            overlay(&backdrop, &painting, 1f64, BlendMode::Normal)
+        }
+    }
+}
+
+/// Shows `original` and `painting` together for a before and after view,
+/// scaling the original to the size of the painting.
+pub fn compare(original: &RgbaImage, painting: &RgbaImage, layout: CompareLayout) -> RgbaImage {
+    let (width, height) = painting.dimensions();
+    let original = resize_to(original, (width, height));
+
+    match layout {
+        CompareLayout::SideBySide => {
+            let mut comparison = RgbaImage::new(width * 2, height);
+            imageops::replace(&mut comparison, &original, 0, 0);
+            imageops::replace(&mut comparison, painting, width as i64, 0);
+            comparison
+        }
+        CompareLayout::Split(divider) => {
+            let divider = ((width as f64) * divider.clamp(0f64, 1f64)).round() as i64;
+            RgbaImage::from_fn(width, height, |x, y| {
+                let offset = (x as i64) - divider;
+                if (-1..=0).contains(&offset) {
+                    DIVIDER_COLOR
+                } else if offset < 0 {
+                    *original.get_pixel(x, y)
+                } else {
+                    *painting.get_pixel(x, y)
+                }
+            })
+        }
+        CompareLayout::Diagonal => {
+            let (w, h) = (width as f64, height as f64);
+            // Distance in pixels from the diagonal, negative above it.
+            let length = ((w * w) + (h * h)).sqrt().max(1f64);
+            RgbaImage::from_fn(width, height, |x, y| {
+                let offset = (((x as f64) * h) + ((y as f64) * w) - (w * h)) / length;
+                if offset.abs() <= 1f64 {
+                    DIVIDER_COLOR
+                } else if offset < 0f64 {
+                    *original.get_pixel(x, y)
+                } else {
+                    *painting.get_pixel(x, y)
+                }
+            })
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! A long running painter listening on a Unix domain socket, so programs
+//! calling it over and over skip starting a process and decoding the same
+//! anchor caches every time.
+//!
+//! Every line a client writes is a JSON job such as
+//! `{"args": ["painting", "-i", "in.png", "-o", "out.png"]}`, the arguments
+//! of one command line without the program name, and is answered with one
+//! line: `{"ok": true}` or `{"ok": false, "error": "..."}`. Jobs run one at a
+//! time, in the order they arrive.
+
+use crate::render::RenderFailure;
+use serde::Deserialize;
+use serde_json::json;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct Job {
+    args: Vec<String>,
+}
+
+/// Listens on `socket_path` and runs every job received with `run`, until
+/// the process is stopped. A socket file left behind by a daemon that is no
+/// longer running is replaced.
+pub fn serve_jobs<F>(socket_path: &Path, mut run: F) -> io::Result<()>
+where
+    F: FnMut(Vec<String>) -> Result<(), String>,
+{
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another daemon is listening there",
+            ));
+        }
+        std::fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+    println!("Listening on {}", socket_path.display());
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(error) = handle_connection(&stream, &mut run) {
+                    eprintln!("Failed to answer job: {}", error);
+                }
+            }
+            Err(error) => {
+                eprintln!("Failed to accept connection: {}", error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection<F>(stream: &UnixStream, run: &mut F) -> io::Result<()>
+where
+    F: FnMut(Vec<String>) -> Result<(), String>,
+{
+    let mut writer = stream;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result =
+            match serde_json::from_str::<Job>(&line) {
+                Err(error) => Err(format!("Invalid job: {}", error)),
+                // A job that panics fails alone instead of stopping the daemon.
+                Ok(job) => panic::catch_unwind(AssertUnwindSafe(|| run(job.args))).unwrap_or_else(
+                    |payload| match payload.downcast_ref::<RenderFailure>() {
+                        Some(failure) => Err(failure.to_string()),
+                        None => Err(String::from("The job panicked")),
+                    },
+                ),
+            };
+        let reply = match result {
+            Ok(()) => json!({ "ok": true }),
+            Err(message) => json!({ "ok": false, "error": message }),
+        };
+        writeln!(writer, "{}", reply)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Comparing two renders of the same image, to check that a refactor or a
+//! new code path paints what the old one did: how many pixels differ, and,
+//! given the label maps of both, how many cells changed shape or color.
+
+use crate::render::{CellMap, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+use std::collections::HashSet;
+
+/// What differs between two renders.
+pub struct RenderDiff {
+    /// Pixels with a channel further apart than the tolerance.
+    pub differing_pixels: u64,
+    /// The largest difference of any channel of any pixel.
+    pub largest_difference: u8,
+    /// How the cells changed, when both label maps were given.
+    pub cells: Option<CellChanges>,
+}
+
+/// The cells of two label maps, matched by their index.
+pub struct CellChanges {
+    pub first_count: usize,
+    pub second_count: usize,
+    /// Cells not owning the same pixels in both.
+    pub reshaped: usize,
+    /// Cells in both whose mean color differs by more than the tolerance.
+    pub recolored: usize,
+    /// Cells that changed shape, color or both.
+    pub changed: usize,
+}
+
+/// The largest difference of the channels of two pixels.
+fn pixel_difference(first: &Rgba<u8>, second: &Rgba<u8>) -> u8 {
+    first
+        .0
+        .iter()
+        .zip(second.0)
+        .map(|(first, second)| first.abs_diff(second))
+        .max()
+        .unwrap_or_default()
+}
+
+/// The mean color of every cell of `cell_map` in `image`, `None` for cells
+/// owning no pixel.
+fn mean_cell_colors(image: &RgbaImage, cell_map: &CellMap) -> Vec<Option<[f64; 4]>> {
+    let count = cell_map
+        .labels
+        .iter()
+        .filter(|label| **label != UNASSIGNED)
+        .map(|label| (*label as usize) + 1)
+        .max()
+        .unwrap_or_default();
+    let mut sums = vec![([0f64; 4], 0u64); count];
+    for (label, pixel) in cell_map.labels.iter().zip(image.pixels()) {
+        if *label == UNASSIGNED {
+            continue;
+        }
+        let (sum, pixels) = &mut sums[*label as usize];
+        for (channel, value) in sum.iter_mut().zip(pixel.0) {
+            *channel += value as f64;
+        }
+        *pixels += 1;
+    }
+
+    sums.into_iter()
+        .map(|(sum, pixels)| (pixels > 0).then(|| sum.map(|channel| channel / (pixels as f64))))
+        .collect()
+}
+
+/// Compares `first` and `second`, two images of the same size, and with
+/// their label maps `labels` the cells they were painted with. Channels at
+/// most `tolerance` apart count as the same.
+pub fn compare_renders(
+    first: &RgbaImage,
+    second: &RgbaImage,
+    labels: Option<(&CellMap, &CellMap)>,
+    tolerance: u8,
+) -> RenderDiff {
+    let mut differing_pixels = 0u64;
+    let mut largest_difference = 0u8;
+    for (first, second) in first.pixels().zip(second.pixels()) {
+        let difference = pixel_difference(first, second);
+        largest_difference = largest_difference.max(difference);
+        if difference > tolerance {
+            differing_pixels += 1;
+        }
+    }
+
+    let cells = labels.map(|(first_labels, second_labels)| {
+        let mut reshaped: HashSet<u32> = HashSet::new();
+        for (first_label, second_label) in first_labels.labels.iter().zip(&second_labels.labels) {
+            if first_label != second_label {
+                reshaped.extend([*first_label, *second_label]);
+            }
+        }
+        reshaped.remove(&UNASSIGNED);
+
+        let first_colors = mean_cell_colors(first, first_labels);
+        let second_colors = mean_cell_colors(second, second_labels);
+        let recolored: HashSet<u32> = first_colors
+            .iter()
+            .zip(&second_colors)
+            .enumerate()
+            .filter(|(_, (first, second))| match (first, second) {
+                (Some(first), Some(second)) => first
+                    .iter()
+                    .zip(second)
+                    .any(|(first, second)| (first - second).abs() > (tolerance as f64)),
+                _ => false,
+            })
+            .map(|(index, _)| index as u32)
+            .collect();
+
+        CellChanges {
+            first_count: first_colors.iter().flatten().count(),
+            second_count: second_colors.iter().flatten().count(),
+            reshaped: reshaped.len(),
+            recolored: recolored.len(),
+            changed: reshaped.union(&recolored).count(),
+        }
+    });
+
+    RenderDiff {
+        differing_pixels,
+        largest_difference,
+        cells,
+    }
+}
+
+/// A heatmap of where `first` and `second` differ: the pixels that are the
+/// same dimmed to gray, those that differ from dark red to yellow as their
+/// channels grow apart, and with `labels` the pixels owned by another cell
+/// in cyan.
+pub fn difference_heatmap(
+    first: &RgbaImage,
+    second: &RgbaImage,
+    labels: Option<(&CellMap, &CellMap)>,
+    tolerance: u8,
+) -> RgbaImage {
+    RgbaImage::from_fn(first.width(), first.height(), |x, y| {
+        let (first_pixel, second_pixel) = (first.get_pixel(x, y), second.get_pixel(x, y));
+        let is_reassigned = labels.is_some_and(|(first_labels, second_labels)| {
+            first_labels.label(x, y) != second_labels.label(x, y)
+        });
+        let difference = pixel_difference(first_pixel, second_pixel);
+
+        if is_reassigned {
+            Rgba([0, 220, 255, 255])
+        } else if difference > tolerance {
+            let heat = (difference as f64) / 255f64;
+            Rgba([
+                (128f64 + (127f64 * heat.min(0.5f64) * 2f64)).round() as u8,
+                (255f64 * ((heat - 0.5f64).max(0f64) * 2f64)).round() as u8,
+                0,
+                255,
+            ])
+        } else {
+            let [red, green, blue, _] = first_pixel.0;
+            let gray = (((red as u32) + (green as u32) + (blue as u32)) / 3) as u8;
+            let dimmed = gray / 4;
+            Rgba([dimmed, dimmed, dimmed, 255])
+        }
+    })
+}
@@ -0,0 +1,83 @@
+//! DXF drawings of cell boundaries for laser cutters and CNC routers.
+
+use crate::geometry::{Bounds, Point};
+use crate::voronoi::inset_ring;
+use std::fmt::Write;
+
+/// Writes one DXF group: its code and value on separate lines.
+fn group(drawing: &mut String, code: u32, value: &str) {
+    let _ = write!(drawing, "{}\n{}\n", code, value);
+}
+
+/// Builds an ASCII DXF (R12) drawing with one closed polyline per ring of
+/// every cell, in millimetres with `y` pointing up.
+///
+/// Cells are given in pixel coordinates of a `bounds` sized image, as for
+/// [`crate::export::cells_to_geojson`], and scaled by `millimetres_per_pixel`.
+/// Every ring is moved `kerf` millimetres into its cell, leaving that much
+/// material between neighbouring pieces; rings too small for it are dropped.
+pub fn cells_to_dxf(
+    cells: &[Vec<Vec<Point>>],
+    bounds: &Bounds,
+    millimetres_per_pixel: f64,
+    kerf: f64,
+) -> String {
+    let height = (bounds.height as f64) * millimetres_per_pixel;
+    let mut drawing = String::new();
+
+    group(&mut drawing, 0, "SECTION");
+    group(&mut drawing, 2, "HEADER");
+    group(&mut drawing, 9, "$INSUNITS");
+    group(&mut drawing, 70, "4");
+    group(&mut drawing, 9, "$EXTMIN");
+    group(&mut drawing, 10, "0");
+    group(&mut drawing, 20, "0");
+    group(&mut drawing, 9, "$EXTMAX");
+    group(
+        &mut drawing,
+        10,
+        &format!("{:.4}", (bounds.width as f64) * millimetres_per_pixel),
+    );
+    group(&mut drawing, 20, &format!("{:.4}", height));
+    group(&mut drawing, 0, "ENDSEC");
+
+    group(&mut drawing, 0, "SECTION");
+    group(&mut drawing, 2, "ENTITIES");
+    for rings in cells {
+        for ring in rings.iter().filter(|ring| ring.len() >= 3) {
+            let ring = match kerf > 0f64 {
+                false => Some(ring.clone()),
+                true => inset_ring(ring, kerf / millimetres_per_pixel),
+            };
+            let ring = match ring {
+                None => continue,
+                Some(ring) => ring,
+            };
+
+            group(&mut drawing, 0, "POLYLINE");
+            group(&mut drawing, 8, "CELLS");
+            group(&mut drawing, 66, "1");
+            group(&mut drawing, 70, "1");
+            for point in &ring {
+                group(&mut drawing, 0, "VERTEX");
+                group(&mut drawing, 8, "CELLS");
+                group(
+                    &mut drawing,
+                    10,
+                    &format!("{:.4}", point.x * millimetres_per_pixel),
+                );
+                group(
+                    &mut drawing,
+                    20,
+                    &format!("{:.4}", height - (point.y * millimetres_per_pixel)),
+                );
+            }
+            group(&mut drawing, 0, "SEQEND");
+            group(&mut drawing, 8, "CELLS");
+        }
+    }
+    group(&mut drawing, 0, "ENDSEC");
+    group(&mut drawing, 0, "EOF");
+
+    drawing
+}
@@ -0,0 +1,150 @@
+//! Vector exports of cell polygons.
+
+use crate::anchors::Anchor;
+use crate::geometry::{Bounds, Point};
+use crate::palette::format_hex_color;
+use crate::pattern::{ink_coverage, FillPattern};
+use crate::voronoi::group_rings;
+use image::Rgba;
+use serde_json::{json, Value};
+
+fn ring_coordinates(polygon: &[Point]) -> Vec<[f64; 2]> {
+    let mut ring: Vec<[f64; 2]> = polygon.iter().map(|point| [point.x, point.y]).collect();
+    if let Some(first) = ring.first().copied() {
+        ring.push(first);
+    }
+
+    ring
+}
+
+fn geometry(rings: &[Vec<Point>]) -> Value {
+    let polygons: Vec<Vec<Vec<[f64; 2]>>> = group_rings(rings)
+        .iter()
+        .map(|polygon| polygon.iter().map(|ring| ring_coordinates(ring)).collect())
+        .collect();
+
+    match &polygons[..] {
+        [polygon] => json!({ "type": "Polygon", "coordinates": polygon }),
+        polygons => json!({ "type": "MultiPolygon", "coordinates": polygons }),
+    }
+}
+
+/// Builds a GeoJSON `FeatureCollection` with one feature per non-empty cell,
+/// in pixel coordinates with `y` pointing down.
+///
+/// Each cell is given as its rings: usually just the polygon from
+/// [`crate::voronoi::cell_polygons`], or several pieces and holes once it has
+/// been clipped to a mask. Every feature carries the cell `index`, its fill
+/// `color` as hex and the `anchor` position as properties.
+pub fn cells_to_geojson(
+    anchors: &[Anchor],
+    cells: &[Vec<Vec<Point>>],
+    colors: &[Rgba<u8>],
+    bounds: &Bounds,
+) -> Value {
+    let features: Vec<Value> = anchors
+        .iter()
+        .zip(cells)
+        .zip(colors)
+        .enumerate()
+        .filter(|(_, ((_, rings), _))| rings.iter().any(|ring| ring.len() >= 3))
+        .map(|(index, ((anchor, rings), color))| {
+            json!({
+                "type": "Feature",
+                "geometry": geometry(rings),
+                "properties": {
+                    "index": index,
+                    "color": format_hex_color(*color),
+                    "anchor": [anchor.point.x, anchor.point.y],
+                },
+            })
+        })
+        .collect();
+
+    json!({
+        "type": "FeatureCollection",
+        "bbox": [0, 0, bounds.width, bounds.height],
+        "features": features,
+    })
+}
+
+/// Adds the `pattern` every feature of a [`cells_to_geojson`] collection is
+/// shaded with as a `fill` property: the pattern name, its `spacing`, the
+/// share of each tile that is inked and the `size` of the lines or dots that
+/// ink it.
+pub fn describe_fill_patterns(
+    geojson: &mut Value,
+    colors: &[Rgba<u8>],
+    pattern: FillPattern,
+    spacing: f64,
+) {
+    let features = match geojson["features"].as_array_mut() {
+        None => return,
+        Some(features) => features,
+    };
+
+    for feature in features {
+        let color = match feature["properties"]["index"].as_u64() {
+            None => continue,
+            Some(index) => colors[index as usize],
+        };
+        let coverage = ink_coverage(color);
+        feature["properties"]["fill"] = json!({
+            "pattern": pattern.name(),
+            "spacing": spacing,
+            "coverage": coverage,
+            "size": pattern.stroke_size(coverage, spacing),
+        });
+    }
+}
+
+/// Builds an HTML snippet of the painting as one absolutely placed `<div>`
+/// per cell, cut to shape with a CSS `clip-path: polygon(...)` in
+/// percentages so it scales with its container, and filled with the cell
+/// color. Every cell carries the `voronoi-cell` class and its `data-index`
+/// for styling and scripting; the snippet brightens cells on hover.
+///
+/// CSS polygons cannot have holes, so cells clipped to a mask are drawn as
+/// their outer pieces.
+pub fn cells_to_html(cells: &[Vec<Vec<Point>>], colors: &[Rgba<u8>], bounds: &Bounds) -> String {
+    let (width, height) = (bounds.width as f64, bounds.height as f64);
+    let mut html = format!(
+        concat!(
+            "<style>\n",
+            ".voronoi-painting {{ position: relative; width: 100%; aspect-ratio: {} / {}; }}\n",
+            ".voronoi-cell {{ position: absolute; inset: 0; transition: filter 0.2s; }}\n",
+            ".voronoi-cell:hover {{ filter: brightness(1.25); }}\n",
+            "</style>\n",
+            "<div class=\"voronoi-painting\">\n",
+        ),
+        bounds.width, bounds.height
+    );
+
+    for (index, (rings, color)) in cells.iter().zip(colors).enumerate() {
+        for polygon in group_rings(rings) {
+            let outer = match polygon.first() {
+                Some(outer) if outer.len() >= 3 => outer,
+                _ => continue,
+            };
+            let points: Vec<String> = outer
+                .iter()
+                .map(|point| {
+                    format!(
+                        "{:.3}% {:.3}%",
+                        (point.x / width) * 100f64,
+                        (point.y / height) * 100f64
+                    )
+                })
+                .collect();
+            html.push_str(&format!(
+                "  <div class=\"voronoi-cell\" data-index=\"{}\" style=\"background: {}; clip-path: polygon({});\"></div>\n",
+                index,
+                format_hex_color(*color),
+                points.join(", ")
+            ));
+        }
+    }
+
+    html.push_str("</div>\n");
+    html
+}
@@ -0,0 +1,182 @@
+//! Sparse optical flow: how far points of one frame moved in the next, by
+//! pyramidal Lucas-Kanade tracking on the luminance.
+
+use crate::analysis::luminance;
+use crate::geometry::Point;
+use image::RgbaImage;
+
+pub struct FlowOptions {
+    /// Half the side of the square window matched around every point.
+    pub window_radius: u32,
+    /// Number of times the frames are halved, to follow motion larger than
+    /// the window.
+    pub levels: u32,
+    pub iterations: u32,
+}
+
+impl Default for FlowOptions {
+    fn default() -> Self {
+        FlowOptions {
+            window_radius: 7,
+            levels: 3,
+            iterations: 10,
+        }
+    }
+}
+
+struct Level {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+}
+
+impl Level {
+    fn downsample(&self) -> Level {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let at = |x: usize, y: usize| {
+            self.values[(y.min(self.height - 1) * self.width) + x.min(self.width - 1)]
+        };
+
+        let mut values = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (left, top) = (x * 2, y * 2);
+                values.push(
+                    (at(left, top) + at(left + 1, top) + at(left, top + 1) + at(left + 1, top + 1))
+                        / 4f64,
+                );
+            }
+        }
+
+        Level {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Bilinear lookup with coordinates clamped to the level.
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let x = x.clamp(0f64, (self.width - 1) as f64);
+        let y = y.clamp(0f64, (self.height - 1) as f64);
+        let (left, top) = (x.floor() as usize, y.floor() as usize);
+        let (right, bottom) = (
+            (left + 1).min(self.width - 1),
+            (top + 1).min(self.height - 1),
+        );
+        let (fx, fy) = (x - (left as f64), y - (top as f64));
+        let at = |x: usize, y: usize| self.values[(y * self.width) + x];
+
+        let upper = at(left, top) + ((at(right, top) - at(left, top)) * fx);
+        let lower = at(left, bottom) + ((at(right, bottom) - at(left, bottom)) * fx);
+        upper + ((lower - upper) * fy)
+    }
+}
+
+fn pyramid(image: &RgbaImage, levels: u32) -> Vec<Level> {
+    let (width, height) = image.dimensions();
+    let mut pyramid = vec![Level {
+        width: width as usize,
+        height: height as usize,
+        values: luminance(image),
+    }];
+    for _ in 0..levels {
+        let coarser = pyramid[pyramid.len() - 1].downsample();
+        pyramid.push(coarser);
+    }
+
+    pyramid
+}
+
+/// Refines `guess`, the motion of `point` from `previous` to `next`, by
+/// Lucas-Kanade iterations on a single level.
+fn track_on_level(
+    previous: &Level,
+    next: &Level,
+    point: &Point,
+    guess: (f64, f64),
+    options: &FlowOptions,
+) -> (f64, f64) {
+    let radius = options.window_radius as i64;
+    let mut window = Vec::with_capacity(((2 * radius) + 1).pow(2) as usize);
+    let (mut xx, mut xy, mut yy) = (0f64, 0f64, 0f64);
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let (x, y) = (point.x + (dx as f64), point.y + (dy as f64));
+            let gx = (previous.sample(x + 1f64, y) - previous.sample(x - 1f64, y)) / 2f64;
+            let gy = (previous.sample(x, y + 1f64) - previous.sample(x, y - 1f64)) / 2f64;
+            xx += gx * gx;
+            xy += gx * gy;
+            yy += gy * gy;
+            window.push((x, y, previous.sample(x, y), gx, gy));
+        }
+    }
+
+    // Without texture in both directions the motion is ambiguous.
+    let determinant = (xx * yy) - (xy * xy);
+    if determinant.abs() < 1e-6 {
+        return guess;
+    }
+
+    let mut motion = guess;
+    for _ in 0..options.iterations {
+        let (mut bx, mut by) = (0f64, 0f64);
+        for (x, y, value, gx, gy) in &window {
+            let difference = value - next.sample(x + motion.0, y + motion.1);
+            bx += difference * gx;
+            by += difference * gy;
+        }
+
+        let step = (
+            ((yy * bx) - (xy * by)) / determinant,
+            ((xx * by) - (xy * bx)) / determinant,
+        );
+        motion = (motion.0 + step.0, motion.1 + step.1);
+        if ((step.0 * step.0) + (step.1 * step.1)) < 1e-4 {
+            break;
+        }
+    }
+
+    motion
+}
+
+/// Estimates how far each of `points` moved from `previous` to `next`,
+/// which must have the same size.
+pub fn track_points(
+    previous: &RgbaImage,
+    next: &RgbaImage,
+    points: &[Point],
+    options: &FlowOptions,
+) -> Vec<Point> {
+    let previous_pyramid = pyramid(previous, options.levels);
+    let next_pyramid = pyramid(next, options.levels);
+
+    points
+        .iter()
+        .map(|point| {
+            let mut motion = (0f64, 0f64);
+            for level in (0..previous_pyramid.len()).rev() {
+                let scale = 2f64.powi(level as i32);
+                let level_point = Point {
+                    x: point.x / scale,
+                    y: point.y / scale,
+                };
+                motion = track_on_level(
+                    &previous_pyramid[level],
+                    &next_pyramid[level],
+                    &level_point,
+                    motion,
+                    options,
+                );
+                if level > 0 {
+                    motion = (motion.0 * 2f64, motion.1 * 2f64);
+                }
+            }
+
+            Point {
+                x: motion.0,
+                y: motion.1,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,79 @@
+//! A tiny bitmap font for numbers and hex codes drawn into images.
+
+use image::{Rgba, RgbaImage};
+
+/// Width of every glyph in font pixels.
+pub const GLYPH_WIDTH: u32 = 5;
+/// Height of every glyph in font pixels.
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// Rows of `character`, the leftmost pixel in the highest of the five bits.
+fn glyph(character: char) -> Option<[u8; 7]> {
+    let rows = match character.to_ascii_lowercase() {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'a' => [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f],
+        'b' => [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e],
+        'c' => [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e],
+        'd' => [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f],
+        'e' => [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e],
+        'f' => [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08],
+        '#' => [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a],
+        ' ' => [0x00; 7],
+        _ => return None,
+    };
+
+    Some(rows)
+}
+
+/// Size in pixels of `text` drawn at `scale`, with one font pixel between
+/// glyphs.
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let count = text.chars().count() as u32;
+    let width = (count * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale;
+
+    (width, GLYPH_HEIGHT * scale)
+}
+
+/// Draws `text` with its top left corner at `x`, `y`, every font pixel
+/// `scale` pixels wide. Characters without a glyph, anything but digits,
+/// `a` to `f`, `#` and spaces, are left blank; parts outside the image are
+/// clipped.
+pub fn draw_text(image: &mut RgbaImage, text: &str, x: i64, y: i64, scale: u32, color: Rgba<u8>) {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let scale = scale.max(1) as i64;
+    for (index, character) in text.chars().enumerate() {
+        let rows = match glyph(character) {
+            None => continue,
+            Some(rows) => rows,
+        };
+        let left = x + ((index as i64) * ((GLYPH_WIDTH as i64) + 1) * scale);
+
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..(GLYPH_WIDTH as i64) {
+                if (bits >> ((GLYPH_WIDTH as i64) - 1 - column)) & 1 == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let (pixel_x, pixel_y) = (
+                            left + (column * scale) + dx,
+                            y + ((row as i64) * scale) + dy,
+                        );
+                        if (0..width).contains(&pixel_x) && (0..height).contains(&pixel_y) {
+                            image.put_pixel(pixel_x as u32, pixel_y as u32, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
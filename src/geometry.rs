@@ -0,0 +1,161 @@
+use crate::anchors::Anchor;
+
+#[derive(Clone)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn squared_distance_from(&self, other_point: &Point) -> f64 {
+        let horizontal_offset = self.x - other_point.x;
+        let vertical_offset = self.y - other_point.y;
+        let horizontal_distance = horizontal_offset * horizontal_offset;
+        let vertical_distance = vertical_offset * vertical_offset;
+
+        horizontal_distance + vertical_distance
+    }
+
+    pub fn closest_anchor<M>(
+        &self,
+        anchors: &[Anchor],
+        candidate_indices: &[usize],
+        metric: &M,
+    ) -> Option<usize>
+    where
+        M: DistanceMetric + ?Sized,
+    {
+        let mut closest_anchor: Option<(usize, f64)> = None;
+        for &index in candidate_indices {
+            let distance = metric.distance(self, &anchors[index].point);
+            match closest_anchor {
+                None => {
+                    closest_anchor = Some((index, distance));
+                }
+                Some((_, min_distance)) => {
+                    if min_distance > distance {
+                        closest_anchor = Some((index, distance));
+                    }
+                }
+            }
+        }
+
+        closest_anchor.map(|(index, _)| index)
+    }
+}
+
+pub struct Bounds {
+    pub width: u64,
+    pub height: u64,
+}
+
+pub struct Distance {
+    pub minimum: u32,
+    pub maximum: u32,
+}
+
+/// Measures how far a pixel is from an anchor when assigning pixels to cells.
+///
+/// Only the ordering of the returned values matters, so implementations are
+/// free to skip monotonic steps like taking a square root.
+pub trait DistanceMetric: Send + Sync {
+    fn distance(&self, from: &Point, to: &Point) -> f64;
+
+    /// The distance in pixels, for combining the values of several metrics.
+    fn length(&self, from: &Point, to: &Point) -> f64 {
+        self.distance(from, to)
+    }
+
+    /// Whether `distance` is the plain squared Euclidean distance, which
+    /// lets a column of pixels be assigned without calling it per pixel.
+    fn is_squared_euclidean(&self) -> bool {
+        false
+    }
+}
+
+/// Straight-line distance, producing the classic convex voronoi cells.
+pub struct Euclidean;
+
+impl DistanceMetric for Euclidean {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        from.squared_distance_from(to)
+    }
+
+    fn length(&self, from: &Point, to: &Point) -> f64 {
+        from.squared_distance_from(to).sqrt()
+    }
+
+    fn is_squared_euclidean(&self) -> bool {
+        true
+    }
+}
+
+/// Taxicab distance, producing cells with diagonal and axis-aligned edges.
+pub struct Manhattan;
+
+impl DistanceMetric for Manhattan {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        (from.x - to.x).abs() + (from.y - to.y).abs()
+    }
+}
+
+/// Chessboard distance, producing cells with axis-aligned and 45° edges.
+pub struct Chebyshev;
+
+impl DistanceMetric for Chebyshev {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        (from.x - to.x).abs().max((from.y - to.y).abs())
+    }
+}
+
+/// Another metric measured after stretching both points, so cells come out
+/// `y_scale / x_scale` times as wide as they are tall.
+pub struct Scaled {
+    pub metric: Box<dyn DistanceMetric>,
+    pub x_scale: f64,
+    pub y_scale: f64,
+}
+
+impl Scaled {
+    fn stretch(&self, point: &Point) -> Point {
+        Point {
+            x: point.x * self.x_scale,
+            y: point.y * self.y_scale,
+        }
+    }
+}
+
+impl DistanceMetric for Scaled {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        self.metric.distance(&self.stretch(from), &self.stretch(to))
+    }
+
+    fn length(&self, from: &Point, to: &Point) -> f64 {
+        self.metric.length(&self.stretch(from), &self.stretch(to))
+    }
+}
+
+/// A weighted mix of the lengths of other metrics, producing cells between
+/// their shapes, such as rounded yet crystalline ones from euclidean and
+/// manhattan distances.
+pub struct Blended {
+    pub metrics: Vec<(Box<dyn DistanceMetric>, f64)>,
+}
+
+impl DistanceMetric for Blended {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        self.metrics
+            .iter()
+            .map(|(metric, weight)| metric.length(from, to) * weight)
+            .sum()
+    }
+}
+
+pub fn metric_from_name(name: &str) -> Option<Box<dyn DistanceMetric>> {
+    match name {
+        "euclidean" => Some(Box::new(Euclidean)),
+        "manhattan" => Some(Box::new(Manhattan)),
+        "chebyshev" => Some(Box::new(Chebyshev)),
+        _ => None,
+    }
+}
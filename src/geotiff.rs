@@ -0,0 +1,299 @@
+//! Georeferencing of GeoTIFF inputs: the tags placing the raster on a map,
+//! carried through to the painting and its cell exports so stylized
+//! basemaps still line up in GIS software.
+
+use serde_json::{json, Value};
+use std::io::{Read, Seek};
+use tiff::decoder::Decoder;
+use tiff::tags::Tag;
+
+pub const MODEL_PIXEL_SCALE: u16 = 33550;
+pub const MODEL_TIEPOINT: u16 = 33922;
+pub const MODEL_TRANSFORMATION: u16 = 34264;
+pub const GEO_KEY_DIRECTORY: u16 = 34735;
+pub const GEO_DOUBLE_PARAMS: u16 = 34736;
+pub const GEO_ASCII_PARAMS: u16 = 34737;
+
+const RASTER_TYPE_KEY: u16 = 1025;
+const GEOGRAPHIC_TYPE_KEY: u16 = 2048;
+const PROJECTED_TYPE_KEY: u16 = 3072;
+/// The `RasterPixelIsPoint` raster type: tie points name pixel centers
+/// rather than their upper left corners.
+const PIXEL_IS_POINT: u16 = 2;
+
+/// The GeoTIFF tags of a raster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoReference {
+    pub pixel_scale: Option<Vec<f64>>,
+    pub tie_points: Option<Vec<f64>>,
+    pub transformation: Option<Vec<f64>>,
+    pub key_directory: Vec<u16>,
+    pub double_params: Option<Vec<f64>>,
+    pub ascii_params: Option<String>,
+}
+
+impl GeoReference {
+    /// Reads the GeoTIFF tags of the page at `index` of the TIFF `reader`,
+    /// or `None` if it has none.
+    pub fn read<R: Read + Seek>(reader: R, index: usize) -> Result<Option<GeoReference>, String> {
+        let mut decoder = Decoder::new(reader).map_err(|error| error.to_string())?;
+        for _ in 0..index {
+            decoder.next_image().map_err(|error| error.to_string())?;
+        }
+
+        let mut doubles = |tag: u16| match decoder.find_tag(Tag::from_u16_exhaustive(tag)) {
+            Ok(Some(value)) => value.into_f64_vec().map(Some),
+            Ok(None) => Ok(None),
+            Err(error) => Err(error),
+        };
+        let pixel_scale = doubles(MODEL_PIXEL_SCALE).map_err(|error| error.to_string())?;
+        let tie_points = doubles(MODEL_TIEPOINT).map_err(|error| error.to_string())?;
+        let transformation = doubles(MODEL_TRANSFORMATION).map_err(|error| error.to_string())?;
+        let double_params = doubles(GEO_DOUBLE_PARAMS).map_err(|error| error.to_string())?;
+        let key_directory = match decoder
+            .find_tag(Tag::from_u16_exhaustive(GEO_KEY_DIRECTORY))
+            .map_err(|error| error.to_string())?
+        {
+            None => return Ok(None),
+            Some(value) => value.into_u16_vec().map_err(|error| error.to_string())?,
+        };
+        let ascii_params = match decoder
+            .find_tag(Tag::from_u16_exhaustive(GEO_ASCII_PARAMS))
+            .map_err(|error| error.to_string())?
+        {
+            None => None,
+            Some(value) => Some(value.into_string().map_err(|error| error.to_string())?),
+        };
+
+        let is_placed = transformation
+            .as_ref()
+            .is_some_and(|matrix| matrix.len() >= 16)
+            || (pixel_scale.as_ref().is_some_and(|scale| scale.len() >= 2)
+                && tie_points.as_ref().is_some_and(|points| points.len() >= 6));
+        if !is_placed {
+            return Err(String::from(
+                "the GeoTIFF has neither a model transformation nor a tie point and pixel scale",
+            ));
+        }
+
+        Ok(Some(GeoReference {
+            pixel_scale,
+            tie_points,
+            transformation,
+            key_directory,
+            double_params,
+            ascii_params,
+        }))
+    }
+
+    /// The value of the short `key` of the key directory.
+    fn key(&self, key: u16) -> Option<u16> {
+        self.key_directory
+            .get(4..)?
+            .chunks_exact(4)
+            .find(|entry| (entry[0] == key) && (entry[1] == 0))
+            .map(|entry| entry[3])
+    }
+
+    /// How far raster coordinates of the tags are from those of pixel
+    /// corners, the coordinates cells are described in.
+    fn raster_offset(&self) -> f64 {
+        match self.key(RASTER_TYPE_KEY) {
+            Some(PIXEL_IS_POINT) => 0.5f64,
+            _ => 0f64,
+        }
+    }
+
+    /// The EPSG code of the projected or geographic coordinate system the
+    /// raster is placed in, if it is one of them.
+    pub fn epsg(&self) -> Option<u16> {
+        [PROJECTED_TYPE_KEY, GEOGRAPHIC_TYPE_KEY]
+            .iter()
+            .find_map(|key| self.key(*key))
+            .filter(|code| (1..32767).contains(code))
+    }
+
+    /// The affine transformation `[a, b, c, d, e, f]` from the pixel corner
+    /// coordinates `(x, y)` of the raster to the map, at `a x + b y + c`
+    /// and `d x + e y + f`.
+    pub fn affine(&self) -> Option<[f64; 6]> {
+        let offset = self.raster_offset();
+        let [a, b, c, d, e, f] = match (&self.transformation, &self.pixel_scale, &self.tie_points) {
+            (Some(matrix), _, _) if matrix.len() >= 16 => [
+                matrix[0], matrix[1], matrix[3], matrix[4], matrix[5], matrix[7],
+            ],
+            (_, Some(scale), Some(points)) if (scale.len() >= 2) && (points.len() >= 6) => [
+                scale[0],
+                0f64,
+                points[3] - (points[0] * scale[0]),
+                0f64,
+                -scale[1],
+                points[4] + (points[1] * scale[1]),
+            ],
+            _ => return None,
+        };
+
+        // A pixel corner at `x` is at `x - offset` in the raster space.
+        Some([a, b, c - (offset * (a + b)), d, e, f - (offset * (d + e))])
+    }
+
+    /// The georeference of the raster resampled from `from` to `to` pixels,
+    /// covering the same area of the map.
+    pub fn scaled(&self, from: (u32, u32), to: (u32, u32)) -> GeoReference {
+        let horizontal = (to.0 as f64) / (from.0 as f64);
+        let vertical = (to.1 as f64) / (from.1 as f64);
+        let offset = self.raster_offset();
+        let scale_coordinate =
+            |coordinate: f64, factor: f64| ((coordinate + offset) * factor) - offset;
+
+        let pixel_scale = self.pixel_scale.as_ref().map(|scale| {
+            let mut scale = scale.clone();
+            if let [horizontal_scale, vertical_scale, ..] = scale.as_mut_slice() {
+                *horizontal_scale /= horizontal;
+                *vertical_scale /= vertical;
+            }
+            scale
+        });
+        let tie_points = self.tie_points.as_ref().map(|points| {
+            let mut points = points.clone();
+            for point in points.chunks_exact_mut(6) {
+                point[0] = scale_coordinate(point[0], horizontal);
+                point[1] = scale_coordinate(point[1], vertical);
+            }
+            points
+        });
+        let transformation = self.transformation.as_ref().map(|matrix| {
+            let mut matrix = matrix.clone();
+            // Every row maps the raster coordinate `(x + offset) / factor -
+            // offset` of the resampled raster as the original one.
+            for row in matrix.chunks_exact_mut(4).take(3) {
+                let (x_factor, y_factor) = (row[0], row[1]);
+                row[0] = x_factor / horizontal;
+                row[1] = y_factor / vertical;
+                row[3] += (x_factor * ((offset / horizontal) - offset))
+                    + (y_factor * ((offset / vertical) - offset));
+            }
+            matrix
+        });
+
+        GeoReference {
+            pixel_scale,
+            tie_points,
+            transformation,
+            ..self.clone()
+        }
+    }
+
+    /// The georeference of the part of the raster from the pixel at `(x,
+    /// y)` on.
+    pub fn cropped(&self, x: u32, y: u32) -> GeoReference {
+        let (x, y) = (x as f64, y as f64);
+        let tie_points = self.tie_points.as_ref().map(|points| {
+            let mut points = points.clone();
+            for point in points.chunks_exact_mut(6) {
+                point[0] -= x;
+                point[1] -= y;
+            }
+            points
+        });
+        let transformation = self.transformation.as_ref().map(|matrix| {
+            let mut matrix = matrix.clone();
+            for row in matrix.chunks_exact_mut(4).take(3) {
+                row[3] += (row[0] * x) + (row[1] * y);
+            }
+            matrix
+        });
+
+        GeoReference {
+            tie_points,
+            transformation,
+            ..self.clone()
+        }
+    }
+
+    /// The lines of an ESRI world file for the raster, placing the center
+    /// of its upper left pixel.
+    pub fn world_file(&self) -> Option<String> {
+        let [a, b, c, d, e, f] = self.affine()?;
+        let lines = [a, d, b, e, c + ((a + b) / 2f64), f + ((d + e) / 2f64)];
+
+        Some(lines.iter().map(|value| format!("{}\n", value)).collect())
+    }
+
+    /// Moves every position of a [`crate::export::cells_to_geojson`]
+    /// collection from pixels onto the map, naming its coordinate system if
+    /// it has an EPSG code.
+    pub fn georeference_geojson(&self, geojson: &mut Value) {
+        let affine = match self.affine() {
+            None => return,
+            Some(affine) => affine,
+        };
+        let to_map = |x: f64, y: f64| {
+            [
+                (affine[0] * x) + (affine[1] * y) + affine[2],
+                (affine[3] * x) + (affine[4] * y) + affine[5],
+            ]
+        };
+
+        if let Some(features) = geojson["features"].as_array_mut() {
+            for feature in features {
+                map_positions(&mut feature["geometry"]["coordinates"], &to_map);
+                map_positions(&mut feature["properties"]["anchor"], &to_map);
+            }
+        }
+        if let Some([left, top, right, bottom]) = geojson["bbox"]
+            .as_array()
+            .and_then(|bbox| bbox.iter().map(Value::as_f64).collect::<Option<Vec<f64>>>())
+            .and_then(|bbox| <[f64; 4]>::try_from(bbox).ok())
+        {
+            let corners = [
+                to_map(left, top),
+                to_map(right, top),
+                to_map(left, bottom),
+                to_map(right, bottom),
+            ];
+            let extent = |axis: usize, pick: fn(f64, f64) -> f64| {
+                corners
+                    .iter()
+                    .map(|corner| corner[axis])
+                    .reduce(pick)
+                    .unwrap_or_default()
+            };
+            geojson["bbox"] = json!([
+                extent(0, f64::min),
+                extent(1, f64::min),
+                extent(0, f64::max),
+                extent(1, f64::max),
+            ]);
+        }
+        if let Some(code) = self.epsg() {
+            geojson["crs"] = json!({
+                "type": "name",
+                "properties": {"name": format!("urn:ogc:def:crs:EPSG::{}", code)},
+            });
+        }
+    }
+}
+
+/// Maps every `[x, y]` position nested in `coordinates` with `to_map`.
+fn map_positions(coordinates: &mut Value, to_map: &dyn Fn(f64, f64) -> [f64; 2]) {
+    let positions = match coordinates.as_array_mut() {
+        None => return,
+        Some(positions) => positions,
+    };
+    match (
+        positions.first().and_then(Value::as_f64),
+        positions.get(1).and_then(Value::as_f64),
+    ) {
+        (Some(x), Some(y)) => {
+            let [map_x, map_y] = to_map(x, y);
+            positions[0] = json!(map_x);
+            positions[1] = json!(map_y);
+        }
+        _ => {
+            for position in positions {
+                map_positions(position, to_map);
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::anchors::{color_anchor_points, Anchor};
+use crate::colorize::{Cell, CellColorizer};
+use crate::geometry::{DistanceMetric, Point};
+use crate::render::{
+    assign_cells, closest_in_column, color_cells, paint_cells, AnchorColumns, CellMap,
+    RenderOptions, UNASSIGNED,
+};
+use image::{Rgba, RgbaImage};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// A rendered tessellation that can be updated as anchors are added, moved or
+/// removed, recomputing only the columns such a change can reach instead of
+/// the whole image.
+///
+/// The result matches [`crate::render::render_voronoi`] with the same anchors,
+/// without smoothing, anti-aliasing or a different output size.
+pub struct IncrementalRenderer<'a> {
+    source_image: Cow<'a, RgbaImage>,
+    minimum_distance: u32,
+    metric: &'a dyn DistanceMetric,
+    colorizer: &'a dyn CellColorizer,
+    anchors: Vec<Anchor>,
+    cell_map: CellMap,
+    colors: Vec<Rgba<u8>>,
+    image: RgbaImage,
+}
+
+impl<'a> IncrementalRenderer<'a> {
+    pub fn new(
+        source_image: &'a RgbaImage,
+        anchors: Vec<Anchor>,
+        options: &RenderOptions<'a>,
+    ) -> IncrementalRenderer<'a> {
+        IncrementalRenderer::from_source(Cow::Borrowed(source_image), anchors, options)
+    }
+
+    /// Like [`IncrementalRenderer::new`], but keeping `source_image` itself,
+    /// for renderers that outlive the request that made them.
+    pub fn with_owned_source(
+        source_image: RgbaImage,
+        anchors: Vec<Anchor>,
+        options: &RenderOptions<'a>,
+    ) -> IncrementalRenderer<'a> {
+        IncrementalRenderer::from_source(Cow::Owned(source_image), anchors, options)
+    }
+
+    fn from_source(
+        source_image: Cow<'a, RgbaImage>,
+        anchors: Vec<Anchor>,
+        options: &RenderOptions<'a>,
+    ) -> IncrementalRenderer<'a> {
+        let (image_width, image_height) = source_image.dimensions();
+        let cell_map = assign_cells(
+            &anchors,
+            image_width,
+            image_height,
+            options.minimum_distance,
+            options.metric,
+        );
+        let colors = color_cells(&cell_map, &anchors, &source_image, options.colorizer);
+        let image = paint_cells(&cell_map, &colors);
+
+        IncrementalRenderer {
+            source_image,
+            minimum_distance: options.minimum_distance,
+            metric: options.metric,
+            colorizer: options.colorizer,
+            anchors,
+            cell_map,
+            colors,
+            image,
+        }
+    }
+
+    pub fn anchors(&self) -> &[Anchor] {
+        &self.anchors
+    }
+
+    pub fn cell_map(&self) -> &CellMap {
+        &self.cell_map
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Adds an anchor colored from the source image and returns its index.
+    pub fn add_anchor(&mut self, point: Point) -> usize {
+        let anchor = color_anchor_points(self.source_image.as_ref(), vec![point]).remove(0);
+        let index = self.anchors.len();
+
+        let old_search = self.search();
+        self.anchors.push(anchor);
+        self.colors.push(Rgba([0, 0, 0, 0]));
+        let search = self.search();
+        let mut columns = self.reachable_columns(&self.anchors[index].point, &search);
+        columns.extend(search.changed_columns(&old_search));
+        columns.sort_unstable();
+        columns.dedup();
+        self.reassign(index as u32, columns, &search);
+
+        index
+    }
+
+    /// Moves an anchor, resampling its color at the new position.
+    pub fn move_anchor(&mut self, index: usize, point: Point) {
+        let old_search = self.search();
+        let old_columns = self.reachable_columns(&self.anchors[index].point, &old_search);
+        self.anchors[index] =
+            color_anchor_points(self.source_image.as_ref(), vec![point]).remove(0);
+        let search = self.search();
+        let mut columns = self.reachable_columns(&self.anchors[index].point, &search);
+        columns.extend(old_columns);
+        columns.extend(search.changed_columns(&old_search));
+        columns.sort_unstable();
+        columns.dedup();
+
+        self.reassign(index as u32, columns, &search);
+    }
+
+    /// Removes an anchor. Like [`Vec::swap_remove`], the last anchor takes
+    /// over its index.
+    pub fn remove_anchor(&mut self, index: usize) -> Anchor {
+        let old_search = self.search();
+        let mut columns = self.reachable_columns(&self.anchors[index].point, &old_search);
+        let label = index as u32;
+        let last = (self.anchors.len() - 1) as u32;
+        let last_columns = self.reachable_columns(&self.anchors[last as usize].point, &old_search);
+
+        // The pixels of the removed cell are labelled again below, those of
+        // the last anchor follow it to its new index.
+        for &x in &columns {
+            for y in 0..self.cell_map.height {
+                if self.cell_map.label(x, y) == label {
+                    self.cell_map.set_label(x, y, UNASSIGNED);
+                }
+            }
+        }
+        if label != last {
+            for &x in &last_columns {
+                for y in 0..self.cell_map.height {
+                    if self.cell_map.label(x, y) == last {
+                        self.cell_map.set_label(x, y, label);
+                    }
+                }
+            }
+        }
+        let removed = self.anchors.swap_remove(index);
+        self.colors.swap_remove(index);
+
+        let search = self.search();
+        columns.extend(search.changed_columns(&old_search));
+        columns.sort_unstable();
+        columns.dedup();
+        let mut affected = self.relabel(columns, &search);
+        affected.remove(&UNASSIGNED);
+        self.recolor(&affected, &search);
+
+        removed
+    }
+
+    /// The current anchors by column, with the window every column is
+    /// searched with.
+    fn search(&self) -> Search {
+        let anchor_columns = AnchorColumns::new(&self.anchors);
+        let windows = (0..self.cell_map.width)
+            .map(|x| {
+                anchor_columns.search_window(x, 0..self.cell_map.height, self.minimum_distance)
+            })
+            .collect();
+
+        Search {
+            anchor_columns,
+            windows,
+        }
+    }
+
+    /// Columns whose candidate anchors can include an anchor at `point`:
+    /// those less than their search window from it.
+    fn reachable_columns(&self, point: &Point, search: &Search) -> Vec<u32> {
+        (0..self.cell_map.width)
+            .filter(|x| ((*x as f64) - point.x).abs() < (search.windows[*x as usize] as f64))
+            .collect()
+    }
+
+    /// Updates the labels in `columns` after the anchor `label` changed,
+    /// then recolors every cell that gained or lost pixels, and its own.
+    fn reassign(&mut self, label: u32, columns: Vec<u32>, search: &Search) {
+        let mut affected = self.relabel(columns, search);
+        affected.insert(label);
+        affected.remove(&UNASSIGNED);
+
+        self.recolor(&affected, search);
+    }
+
+    /// Labels every pixel of `columns` again the way [`assign_cells`] does,
+    /// returning the labels of the cells that gained or lost pixels.
+    fn relabel(&mut self, columns: Vec<u32>, search: &Search) -> HashSet<u32> {
+        let mut affected = HashSet::new();
+        for x in columns {
+            let candidates = search
+                .anchor_columns
+                .candidates(x, search.windows[x as usize]);
+            let labels = closest_in_column(
+                x,
+                0..self.cell_map.height,
+                &self.anchors,
+                &candidates,
+                self.metric,
+            );
+
+            for (y, updated) in (0..self.cell_map.height).zip(labels) {
+                let current = self.cell_map.label(x, y);
+                if updated != current {
+                    self.cell_map.set_label(x, y, updated);
+                    affected.insert(current);
+                    affected.insert(updated);
+                }
+                if updated == UNASSIGNED {
+                    self.image.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                }
+            }
+        }
+
+        affected
+    }
+
+    fn recolor(&mut self, affected: &HashSet<u32>, search: &Search) {
+        if affected.is_empty() {
+            return;
+        }
+
+        let mut cell_pixels: Vec<(u32, Vec<(u32, u32)>)> =
+            affected.iter().map(|label| (*label, Vec::new())).collect();
+        let mut columns: Vec<u32> = affected
+            .iter()
+            .flat_map(|label| self.reachable_columns(&self.anchors[*label as usize].point, search))
+            .collect();
+        columns.sort_unstable();
+        columns.dedup();
+        for x in columns {
+            for y in 0..self.cell_map.height {
+                let label = self.cell_map.label(x, y);
+                if let Some((_, pixels)) = cell_pixels
+                    .iter_mut()
+                    .find(|(affected_label, _)| *affected_label == label)
+                {
+                    pixels.push((x, y));
+                }
+            }
+        }
+
+        for (label, pixels) in cell_pixels {
+            let index = label as usize;
+            let color = self.colorizer.colorize(
+                &Cell {
+                    index,
+                    anchor: &self.anchors[index],
+                    pixels: &pixels,
+                },
+                &self.source_image,
+            );
+            self.colors[index] = color;
+            for (x, y) in pixels {
+                self.image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// The anchors of an [`IncrementalRenderer`] by column, with the
+/// [`AnchorColumns::search_window`] of every column.
+struct Search {
+    anchor_columns: AnchorColumns,
+    windows: Vec<u32>,
+}
+
+impl Search {
+    /// Columns searched with another window than in `other`, whose labels
+    /// can change without any anchor in reach of them moving.
+    fn changed_columns<'s>(&'s self, other: &'s Search) -> impl Iterator<Item = u32> + 's {
+        (0..self.windows.len())
+            .filter(|x| self.windows[*x] != other.windows[*x])
+            .map(|x| x as u32)
+    }
+}
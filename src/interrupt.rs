@@ -0,0 +1,44 @@
+//! Interrupting a painting with Ctrl-C: the first interrupt stops assigning
+//! columns, so the ones already finished can still be saved, and a second
+//! one kills the process as usual.
+
+use crate::render::RenderObserver;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    // Storing an atomic and resetting the handler are both safe in a signal
+    // handler.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+/// Makes Ctrl-C set [`interrupted`] instead of killing the process. Only
+/// Unix signals are caught; elsewhere Ctrl-C still kills it.
+pub fn catch_interrupts() {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Whether Ctrl-C was pressed since [`catch_interrupts`].
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Cancels the render it observes once [`interrupted`].
+pub struct InterruptObserver;
+
+impl RenderObserver for InterruptObserver {
+    fn is_cancelled(&self) -> bool {
+        interrupted()
+    }
+}
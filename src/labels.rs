@@ -0,0 +1,148 @@
+//! Cell label maps: images whose every pixel holds the index of the anchor
+//! owning it, for tools that need the assignment rather than the colors.
+//!
+//! Up to 65535 cells are stored as 16-bit grayscale with `65535` for pixels
+//! outside every cell. Larger diagrams are stored as RGBA, every pixel the
+//! big endian bytes of a 32-bit index and `0xFFFFFFFF` outside every cell.
+//!
+//! A JSON palette written next to the label map holds the anchor, weight and
+//! color of every cell, so together they describe a finished tessellation
+//! without the image it was painted from.
+
+use crate::anchors::Anchor;
+use crate::geometry::Point;
+use crate::palette::{format_hex_color, parse_hex_color};
+use crate::render::{output_anchors, CellMap, RenderOptions, UNASSIGNED};
+use image::{DynamicImage, ImageBuffer, Luma, Rgba};
+use serde::{Deserialize, Serialize};
+
+const UNASSIGNED_16: u16 = u16::MAX;
+
+/// Encodes `cell_map` as a label map image of its size, in the narrowest
+/// format that holds `anchor_count` cells.
+pub fn encode_label_map(cell_map: &CellMap, anchor_count: usize) -> DynamicImage {
+    let (width, height) = (cell_map.width, cell_map.height);
+    if anchor_count < (UNASSIGNED_16 as usize) {
+        DynamicImage::ImageLuma16(ImageBuffer::from_fn(width, height, |x, y| {
+            match cell_map.label(x, y) {
+                UNASSIGNED => Luma([UNASSIGNED_16]),
+                label => Luma([label as u16]),
+            }
+        }))
+    } else {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            Rgba(cell_map.label(x, y).to_be_bytes())
+        }))
+    }
+}
+
+/// Reads back a label map written by [`encode_label_map`].
+pub fn decode_label_map(image: &DynamicImage) -> Result<CellMap, String> {
+    let mut cell_map = CellMap::new(image.width(), image.height());
+    match image {
+        DynamicImage::ImageLuma16(labels) => {
+            for (x, y, Luma([label])) in labels.enumerate_pixels() {
+                if *label != UNASSIGNED_16 {
+                    cell_map.set_label(x, y, *label as u32);
+                }
+            }
+        }
+        DynamicImage::ImageRgba8(labels) => {
+            for (x, y, Rgba(bytes)) in labels.enumerate_pixels() {
+                cell_map.set_label(x, y, u32::from_be_bytes(*bytes));
+            }
+        }
+        _ => {
+            return Err(String::from(
+                "a label map must be a 16-bit grayscale or an 8-bit RGBA PNG",
+            ))
+        }
+    }
+
+    Ok(cell_map)
+}
+
+/// The cells of a label map, as written to its JSON sidecar.
+#[derive(Serialize, Deserialize)]
+pub struct LabelPalette {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<LabelledCell>,
+}
+
+impl LabelPalette {
+    /// The anchors of the cells in index order, colored as listed, so every
+    /// label of the map names the anchor at its position.
+    pub fn anchors(&self) -> Result<Vec<Anchor>, String> {
+        let mut anchors: Vec<Option<Anchor>> = vec![None; self.cells.len()];
+        for cell in &self.cells {
+            let slot = anchors.get_mut(cell.index as usize).ok_or(format!(
+                "cell {} is past the {} cells listed",
+                cell.index,
+                self.cells.len()
+            ))?;
+            if slot.is_some() {
+                return Err(format!("cell {} is listed twice", cell.index));
+            }
+            let color = parse_hex_color(&cell.color).ok_or(format!(
+                "cell {} has color `{}`, expected `#RRGGBB` or `#RRGGBBAA`",
+                cell.index, cell.color
+            ))?;
+            *slot = Some(Anchor {
+                point: Point {
+                    x: cell.x,
+                    y: cell.y,
+                },
+                color,
+            });
+        }
+
+        // Every slot is filled: indices are distinct and below their count.
+        Ok(anchors.into_iter().flatten().collect())
+    }
+}
+
+/// One cell of a [`LabelPalette`], its anchor in label map pixels.
+#[derive(Serialize, Deserialize)]
+pub struct LabelledCell {
+    pub index: u32,
+    pub x: f64,
+    pub y: f64,
+    /// The additive weight of the cell in pixels, 0 unless painted with
+    /// `--weighting additive`.
+    #[serde(default)]
+    pub weight: f64,
+    /// `#RRGGBB`, or `#RRGGBBAA` when not opaque.
+    pub color: String,
+}
+
+/// Describes the cells of `anchors`, placed on a `source_size` image and
+/// filled with `colors`, at the output size of `options` that the label map
+/// is written at.
+pub fn label_palette(
+    anchors: &[Anchor],
+    colors: &[Rgba<u8>],
+    source_size: (u32, u32),
+    options: &RenderOptions,
+) -> LabelPalette {
+    let (width, height) = options.output_size.unwrap_or(source_size);
+    let (anchors, _) = output_anchors(anchors, source_size, options);
+    let cells = anchors
+        .iter()
+        .zip(colors)
+        .enumerate()
+        .map(|(index, (anchor, color))| LabelledCell {
+            index: index as u32,
+            x: anchor.point.x,
+            y: anchor.point.y,
+            weight: options.radii.map_or(0f64, |radii| radii[index]),
+            color: format_hex_color(*color),
+        })
+        .collect();
+
+    LabelPalette {
+        width,
+        height,
+        cells,
+    }
+}
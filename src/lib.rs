@@ -0,0 +1,81 @@
+pub mod adjust;
+pub mod analysis;
+pub mod anchors;
+pub mod animation;
+pub mod art;
+pub mod base64;
+pub mod burst;
+pub mod cache;
+pub mod cmyk;
+pub mod color;
+pub mod colorize;
+pub mod compose;
+#[cfg(unix)]
+pub mod daemon;
+pub mod diff;
+pub mod dxf;
+pub mod export;
+pub mod flow;
+pub mod font;
+pub mod geometry;
+pub mod geotiff;
+pub mod incremental;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod interrupt;
+pub mod labels;
+pub mod locations;
+pub mod lottie;
+pub mod manifest;
+pub mod mask;
+pub mod merge;
+pub mod metrics;
+pub mod nested;
+#[cfg(all(feature = "net", not(target_arch = "wasm32")))]
+pub mod net;
+pub mod noise;
+pub mod numbers;
+pub mod optimize;
+pub mod orientation;
+pub mod pages;
+pub mod palette;
+pub mod paths;
+pub mod pattern;
+pub mod pdf;
+pub mod preset;
+pub mod profile;
+pub mod projection;
+pub mod psd;
+#[cfg(feature = "raw")]
+pub mod raw;
+pub mod recipe;
+pub mod refine;
+pub mod relax;
+pub mod relief;
+pub mod render;
+pub mod rounding;
+pub mod sampling;
+pub mod script;
+pub mod selftest;
+pub mod sequence;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod snapshot;
+pub mod spritesheet;
+pub mod stats;
+pub mod stipple;
+pub mod stream;
+pub mod terminal;
+pub mod tiles;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timings;
+pub mod transform;
+pub mod treemap;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod video;
+pub mod vision;
+pub mod voronoi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "window")]
+pub mod window;
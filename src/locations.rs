@@ -0,0 +1,239 @@
+//! Anchors from real-world point data: the waypoints of a GPX file or the
+//! rows of a CSV of latitudes and longitudes, projected onto a map image of
+//! a known bounding box so every location becomes a cell, its territory.
+
+use crate::geometry::Point;
+use std::f64::consts::PI;
+
+/// The furthest latitude Web Mercator maps reach, where they are square.
+const MERCATOR_LIMIT: f64 = 85.051_128_779_806_59;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// How a map image lays out latitudes and longitudes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapProjection {
+    /// Latitude and longitude spaced evenly, as plate carrée maps are.
+    Equirectangular,
+    /// Latitudes stretched towards the poles, as web map tiles are.
+    Mercator,
+}
+
+impl MapProjection {
+    pub fn from_name(name: &str) -> Option<MapProjection> {
+        match name {
+            "equirectangular" => Some(MapProjection::Equirectangular),
+            "mercator" => Some(MapProjection::Mercator),
+            _ => None,
+        }
+    }
+
+    /// The vertical coordinate of `latitude` on maps of this projection,
+    /// growing northwards.
+    fn northing(self, latitude: f64) -> f64 {
+        match self {
+            MapProjection::Equirectangular => latitude,
+            MapProjection::Mercator => {
+                let latitude = latitude.clamp(-MERCATOR_LIMIT, MERCATOR_LIMIT);
+                ((PI / 4f64) + (latitude.to_radians() / 2f64)).tan().ln()
+            }
+        }
+    }
+}
+
+/// The longitudes and latitudes at the edges of a map image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapBounds {
+    pub west: f64,
+    pub south: f64,
+    pub east: f64,
+    pub north: f64,
+}
+
+impl MapBounds {
+    /// Parses `west,south,east,north` in degrees.
+    pub fn parse(value: &str) -> Option<MapBounds> {
+        let edges = value
+            .split(',')
+            .map(|edge| edge.trim().parse::<f64>().ok())
+            .collect::<Option<Vec<f64>>>()?;
+
+        match edges[..] {
+            [west, south, east, north]
+                if (west < east)
+                    && (south < north)
+                    && edges.iter().all(|edge| edge.is_finite()) =>
+            {
+                Some(MapBounds {
+                    west,
+                    south,
+                    east,
+                    north,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Where `location` is on a `width` by `height` map of these bounds, or
+    /// `None` if it is off the map.
+    pub fn project(
+        &self,
+        location: &Location,
+        projection: MapProjection,
+        width: u32,
+        height: u32,
+    ) -> Option<Point> {
+        let is_inside = (self.west..=self.east).contains(&location.longitude)
+            && (self.south..=self.north).contains(&location.latitude);
+        if !is_inside {
+            return None;
+        }
+
+        let (top, bottom) = (
+            projection.northing(self.north),
+            projection.northing(self.south),
+        );
+        let x = (location.longitude - self.west) / (self.east - self.west);
+        let y = (top - projection.northing(location.latitude)) / (top - bottom);
+
+        // Locations on the far edges stay on the last pixel.
+        Some(Point {
+            x: (x * (width as f64)).min((width as f64) - 1f64),
+            y: (y * (height as f64)).min((height as f64) - 1f64),
+        })
+    }
+}
+
+/// The value of the attribute `name` in the inside `tag` of an XML start
+/// tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(start) = rest.find(name) {
+        let is_whole = rest[..start].ends_with(char::is_whitespace);
+        let after = rest[(start + name.len())..].trim_start();
+        rest = &rest[(start + name.len())..];
+        let value = match after.strip_prefix('=') {
+            Some(value) if is_whole => value.trim_start(),
+            _ => continue,
+        };
+        let quote = value
+            .chars()
+            .next()
+            .filter(|quote| (*quote == '"') || (*quote == '\''))?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+
+    None
+}
+
+/// The waypoints, track points and route points of a GPX file, in order.
+pub fn parse_gpx(text: &str) -> Result<Vec<Location>, String> {
+    let mut locations = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[(start + 1)..];
+        let end = rest.find('>').ok_or("a tag is not closed")?;
+        let tag = &rest[..end];
+        rest = &rest[(end + 1)..];
+
+        let name = tag
+            .split(|character: char| character.is_whitespace() || (character == '/'))
+            .next()
+            .unwrap_or_default();
+        // Elements may be namespaced, like `gpx:trkpt`.
+        let name = name.rsplit(':').next().unwrap_or(name);
+        if !matches!(name, "wpt" | "trkpt" | "rtept") {
+            continue;
+        }
+
+        let coordinate = |attribute_name: &str| {
+            attribute(tag, attribute_name)
+                .ok_or_else(|| format!("a `{}` has no `{}`", name, attribute_name))?
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| {
+                    format!(
+                        "a `{}` has a `{}` that is not a number",
+                        name, attribute_name
+                    )
+                })
+        };
+        locations.push(Location {
+            latitude: coordinate("lat")?,
+            longitude: coordinate("lon")?,
+        });
+    }
+
+    Ok(locations)
+}
+
+/// The fields of a CSV line, with double quoted fields unquoted.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut is_quoted = false;
+    let mut characters = line.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' if is_quoted && (characters.peek() == Some(&'"')) => {
+                characters.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => is_quoted = !is_quoted,
+            ',' if !is_quoted => fields.push(String::new()),
+            character => fields.last_mut().unwrap().push(character),
+        }
+    }
+
+    fields
+}
+
+/// The locations of a CSV whose header names a `lat` or `latitude` column
+/// and a `lon`, `lng` or `longitude` one.
+pub fn parse_location_csv(text: &str) -> Result<Vec<Location>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header = match lines.next() {
+        None => return Ok(Vec::new()),
+        Some((_, header)) => csv_fields(header),
+    };
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|field| names.contains(&field.trim().to_lowercase().as_str()))
+    };
+    let (latitude_column, longitude_column) =
+        match (
+            column(&["lat", "latitude"]),
+            column(&["lon", "lng", "long", "longitude"]),
+        ) {
+            (Some(latitude_column), Some(longitude_column)) => (latitude_column, longitude_column),
+            _ => return Err(String::from(
+                "the header needs a `lat` or `latitude` and a `lon`, `lng` or `longitude` column",
+            )),
+        };
+
+    lines
+        .map(|(index, line)| {
+            let fields = csv_fields(line);
+            let coordinate = |column: usize, name: &str| {
+                fields
+                    .get(column)
+                    .and_then(|field| field.trim().parse::<f64>().ok())
+                    .ok_or_else(|| format!("line {}: the {} is not a number", index + 1, name))
+            };
+
+            Ok(Location {
+                latitude: coordinate(latitude_column, "latitude")?,
+                longitude: coordinate(longitude_column, "longitude")?,
+            })
+        })
+        .collect()
+}
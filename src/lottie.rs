@@ -0,0 +1,137 @@
+//! Lottie animations of a painting building up, cell by cell, as vector
+//! shapes a web page can play at any size.
+
+use crate::geometry::{Bounds, Point};
+use image::Rgba;
+use serde_json::{json, Value};
+
+/// How the cells of a [`cells_to_lottie`] animation appear.
+pub struct Reveal {
+    /// Seconds from the first cell starting to appear to the last being in.
+    pub duration: f64,
+    pub fps: u32,
+    /// Share of `duration` every cell takes to fade in.
+    pub fade: f64,
+}
+
+fn static_value(value: Value) -> Value {
+    json!({ "a": 0, "k": value })
+}
+
+fn path(ring: &[Point]) -> Value {
+    let vertices: Vec<[f64; 2]> = ring.iter().map(|point| [point.x, point.y]).collect();
+    let tangents = vec![[0f64, 0f64]; vertices.len()];
+
+    json!({
+        "ty": "sh",
+        "ks": static_value(json!({ "i": tangents, "o": tangents, "v": vertices, "c": true })),
+    })
+}
+
+/// Opacity fading from nothing to `opacity` percent between two frames.
+fn fade_in(start: f64, end: f64, opacity: f64) -> Value {
+    json!({
+        "a": 1,
+        "k": [
+            {
+                "t": start,
+                "s": [0],
+                "i": { "x": [0.667], "y": [1] },
+                "o": { "x": [0.333], "y": [0] },
+            },
+            { "t": end, "s": [opacity] },
+        ],
+    })
+}
+
+fn group_transform() -> Value {
+    json!({
+        "ty": "tr",
+        "p": static_value(json!([0, 0])),
+        "a": static_value(json!([0, 0])),
+        "s": static_value(json!([100, 100])),
+        "r": static_value(json!(0)),
+        "o": static_value(json!(100)),
+        "sk": static_value(json!(0)),
+        "sa": static_value(json!(0)),
+    })
+}
+
+/// Builds a Lottie animation of the cells fading in one after another in
+/// the order given, each given as its rings like for
+/// [`crate::export::cells_to_geojson`]. Holes are kept with an even-odd
+/// fill, and the last frame holds the whole painting.
+pub fn cells_to_lottie(
+    cells: &[Vec<Vec<Point>>],
+    colors: &[Rgba<u8>],
+    bounds: &Bounds,
+    reveal: &Reveal,
+) -> Value {
+    let visible: Vec<(&Vec<Vec<Point>>, &Rgba<u8>)> = cells
+        .iter()
+        .zip(colors)
+        .filter(|(rings, color)| (color[3] > 0) && rings.iter().any(|ring| ring.len() >= 3))
+        .collect();
+
+    let frames = (reveal.duration * (reveal.fps as f64)).round().max(1f64);
+    let fade = (frames * reveal.fade.clamp(0f64, 1f64))
+        .max(1f64)
+        .min(frames);
+    let step = (frames - fade) / (visible.len().max(2) - 1) as f64;
+    let shapes: Vec<Value> = visible
+        .iter()
+        .enumerate()
+        .map(|(index, (rings, color))| {
+            let start = step * (index as f64);
+            let mut items: Vec<Value> = rings
+                .iter()
+                .filter(|ring| ring.len() >= 3)
+                .map(|ring| path(ring))
+                .collect();
+            let channel = |channel: u8| (channel as f64) / 255f64;
+            items.push(json!({
+                "ty": "fl",
+                "c": static_value(json!([channel(color[0]), channel(color[1]), channel(color[2]), 1])),
+                "o": fade_in(start, start + fade, channel(color[3]) * 100f64),
+                "r": 2,
+            }));
+            items.push(group_transform());
+
+            json!({ "ty": "gr", "nm": format!("cell {}", index), "it": items })
+        })
+        .collect();
+
+    // One frame past the last fade so players end on the finished painting.
+    let end = frames + 1f64;
+    json!({
+        "v": "5.7.0",
+        "fr": reveal.fps,
+        "ip": 0,
+        "op": end,
+        "w": bounds.width,
+        "h": bounds.height,
+        "nm": "voronoi-painter",
+        "ddd": 0,
+        "assets": [],
+        "layers": [{
+            "ddd": 0,
+            "ind": 1,
+            "ty": 4,
+            "nm": "cells",
+            "sr": 1,
+            "ks": {
+                "o": static_value(json!(100)),
+                "r": static_value(json!(0)),
+                "p": static_value(json!([0, 0, 0])),
+                "a": static_value(json!([0, 0, 0])),
+                "s": static_value(json!([100, 100, 100])),
+            },
+            "ao": 0,
+            "shapes": shapes,
+            "ip": 0,
+            "op": end,
+            "st": 0,
+            "bm": 0,
+        }],
+    })
+}
@@ -1,381 +1,7263 @@
-extern crate core;
-
-use byteorder::{ByteOrder, LittleEndian};
-use clap::{arg, Command};
-use image::{GenericImageView, Rgba};
-use rand::Rng;
-use std::collections::VecDeque;
-use std::f64::consts::PI;
+use clap::{arg, Arg, ArgMatches, Command, ValueSource};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{
+    imageops, ColorType, Delay, GrayImage, ImageEncoder, ImageError, ImageFormat, ImageResult,
+    Rgba, RgbaImage,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::panic;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{mpsc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use voronoi_painter::adjust::{auto_levels, equalize, Adjustments};
+use voronoi_painter::analysis::{gradient_magnitude, snap_to_edges};
+use voronoi_painter::anchors::{color_anchor_points, sample_anchor_colors, Anchor, ColorSampling};
+use voronoi_painter::animation::{
+    animate, decode_gif_frames, encode_apng, encode_gif, encode_gif_frames, Animation, CellReveal,
+    Easing,
+};
+use voronoi_painter::art::{color_anchors_from_palette, SpatialGradient};
+use voronoi_painter::burst::{combine_exposures, BurstMode};
+#[cfg(unix)]
+use voronoi_painter::cache::keep_decoded_caches;
+#[cfg(feature = "window")]
+use voronoi_painter::cache::read_anchor_points_from_file;
+use voronoi_painter::cache::{image_hash, read_anchor_cache, write_anchor_cache, AnchorCache};
+use voronoi_painter::cmyk::{write_cmyk_tiff, CmykProfile};
+use voronoi_painter::color::ColorSpace;
+use voronoi_painter::colorize::{
+    AnchorColorizer, CellColorizer, ColorizerRegistry, PaletteColorizer, PosterizeColorizer,
+    ToneColorizer,
+};
+use voronoi_painter::compose::{
+    compare, fill_background, overlay, preserve, Background, BlendMode, CompareLayout,
+};
+#[cfg(unix)]
+use voronoi_painter::daemon::serve_jobs;
+use voronoi_painter::diff::{compare_renders, difference_heatmap};
+use voronoi_painter::dxf::cells_to_dxf;
+use voronoi_painter::export::{cells_to_geojson, cells_to_html, describe_fill_patterns};
+use voronoi_painter::flow::{track_points, FlowOptions};
+use voronoi_painter::geometry::{
+    metric_from_name, Blended, Bounds, Distance, DistanceMetric, Point, Scaled,
+};
+use voronoi_painter::geotiff::GeoReference;
+use voronoi_painter::interrupt::{catch_interrupts, interrupted, InterruptObserver};
+use voronoi_painter::labels::{decode_label_map, encode_label_map, label_palette, LabelPalette};
+use voronoi_painter::locations::{parse_gpx, parse_location_csv, MapBounds, MapProjection};
+use voronoi_painter::lottie::{cells_to_lottie, Reveal};
+use voronoi_painter::manifest::{file_hash, Manifest};
+use voronoi_painter::mask::{MaskedSampler, ShapeMask};
+use voronoi_painter::metrics::{mean_squared_error, psnr_from_mse, structural_similarity};
+use voronoi_painter::nested::{render_nested, NestedColoring, NestedLevel};
+use voronoi_painter::noise::{paint_noise, worley_noise, WorleyFeature};
+use voronoi_painter::numbers::{label_cells, paint_by_numbers};
+use voronoi_painter::optimize::optimize_anchors;
+use voronoi_painter::orientation::{OrientationField, Oriented};
+use voronoi_painter::pages::{read_tiff_page, write_tiff_pages};
+use voronoi_painter::palette::{parse_hex_color, Palette};
+use voronoi_painter::paths::{simplify_ring, PathShaping};
+use voronoi_painter::pattern::FillPattern;
+use voronoi_painter::pdf::{
+    cells_to_pdf_content, cells_to_pdf_tile, parse_physical_length, parse_physical_size,
+    PdfDocument, MILLIMETRES_PER_INCH, POINTS_PER_INCH,
+};
+use voronoi_painter::preset::{find_preset, PRESETS};
+use voronoi_painter::profile::{embed_jpeg_profile, zlib_stored, OutputProfile};
+use voronoi_painter::projection::{
+    EdgeMode, EquirectangularSampler, MirroredSampler, Projection, TileableSampler,
+};
+use voronoi_painter::psd::decode_psd;
+use voronoi_painter::recipe::{Recipe, RecipeTable, RecipeValue};
+use voronoi_painter::refine::{
+    refine_high_variance_cells, refine_to_target_error, ErrorTarget, RefinementOptions,
+};
+use voronoi_painter::relax::{gradient_weighted_relaxation, RelaxationOptions};
+use voronoi_painter::relief::{Relief, DEFAULT_DEPTH};
+use voronoi_painter::render::{
+    assign_cells, assign_output_cells, catch_render_failure, color_cells, finish_cell_colors,
+    paint_voronoi, render_voronoi, render_voronoi_styles, render_voronoi_timed, scale_anchors,
+    set_auto_tune, set_best_effort, set_progress_format, set_worker_threads, Assignment, CellMap,
+    CellStyle, HigherOrder, Precision, ProgressFormat, RenderOptions, UNASSIGNED,
+};
+use voronoi_painter::sampling::{
+    sampler_from_name, AnchorSampler, BorderSeededSampler, JitteredGridSampler, PoissonDiskSampler,
+    VariablePoissonSampler, SAMPLER_NAMES,
+};
+use voronoi_painter::script::CellScript;
+use voronoi_painter::selftest;
+use voronoi_painter::sequence::FrameSequence;
+use voronoi_painter::server::serve;
+use voronoi_painter::snapshot::{SnapshotInterval, SnapshotObserver};
+use voronoi_painter::spritesheet::pack_sprite_sheet;
+use voronoi_painter::stats::{cell_statistics, cell_statistics_csv};
+use voronoi_painter::stream::{stream_cell_colors, stream_cells_png};
+use voronoi_painter::terminal::{write_preview, TerminalGraphics};
+use voronoi_painter::tiles::PageLayout;
+use voronoi_painter::timings::Timings;
+use voronoi_painter::treemap::{area_weighted_layout, color_shares, paint_power_diagram};
+use voronoi_painter::video::{probe, FrameReader, FrameWriter};
+use voronoi_painter::vision::ColorVision;
+use voronoi_painter::voronoi::{
+    cell_polygons, clip_cells_to_rings, polygon_area, polygon_centroid,
+};
+#[cfg(feature = "window")]
+use voronoi_painter::window::{edit_anchors, watch_render};
 
-#[derive(Clone)]
-struct Point {
-    x: f64,
-    y: f64,
+/// Where the anchors of `source` are cached: `--anchors`, or a file in
+/// `--cache-dir` named after the image, its size and the placement settings,
+/// so that another run with the same settings finds it.
+fn anchor_cache_path(
+    sub_matches: &ArgMatches,
+    source: &RgbaImage,
+) -> Result<Option<String>, String> {
+    let cache_dir = match sub_matches.value_of("cache-dir") {
+        None => return Ok(sub_matches.value_of("anchors").map(String::from)),
+        Some(cache_dir) => cache_dir,
+    };
+    fs::create_dir_all(cache_dir)
+        .map_err(|error| format!("Could not create cache directory {}: {}", cache_dir, error))?;
+
+    let (width, height) = source.dimensions();
+    let name = format!(
+        "{:016x}-{}x{}-{}px-{}{}-{}.anchors",
+        image_hash(source),
+        width,
+        height,
+        parse_minimum_distance(sub_matches, width, height)?,
+        required_value(sub_matches, "sampling")?,
+        sub_matches
+            .value_of("max-distance")
+            .map_or(String::new(), |maximum| format!("-max{}px", maximum)),
+        sub_matches
+            .value_of("seed")
+            .map_or(String::from("unseeded"), |seed| format!("seed{}", seed)),
+    );
+
+    Ok(Some(
+        Path::new(cache_dir)
+            .join(name)
+            .to_string_lossy()
+            .into_owned(),
+    ))
+}
+
+/// Reads the anchors cached at [`anchor_cache_path`], placing them with
+/// `sampler` over `source` and caching them there with the `spacing` they
+/// were placed at if there are none yet.
+fn load_or_generate_anchor_points(
+    sub_matches: &ArgMatches,
+    source: &RgbaImage,
+    sampler: &dyn AnchorSampler,
+    spacing: f64,
+    rng: &mut dyn RngCore,
+) -> Result<Vec<Point>, String> {
+    let (width, height) = source.dimensions();
+    let bounds = Bounds {
+        width: width as u64,
+        height: height as u64,
+    };
+    let anchors_cache_path =
+        match anchor_cache_path(sub_matches, source)? {
+            None if sub_matches.is_present("anchors-required") => return Err(String::from(
+                "`--anchors-required` needs `--anchors` or `--cache-dir` to read the anchors from",
+            )),
+            None => return sampler.try_sample(&bounds, rng),
+            Some(anchors_cache_path) => anchors_cache_path,
+        };
+    let anchors_cache_path = anchors_cache_path.as_str();
+
+    let source_hash = image_hash(source);
+    match read_anchor_cache(anchors_cache_path) {
+        Ok(cache) => {
+            check_anchor_cache(
+                sub_matches,
+                anchors_cache_path,
+                &cache,
+                (width, height),
+                source_hash,
+            )?;
+
+            Ok(
+                match rescaled_canvas(sub_matches, anchors_cache_path, &cache, (width, height))? {
+                    None => cache.points,
+                    Some((from_width, from_height)) => {
+                        let horizontal_scale = (width as f64) / (from_width as f64);
+                        let vertical_scale = (height as f64) / (from_height as f64);
+                        println!(
+                            "Rescaled the anchors from {}x{} to {}x{}",
+                            from_width, from_height, width, height
+                        );
+
+                        cache
+                            .points
+                            .into_iter()
+                            .map(|point| Point {
+                                x: point.x * horizontal_scale,
+                                y: point.y * vertical_scale,
+                            })
+                            .collect()
+                    }
+                },
+            )
+        }
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(format!(
+            "Could not read anchors {}: {}",
+            anchors_cache_path, error
+        )),
+        Err(_) if sub_matches.is_present("anchors-required") => Err(format!(
+            "Anchors {} do not exist, and `--anchors-required` does not place new ones",
+            anchors_cache_path
+        )),
+        Err(_) => {
+            let anchor_points = sampler.try_sample(&bounds, rng)?;
+            let cache = AnchorCache {
+                canvas: Some((width, height)),
+                spacing: Some(spacing),
+                source_hash: Some(source_hash),
+                points: anchor_points.clone(),
+                ..AnchorCache::default()
+            };
+            write_anchor_cache(&cache, anchors_cache_path).map_err(|error| {
+                format!("Could not write anchors {}: {}", anchors_cache_path, error)
+            })?;
+
+            Ok(anchor_points)
+        }
+    }
+}
+
+/// Warns when a cache was made for another image than one of this `canvas`
+/// size and [`image_hash`], or fails with `--strict-cache`. Caches rescaled
+/// to the canvas by `--anchors-rescale` are for another image by design.
+fn check_anchor_cache(
+    sub_matches: &ArgMatches,
+    anchors_path: &str,
+    cache: &AnchorCache,
+    canvas: (u32, u32),
+    source_hash: u64,
+) -> Result<(), String> {
+    if rescaled_canvas(sub_matches, anchors_path, cache, canvas)?.is_some() {
+        return Ok(());
+    }
+
+    match cache.mismatch(canvas, source_hash) {
+        None => Ok(()),
+        Some(reason) if sub_matches.is_present("strict-cache") => {
+            Err(format!("Anchors {} {}", anchors_path, reason))
+        }
+        Some(reason) => {
+            eprintln!(
+                "Warning: anchors {} {}, so the cells may not follow the input",
+                anchors_path, reason
+            );
+
+            Ok(())
+        }
+    }
+}
+
+/// The size of the canvas the anchors of `cache` were placed on, when
+/// `--anchors-rescale` stretches them onto a `canvas` of another size.
+fn rescaled_canvas(
+    sub_matches: &ArgMatches,
+    anchors_path: &str,
+    cache: &AnchorCache,
+    canvas: (u32, u32),
+) -> Result<Option<(u32, u32)>, String> {
+    if !sub_matches.is_present("anchors-rescale") {
+        return Ok(None);
+    }
+
+    match cache.canvas {
+        None => Err(format!(
+            "Anchors {} do not record the size of the image they were placed on, so `--anchors-rescale` cannot scale them",
+            anchors_path
+        )),
+        Some((0, _)) | Some((_, 0)) => Err(format!(
+            "Anchors {} were placed on an empty image, so `--anchors-rescale` cannot scale them",
+            anchors_path
+        )),
+        Some(from) if from != canvas => Ok(Some(from)),
+        Some(_) => Ok(None),
+    }
+}
+
+/// `minimum_distance` stretched as much as `--anchors-rescale` stretches the
+/// `--anchors` cache onto a `canvas` of another size, so cells are looked
+/// for as far away as the rescaled anchors are apart.
+fn rescaled_minimum_distance(
+    sub_matches: &ArgMatches,
+    minimum_distance: u32,
+    canvas: (u32, u32),
+) -> Result<u32, String> {
+    let anchors_path = match sub_matches.value_of("anchors") {
+        Some(anchors_path) if sub_matches.is_present("anchors-rescale") => anchors_path,
+        _ => return Ok(minimum_distance),
+    };
+    let cache = match read_anchor_cache(anchors_path) {
+        Ok(cache) => cache,
+        // Anchors placed anew fit the canvas, and unreadable ones fail
+        // where they are loaded.
+        Err(_) => return Ok(minimum_distance),
+    };
+
+    Ok(
+        match rescaled_canvas(sub_matches, anchors_path, &cache, canvas)? {
+            None => minimum_distance,
+            Some((from_width, from_height)) => {
+                let scale = ((canvas.0 as f64) / (from_width as f64))
+                    .max((canvas.1 as f64) / (from_height as f64));
+                (((minimum_distance as f64) * scale).ceil() as u32).max(1)
+            }
+        },
+    )
+}
+
+/// Settings passed to the encoder of the output format instead of its
+/// defaults.
+struct EncoderOptions {
+    jpeg_quality: u8,
+    png_compression: CompressionType,
+    /// Space the output is converted to and tagged with, if not left as
+    /// untagged sRGB.
+    profile: Option<OutputProfile>,
+    /// Where the output is on a map, written as GeoTIFF tags or as a world
+    /// file next to it.
+    georeference: Option<GeoReference>,
+}
+
+fn parse_encoder_options(sub_matches: &ArgMatches) -> Result<EncoderOptions, String> {
+    let jpeg_quality = match required_value(sub_matches, "jpeg-quality")?.parse::<u8>() {
+        Ok(quality) if (1..=100).contains(&quality) => quality,
+        _ => {
+            return Err(String::from(
+                "`--jpeg-quality` must be a whole number from 1 to 100",
+            ))
+        }
+    };
+    let png_compression = match required_value(sub_matches, "png-compression")? {
+        "fast" => CompressionType::Fast,
+        "best" => CompressionType::Best,
+        _ => CompressionType::Default,
+    };
+    let profile = match sub_matches.value_of("output-profile") {
+        None => None,
+        Some(name) => Some(OutputProfile::from_name(name).ok_or(format!(
+            "Unknown output profile `{}`, expected one of: srgb, display-p3",
+            name
+        ))?),
+    };
+
+    Ok(EncoderOptions {
+        jpeg_quality,
+        png_compression,
+        profile,
+        georeference: None,
+    })
+}
+
+fn write_image(image: &RgbaImage, path: &Path, encoder: &EncoderOptions) -> ImageResult<()> {
+    if let Some(georeference) = &encoder.georeference {
+        return write_georeferenced_image(image, path, encoder, georeference);
+    }
+    if let Some(profile) = encoder.profile {
+        return write_profiled_image(image, path, encoder, profile);
+    }
+
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Jpeg) => {
+            JpegEncoder::new_with_quality(BufWriter::new(File::create(path)?), encoder.jpeg_quality)
+                .encode_image(image)
+        }
+        Ok(ImageFormat::Png) => PngEncoder::new_with_quality(
+            BufWriter::new(File::create(path)?),
+            encoder.png_compression,
+            FilterType::Adaptive,
+        )
+        .write_image(image, image.width(), image.height(), ColorType::Rgba8),
+        _ => image.save(path),
+    }
+}
+
+/// Writes `image` placed on the map by `georeference`: TIFF outputs carry it
+/// as GeoTIFF tags, others get a world file next to them.
+fn write_georeferenced_image(
+    image: &RgbaImage,
+    path: &Path,
+    encoder: &EncoderOptions,
+    georeference: &GeoReference,
+) -> ImageResult<()> {
+    if ImageFormat::from_path(path).ok() == Some(ImageFormat::Tiff) {
+        let mut converted = image.clone();
+        if let Some(profile) = encoder.profile {
+            profile.convert(&mut converted);
+        }
+        let icc_profile = encoder.profile.map(OutputProfile::icc_profile);
+        return write_tiff_pages(
+            &[&converted],
+            icc_profile.as_deref(),
+            Some(georeference),
+            BufWriter::new(File::create(path)?),
+        )
+        .map_err(|error| ImageError::IoError(io::Error::other(error)));
+    }
+
+    write_image(
+        image,
+        path,
+        &EncoderOptions {
+            jpeg_quality: encoder.jpeg_quality,
+            png_compression: encoder.png_compression,
+            profile: encoder.profile,
+            georeference: None,
+        },
+    )?;
+    match georeference.world_file() {
+        None => Ok(()),
+        Some(world_file) => {
+            fs::write(world_file_path(path), world_file).map_err(ImageError::IoError)
+        }
+    }
+}
+
+/// The world file of the image at `path`, named after its extension as GIS
+/// software looks for it: `.pgw` for `.png`, `.jgw` for `.jpg`.
+fn world_file_path(path: &Path) -> PathBuf {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut characters = extension.chars();
+    let world_extension = match (characters.next(), characters.last()) {
+        (Some(first), Some(last)) => format!("{}{}w", first, last),
+        _ => format!("{}w", extension),
+    };
+
+    path.with_extension(world_extension)
+}
+
+/// Writes `image` converted to `profile` and tagged with its ICC profile,
+/// which only PNG, JPEG and TIFF outputs can carry.
+fn write_profiled_image(
+    image: &RgbaImage,
+    path: &Path,
+    encoder: &EncoderOptions,
+    profile: OutputProfile,
+) -> ImageResult<()> {
+    let mut converted = image.clone();
+    profile.convert(&mut converted);
+    let icc_profile = profile.icc_profile();
+
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Png) => {
+            let mut png_encoder = png::Encoder::new(
+                BufWriter::new(File::create(path)?),
+                converted.width(),
+                converted.height(),
+            );
+            png_encoder.set_color(png::ColorType::Rgba);
+            png_encoder.set_depth(png::BitDepth::Eight);
+            png_encoder.set_compression(apng_compression(encoder));
+            let mut writer = png_encoder.write_header().map_err(png_error)?;
+            let mut chunk = b"ICC profile\0\0".to_vec();
+            chunk.extend(zlib_stored(&icc_profile));
+            writer
+                .write_chunk(png::chunk::iCCP, &chunk)
+                .and_then(|_| writer.write_image_data(converted.as_raw()))
+                .map_err(png_error)
+        }
+        Ok(ImageFormat::Jpeg) => {
+            let mut jpeg = Vec::new();
+            JpegEncoder::new_with_quality(&mut jpeg, encoder.jpeg_quality)
+                .encode_image(&converted)?;
+            fs::write(path, embed_jpeg_profile(&jpeg, &icc_profile))?;
+            Ok(())
+        }
+        Ok(ImageFormat::Tiff) => write_tiff_pages(
+            &[&converted],
+            Some(&icc_profile),
+            None,
+            BufWriter::new(File::create(path)?),
+        )
+        .map_err(|error| ImageError::IoError(io::Error::other(error))),
+        _ => Err(ImageError::IoError(io::Error::other(
+            "`--output-profile` can only tag PNG, JPEG and TIFF outputs",
+        ))),
+    }
+}
+
+fn png_error(error: png::EncodingError) -> ImageError {
+    ImageError::IoError(io::Error::other(error.to_string()))
+}
+
+fn apng_compression(encoder: &EncoderOptions) -> png::Compression {
+    match encoder.png_compression {
+        CompressionType::Fast => png::Compression::Fast,
+        CompressionType::Best => png::Compression::Best,
+        CompressionType::Huffman => png::Compression::Huffman,
+        CompressionType::Rle => png::Compression::Rle,
+        _ => png::Compression::Default,
+    }
+}
+
+/// What an animation written to `output_path` is saved as.
+#[derive(Clone, Copy, PartialEq)]
+enum AnimationFormat {
+    Gif,
+    /// Animated PNG, for `.png` and `.apng` paths.
+    Apng,
+    /// A directory of numbered PNG frames.
+    Frames,
+}
+
+fn animation_format(output_path: &str) -> AnimationFormat {
+    let extension = Path::new(output_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("gif") => AnimationFormat::Gif,
+        Some("png") | Some("apng") => AnimationFormat::Apng,
+        _ => AnimationFormat::Frames,
+    }
+}
+
+/// The `COLUMNSxROWS` grid of `--spritesheet`, if one was asked for.
+fn parse_sprite_sheet(sub_matches: &ArgMatches) -> Result<Option<(u32, u32)>, String> {
+    match sub_matches.value_of("spritesheet") {
+        None => Ok(None),
+        Some(grid) => parse_size(grid).map(Some).ok_or_else(|| {
+            String::from("`--spritesheet` must be a number of columns and rows, like `4x3`")
+        }),
+    }
+}
+
+/// Writes `frames` packed into a `grid` sprite sheet to `output_path`, and
+/// its descriptor to a JSON file next to it.
+fn write_sprite_sheet(
+    frames: &[(RgbaImage, Delay)],
+    grid: (u32, u32),
+    output_path: &str,
+    encoder: &EncoderOptions,
+) -> Result<(), String> {
+    let path = Path::new(output_path);
+    let image_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (sheet, descriptor) = pack_sprite_sheet(frames, grid, &image_name)?;
+    write_image(&sheet, path, encoder)
+        .map_err(|error| format!("Could not save sprite sheet {}: {}", output_path, error))?;
+
+    let descriptor_path = path.with_extension("json");
+    serde_json::to_vec_pretty(&descriptor)
+        .map_err(io::Error::other)
+        .and_then(|contents| fs::write(&descriptor_path, contents))
+        .map_err(|error| {
+            format!(
+                "Could not save sprite sheet descriptor {}: {}",
+                descriptor_path.display(),
+                error
+            )
+        })?;
+    println!(
+        "Packed {} frames into {} and described them in {}",
+        descriptor.frame_count,
+        output_path,
+        descriptor_path.display()
+    );
+
+    Ok(())
+}
+
+fn write_animation(
+    frames: Vec<RgbaImage>,
+    fps: u32,
+    output_path: &str,
+    encoder: &EncoderOptions,
+) -> ImageResult<()> {
+    let format = animation_format(output_path);
+    if encoder.profile.is_some() && format != AnimationFormat::Frames {
+        return Err(ImageError::IoError(io::Error::other(
+            "`--output-profile` cannot tag GIF and animated PNG outputs",
+        )));
+    }
+
+    if format == AnimationFormat::Gif {
+        encode_gif(frames, fps, File::create(output_path)?)?;
+    } else if format == AnimationFormat::Apng {
+        let delay = Delay::from_numer_denom_ms(1000, fps);
+        let frames: Vec<(RgbaImage, Delay)> =
+            frames.into_iter().map(|frame| (frame, delay)).collect();
+        encode_apng(
+            &frames,
+            apng_compression(encoder),
+            BufWriter::new(File::create(output_path)?),
+        )?;
+    } else {
+        fs::create_dir_all(output_path)?;
+        for (index, frame) in frames.iter().enumerate() {
+            write_image(
+                frame,
+                &Path::new(output_path).join(format!("frame_{:04}.png", index)),
+                encoder,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Guards `output_path` against being overwritten: without `--force` an
+/// existing path is an error, or with `--suffix` the first free `-N` name.
+fn resolve_output_path(sub_matches: &ArgMatches, output_path: &str) -> Result<String, String> {
+    let path = Path::new(output_path);
+    if !path.exists() || sub_matches.is_present("force") {
+        return Ok(output_path.to_string());
+    }
+    if !sub_matches.is_present("suffix") {
+        return Err(format!(
+            "Output {} already exists, use `--force` to overwrite it or `--suffix` to write next to it",
+            output_path
+        ));
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy());
+    (1..)
+        .map(|counter| {
+            let name = match &extension {
+                None => format!("{}-{}", stem, counter),
+                Some(extension) => format!("{}-{}.{}", stem, counter, extension),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .map(|candidate| candidate.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("Could not find a free name next to {}", output_path))
+}
+
+fn save_output_image(
+    output_image: &RgbaImage,
+    output_path: &str,
+    sub_matches: &ArgMatches,
+) -> Result<(), String> {
+    save_encoded_output_image(
+        output_image,
+        output_path,
+        sub_matches,
+        &parse_encoder_options(sub_matches)?,
+    )
+}
+
+/// Like [`save_output_image`], with `encoder` in place of the encoder
+/// options of `sub_matches`.
+fn save_encoded_output_image(
+    output_image: &RgbaImage,
+    output_path: &str,
+    sub_matches: &ArgMatches,
+    encoder: &EncoderOptions,
+) -> Result<(), String> {
+    write_image(output_image, Path::new(output_path), encoder)
+        .map_err(|error| format!("Could not save output image {}: {}", output_path, error))?;
+
+    match sub_matches.value_of("show") {
+        None => Ok(()),
+        Some(protocol) => {
+            let graphics = match protocol {
+                "auto" => TerminalGraphics::detect(),
+                protocol => TerminalGraphics::from_name(protocol).ok_or(format!(
+                    "Unknown terminal graphics `{}`, expected one of: auto, kitty, iterm, sixel",
+                    protocol
+                ))?,
+            };
+            write_preview(output_image, graphics, &mut io::stdout().lock())
+                .map_err(|error| format!("Could not show preview: {}", error))
+        }
+    }
+}
+
+fn parse_focus(value: &str) -> Option<Point> {
+    let (x, y) = value.split_once(',')?;
+    let x = x.trim().parse::<f64>().ok()?;
+    let y = y.trim().parse::<f64>().ok()?;
+
+    if (0f64..=1f64).contains(&x) && (0f64..=1f64).contains(&y) {
+        Some(Point { x, y })
+    } else {
+        None
+    }
+}
+
+/// Reads the animation settings for an input of `image_size`; a random
+/// `--reveal-from` origin and the `--fade-in` timings are drawn from `rng`.
+fn parse_animation(
+    sub_matches: &ArgMatches,
+    image_size: (u32, u32),
+    rng: &mut dyn RngCore,
+) -> Result<Animation, String> {
+    let duration = match sub_matches.value_of("duration").map(str::parse::<f64>) {
+        Some(Ok(duration)) if duration > 0f64 => duration,
+        _ => {
+            return Err(String::from(
+                "`--duration` must be a positive number of seconds",
+            ))
+        }
+    };
+    let fps = match sub_matches.value_of("fps").map(str::parse::<u32>) {
+        Some(Ok(fps)) if fps > 0 => fps,
+        _ => return Err(String::from("`--fps` must be a positive integer")),
+    };
+    let zoom = match sub_matches.value_of("zoom").map(str::parse::<f64>) {
+        Some(Ok(zoom)) if zoom >= 1f64 => zoom,
+        _ => {
+            return Err(String::from(
+                "`--zoom` must be a number greater than or equal to 1",
+            ))
+        }
+    };
+    let focus = match sub_matches.value_of("focus").map(parse_focus) {
+        Some(Some(focus)) => focus,
+        _ => {
+            return Err(String::from(
+                "`--focus` must be two fractions between 0 and 1, like `0.5,0.5`",
+            ))
+        }
+    };
+    let retessellate_every = match sub_matches.value_of("retessellate").map(str::parse::<u32>) {
+        None => None,
+        Some(Ok(interval)) if interval > 0 => Some(interval),
+        Some(_) => {
+            return Err(String::from(
+                "`--retessellate` must be a positive number of frames",
+            ))
+        }
+    };
+
+    let boil = match sub_matches.value_of("boil").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(boil)) if boil > 0f64 => Some(boil),
+        Some(_) => return Err(String::from("`--boil` must be a positive number of pixels")),
+    };
+
+    let reveal = match (
+        sub_matches.value_of("reveal-from"),
+        sub_matches.value_of("fade-in"),
+    ) {
+        (None, None) => None,
+        (Some(origin), None) => {
+            let (width, height) = image_size;
+            let origin = match origin {
+                "center" => Point { x: 0.5, y: 0.5 },
+                "random" => Point {
+                    x: rng.gen::<f64>(),
+                    y: rng.gen::<f64>(),
+                },
+                origin => parse_focus(origin).ok_or_else(|| {
+                    String::from(
+                        "`--reveal-from` must be center, random or two fractions between 0 and 1, like `0.5,0.5`",
+                    )
+                })?,
+            };
+            Some(CellReveal::Growth {
+                origin: Point {
+                    x: origin.x * (width as f64),
+                    y: origin.y * (height as f64),
+                },
+            })
+        }
+        (None, Some(length)) => match length.parse::<f64>() {
+            Ok(length) if (length > 0f64) && (length <= 1f64) => Some(CellReveal::FadeIn {
+                length,
+                seed: rng.next_u64(),
+            }),
+            _ => {
+                return Err(String::from(
+                    "`--fade-in` must be a share of the animation above 0 and up to 1",
+                ))
+            }
+        },
+        (Some(_), Some(_)) => {
+            return Err(String::from(
+                "`--reveal-from` and `--fade-in` cannot be combined",
+            ))
+        }
+    };
+    let easing = required_value(sub_matches, "easing")?;
+    let easing = Easing::from_name(easing).ok_or(format!(
+        "Unknown easing `{}`, expected one of: linear, ease-in, ease-out, ease-in-out",
+        easing
+    ))?;
+
+    Ok(Animation {
+        duration,
+        fps,
+        zoom,
+        focus,
+        retessellate_every,
+        boil,
+        reveal,
+        easing,
+        background: parse_background(sub_matches)?,
+    })
+}
+
+fn required_value<'a>(sub_matches: &'a ArgMatches, name: &str) -> Result<&'a str, String> {
+    sub_matches.value_of(name).ok_or(format!(
+        "Value for `--{}` not provided, please use the `--{} <VALUE>` arg",
+        name, name
+    ))
+}
+
+#[cfg(feature = "net")]
+fn open_input_image(input_image_path: &str) -> Result<RgbaImage, String> {
+    if !voronoi_painter::net::is_url(input_image_path) {
+        return open_local_image(input_image_path);
+    }
+
+    let bytes = voronoi_painter::net::fetch(input_image_path).map_err(|error| {
+        format!(
+            "Could not download input image {}: {}",
+            input_image_path, error
+        )
+    })?;
+    image::load_from_memory(&bytes)
+        .map(|input_image| input_image.to_rgba8())
+        .map_err(|error| format!("Could not open input image {}: {}", input_image_path, error))
+}
+
+#[cfg(not(feature = "net"))]
+fn open_input_image(input_image_path: &str) -> Result<RgbaImage, String> {
+    if input_image_path.starts_with("http://") || input_image_path.starts_with("https://") {
+        return Err(format!(
+            "Could not open input image {}: downloading needs a build with the `net` feature",
+            input_image_path
+        ));
+    }
+
+    open_local_image(input_image_path)
+}
+
+/// Extensions of the camera RAW files opened with [`open_raw_image`].
+const RAW_EXTENSIONS: &[&str] = &["arw", "cr2", "dng", "nef"];
+
+fn is_raw_path(input_image_path: &str) -> bool {
+    Path::new(input_image_path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            RAW_EXTENSIONS
+                .iter()
+                .any(|raw| extension.eq_ignore_ascii_case(raw))
+        })
+}
+
+#[cfg(feature = "raw")]
+fn open_raw_image(input_image_path: &str) -> Result<RgbaImage, String> {
+    fs::read(input_image_path)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| voronoi_painter::raw::decode_raw(&bytes))
+        .map_err(|error| format!("Could not open input image {}: {}", input_image_path, error))
+}
+
+#[cfg(not(feature = "raw"))]
+fn open_raw_image(input_image_path: &str) -> Result<RgbaImage, String> {
+    Err(format!(
+        "Could not open input image {}: camera RAW files need a build with the `raw` feature",
+        input_image_path
+    ))
+}
+
+fn is_psd_path(input_image_path: &str) -> bool {
+    Path::new(input_image_path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("psd") || extension.eq_ignore_ascii_case("psb")
+        })
+}
+
+/// Opens a Photoshop document flattened, or only its layer named `layer`.
+fn open_psd_image(input_image_path: &str, layer: Option<&str>) -> Result<RgbaImage, String> {
+    fs::read(input_image_path)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| decode_psd(&bytes, layer))
+        .map_err(|error| format!("Could not open input image {}: {}", input_image_path, error))
+}
+
+fn open_local_image(input_image_path: &str) -> Result<RgbaImage, String> {
+    if is_raw_path(input_image_path) {
+        return open_raw_image(input_image_path);
+    }
+    if is_psd_path(input_image_path) {
+        return open_psd_image(input_image_path, None);
+    }
+
+    image::open(input_image_path)
+        .map(|input_image| input_image.to_rgba8())
+        .map_err(|error| format!("Could not open input image {}: {}", input_image_path, error))
+}
+
+/// Opens the input of a painting, or its `--page` if it is a multi-page
+/// TIFF, or its `--psd-layer` if it is a Photoshop document.
+fn open_painting_input(
+    sub_matches: &ArgMatches,
+    input_image_path: &str,
+) -> Result<RgbaImage, String> {
+    if let Some(layer) = sub_matches.value_of("psd-layer") {
+        if !is_psd_path(input_image_path) {
+            return Err(format!(
+                "`--psd-layer` needs a PSD or PSB input, not {}",
+                input_image_path
+            ));
+        }
+        return open_psd_image(input_image_path, Some(layer));
+    }
+    let page = match sub_matches.value_of("page").map(str::parse::<usize>) {
+        None => return open_input_image(input_image_path),
+        Some(Ok(page)) if page >= 1 => page,
+        _ => {
+            return Err(String::from(
+                "`--page` must be a whole number from 1, the first page",
+            ))
+        }
+    };
+    if ImageFormat::from_path(input_image_path).ok() != Some(ImageFormat::Tiff) {
+        return Err(format!(
+            "`--page` needs a TIFF input, not {}",
+            input_image_path
+        ));
+    }
+
+    File::open(input_image_path)
+        .map_err(|error| error.to_string())
+        .and_then(|file| read_tiff_page(io::BufReader::new(file), page - 1))
+        .map_err(|error| format!("Could not open input image {}: {}", input_image_path, error))
+}
+
+/// The further `--input` exposures after the first, which only the
+/// `painting` sub-command takes; `watch` paints one dropped image at a time.
+fn burst_paths(sub_matches: &ArgMatches) -> Vec<&str> {
+    match sub_matches.is_valid_arg("input") {
+        true => sub_matches
+            .values_of("input")
+            .map_or(Vec::new(), |paths| paths.skip(1).collect()),
+        false => Vec::new(),
+    }
+}
+
+/// `input_image` combined with the further exposures of [`burst_paths`] as
+/// `--burst` asks, or as it is without them.
+fn combine_burst(sub_matches: &ArgMatches, input_image: RgbaImage) -> Result<RgbaImage, String> {
+    let paths = burst_paths(sub_matches);
+    if paths.is_empty() {
+        return Ok(input_image);
+    }
+    let name = required_value(sub_matches, "burst")?;
+    let mode = BurstMode::from_name(name).ok_or(format!(
+        "Unknown burst mode `{}`, expected one of: mean, fuse",
+        name
+    ))?;
+
+    let mut exposures = vec![input_image];
+    for path in paths {
+        let exposure = open_painting_input(sub_matches, path)?;
+        if exposure.dimensions() != exposures[0].dimensions() {
+            return Err(format!(
+                "Exposure {} is {}x{}, not {}x{} like the first input, so they cannot be aligned",
+                path,
+                exposure.width(),
+                exposure.height(),
+                exposures[0].width(),
+                exposures[0].height()
+            ));
+        }
+        exposures.push(exposure);
+    }
+    println!("Combined {} exposures", exposures.len());
+
+    Ok(combine_exposures(&exposures, mode))
+}
+
+/// Anchors at the locations of the GPX or CSV file at `points_path`, on
+/// the `width` by `height` input map spanning `--map-bounds`.
+fn load_location_points(
+    sub_matches: &ArgMatches,
+    points_path: &str,
+    width: u32,
+    height: u32,
+) -> Result<Vec<Point>, String> {
+    let bounds = match sub_matches.value_of("map-bounds") {
+        None => {
+            return Err(String::from(
+                "`--points` needs the `--map-bounds` of the input map",
+            ))
+        }
+        Some(bounds) => MapBounds::parse(bounds).ok_or(String::from(
+            "`--map-bounds` must be the west, south, east and north edges of the map in degrees, like `-10.5,35,30,60`",
+        ))?,
+    };
+    let projection_name = required_value(sub_matches, "map-projection")?;
+    let projection = MapProjection::from_name(projection_name).ok_or(format!(
+        "Unknown map projection `{}`, expected one of: equirectangular, mercator",
+        projection_name
+    ))?;
+
+    let text = fs::read_to_string(points_path)
+        .map_err(|error| format!("Could not read points {}: {}", points_path, error))?;
+    let is_gpx = Path::new(points_path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gpx"));
+    let locations = match is_gpx {
+        true => parse_gpx(&text),
+        false => parse_location_csv(&text),
+    }
+    .map_err(|error| format!("Could not read points {}: {}", points_path, error))?;
+
+    let points: Vec<Point> = locations
+        .iter()
+        .filter_map(|location| bounds.project(location, projection, width, height))
+        .collect();
+    if points.is_empty() {
+        return Err(format!(
+            "None of the {} locations of {} are on the map",
+            locations.len(),
+            points_path
+        ));
+    }
+    println!(
+        "Placed {} anchor points at the locations of {}",
+        points.len(),
+        points_path
+    );
+    if points.len() < locations.len() {
+        println!(
+            "Skipped {} locations off the map",
+            locations.len() - points.len()
+        );
+    }
+
+    Ok(points)
+}
+
+/// The GeoTIFF tags of the input page being painted, if it is a TIFF that
+/// has them.
+fn read_georeference(
+    sub_matches: &ArgMatches,
+    input_image_path: &str,
+) -> Result<Option<GeoReference>, String> {
+    if ImageFormat::from_path(input_image_path).ok() != Some(ImageFormat::Tiff) {
+        return Ok(None);
+    }
+    let page = match sub_matches.value_of("page").map(str::parse::<usize>) {
+        Some(Ok(page)) => page.saturating_sub(1),
+        _ => 0,
+    };
+
+    File::open(input_image_path)
+        .map_err(|error| error.to_string())
+        .and_then(|file| GeoReference::read(io::BufReader::new(file), page))
+        .map_err(|error| {
+            format!(
+                "Could not read the georeferencing of {}: {}",
+                input_image_path, error
+            )
+        })
+}
+
+fn parse_region(value: &str) -> Result<(u32, u32, u32, u32), String> {
+    let parts = value
+        .split(',')
+        .map(|part| part.trim().parse::<u32>().ok())
+        .collect::<Option<Vec<u32>>>();
+
+    match parts.as_deref() {
+        Some(&[x, y, width, height]) if (width > 0) && (height > 0) => Ok((x, y, width, height)),
+        Some(&[_, _, _, _]) => Err(String::from(
+            "The width and height of `--region` must be positive",
+        )),
+        _ => Err(String::from(
+            "`--region` must be four non-negative integers, like `x,y,width,height`",
+        )),
+    }
+}
+
+fn parse_adjustments(sub_matches: &ArgMatches) -> Result<Adjustments, String> {
+    let number = |name: &str, is_valid: fn(f64) -> bool, expected: &str| {
+        required_value(sub_matches, name)?
+            .parse::<f64>()
+            .ok()
+            .filter(|value| is_valid(*value))
+            .ok_or(format!("`--{}` must be {}", name, expected))
+    };
+
+    Ok(Adjustments {
+        brightness: number(
+            "brightness",
+            |value| (-1f64..=1f64).contains(&value),
+            "a number from -1 to 1",
+        )?,
+        contrast: number("contrast", |value| value >= 0f64, "a non-negative number")?,
+        saturation: number("saturation", |value| value >= 0f64, "a non-negative number")?,
+        gamma: number("gamma", |value| value > 0f64, "a positive number")?,
+    })
+}
+
+/// What `--auto-levels` or `--equalize` does to the copy of the input the
+/// cell colors are sampled from, if either is given.
+fn parse_color_levels(sub_matches: &ArgMatches) -> Option<fn(&mut RgbaImage)> {
+    if sub_matches.is_present("auto-levels") {
+        Some(auto_levels)
+    } else if sub_matches.is_present("equalize") {
+        Some(equalize)
+    } else {
+        None
+    }
+}
+
+fn parse_pre_blur(sub_matches: &ArgMatches) -> Result<Option<f32>, String> {
+    match sub_matches.value_of("pre-blur").map(str::parse::<f32>) {
+        None => Ok(None),
+        Some(Ok(sigma)) if sigma > 0f32 => Ok(Some(sigma)),
+        Some(_) => Err(String::from("`--pre-blur` must be a positive sigma")),
+    }
+}
+
+fn crop_to_region(input_image: RgbaImage, sub_matches: &ArgMatches) -> Result<RgbaImage, String> {
+    let region = match sub_matches.value_of("region") {
+        None => return Ok(input_image),
+        Some(region) => parse_region(region)?,
+    };
+
+    let (x, y, width, height) = region;
+    let (image_width, image_height) = input_image.dimensions();
+    if ((x as u64) + (width as u64) > (image_width as u64))
+        || ((y as u64) + (height as u64) > (image_height as u64))
+    {
+        return Err(format!(
+            "`--region` {},{},{},{} does not fit inside the {}x{} input image",
+            x, y, width, height, image_width, image_height
+        ));
+    }
+
+    Ok(imageops::crop_imm(&input_image, x, y, width, height).to_image())
+}
+
+/// The `--color-source` image scaled to the `width` by `height` input and
+/// cropped to the same `--region`, so every anchor lands on the matching spot.
+fn load_color_source(
+    sub_matches: &ArgMatches,
+    width: u32,
+    height: u32,
+) -> Result<Option<RgbaImage>, String> {
+    let color_path = match sub_matches.value_of("color-source") {
+        None => return Ok(None),
+        Some(color_path) => color_path,
+    };
+
+    let color_source = open_input_image(color_path)?;
+    let color_source = if color_source.dimensions() == (width, height) {
+        color_source
+    } else {
+        imageops::resize(&color_source, width, height, imageops::FilterType::Triangle)
+    };
+    crop_to_region(color_source, sub_matches).map(Some)
+}
+
+fn parse_color_space(sub_matches: &ArgMatches) -> Result<ColorSpace, String> {
+    let name = required_value(sub_matches, "color-space")?;
+    ColorSpace::from_name(name).ok_or(format!(
+        "Unknown color space `{}`, expected one of: srgb, linear, oklab, cielab",
+        name
+    ))
+}
+
+/// The space `--match-space` compares colors in, if it is given.
+fn parse_match_space(sub_matches: &ArgMatches) -> Result<Option<ColorSpace>, String> {
+    match sub_matches.value_of("match-space") {
+        None => Ok(None),
+        Some("rgb") => Ok(Some(ColorSpace::Srgb)),
+        Some("lab") => Ok(Some(ColorSpace::Cielab)),
+        Some("oklab") => Ok(Some(ColorSpace::Oklab)),
+        Some(name) => Err(format!(
+            "Unknown match space `{}`, expected one of: rgb, lab, oklab",
+            name
+        )),
+    }
+}
+
+fn load_cell_palette(sub_matches: &ArgMatches) -> Result<Option<Palette>, String> {
+    match sub_matches.value_of("palette") {
+        None => Ok(None),
+        Some(palette_path) => Palette::load(palette_path)
+            .map(Some)
+            .map_err(|error| format!("Could not read palette {}: {}", palette_path, error)),
+    }
+}
+
+/// Parses `--duotone dark=#112233,light=#ffeecc`, or `--tritone` with a
+/// `mid` tone between them, into a ramp from the dark to the light tone.
+fn parse_tones(sub_matches: &ArgMatches) -> Result<Option<Palette>, String> {
+    let (flag, value, names) = match (
+        sub_matches.value_of("duotone"),
+        sub_matches.value_of("tritone"),
+    ) {
+        (None, None) => return Ok(None),
+        (Some(value), None) => ("duotone", value, &["dark", "light"][..]),
+        (None, Some(value)) => ("tritone", value, &["dark", "mid", "light"][..]),
+        (Some(_), Some(_)) => {
+            return Err(String::from(
+                "`--duotone` and `--tritone` cannot be combined",
+            ))
+        }
+    };
+    let usage = format!(
+        "`--{}` must name every tone as a hex color, like `{}`",
+        flag,
+        match flag {
+            "duotone" => "dark=#112233,light=#ffeecc",
+            _ => "dark=#112233,mid=#cc5544,light=#ffeecc",
+        }
+    );
+
+    let mut tones = vec![None; names.len()];
+    for entry in value.split(',') {
+        let (name, color) = entry.split_once('=').ok_or_else(|| usage.clone())?;
+        let slot = names
+            .iter()
+            .position(|tone| *tone == name.trim())
+            .ok_or_else(|| usage.clone())?;
+        tones[slot] = Some(parse_hex_color(color).ok_or_else(|| usage.clone())?);
+    }
+    let colors = tones
+        .into_iter()
+        .collect::<Option<Vec<Rgba<u8>>>>()
+        .ok_or(usage)?;
+
+    Ok(Some(Palette { colors }))
+}
+
+fn parse_posterize(sub_matches: &ArgMatches) -> Result<Option<u32>, String> {
+    match sub_matches.value_of("posterize").map(str::parse::<u32>) {
+        None => Ok(None),
+        Some(Ok(levels)) if (2..=256).contains(&levels) => Ok(Some(levels)),
+        Some(_) => Err(String::from(
+            "`--posterize` must be a number of levels from 2 to 256",
+        )),
+    }
+}
+
+fn parse_random_palette(
+    sub_matches: &ArgMatches,
+    rng: &mut dyn RngCore,
+) -> Result<Option<Palette>, String> {
+    let count = match sub_matches
+        .value_of("random-palette")
+        .map(str::parse::<usize>)
+    {
+        None => return Ok(None),
+        Some(Ok(count)) if count > 0 => count,
+        Some(_) => {
+            return Err(String::from(
+                "`--random-palette` must be a positive number of colors",
+            ))
+        }
+    };
+    let hue_range = required_value(sub_matches, "hue-range")?
+        .split_once("..")
+        .and_then(|(from, to)| {
+            Some((
+                from.trim().parse::<f64>().ok()?,
+                to.trim().parse::<f64>().ok()?,
+            ))
+        })
+        .filter(|(from, to)| (0f64..=360f64).contains(from) && (0f64..=360f64).contains(to))
+        .ok_or_else(|| {
+            String::from("`--hue-range` must be two hues from 0 to 360 degrees, like `180..300`")
+        })?;
+
+    Ok(Some(Palette::harmonious(count, hue_range, rng)))
+}
+
+fn find_colorizer<'a>(
+    registry: &'a ColorizerRegistry,
+    sub_matches: &ArgMatches,
+) -> Result<&'a dyn CellColorizer, String> {
+    let color_mode = required_value(sub_matches, "color-mode")?;
+    registry.get(color_mode).ok_or(format!(
+        "Unknown color mode `{}`, expected one of: {}",
+        color_mode,
+        registry.names().join(", ")
+    ))
+}
+
+fn parse_aspect(sub_matches: &ArgMatches) -> Result<Option<(f64, f64)>, String> {
+    let aspect = match sub_matches.value_of("aspect") {
+        None => return Ok(None),
+        Some(aspect) => aspect,
+    };
+    let scales = aspect
+        .split_once(':')
+        .and_then(|(x_scale, y_scale)| {
+            Some((
+                x_scale.trim().parse::<f64>().ok()?,
+                y_scale.trim().parse::<f64>().ok()?,
+            ))
+        })
+        .filter(|(x_scale, y_scale)| (*x_scale > 0f64) && (*y_scale > 0f64));
+
+    match scales {
+        Some(scales) => Ok(Some(scales)),
+        None => Err(String::from(
+            "`--aspect` must be two positive numbers like `1:3`",
+        )),
+    }
+}
+
+fn named_metric(name: &str) -> Result<Box<dyn DistanceMetric>, String> {
+    metric_from_name(name).ok_or(format!(
+        "Unknown metric `{}`, expected one of: euclidean, manhattan, chebyshev",
+        name
+    ))
+}
+
+/// Parses `--metric-blend` weights like `euclidean:0.7,manhattan:0.3`.
+fn parse_metric_blend(value: &str) -> Result<Blended, String> {
+    let metrics = value
+        .split(',')
+        .map(|entry| {
+            let (name, weight) = entry.split_once(':').ok_or_else(|| {
+                String::from(
+                    "`--metric-blend` must list metrics with weights, like `euclidean:0.7,manhattan:0.3`",
+                )
+            })?;
+            match weight.trim().parse::<f64>() {
+                Ok(weight) if weight > 0f64 => Ok((named_metric(name.trim())?, weight)),
+                _ => Err(format!(
+                    "The weight of `{}` in `--metric-blend` must be a positive number",
+                    name.trim()
+                )),
+            }
+        })
+        .collect::<Result<Vec<(Box<dyn DistanceMetric>, f64)>, String>>()?;
+
+    Ok(Blended { metrics })
+}
+
+fn find_metric(sub_matches: &ArgMatches) -> Result<Box<dyn DistanceMetric>, String> {
+    let metric = match sub_matches.value_of("metric-blend") {
+        None => named_metric(required_value(sub_matches, "metric")?)?,
+        Some(_) if sub_matches.occurrences_of("metric") > 0 => {
+            return Err(String::from(
+                "`--metric` and `--metric-blend` cannot be combined",
+            ))
+        }
+        Some(blend) => Box::new(parse_metric_blend(blend)?),
+    };
+
+    Ok(match parse_aspect(sub_matches)? {
+        None => metric,
+        Some((x_scale, y_scale)) => Box::new(Scaled {
+            metric,
+            x_scale,
+            y_scale,
+        }),
+    })
+}
+
+/// Reads `--assignment`, which only the Euclidean metric on a flat surface
+/// can run as a distance transform.
+fn parse_assignment(
+    sub_matches: &ArgMatches,
+    metric: &dyn DistanceMetric,
+    projection: Projection,
+) -> Result<Assignment, String> {
+    match required_value(sub_matches, "assignment")? {
+        "distance-transform" if !metric.is_squared_euclidean() => Err(String::from(
+            "`--assignment distance-transform` needs the euclidean `--metric`, without `--aspect`, `--metric-blend` or `--orient-cells`",
+        )),
+        "distance-transform" if projection != Projection::Flat => Err(String::from(
+            "`--assignment distance-transform` cannot be combined with `--tileable` or `--projection`",
+        )),
+        "distance-transform" => Ok(Assignment::DistanceTransform),
+        _ => Ok(Assignment::Search),
+    }
+}
+
+/// Reads `--precision`, which only the Euclidean metric searching a flat
+/// surface measures with.
+fn parse_precision(
+    sub_matches: &ArgMatches,
+    metric: &dyn DistanceMetric,
+    projection: Projection,
+) -> Result<Precision, String> {
+    match required_value(sub_matches, "precision")? {
+        "f32" if !metric.is_squared_euclidean() => Err(String::from(
+            "`--precision f32` needs the euclidean `--metric`, without `--aspect`, `--metric-blend` or `--orient-cells`",
+        )),
+        "f32" if projection != Projection::Flat => Err(String::from(
+            "`--precision f32` cannot be combined with `--tileable` or `--projection`",
+        )),
+        "f32" if required_value(sub_matches, "assignment")? == "distance-transform" => {
+            Err(String::from(
+                "`--precision f32` cannot be combined with `--assignment distance-transform`",
+            ))
+        }
+        "f32" => Ok(Precision::Single),
+        _ => Ok(Precision::Double),
+    }
+}
+
+fn parse_higher_order(sub_matches: &ArgMatches) -> Result<Option<HigherOrder>, String> {
+    let k = match sub_matches.value_of("order").map(str::parse::<usize>) {
+        None | Some(Ok(1)) => return Ok(None),
+        Some(Ok(k)) if k > 1 => k,
+        Some(_) => {
+            return Err(String::from(
+                "`--order` must be a positive whole number of nearest anchors",
+            ))
+        }
+    };
+    match required_value(sub_matches, "order-coloring")? {
+        "set" => Ok(Some(HigherOrder::Set(k))),
+        _ => Ok(Some(HigherOrder::Nearest(k))),
+    }
+}
+
+/// Fails when `options` ask for a higher-order diagram along with something
+/// that draws every pixel by its closest anchor.
+fn check_higher_order(options: &RenderOptions) -> Result<(), String> {
+    if options.order.is_none() {
+        return Ok(());
+    }
+    let conflicts = [
+        ("smooth", options.smoothing.is_some()),
+        ("antialias", options.antialias.is_some()),
+        (
+            "tileable` or `--projection",
+            options.projection != Projection::Flat,
+        ),
+        (
+            "assignment distance-transform",
+            options.assignment == Assignment::DistanceTransform,
+        ),
+        ("weighting additive", options.radii.is_some()),
+    ];
+    match conflicts.iter().find(|(_, is_present)| *is_present) {
+        None => Ok(()),
+        Some((conflict, _)) => Err(format!(
+            "`--{}` cannot be combined with `--order`",
+            conflict
+        )),
+    }
+}
+
+fn parse_edge_snapping(sub_matches: &ArgMatches) -> Result<Option<(u32, f64)>, String> {
+    let radius = match sub_matches.value_of("snap-edges").map(str::parse::<u32>) {
+        None => return Ok(None),
+        Some(Ok(radius)) => radius,
+        Some(Err(_)) => return Err(String::from("`--snap-edges` must be a radius in pixels")),
+    };
+    match required_value(sub_matches, "snap-threshold")?.parse::<f64>() {
+        Ok(threshold) if (0f64..=1f64).contains(&threshold) => Ok(Some((radius, threshold))),
+        _ => Err(String::from(
+            "`--snap-threshold` must be a number from 0 to 1",
+        )),
+    }
+}
+
+fn parse_orientation_stretch(sub_matches: &ArgMatches) -> Result<Option<f64>, String> {
+    let is_resized =
+        sub_matches.is_present("output-scale") || sub_matches.is_present("output-size");
+    match sub_matches.value_of("orient-cells").map(str::parse::<f64>) {
+        None => Ok(None),
+        // The orientation field is measured in input pixels.
+        Some(_) if is_resized => Err(String::from(
+            "`--orient-cells` cannot be combined with `--output-scale` or `--output-size`",
+        )),
+        Some(Ok(stretch)) if stretch >= 1f64 => Ok(Some(stretch)),
+        Some(_) => Err(String::from(
+            "`--orient-cells` must be a stretch factor of at least 1",
+        )),
+    }
+}
+
+/// How far to either side of a column anchors are considered for its pixels.
+/// `--aspect` can stretch cells beyond the spacing anchors were sampled with.
+fn candidate_window(sub_matches: &ArgMatches, minimum_distance: u32) -> Result<u32, String> {
+    Ok(match parse_aspect(sub_matches)? {
+        None => minimum_distance,
+        Some((x_scale, y_scale)) => {
+            ((minimum_distance as f64) * (x_scale.max(y_scale) / x_scale)).ceil() as u32
+        }
+    })
+}
+
+/// How far from an anchor Poisson-disk sampling proposes the next ones when
+/// placing them `minimum_distance` apart, from `--max-distance` given for the
+/// spacing of `--min-distance` and stretched as much for other spacings,
+/// such as those of nested levels.
+fn parse_maximum_distance(
+    sub_matches: &ArgMatches,
+    bounds: &Bounds,
+    minimum_distance: u32,
+) -> Result<Option<u32>, String> {
+    let maximum = match sub_matches.value_of("max-distance") {
+        None => return Ok(None),
+        Some(maximum) => maximum,
+    };
+    let spacing = parse_minimum_distance(sub_matches, bounds.width as u32, bounds.height as u32)?;
+    match maximum.parse::<u32>() {
+        Ok(maximum) if maximum > spacing => Ok(Some(
+            (((maximum as f64) * (minimum_distance as f64)) / (spacing as f64))
+                .round()
+                .max((minimum_distance + 1) as f64) as u32,
+        )),
+        _ => Err(format!(
+            "`--max-distance` must be a whole number of pixels larger than the minimum distance of {}",
+            spacing
+        )),
+    }
+}
+
+fn find_sampler(
+    sub_matches: &ArgMatches,
+    bounds: &Bounds,
+    minimum_distance: u32,
+) -> Result<Box<dyn AnchorSampler>, String> {
+    let sampling = required_value(sub_matches, "sampling")?;
+    let jitter = match required_value(sub_matches, "jitter")?.parse::<f64>() {
+        Ok(jitter) if (0f64..=1f64).contains(&jitter) => jitter,
+        _ => return Err(String::from("`--jitter` must be a number from 0 to 1")),
+    };
+    if (sampling != "jittered-grid") && (sub_matches.occurrences_of("jitter") > 0) {
+        return Err(String::from(
+            "`--jitter` only applies to `--sampling jittered-grid`",
+        ));
+    }
+    let maximum_distance = parse_maximum_distance(sub_matches, bounds, minimum_distance)?;
+    if (sampling != "poisson") && maximum_distance.is_some() {
+        return Err(String::from(
+            "`--max-distance` only applies to `--sampling poisson`",
+        ));
+    }
+    let sampler: Box<dyn AnchorSampler> = match (sampling, maximum_distance) {
+        ("jittered-grid", _) => Box::new(JitteredGridSampler {
+            spacing: minimum_distance as f64,
+            jitter,
+        }),
+        ("poisson", Some(maximum)) => Box::new(PoissonDiskSampler {
+            distance: Distance {
+                minimum: minimum_distance,
+                maximum,
+            },
+        }),
+        _ => sampler_from_name(sampling, bounds, minimum_distance).ok_or(format!(
+            "Unknown sampling `{}`, expected one of: {}",
+            sampling,
+            SAMPLER_NAMES.join(", ")
+        ))?,
+    };
+
+    Ok(match sub_matches.is_present("seed-border") {
+        true => Box::new(BorderSeededSampler {
+            sampler,
+            spacing: minimum_distance as f64,
+        }),
+        false => sampler,
+    })
+}
+
+fn find_masked_sampler<'a>(
+    sub_matches: &ArgMatches,
+    bounds: &Bounds,
+    minimum_distance: u32,
+    edge_mode: EdgeMode,
+    mask: Option<&'a ShapeMask>,
+) -> Result<Box<dyn AnchorSampler + 'a>, String> {
+    let sampler = find_sampler(sub_matches, bounds, minimum_distance)?;
+
+    Ok(mask_sampler(
+        edge_sampler(sampler, edge_mode, minimum_distance),
+        mask,
+    ))
+}
+
+/// Mirroring moves anchors off the edges, so it comes before the mask
+/// keeps those inside it.
+fn edge_sampler(
+    sampler: Box<dyn AnchorSampler>,
+    edge_mode: EdgeMode,
+    minimum_distance: u32,
+) -> Box<dyn AnchorSampler> {
+    match edge_mode {
+        EdgeMode::Clamp => sampler,
+        EdgeMode::Mirror => Box::new(MirroredSampler {
+            sampler,
+            minimum_distance: minimum_distance as f64,
+        }),
+    }
+}
+
+fn mask_sampler<'a>(
+    sampler: Box<dyn AnchorSampler>,
+    mask: Option<&'a ShapeMask>,
+) -> Box<dyn AnchorSampler + 'a> {
+    match mask {
+        None => sampler,
+        Some(mask) => Box::new(MaskedSampler { sampler, mask }),
+    }
+}
+
+/// Spaces anchors by `--depth-map`: bright (near) pixels get
+/// `minimum_distance` and black (far) ones `--far-distance`.
+fn load_depth_sampler(
+    sub_matches: &ArgMatches,
+    depth_path: &str,
+    width: u32,
+    height: u32,
+    minimum_distance: u32,
+) -> Result<VariablePoissonSampler, String> {
+    if sub_matches.occurrences_of("sampling") > 0 {
+        return Err(String::from(
+            "`--depth-map` places its own anchors and cannot be combined with `--sampling`",
+        ));
+    }
+    let far_distance = match sub_matches.value_of("far-distance").map(str::parse::<u32>) {
+        None => minimum_distance * 4,
+        Some(Ok(far_distance)) if far_distance >= minimum_distance => far_distance,
+        Some(_) => {
+            return Err(String::from(
+                "`--far-distance` must be an integer no smaller than the minimum distance",
+            ))
+        }
+    };
+
+    let depth = image::open(depth_path)
+        .map_err(|error| format!("Could not open depth map {}: {}", depth_path, error))?
+        .to_luma8();
+    let depth = imageops::resize(&depth, width, height, imageops::FilterType::Triangle);
+    let (near, far) = (minimum_distance as f64, far_distance as f64);
+
+    Ok(VariablePoissonSampler {
+        width,
+        height,
+        spacing: depth
+            .pixels()
+            .map(|pixel| far - ((far - near) * ((pixel.0[0] as f64) / 255f64)))
+            .collect(),
+    })
+}
+
+/// Spaces anchors by the blurred gradient magnitude of the input, from
+/// `minimum_distance` at its strongest edges to four times that in flat
+/// areas, with `--auto-detail` raising weaker edges towards the former.
+fn detail_sampler(
+    sub_matches: &ArgMatches,
+    strength: &str,
+    input_image: &RgbaImage,
+    minimum_distance: u32,
+) -> Result<VariablePoissonSampler, String> {
+    if sub_matches.occurrences_of("sampling") > 0 {
+        return Err(String::from(
+            "`--auto-detail` places its own anchors and cannot be combined with `--sampling`",
+        ));
+    }
+    let strength = match strength.parse::<f64>() {
+        Ok(strength) if strength > 0f64 => strength,
+        _ => return Err(String::from("`--auto-detail` must be a positive number")),
+    };
+
+    let (width, height) = input_image.dimensions();
+    let magnitude = gradient_magnitude(input_image);
+    let edges = GrayImage::from_raw(
+        width,
+        height,
+        magnitude
+            .iter()
+            .map(|value| (value * 255f64).round() as u8)
+            .collect(),
+    )
+    .ok_or_else(|| String::from("Could not build the gradient map"))?;
+    // Edges pull small cells into their surroundings, not only onto them.
+    let detail = imageops::blur(&edges, minimum_distance as f32);
+    let strongest = detail
+        .pixels()
+        .map(|pixel| pixel.0[0])
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let (near, far) = (minimum_distance as f64, (minimum_distance * 4) as f64);
+
+    Ok(VariablePoissonSampler {
+        width,
+        height,
+        spacing: detail
+            .pixels()
+            .map(|pixel| {
+                let detail = ((pixel.0[0] as f64) / strongest).powf(1f64 / strength);
+                far - ((far - near) * detail)
+            })
+            .collect(),
+    })
+}
+
+/// Spaces anchors by `--subject-mask`: the white subject gets
+/// `minimum_distance` and the rest `--background-distance`.
+fn load_subject_sampler(
+    sub_matches: &ArgMatches,
+    mask_path: &str,
+    width: u32,
+    height: u32,
+    minimum_distance: u32,
+) -> Result<VariablePoissonSampler, String> {
+    if sub_matches.occurrences_of("sampling") > 0 {
+        return Err(String::from(
+            "`--subject-mask` places its own anchors and cannot be combined with `--sampling`",
+        ));
+    }
+    let background_distance = match sub_matches
+        .value_of("background-distance")
+        .map(str::parse::<u32>)
+    {
+        None => minimum_distance * 4,
+        Some(Ok(background_distance)) if background_distance > 0 => background_distance,
+        Some(_) => {
+            return Err(String::from(
+                "`--background-distance` must be a positive integer",
+            ))
+        }
+    };
+
+    let subject = ShapeMask::load(mask_path, width, height)
+        .map_err(|error| format!("Could not open subject mask {}: {}", mask_path, error))?;
+    let (inside, outside) = (minimum_distance as f64, background_distance as f64);
+
+    // The small cells could not grow large ones across a hard edge, so the
+    // spacing ramps up over about one background cell outside the subject,
+    // from the blurred mask at half coverage along its edge.
+    let ramp = subject.coverage(width, height, outside * 2f64);
+    Ok(VariablePoissonSampler {
+        width,
+        height,
+        spacing: subject
+            .coverage(width, height, 0f64)
+            .into_iter()
+            .zip(ramp)
+            .map(|(coverage, ramp)| {
+                outside - ((outside - inside) * coverage.max((ramp * 2f64).min(1f64)))
+            })
+            .collect(),
+    })
+}
+
+/// Wraps `sampler` so its anchors suit the surface of `projection`.
+fn project_sampler<'a>(
+    sampler: Box<dyn AnchorSampler + 'a>,
+    projection: Projection,
+    minimum_distance: u32,
+) -> Box<dyn AnchorSampler + 'a> {
+    match projection {
+        Projection::Flat => sampler,
+        Projection::Equirectangular => Box::new(EquirectangularSampler { sampler }),
+        Projection::Torus => Box::new(TileableSampler {
+            sampler,
+            minimum_distance: minimum_distance as f64,
+        }),
+    }
+}
+
+fn seeded_rng(sub_matches: &ArgMatches) -> Result<StdRng, String> {
+    match sub_matches.value_of("seed") {
+        None => Ok(StdRng::from_entropy()),
+        Some(seed) => seed
+            .parse::<u64>()
+            .map(StdRng::seed_from_u64)
+            .map_err(|_| String::from("`--seed` must be a non-negative integer")),
+    }
+}
+
+fn parse_color_sampling(sub_matches: &ArgMatches) -> Result<ColorSampling, String> {
+    if let Some(radius) = sub_matches.value_of("sample-radius") {
+        return match radius.parse::<u32>() {
+            Ok(radius) => Ok(ColorSampling::Disc { radius }),
+            Err(_) => Err(String::from(
+                "`--sample-radius` must be a non-negative whole number of pixels",
+            )),
+        };
+    }
+
+    let name = required_value(sub_matches, "anchor-sampling")?;
+    ColorSampling::from_name(name).ok_or(format!(
+        "Unknown anchor sampling `{}`, expected one of: nearest, bilinear",
+        name
+    ))
+}
+
+fn parse_merge_threshold(sub_matches: &ArgMatches) -> Result<Option<f64>, String> {
+    match sub_matches.value_of("merge-threshold") {
+        None => Ok(None),
+        Some(value) => match value.parse::<f64>() {
+            Ok(threshold) if threshold > 0f64 => Ok(Some(threshold)),
+            _ => Err(String::from(
+                "`--merge-threshold` must be a positive number",
+            )),
+        },
+    }
+}
+
+fn load_cell_script(sub_matches: &ArgMatches) -> Result<Option<CellScript>, String> {
+    match sub_matches.value_of("script") {
+        None => Ok(None),
+        Some(script_path) => {
+            let source = fs::read_to_string(script_path)
+                .map_err(|error| format!("Could not read script {}: {}", script_path, error))?;
+            CellScript::parse(&source)
+                .map(Some)
+                .map_err(|error| format!("Script {}: {}", script_path, error))
+        }
+    }
+}
+
+/// Fails with why `script` stopped, if it stopped in a painting.
+fn check_cell_script(sub_matches: &ArgMatches, script: Option<&CellScript>) -> Result<(), String> {
+    match script.and_then(CellScript::failure) {
+        None => Ok(()),
+        Some(failure) => Err(format!(
+            "Script {}: {}",
+            sub_matches.value_of("script").unwrap_or_default(),
+            failure
+        )),
+    }
+}
+
+fn parse_color_vision(sub_matches: &ArgMatches) -> Result<Option<ColorVision>, String> {
+    match sub_matches.value_of("cvd-safe") {
+        None => Ok(None),
+        Some(name) => ColorVision::from_name(name).map(Some).ok_or(format!(
+            "Unknown color vision deficiency `{}`, expected one of: deuteranopia, protanopia, tritanopia",
+            name
+        )),
+    }
+}
+
+fn parse_cell_style(sub_matches: &ArgMatches) -> Result<CellStyle, String> {
+    let fill = required_value(sub_matches, "fill")?;
+    let pattern = FillPattern::from_name(fill).ok_or(format!(
+        "Unknown fill `{}`, expected one of: solid, hatch, cross-hatch, dots",
+        fill
+    ))?;
+    let style = required_value(sub_matches, "style")?;
+    if (style == "fill") && (pattern == FillPattern::Solid) {
+        return Ok(CellStyle::Filled);
+    }
+    if (style != "fill") && (pattern != FillPattern::Solid) {
+        return Err(format!(
+            "`--fill` cannot be combined with `--style {}`",
+            style
+        ));
+    }
+    if (style != "stained-glass") && sub_matches.is_present("smooth") {
+        return Err(String::from(
+            "`--smooth` cannot be combined with `--style outline`, `--style stipple` or pattern fills",
+        ));
+    }
+
+    match style {
+        "fill" => Ok(CellStyle::Patterned {
+            pattern,
+            spacing: parse_pattern_spacing(sub_matches)?,
+            background: parse_outline_background(sub_matches)?,
+        }),
+        _ => cell_style_from_name(sub_matches, style),
+    }
+}
+
+/// The cell style called `name` in `--styles`, drawn with the stroke,
+/// pattern spacing and background options.
+fn cell_style_from_name(sub_matches: &ArgMatches, name: &str) -> Result<CellStyle, String> {
+    match name {
+        "mosaic" => Ok(CellStyle::Filled),
+        "outline" => {
+            let (width, color) = parse_stroke(sub_matches)?;
+            Ok(CellStyle::Outline {
+                width,
+                color,
+                background: parse_outline_background(sub_matches)?,
+            })
+        }
+        "stained-glass" => {
+            let (width, color) = parse_stroke(sub_matches)?;
+            Ok(CellStyle::StainedGlass { width, color })
+        }
+        "stipple" => Ok(CellStyle::Stipple {
+            background: parse_outline_background(sub_matches)?,
+        }),
+        name => match FillPattern::from_name(name) {
+            Some(pattern) if pattern != FillPattern::Solid => Ok(CellStyle::Patterned {
+                pattern,
+                spacing: parse_pattern_spacing(sub_matches)?,
+                background: parse_outline_background(sub_matches)?,
+            }),
+            _ => Err(format!(
+                "Unknown style `{}`, expected one of: mosaic, outline, stained-glass, stipple, hatch, cross-hatch, dots",
+                name
+            )),
+        },
+    }
+}
+
+fn parse_styles(sub_matches: &ArgMatches) -> Result<Vec<(String, CellStyle)>, String> {
+    match sub_matches.value_of("styles") {
+        None => Ok(Vec::new()),
+        Some(styles) => styles
+            .split(',')
+            .map(str::trim)
+            .map(|name| Ok((name.to_string(), cell_style_from_name(sub_matches, name)?)))
+            .collect(),
+    }
+}
+
+fn parse_pattern_spacing(sub_matches: &ArgMatches) -> Result<f64, String> {
+    match required_value(sub_matches, "pattern-spacing")?.parse::<f64>() {
+        Ok(spacing) if spacing >= 2f64 => Ok(spacing),
+        _ => Err(String::from(
+            "`--pattern-spacing` must be a number of pixels of at least 2",
+        )),
+    }
+}
+
+fn parse_outline_background(sub_matches: &ArgMatches) -> Result<Rgba<u8>, String> {
+    match required_value(sub_matches, "outline-background")? {
+        "white" => Ok(Rgba([255, 255, 255, 255])),
+        "transparent" => Ok(Rgba([255, 255, 255, 0])),
+        color => parse_hex_color(color).ok_or_else(|| {
+            String::from(
+                "`--outline-background` must be white, transparent or a `#RRGGBB` or `#RRGGBBAA` color",
+            )
+        }),
+    }
+}
+
+/// Width and color of the strokes between cells.
+fn parse_stroke(sub_matches: &ArgMatches) -> Result<(f64, Rgba<u8>), String> {
+    let width = match required_value(sub_matches, "stroke-width")?.parse::<f64>() {
+        Ok(width) if width >= 1f64 => width,
+        _ => {
+            return Err(String::from(
+                "`--stroke-width` must be a number of pixels of at least 1",
+            ))
+        }
+    };
+    let color = parse_hex_color(required_value(sub_matches, "stroke-color")?)
+        .ok_or_else(|| String::from("`--stroke-color` must be a `#RRGGBB` or `#RRGGBBAA` color"))?;
+
+    Ok((width, color))
+}
+
+fn parse_smoothing(sub_matches: &ArgMatches) -> Result<Option<usize>, String> {
+    match sub_matches.value_of("smooth").map(str::parse::<usize>) {
+        None => Ok(None),
+        Some(Ok(k)) if k > 0 => Ok(Some(k)),
+        Some(_) => Err(String::from(
+            "`--smooth` must be a positive number of anchors to blend",
+        )),
+    }
+}
+
+fn parse_antialias(sub_matches: &ArgMatches) -> Result<Option<u32>, String> {
+    match sub_matches.value_of("antialias").map(str::parse::<u32>) {
+        None => Ok(None),
+        Some(Ok(samples)) if (1..=16).contains(&samples) => Ok(Some(samples)),
+        Some(_) => Err(String::from(
+            "`--antialias` must be an integer between 1 and 16",
+        )),
+    }
+}
+
+fn parse_round_corners(sub_matches: &ArgMatches) -> Result<Option<f64>, String> {
+    match sub_matches.value_of("round-corners").map(str::parse::<f64>) {
+        None => Ok(None),
+        Some(Ok(radius)) if (1f64..=64f64).contains(&radius) => Ok(Some(radius)),
+        Some(_) => Err(String::from(
+            "`--round-corners` must be a radius from 1 to 64 pixels",
+        )),
+    }
+}
+
+fn parse_path_shaping(sub_matches: &ArgMatches) -> Result<PathShaping, String> {
+    let simplify = match sub_matches.value_of("simplify").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(tolerance)) if tolerance.is_finite() && (tolerance >= 0f64) => Some(tolerance),
+        Some(_) => {
+            return Err(String::from(
+                "`--simplify` must be a tolerance of at least 0 pixels",
+            ))
+        }
+    };
+
+    Ok(PathShaping {
+        simplify,
+        smooth: sub_matches.is_present("smooth-paths"),
+    })
+}
+
+fn parse_min_cell_area(sub_matches: &ArgMatches) -> Result<Option<u64>, String> {
+    match sub_matches.value_of("min-cell-area").map(str::parse::<u64>) {
+        None => Ok(None),
+        Some(Ok(area)) if area > 0 => Ok(Some(area)),
+        Some(_) => Err(String::from(
+            "`--min-cell-area` must be a positive number of pixels",
+        )),
+    }
+}
+
+fn parse_relief(sub_matches: &ArgMatches) -> Result<Option<Relief>, String> {
+    let depth = match sub_matches.value_of("bevel").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(depth)) if (1f64..=64f64).contains(&depth) => Some(depth),
+        Some(_) => {
+            return Err(String::from(
+                "`--bevel` must be a depth from 1 to 64 pixels",
+            ))
+        }
+    };
+    let shadow = sub_matches.is_present("cell-shadow");
+    if depth.is_none() && !shadow {
+        return Ok(None);
+    }
+
+    Ok(Some(Relief {
+        depth: depth.unwrap_or(DEFAULT_DEPTH),
+        bevel: depth.is_some(),
+        shadow,
+    }))
+}
+
+fn parse_output_size(
+    sub_matches: &ArgMatches,
+    image_width: u32,
+    image_height: u32,
+) -> Result<Option<(u32, u32)>, String> {
+    if let Some(output_size) = sub_matches.value_of("output-size") {
+        return parse_size(output_size)
+            .map(Some)
+            .ok_or_else(|| String::from("`--output-size` must look like `4096x4096`"));
+    }
+
+    if let Some(print_size) = sub_matches.value_of("print-size") {
+        let dpi = parse_dpi(sub_matches)?;
+        let (width, height) = parse_physical_size(print_size)
+            .map(|size| orient_to_image(size, image_width, image_height))
+            .ok_or_else(|| {
+                String::from(
+                    "`--print-size` must be A3, A4, A5, letter, legal or a size like `50x70cm`",
+                )
+            })?;
+        // Fits the image inside the print, keeping its aspect ratio.
+        let scale = ((width / MILLIMETRES_PER_INCH) * dpi / (image_width as f64))
+            .min((height / MILLIMETRES_PER_INCH) * dpi / (image_height as f64));
+        return Ok(Some((
+            ((image_width as f64) * scale).round().max(1f64) as u32,
+            ((image_height as f64) * scale).round().max(1f64) as u32,
+        )));
+    }
+
+    match sub_matches.value_of("output-scale").map(str::parse::<f64>) {
+        None => Ok(None),
+        Some(Ok(scale)) if scale > 0f64 => Ok(Some((
+            ((image_width as f64) * scale).round().max(1f64) as u32,
+            ((image_height as f64) * scale).round().max(1f64) as u32,
+        ))),
+        Some(_) => Err(String::from("`--output-scale` must be a positive number")),
+    }
+}
+
+fn parse_level_distances(
+    sub_matches: &ArgMatches,
+    minimum_distance: u32,
+) -> Result<Vec<u32>, String> {
+    if let Some(level_distances) = sub_matches.value_of("level-distances") {
+        return level_distances
+            .split(',')
+            .map(
+                |level_distance| match level_distance.trim().parse::<u32>() {
+                    Ok(level_distance) if level_distance > 0 => Ok(level_distance),
+                    _ => Err(String::from(
+                        "`--level-distances` must be a comma separated list of positive integers",
+                    )),
+                },
+            )
+            .collect();
+    }
+
+    match required_value(sub_matches, "levels")?.parse::<u32>() {
+        Ok(levels) if (1..=8).contains(&levels) => Ok((0..levels)
+            .rev()
+            .map(|level| minimum_distance * 3u32.pow(level))
+            .collect()),
+        _ => Err(String::from(
+            "`--levels` must be an integer between 1 and 8",
+        )),
+    }
+}
+
+fn parse_relaxation(sub_matches: &ArgMatches) -> Result<Option<RelaxationOptions>, String> {
+    let iterations = match sub_matches
+        .value_of("cvt-iterations")
+        .map(str::parse::<u32>)
+    {
+        None => return Ok(None),
+        Some(Ok(iterations)) => iterations,
+        Some(Err(_)) => return Err(String::from("`--cvt-iterations` must be an integer")),
+    };
+    let convergence_threshold = match required_value(sub_matches, "cvt-threshold")?.parse::<f64>() {
+        Ok(threshold) if threshold >= 0f64 => threshold,
+        _ => {
+            return Err(String::from(
+                "`--cvt-threshold` must be a non-negative number of pixels",
+            ))
+        }
+    };
+
+    Ok(Some(RelaxationOptions {
+        iterations,
+        convergence_threshold,
+    }))
+}
+
+fn parse_refinement(
+    sub_matches: &ArgMatches,
+    minimum_distance: u32,
+) -> Result<Option<RefinementOptions>, String> {
+    let passes = match sub_matches.value_of("refine-passes").map(str::parse::<u32>) {
+        None => return Ok(None),
+        Some(Ok(passes)) => passes,
+        Some(Err(_)) => return Err(String::from("`--refine-passes` must be an integer")),
+    };
+    let variance_threshold = match required_value(sub_matches, "variance-threshold")?.parse::<f64>()
+    {
+        Ok(threshold) if threshold >= 0f64 => threshold,
+        _ => {
+            return Err(String::from(
+                "`--variance-threshold` must be a non-negative number",
+            ))
+        }
+    };
+    let maximum_anchors = parse_maximum_anchors(sub_matches)?;
+
+    Ok(Some(RefinementOptions {
+        passes,
+        variance_threshold,
+        maximum_anchors,
+        minimum_distance: (minimum_distance as f64) / 2f64,
+    }))
+}
+
+fn parse_target_error(sub_matches: &ArgMatches) -> Result<Option<ErrorTarget>, String> {
+    let value = match sub_matches.value_of("target-error") {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+
+    let target = match value.split_once(':') {
+        Some(("psnr", decibels)) => decibels
+            .trim_end_matches("dB")
+            .parse::<f64>()
+            .ok()
+            .filter(|decibels| *decibels > 0f64)
+            .map(ErrorTarget::Psnr),
+        Some(("mse", error)) => error
+            .parse::<f64>()
+            .ok()
+            .filter(|error| *error >= 0f64)
+            .map(ErrorTarget::Mse),
+        _ => None,
+    };
+    target.map(Some).ok_or_else(|| {
+        String::from("`--target-error` must look like `psnr:30` (decibels) or `mse:40`")
+    })
+}
+
+fn parse_optimization(sub_matches: &ArgMatches) -> Result<Option<u32>, String> {
+    match sub_matches.value_of("optimize").map(str::parse::<u32>) {
+        None => Ok(None),
+        Some(Ok(iterations)) if iterations > 0 => Ok(Some(iterations)),
+        _ => Err(String::from(
+            "`--optimize` must be a positive number of iterations",
+        )),
+    }
+}
+
+fn parse_maximum_anchors(sub_matches: &ArgMatches) -> Result<Option<usize>, String> {
+    match sub_matches.value_of("max-anchors").map(str::parse::<usize>) {
+        None => Ok(None),
+        Some(Ok(maximum)) => Ok(Some(maximum)),
+        Some(Err(_)) => Err(String::from("`--max-anchors` must be an integer")),
+    }
+}
+
+fn parse_projection(sub_matches: &ArgMatches) -> Result<Projection, String> {
+    let (projection, flag) = if sub_matches.is_present("tileable") {
+        (Projection::Torus, String::from("--tileable"))
+    } else {
+        let name = required_value(sub_matches, "projection")?;
+        let projection = Projection::from_name(name).ok_or(format!(
+            "Unknown projection `{}`, expected one of: flat, equirectangular",
+            name
+        ))?;
+
+        (projection, format!("--projection {}", name))
+    };
+
+    let mut conflicts = vec![
+        (
+            "levels",
+            required_value(sub_matches, "levels")? != "1"
+                || sub_matches.is_present("level-distances"),
+        ),
+        ("cvt-iterations", sub_matches.is_present("cvt-iterations")),
+        ("export-cells", sub_matches.is_present("export-cells")),
+        ("export-pdf", sub_matches.is_present("export-pdf")),
+        ("export-dxf", sub_matches.is_present("export-dxf")),
+        ("export-lottie", sub_matches.is_present("export-lottie")),
+        ("export-html", sub_matches.is_present("export-html")),
+        ("label-cells", sub_matches.is_present("label-cells")),
+    ];
+    if projection == Projection::Equirectangular {
+        conflicts.push(("smooth", sub_matches.is_present("smooth")));
+        conflicts.push((
+            "metric",
+            required_value(sub_matches, "metric")? != "euclidean",
+        ));
+        conflicts.push(("metric-blend", sub_matches.is_present("metric-blend")));
+        conflicts.push(("aspect", sub_matches.is_present("aspect")));
+        conflicts.push(("orient-cells", sub_matches.is_present("orient-cells")));
+    }
+
+    match conflicts.iter().find(|(_, is_present)| *is_present) {
+        Some((conflict, _)) if projection != Projection::Flat => Err(format!(
+            "`{}` cannot be combined with `--{}`",
+            flag, conflict
+        )),
+        _ => Ok(projection),
+    }
+}
+
+/// Mirroring only bounds flat images, and not the levels of nested ones or
+/// anchors already seeded along the borders.
+fn parse_edge_mode(sub_matches: &ArgMatches, projection: Projection) -> Result<EdgeMode, String> {
+    let name = required_value(sub_matches, "edge-mode")?;
+    let edge_mode = EdgeMode::from_name(name).ok_or(format!(
+        "Unknown edge mode `{}`, expected one of: clamp, mirror",
+        name
+    ))?;
+    if edge_mode == EdgeMode::Clamp {
+        return Ok(edge_mode);
+    }
+
+    if projection != Projection::Flat {
+        return Err(String::from(
+            "`--edge-mode mirror` only applies to `--projection flat`, without `--tileable`",
+        ));
+    }
+    if sub_matches.is_present("seed-border") {
+        return Err(String::from(
+            "`--edge-mode mirror` cannot be combined with `--seed-border`",
+        ));
+    }
+    if (required_value(sub_matches, "levels")? != "1") || sub_matches.is_present("level-distances")
+    {
+        return Err(String::from(
+            "`--edge-mode mirror` cannot be combined with nested levels",
+        ));
+    }
+
+    Ok(edge_mode)
+}
+
+#[cfg(feature = "window")]
+fn watch_render_requested(sub_matches: &ArgMatches) -> bool {
+    sub_matches.is_present("watch-render")
+}
+
+#[cfg(not(feature = "window"))]
+fn watch_render_requested(_sub_matches: &ArgMatches) -> bool {
+    false
+}
+
+#[cfg(feature = "window")]
+fn watch_render_in_window(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+) -> Result<RgbaImage, String> {
+    let (image_width, image_height) = input_image.dimensions();
+    let preview_colors: Vec<image::Rgba<u8>> = anchors.iter().map(|anchor| anchor.color).collect();
+
+    let output_image = watch_render(image_width, image_height, &preview_colors, |observer| {
+        render_voronoi(
+            input_image,
+            anchors,
+            &RenderOptions {
+                observer: Some(observer),
+                ..*options
+            },
+        )
+    })
+    .map_err(|error| format!("Could not open preview window: {}", error))?;
+
+    output_image.ok_or_else(|| String::from("Render cancelled from the preview window"))
+}
+
+#[cfg(not(feature = "window"))]
+fn watch_render_in_window(
+    _input_image: &RgbaImage,
+    _anchors: &[Anchor],
+    _options: &RenderOptions,
+) -> Result<RgbaImage, String> {
+    unreachable!("`--watch-render` is only available with the `window` feature")
+}
+
+/// The polygons of every cell, clipped to the mask, and their colors.
+struct CellGeometry {
+    bounds: Bounds,
+    cells: Vec<Vec<Vec<Point>>>,
+    colors: Vec<Rgba<u8>>,
+}
+
+/// The colors `options` fill the cells of `anchors` with on `input_image`.
+fn painted_colors(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+) -> Vec<Rgba<u8>> {
+    let (image_width, image_height) = input_image.dimensions();
+    let mut cell_map = assign_cells(
+        anchors,
+        image_width,
+        image_height,
+        options.minimum_distance,
+        options.metric,
+    );
+    if let Some(mask) = options.mask {
+        mask.apply(&mut cell_map);
+    }
+    let mut colors = color_cells(&cell_map, anchors, input_image, options.colorizer);
+    finish_cell_colors(&cell_map, &mut colors, options);
+
+    colors
+}
+
+fn cell_geometry(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    paths: PathShaping,
+) -> CellGeometry {
+    let (image_width, image_height) = input_image.dimensions();
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+
+    let polygons = cell_polygons(anchors, &bounds);
+    let cells: Vec<Vec<Vec<Point>>> = match options.mask {
+        None => polygons.into_iter().map(|polygon| vec![polygon]).collect(),
+        Some(mask) => {
+            // Simplified once for every cell, so that they still fit together.
+            let outline: Vec<Vec<Point>> = mask
+                .outline()
+                .iter()
+                .map(|ring| match paths.simplify {
+                    None => ring.clone(),
+                    Some(tolerance) => simplify_ring(ring, tolerance),
+                })
+                .collect();
+            clip_cells_to_rings(&polygons, &outline)
+        }
+    };
+    let cells = match paths == PathShaping::default() {
+        true => cells,
+        false => cells
+            .iter()
+            .map(|rings| rings.iter().map(|ring| paths.apply(ring)).collect())
+            .collect(),
+    };
+    let colors = painted_colors(input_image, anchors, options);
+
+    CellGeometry {
+        bounds,
+        cells,
+        colors,
+    }
+}
+
+fn export_cells(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    paths: PathShaping,
+    georeference: Option<&GeoReference>,
+    export_path: &str,
+) -> Result<(), String> {
+    let CellGeometry {
+        bounds,
+        cells,
+        colors,
+    } = cell_geometry(input_image, anchors, options, paths);
+    let mut geojson = cells_to_geojson(anchors, &cells, &colors, &bounds);
+    if let CellStyle::Patterned {
+        pattern, spacing, ..
+    } = options.style
+    {
+        describe_fill_patterns(&mut geojson, &colors, pattern, spacing);
+    }
+    if let Some(georeference) = georeference {
+        georeference.georeference_geojson(&mut geojson);
+    }
+
+    serde_json::to_vec(&geojson)
+        .map_err(io::Error::other)
+        .and_then(|contents| fs::write(export_path, contents))
+        .map_err(|error| format!("Could not export cells to {}: {}", export_path, error))
+}
+
+fn export_dxf(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    paths: PathShaping,
+    sub_matches: &ArgMatches,
+    dxf_path: &str,
+) -> Result<(), String> {
+    let kerf = match required_value(sub_matches, "kerf")?.parse::<f64>() {
+        Ok(kerf) if kerf >= 0f64 => kerf,
+        _ => {
+            return Err(String::from(
+                "`--kerf` must be a non-negative number of millimetres",
+            ))
+        }
+    };
+    let millimetres_per_pixel = MILLIMETRES_PER_INCH / parse_dpi(sub_matches)?;
+    let CellGeometry { bounds, cells, .. } = cell_geometry(input_image, anchors, options, paths);
+
+    fs::write(
+        dxf_path,
+        cells_to_dxf(&cells, &bounds, millimetres_per_pixel, kerf),
+    )
+    .map_err(|error| format!("Could not write DXF {}: {}", dxf_path, error))
+}
+
+/// Writes the cells fading in over `--lottie-duration`, in the order their
+/// anchors were placed.
+fn export_lottie(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    paths: PathShaping,
+    sub_matches: &ArgMatches,
+    lottie_path: &str,
+) -> Result<(), String> {
+    let duration = match required_value(sub_matches, "lottie-duration")?.parse::<f64>() {
+        Ok(duration) if duration > 0f64 => duration,
+        _ => {
+            return Err(String::from(
+                "`--lottie-duration` must be a positive number of seconds",
+            ))
+        }
+    };
+    let CellGeometry {
+        bounds,
+        cells,
+        colors,
+    } = cell_geometry(input_image, anchors, options, paths);
+    let reveal = Reveal {
+        duration,
+        fps: 30,
+        fade: 0.1f64,
+    };
+
+    serde_json::to_vec(&cells_to_lottie(&cells, &colors, &bounds, &reveal))
+        .map_err(io::Error::other)
+        .and_then(|contents| fs::write(lottie_path, contents))
+        .map_err(|error| {
+            format!(
+                "Could not write Lottie animation {}: {}",
+                lottie_path, error
+            )
+        })
+}
+
+/// Writes the label map of the cells to `labels_path` and their anchors and
+/// colors to a JSON palette next to it.
+fn export_labels(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    labels_path: &str,
+) -> Result<(), String> {
+    let source_size = input_image.dimensions();
+    let cell_map = assign_output_cells(anchors, source_size, options);
+    encode_label_map(&cell_map, anchors.len())
+        .save(labels_path)
+        .map_err(|error| format!("Could not export labels to {}: {}", labels_path, error))?;
+
+    let palette_path = Path::new(labels_path).with_extension("json");
+    let colors = painted_colors(input_image, anchors, options);
+    serde_json::to_vec_pretty(&label_palette(anchors, &colors, source_size, options))
+        .map_err(io::Error::other)
+        .and_then(|contents| fs::write(&palette_path, contents))
+        .map_err(|error| {
+            format!(
+                "Could not export label palette to {}: {}",
+                palette_path.display(),
+                error
+            )
+        })
+}
+
+/// Writes a CSV row of [`cell_statistics`] for every cell to `stats_path`,
+/// measured on the pixels of the input they are painted from.
+fn export_cell_stats(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    stats_path: &str,
+) -> Result<(), String> {
+    let source_options = RenderOptions {
+        output_size: None,
+        ..*options
+    };
+    let cell_map = assign_output_cells(anchors, input_image.dimensions(), &source_options);
+
+    fs::write(
+        stats_path,
+        cell_statistics_csv(&cell_statistics(&cell_map, anchors, input_image)),
+    )
+    .map_err(|error| {
+        format!(
+            "Could not export cell statistics to {}: {}",
+            stats_path, error
+        )
+    })
+}
+
+fn export_html(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    paths: PathShaping,
+    html_path: &str,
+) -> Result<(), String> {
+    let CellGeometry {
+        bounds,
+        cells,
+        colors,
+    } = cell_geometry(input_image, anchors, options, paths);
+
+    fs::write(html_path, cells_to_html(&cells, &colors, &bounds))
+        .map_err(|error| format!("Could not write HTML {}: {}", html_path, error))
+}
+
+fn parse_dpi(sub_matches: &ArgMatches) -> Result<f64, String> {
+    match required_value(sub_matches, "dpi")?.parse::<f64>() {
+        Ok(dpi) if dpi > 0f64 => Ok(dpi),
+        _ => Err(String::from("`--dpi` must be a positive number")),
+    }
+}
+
+/// Turns a physical `(width, height)` to landscape for landscape images and
+/// to portrait otherwise.
+fn orient_to_image((width, height): (f64, f64), image_width: u32, image_height: u32) -> (f64, f64) {
+    let (short, long) = (width.min(height), width.max(height));
+    if image_width > image_height {
+        (long, short)
+    } else {
+        (short, long)
+    }
+}
+
+/// Size of the PDF page in points: `--page-size`, turned to match the
+/// orientation of the image, or else the image printed at `--dpi`.
+fn parse_pdf_page(
+    sub_matches: &ArgMatches,
+    image_width: u32,
+    image_height: u32,
+) -> Result<(f64, f64), String> {
+    let to_points = |millimetres: f64| (millimetres / MILLIMETRES_PER_INCH) * POINTS_PER_INCH;
+    match sub_matches.value_of("page-size") {
+        None => {
+            let dpi = parse_dpi(sub_matches)?;
+            Ok((
+                ((image_width as f64) / dpi) * POINTS_PER_INCH,
+                ((image_height as f64) / dpi) * POINTS_PER_INCH,
+            ))
+        }
+        Some(page_size) => {
+            let (width, height) = parse_physical_size(page_size)
+                .map(|size| orient_to_image(size, image_width, image_height))
+                .ok_or_else(|| {
+                    String::from(
+                        "`--page-size` must be A3, A4, A5, letter, legal or a size like `50x70cm`",
+                    )
+                })?;
+            Ok((to_points(width), to_points(height)))
+        }
+    }
+}
+
+/// Pages of `--tile-pages` laid over a `width` by `height` pixel painting at
+/// `--dpi`, with `--overlap` between them.
+fn parse_page_layout(
+    sub_matches: &ArgMatches,
+    width: u32,
+    height: u32,
+) -> Result<Option<PageLayout>, String> {
+    let page_size = match sub_matches.value_of("tile-pages") {
+        None => return Ok(None),
+        Some(page_size) => page_size,
+    };
+    let (page_width, page_height) = parse_physical_size(page_size).ok_or_else(|| {
+        String::from("`--tile-pages` must be A3, A4, A5, letter, legal or a size like `50x70cm`")
+    })?;
+    let overlap =
+        parse_physical_length(required_value(sub_matches, "overlap")?).ok_or_else(|| {
+            String::from("`--overlap` must be a length like `10mm`, `1cm` or `0.5in`")
+        })?;
+    if overlap >= page_width.min(page_height) {
+        return Err(String::from("`--overlap` must be smaller than the pages"));
+    }
+
+    let pixels_per_millimetre = parse_dpi(sub_matches)? / MILLIMETRES_PER_INCH;
+    Ok(Some(PageLayout::new(
+        width,
+        height,
+        (
+            page_width * pixels_per_millimetre,
+            page_height * pixels_per_millimetre,
+        ),
+        overlap * pixels_per_millimetre,
+    )))
+}
+
+/// Writes every page of `layout` next to `output_path` as
+/// `NAME-page-ROW-COLUMN`, counted from 1.
+fn save_page_tiles(
+    output_image: &RgbaImage,
+    layout: &PageLayout,
+    output_path: &str,
+    sub_matches: &ArgMatches,
+) -> Result<(), String> {
+    let output = Path::new(output_path);
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output
+        .extension()
+        .map(|extension| extension.to_string_lossy())
+        .unwrap_or_else(|| "png".into());
+    let encoder = parse_encoder_options(sub_matches)?;
+    // Crop marks a quarter of a millimetre wide.
+    let line_width = (parse_dpi(sub_matches)? / (MILLIMETRES_PER_INCH * 4f64)).round() as u32;
+
+    for row in 0..layout.rows {
+        for column in 0..layout.columns {
+            let page_path = output
+                .with_file_name(format!(
+                    "{}-page-{}-{}.{}",
+                    stem,
+                    row + 1,
+                    column + 1,
+                    extension
+                ))
+                .to_string_lossy()
+                .into_owned();
+            let page_path = resolve_output_path(sub_matches, &page_path)?;
+            write_image(
+                &layout.paint_page(output_image, column, row, line_width),
+                Path::new(&page_path),
+                &encoder,
+            )
+            .map_err(|error| format!("Could not save page {}: {}", page_path, error))?;
+        }
+    }
+    println!(
+        "Split into {} pages, {} across and {} down",
+        layout.page_count(),
+        layout.columns,
+        layout.rows
+    );
+
+    Ok(())
+}
+
+/// Writes the cells to a PDF, on one `page` or, split over the pages of a
+/// `layout` of the `render_size` pixel painting at `dpi`, one PDF page per
+/// page of the layout.
+fn export_pdf(
+    input_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    paths: PathShaping,
+    page: (f64, f64),
+    tiles: Option<(&PageLayout, (u32, u32), f64)>,
+    pdf_path: &str,
+) -> Result<(), String> {
+    let CellGeometry {
+        bounds,
+        cells,
+        colors,
+    } = cell_geometry(input_image, anchors, options, paths);
+    let mut document = PdfDocument::new();
+    match tiles {
+        None => document.add_page(
+            page.0,
+            page.1,
+            cells_to_pdf_content(&cells, &colors, &bounds, &options.style, page),
+        ),
+        Some((layout, (render_width, _), dpi)) => {
+            let points_per_render_pixel = POINTS_PER_INCH / dpi;
+            let render_scale = (render_width as f64) / (bounds.width as f64);
+            let (page_width, page_height) = (
+                layout.page_width * points_per_render_pixel,
+                layout.page_height * points_per_render_pixel,
+            );
+            for row in 0..layout.rows {
+                for column in 0..layout.columns {
+                    let origin = layout.origin(column, row);
+                    let marks: Vec<(Point, Point)> = layout
+                        .crop_marks(column, row)
+                        .iter()
+                        .map(|(from, to)| {
+                            let to_points = |point: &Point| Point {
+                                x: point.x * points_per_render_pixel,
+                                y: point.y * points_per_render_pixel,
+                            };
+                            (to_points(from), to_points(to))
+                        })
+                        .collect();
+                    document.add_page(
+                        page_width,
+                        page_height,
+                        cells_to_pdf_tile(
+                            &cells,
+                            &colors,
+                            &options.style,
+                            points_per_render_pixel * render_scale,
+                            Point {
+                                x: origin.x / render_scale,
+                                y: origin.y / render_scale,
+                            },
+                            page_height,
+                            &marks,
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fs::write(pdf_path, document.to_bytes())
+        .map_err(|error| format!("Could not write PDF {}: {}", pdf_path, error))
+}
+
+fn parse_background(sub_matches: &ArgMatches) -> Result<Background, String> {
+    match required_value(sub_matches, "background")? {
+        "transparent" => Ok(Background::Transparent),
+        "original" => Ok(Background::Original),
+        color => parse_hex_color(color)
+            .map(Background::Color)
+            .ok_or_else(|| {
+                String::from(
+                    "`--background` must be transparent, original or a color like `#RRGGBB`",
+                )
+            }),
+    }
+}
+
+fn parse_overlay_opacity(sub_matches: &ArgMatches) -> Result<Option<f64>, String> {
+    match sub_matches.value_of("overlay-opacity") {
+        None => Ok(None),
+        Some(value) => match value.parse::<f64>() {
+            Ok(opacity) if (0f64..=1f64).contains(&opacity) => Ok(Some(opacity)),
+            _ => Err(String::from(
+                "`--overlay-opacity` must be a number from 0 to 1",
+            )),
+        },
+    }
+}
+
+/// The radius of every anchor of the `--anchors` cache with `--weighting
+/// additive`, whose curved cells only assigning pixels one at a time draws.
+fn parse_additive_radii(sub_matches: &ArgMatches) -> Result<Option<Vec<f64>>, String> {
+    if required_value(sub_matches, "weighting")? != "additive" {
+        return Ok(None);
+    }
+    let conflicts = [
+        (
+            "levels",
+            required_value(sub_matches, "levels")? != "1"
+                || sub_matches.is_present("level-distances"),
+        ),
+        ("smooth", sub_matches.is_present("smooth")),
+        ("antialias", sub_matches.is_present("antialias")),
+        ("output-scale", sub_matches.is_present("output-scale")),
+        ("output-size", sub_matches.is_present("output-size")),
+        ("tileable", sub_matches.is_present("tileable")),
+        (
+            "projection",
+            required_value(sub_matches, "projection")? != "flat",
+        ),
+        (
+            "assignment",
+            required_value(sub_matches, "assignment")? == "distance-transform",
+        ),
+        ("refine-passes", sub_matches.is_present("refine-passes")),
+        ("target-error", sub_matches.is_present("target-error")),
+        ("export-cells", sub_matches.is_present("export-cells")),
+        ("export-pdf", sub_matches.is_present("export-pdf")),
+        ("export-dxf", sub_matches.is_present("export-dxf")),
+        ("export-lottie", sub_matches.is_present("export-lottie")),
+        ("export-html", sub_matches.is_present("export-html")),
+        ("stream-png", sub_matches.is_present("stream-png")),
+    ];
+    if let Some((conflict, _)) = conflicts.iter().find(|(_, is_present)| *is_present) {
+        return Err(format!(
+            "`--{}` cannot be combined with `--weighting additive`",
+            conflict
+        ));
+    }
+
+    let anchors_path = sub_matches.value_of("anchors").ok_or(String::from(
+        "`--weighting additive` needs `--anchors` with a radius for every anchor",
+    ))?;
+    let cache = read_anchor_cache(anchors_path)
+        .map_err(|error| format!("Could not read anchors {}: {}", anchors_path, error))?;
+    match cache.radii {
+        None => Err(format!(
+            "Anchors {} have no radii to weight the cells with",
+            anchors_path
+        )),
+        Some(radii) if radii.iter().any(|radius| !radius.is_finite()) => Err(format!(
+            "Anchors {} have a radius that is not a number",
+            anchors_path
+        )),
+        Some(radii) => Ok(Some(radii)),
+    }
+}
+
+/// The minimum distance of `--second-pass` and the opacity it is laid over
+/// the first pass at.
+fn parse_second_pass(sub_matches: &ArgMatches) -> Result<Option<(u32, f64)>, String> {
+    let second_distance = match sub_matches.value_of("second-pass").map(str::parse::<u32>) {
+        None => return Ok(None),
+        Some(Ok(second_distance)) if second_distance > 0 => second_distance,
+        Some(_) => {
+            return Err(String::from(
+                "`--second-pass` must be a positive number of pixels",
+            ))
+        }
+    };
+    match required_value(sub_matches, "second-pass-opacity")?.parse::<f64>() {
+        Ok(opacity) if (0f64..=1f64).contains(&opacity) => Ok(Some((second_distance, opacity))),
+        _ => Err(String::from(
+            "`--second-pass-opacity` must be a number from 0 to 1",
+        )),
+    }
+}
+
+fn parse_compare_layout(sub_matches: &ArgMatches) -> Result<CompareLayout, String> {
+    match required_value(sub_matches, "compare-layout")? {
+        "diagonal" => Ok(CompareLayout::Diagonal),
+        "split" => match required_value(sub_matches, "divider")?.parse::<f64>() {
+            Ok(divider) if (0f64..=1f64).contains(&divider) => Ok(CompareLayout::Split(divider)),
+            _ => Err(String::from("`--divider` must be a number from 0 to 1")),
+        },
+        _ => Ok(CompareLayout::SideBySide),
+    }
+}
+
+/// Prints how closely the painting reproduces the input, and appends it as a
+/// JSON line to `report_path` if given.
+fn report_metrics(
+    input_image: &RgbaImage,
+    output_image: &RgbaImage,
+    cell_count: usize,
+    (input_path, output_path): (&str, &str),
+    report_path: Option<&str>,
+) -> Result<(), String> {
+    let (width, height) = input_image.dimensions();
+    let resized;
+    let output_image = if output_image.dimensions() == (width, height) {
+        output_image
+    } else {
+        resized = imageops::resize(output_image, width, height, imageops::FilterType::Triangle);
+        &resized
+    };
+
+    let mse = mean_squared_error(input_image, output_image);
+    let (psnr, ssim) = (
+        psnr_from_mse(mse),
+        structural_similarity(input_image, output_image),
+    );
+    println!("PSNR {:.2} dB, SSIM {:.4}", psnr, ssim);
+
+    let report_path = match report_path {
+        None => return Ok(()),
+        Some(report_path) => report_path,
+    };
+    let line = serde_json::json!({
+        "input": input_path,
+        "output": output_path,
+        "cells": cell_count,
+        "mse": mse,
+        "psnr": psnr,
+        "ssim": ssim,
+    });
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(report_path)
+        .and_then(|mut report| writeln!(report, "{}", line))
+        .map_err(|error| format!("Could not append metrics to {}: {}", report_path, error))
+}
+
+fn run_painting(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_image_path = required_value(sub_matches, "input")?;
+    let output_path = required_value(sub_matches, "output")?;
+    apply_worker_threads(sub_matches, 1)?;
+    catch_interrupts();
+
+    paint_and_record(
+        sub_matches,
+        input_image_path,
+        output_path,
+        painting_options(sub_matches),
+    )
+}
+
+/// Paints `input_image_path` to `output_path`, and writes the `--manifest`
+/// reproducing it with the [`painting_options`] `options` if there is one.
+fn paint_and_record(
+    sub_matches: &ArgMatches,
+    input_image_path: &str,
+    output_path: &str,
+    mut options: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), String> {
+    let manifest_path = match sub_matches.value_of("manifest") {
+        None => return paint_image(sub_matches, input_image_path, output_path),
+        Some(manifest_path) => manifest_path,
+    };
+    if !burst_paths(sub_matches).is_empty() {
+        return Err(String::from(
+            "`--manifest` records a single input, not several `--input` exposures",
+        ));
+    }
+    let manifest_path = resolve_output_path(sub_matches, manifest_path)?;
+
+    // An unseeded painting is given a seed, so that it can be painted again.
+    let seed = match options.remove("seed") {
+        None => rand::random::<u64>(),
+        Some(seed) => seed
+            .as_str()
+            .and_then(|seed| seed.parse::<u64>().ok())
+            .ok_or(String::from("`--seed` must be a non-negative integer"))?,
+    };
+    match sub_matches.is_present("seed") {
+        true => paint_image(sub_matches, input_image_path, output_path)?,
+        false => {
+            let mut seeded_options = options.clone();
+            seeded_options.insert(String::from("seed"), serde_json::json!(seed));
+            if sub_matches.is_present("force") {
+                seeded_options.insert(String::from("force"), serde_json::Value::Bool(true));
+            }
+            let seeded_matches = painting_matches(input_image_path, output_path, &seeded_options)?;
+            paint_image(&seeded_matches, input_image_path, output_path)?;
+        }
+    }
+
+    let is_local_input = !input_image_path.contains("://");
+    let absolute = |path: &str| {
+        fs::canonicalize(path).map_or(path.to_string(), |path| path.to_string_lossy().into_owned())
+    };
+    let manifest = Manifest {
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        backend: format!("cpu {}-{}", env::consts::ARCH, env::consts::OS),
+        input: match is_local_input {
+            true => absolute(input_image_path),
+            false => input_image_path.to_string(),
+        },
+        input_hash: match is_local_input {
+            true => Some(file_hash(input_image_path)?),
+            false => None,
+        },
+        output: absolute(output_path),
+        output_hash: file_hash(output_path)?,
+        seed,
+        anchor_cache_hash: match sub_matches.value_of("anchors") {
+            Some(anchors_path) if Path::new(anchors_path).exists() => {
+                Some(file_hash(anchors_path)?)
+            }
+            _ => None,
+        },
+        options,
+    };
+    manifest.save(&manifest_path)?;
+    println!("Saved the manifest to {}", manifest_path);
+
+    Ok(())
+}
+
+/// The painting options of `sub_matches` given on the command line or by
+/// its preset, by name as [`painting_matches`] takes them, but for the paths
+/// of the input, the output and the manifest and how outputs are written.
+fn painting_options(sub_matches: &ArgMatches) -> serde_json::Map<String, serde_json::Value> {
+    let skipped = [
+        "help",
+        "version",
+        "input",
+        "output",
+        "manifest",
+        "preset",
+        "preset-list",
+        "force",
+        "suffix",
+    ];
+    let command = command_line();
+    let painting = match command.find_subcommand("painting") {
+        None => return serde_json::Map::new(),
+        Some(painting) => painting,
+    };
+
+    painting
+        .get_arguments()
+        .map(Arg::get_id)
+        .filter(|id| !skipped.contains(id))
+        .filter(|id| sub_matches.value_source(id) == Some(ValueSource::CommandLine))
+        .map(|id| {
+            let value = match sub_matches.value_of(id) {
+                None => serde_json::Value::Bool(true),
+                Some(value) => serde_json::Value::String(value.to_string()),
+            };
+            (id.to_string(), value)
+        })
+        .collect()
+}
+
+/// Paints the painting of a manifest again, and checks that it is the same.
+fn run_reproduce(sub_matches: &ArgMatches) -> Result<(), String> {
+    let manifest = Manifest::load(required_value(sub_matches, "MANIFEST")?)?;
+    if manifest.version != env!("CARGO_PKG_VERSION") {
+        println!(
+            "Warning: the painting was painted by version {}, not {}",
+            manifest.version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    if let Some(input_hash) = &manifest.input_hash {
+        if file_hash(&manifest.input)? != *input_hash {
+            return Err(format!(
+                "The input {} has changed since it was painted",
+                manifest.input
+            ));
+        }
+    }
+    let anchors_path = manifest
+        .options
+        .get("anchors")
+        .and_then(|path| path.as_str());
+    if let (Some(anchors_path), Some(anchor_cache_hash)) =
+        (anchors_path, &manifest.anchor_cache_hash)
+    {
+        if Path::new(anchors_path).exists() && (file_hash(anchors_path)? != *anchor_cache_hash) {
+            return Err(format!(
+                "The anchors {} have changed since the painting was painted",
+                anchors_path
+            ));
+        }
+    }
+
+    let output_path = match sub_matches.value_of("output") {
+        Some(output_path) => output_path.to_string(),
+        None => {
+            let path = Path::new(&manifest.output);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                None => format!("{}.reproduced", stem),
+                Some(extension) => {
+                    format!("{}.reproduced.{}", stem, extension.to_string_lossy())
+                }
+            };
+            path.with_file_name(name).to_string_lossy().into_owned()
+        }
+    };
+    let output_path = resolve_output_path(sub_matches, &output_path)?;
+
+    let mut options = manifest.options.clone();
+    options.insert(String::from("seed"), serde_json::json!(manifest.seed));
+    options.insert(String::from("force"), serde_json::Value::Bool(true));
+    let painting_matches = painting_matches(&manifest.input, &output_path, &options)?;
+    apply_worker_threads(&painting_matches, 1)?;
+    catch_interrupts();
+    paint_image(&painting_matches, &manifest.input, &output_path)?;
+
+    let output_hash = file_hash(&output_path)?;
+    if output_hash != manifest.output_hash {
+        return Err(format!(
+            "{} is not the same as {}: its hash is {}, not {}",
+            output_path, manifest.output, output_hash, manifest.output_hash
+        ));
+    }
+    println!("Reproduced {} exactly as {}", manifest.output, output_path);
+
+    Ok(())
+}
+
+/// Writes the painting in every one of `styles` to `--output-dir`, named
+/// after `output_path` and the style.
+fn save_styled_paintings(
+    sub_matches: &ArgMatches,
+    output_path: &str,
+    styles: &[(String, CellStyle)],
+    paintings: &[RgbaImage],
+) -> Result<(), String> {
+    let output_dir = required_value(sub_matches, "output-dir")?;
+    fs::create_dir_all(output_dir).map_err(|error| {
+        format!(
+            "Could not create output directory {}: {}",
+            output_dir, error
+        )
+    })?;
+    let output_path = Path::new(output_path);
+    let stem = output_path
+        .file_stem()
+        .map_or(String::from("painting"), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+    let extension = output_path
+        .extension()
+        .map_or(String::from("png"), |extension| {
+            extension.to_string_lossy().into_owned()
+        });
+    let encoder = parse_encoder_options(sub_matches)?;
+
+    for ((name, _), painting) in styles.iter().zip(paintings) {
+        let style_path = Path::new(output_dir).join(format!("{}-{}.{}", stem, name, extension));
+        let style_path = resolve_output_path(sub_matches, &style_path.to_string_lossy())?;
+        write_image(painting, Path::new(&style_path), &encoder)
+            .map_err(|error| format!("Could not save output image {}: {}", style_path, error))?;
+        println!("Saved the {} painting to {}", name, style_path);
+    }
+
+    Ok(())
+}
+
+/// The frames of `input_image_path` and their delays if it is a GIF of more
+/// than one frame.
+fn open_animated_gif(input_image_path: &str) -> Result<Option<Vec<(RgbaImage, Delay)>>, String> {
+    let is_local_gif = !input_image_path.contains("://")
+        && (ImageFormat::from_path(input_image_path).ok() == Some(ImageFormat::Gif));
+    if !is_local_gif {
+        return Ok(None);
+    }
+
+    let frames = File::open(input_image_path)
+        .map_err(ImageError::IoError)
+        .and_then(|file| decode_gif_frames(io::BufReader::new(file)))
+        .map_err(|error| format!("Could not open input image {}: {}", input_image_path, error))?;
+    Ok((frames.len() > 1).then_some(frames))
+}
+
+/// Paints every frame of an animated GIF with the same anchors, keeping the
+/// delay of every frame.
+fn paint_animated_gif(
+    sub_matches: &ArgMatches,
+    input_image_path: &str,
+    frames: Vec<(RgbaImage, Delay)>,
+    output_path: &str,
+) -> Result<(), String> {
+    let conflicts = [
+        (
+            "levels",
+            required_value(sub_matches, "levels")? != "1"
+                || sub_matches.is_present("level-distances"),
+        ),
+        ("export-cells", sub_matches.is_present("export-cells")),
+        ("export-pdf", sub_matches.is_present("export-pdf")),
+        ("export-dxf", sub_matches.is_present("export-dxf")),
+        ("export-lottie", sub_matches.is_present("export-lottie")),
+        ("export-html", sub_matches.is_present("export-html")),
+        ("export-cmyk", sub_matches.is_present("export-cmyk")),
+        ("export-labels", sub_matches.is_present("export-labels")),
+        (
+            "export-cell-stats",
+            sub_matches.is_present("export-cell-stats"),
+        ),
+        ("brightness", sub_matches.occurrences_of("brightness") > 0),
+        ("contrast", sub_matches.occurrences_of("contrast") > 0),
+        ("saturation", sub_matches.occurrences_of("saturation") > 0),
+        ("gamma", sub_matches.occurrences_of("gamma") > 0),
+        ("pre-blur", sub_matches.is_present("pre-blur")),
+        ("auto-levels", sub_matches.is_present("auto-levels")),
+        ("equalize", sub_matches.is_present("equalize")),
+        ("compare", sub_matches.is_present("compare")),
+        ("styles", sub_matches.is_present("styles")),
+        ("preserve-mask", sub_matches.is_present("preserve-mask")),
+        ("second-pass", sub_matches.is_present("second-pass")),
+        ("stream-png", sub_matches.is_present("stream-png")),
+        (
+            "weighting",
+            required_value(sub_matches, "weighting")? != "none",
+        ),
+        ("snapshot-every", sub_matches.is_present("snapshot-every")),
+        ("page", sub_matches.is_present("page")),
+        ("original-page", sub_matches.is_present("original-page")),
+        ("psd-layer", sub_matches.is_present("psd-layer")),
+        ("script", sub_matches.is_present("script")),
+        ("points", sub_matches.is_present("points")),
+    ];
+    if let Some((conflict, _)) = conflicts.iter().find(|(_, is_present)| *is_present) {
+        return Err(format!(
+            "`--{}` cannot be used with an animated GIF input",
+            conflict
+        ));
+    }
+    let sprite_sheet = parse_sprite_sheet(sub_matches)?;
+    let format = animation_format(output_path);
+    if (format == AnimationFormat::Frames) && sprite_sheet.is_none() {
+        return Err(format!(
+            "{} is an animated GIF, so the output must be a `.gif` or an animated `.png`",
+            input_image_path
+        ));
+    }
+
+    let count = frames.len();
+    let (frames, delays): (Vec<RgbaImage>, Vec<Delay>) = frames.into_iter().unzip();
+    let mut painted = Vec::with_capacity(count);
+    paint_frames(
+        sub_matches,
+        frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| Ok((format!("{} of {}", index + 1, input_image_path), frame))),
+        0f64,
+        false,
+        |frame| {
+            painted.push(frame);
+            Ok(())
+        },
+    )?;
+
+    let encoder = parse_encoder_options(sub_matches)?;
+    let painted: Vec<(RgbaImage, Delay)> = painted.into_iter().zip(delays).collect();
+    if let Some(grid) = sprite_sheet {
+        return write_sprite_sheet(&painted, grid, output_path, &encoder);
+    }
+    File::create(output_path)
+        .map_err(ImageError::IoError)
+        .and_then(|file| match format {
+            AnimationFormat::Apng => {
+                encode_apng(&painted, apng_compression(&encoder), BufWriter::new(file))
+            }
+            _ => encode_gif_frames(painted, file),
+        })
+        .map_err(|error| format!("Could not save output image {}: {}", output_path, error))?;
+    println!("Saved {} painted frames to {}", count, output_path);
+
+    Ok(())
+}
+
+/// Paints the cells straight into the `--stream-png` output a strip of rows at
+/// a time, for outputs too large to hold as one image.
+fn stream_painting(
+    sub_matches: &ArgMatches,
+    color_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    output_path: &str,
+    timings: &mut Timings,
+) -> Result<(), String> {
+    let conflicts = [
+        (
+            "levels",
+            required_value(sub_matches, "levels")? != "1"
+                || sub_matches.is_present("level-distances"),
+        ),
+        ("smooth", options.smoothing.is_some()),
+        ("antialias", options.antialias.is_some()),
+        ("style", !matches!(options.style, CellStyle::Filled)),
+        ("styles", sub_matches.is_present("styles")),
+        ("shape-mask", options.mask.is_some()),
+        ("projection", options.projection != Projection::Flat),
+        ("order", options.order.is_some()),
+        ("snapshot-every", sub_matches.is_present("snapshot-every")),
+        ("script", options.script.is_some()),
+        ("second-pass", sub_matches.is_present("second-pass")),
+        ("export-cells", sub_matches.is_present("export-cells")),
+        ("export-pdf", sub_matches.is_present("export-pdf")),
+        ("export-dxf", sub_matches.is_present("export-dxf")),
+        ("export-lottie", sub_matches.is_present("export-lottie")),
+        ("export-html", sub_matches.is_present("export-html")),
+        ("export-cmyk", sub_matches.is_present("export-cmyk")),
+        ("export-labels", sub_matches.is_present("export-labels")),
+        (
+            "export-cell-stats",
+            sub_matches.is_present("export-cell-stats"),
+        ),
+        ("tile-pages", sub_matches.is_present("tile-pages")),
+        ("overlay-opacity", sub_matches.is_present("overlay-opacity")),
+        ("blend", sub_matches.is_present("blend")),
+        ("preserve-mask", sub_matches.is_present("preserve-mask")),
+        ("compare", sub_matches.is_present("compare")),
+        ("metrics", sub_matches.is_present("metrics")),
+        ("label-cells", sub_matches.is_present("label-cells")),
+        ("show", sub_matches.is_present("show")),
+        ("watch-render", watch_render_requested(sub_matches)),
+        ("original-page", sub_matches.is_present("original-page")),
+    ];
+    if let Some((conflict, _)) = conflicts.iter().find(|(_, is_present)| *is_present) {
+        return Err(format!(
+            "`--{}` cannot be combined with `--stream-png`",
+            conflict
+        ));
+    }
+    if !matches!(ImageFormat::from_path(output_path), Ok(ImageFormat::Png)) {
+        return Err(format!(
+            "`--stream-png` writes PNG images, so {} must be a `.png`",
+            output_path
+        ));
+    }
+    let background = match parse_background(sub_matches)? {
+        Background::Transparent => Rgba([0, 0, 0, 0]),
+        Background::Color(color) => color,
+        Background::Original => {
+            return Err(String::from(
+                "`--background original` cannot be combined with `--stream-png`",
+            ))
+        }
+    };
+
+    let (image_width, image_height) = color_image.dimensions();
+    let colors = timings.measure("color sampling", || {
+        // Colorizers needing more of a cell than the mean of its pixels,
+        // such as the median, still assign the whole input at once.
+        stream_cell_colors(color_image, anchors, options).unwrap_or_else(|| {
+            let cell_map = assign_cells(
+                anchors,
+                image_width,
+                image_height,
+                options.minimum_distance,
+                options.metric,
+            );
+            let mut colors = color_cells(&cell_map, anchors, color_image, options.colorizer);
+            finish_cell_colors(&cell_map, &mut colors, options);
+
+            colors
+        })
+    });
+
+    let compression = apng_compression(&parse_encoder_options(sub_matches)?);
+    timings
+        .measure("painting", || {
+            File::create(output_path)
+                .map_err(png::EncodingError::from)
+                .and_then(|file| {
+                    stream_cells_png(
+                        BufWriter::new(file),
+                        anchors,
+                        &colors,
+                        (image_width, image_height),
+                        options,
+                        background,
+                        compression,
+                    )
+                })
+        })
+        .map_err(|error| format!("Could not save output image {}: {}", output_path, error))?;
+    if interrupted() {
+        println!(
+            "Interrupted, saved the rows painted so far to {}",
+            output_path
+        );
+    }
+
+    if sub_matches.is_present("timings") {
+        println!("{}", timings.report());
+    }
+
+    Ok(())
+}
+
+fn paint_image(
+    sub_matches: &ArgMatches,
+    input_image_path: &str,
+    output_path: &str,
+) -> Result<(), String> {
+    paint_image_to(
+        sub_matches,
+        input_image_path,
+        &resolve_output_path(sub_matches, output_path)?,
+    )
+}
+
+/// Paints like [`paint_image`] to `output_path` as it is, whether or not
+/// it already exists.
+fn paint_image_to(
+    sub_matches: &ArgMatches,
+    input_image_path: &str,
+    output_path: &str,
+) -> Result<(), String> {
+    let mut timings = Timings::new();
+    if sub_matches.is_present("original-page")
+        && ImageFormat::from_path(output_path).ok() != Some(ImageFormat::Tiff)
+    {
+        return Err(String::from(
+            "`--original-page` needs a `.tif` or `.tiff` output",
+        ));
+    }
+    if let Some(frames) = open_animated_gif(input_image_path)? {
+        if !burst_paths(sub_matches).is_empty() {
+            return Err(String::from(
+                "An animated GIF input cannot be combined with further `--input` exposures",
+            ));
+        }
+        return paint_animated_gif(sub_matches, input_image_path, frames, output_path);
+    }
+    if sub_matches.is_present("spritesheet") {
+        return Err(String::from("`--spritesheet` needs an animated GIF input"));
+    }
+    let paths = parse_path_shaping(sub_matches)?;
+    let export_path = match sub_matches.value_of("export-cells") {
+        None => None,
+        Some(export_path) => Some(resolve_output_path(sub_matches, export_path)?),
+    };
+    let pdf_path = match sub_matches.value_of("export-pdf") {
+        None => None,
+        Some(pdf_path) => Some(resolve_output_path(sub_matches, pdf_path)?),
+    };
+    let dxf_path = match sub_matches.value_of("export-dxf") {
+        None => None,
+        Some(dxf_path) => Some(resolve_output_path(sub_matches, dxf_path)?),
+    };
+    let lottie_path = match sub_matches.value_of("export-lottie") {
+        None => None,
+        Some(lottie_path) => Some(resolve_output_path(sub_matches, lottie_path)?),
+    };
+    let html_path = match sub_matches.value_of("export-html") {
+        None => None,
+        Some(html_path) => Some(resolve_output_path(sub_matches, html_path)?),
+    };
+    let cmyk_path = match sub_matches.value_of("export-cmyk") {
+        None => None,
+        Some(cmyk_path) => Some(resolve_output_path(sub_matches, cmyk_path)?),
+    };
+    let labels_path = match sub_matches.value_of("export-labels") {
+        None => None,
+        Some(labels_path) => Some(resolve_output_path(sub_matches, labels_path)?),
+    };
+    let cell_stats_path = match sub_matches.value_of("export-cell-stats") {
+        None => None,
+        Some(cell_stats_path) => Some(resolve_output_path(sub_matches, cell_stats_path)?),
+    };
+    let cmyk_profile = match sub_matches.value_of("cmyk-profile") {
+        None => None,
+        Some(profile_path) => Some(
+            fs::read(profile_path)
+                .and_then(CmykProfile::from_bytes)
+                .map_err(|error| {
+                    format!("Could not load ICC profile {}: {}", profile_path, error)
+                })?,
+        ),
+    };
+    let compare_path = match sub_matches.value_of("compare") {
+        None => None,
+        Some(compare_path) => Some(resolve_output_path(sub_matches, compare_path)?),
+    };
+    let color_space = parse_color_space(sub_matches)?;
+    let registry = ColorizerRegistry::with_color_space(color_space);
+    let colorizer = find_colorizer(&registry, sub_matches)?;
+    let palette = load_cell_palette(sub_matches)?;
+    let palette_space = parse_match_space(sub_matches)?.unwrap_or(color_space);
+    let palette_colorizer = palette.as_ref().map(|palette| PaletteColorizer {
+        colorizer,
+        palette,
+        color_space: palette_space,
+    });
+    let colorizer: &dyn CellColorizer = match &palette_colorizer {
+        None => colorizer,
+        Some(palette_colorizer) => palette_colorizer,
+    };
+    let tones = parse_tones(sub_matches)?;
+    let tone_colorizer = tones
+        .as_ref()
+        .map(|tones| ToneColorizer { colorizer, tones });
+    let colorizer: &dyn CellColorizer = match &tone_colorizer {
+        None => colorizer,
+        Some(tone_colorizer) => tone_colorizer,
+    };
+    let posterize_colorizer =
+        parse_posterize(sub_matches)?.map(|levels| PosterizeColorizer { colorizer, levels });
+    let colorizer: &dyn CellColorizer = match &posterize_colorizer {
+        None => colorizer,
+        Some(posterize_colorizer) => posterize_colorizer,
+    };
+    // Random palette colors are handed out to the anchors, not the pixels.
+    let colorizer: &dyn CellColorizer = if sub_matches.is_present("random-palette") {
+        &AnchorColorizer
+    } else {
+        colorizer
+    };
+    let metric = find_metric(sub_matches)?;
+
+    let started = Instant::now();
+    let input_image = open_painting_input(sub_matches, input_image_path)?;
+    let input_image = combine_burst(sub_matches, input_image)?;
+    let georeference = read_georeference(sub_matches, input_image_path)?;
+    let (full_width, full_height) = input_image.dimensions();
+    let mut color_source = load_color_source(sub_matches, full_width, full_height)?;
+    let mut input_image = crop_to_region(input_image, sub_matches)?;
+    let georeference = match (
+        georeference,
+        sub_matches
+            .value_of("region")
+            .map(parse_region)
+            .transpose()?,
+    ) {
+        (Some(georeference), Some((x, y, _, _))) => Some(georeference.cropped(x, y)),
+        (georeference, _) => georeference,
+    };
+    let adjustments = parse_adjustments(sub_matches)?;
+    adjustments.apply(&mut input_image);
+    if let Some(color_source) = &mut color_source {
+        adjustments.apply(color_source);
+    }
+    timings.record("decode", started.elapsed());
+    // Cells follow the structure of the input but take their colors from here.
+    let color_image = color_source.as_ref().unwrap_or(&input_image);
+    let leveled_colors = parse_color_levels(sub_matches).map(|levels| {
+        let mut leveled_colors = color_image.clone();
+        levels(&mut leveled_colors);
+        leveled_colors
+    });
+    let color_image = leveled_colors.as_ref().unwrap_or(color_image);
+    // Blurring only the colors keeps noise out of them but not the edges.
+    let blurred_colors =
+        parse_pre_blur(sub_matches)?.map(|sigma| imageops::blur(color_image, sigma));
+    let color_image = blurred_colors.as_ref().unwrap_or(color_image);
+
+    let (image_width, image_height) = input_image.dimensions();
+
+    let minimum_distance = parse_minimum_distance(sub_matches, image_width, image_height)?;
+    let minimum_distance =
+        rescaled_minimum_distance(sub_matches, minimum_distance, (image_width, image_height))?;
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+
+    let spacing_sampler = match (
+        sub_matches.value_of("depth-map"),
+        sub_matches.value_of("subject-mask"),
+        sub_matches.value_of("auto-detail"),
+    ) {
+        (None, None, None) => None,
+        (Some(depth_path), None, None) => Some(load_depth_sampler(
+            sub_matches,
+            depth_path,
+            image_width,
+            image_height,
+            minimum_distance,
+        )?),
+        (None, Some(mask_path), None) => Some(load_subject_sampler(
+            sub_matches,
+            mask_path,
+            image_width,
+            image_height,
+            minimum_distance,
+        )?),
+        (None, None, Some(strength)) => Some(detail_sampler(
+            sub_matches,
+            strength,
+            &input_image,
+            minimum_distance,
+        )?),
+        _ => {
+            return Err(String::from(
+                "`--depth-map`, `--subject-mask` and `--auto-detail` cannot be combined",
+            ))
+        }
+    };
+    // Cells reach as far as the largest spacing anchors were placed with, or
+    // anywhere on the map between locations, which are as far apart as the
+    // data has them.
+    let largest_distance = match sub_matches.is_present("points") {
+        true => image_width.max(image_height),
+        false => spacing_sampler
+            .as_ref()
+            .map(|sampler| sampler.spacing.iter().copied().fold(0f64, f64::max).ceil() as u32)
+            .unwrap_or(minimum_distance)
+            .max(minimum_distance),
+    };
+
+    let orientation = parse_orientation_stretch(sub_matches)?.map(|stretch| {
+        (
+            OrientationField::from_image(&input_image, minimum_distance),
+            stretch,
+        )
+    });
+    let (metric, window): (Box<dyn DistanceMetric + '_>, u32) = match &orientation {
+        None => (metric, candidate_window(sub_matches, largest_distance)?),
+        Some((field, stretch)) => (
+            Box::new(Oriented {
+                metric,
+                field,
+                stretch: *stretch,
+            }),
+            ((candidate_window(sub_matches, largest_distance)? as f64) * stretch).ceil() as u32,
+        ),
+    };
+
+    let mask = match sub_matches.value_of("shape-mask") {
+        None => None,
+        Some(mask_path) => Some(
+            ShapeMask::load(mask_path, image_width, image_height)
+                .map_err(|error| format!("Could not open shape mask {}: {}", mask_path, error))?,
+        ),
+    };
+    let projection = parse_projection(sub_matches)?;
+    let edge_mode = parse_edge_mode(sub_matches, projection)?;
+    let sampler = match spacing_sampler {
+        None => find_masked_sampler(
+            sub_matches,
+            &bounds,
+            minimum_distance,
+            edge_mode,
+            mask.as_ref(),
+        )?,
+        Some(_) if sub_matches.is_present("max-distance") => {
+            return Err(String::from(
+                "`--max-distance` cannot be combined with `--depth-map`, `--subject-mask` or `--auto-detail`, which space anchors themselves",
+            ))
+        }
+        Some(_) if sub_matches.is_present("seed-border") => {
+            return Err(String::from(
+                "`--seed-border` needs evenly spaced anchors, not `--depth-map`, `--subject-mask` or `--auto-detail`",
+            ))
+        }
+        Some(spacing_sampler) => mask_sampler(
+            edge_sampler(Box::new(spacing_sampler), edge_mode, minimum_distance),
+            mask.as_ref(),
+        ),
+    };
+    let sampler = project_sampler(sampler, projection, minimum_distance);
+    let mut rng = seeded_rng(sub_matches)?;
+    let radii = parse_additive_radii(sub_matches)?;
+    let script = load_cell_script(sub_matches)?;
+
+    let options = RenderOptions {
+        minimum_distance: window,
+        metric: metric.as_ref(),
+        colorizer,
+        smoothing: parse_smoothing(sub_matches)?,
+        antialias: parse_antialias(sub_matches)?,
+        round_corners: parse_round_corners(sub_matches)?,
+        min_cell_area: parse_min_cell_area(sub_matches)?,
+        relief: parse_relief(sub_matches)?,
+        match_space: parse_match_space(sub_matches)?.unwrap_or(ColorSpace::Cielab),
+        output_size: parse_output_size(sub_matches, image_width, image_height)?,
+        observer: Some(&InterruptObserver),
+        mask: mask.as_ref(),
+        projection,
+        merge_threshold: parse_merge_threshold(sub_matches)?,
+        style: parse_cell_style(sub_matches)?,
+        assignment: parse_assignment(sub_matches, metric.as_ref(), projection)?,
+        color_vision: parse_color_vision(sub_matches)?,
+        radii: radii.as_deref(),
+        order: parse_higher_order(sub_matches)?,
+        script: script.as_ref(),
+        precision: parse_precision(sub_matches, metric.as_ref(), projection)?,
+    };
+    check_higher_order(&options)?;
+    if options.order.is_some() {
+        let conflicts = [
+            (
+                "levels",
+                required_value(sub_matches, "levels")? != "1"
+                    || sub_matches.is_present("level-distances"),
+            ),
+            ("export-cells", sub_matches.is_present("export-cells")),
+            ("export-pdf", sub_matches.is_present("export-pdf")),
+            ("export-dxf", sub_matches.is_present("export-dxf")),
+            ("export-lottie", sub_matches.is_present("export-lottie")),
+            ("export-html", sub_matches.is_present("export-html")),
+        ];
+        if let Some((conflict, _)) = conflicts.iter().find(|(_, is_present)| *is_present) {
+            return Err(format!(
+                "`--{}` cannot be combined with `--order`",
+                conflict
+            ));
+        }
+    }
+    let level_distances = parse_level_distances(sub_matches, minimum_distance)?;
+    let styles = parse_styles(sub_matches)?;
+    if level_distances.len() > 1 {
+        let conflicts = [
+            ("output-scale", sub_matches.is_present("output-scale")),
+            ("output-size", sub_matches.is_present("output-size")),
+            ("smooth", options.smoothing.is_some()),
+            (
+                "assignment distance-transform",
+                options.assignment == Assignment::DistanceTransform,
+            ),
+            ("export-cells", sub_matches.is_present("export-cells")),
+            ("random-palette", sub_matches.is_present("random-palette")),
+            ("export-pdf", sub_matches.is_present("export-pdf")),
+            ("export-dxf", sub_matches.is_present("export-dxf")),
+            ("export-lottie", sub_matches.is_present("export-lottie")),
+            ("export-html", sub_matches.is_present("export-html")),
+            ("export-labels", sub_matches.is_present("export-labels")),
+            (
+                "export-cell-stats",
+                sub_matches.is_present("export-cell-stats"),
+            ),
+            ("round-corners", options.round_corners.is_some()),
+            ("bevel", sub_matches.is_present("bevel")),
+            ("cell-shadow", sub_matches.is_present("cell-shadow")),
+            ("min-cell-area", options.min_cell_area.is_some()),
+            ("label-cells", sub_matches.is_present("label-cells")),
+            ("snapshot-every", sub_matches.is_present("snapshot-every")),
+            ("script", options.script.is_some()),
+            ("depth-map", sub_matches.is_present("depth-map")),
+            ("subject-mask", sub_matches.is_present("subject-mask")),
+            ("styles", !styles.is_empty()),
+        ];
+        if let Some((conflict, _)) = conflicts.iter().find(|(_, is_present)| *is_present) {
+            return Err(format!(
+                "`--{}` cannot be combined with nested levels",
+                conflict
+            ));
+        }
+    }
+
+    let anchors_path = sub_matches.value_of("anchors");
+    let cache_colors = sub_matches.is_present("cache-colors");
+    let cached_anchors = match anchors_path {
+        Some(anchors_path) if cache_colors => match read_anchor_cache(anchors_path) {
+            Ok(cache) => match cache.anchors() {
+                None => None,
+                Some(anchors) => {
+                    check_anchor_cache(
+                        sub_matches,
+                        anchors_path,
+                        &cache,
+                        (image_width, image_height),
+                        image_hash(&input_image),
+                    )?;
+                    let from = rescaled_canvas(
+                        sub_matches,
+                        anchors_path,
+                        &cache,
+                        (image_width, image_height),
+                    )?;
+                    Some((anchors, from))
+                }
+            },
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                return Err(format!(
+                    "Could not read anchors {}: {}",
+                    anchors_path, error
+                ))
+            }
+            Err(_) => None,
+        },
+        _ => None,
+    };
+    let anchors = match cached_anchors {
+        Some((anchors, from)) => {
+            println!("Loaded {} colored anchor points", anchors.len());
+            match from {
+                None => anchors,
+                Some((from_width, from_height)) => {
+                    println!(
+                        "Rescaled the anchors from {}x{} to {}x{}",
+                        from_width, from_height, image_width, image_height
+                    );
+
+                    scale_anchors(
+                        &anchors,
+                        (from_width, from_height),
+                        (image_width, image_height),
+                    )
+                }
+            }
+        }
+        None => {
+            let started = Instant::now();
+            let anchor_points = match sub_matches.value_of("points") {
+                None => {
+                    let anchor_points = load_or_generate_anchor_points(
+                        sub_matches,
+                        &input_image,
+                        sampler.as_ref(),
+                        largest_distance as f64,
+                        &mut rng,
+                    )?;
+                    println!("Generated {} anchor points", anchor_points.len());
+
+                    anchor_points
+                }
+                Some(points_path) => {
+                    load_location_points(sub_matches, points_path, image_width, image_height)?
+                }
+            };
+            timings.record("anchors", started.elapsed());
+
+            let started = Instant::now();
+            let anchor_points = match parse_relaxation(sub_matches)? {
+                None => anchor_points,
+                Some(relaxation) => {
+                    let (relaxed_points, iterations) = gradient_weighted_relaxation(
+                        &input_image,
+                        anchor_points,
+                        &relaxation,
+                        &options,
+                    );
+                    println!("Relaxed anchor points in {} iterations", iterations);
+
+                    relaxed_points
+                }
+            };
+            let anchor_points = match parse_edge_snapping(sub_matches)? {
+                None => anchor_points,
+                Some((radius, threshold)) => {
+                    snap_to_edges(&input_image, anchor_points, radius, threshold)
+                }
+            };
+            let anchor_points = match parse_refinement(sub_matches, minimum_distance)? {
+                None => anchor_points,
+                Some(refinement) => {
+                    let (refined_points, passes) = refine_high_variance_cells(
+                        &input_image,
+                        anchor_points,
+                        &refinement,
+                        &options,
+                    );
+                    println!(
+                        "Refined detailed cells to {} anchor points in {} passes",
+                        refined_points.len(),
+                        passes
+                    );
+
+                    refined_points
+                }
+            };
+            let anchor_points = match parse_target_error(sub_matches)? {
+                None => anchor_points,
+                Some(target) => {
+                    let (refined_points, error) = refine_to_target_error(
+                        &input_image,
+                        anchor_points,
+                        target,
+                        parse_maximum_anchors(sub_matches)?,
+                        &options,
+                        &mut rng,
+                    );
+                    println!(
+                        "Reached PSNR {:.2} dB (MSE {:.2}) with {} cells",
+                        psnr_from_mse(error),
+                        error,
+                        refined_points.len()
+                    );
+
+                    refined_points
+                }
+            };
+            let anchor_points = match parse_optimization(sub_matches)? {
+                None => anchor_points,
+                Some(iterations) => {
+                    let (optimized_points, initial_error, error) = optimize_anchors(
+                        &input_image,
+                        anchor_points,
+                        iterations,
+                        minimum_distance as f64,
+                        &options,
+                        &mut rng,
+                    );
+                    println!(
+                        "Optimized anchor points from MSE {:.2} to {:.2} (PSNR {:.2} dB)",
+                        initial_error,
+                        error,
+                        psnr_from_mse(error)
+                    );
+
+                    optimized_points
+                }
+            };
+            timings.record("anchors", started.elapsed());
+
+            let started = Instant::now();
+            let anchors = match parse_random_palette(sub_matches, &mut rng)? {
+                None => sample_anchor_colors(
+                    color_image,
+                    anchor_points,
+                    parse_color_sampling(sub_matches)?,
+                ),
+                Some(palette) => {
+                    color_anchors_from_palette(anchor_points, &bounds, &palette, None, &mut rng)
+                }
+            };
+
+            timings.record("color sampling", started.elapsed());
+
+            if let (true, Some(anchors_path)) = (cache_colors, anchors_path) {
+                let cache = AnchorCache {
+                    canvas: Some((image_width, image_height)),
+                    spacing: Some(largest_distance as f64),
+                    source_hash: Some(image_hash(&input_image)),
+                    points: anchors.iter().map(|anchor| anchor.point.clone()).collect(),
+                    colors: Some(anchors.iter().map(|anchor| anchor.color).collect()),
+                    radii: radii.clone(),
+                };
+                write_anchor_cache(&cache, anchors_path).map_err(|error| {
+                    format!("Could not write anchors {}: {}", anchors_path, error)
+                })?;
+            }
+
+            anchors
+        }
+    };
+
+    if let Some(radii) = &radii {
+        if radii.len() != anchors.len() {
+            return Err(format!(
+                "`--weighting additive` needs a radius for each of the {} anchors, not {}",
+                anchors.len(),
+                radii.len()
+            ));
+        }
+    }
+
+    if sub_matches.is_present("stream-png") {
+        return stream_painting(
+            sub_matches,
+            color_image,
+            &anchors,
+            &options,
+            output_path,
+            &mut timings,
+        );
+    }
+
+    let snapshot_colors: Vec<Rgba<u8>> = anchors.iter().map(|anchor| anchor.color).collect();
+    let snapshots = match sub_matches.value_of("snapshot-every") {
+        None => None,
+        Some(interval) => Some(SnapshotObserver::new(
+            SnapshotInterval::parse(interval).ok_or(String::from(
+                "`--snapshot-every` must be a number of seconds like `30s` or of columns like `500`",
+            ))?,
+            match sub_matches.value_of("snapshot") {
+                None => Path::new(output_path).with_extension("snapshot.png"),
+                Some(snapshot_path) => PathBuf::from(snapshot_path),
+            },
+            &snapshot_colors,
+            image_width,
+            image_height,
+        )),
+    };
+    let options = RenderOptions {
+        observer: match &snapshots {
+            None => options.observer,
+            Some(snapshots) => Some(snapshots),
+        },
+        ..options
+    };
+
+    let started = Instant::now();
+    let output_image_buffer = if level_distances.len() > 1 {
+        let coloring = match required_value(sub_matches, "nested-colors")? {
+            "inherit" => NestedColoring::Inherit,
+            _ => NestedColoring::Resample,
+        };
+        let samplers = level_distances
+            .iter()
+            .map(|level_distance| {
+                find_masked_sampler(
+                    sub_matches,
+                    &bounds,
+                    *level_distance,
+                    EdgeMode::Clamp,
+                    mask.as_ref(),
+                )
+            })
+            .collect::<Result<Vec<Box<dyn AnchorSampler>>, String>>()?;
+        let levels: Vec<NestedLevel> = samplers
+            .iter()
+            .zip(&level_distances)
+            .map(|(sampler, level_distance)| NestedLevel {
+                sampler: sampler.as_ref(),
+                minimum_distance: *level_distance,
+            })
+            .collect();
+
+        let painting = render_nested(color_image, &levels, coloring, &mut rng, &options);
+        timings.record("render", started.elapsed());
+
+        painting
+    } else if watch_render_requested(sub_matches) {
+        if !styles.is_empty() {
+            return Err(String::from(
+                "`--styles` cannot be combined with `--watch-render`",
+            ));
+        }
+        let painting = watch_render_in_window(color_image, &anchors, &options)?;
+        timings.record("render", started.elapsed());
+
+        painting
+    } else if styles.is_empty() {
+        render_voronoi_timed(color_image, &anchors, &options, &mut timings)
+    } else {
+        let all_styles: Vec<CellStyle> = iter::once(options.style)
+            .chain(styles.iter().map(|(_, style)| *style))
+            .collect();
+        let mut paintings =
+            render_voronoi_styles(color_image, &anchors, &options, &all_styles, &mut timings);
+        let painting = paintings.remove(0);
+        check_cell_script(sub_matches, script.as_ref())?;
+        timings.measure("encode", || {
+            save_styled_paintings(sub_matches, output_path, &styles, &paintings)
+        })?;
+
+        painting
+    };
+    check_cell_script(sub_matches, script.as_ref())?;
+    // Whatever columns were finished before Ctrl-C are saved, and nothing
+    // else is done with them.
+    if interrupted() {
+        timings.measure("encode", || {
+            save_output_image(&output_image_buffer, output_path, sub_matches)
+        })?;
+        println!(
+            "Interrupted, saved the columns painted so far to {}",
+            output_path
+        );
+        return Ok(());
+    }
+
+    let output_image_buffer = match parse_second_pass(sub_matches)? {
+        None => output_image_buffer,
+        Some((second_distance, opacity)) => {
+            if options.output_size.is_some() {
+                return Err(String::from(
+                    "`--output-scale` and `--output-size` cannot be combined with `--second-pass`",
+                ));
+            }
+            if !styles.is_empty() {
+                return Err(String::from(
+                    "`--styles` cannot be combined with `--second-pass`",
+                ));
+            }
+            let started = Instant::now();
+            let second_options = RenderOptions {
+                minimum_distance: (((window as f64) * (second_distance as f64))
+                    / (largest_distance as f64))
+                    .ceil()
+                    .max(window as f64) as u32,
+                radii: None,
+                ..options
+            };
+            // A fresh generator from `--seed` seeds both passes alike.
+            let mut second_rng = seeded_rng(sub_matches)?;
+            let second_sampler = project_sampler(
+                find_masked_sampler(
+                    sub_matches,
+                    &bounds,
+                    second_distance,
+                    edge_mode,
+                    mask.as_ref(),
+                )?,
+                projection,
+                second_distance,
+            );
+            let second_anchors = sample_anchor_colors(
+                &output_image_buffer,
+                second_sampler.try_sample(&bounds, &mut second_rng)?,
+                parse_color_sampling(sub_matches)?,
+            );
+            println!(
+                "Re-tessellated the painting with {} anchor points",
+                second_anchors.len()
+            );
+            let second_pass =
+                render_voronoi(&output_image_buffer, &second_anchors, &second_options);
+            timings.record("second pass", started.elapsed());
+
+            overlay(
+                &output_image_buffer,
+                &second_pass,
+                opacity,
+                BlendMode::Normal,
+            )
+        }
+    };
+    check_cell_script(sub_matches, script.as_ref())?;
+
+    let started = Instant::now();
+    if let Some(export_path) = export_path {
+        export_cells(
+            color_image,
+            &anchors,
+            &options,
+            paths,
+            georeference.as_ref(),
+            &export_path,
+        )?;
+    }
+    if let Some(dxf_path) = dxf_path {
+        export_dxf(
+            color_image,
+            &anchors,
+            &options,
+            paths,
+            sub_matches,
+            &dxf_path,
+        )?;
+    }
+    if let Some(lottie_path) = lottie_path {
+        export_lottie(
+            color_image,
+            &anchors,
+            &options,
+            paths,
+            sub_matches,
+            &lottie_path,
+        )?;
+    }
+    if let Some(html_path) = html_path {
+        export_html(color_image, &anchors, &options, paths, &html_path)?;
+    }
+    if let Some(labels_path) = labels_path {
+        export_labels(color_image, &anchors, &options, &labels_path)?;
+    }
+    if let Some(cell_stats_path) = cell_stats_path {
+        export_cell_stats(color_image, &anchors, &options, &cell_stats_path)?;
+    }
+    let (render_width, render_height) = output_image_buffer.dimensions();
+    let page_layout = parse_page_layout(sub_matches, render_width, render_height)?;
+    if let Some(pdf_path) = pdf_path {
+        let page = parse_pdf_page(sub_matches, image_width, image_height)?;
+        let tiles = match &page_layout {
+            None => None,
+            Some(layout) => Some((
+                layout,
+                (render_width, render_height),
+                parse_dpi(sub_matches)?,
+            )),
+        };
+        export_pdf(
+            color_image,
+            &anchors,
+            &options,
+            paths,
+            page,
+            tiles,
+            &pdf_path,
+        )?;
+    }
+    let output_image_buffer = fill_background(
+        &input_image,
+        output_image_buffer,
+        parse_background(sub_matches)?,
+    );
+    timings.record("exports", started.elapsed());
+
+    let started = Instant::now();
+    let blend_mode = match sub_matches.value_of("blend") {
+        None => BlendMode::Normal,
+        Some(name) => BlendMode::from_name(name).ok_or(format!(
+            "Unknown blend mode `{}`, expected one of: normal, multiply, screen, overlay, soft-light",
+            name
+        ))?,
+    };
+    let output_image_buffer = match parse_overlay_opacity(sub_matches)? {
+        None if blend_mode == BlendMode::Normal => output_image_buffer,
+        opacity => overlay(
+            &input_image,
+            &output_image_buffer,
+            opacity.unwrap_or(1f64),
+            blend_mode,
+        ),
+    };
+    let output_image_buffer = match sub_matches.value_of("preserve-mask") {
+        None => output_image_buffer,
+        Some(mask_path) => {
+            let feather = match required_value(sub_matches, "preserve-feather")?.parse::<f64>() {
+                Ok(feather) if feather >= 0f64 => feather,
+                _ => {
+                    return Err(String::from(
+                        "`--preserve-feather` must be a non-negative number of pixels",
+                    ))
+                }
+            };
+            let (width, height) = output_image_buffer.dimensions();
+            let preserved =
+                ShapeMask::load(mask_path, image_width, image_height).map_err(|error| {
+                    format!("Could not open preserve mask {}: {}", mask_path, error)
+                })?;
+            preserve(
+                &input_image,
+                &output_image_buffer,
+                &preserved.coverage(width, height, feather),
+            )
+        }
+    };
+    if let Some(compare_path) = compare_path {
+        let comparison = compare(
+            &input_image,
+            &output_image_buffer,
+            parse_compare_layout(sub_matches)?,
+        );
+        write_image(
+            &comparison,
+            Path::new(&compare_path),
+            &parse_encoder_options(sub_matches)?,
+        )
+        .map_err(|error| {
+            format!(
+                "Could not save comparison image {}: {}",
+                compare_path, error
+            )
+        })?;
+    }
+    if sub_matches.is_present("metrics") {
+        report_metrics(
+            &input_image,
+            &output_image_buffer,
+            anchors.len(),
+            (input_image_path, output_path),
+            sub_matches.value_of("metrics"),
+        )?;
+    }
+
+    let output_image_buffer = if sub_matches.is_present("label-cells") {
+        let mut labeled = output_image_buffer;
+        let positions = cell_label_positions(&anchors, &bounds, &labeled);
+        label_cells(&mut labeled, &positions);
+        labeled
+    } else {
+        output_image_buffer
+    };
+
+    timings.record("compositing", started.elapsed());
+
+    let started = Instant::now();
+    if let Some(cmyk_path) = cmyk_path {
+        let dpi = parse_dpi(sub_matches)?;
+        File::create(&cmyk_path)
+            .and_then(|file| {
+                write_cmyk_tiff(
+                    &output_image_buffer,
+                    cmyk_profile.as_ref(),
+                    dpi,
+                    BufWriter::new(file),
+                )
+            })
+            .map_err(|error| format!("Could not write CMYK TIFF {}: {}", cmyk_path, error))?;
+    }
+    if let Some(layout) = &page_layout {
+        save_page_tiles(&output_image_buffer, layout, output_path, sub_matches)?;
+    }
+    // The painting covers the same area of the map as the input, at its own
+    // size.
+    let georeference = georeference.map(|georeference| {
+        georeference.scaled(input_image.dimensions(), output_image_buffer.dimensions())
+    });
+    if sub_matches.is_present("original-page") {
+        let profile = parse_encoder_options(sub_matches)?.profile;
+        let mut pages = [output_image_buffer.clone(), input_image.clone()];
+        if let Some(profile) = profile {
+            pages.iter_mut().for_each(|page| profile.convert(page));
+        }
+        let icc_profile = profile.map(OutputProfile::icc_profile);
+        File::create(output_path)
+            .map_err(|error| error.to_string())
+            .and_then(|file| {
+                write_tiff_pages(
+                    &[&pages[0], &pages[1]],
+                    icc_profile.as_deref(),
+                    georeference.as_ref(),
+                    BufWriter::new(file),
+                )
+            })
+            .map_err(|error| format!("Could not save output image {}: {}", output_path, error))?;
+    } else {
+        let encoder = EncoderOptions {
+            georeference,
+            ..parse_encoder_options(sub_matches)?
+        };
+        save_encoded_output_image(&output_image_buffer, output_path, sub_matches, &encoder)?;
+    }
+    timings.record("encode", started.elapsed());
+
+    if sub_matches.is_present("timings") {
+        println!("{}", timings.report());
+    }
+
+    Ok(())
+}
+
+/// Centroid and area of every cell scaled from the input `bounds` to the
+/// size of `output_image`.
+fn cell_label_positions(
+    anchors: &[Anchor],
+    bounds: &Bounds,
+    output_image: &RgbaImage,
+) -> Vec<Option<(Point, f64)>> {
+    let (output_width, output_height) = output_image.dimensions();
+    let x_scale = (output_width as f64) / (bounds.width as f64);
+    let y_scale = (output_height as f64) / (bounds.height as f64);
+
+    cell_polygons(anchors, bounds)
+        .iter()
+        .map(|polygon| {
+            polygon_centroid(polygon).map(|centroid| {
+                (
+                    Point {
+                        x: centroid.x * x_scale,
+                        y: centroid.y * y_scale,
+                    },
+                    polygon_area(polygon) * x_scale * y_scale,
+                )
+            })
+        })
+        .collect()
+}
+
+fn run_animate(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_image_path = required_value(sub_matches, "input")?;
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    apply_worker_threads(sub_matches, 1)?;
+    let encoder = parse_encoder_options(sub_matches)?;
+    let sprite_sheet = parse_sprite_sheet(sub_matches)?;
+
+    let color_space = parse_color_space(sub_matches)?;
+    let registry = ColorizerRegistry::with_color_space(color_space);
+    let colorizer = find_colorizer(&registry, sub_matches)?;
+    let palette = load_cell_palette(sub_matches)?;
+    let palette_space = parse_match_space(sub_matches)?.unwrap_or(color_space);
+    let palette_colorizer = palette.as_ref().map(|palette| PaletteColorizer {
+        colorizer,
+        palette,
+        color_space: palette_space,
+    });
+    let colorizer: &dyn CellColorizer = match &palette_colorizer {
+        None => colorizer,
+        Some(palette_colorizer) => palette_colorizer,
+    };
+    let tones = parse_tones(sub_matches)?;
+    let tone_colorizer = tones
+        .as_ref()
+        .map(|tones| ToneColorizer { colorizer, tones });
+    let colorizer: &dyn CellColorizer = match &tone_colorizer {
+        None => colorizer,
+        Some(tone_colorizer) => tone_colorizer,
+    };
+    let posterize_colorizer =
+        parse_posterize(sub_matches)?.map(|levels| PosterizeColorizer { colorizer, levels });
+    let colorizer: &dyn CellColorizer = match &posterize_colorizer {
+        None => colorizer,
+        Some(posterize_colorizer) => posterize_colorizer,
+    };
+    let metric = find_metric(sub_matches)?;
+
+    let input_image = crop_to_region(open_input_image(input_image_path)?, sub_matches)?;
+
+    let (image_width, image_height) = input_image.dimensions();
+
+    let minimum_distance = parse_minimum_distance(sub_matches, image_width, image_height)?;
+    let minimum_distance =
+        rescaled_minimum_distance(sub_matches, minimum_distance, (image_width, image_height))?;
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+
+    let sampler = find_sampler(sub_matches, &bounds, minimum_distance)?;
+    let mut rng = seeded_rng(sub_matches)?;
+    let animation = parse_animation(sub_matches, (image_width, image_height), &mut rng)?;
+
+    let anchor_points = load_or_generate_anchor_points(
+        sub_matches,
+        &input_image,
+        sampler.as_ref(),
+        minimum_distance as f64,
+        &mut rng,
+    )?;
+    let options = RenderOptions {
+        minimum_distance: candidate_window(sub_matches, minimum_distance)?,
+        metric: metric.as_ref(),
+        colorizer,
+        smoothing: parse_smoothing(sub_matches)?,
+        antialias: parse_antialias(sub_matches)?,
+        round_corners: parse_round_corners(sub_matches)?,
+        min_cell_area: parse_min_cell_area(sub_matches)?,
+        relief: parse_relief(sub_matches)?,
+        match_space: parse_match_space(sub_matches)?.unwrap_or(ColorSpace::Cielab),
+        output_size: parse_output_size(sub_matches, image_width, image_height)?,
+        observer: None,
+        mask: None,
+        projection: Projection::Flat,
+        merge_threshold: parse_merge_threshold(sub_matches)?,
+        style: parse_cell_style(sub_matches)?,
+        assignment: parse_assignment(sub_matches, metric.as_ref(), Projection::Flat)?,
+        color_vision: parse_color_vision(sub_matches)?,
+        radii: None,
+        order: parse_higher_order(sub_matches)?,
+        script: None,
+        precision: parse_precision(sub_matches, metric.as_ref(), Projection::Flat)?,
+    };
+    check_higher_order(&options)?;
+    let frames = animate(
+        &input_image,
+        &animation,
+        anchor_points,
+        sampler.as_ref(),
+        &mut rng,
+        &options,
+    );
+
+    if let Some(grid) = sprite_sheet {
+        let delay = Delay::from_numer_denom_ms(1000, animation.fps);
+        let frames: Vec<(RgbaImage, Delay)> =
+            frames.into_iter().map(|frame| (frame, delay)).collect();
+        return write_sprite_sheet(&frames, grid, output_path, &encoder);
+    }
+    write_animation(frames, animation.fps, output_path, &encoder)
+        .map_err(|error| format!("Could not write animation {}: {}", output_path, error))
+}
+
+fn run_paint_by_numbers(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_image_path = required_value(sub_matches, "input")?;
+    let output_path = required_value(sub_matches, "output")?;
+    let legend_path = match sub_matches.value_of("legend") {
+        Some(legend_path) => legend_path.to_string(),
+        None => {
+            let output = Path::new(output_path);
+            let stem = output
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_default();
+            let extension = output
+                .extension()
+                .map(|extension| extension.to_string_lossy())
+                .unwrap_or_else(|| "png".into());
+            output
+                .with_file_name(format!("{}-legend.{}", stem, extension))
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    let output_path = &resolve_output_path(sub_matches, output_path)?;
+    let legend_path = &resolve_output_path(sub_matches, &legend_path)?;
+    let preview_path = match sub_matches.value_of("preview") {
+        None => None,
+        Some(preview_path) => Some(resolve_output_path(sub_matches, preview_path)?),
+    };
+    let paints = match required_value(sub_matches, "paints")?.parse::<usize>() {
+        Ok(paints) if paints > 0 => paints,
+        _ => return Err(String::from("`--paints` must be a positive number")),
+    };
+    let maximum_scale = match required_value(sub_matches, "number-size")?.parse::<u32>() {
+        Ok(scale) if scale > 0 => scale,
+        _ => {
+            return Err(String::from(
+                "`--number-size` must be a positive whole number",
+            ))
+        }
+    };
+
+    let registry = ColorizerRegistry::with_builtins();
+    let colorizer = find_colorizer(&registry, sub_matches)?;
+    let input_image = open_input_image(input_image_path)?;
+    let (image_width, image_height) = input_image.dimensions();
+    let minimum_distance = parse_minimum_distance(sub_matches, image_width, image_height)?;
+    let minimum_distance =
+        rescaled_minimum_distance(sub_matches, minimum_distance, (image_width, image_height))?;
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+    let sampler = find_sampler(sub_matches, &bounds, minimum_distance)?;
+    let mut rng = seeded_rng(sub_matches)?;
+    let anchor_points = load_or_generate_anchor_points(
+        sub_matches,
+        &input_image,
+        sampler.as_ref(),
+        minimum_distance as f64,
+        &mut rng,
+    )?;
+    let anchors = color_anchor_points(&input_image, anchor_points);
+
+    let options = RenderOptions {
+        minimum_distance,
+        colorizer,
+        ..RenderOptions::default()
+    };
+    let cell_map = assign_cells(
+        &anchors,
+        image_width,
+        image_height,
+        options.minimum_distance,
+        options.metric,
+    );
+    let colors = color_cells(&cell_map, &anchors, &input_image, options.colorizer);
+    let kit = paint_by_numbers(&cell_map, &colors, paints, maximum_scale);
+    println!(
+        "Numbered {} cells with {} paints",
+        anchors.len(),
+        kit.palette.colors.len()
+    );
+
+    let encoder = parse_encoder_options(sub_matches)?;
+    write_image(&kit.legend, Path::new(legend_path), &encoder)
+        .map_err(|error| format!("Could not save legend {}: {}", legend_path, error))?;
+    if let Some(preview_path) = preview_path {
+        write_image(&kit.preview, Path::new(&preview_path), &encoder)
+            .map_err(|error| format!("Could not save preview {}: {}", preview_path, error))?;
+    }
+    save_output_image(&kit.sheet, output_path, sub_matches)
+}
+
+/// Splits the cores between `jobs` images rendered at once, unless
+/// `--threads` sets the worker threads of every render. With `--auto-tune`
+/// the share of the cores is the most threads a render may pick.
+fn apply_worker_threads(sub_matches: &ArgMatches, jobs: usize) -> Result<(), String> {
+    set_progress_format(match sub_matches.value_of("progress") {
+        Some("json") => ProgressFormat::Json,
+        _ => ProgressFormat::Text,
+    });
+    set_best_effort(sub_matches.is_present("best-effort"));
+    let auto_tune = sub_matches.is_present("auto-tune");
+    set_auto_tune(auto_tune);
+    let threads = match sub_matches.value_of("threads") {
+        Some(threads) => match threads.parse::<usize>() {
+            Ok(threads) if threads > 0 => threads,
+            _ => return Err(String::from("`--threads` must be a positive whole number")),
+        },
+        None if (jobs > 1) || auto_tune => {
+            let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+            (cores / jobs).max(1)
+        }
+        None => return Ok(()),
+    };
+    set_worker_threads(threads);
+
+    Ok(())
+}
+
+fn parse_milliseconds(sub_matches: &ArgMatches, name: &str) -> Result<Duration, String> {
+    required_value(sub_matches, name)?
+        .parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("`--{}` must be a whole number of milliseconds", name))
+}
+
+/// Size and modification time of a file, which stop changing once it has
+/// been written completely.
+fn file_state(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+fn list_images(directory: &str) -> Result<Vec<PathBuf>, String> {
+    let mut entries = fs::read_dir(directory)
+        .and_then(|entries| entries.collect::<io::Result<Vec<fs::DirEntry>>>())
+        .map_err(|error| format!("Could not read input directory {}: {}", directory, error))?
+        .into_iter()
+        .map(|entry| entry.path())
+        .filter(|entry| entry.is_file() && image::ImageFormat::from_path(entry).is_ok())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    Ok(entries)
+}
+
+/// Values of the `{field}` placeholders of `--name-template` for the
+/// `counter`th image painted from `input_path`.
+fn name_fields(
+    sub_matches: &ArgMatches,
+    input_path: &Path,
+    counter: usize,
+) -> Vec<(&'static str, String)> {
+    let text = |name: &str| sub_matches.value_of(name).unwrap_or_default().to_string();
+    let minimum_distance = match sub_matches.value_of("min-distance-pct") {
+        None => text("min-distance"),
+        Some(percent) => format!("{}pct", percent.trim_end_matches('%')),
+    };
+    let metric = match sub_matches.value_of("metric-blend") {
+        None => text("metric"),
+        Some(blend) => blend.replace(':', "").replace(',', "-"),
+    };
+
+    vec![
+        (
+            "stem",
+            input_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        (
+            "ext",
+            input_path
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        ("min_distance", minimum_distance),
+        ("sampling", text("sampling")),
+        ("metric", metric),
+        ("color_mode", text("color-mode")),
+        (
+            "seed",
+            sub_matches.value_of("seed").unwrap_or("random").to_string(),
+        ),
+        ("counter", format!("{:04}", counter)),
+    ]
+}
+
+/// Replaces every `{field}` of `template` with its value from `fields`.
+fn fill_name_template(template: &str, fields: &[(&str, String)]) -> Result<String, String> {
+    let mut name = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("`--name-template` has an unclosed `{{` in {}", template))?
+            + start;
+
+        let field = &rest[(start + 1)..end];
+        let value = fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown `{{{}}}` in `--name-template`, expected one of: {}",
+                    field,
+                    fields
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )
+            })?;
+        name.push_str(value);
+        rest = &rest[(end + 1)..];
+    }
+    name.push_str(rest);
+
+    Ok(name)
+}
+
+/// Counts of the images handed to the painting jobs, shared between them to
+/// report progress across the whole queue.
+#[derive(Default)]
+struct QueueProgress {
+    queued: usize,
+    finished: usize,
+    failed: usize,
+}
+
+fn run_watch(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_directory = required_value(sub_matches, "input-dir")?;
+    let output_directory = required_value(sub_matches, "output-dir")?;
+    let poll_interval = parse_milliseconds(sub_matches, "poll-interval")?;
+    let settle = parse_milliseconds(sub_matches, "settle")?;
+    let jobs = match required_value(sub_matches, "jobs")?.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => jobs,
+        _ => return Err(String::from("`--jobs` must be a positive whole number")),
+    };
+    apply_worker_threads(sub_matches, jobs)?;
+    let name_template = required_value(sub_matches, "name-template")?;
+    fill_name_template(
+        name_template,
+        &name_fields(sub_matches, Path::new("input.png"), 1),
+    )?;
+
+    fs::create_dir_all(output_directory).map_err(|error| {
+        format!(
+            "Could not create output directory {}: {}",
+            output_directory, error
+        )
+    })?;
+
+    // The state every file was last painted in, and the state it was last
+    // seen in along with when that state was first seen.
+    let mut painted: HashMap<PathBuf, (u64, SystemTime)> = HashMap::new();
+    let mut pending: HashMap<PathBuf, ((u64, SystemTime), Instant)> = HashMap::new();
+    // Where every output named so far was written, so repaints overwrite
+    // what this watch wrote without `--force`.
+    let mut written: HashMap<PathBuf, String> = HashMap::new();
+    if !sub_matches.is_present("existing") {
+        for path in list_images(input_directory)? {
+            if let Some(state) = file_state(&path) {
+                painted.insert(path, state);
+            }
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel::<(String, String)>();
+    let receiver = Mutex::new(receiver);
+    let progress = Mutex::new(QueueProgress::default());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let next_job = receiver.lock().unwrap().recv();
+                let (input_path, output_path) = match next_job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+
+                let result =
+                    catch_render_failure(|| paint_image_to(sub_matches, &input_path, &output_path));
+                let mut progress = progress.lock().unwrap();
+                progress.finished += 1;
+                match result {
+                    Ok(()) => println!(
+                        "[{}/{}] Wrote {}",
+                        progress.finished, progress.queued, output_path
+                    ),
+                    Err(message) => {
+                        progress.failed += 1;
+                        eprintln!(
+                            "[{}/{}] {} ({} failed so far)",
+                            progress.finished, progress.queued, message, progress.failed
+                        );
+                    }
+                }
+            });
+        }
+
+        // Moved in so that returning closes the queue and lets the jobs end.
+        let sender = sender;
+        println!("Watching {} for new images", input_directory);
+        loop {
+            for path in list_images(input_directory)? {
+                let state = match file_state(&path) {
+                    None => continue,
+                    Some(state) => state,
+                };
+                if painted.get(&path) == Some(&state) {
+                    continue;
+                }
+
+                let now = Instant::now();
+                let since = match pending.get(&path) {
+                    Some((pending_state, since)) if *pending_state == state => *since,
+                    _ => {
+                        pending.insert(path.clone(), (state, now));
+                        now
+                    }
+                };
+                if now.duration_since(since) < settle {
+                    continue;
+                }
+
+                pending.remove(&path);
+                painted.insert(path.clone(), state);
+                let input_path = path.to_string_lossy().into_owned();
+
+                let mut progress = progress.lock().unwrap();
+                progress.queued += 1;
+                let output_path = Path::new(output_directory).join(fill_name_template(
+                    name_template,
+                    &name_fields(sub_matches, &path, progress.queued),
+                )?);
+                let output_path = match written.get(&output_path) {
+                    Some(written_path) => written_path.clone(),
+                    None => {
+                        match resolve_output_path(sub_matches, &output_path.to_string_lossy()) {
+                            Ok(resolved_path) => {
+                                written.insert(output_path, resolved_path.clone());
+                                resolved_path
+                            }
+                            Err(message) => {
+                                progress.queued -= 1;
+                                eprintln!("{}", message);
+                                continue;
+                            }
+                        }
+                    }
+                };
+                println!(
+                    "Queued {} ({} of {} painted)",
+                    input_path, progress.finished, progress.queued
+                );
+                sender.send((input_path, output_path)).ok();
+            }
+
+            pending.retain(|path, _| path.exists());
+            thread::sleep(poll_interval);
+        }
+    })
+}
+
+/// A line read by `jobs`: an image to paint and the `painting` options to
+/// paint it with, by their long names without the dashes.
+#[derive(Deserialize)]
+struct PaintingJob {
+    input: String,
+    output: String,
+    #[serde(default)]
+    options: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The paths of a job, the `painting` command line it runs and the options
+/// its manifest records.
+type ParsedJob = (
+    String,
+    String,
+    ArgMatches,
+    serde_json::Map<String, serde_json::Value>,
+);
+
+/// Reads the job on one line of `jobs` input into its paths and the
+/// `painting` command line it runs.
+fn parse_painting_job(line: &str) -> Result<ParsedJob, String> {
+    let mut job: PaintingJob =
+        serde_json::from_str(line).map_err(|error| format!("Invalid job: {}", error))?;
+    // Seeded and recorded here rather than by `paint_and_record`, which
+    // would build the command line again on the worker thread of the job.
+    if job.options.contains_key("manifest") && !job.options.contains_key("seed") {
+        job.options.insert(
+            String::from("seed"),
+            serde_json::json!(rand::random::<u64>()),
+        );
+    }
+    let sub_matches = painting_matches(&job.input, &job.output, &job.options)?;
+    let options = match job.options.contains_key("manifest") {
+        true => painting_options(&sub_matches),
+        false => serde_json::Map::new(),
+    };
+
+    Ok((job.input, job.output, sub_matches, options))
+}
+
+/// Parses the `painting` command line painting `input` to `output` with
+/// `options`, flags by name with their values, as a job has them.
+fn painting_matches(
+    input: &str,
+    output: &str,
+    options: &serde_json::Map<String, serde_json::Value>,
+) -> Result<ArgMatches, String> {
+    let mut arguments = vec![
+        String::from("voronoi-painter"),
+        String::from("painting"),
+        String::from("--input"),
+        input.to_string(),
+        String::from("--output"),
+        output.to_string(),
+    ];
+    for (key, value) in options {
+        match value {
+            serde_json::Value::Null | serde_json::Value::Bool(false) => {}
+            serde_json::Value::Bool(true) => arguments.push(format!("--{}", key)),
+            // Joined to their flag, so that values like `-30` are not
+            // taken for flags.
+            serde_json::Value::String(text) => arguments.push(format!("--{}={}", key, text)),
+            serde_json::Value::Number(number) => arguments.push(format!("--{}={}", key, number)),
+            serde_json::Value::Array(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| match value {
+                        serde_json::Value::String(text) => Ok(text.clone()),
+                        serde_json::Value::Number(number) => Ok(number.to_string()),
+                        _ => Err(format!("Option `{}` must list strings or numbers", key)),
+                    })
+                    .collect::<Result<Vec<String>, String>>()?;
+                arguments.push(format!("--{}={}", key, values.join(",")));
+            }
+            serde_json::Value::Object(_) => {
+                return Err(format!("Option `{}` cannot be an object", key))
+            }
+        }
+    }
+
+    let matches = command_line()
+        .try_get_matches_from(expand_preset(arguments)?)
+        .map_err(|error| error.to_string())?;
+    match matches.subcommand() {
+        Some(("painting", sub_matches)) => Ok(sub_matches.clone()),
+        _ => Err(String::from("No known sub-command found")),
+    }
+}
+
+fn run_jobs(sub_matches: &ArgMatches) -> Result<(), String> {
+    let jobs = match required_value(sub_matches, "jobs")?.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => jobs,
+        _ => return Err(String::from("`--jobs` must be a positive whole number")),
+    };
+    apply_worker_threads(sub_matches, jobs)?;
+
+    let (sender, receiver) = mpsc::channel::<(usize, Result<ParsedJob, String>)>();
+    let receiver = Mutex::new(receiver);
+    let failed = Mutex::new(0usize);
+
+    thread::scope(|scope| -> Result<(), String> {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let next_job = receiver.lock().unwrap().recv();
+                let (line_number, job) = match next_job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+
+                let started = Instant::now();
+                let result = job.and_then(|(input, output, job_matches, options)| {
+                    catch_render_failure(|| {
+                        paint_and_record(&job_matches, &input, &output, options)
+                    })
+                    .map(|_| (input, output))
+                });
+                let seconds = (started.elapsed().as_secs_f64() * 1000f64).round() / 1000f64;
+                let report = match result {
+                    Ok((input, output)) => serde_json::json!({
+                        "line": line_number,
+                        "ok": true,
+                        "input": input,
+                        "output": output,
+                        "seconds": seconds,
+                    }),
+                    Err(message) => {
+                        *failed.lock().unwrap() += 1;
+                        serde_json::json!({
+                            "line": line_number,
+                            "ok": false,
+                            "error": message,
+                            "seconds": seconds,
+                        })
+                    }
+                };
+                println!("{}", report);
+            });
+        }
+
+        // Moved in so that the end of the input closes the queue. Jobs are
+        // parsed here, as building the command line needs a deeper stack
+        // than the painting threads get.
+        let sender = sender;
+        for (index, line) in io::stdin().lines().enumerate() {
+            let line = line.map_err(|error| format!("Could not read jobs: {}", error))?;
+            if !line.trim().is_empty() {
+                sender.send((index + 1, parse_painting_job(&line))).ok();
+            }
+        }
+
+        Ok(())
+    })?;
+
+    let failed = *failed.lock().unwrap();
+    match failed {
+        0 => Ok(()),
+        failed => Err(format!("{} jobs failed", failed)),
+    }
+}
+
+/// Expands directories among `paths` into the images they contain, sorted by
+/// name.
+fn collect_frame_paths(paths: Vec<&str>) -> Result<Vec<String>, String> {
+    let mut frame_paths = Vec::new();
+    for path in paths {
+        if !Path::new(path).is_dir() {
+            frame_paths.push(path.to_string());
+            continue;
+        }
+
+        let mut entries = fs::read_dir(path)
+            .and_then(|entries| entries.collect::<io::Result<Vec<fs::DirEntry>>>())
+            .map_err(|error| format!("Could not read frames directory {}: {}", path, error))?
+            .into_iter()
+            .map(|entry| entry.path())
+            .filter(|entry| entry.is_file() && image::ImageFormat::from_path(entry).is_ok())
+            .collect::<Vec<_>>();
+        entries.sort();
+        frame_paths.extend(
+            entries
+                .into_iter()
+                .map(|entry| entry.to_string_lossy().into_owned()),
+        );
+    }
+
+    Ok(frame_paths)
+}
+
+fn run_sequence(sub_matches: &ArgMatches) -> Result<(), String> {
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    apply_worker_threads(sub_matches, 1)?;
+    let encoder = parse_encoder_options(sub_matches)?;
+    let frame_paths =
+        collect_frame_paths(sub_matches.values_of("input").unwrap_or_default().collect())?;
+    let fps = match required_value(sub_matches, "fps")?.parse::<u32>() {
+        Ok(fps) if fps > 0 => fps,
+        _ => return Err(String::from("`--fps` must be a positive integer")),
+    };
+    let sprite_sheet = parse_sprite_sheet(sub_matches)?;
+
+    let mut frames = Vec::with_capacity(frame_paths.len());
+    paint_frames(
+        sub_matches,
+        frame_paths
+            .iter()
+            .map(|frame_path| Ok((frame_path.clone(), open_input_image(frame_path)?))),
+        parse_color_smoothing(sub_matches)?,
+        sub_matches.is_present("advect"),
+        |frame| {
+            frames.push(frame);
+            Ok(())
+        },
+    )?;
+
+    if let Some(grid) = sprite_sheet {
+        let delay = Delay::from_numer_denom_ms(1000, fps);
+        let frames: Vec<(RgbaImage, Delay)> =
+            frames.into_iter().map(|frame| (frame, delay)).collect();
+        return write_sprite_sheet(&frames, grid, output_path, &encoder);
+    }
+    write_animation(frames, fps, output_path, &encoder)
+        .map_err(|error| format!("Could not write sequence {}: {}", output_path, error))
+}
+
+fn run_video(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_path = required_value(sub_matches, "input")?;
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    apply_worker_threads(sub_matches, 1)?;
+    let info = probe(input_path)
+        .map_err(|error| format!("Could not read video {}: {}", input_path, error))?;
+    let frame_rate = match required_value(sub_matches, "fps")? {
+        "keep" => None,
+        fps => match fps.parse::<f64>() {
+            Ok(rate) if rate > 0f64 => Some(fps),
+            _ => return Err(String::from("`--fps` must be `keep` or a positive number")),
+        },
+    };
+    println!(
+        "Painting {}x{} video at {} frames per second",
+        info.width,
+        info.height,
+        frame_rate.unwrap_or(&info.frame_rate)
+    );
+
+    let reader = FrameReader::open(input_path, &info, frame_rate)
+        .map_err(|error| format!("Could not read video {}: {}", input_path, error))?;
+    let audio_from = (!sub_matches.is_present("no-audio")).then_some(input_path);
+    let mut writer: Option<FrameWriter> = None;
+    let write_error =
+        |error: io::Error| format!("Could not write video {}: {}", output_path, error);
+    let mut count = 0;
+    paint_frames(
+        sub_matches,
+        reader.enumerate().map(|(index, frame)| {
+            let frame =
+                frame.map_err(|error| format!("Could not read video {}: {}", input_path, error))?;
+            Ok((format!("{} of {}", index + 1, input_path), frame))
+        }),
+        parse_color_smoothing(sub_matches)?,
+        sub_matches.is_present("advect"),
+        |frame| {
+            if writer.is_none() {
+                writer = Some(
+                    FrameWriter::create(
+                        output_path,
+                        frame.width(),
+                        frame.height(),
+                        frame_rate.unwrap_or(&info.frame_rate),
+                        audio_from,
+                    )
+                    .map_err(write_error)?,
+                );
+            }
+            count += 1;
+            println!("Painted frame {}", count);
+            writer
+                .as_mut()
+                .unwrap()
+                .write_frame(&frame)
+                .map_err(write_error)
+        },
+    )?;
+
+    writer
+        .ok_or_else(|| String::from("No input frames found"))?
+        .finish()
+        .map_err(write_error)
+}
+
+fn parse_color_smoothing(sub_matches: &ArgMatches) -> Result<f64, String> {
+    match required_value(sub_matches, "color-smoothing")?.parse::<f64>() {
+        Ok(color_smoothing) if (0f64..1f64).contains(&color_smoothing) => Ok(color_smoothing),
+        _ => Err(String::from(
+            "`--color-smoothing` must be a number from 0 up to, but not including, 1",
+        )),
+    }
+}
+
+/// Tessellates `frames`, each named for error messages, with the same
+/// anchors placed on the first, handing every painted frame to `paint`.
+/// With `advect` the anchors follow the optical flow between frames.
+fn paint_frames(
+    sub_matches: &ArgMatches,
+    frames: impl Iterator<Item = Result<(String, RgbaImage), String>>,
+    color_smoothing: f64,
+    advect: bool,
+    mut paint: impl FnMut(RgbaImage) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut frames = frames.peekable();
+
+    let first_frame = match frames.peek() {
+        None => return Err(String::from("No input frames found")),
+        Some(Err(error)) => return Err(error.clone()),
+        Some(Ok((_, first_frame))) => crop_to_region(first_frame.clone(), sub_matches)?,
+    };
+    let (image_width, image_height) = first_frame.dimensions();
+
+    let color_space = parse_color_space(sub_matches)?;
+    let registry = ColorizerRegistry::with_color_space(color_space);
+    let colorizer = find_colorizer(&registry, sub_matches)?;
+    let palette = load_cell_palette(sub_matches)?;
+    let palette_space = parse_match_space(sub_matches)?.unwrap_or(color_space);
+    let palette_colorizer = palette.as_ref().map(|palette| PaletteColorizer {
+        colorizer,
+        palette,
+        color_space: palette_space,
+    });
+    let colorizer: &dyn CellColorizer = match &palette_colorizer {
+        None => colorizer,
+        Some(palette_colorizer) => palette_colorizer,
+    };
+    let tones = parse_tones(sub_matches)?;
+    let tone_colorizer = tones
+        .as_ref()
+        .map(|tones| ToneColorizer { colorizer, tones });
+    let colorizer: &dyn CellColorizer = match &tone_colorizer {
+        None => colorizer,
+        Some(tone_colorizer) => tone_colorizer,
+    };
+    let posterize_colorizer =
+        parse_posterize(sub_matches)?.map(|levels| PosterizeColorizer { colorizer, levels });
+    let colorizer: &dyn CellColorizer = match &posterize_colorizer {
+        None => colorizer,
+        Some(posterize_colorizer) => posterize_colorizer,
+    };
+    let metric = find_metric(sub_matches)?;
+    let minimum_distance = parse_minimum_distance(sub_matches, image_width, image_height)?;
+    let minimum_distance =
+        rescaled_minimum_distance(sub_matches, minimum_distance, (image_width, image_height))?;
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+    let sampler = find_sampler(sub_matches, &bounds, minimum_distance)?;
+    let mut rng = seeded_rng(sub_matches)?;
+    let anchor_points = load_or_generate_anchor_points(
+        sub_matches,
+        &first_frame,
+        sampler.as_ref(),
+        minimum_distance as f64,
+        &mut rng,
+    )?;
+    println!("Generated {} anchor points", anchor_points.len());
+
+    let options = RenderOptions {
+        minimum_distance: candidate_window(sub_matches, minimum_distance)?,
+        metric: metric.as_ref(),
+        colorizer,
+        smoothing: parse_smoothing(sub_matches)?,
+        antialias: parse_antialias(sub_matches)?,
+        round_corners: parse_round_corners(sub_matches)?,
+        min_cell_area: parse_min_cell_area(sub_matches)?,
+        relief: parse_relief(sub_matches)?,
+        match_space: parse_match_space(sub_matches)?.unwrap_or(ColorSpace::Cielab),
+        output_size: parse_output_size(sub_matches, image_width, image_height)?,
+        observer: None,
+        mask: None,
+        projection: Projection::Flat,
+        merge_threshold: parse_merge_threshold(sub_matches)?,
+        style: parse_cell_style(sub_matches)?,
+        assignment: parse_assignment(sub_matches, metric.as_ref(), Projection::Flat)?,
+        color_vision: parse_color_vision(sub_matches)?,
+        radii: None,
+        order: parse_higher_order(sub_matches)?,
+        script: None,
+        precision: parse_precision(sub_matches, metric.as_ref(), Projection::Flat)?,
+    };
+    check_higher_order(&options)?;
+    let mut sequence = FrameSequence::new(
+        sample_anchor_colors(
+            &first_frame,
+            anchor_points,
+            parse_color_sampling(sub_matches)?,
+        ),
+        image_width,
+        image_height,
+        color_smoothing,
+        &options,
+    );
+
+    let mut previous_frame: Option<RgbaImage> = None;
+    for frame in frames {
+        let (frame_name, frame) = frame?;
+        let frame = crop_to_region(frame, sub_matches)?;
+        if frame.dimensions() != (image_width, image_height) {
+            return Err(format!(
+                "Frame {} is {}x{}, but the sequence is {}x{}",
+                frame_name,
+                frame.width(),
+                frame.height(),
+                image_width,
+                image_height
+            ));
+        }
+        if let (true, Some(previous_frame)) = (advect, &previous_frame) {
+            let anchor_points: Vec<Point> = sequence
+                .anchors()
+                .iter()
+                .map(|anchor| anchor.point.clone())
+                .collect();
+            let displacements = track_points(
+                previous_frame,
+                &frame,
+                &anchor_points,
+                &FlowOptions::default(),
+            );
+            sequence.move_anchors(
+                &displacements,
+                sampler.try_sample(&bounds, &mut rng)?,
+                minimum_distance as f64,
+            );
+        }
+        paint(sequence.render_frame(&frame))?;
+        previous_frame = Some(frame);
+    }
+
+    Ok(())
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once(['x', 'X'])?;
+    let width = width.trim().parse::<u32>().ok()?;
+    let height = height.trim().parse::<u32>().ok()?;
+
+    if (width > 0) && (height > 0) {
+        Some((width, height))
+    } else {
+        None
+    }
+}
+
+/// Reads `--min-distance`, or `--min-distance-pct` as a percentage of the
+/// diagonal of a `width`×`height` image.
+fn parse_minimum_distance(
+    sub_matches: &ArgMatches,
+    width: u32,
+    height: u32,
+) -> Result<u32, String> {
+    if let Some(percentage) = sub_matches.value_of("min-distance-pct") {
+        let diagonal = (width as f64).hypot(height as f64);
+        return match percentage.trim().trim_end_matches('%').parse::<f64>() {
+            Ok(percentage) if percentage > 0f64 => {
+                Ok(((diagonal * percentage) / 100f64).round().max(1f64) as u32)
+            }
+            _ => Err(String::from(
+                "`--min-distance-pct` must be a positive percentage such as `1.5%`",
+            )),
+        };
+    }
+
+    match required_value(sub_matches, "min-distance")?.parse::<u32>() {
+        Ok(minimum_distance) if minimum_distance > 0 => Ok(minimum_distance),
+        _ => Err(String::from("`--min-distance` must be a positive integer")),
+    }
 }
 
-impl Point {
-    fn squared_distance_from(&self, other_point: &Point) -> f64 {
-        let horizontal_distance = (self.x - other_point.x).powf(2f64);
-        let vertical_distance = (self.y - other_point.y).powf(2f64);
+/// Pixels of the strip from the middle of the input `estimate` paints to
+/// calibrate.
+const CALIBRATION_PIXELS: u32 = 384 * 384;
 
-        horizontal_distance + vertical_distance
+fn describe_seconds(seconds: f64) -> String {
+    match seconds {
+        seconds if seconds < 1f64 => String::from("under a second"),
+        seconds if seconds < 90f64 => format!("about {:.0} s", seconds),
+        seconds if seconds < 5400f64 => format!("about {:.0} min", seconds / 60f64),
+        seconds => format!("about {:.1} h", seconds / 3600f64),
     }
+}
 
-    fn closest_anchor(
-        &self,
-        anchors: &Vec<Anchor>,
-        minimum_distance_between_anchors: u32,
-    ) -> Option<Anchor> {
-        let x = (minimum_distance_between_anchors as f64) / 2f64;
-        let x = x * x;
+fn describe_bytes(bytes: f64) -> String {
+    match bytes / (1024f64 * 1024f64) {
+        megabytes if megabytes < 1024f64 => format!("{:.0} MB", megabytes.max(1f64)),
+        megabytes => format!("{:.1} GB", megabytes / 1024f64),
+    }
+}
 
-        let mut closest_anchor: Option<(Anchor, f64)> = None;
-        for anchor in anchors {
-            let distance = self.squared_distance_from(&anchor.point);
-            if distance < x {
-                closest_anchor = Some((anchor.clone(), distance));
-            } else {
-                match closest_anchor {
-                    None => {
-                        closest_anchor = Some((anchor.clone(), distance));
-                    }
-                    Some((_, min_distance)) => {
-                        if min_distance > distance {
-                            closest_anchor = Some((anchor.clone(), distance));
-                        }
-                    }
-                }
-            }
-        }
+/// Predicts how many anchors painting the input with these parameters
+/// places, the memory it peaks at and how long it takes, by painting a strip
+/// of [`CALIBRATION_PIXELS`] from its middle and scaling up what that took
+/// by the area of the whole.
+///
+/// The strip runs the full height of the input, since every column searches
+/// the anchors near it from top to bottom and so takes longer the taller it
+/// is.
+fn run_estimate(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_image_path = required_value(sub_matches, "input")?;
+    apply_worker_threads(sub_matches, 1)?;
+    let started = Instant::now();
+    let input_image = crop_to_region(
+        open_painting_input(sub_matches, input_image_path)?,
+        sub_matches,
+    )?;
+    let decoding = started.elapsed().as_secs_f64();
+    let (width, height) = input_image.dimensions();
+    let minimum_distance = parse_minimum_distance(sub_matches, width, height)?;
+    let (output_width, output_height) =
+        parse_output_size(sub_matches, width, height)?.unwrap_or((width, height));
+    let encoder = parse_encoder_options(sub_matches)?;
+
+    let (calibration_width, calibration_height) =
+        ((CALIBRATION_PIXELS / height).clamp(1, width), height);
+    let calibration_image = imageops::crop_imm(
+        &input_image,
+        (width - calibration_width) / 2,
+        (height - calibration_height) / 2,
+        calibration_width,
+        calibration_height,
+    )
+    .to_image();
+    let enlarge = |length: u32, from: u32, to: u32| {
+        (((length as f64) * (to as f64)) / (from as f64))
+            .round()
+            .max(1f64) as u32
+    };
+    let calibration_output = (
+        enlarge(calibration_width, width, output_width),
+        enlarge(calibration_height, height, output_height),
+    );
+
+    let registry = ColorizerRegistry::with_color_space(parse_color_space(sub_matches)?);
+    let metric = find_metric(sub_matches)?;
+    let options = RenderOptions {
+        minimum_distance: candidate_window(sub_matches, minimum_distance)?,
+        metric: metric.as_ref(),
+        colorizer: find_colorizer(&registry, sub_matches)?,
+        smoothing: parse_smoothing(sub_matches)?,
+        antialias: parse_antialias(sub_matches)?,
+        round_corners: parse_round_corners(sub_matches)?,
+        min_cell_area: parse_min_cell_area(sub_matches)?,
+        relief: parse_relief(sub_matches)?,
+        match_space: parse_match_space(sub_matches)?.unwrap_or(ColorSpace::Cielab),
+        output_size: (calibration_output != (calibration_width, calibration_height))
+            .then_some(calibration_output),
+        style: parse_cell_style(sub_matches)?,
+        ..RenderOptions::default()
+    };
+    let bounds = Bounds {
+        width: calibration_width as u64,
+        height: calibration_height as u64,
+    };
+    let sampler = find_sampler(sub_matches, &bounds, minimum_distance)?;
+    let mut rng = seeded_rng(sub_matches)?;
 
-        closest_anchor.map(|(anchor, _)| anchor)
+    let started = Instant::now();
+    let anchors = sample_anchor_colors(
+        &calibration_image,
+        sampler.try_sample(&bounds, &mut rng)?,
+        parse_color_sampling(sub_matches)?,
+    );
+    let sampling = started.elapsed().as_secs_f64();
+    let started = Instant::now();
+    let painting = render_voronoi(&calibration_image, &anchors, &options);
+    let rendering = started.elapsed().as_secs_f64();
+    let started = Instant::now();
+    let mut encoded = Vec::new();
+    let output_format = sub_matches
+        .value_of("output")
+        .and_then(|output_path| ImageFormat::from_path(output_path).ok());
+    match output_format {
+        Some(ImageFormat::Jpeg) => {
+            JpegEncoder::new_with_quality(&mut encoded, encoder.jpeg_quality)
+                .encode_image(&painting)
+        }
+        _ => PngEncoder::new_with_quality(
+            &mut encoded,
+            encoder.png_compression,
+            FilterType::Adaptive,
+        )
+        .write_image(
+            painting.as_raw(),
+            painting.width(),
+            painting.height(),
+            ColorType::Rgba8,
+        ),
     }
-}
+    .map_err(|error| format!("Could not encode the calibration painting: {}", error))?;
+    let encoding = started.elapsed().as_secs_f64();
 
-#[derive(Clone)]
-struct Anchor {
-    point: Point,
-    color: Rgba<u8>,
-}
+    // Placing anchors grows with the area, and assigning, painting and
+    // encoding with the output pixels.
+    let area = ((width as f64) * (height as f64))
+        / ((calibration_width as f64) * (calibration_height as f64));
+    let anchor_count = (anchors.len() as f64) * area;
+    let output_pixels = (output_width as f64) * (output_height as f64);
+    let pixels = output_pixels / ((calibration_output.0 as f64) * (calibration_output.1 as f64));
+    let seconds = decoding + (sampling * area) + ((rendering + encoding) * pixels);
+
+    // Decoding, converting and labelling the input take about 24 bytes a
+    // pixel, painting and encoding the output about 12 and its own labels
+    // when it is another size 4 more, on top of the anchors and their colors.
+    let input_pixels = (width as f64) * (height as f64);
+    let output_labels = if (output_width, output_height) == (width, height) {
+        0f64
+    } else {
+        output_pixels * 4f64
+    };
+    let bytes = (input_pixels * 24f64)
+        + (output_pixels * 12f64)
+        + output_labels
+        + (anchor_count * ((mem::size_of::<Anchor>() + 4) as f64));
+
+    println!(
+        "Calibrated with {} anchors on a {}x{} strip in {:.2} s",
+        anchors.len(),
+        calibration_width,
+        calibration_height,
+        sampling + rendering + encoding
+    );
+    println!("Anchors: about {:.0}", anchor_count);
+    println!("Peak memory: about {}", describe_bytes(bytes));
+    println!("Render time: {}", describe_seconds(seconds));
 
-struct Bounds {
-    width: u64,
-    height: u64,
+    Ok(())
 }
 
-struct Distance {
-    minimum: u32,
-    maximum: u32,
+fn run_generate(sub_matches: &ArgMatches) -> Result<(), String> {
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    let (width, height) = parse_size(required_value(sub_matches, "size")?)
+        .ok_or_else(|| String::from("`--size` must look like `512x512`"))?;
+    let minimum_distance = parse_minimum_distance(sub_matches, width, height)?;
+    let feature = WorleyFeature::from_name(required_value(sub_matches, "feature")?)
+        .ok_or_else(|| String::from("`--feature` must be one of: f1, f2, f2-f1"))?;
+    let palette = match sub_matches.value_of("palette") {
+        None => Palette::grayscale(),
+        Some(colors) => Palette::parse_list(colors).ok_or_else(|| {
+            String::from("`--palette` must be a comma separated list of hex colors")
+        })?,
+    };
+
+    let bounds = Bounds {
+        width: width as u64,
+        height: height as u64,
+    };
+    let sampler = find_sampler(sub_matches, &bounds, minimum_distance)?;
+    let mut rng = seeded_rng(sub_matches)?;
+    let anchor_points = sampler.try_sample(&bounds, &mut rng)?;
+
+    let noise = worley_noise(&anchor_points, width, height, minimum_distance, feature);
+
+    save_output_image(
+        &paint_noise(&noise, height, &palette),
+        output_path,
+        sub_matches,
+    )
 }
 
-fn random_point_at_certain_distance_from_given_point(
-    source_point: &Point,
-    distance: &Distance,
-    bounds: &Bounds,
-) -> Point {
-    let mut rng = rand::thread_rng();
+fn run_art(sub_matches: &ArgMatches) -> Result<(), String> {
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    let palette_path = required_value(sub_matches, "palette")?;
+    let (width, height) = parse_size(required_value(sub_matches, "size")?)
+        .ok_or_else(|| String::from("`--size` must look like `1920x1080`"))?;
+    let minimum_distance = parse_minimum_distance(sub_matches, width, height)?;
+    let metric = find_metric(sub_matches)?;
+    let gradient = match sub_matches.value_of("gradient").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(angle)) => {
+            let jitter = required_value(sub_matches, "gradient-jitter")?
+                .parse::<f64>()
+                .map_err(|_| String::from("`--gradient-jitter` must be a number"))?;
+            Some(SpatialGradient { angle, jitter })
+        }
+        Some(Err(_)) => return Err(String::from("`--gradient` must be an angle in degrees")),
+    };
 
-    let angle = rng.gen::<f64>() * (2f64 * PI);
-    let actual_distance = (distance.minimum as f64)
-        + (rng.gen::<f64>() * ((distance.maximum - distance.minimum) as f64));
+    let palette = Palette::load(palette_path)
+        .map_err(|error| format!("Could not read palette {}: {}", palette_path, error))?;
 
-    let point = Point {
-        x: (actual_distance * angle.cos()) + source_point.x,
-        y: (actual_distance * angle.sin()) + source_point.y,
+    let bounds = Bounds {
+        width: width as u64,
+        height: height as u64,
     };
+    let sampler = find_sampler(sub_matches, &bounds, minimum_distance)?;
+    let mut rng = seeded_rng(sub_matches)?;
+    let anchor_points = sampler.try_sample(&bounds, &mut rng)?;
+    let anchors = color_anchors_from_palette(
+        anchor_points,
+        &bounds,
+        &palette,
+        gradient.as_ref(),
+        &mut rng,
+    );
 
-    let is_point_in_horizontal_bounds = (point.x > 0f64) && (point.x < (bounds.width as f64));
-    let is_point_in_vertical_bounds = (point.y > 0f64) && (point.y < (bounds.height as f64));
+    let options = RenderOptions {
+        minimum_distance: candidate_window(sub_matches, minimum_distance)?,
+        metric: metric.as_ref(),
+        ..RenderOptions::default()
+    };
+    let canvas = RgbaImage::new(width, height);
 
-    if is_point_in_horizontal_bounds && is_point_in_vertical_bounds {
-        point
-    } else {
-        random_point_at_certain_distance_from_given_point(source_point, distance, bounds)
-    }
+    save_output_image(
+        &render_voronoi(&canvas, &anchors, &options),
+        output_path,
+        sub_matches,
+    )
 }
 
-fn generate_anchor_candidates(
-    source_point: &Point,
-    distance: &Distance,
-    bounds: &Bounds,
-) -> Vec<Point> {
-    let mut candidates = Vec::with_capacity(25);
-
-    for _ in 0..25 {
-        candidates.push(random_point_at_certain_distance_from_given_point(
-            source_point,
-            distance,
-            bounds,
+fn run_palette_poster(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_image = open_input_image(required_value(sub_matches, "input")?)?;
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    let (width, height) = match sub_matches.value_of("size") {
+        None => input_image.dimensions(),
+        Some(size) => {
+            parse_size(size).ok_or_else(|| String::from("`--size` must look like `1920x1080`"))?
+        }
+    };
+    let count = match required_value(sub_matches, "colors")?.parse::<usize>() {
+        Ok(count) if count > 0 => count,
+        _ => return Err(String::from("`--colors` must be a positive integer")),
+    };
+    let iterations = required_value(sub_matches, "iterations")?
+        .parse::<u32>()
+        .map_err(|_| String::from("`--iterations` must be a non-negative integer"))?;
+
+    let (palette, shares) = color_shares(&input_image, count);
+    if palette.colors.is_empty() {
+        return Err(String::from(
+            "The input has no opaque pixels to take colors from",
         ));
     }
+    let bounds = Bounds {
+        width: width as u64,
+        height: height as u64,
+    };
+    let mut rng = seeded_rng(sub_matches)?;
+    let (sites, error) = area_weighted_layout(&bounds, &shares, iterations, &mut rng);
+    println!(
+        "Laid out {} colors with cell areas within {:.1}% of their shares",
+        sites.len(),
+        error * 100f64
+    );
 
-    candidates
+    save_output_image(
+        &paint_power_diagram(width, height, &sites, &palette.colors),
+        output_path,
+        sub_matches,
+    )
 }
 
-fn generate_anchor_points(bounds: &Bounds, minimum_distance: u32) -> Vec<Point> {
-    let mut rng = rand::thread_rng();
+fn run_render(sub_matches: &ArgMatches) -> Result<(), String> {
+    let anchors_path = required_value(sub_matches, "anchors")?;
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    let cache = read_anchor_cache(anchors_path)
+        .map_err(|error| format!("Could not read anchors {}: {}", anchors_path, error))?;
+    let (anchors, canvas, spacing) = match (cache.anchors(), cache.canvas, cache.spacing) {
+        (Some(anchors), Some(canvas), Some(spacing)) => (anchors, canvas, spacing),
+        _ => {
+            return Err(format!(
+                "Anchors {} have no colors, save them with `painting --cache-colors`",
+                anchors_path
+            ))
+        }
+    };
+    let (width, height) = match sub_matches.value_of("size") {
+        None => canvas,
+        Some(size) => {
+            parse_size(size).ok_or_else(|| String::from("`--size` must look like `1920x1080`"))?
+        }
+    };
+    let anchors = scale_anchors(&anchors, canvas, (width, height));
+    // Stretching the anchors stretches the distances between them as much.
+    let stretch = ((width as f64) / (canvas.0 as f64)).max((height as f64) / (canvas.1 as f64));
+    let minimum_distance = (spacing * stretch).ceil() as u32;
+
+    let metric = find_metric(sub_matches)?;
+    let options = RenderOptions {
+        minimum_distance: candidate_window(sub_matches, minimum_distance)?,
+        metric: metric.as_ref(),
+        smoothing: parse_smoothing(sub_matches)?,
+        antialias: parse_antialias(sub_matches)?,
+        round_corners: parse_round_corners(sub_matches)?,
+        min_cell_area: parse_min_cell_area(sub_matches)?,
+        relief: parse_relief(sub_matches)?,
+        match_space: parse_match_space(sub_matches)?.unwrap_or(ColorSpace::Cielab),
+        style: parse_cell_style(sub_matches)?,
+        ..RenderOptions::default()
+    };
+    println!("Loaded {} colored anchor points", anchors.len());
+    let canvas = RgbaImage::new(width, height);
 
-    let squared_minimum_distance = minimum_distance * minimum_distance;
+    save_output_image(
+        &render_voronoi(&canvas, &anchors, &options),
+        output_path,
+        sub_matches,
+    )
+}
 
-    let mut final_anchors: Vec<Point> = Vec::new();
-    let mut anchor_candidates: VecDeque<Point> = VecDeque::new();
+fn run_recolor(sub_matches: &ArgMatches) -> Result<(), String> {
+    let labels_path = required_value(sub_matches, "labels")?;
+    let palette_path = required_value(sub_matches, "palette")?;
+    let output_path = &resolve_output_path(sub_matches, required_value(sub_matches, "output")?)?;
+    if sub_matches.is_present("smooth") {
+        return Err(String::from(
+            "`--smooth` blends anchors a label map does not keep the spacing of, repaint with `painting` instead",
+        ));
+    }
 
-    let first_anchor = Point {
-        x: rng.gen::<f64>() * (bounds.width as f64),
-        y: rng.gen::<f64>() * (bounds.height as f64),
-    };
+    let cell_map = open_label_map(labels_path)?;
+    let palette: LabelPalette = fs::read(palette_path)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| serde_json::from_slice(&contents).map_err(|error| error.to_string()))
+        .map_err(|error| format!("Could not read palette {}: {}", palette_path, error))?;
+    let anchors = palette
+        .anchors()
+        .map_err(|error| format!("Invalid palette {}: {}", palette_path, error))?;
+    if (palette.width, palette.height) != (cell_map.width, cell_map.height) {
+        return Err(format!(
+            "Palette {} describes a {}x{} label map, but {} is {}x{}",
+            palette_path,
+            palette.width,
+            palette.height,
+            labels_path,
+            cell_map.width,
+            cell_map.height
+        ));
+    }
+    if let Some(label) = cell_map
+        .labels
+        .iter()
+        .find(|label| (**label != UNASSIGNED) && ((**label as usize) >= anchors.len()))
+    {
+        return Err(format!(
+            "Labels {} name cell {}, but palette {} lists {} cells",
+            labels_path,
+            label,
+            palette_path,
+            anchors.len()
+        ));
+    }
+    println!("Loaded {} labelled cells", anchors.len());
 
-    final_anchors.push(first_anchor.clone());
+    let color_space = parse_color_space(sub_matches)?;
+    let mut colors = match sub_matches.value_of("input") {
+        None => anchors.iter().map(|anchor| anchor.color).collect(),
+        Some(input_path) => {
+            let registry = ColorizerRegistry::with_color_space(color_space);
+            let colorizer = find_colorizer(&registry, sub_matches)?;
+            let input_image = open_input_image(input_path)?;
+            let input_image = if input_image.dimensions() == (cell_map.width, cell_map.height) {
+                input_image
+            } else {
+                imageops::resize(
+                    &input_image,
+                    cell_map.width,
+                    cell_map.height,
+                    imageops::FilterType::Triangle,
+                )
+            };
 
-    let distance = Distance {
-        minimum: minimum_distance,
-        maximum: minimum_distance * 2,
+            color_cells(&cell_map, &anchors, &input_image, colorizer)
+        }
     };
-    anchor_candidates.extend(generate_anchor_candidates(&first_anchor, &distance, bounds));
+    if let Some(snap_path) = sub_matches.value_of("snap-palette") {
+        let snap_palette = Palette::load(snap_path)
+            .map_err(|error| format!("Could not read palette {}: {}", snap_path, error))?;
+        for color in colors.iter_mut() {
+            *color = snap_palette.nearest(
+                *color,
+                parse_match_space(sub_matches)?.unwrap_or(color_space),
+            );
+        }
+    }
 
-    loop {
-        match anchor_candidates.pop_front() {
-            None => {
-                break;
-            }
-            Some(candidate) => {
-                let mut is_valid_anchor = true;
-                for anchor in &final_anchors {
-                    if anchor.squared_distance_from(&candidate) < (squared_minimum_distance as f64)
-                    {
-                        is_valid_anchor = false;
-                        break;
-                    }
-                }
+    let options = RenderOptions {
+        antialias: parse_antialias(sub_matches)?,
+        round_corners: parse_round_corners(sub_matches)?,
+        min_cell_area: parse_min_cell_area(sub_matches)?,
+        relief: parse_relief(sub_matches)?,
+        match_space: parse_match_space(sub_matches)?.unwrap_or(ColorSpace::Cielab),
+        style: parse_cell_style(sub_matches)?,
+        ..RenderOptions::default()
+    };
+
+    save_output_image(
+        &paint_voronoi(&cell_map, &anchors, colors, &options),
+        output_path,
+        sub_matches,
+    )
+}
 
-                if is_valid_anchor {
-                    final_anchors.push(candidate.clone());
+/// Reads a label map written by `--export-labels`.
+fn open_label_map(labels_path: &str) -> Result<CellMap, String> {
+    image::open(labels_path)
+        .map_err(|error| error.to_string())
+        .and_then(|labels| decode_label_map(&labels))
+        .map_err(|error| format!("Could not read labels {}: {}", labels_path, error))
+}
 
-                    match final_anchors.last() {
-                        None => {}
-                        Some(source) => {
-                            anchor_candidates
-                                .extend(generate_anchor_candidates(source, &distance, bounds));
-                        }
-                    }
+fn run_diff(sub_matches: &ArgMatches) -> Result<(), String> {
+    let first_path = required_value(sub_matches, "FIRST")?;
+    let second_path = required_value(sub_matches, "SECOND")?;
+    let tolerance = required_value(sub_matches, "tolerance")?
+        .parse::<u8>()
+        .map_err(|_| {
+            String::from("`--tolerance` must be a whole number of levels from 0 to 255")
+        })?;
+    let heatmap_path = match sub_matches.value_of("heatmap") {
+        None => None,
+        Some(heatmap_path) => Some(resolve_output_path(sub_matches, heatmap_path)?),
+    };
+
+    let first = open_input_image(first_path)?;
+    let second = open_input_image(second_path)?;
+    if first.dimensions() != second.dimensions() {
+        return Err(format!(
+            "{} is {}x{}, but {} is {}x{}",
+            first_path,
+            first.width(),
+            first.height(),
+            second_path,
+            second.width(),
+            second.height()
+        ));
+    }
+    let labels = match (
+        sub_matches.value_of("labels-a"),
+        sub_matches.value_of("labels-b"),
+    ) {
+        (Some(first_labels_path), Some(second_labels_path)) => {
+            let label_maps = [
+                (first_labels_path, open_label_map(first_labels_path)?),
+                (second_labels_path, open_label_map(second_labels_path)?),
+            ];
+            for (labels_path, cell_map) in &label_maps {
+                if (cell_map.width, cell_map.height) != first.dimensions() {
+                    return Err(format!(
+                        "Labels {} are {}x{}, but the renders are {}x{}",
+                        labels_path,
+                        cell_map.width,
+                        cell_map.height,
+                        first.width(),
+                        first.height()
+                    ));
                 }
             }
+            let [(_, first_labels), (_, second_labels)] = label_maps;
+            Some((first_labels, second_labels))
         }
+        _ => None,
+    };
+    let label_maps = labels.as_ref().map(|(first, second)| (first, second));
+
+    let diff = compare_renders(&first, &second, label_maps, tolerance);
+    let pixel_count = (first.width() as u64) * (first.height() as u64);
+    println!(
+        "{} of {} pixels differ ({:.3}%), by up to {} levels",
+        diff.differing_pixels,
+        pixel_count,
+        ((diff.differing_pixels as f64) * 100f64) / (pixel_count.max(1) as f64),
+        diff.largest_difference
+    );
+    if let Some(cells) = &diff.cells {
+        println!(
+            "{} of {} cells changed: {} in shape, {} in color ({} cells in {})",
+            cells.changed,
+            cells.first_count,
+            cells.reshaped,
+            cells.recolored,
+            cells.second_count,
+            second_path
+        );
+    }
+
+    if let Some(heatmap_path) = heatmap_path {
+        let heatmap = difference_heatmap(&first, &second, label_maps, tolerance);
+        write_image(
+            &heatmap,
+            Path::new(&heatmap_path),
+            &parse_encoder_options(sub_matches)?,
+        )
+        .map_err(|error| format!("Could not save heatmap {}: {}", heatmap_path, error))?;
+        println!("Saved the heatmap to {}", heatmap_path);
     }
 
-    final_anchors
+    let is_same =
+        (diff.differing_pixels == 0) && diff.cells.as_ref().is_none_or(|cells| cells.changed == 0);
+    match is_same {
+        true => Ok(()),
+        false => Err(format!("{} and {} differ", first_path, second_path)),
+    }
 }
 
-fn pixel_calculator(
-    x: u32,
-    image_height: u32,
-    anchors: Vec<Anchor>,
-    minimum_distance_between_anchors: u32,
-) -> Vec<(Point, Rgba<u8>)> {
-    let mut pixels: Vec<(Point, Rgba<u8>)> = Vec::with_capacity(image_height as usize);
+/// The image of an earlier recipe step, or the input, named by `from`, by
+/// default the image of the step before.
+fn recipe_source<'a>(
+    images: &'a HashMap<String, RgbaImage>,
+    step: &RecipeTable,
+    previous: &str,
+) -> Result<&'a RgbaImage, String> {
+    let from = match step.get("from") {
+        None => previous,
+        Some(from) => from.as_text().ok_or(format!(
+            "Step at line {}: `from` must be a string",
+            step.line
+        ))?,
+    };
 
-    let mut filtered_anchors: Vec<Anchor> = Vec::with_capacity(anchors.len());
+    images.get(from).ok_or(format!(
+        "Step at line {}: there is no image `{}` yet",
+        step.line, from
+    ))
+}
 
-    for anchor in anchors {
-        if (anchor.point.x > (((x as i64) - (minimum_distance_between_anchors as i64)) as f64))
-            && (anchor.point.x < (((x as i64) + (minimum_distance_between_anchors as i64)) as f64))
-        {
-            filtered_anchors.push(anchor);
-        }
-    }
+/// Paints `source` with the `painting` subcommand, every other setting of
+/// the step passed as its flag.
+fn run_recipe_tessellation(
+    source: &RgbaImage,
+    step: &RecipeTable,
+    work_dir: &Path,
+    name: &str,
+) -> Result<RgbaImage, String> {
+    let input_path = work_dir.join(format!("{}-input.png", name));
+    let output_path = work_dir.join(format!("{}.png", name));
+    source
+        .save(&input_path)
+        .map_err(|error| format!("Could not write {}: {}", input_path.display(), error))?;
 
-    for y in 0..image_height {
-        let point = Point {
-            x: x as f64,
-            y: y as f64,
-        };
-        let closest_anchor =
-            point.closest_anchor(&filtered_anchors, minimum_distance_between_anchors);
-        match closest_anchor {
-            None => {}
-            Some(anchor) => {
-                pixels.push((point, anchor.color));
+    let mut arguments = vec![
+        String::from("voronoi-painter"),
+        String::from("painting"),
+        String::from("--input"),
+        input_path.to_string_lossy().into_owned(),
+        String::from("--output"),
+        output_path.to_string_lossy().into_owned(),
+        String::from("--force"),
+    ];
+    for (key, value) in &step.settings {
+        match (key.as_str(), value) {
+            ("op" | "name" | "from", _) => {}
+            ("input" | "output", _) => {
+                return Err(format!(
+                    "Step at line {}: tessellations read `from` and are named by `name`, not `{}`",
+                    step.line, key
+                ))
+            }
+            (_, RecipeValue::Boolean(false)) => {}
+            (key, RecipeValue::Boolean(true)) => arguments.push(format!("--{}", key)),
+            (key, value) => {
+                arguments.push(format!("--{}", key));
+                arguments.push(value.to_argument());
             }
         }
     }
 
-    println!("Finished processing column: {}", x);
+    let arguments = expand_preset(arguments)
+        .map_err(|error| format!("Step at line {}: {}", step.line, error))?;
+    let matches = command_line()
+        .try_get_matches_from(arguments)
+        .map_err(|error| format!("Step at line {}: {}", step.line, error))?;
+    match matches.subcommand() {
+        Some(("painting", sub_matches)) => run_painting(sub_matches)?,
+        _ => return Err(String::from("No known sub-command found")),
+    }
 
-    pixels
+    open_input_image(&output_path.to_string_lossy())
 }
 
-fn read_anchor_points_from_file(anchors_cache_path: &str) -> std::io::Result<Vec<Point>> {
-    let mut anchor_points: Vec<Point> = Vec::new();
+fn run_recipe(sub_matches: &ArgMatches) -> Result<(), String> {
+    let recipe_path = required_value(sub_matches, "RECIPE")?;
+    let recipe = fs::read_to_string(recipe_path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| Recipe::parse(&text))
+        .map_err(|error| format!("Could not read recipe {}: {}", recipe_path, error))?;
+    let base = Path::new(recipe_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let relative = |path: &str| base.join(path).to_string_lossy().into_owned();
+    let encoder = parse_encoder_options(sub_matches)?;
 
-    let mut existing_anchor_file = File::open(anchors_cache_path)?;
+    let mut images: HashMap<String, RgbaImage> = HashMap::new();
+    if let Some(input) = recipe.settings.get("input") {
+        let input = input
+            .as_text()
+            .ok_or_else(|| String::from("The recipe `input` must be a path"))?;
+        images.insert(String::from("input"), open_input_image(&relative(input))?);
+    }
+    let work_dir = env::temp_dir().join(format!("voronoi-painter-recipe-{}", process::id()));
+    fs::create_dir_all(&work_dir)
+        .map_err(|error| format!("Could not create {}: {}", work_dir.display(), error))?;
 
-    let mut buffer: [u8; 8] = [0; 8];
+    let mut run_steps = || -> Result<(), String> {
+        let mut previous = String::from("input");
+        for (index, step) in recipe.steps.iter().enumerate() {
+            let op = step
+                .get("op")
+                .and_then(RecipeValue::as_text)
+                .ok_or(format!("Step at line {} needs an `op`", step.line))?;
+            let name = match step.get("name") {
+                None => format!("step-{}", index + 1),
+                Some(name) => name
+                    .as_text()
+                    .ok_or(format!(
+                        "Step at line {}: `name` must be a string",
+                        step.line
+                    ))?
+                    .to_string(),
+            };
+            println!("Running step {} ({})", name, op);
 
-    loop {
-        let x = match existing_anchor_file.read_exact(&mut buffer) {
-            Ok(_) => LittleEndian::read_f64(&buffer),
-            Err(_) => {
-                break;
-            }
-        };
-        let y = match existing_anchor_file.read_exact(&mut buffer) {
-            Ok(_) => LittleEndian::read_f64(&buffer),
-            Err(_) => {
-                break;
-            }
-        };
+            let image = match op {
+                "blur" => {
+                    let sigma = step
+                        .get("sigma")
+                        .and_then(RecipeValue::as_number)
+                        .filter(|sigma| *sigma > 0f64)
+                        .ok_or(format!(
+                            "Step at line {}: `blur` needs a positive `sigma`",
+                            step.line
+                        ))?;
+                    imageops::blur(recipe_source(&images, step, &previous)?, sigma as f32)
+                }
+                "tessellate" => run_recipe_tessellation(
+                    recipe_source(&images, step, &previous)?,
+                    step,
+                    &work_dir,
+                    &name,
+                )?,
+                "composite" => {
+                    let layers = match step.get("layers") {
+                        Some(RecipeValue::List(layers)) => layers
+                            .iter()
+                            .map(|layer| layer.as_text().and_then(|layer| images.get(layer)))
+                            .collect::<Option<Vec<&RgbaImage>>>(),
+                        _ => None,
+                    }
+                    .filter(|layers| !layers.is_empty())
+                    .ok_or(format!(
+                        "Step at line {}: `composite` needs `layers`, a list of earlier images",
+                        step.line
+                    ))?;
+                    let opacity = step
+                        .get("opacity")
+                        .map(|opacity| {
+                            opacity.as_number().ok_or(format!(
+                                "Step at line {}: `opacity` must be a number",
+                                step.line
+                            ))
+                        })
+                        .transpose()?
+                        .unwrap_or(1f64);
+                    let blend = step
+                        .get("blend")
+                        .map(|blend| {
+                            blend.as_text().and_then(BlendMode::from_name).ok_or(format!(
+                                "Step at line {}: `blend` must be one of: normal, multiply, screen, overlay, soft-light",
+                                step.line
+                            ))
+                        })
+                        .transpose()?
+                        .unwrap_or(BlendMode::Normal);
 
-        anchor_points.push(Point { x, y });
-    }
+                    layers[1..]
+                        .iter()
+                        .fold(layers[0].clone(), |below, above| {
+                            overlay(&below, above, opacity, blend)
+                        })
+                }
+                "save" => {
+                    let source = recipe_source(&images, step, &previous)?;
+                    let paths = match step.get("path") {
+                        Some(RecipeValue::List(paths)) => paths.iter().collect(),
+                        Some(path) => vec![path],
+                        None => Vec::new(),
+                    };
+                    if paths.is_empty() {
+                        return Err(format!("Step at line {}: `save` needs a `path`", step.line));
+                    }
+                    for path in paths {
+                        let path = path.as_text().ok_or(format!(
+                            "Step at line {}: `path` must be a string or a list of them",
+                            step.line
+                        ))?;
+                        let path = resolve_output_path(sub_matches, &relative(path))?;
+                        write_image(source, Path::new(&path), &encoder)
+                            .map_err(|error| format!("Could not save output image {}: {}", path, error))?;
+                        println!("Saved {}", path);
+                    }
+                    continue;
+                }
+                op => {
+                    return Err(format!(
+                        "Step at line {}: unknown op `{}`, expected one of: blur, tessellate, composite, save",
+                        step.line, op
+                    ))
+                }
+            };
+            images.insert(name.clone(), image);
+            previous = name;
+        }
+
+        Ok(())
+    };
+    let result = run_steps();
+    fs::remove_dir_all(&work_dir).ok();
+
+    result
+}
+
+#[cfg(feature = "window")]
+fn run_edit(sub_matches: &ArgMatches) -> Result<(), String> {
+    let input_image_path = required_value(sub_matches, "input")?;
+    let anchors_path = required_value(sub_matches, "anchors")?.to_string();
+    let input_image = crop_to_region(open_input_image(input_image_path)?, sub_matches)?;
+
+    let anchor_points = match read_anchor_points_from_file(&anchors_path) {
+        Ok(anchor_points) => anchor_points,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(error) => {
+            return Err(format!(
+                "Could not read anchors {}: {}",
+                anchors_path, error
+            ))
+        }
+    };
+    println!("Loaded {} anchor points", anchor_points.len());
+
+    let canvas = input_image.dimensions();
+    let source_hash = image_hash(&input_image);
+    let save = |anchor_points: &[Point]| match write_anchor_cache(
+        &AnchorCache {
+            canvas: Some(canvas),
+            source_hash: Some(source_hash),
+            points: anchor_points.to_vec(),
+            ..AnchorCache::default()
+        },
+        &anchors_path,
+    ) {
+        Ok(_) => println!(
+            "Saved {} anchor points to {}",
+            anchor_points.len(),
+            anchors_path
+        ),
+        Err(error) => eprintln!("Could not save anchors {}: {}", anchors_path, error),
+    };
+    let registry = ColorizerRegistry::with_builtins();
+    let metric = find_metric(sub_matches)?;
+    let options = RenderOptions {
+        minimum_distance: candidate_window(sub_matches, RenderOptions::default().minimum_distance)?,
+        colorizer: find_colorizer(&registry, sub_matches)?,
+        metric: metric.as_ref(),
+        ..RenderOptions::default()
+    };
+    let anchor_points = edit_anchors(&input_image, anchor_points, &options, save)
+        .map_err(|error| format!("Could not open editor window: {}", error))?;
+    save(&anchor_points);
 
-    Ok(anchor_points)
+    Ok(())
 }
 
-fn write_anchor_points_to_file(
-    anchor_points: Vec<Point>,
-    anchors_cache_path: &str,
-) -> std::io::Result<()> {
-    let mut anchor_file = File::create(anchors_cache_path)?;
+#[cfg(feature = "window")]
+fn edit_subcommands() -> Vec<Command<'static>> {
+    vec![Command::new("edit")
+        .about("Place, move and delete anchors by hand over a preview of the input")
+        .arg(arg!(-i --input <VALUE>).required(true))
+        .arg(arg!(-a --anchors <FILE> "Anchor cache to load and save").required(true))
+        .arg(
+            arg!(--region <RECT> "Only edit the `x,y,width,height` crop of the input")
+                .required(false),
+        )
+        .arg(
+            arg!(--"color-mode" <MODE> "How previewed cells are filled: anchor, mean, median or dominant")
+                .required(false)
+                .default_value("anchor"),
+        )
+        .arg(
+            arg!(--metric <METRIC> "Distance used to assign pixels to previewed cells")
+                .required(false)
+                .possible_values(["euclidean", "manhattan", "chebyshev"])
+                .default_value("euclidean"),
+        )
+        .arg(aspect_arg())]
+}
 
-    let mut buffer = [0; 8];
+#[cfg(not(feature = "window"))]
+fn edit_subcommands() -> Vec<Command<'static>> {
+    Vec::new()
+}
 
-    for anchor in anchor_points {
-        LittleEndian::write_f64(&mut buffer, anchor.x);
-        match anchor_file.write_all(&buffer) {
-            Ok(_) => {}
-            Err(_) => {}
+fn run_self_test() -> Result<(), String> {
+    let outcomes = selftest::run_self_test();
+    for outcome in &outcomes {
+        if outcome.passed() {
+            println!("pass  {}", outcome.name);
+        } else {
+            println!(
+                "FAIL  {}: expected {:016x}, painted {:016x}",
+                outcome.name, outcome.expected, outcome.actual
+            );
         }
-        LittleEndian::write_f64(&mut buffer, anchor.y);
-        match anchor_file.write_all(&buffer) {
-            Ok(_) => {}
-            Err(_) => {}
+    }
+
+    match outcomes.iter().filter(|outcome| !outcome.passed()).count() {
+        0 => {
+            println!("All {} self tests passed", outcomes.len());
+            Ok(())
         }
+        failed => Err(format!(
+            "{} of {} self tests painted something else than a known good build",
+            failed,
+            outcomes.len()
+        )),
     }
+}
 
-    Ok(())
+fn run_serve(sub_matches: &ArgMatches) -> Result<(), String> {
+    let address = required_value(sub_matches, "address")?;
+
+    serve(address).map_err(|error| format!("Could not start server on {}: {}", address, error))
 }
 
-fn main() {
-    let arguments = Command::new("voronoi-painter")
+#[cfg(unix)]
+fn run_daemon(sub_matches: &ArgMatches) -> Result<(), String> {
+    let socket_path = required_value(sub_matches, "socket")?;
+    keep_decoded_caches(true);
+
+    serve_jobs(Path::new(socket_path), |args| {
+        let arguments = expand_preset(
+            iter::once(String::from("voronoi-painter"))
+                .chain(args)
+                .collect(),
+        )?;
+        let matches = command_line()
+            .try_get_matches_from(arguments)
+            .map_err(|error| error.to_string())?;
+        match matches.subcommand_name() {
+            Some("daemon") => Err(String::from("A daemon cannot start another daemon")),
+            _ => run_command(&matches),
+        }
+    })
+    .map_err(|error| format!("Could not listen on {}: {}", socket_path, error))
+}
+
+#[cfg(unix)]
+fn daemon_subcommands() -> Vec<Command<'static>> {
+    vec![Command::new("daemon")
+        .about("Keep running and paint the jobs sent as JSON lines to a Unix socket, keeping decoded anchor caches between them")
+        .arg(
+            arg!(--socket <PATH> "Unix socket to listen on")
+                .required(false)
+                .default_value("voronoi-painter.sock"),
+        )]
+}
+
+#[cfg(not(unix))]
+fn daemon_subcommands() -> Vec<Command<'static>> {
+    Vec::new()
+}
+
+#[cfg(feature = "window")]
+fn watch_render_args() -> Vec<Arg<'static>> {
+    vec![arg!(--"watch-render" "Show the render in a window as columns complete").required(false)]
+}
+
+#[cfg(not(feature = "window"))]
+fn watch_render_args() -> Vec<Arg<'static>> {
+    Vec::new()
+}
+
+/// How the cells are drawn, for every command that renders anchors.
+fn cell_style_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--style <STYLE> "Fill the cells, only draw the outlines between them, fill them and lead them like stained glass, or stipple a disc sized by its area at the centroid of each")
+            .required(false)
+            .possible_values(["fill", "outline", "stained-glass", "stipple"])
+            .default_value("fill"),
+        arg!(--fill <PATTERN> "Shade the cells with a pattern whose ink follows their darkness")
+            .required(false)
+            .possible_values(["solid", "hatch", "cross-hatch", "dots"])
+            .default_value("solid"),
+        arg!(--"pattern-spacing" <PIXELS> "Distance between the lines or dots of `--fill` patterns")
+            .required(false)
+            .default_value("6"),
+        arg!(--"stroke-width" <PIXELS> "Width of the outlines drawn by `--style outline` and the leading of `stained-glass`")
+            .required(false)
+            .default_value("1"),
+        arg!(--"stroke-color" <HEX> "Color of the outlines drawn by `--style outline` and the leading of `stained-glass`")
+            .required(false)
+            .default_value("#000000"),
+        arg!(--"outline-background" <BACKGROUND> "What `--style outline`, `--style stipple` and pattern fills are drawn on: white, transparent or a `#RRGGBB` color")
+            .required(false)
+            .default_value("white"),
+        arg!(--smooth <K> "Blend every pixel between its K nearest anchors for soft edges")
+            .required(false),
+        arg!(--antialias <N> "Supersample pixels on cell boundaries with an NxN grid")
+            .required(false),
+        arg!(--"round-corners" <RADIUS> "Round off the corners where cells meet within this many pixels, for soft pebbles instead of sharp shards")
+            .required(false)
+            .conflicts_with_all(&["smooth", "antialias"]),
+arg!(--bevel <DEPTH> "Raise every cell into a tile whose edges slope down over this many pixels, lit from the top left")
+            .required(false),
+        arg!(--"cell-shadow" "Shade the gaps below and to the right of every raised tile with a soft shadow")
+            .required(false),
+                arg!(--"min-cell-area" <PX> "Merge the cells smaller than this many pixels of the painting into the neighbour closest to them in color, so no unreadable slivers are left")
+            .required(false),
+        arg!(--"match-space" <SPACE> "Space nearest palette colors and colors of cells to merge are found in: rgb, lab or oklab. Palettes match in `--color-space` and merges in lab by default")
+            .required(false)
+            .possible_values(["rgb", "lab", "oklab"]),
+    ]
+}
+
+/// How renders spread over worker threads and report their progress.
+fn worker_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--threads <COUNT> "Worker threads used by every render").required(false),
+        arg!(--"auto-tune" "Time the first columns of every render to pick how many worker threads to run and how many columns each takes at once")
+            .required(false)
+            .conflicts_with("threads"),
+        arg!(--"best-effort" "Leave the columns whose cell assignment fails unassigned and list them, instead of failing the render")
+            .required(false),
+        arg!(--progress <FORMAT> "Report finished columns as text lines, or as JSON lines on stderr with the phase, percent done, columns done and seconds left")
+            .required(false)
+            .possible_values(["text", "json"])
+            .default_value("text"),
+    ]
+}
+
+fn encoder_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--"jpeg-quality" <QUALITY> "Quality of JPEG outputs, from 1 to 100")
+            .required(false)
+            .default_value("75"),
+        arg!(--"png-compression" <LEVEL> "How hard PNG outputs are compressed")
+            .required(false)
+            .possible_values(["fast", "default", "best"])
+            .default_value("default"),
+        arg!(--"output-profile" <PROFILE> "Convert PNG, JPEG and TIFF outputs to this color space and tag them with its ICC profile: srgb, or display-p3 for wide gamut phone and tablet screens")
+            .required(false)
+            .possible_values(["srgb", "display-p3"]),
+    ]
+}
+
+fn sprite_sheet_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--spritesheet <GRID> "Pack the frames of the animation into one `COLUMNSxROWS` grid image at the output instead, plus a JSON descriptor of the frames next to it for game engines")
+            .required(false),
+    ]
+}
+
+fn overwrite_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--force "Overwrite outputs that already exist").required(false),
+        arg!(--suffix "Add `-1`, `-2`, ... to the names of outputs that already exist instead of failing")
+            .required(false)
+            .conflicts_with("force"),
+    ]
+}
+
+fn minimum_distance_args(default_pixels: &str) -> Vec<Arg<'_>> {
+    vec![
+        arg!(--"min-distance" <PIXELS> "Minimum distance between anchors")
+            .required(false)
+            .default_value(default_pixels),
+        arg!(--"min-distance-pct" <PERCENT> "Minimum distance between anchors as a percentage of the image diagonal, such as `1.5%`")
+            .required(false)
+            .conflicts_with("min-distance"),
+    ]
+}
+
+fn anchor_cache_args() -> Vec<Arg<'static>> {
+    vec![
+        arg!(--"strict-cache" "Fail instead of warning when the `--anchors` cache was made for a different image")
+            .required(false)
+            .requires("anchors"),
+        arg!(--"anchors-rescale" "Scale the `--anchors` cache and the minimum distance to the size of the input when it was placed on an image of another size")
+            .required(false)
+            .requires("anchors"),
+        arg!(--"anchors-required" "Fail instead of placing new anchors when the `--anchors` or `--cache-dir` cache does not exist yet")
+            .required(false),
+        arg!(--"cache-dir" <DIR> "Cache the anchors in this directory under a name made from the input, its size, the minimum distance, the sampling and the seed, and reuse them whenever those match")
+            .required(false)
+            .conflicts_with("anchors"),
+    ]
+}
+
+fn aspect_arg<'a>() -> Arg<'a> {
+    arg!(--aspect <RATIO> "Scale x and y distances by `FX:FY`, stretching cells into shards or slats")
+        .required(false)
+}
+
+fn preview_arg(command: Command) -> Command {
+    command.arg(
+        arg!(--show [PROTOCOL] "Display the result in the terminal: auto, kitty, iterm or sixel")
+            .required(false)
+            .possible_values(["auto", "kitty", "iterm", "sixel"])
+            .min_values(0)
+            .default_missing_value("auto"),
+    )
+}
+
+fn sampling_args(command: Command) -> Command {
+    command
+        .arg(
+            arg!(--sampling <STRATEGY> "How anchors are placed")
+                .required(false)
+                .possible_values(SAMPLER_NAMES)
+                .default_value("poisson"),
+        )
+        .arg(
+            arg!(--jitter <FRACTION> "How far jittered-grid anchors stray from their cell centers, from 0 to 1")
+                .required(false)
+                .default_value("0.5"),
+        )
+        .arg(
+            arg!(--"max-distance" <PIXELS> "How far from an anchor Poisson-disk sampling places the next ones, twice the minimum distance by default: closer packs them more evenly, farther places fewer faster")
+                .required(false),
+        )
+        .arg(arg!(--seed <VALUE> "Seed for reproducible anchor placement").required(false))
+        .arg(
+            arg!(--"seed-border" "Seed anchors along the borders and in the corners, so edge cells are as large as the others")
+                .required(false),
+        )
+}
+
+fn tessellation_args(command: Command<'static>) -> Command<'static> {
+    sampling_args(command)
+        .args(minimum_distance_args("10"))
+        .arg(arg!(-a --anchors <VALUE>).required(false))
+        .args(anchor_cache_args())
+        .arg(
+            arg!(--"color-mode" <MODE> "How cells are filled: anchor, mean, median or dominant")
+                .required(false)
+                .default_value("anchor"),
+        )
+        .arg(
+            arg!(--"color-space" <SPACE> "Space the mean and dominant color modes average and cluster colors in, and `--palette` matches them in")
+                .required(false)
+                .possible_values(["srgb", "linear", "oklab", "cielab"])
+                .default_value("srgb"),
+        )
+        .arg(
+            arg!(--"anchor-sampling" <SAMPLING> "How the anchor color mode reads the input between pixel centers")
+                .required(false)
+                .possible_values(["nearest", "bilinear"])
+                .default_value("nearest"),
+        )
+        .arg(
+            arg!(--"sample-radius" <PIXELS> "Color every anchor with the mean of the input pixels within this radius of it instead of the one under it")
+                .required(false)
+                .conflicts_with("anchor-sampling"),
+        )
+        .arg(
+            arg!(--metric <METRIC> "Distance used to assign pixels to cells")
+                .required(false)
+                .possible_values(["euclidean", "manhattan", "chebyshev"])
+                .default_value("euclidean"),
+        )
+        .arg(
+            arg!(--"metric-blend" <WEIGHTS> "Assign pixels by a weighted mix of metrics instead, like `euclidean:0.7,manhattan:0.3`, for cells between their shapes")
+                .required(false),
+        )
+        .arg(aspect_arg())
+        .arg(
+            arg!(--assignment <METHOD> "Find the closest anchor of every pixel by searching the nearby anchors, or with a Euclidean distance transform whose time does not grow with the number of anchors, for hundreds of thousands of cells")
+                .required(false)
+                .possible_values(["search", "distance-transform"])
+                .default_value("search"),
+        )
+        .arg(
+            arg!(--precision <PRECISION> "Measure Euclidean distances between pixels and anchors in f64, reproducing earlier renders exactly, or in f32, reading half the memory for huge anchor sets at the cost of an odd pixel between two cells going to the other one")
+                .required(false)
+                .possible_values(["f64", "f32"])
+                .default_value("f64"),
+        )
+        .arg(
+            arg!(--order <K> "Assign every pixel among its K nearest anchors instead of the closest, for the intricate overlapping cells of a higher-order diagram")
+                .required(false),
+        )
+        .arg(
+            arg!(--"order-coloring" <MODE> "Color `--order` pixels by their K-th nearest anchor, or by one of their K nearest picked by a hash of the set so every region sharing it is one color")
+                .required(false)
+                .possible_values(["kth", "set"])
+                .default_value("kth"),
+        )
+        .arg(
+            arg!(--"merge-threshold" <DELTA_E> "Merge neighbouring cells whose colors differ by less than this CIELAB ΔE into one region, or this many hundredths of the distance in `--match-space` rgb or oklab")
+                .required(false),
+        )
+        .arg(
+            arg!(--"cvd-safe" <DEFICIENCY> "Nudge apart the lightness of neighbouring cells this color vision deficiency would make look alike")
+                .required(false)
+                .possible_values(["deuteranopia", "protanopia", "tritanopia"]),
+        )
+        .arg(
+            arg!(--palette <FILE> "Snap cell colors to the nearest entry of a hex, GPL or ASE palette file")
+                .required(false)
+                .conflicts_with("merge-threshold"),
+        )
+        .arg(
+            arg!(--posterize <LEVELS> "Round every channel of the cell colors to this many levels, flattening gradients into bold bands")
+                .required(false),
+        )
+        .arg(
+            arg!(--duotone <TONES> "Color every cell on a ramp from a dark to a light tone by its lightness, like `dark=#112233,light=#ffeecc`")
+                .required(false)
+                .conflicts_with("palette"),
+        )
+        .arg(
+            arg!(--tritone <TONES> "Like `--duotone` with a middle tone, as `dark=#112233,mid=#cc5544,light=#ffeecc`")
+                .required(false)
+                .conflicts_with_all(&["palette", "duotone"]),
+        )
+        .args(cell_style_args())
+        .arg(
+            arg!(--region <RECT> "Only tessellate the `x,y,width,height` crop of the input")
+                .required(false),
+        )
+        .arg(
+            arg!(--"output-scale" <FACTOR> "Rasterize the cells at this multiple of the input resolution")
+                .required(false),
+        )
+        .arg(
+            arg!(--"output-size" <WIDTHxHEIGHT> "Rasterize the cells at this exact size")
+                .required(false)
+                .conflicts_with("output-scale"),
+        )
+        .arg(
+            arg!(--"print-size" <SIZE> "Rasterize the cells to fill an A3, A4, A5, letter, legal or `50x70cm` print at `--dpi`, turned to match the image")
+                .required(false)
+                .conflicts_with_all(&["output-scale", "output-size"]),
+        )
+        .arg(
+            arg!(--dpi <DPI> "Resolution the image pixels are printed or cut at")
+                .required(false)
+                .default_value("300"),
+        )
+        .args(worker_args())
+}
+
+fn painting_args(command: Command<'static>) -> Command<'static> {
+    preview_arg(tessellation_args(command))
+    .args(sprite_sheet_args())
+    .arg(
+        arg!(--preset <NAME> "Start from the flags of a named look, which the other flags override")
+            .required(false)
+            .possible_values(PRESETS.iter().map(|preset| preset.name)),
+    )
+    .arg(
+        arg!(--levels <COUNT> "Subdivide every cell into finer voronoi diagrams this many times")
+            .required(false)
+            .default_value("1"),
+    )
+    .arg(
+        arg!(--"level-distances" <PIXELS> "Comma separated minimum distance of each level, coarsest first")
+            .required(false),
+    )
+    .arg(
+        arg!(--"nested-colors" <MODE> "Whether nested cells inherit their parent's color or resample the image")
+            .required(false)
+            .possible_values(["inherit", "resample"])
+            .default_value("resample"),
+    )
+    .arg(
+        arg!(--"cvt-iterations" <COUNT> "Relax anchors towards gradient-weighted cell centroids, aligning cells with edges")
+            .required(false),
+    )
+    .arg(
+        arg!(--"cvt-threshold" <PIXELS> "Stop relaxing once no anchor moves further than this")
+            .required(false)
+            .default_value("0.5"),
+    )
+    .arg(
+        arg!(--"refine-passes" <COUNT> "Add anchors inside cells whose colors vary too much, re-tessellating up to this many times")
+            .required(false),
+    )
+    .arg(
+        arg!(--"variance-threshold" <VARIANCE> "Color variance, with channels from 0 to 1, above which cells are split")
+            .required(false)
+            .default_value("0.01"),
+    )
+    .arg(
+        arg!(--"target-error" <ERROR> "Keep adding anchors where the cells differ most from the input until reaching `psnr:DECIBELS` or `mse:ERROR`")
+            .required(false),
+    )
+    .arg(
+        arg!(--"optimize" <ITERATIONS> "Move anchors where the cells differ most from the input, keeping the moves that lower the error, for up to this many iterations")
+            .required(false),
+    )
+    .arg(
+        arg!(--"max-anchors" <COUNT> "Stop `--refine-passes` or `--target-error` once there are this many anchors")
+            .required(false),
+    )
+    .arg(
+            arg!(--"random-palette" <COUNT> "Color the cells from a generated palette of this many harmonious colors instead of the input")
+                .required(false)
+                .conflicts_with_all(&["palette", "color-source", "duotone", "tritone"]),
+        )
+        .arg(
+            arg!(--"hue-range" <RANGE> "Hues, as `from..to` degrees, the random palette is drawn from")
+                .required(false)
+                .default_value("0..360"),
+        )
+        .arg(
+            arg!(--"color-source" <FILE> "Take the cell colors from this image instead, scaled to the input")
+                .required(false),
+        )
+        .arg(
+            arg!(--brightness <AMOUNT> "Brighten the input, or darken it below 0, by this fraction of full range from -1 to 1 before tessellating it")
+                .required(false)
+                .default_value("0"),
+        )
+        .arg(
+            arg!(--contrast <FACTOR> "Spread the input channels this many times further from mid gray, below 1 to flatten them")
+                .required(false)
+                .default_value("1"),
+        )
+        .arg(
+            arg!(--saturation <FACTOR> "Spread the input colors this many times further from gray, 0 for grayscale")
+                .required(false)
+                .default_value("1"),
+        )
+        .arg(
+            arg!(--gamma <GAMMA> "Brighten the midtones of the input above 1, or darken them below")
+                .required(false)
+                .default_value("1"),
+        )
+        .arg(
+            arg!(--"auto-levels" "Sample the cell colors from a copy of the input with every channel stretched to the full range, for washed out scans")
+                .required(false),
+        )
+        .arg(
+            arg!(--equalize "Sample the cell colors from a copy of the input with its brightness spread evenly from black to white, for the most contrast")
+                .required(false)
+                .conflicts_with("auto-levels"),
+        )
+        .arg(
+            arg!(--"pre-blur" <SIGMA> "Sample the cell colors from a copy of the input blurred this much, against sensor noise and JPEG artifacts, while cells still follow the sharp input")
+                .required(false),
+        )
+        .arg(
+        arg!(--"depth-map" <FILE> "Grayscale depth image: bright (near) areas get small cells, dark (far) areas large ones")
+            .required(false),
+    )
+    .arg(
+        arg!(--"far-distance" <PIXELS> "Minimum distance between anchors in the farthest areas of `--depth-map`, 4 times the minimum distance by default")
+            .required(false)
+            .requires("depth-map"),
+    )
+    .arg(
+        arg!(--"auto-detail" <STRENGTH> "Place small cells along the edges of the input and up to 4 times larger ones in flat areas; above 1 weaker edges get small cells too")
+            .required(false),
+    )
+    .arg(
+        arg!(--"subject-mask" <FILE> "Segmentation image: the white subject gets cells `--min-distance` apart and the background ones `--background-distance` apart")
+            .required(false),
+    )
+    .arg(
+        arg!(--"background-distance" <PIXELS> "Minimum distance between anchors outside `--subject-mask`, 4 times the minimum distance by default")
+            .required(false)
+            .requires("subject-mask"),
+    )
+    .arg(
+        arg!(--"orient-cells" <STRETCH> "Elongate cells up to this many times along the local edges of the input, like brush strokes")
+            .required(false),
+    )
+    .arg(
+        arg!(--"snap-edges" <PIXELS> "Move every anchor onto the closest strong edge within this radius")
+            .required(false),
+    )
+    .arg(
+        arg!(--"snap-threshold" <FRACTION> "Edge strength, relative to the strongest edge, that anchors snap to")
+            .required(false)
+            .default_value("0.3"),
+    )
+    .arg(
+        arg!(--"shape-mask" <FILE> "Only place and draw cells inside the white area of this image")
+            .required(false),
+    )
+    .arg(
+        arg!(--points <FILE> "Place an anchor at every location of this GPX file or CSV of `lat` and `lon` columns, on an input map spanning `--map-bounds`")
+            .required(false)
+            .conflicts_with_all(&["anchors", "region"]),
+    )
+    .arg(
+        arg!(--"map-bounds" <BOUNDS> "Longitudes and latitudes of the edges of the input map `--points` are placed on, as `west,south,east,north`")
+            .required(false)
+            .allow_hyphen_values(true)
+            .requires("points"),
+    )
+    .arg(
+        arg!(--"map-projection" <PROJECTION> "How the input map of `--points` lays out latitudes: evenly, or stretched towards the poles like web maps")
+            .required(false)
+            .possible_values(["equirectangular", "mercator"])
+            .default_value("equirectangular"),
+    )
+    .arg(
+        arg!(--script <FILE> "Run this cell script for every cell, choosing its fill and border from its anchor, area, color and neighbors")
+            .required(false),
+    )
+    .arg(
+        arg!(--"second-pass" <PIXELS> "Tessellate the painting again with anchors this far apart, finer or coarser than the first pass, for a layered fracturing effect; exports describe the first pass")
+            .required(false),
+    )
+    .arg(
+        arg!(--"second-pass-opacity" <OPACITY> "Opacity the `--second-pass` is laid over the first pass at, from 0 to 1")
+            .required(false)
+            .default_value("1")
+            .requires("second-pass"),
+    )
+    .arg(
+        arg!(--"preserve-mask" <FILE> "Keep the input untouched inside the white area of this image, such as a face, and tessellate only the rest")
+            .required(false),
+    )
+    .arg(
+        arg!(--"preserve-feather" <PIXELS> "Fade between the kept input and the cells over this many pixels at the border of `--preserve-mask`")
+            .required(false)
+            .default_value("0")
+            .requires("preserve-mask"),
+    )
+    .arg(
+        arg!(--"overlay-opacity" <OPACITY> "Lay the cells over the input at this opacity, from 0 to 1, for a fractured glass look")
+            .required(false),
+    )
+    .arg(
+        arg!(--blend <MODE> "Blend the cells into the input instead of covering it")
+            .required(false)
+            .possible_values(["normal", "multiply", "screen", "overlay", "soft-light"]),
+    )
+    .arg(
+        arg!(--compare <FILE> "Also write the input and the painting together for a before and after view")
+            .required(false),
+    )
+    .arg(
+        arg!(--"compare-layout" <LAYOUT> "How `--compare` shows both images")
+            .required(false)
+            .possible_values(["side-by-side", "split", "diagonal"])
+            .default_value("side-by-side"),
+    )
+    .arg(
+        arg!(--divider <FRACTION> "Where the `split` comparison changes from the input to the painting, from 0 (left) to 1 (right)")
+            .required(false)
+            .default_value("0.5"),
+    )
+    .arg(
+        arg!(--metrics [FILE] "Print the PSNR and SSIM of the painting against the input, and append them as a JSON line to FILE if given")
+            .required(false)
+            .min_values(0),
+    )
+    .arg(
+        arg!(--styles <STYLES> "Also paint the cells in these comma separated styles from the same assignment, as `NAME-STYLE` images in `--output-dir`: mosaic, outline, stained-glass, stipple, hatch, cross-hatch or dots")
+            .required(false)
+            .requires("output-dir"),
+    )
+    .arg(
+        arg!(--"cache-colors" "Save the colors of the final anchors in the `--anchors` cache, and paint with the colors of a cache saved like this instead of the input's, so `render` can repaint it without the input")
+            .required(false)
+            .requires("anchors"),
+    )
+    .arg(
+        arg!(--weighting <WEIGHTING> "Weight the cells by the radii saved with the `--anchors` cache: none, or additive for cells reaching as much further as their anchor's radius, bulging into their neighbours with curved boundaries like soap bubbles")
+            .required(false)
+            .possible_values(["none", "additive"])
+            .default_value("none"),
+    )
+    .arg(
+        arg!(--page <N> "Tessellate this page of a multi-page TIFF input, the first being 1")
+            .required(false),
+    )
+    .arg(
+        arg!(--"psd-layer" <NAME> "Tessellate only this layer of a PSD or PSB input instead of its flattened composite")
+            .required(false)
+            .conflicts_with("page"),
+    )
+    .arg(
+        arg!(--"original-page" "Write the painting to a `.tif` or `.tiff` output with the input, as it was tessellated, as its second page, for editors that show the pages as layers")
+            .required(false),
+    )
+    .arg(
+        arg!(--"snapshot-every" <INTERVAL> "While painting, write the columns assigned so far to `--snapshot` every this many seconds, like `30s`, or columns, like `500`")
+            .required(false),
+    )
+    .arg(
+        arg!(--snapshot <FILE> "PNG `--snapshot-every` writes to, by default NAME.snapshot.png next to the output")
+            .required(false)
+            .requires("snapshot-every"),
+    )
+    .arg(
+        arg!(--timings "Print how long decoding, placing anchors, sampling colors, assigning pixels and encoding took, and how busy the worker threads were")
+            .required(false),
+    )
+    .arg(
+        arg!(--background <BACKGROUND> "What fills the canvas where no cell is drawn, such as outside `--shape-mask`: transparent, a `#RRGGBB` color, or original for the input pixels")
+            .required(false)
+            .default_value("transparent"),
+    )
+    .arg(
+        arg!(--"export-cells" <FILE> "Also write the cell polygons and colors as GeoJSON")
+            .required(false),
+    )
+    .arg(
+        arg!(--simplify <TOLERANCE> "Drop the vertices of the exported cell outlines closer than this many pixels to a simpler outline, such as the stair steps traced around `--shape-mask`")
+            .required(false),
+    )
+    .arg(
+        arg!(--"smooth-paths" "Round every corner of the exported cell outlines into a curve")
+            .required(false),
+    )
+    .arg(
+        arg!(--"export-labels" <FILE> "Also write a PNG storing in every pixel the index of the cell owning it, as 16-bit gray or, past 65535 cells, a 32-bit big endian RGBA value, and the anchor and color of every cell to a JSON palette next to it")
+            .required(false),
+    )
+    .arg(
+        arg!(--"export-cell-stats" <FILE> "Also write a CSV row for every cell with its anchor, area and perimeter in pixels, the mean and variance of the input colors it covers and how many cells it touches")
+            .required(false),
+    )
+    .arg(
+        arg!(--"label-cells" "Write the index of every cell at its centroid, as for assembling a mosaic")
+            .required(false),
+    )
+    .arg(
+        arg!(--"export-pdf" <FILE> "Also write the cells as vector paths to a PDF file")
+            .required(false),
+    )
+    .arg(
+        arg!(--"page-size" <SIZE> "Fit the PDF onto an A3, A4, A5, letter, legal or `50x70cm` page instead of printing it at `--dpi`")
+            .required(false),
+    )
+    .arg(
+        arg!(--"export-cmyk" <FILE> "Also write the painting as a CMYK TIFF at `--dpi` for print")
+            .required(false),
+    )
+    .arg(
+        arg!(--"cmyk-profile" <ICC> "Separate the `--export-cmyk` colors with this ICC output profile, and embed it, instead of plain under color removal")
+            .required(false)
+            .requires("export-cmyk"),
+    )
+    .arg(
+        arg!(--"stream-png" "Paint and write the PNG output a strip of rows at a time instead of holding the whole image, for very large `--output-size` renders")
+            .required(false),
+    )
+    .arg(
+        arg!(--"tile-pages" <SIZE> "Also split the painting at `--dpi` over overlapping A3, A4, A5, letter, legal or `50x70cm` pages with crop marks, as `NAME-page-ROW-COLUMN` images, and the `--export-pdf` over as many PDF pages")
+            .required(false)
+            .conflicts_with("page-size"),
+    )
+    .arg(
+        arg!(--overlap <LENGTH> "How much neighbouring `--tile-pages` pages share, such as `10mm`, `1cm` or `0.5in`")
+            .required(false)
+            .default_value("10mm"),
+    )
+    .arg(
+        arg!(--"export-lottie" <FILE> "Also write the cells appearing one by one as a Lottie JSON animation, to embed on web pages")
+            .required(false),
+    )
+    .arg(
+        arg!(--"lottie-duration" <SECONDS> "How long the `--export-lottie` animation takes to build up the painting")
+            .required(false)
+            .default_value("3")
+            .requires("export-lottie"),
+    )
+    .arg(
+        arg!(--"export-html" <FILE> "Also write the cells as an HTML snippet of `clip-path` divs, for web backgrounds with per-cell hover effects")
+            .required(false),
+    )
+    .arg(
+        arg!(--"export-dxf" <FILE> "Also write the cell boundaries as DXF polylines in millimetres at `--dpi`, for laser cutting")
+            .required(false),
+    )
+    .arg(
+        arg!(--kerf <MM> "Move the DXF cell boundaries this many millimetres into every cell")
+            .required(false)
+            .default_value("0"),
+    )
+    .arg(
+        arg!(--projection <PROJECTION> "Tessellate a 360° equirectangular panorama on the sphere so it wraps seamlessly")
+            .required(false)
+            .possible_values(["flat", "equirectangular"])
+            .default_value("flat"),
+    )
+    .arg(
+        arg!(--tileable "Wrap distances and anchors around the edges so the output tiles seamlessly")
+            .conflicts_with("projection"),
+    )
+    .arg(
+        arg!(--"edge-mode" <MODE> "How the edges bound the anchors: `clamp` places them right up to the edges, `mirror` spaces them from their reflections so edge cells are not flattened into slivers")
+            .required(false)
+            .possible_values(["clamp", "mirror"])
+            .default_value("clamp"),
+    )
+    .args(watch_render_args())
+}
+
+fn command_line() -> Command<'static> {
+    Command::new("voronoi-painter")
         .version("0.1.0")
         .author("Varun Barad <varun@varunbarad.com>")
         .about("CLI tool to convert an image to its voronoi diagram")
         .args_override_self(true)
         .subcommand_required(true)
-        .subcommand(
+        .subcommand(painting_args(
             Command::new("painting")
                 .about("Convert a painting to its voronoi diagram")
+                .arg(arg!(-i --input <VALUE> "Image to paint, or several aligned exposures of it to combine as `--burst` asks").required(false).required_unless_present("preset-list").multiple_occurrences(true))
+                .arg(arg!(--burst <MODE> "How several `--input` exposures are combined: `mean` averages out their noise, `fuse` takes every part from the frames exposing it best").required(false).possible_values(["mean", "fuse"]).default_value("mean"))
+                .arg(arg!(-o --output <VALUE>).required(false).required_unless_present("preset-list"))
+                .arg(arg!(--"preset-list" "List the presets and the flags each stands for").required(false))
+                .arg(arg!(--manifest <FILE> "Save everything needed to paint the painting again to this JSON file, for the `reproduce` sub-command").required(false).conflicts_with("suffix"))
+                .arg(arg!(--"output-dir" <DIR> "Directory the `--styles` paintings are written to").required(false))
+                .args(overwrite_args())
+                .args(encoder_args()),
+        ))
+        .subcommand(painting_args(
+            Command::new("watch")
+                .about("Paint every image that appears in a directory, like a drop folder")
+                .arg(arg!(--"input-dir" <DIR> "Directory watched for new images").required(true))
+                .arg(arg!(--"output-dir" <DIR> "Directory the paintings are written to, as PNG. An image that changes is repainted over the painting this watch wrote for it, whether or not `--force` is given").required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(
+                    arg!(--"poll-interval" <MILLISECONDS> "How often the input directory is checked")
+                        .required(false)
+                        .default_value("500"),
+                )
+                .arg(
+                    arg!(--settle <MILLISECONDS> "How long a file must stay unchanged before it is painted, so partially written files are skipped")
+                        .required(false)
+                        .default_value("1000"),
+                )
+                .arg(
+                    arg!(--"name-template" <TEMPLATE> "Name of every painting, from {stem}, {ext}, {min_distance}, {sampling}, {metric}, {color_mode}, {seed} and {counter}")
+                        .required(false)
+                        .default_value("{stem}.png"),
+                )
+                .arg(
+                    arg!(--jobs <COUNT> "How many images are painted at once, sharing the cores between them")
+                        .required(false)
+                        .default_value("1"),
+                )
+                .arg(
+                    arg!(--existing "Also paint the images already in the directory when watching starts")
+                        .required(false),
+                ),
+        ))
+        .subcommand(
+            Command::new("jobs")
+                .about("Paint the jobs read from stdin, one JSON object per line like `{\"input\": \"in.png\", \"output\": \"out.png\", \"options\": {\"min-distance\": 12}}`, printing a JSON result line for each")
+                .arg(
+                    arg!(--jobs <COUNT> "How many images are painted at once, sharing the cores between them")
+                        .required(false)
+                        .default_value("1"),
+                )
+                .args(worker_args()),
+        )
+        .subcommand(tessellation_args(
+            Command::new("animate")
+                .about("Animate a slow zoom/pan over the voronoi diagram of an image, as a GIF, an animated PNG or a directory of frames")
+                .arg(arg!(-i --input <VALUE>).required(true))
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(arg!(--duration <SECONDS>).required(false).default_value("3"))
+                .args(sprite_sheet_args())
+                .arg(arg!(--fps <VALUE>).required(false).default_value("12"))
+                .arg(arg!(--zoom <FACTOR>).required(false).default_value("1.5"))
+                .arg(arg!(--focus <POSITION>).required(false).default_value("0.5,0.5"))
+                .arg(arg!(--retessellate <FRAMES>).required(false))
+                .arg(
+                    arg!(--"reveal-from" <ORIGIN> "Make the cells appear in order of distance from `x,y` (fractions of the size), center or random, like crystals growing")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--"fade-in" <FRACTION> "Fade every cell in from a random moment, over a random share of the animation of up to this much, from 0 to 1")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--easing <EASING> "How `--reveal-from` and `--fade-in` speed up and slow down")
+                        .required(false)
+                        .possible_values(["linear", "ease-in", "ease-out", "ease-in-out"])
+                        .default_value("linear"),
+                )
+                .arg(
+                    arg!(--background <BACKGROUND> "What shows where cells have not appeared yet: original for the input pixels, transparent or a `#RRGGBB` color")
+                        .required(false)
+                        .default_value("original"),
+                )
+                .arg(
+                    arg!(--boil <PIXELS> "Jitter every anchor up to this far on every frame for a hand-drawn wobble; use `--zoom 1` to keep the view still")
+                        .required(false),
+                ),
+        ))
+        .subcommand(tessellation_args(
+            Command::new("sequence")
+                .about("Tessellate aligned frames of a burst or timelapse with the same anchors, as a GIF, an animated PNG or a directory of frames")
+                .arg(
+                    arg!(-i --input <PATH> "Frames in order, or directories of them")
+                        .required(true)
+                        .multiple_values(true),
+                )
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .args(sprite_sheet_args())
+                .arg(arg!(--fps <VALUE>).required(false).default_value("12"))
+                .arg(
+                    arg!(--"color-smoothing" <FACTOR> "Keep this share of every cell's previous color to calm flicker, from 0 up to 1")
+                        .required(false)
+                        .default_value("0"),
+                )
+                .arg(
+                    arg!(--advect "Move anchors along the optical flow between frames so cells ride moving objects")
+                        .required(false),
+                ),
+        ))
+        .subcommand(tessellation_args(
+            Command::new("video")
+                .about("Tessellate the frames of a video with the same anchors, through ffmpeg, keeping its audio")
+                .arg(arg!(-i --input <VALUE>).required(true))
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .arg(
+                    arg!(--fps <VALUE> "Frames per second to paint, or `keep` for the rate of the input")
+                        .required(false)
+                        .default_value("keep"),
+                )
+                .arg(
+                    arg!(--"color-smoothing" <FACTOR> "Keep this share of every cell's previous color to calm flicker, from 0 up to 1")
+                        .required(false)
+                        .default_value("0"),
+                )
+                .arg(
+                    arg!(--advect "Move anchors along the optical flow between frames so cells ride moving objects")
+                        .required(false),
+                )
+                .arg(arg!(--"no-audio" "Leave the audio of the input out").required(false)),
+        ))
+        .subcommand(preview_arg(sampling_args(
+            Command::new("paint-by-numbers")
+                .about("Make a paint-by-numbers kit: a numbered outline sheet and a legend of its paints")
+                .arg(arg!(-i --input <VALUE>).required(true))
+                .arg(arg!(-o --output <VALUE> "The outline sheet").required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .args(minimum_distance_args("16"))
+                .arg(arg!(-a --anchors <VALUE>).required(false))
+                .args(anchor_cache_args())
+                .arg(
+                    arg!(--"color-mode" <MODE> "How cells are colored before quantizing: anchor, mean, median or dominant")
+                        .required(false)
+                        .default_value("mean"),
+                )
+                .arg(arg!(--paints <COUNT> "Number of paints the colors are reduced to").required(false).default_value("12"))
+                .arg(arg!(--legend <FILE> "Legend of the paints, next to the sheet as `NAME-legend` by default").required(false))
+                .arg(arg!(--preview <FILE> "Also write the regions filled with their paints").required(false))
+                .arg(
+                    arg!(--"number-size" <SCALE> "Largest size of the numbers, in multiples of their 5x7 pixel font")
+                        .required(false)
+                        .default_value("3"),
+                ),
+        )))
+        .subcommand(painting_args(
+            Command::new("estimate")
+                .about("Predict how many anchors painting an input with these options places, the memory it peaks at and how long it takes, from a quick painting of a small copy")
+                .arg(arg!(-i --input <VALUE>).required(true))
+                .arg(arg!(-o --output <VALUE> "Ignored, so a `painting` command line can be estimated as it is").required(false))
+                .arg(arg!(--"output-dir" <DIR> "Ignored like `--output`").required(false))
+                .args(encoder_args()),
+        ))
+        .subcommand(preview_arg(sampling_args(
+            Command::new("generate")
+                .about("Generate a Worley (cellular) noise texture without an input image")
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(arg!(--size <WIDTHxHEIGHT>).required(false).default_value("512x512"))
+                .args(minimum_distance_args("32"))
+                .arg(
+                    arg!(--feature <FEATURE> "Distance feature used as the noise value")
+                        .required(false)
+                        .possible_values(["f1", "f2", "f2-f1"])
+                        .default_value("f1"),
+                )
+                .arg(
+                    arg!(--palette <COLORS> "Comma separated hex colors used as a gradient instead of grayscale")
+                        .required(false),
+                ),
+        )))
+        .subcommand(preview_arg(sampling_args(
+            Command::new("art")
+                .about("Tessellate a blank canvas and color its cells from a palette file")
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(arg!(--palette <FILE> "Palette file with one hex color per line").required(true))
+                .arg(arg!(--size <WIDTHxHEIGHT>).required(false).default_value("1920x1080"))
+                .args(minimum_distance_args("40"))
+                .arg(
+                    arg!(--gradient <DEGREES> "Walk through the palette in this direction across the canvas")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--"gradient-jitter" <FRACTION> "How far cells may stray from the gradient")
+                        .required(false)
+                        .default_value("0.3"),
+                )
+                .arg(
+                    arg!(--metric <METRIC> "Distance used to assign pixels to cells")
+                        .required(false)
+                        .possible_values(["euclidean", "manhattan", "chebyshev"])
+                        .default_value("euclidean"),
+                )
+                .arg(aspect_arg()),
+        )))
+        .subcommand(preview_arg(
+            Command::new("palette-poster")
+                .about("Lay out the colors of an image as cells as large as the share of the image they cover")
                 .arg(arg!(-i --input <VALUE>).required(true))
                 .arg(arg!(-o --output <VALUE>).required(true))
-                .arg(arg!(-a --anchors <VALUE>).required(false)),
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(arg!(--colors <COUNT> "Number of colors the image is reduced to, one cell each").required(false).default_value("8"))
+                .arg(arg!(--size <WIDTHxHEIGHT> "Size of the poster, by default that of the input").required(false))
+                .arg(
+                    arg!(--iterations <COUNT> "Most rounds of fitting the cell areas to the color shares")
+                        .required(false)
+                        .default_value("200"),
+                )
+                .arg(arg!(--seed <VALUE> "Seed for reproducible cell placement").required(false)),
+        ))
+        .subcommand(preview_arg(
+            Command::new("render")
+                .about("Repaint an anchor cache saved with `painting --cache-colors`, without the input image")
+                .arg(arg!(-a --anchors <FILE> "Anchor cache with colors").required(true))
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(
+                    arg!(--size <WIDTHxHEIGHT> "Size to paint at, stretching the anchors from the canvas they were placed on, which is the default")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--metric <METRIC> "Distance used to assign pixels to cells")
+                        .required(false)
+                        .possible_values(["euclidean", "manhattan", "chebyshev"])
+                        .default_value("euclidean"),
+                )
+                .arg(aspect_arg())
+                .args(cell_style_args()),
+        ))
+        .subcommand(preview_arg(
+            Command::new("recolor")
+                .about("Repaint a label map saved with `painting --export-labels` with new colors or cell styles, without assigning any pixel again")
+                .arg(arg!(--labels <FILE> "Label map written by `--export-labels`").required(true))
+                .arg(
+                    arg!(--palette <FILE> "JSON palette of the cells, as written next to the label map and possibly edited")
+                        .required(true),
+                )
+                .arg(arg!(-o --output <VALUE>).required(true))
+                .args(overwrite_args())
+                .args(encoder_args())
+                .arg(
+                    arg!(-i --input <IMAGE> "Sample the cell colors anew from this image, stretched over the label map, instead of taking them from the palette")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--"color-mode" <MODE> "How cells are filled from `--input`: anchor, mean, median or dominant")
+                        .required(false)
+                        .default_value("mean")
+                        .requires("input"),
+                )
+                .arg(
+                    arg!(--"snap-palette" <FILE> "Snap the cell colors to the nearest entry of a hex, GPL or ASE palette file")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--"color-space" <SPACE> "Space the mean and dominant color modes average and cluster colors in, and `--snap-palette` matches them in")
+                        .required(false)
+                        .possible_values(["srgb", "linear", "oklab", "cielab"])
+                        .default_value("srgb"),
+                )
+                .args(cell_style_args()),
+        ))
+        .subcommands(edit_subcommands())
+        .subcommand(
+            Command::new("selftest")
+                .about("Paint built-in synthetic images with fixed seeds and check they come out exactly as a known good build painted them"),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Start an HTTP server that paints images uploaded to `POST /render`, or to `POST /sessions` to edit their anchors")
+                .arg(
+                    arg!(--address <VALUE>)
+                        .required(false)
+                        .default_value("127.0.0.1:8080"),
+                ),
+        )
+        .subcommands(daemon_subcommands())
+        .subcommand(
+            Command::new("diff")
+                .about("Compare two renders, reporting how many pixels and cells differ and failing if any do, to check that a change still paints the same")
+                .arg(arg!(<FIRST> "First render"))
+                .arg(arg!(<SECOND> "Second render, of the same size"))
+                .arg(
+                    arg!(--"labels-a" <FILE> "Label map of the first render, written by `--export-labels`, to count the cells that changed shape or color")
+                        .required(false)
+                        .requires("labels-b"),
+                )
+                .arg(
+                    arg!(--"labels-b" <FILE> "Label map of the second render")
+                        .required(false)
+                        .requires("labels-a"),
+                )
+                .arg(
+                    arg!(--heatmap <FILE> "Save where the renders differ: red to yellow as colors grow apart and cyan where pixels went to another cell")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--tolerance <LEVELS> "How many levels apart the channels of a pixel may be and count as the same")
+                        .required(false)
+                        .default_value("0"),
+                )
+                .args(overwrite_args())
+                .args(encoder_args()),
+        )
+        .subcommand(
+            Command::new("reproduce")
+                .about("Paint the painting of a `--manifest` again and check that it is the same file")
+                .arg(arg!(<MANIFEST> "Manifest saved with `painting --manifest`"))
+                .arg(arg!(-o --output <VALUE> "Where the painting is painted again, by default next to the original with `.reproduced` before its extension").required(false))
+                .args(overwrite_args()),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run the steps of a TOML recipe: blur, tessellate, composite and save")
+                .arg(arg!(<RECIPE> "Recipe file; paths in it are relative to it"))
+                .args(overwrite_args())
+                .args(encoder_args()),
         )
-        .get_matches();
+}
 
+/// Runs the sub-command of a parsed command line.
+fn run_command(arguments: &ArgMatches) -> Result<(), String> {
     match arguments.subcommand() {
-        Some(("painting", sub_matches)) => match sub_matches.value_of("input") {
-            None => {
-                eprintln!("Path to input image not provided, please use the `--input <VALUE>` arg");
-            }
-            Some(input_image_path) => match sub_matches.value_of("output") {
-                None => {
-                    eprint!("Path for output not provided, please use the `--output <VALUE>` arg");
-                }
-                Some(output_path) => {
-                    let input_image = image::open(input_image_path).unwrap();
-
-                    let (image_width, image_height) = input_image.dimensions();
+        Some(("painting", sub_matches)) if sub_matches.is_present("preset-list") => {
+            print_presets();
+            Ok(())
+        }
+        Some(("painting", sub_matches)) => run_painting(sub_matches),
+        Some(("watch", sub_matches)) => run_watch(sub_matches),
+        Some(("jobs", sub_matches)) => run_jobs(sub_matches),
+        Some(("animate", sub_matches)) => run_animate(sub_matches),
+        Some(("sequence", sub_matches)) => run_sequence(sub_matches),
+        Some(("video", sub_matches)) => run_video(sub_matches),
+        Some(("paint-by-numbers", sub_matches)) => run_paint_by_numbers(sub_matches),
+        Some(("estimate", sub_matches)) => run_estimate(sub_matches),
+        Some(("generate", sub_matches)) => run_generate(sub_matches),
+        Some(("art", sub_matches)) => run_art(sub_matches),
+        Some(("palette-poster", sub_matches)) => run_palette_poster(sub_matches),
+        Some(("render", sub_matches)) => run_render(sub_matches),
+        Some(("recolor", sub_matches)) => run_recolor(sub_matches),
+        #[cfg(feature = "window")]
+        Some(("edit", sub_matches)) => run_edit(sub_matches),
+        Some(("selftest", _)) => run_self_test(),
+        Some(("serve", sub_matches)) => run_serve(sub_matches),
+        #[cfg(unix)]
+        Some(("daemon", sub_matches)) => run_daemon(sub_matches),
+        Some(("diff", sub_matches)) => run_diff(sub_matches),
+        Some(("reproduce", sub_matches)) => run_reproduce(sub_matches),
+        Some(("run", sub_matches)) => run_recipe(sub_matches),
+        _ => Err(String::from("No known sub-command found")),
+    }
+}
 
-                    let minimum_distance = 10u32;
-                    let bounds = Bounds {
-                        width: image_width as u64,
-                        height: image_height as u64,
-                    };
+/// Exit code of a painting stopped by Ctrl-C, as shells report a process
+/// killed by it.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
 
-                    let anchor_points = match sub_matches.value_of("anchors") {
-                        None => generate_anchor_points(&bounds, minimum_distance),
-                        Some(anchors_cache_path) => {
-                            match read_anchor_points_from_file(anchors_cache_path) {
-                                Ok(existing_anchor_points) => existing_anchor_points,
-                                Err(_) => {
-                                    let anchor_points =
-                                        generate_anchor_points(&bounds, minimum_distance);
-                                    match write_anchor_points_to_file(
-                                        anchor_points.clone(),
-                                        anchors_cache_path,
-                                    ) {
-                                        Ok(_) => {}
-                                        Err(_) => {}
-                                    }
-
-                                    anchor_points
-                                }
-                            }
-                        }
-                    };
+/// Prints every preset with what it looks like and the flags it stands for.
+fn print_presets() {
+    for preset in PRESETS {
+        println!("{:<14} {}", preset.name, preset.description);
+        println!("{:<14} {}", "", preset.arguments.join(" "));
+    }
+}
 
-                    let mut anchors: Vec<Anchor> = Vec::with_capacity(anchor_points.len());
-                    for point in anchor_points {
-                        let x = point.x as u32;
-                        let y = point.y as u32;
-                        anchors.push(Anchor {
-                            point,
-                            color: input_image.get_pixel(x, y),
-                        });
-                    }
+/// Puts the flags of the `--preset` in `arguments` right after its
+/// sub-command, before every flag given, so that those override them.
+fn expand_preset(mut arguments: Vec<String>) -> Result<Vec<String>, String> {
+    let name = arguments.iter().enumerate().find_map(|(index, argument)| {
+        match argument.strip_prefix("--preset=") {
+            Some(name) => Some(name.to_string()),
+            None if argument == "--preset" => arguments.get(index + 1).cloned(),
+            None => None,
+        }
+    });
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(arguments),
+    };
+    let preset = find_preset(&name).ok_or_else(|| {
+        let names: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+        format!(
+            "Unknown preset `{}`, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })?;
 
-                    println!("Generated {} anchor points", anchors.len());
-
-                    let mut output_image_buffer =
-                        image::ImageBuffer::new(image_width, image_height);
-
-                    for step in (0..image_width).step_by(10) {
-                        let mut thread_pool = Vec::with_capacity(10);
-                        for x in 0..10 {
-                            if (x + step) >= image_width {
-                                break;
-                            } else {
-                                let loop_anchors = anchors.clone();
-                                let handle = thread::spawn(move || {
-                                    pixel_calculator(
-                                        x + step,
-                                        image_height,
-                                        loop_anchors,
-                                        minimum_distance,
-                                    )
-                                });
-
-                                thread_pool.push(handle);
-                            }
-                        }
+    let position = arguments.len().min(2);
+    arguments.splice(
+        position..position,
+        preset.arguments.iter().map(|argument| argument.to_string()),
+    );
 
-                        for thread in thread_pool {
-                            match thread.join() {
-                                Ok(pixels) => {
-                                    for (coordinates, color) in pixels {
-                                        output_image_buffer.put_pixel(
-                                            coordinates.x as u32,
-                                            coordinates.y as u32,
-                                            color,
-                                        );
-                                    }
-                                }
-                                Err(message) => {
-                                    panic::resume_unwind(message);
-                                }
-                            }
-                        }
-                    }
+    Ok(arguments)
+}
 
-                    output_image_buffer.save(output_path).unwrap();
-                }
-            },
-        },
-        _ => {
-            eprintln!("No known sub-command found");
+fn main() {
+    let arguments = match expand_preset(env::args().collect()) {
+        Ok(arguments) => command_line().get_matches_from(arguments),
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(1);
         }
+    };
+
+    let result = catch_render_failure(|| run_command(&arguments));
+    if let Err(message) = &result {
+        eprintln!("{}", message);
+    }
+    if interrupted() {
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if result.is_err() {
+        process::exit(1);
     }
 }
@@ -0,0 +1,65 @@
+//! Reproducibility manifests: a JSON sidecar of a painting recording what it
+//! was painted from and with, so that `reproduce` can paint it again and
+//! check that the result is the same file, byte for byte.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Version of voronoi-painter the painting was painted with.
+    pub version: String,
+    /// What computed the painting, and on which architecture and system.
+    pub backend: String,
+    pub input: String,
+    /// [`file_hash`] of the input, unless it is not a local file.
+    pub input_hash: Option<String>,
+    pub output: String,
+    pub output_hash: String,
+    pub seed: u64,
+    /// [`file_hash`] of the `--anchors` cache, if the painting had one.
+    pub anchor_cache_hash: Option<String>,
+    /// Every painting option given but the input and output, the way `jobs`
+    /// takes them.
+    pub options: Map<String, Value>,
+}
+
+impl Manifest {
+    pub fn load(path: &str) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Could not read manifest {}: {}", path, error))?;
+
+        serde_json::from_str(&text).map_err(|error| format!("Invalid manifest {}: {}", path, error))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+
+        fs::write(path, text + "\n")
+            .map_err(|error| format!("Could not save manifest {}: {}", path, error))
+    }
+}
+
+/// FNV-1a hash of the bytes of the file at `path`, as 16 hex digits.
+pub fn file_hash(path: &str) -> Result<String, String> {
+    let file = File::open(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+    let mut reader = BufReader::new(file);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hash = 0xcbf29ce484222325u64;
+    loop {
+        let count = reader
+            .read(&mut buffer)
+            .map_err(|error| format!("Could not read {}: {}", path, error))?;
+        if count == 0 {
+            break;
+        }
+        for byte in &buffer[..count] {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    Ok(format!("{:016x}", hash))
+}
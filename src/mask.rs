@@ -0,0 +1,217 @@
+use crate::geometry::{Bounds, Point};
+use crate::render::{CellMap, UNASSIGNED};
+use crate::sampling::AnchorSampler;
+use image::{imageops, GenericImageView, GrayImage, ImageResult, Luma, Rgba, RgbaImage};
+use rand::RngCore;
+use std::collections::HashMap;
+
+/// A binary shape limiting where cells are generated and drawn.
+pub struct ShapeMask {
+    pub width: u32,
+    pub height: u32,
+    inside: Vec<bool>,
+}
+
+impl ShapeMask {
+    /// Builds a mask from the bright, opaque pixels of `image`, stretched to
+    /// `width`×`height`.
+    pub fn from_image<I>(image: &I, width: u32, height: u32) -> ShapeMask
+    where
+        I: GenericImageView<Pixel = Rgba<u8>>,
+    {
+        let resized = imageops::resize(image, width, height, imageops::FilterType::Nearest);
+        let inside = resized
+            .pixels()
+            .map(|pixel| {
+                let [red, green, blue, alpha] = pixel.0;
+                let luma =
+                    (0.2126 * (red as f64)) + (0.7152 * (green as f64)) + (0.0722 * (blue as f64));
+                (luma >= 128f64) && (alpha >= 128)
+            })
+            .collect();
+
+        ShapeMask {
+            width,
+            height,
+            inside,
+        }
+    }
+
+    pub fn load(path: &str, width: u32, height: u32) -> ImageResult<ShapeMask> {
+        let image = image::open(path)?.to_rgba8();
+
+        Ok(ShapeMask::from_image(&image, width, height))
+    }
+
+    pub fn contains_pixel(&self, x: u32, y: u32) -> bool {
+        (x < self.width)
+            && (y < self.height)
+            && self.inside[((y as usize) * (self.width as usize)) + (x as usize)]
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        (point.x >= 0f64)
+            && (point.y >= 0f64)
+            && self.contains_pixel(point.x as u32, point.y as u32)
+    }
+
+    /// Whether pixel `(x, y)` of a `width`×`height` raster falls inside the
+    /// mask, for rasters at a different size than the mask itself.
+    fn covers(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let mask_x = (((x as u64) * (self.width as u64)) / (width as u64)) as u32;
+        let mask_y = (((y as u64) * (self.height as u64)) / (height as u64)) as u32;
+
+        self.contains_pixel(mask_x, mask_y)
+    }
+
+    /// Unassigns every pixel of `cell_map` outside the mask.
+    pub fn apply(&self, cell_map: &mut CellMap) {
+        let (width, height) = (cell_map.width, cell_map.height);
+        for y in 0..height {
+            for x in 0..width {
+                if !self.covers(x, y, width, height) {
+                    cell_map.set_label(x, y, UNASSIGNED);
+                }
+            }
+        }
+    }
+
+    /// Makes every pixel of `image` outside the mask transparent.
+    pub fn clear_outside(&self, image: &mut RgbaImage) {
+        let (width, height) = image.dimensions();
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if !self.covers(x, y, width, height) {
+                *pixel = Rgba([0, 0, 0, 0]);
+            }
+        }
+    }
+
+    /// How much of every pixel of a `width`×`height` raster the mask covers,
+    /// from `0` to `1`, row by row. With a `feather` of more than `0` the
+    /// border fades out over about that many pixels.
+    pub fn coverage(&self, width: u32, height: u32, feather: f64) -> Vec<f64> {
+        let sharp = GrayImage::from_fn(width, height, |x, y| {
+            Luma([if self.covers(x, y, width, height) {
+                u8::MAX
+            } else {
+                0
+            }])
+        });
+        let soft = if feather > 0f64 {
+            imageops::blur(&sharp, (feather / 2f64) as f32)
+        } else {
+            sharp
+        };
+
+        soft.pixels()
+            .map(|pixel| (pixel[0] as f64) / 255f64)
+            .collect()
+    }
+
+    /// Traces the border of the mask along pixel edges.
+    ///
+    /// Outer rings run the same way as the cells of
+    /// [`crate::voronoi::cell_polygons`] and holes run the other way.
+    pub fn outline(&self) -> Vec<Vec<Point>> {
+        let inside =
+            |x: i64, y: i64| (x >= 0) && (y >= 0) && self.contains_pixel(x as u32, y as u32);
+
+        let mut edges: Vec<((i64, i64), (i64, i64))> = Vec::new();
+        for y in 0..(self.height as i64) {
+            for x in 0..(self.width as i64) {
+                if !inside(x, y) {
+                    continue;
+                }
+                if !inside(x, y - 1) {
+                    edges.push(((x, y), (x + 1, y)));
+                }
+                if !inside(x + 1, y) {
+                    edges.push(((x + 1, y), (x + 1, y + 1)));
+                }
+                if !inside(x, y + 1) {
+                    edges.push(((x + 1, y + 1), (x, y + 1)));
+                }
+                if !inside(x - 1, y) {
+                    edges.push(((x, y + 1), (x, y)));
+                }
+            }
+        }
+
+        let mut outgoing: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (index, (start, _)) in edges.iter().enumerate() {
+            outgoing.entry(*start).or_default().push(index);
+        }
+
+        let mut used = vec![false; edges.len()];
+        let mut rings = Vec::new();
+        for first in 0..edges.len() {
+            if used[first] {
+                continue;
+            }
+
+            let mut ring: Vec<(i64, i64)> = Vec::new();
+            let mut current = first;
+            loop {
+                used[current] = true;
+                let (start, end) = edges[current];
+                ring.push(start);
+
+                let next = outgoing
+                    .get(&end)
+                    .and_then(|candidates| candidates.iter().find(|index| !used[**index]));
+                match next {
+                    Some(next) => current = *next,
+                    None => break,
+                }
+            }
+
+            rings.push(remove_collinear_vertices(&ring));
+        }
+
+        rings
+    }
+}
+
+fn remove_collinear_vertices(ring: &[(i64, i64)]) -> Vec<Point> {
+    let count = ring.len();
+    (0..count)
+        .filter(|index| {
+            let previous = ring[(index + count - 1) % count];
+            let current = ring[*index];
+            let next = ring[(index + 1) % count];
+            let cross = ((current.0 - previous.0) * (next.1 - current.1))
+                - ((current.1 - previous.1) * (next.0 - current.0));
+
+            cross != 0
+        })
+        .map(|index| Point {
+            x: ring[index].0 as f64,
+            y: ring[index].1 as f64,
+        })
+        .collect()
+}
+
+/// Keeps only the anchors of another sampler that fall inside a mask.
+pub struct MaskedSampler<'a> {
+    pub sampler: Box<dyn AnchorSampler>,
+    pub mask: &'a ShapeMask,
+}
+
+impl MaskedSampler<'_> {
+    fn keep_inside(&self, points: Vec<Point>) -> Vec<Point> {
+        points
+            .into_iter()
+            .filter(|point| self.mask.contains(point))
+            .collect()
+    }
+}
+
+impl AnchorSampler for MaskedSampler<'_> {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        self.keep_inside(self.sampler.sample(bounds, rng))
+    }
+
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        Ok(self.keep_inside(self.sampler.try_sample(bounds, rng)?))
+    }
+}
@@ -0,0 +1,265 @@
+//! Merging neighbouring cells whose colors are too close to tell apart, so
+//! flat areas such as skies become single regions, and cells too small to
+//! read into their neighbours.
+
+use crate::color::ColorSpace;
+use crate::render::{CellMap, UNASSIGNED};
+use image::Rgba;
+use std::collections::{BTreeSet, HashSet};
+
+/// Pairs of cells that touch horizontally or vertically, smaller index first.
+pub(crate) fn neighbouring_cells(cell_map: &CellMap) -> HashSet<(u32, u32)> {
+    let mut pairs = HashSet::new();
+    for y in 0..cell_map.height {
+        for x in 0..cell_map.width {
+            let label = cell_map.label(x, y);
+            if label == UNASSIGNED {
+                continue;
+            }
+
+            let neighbours = [
+                (x + 1 < cell_map.width).then(|| cell_map.label(x + 1, y)),
+                (y + 1 < cell_map.height).then(|| cell_map.label(x, y + 1)),
+            ];
+            for neighbour in neighbours.into_iter().flatten() {
+                if (neighbour != UNASSIGNED) && (neighbour != label) {
+                    pairs.insert((label.min(neighbour), label.max(neighbour)));
+                }
+            }
+        }
+    }
+
+    pairs
+}
+
+/// How many pixels each of `cell_count` cells of `cell_map` owns.
+pub(crate) fn cell_areas(cell_map: &CellMap, cell_count: usize) -> Vec<usize> {
+    let mut areas = vec![0usize; cell_count];
+    for &label in &cell_map.labels {
+        if label != UNASSIGNED {
+            areas[label as usize] += 1;
+        }
+    }
+
+    areas
+}
+
+/// A group of merged cells with the area-weighted sums of their colors.
+struct Region {
+    parent: usize,
+    area: f64,
+    coordinates: [f64; 3],
+    alpha: f64,
+}
+
+fn find_root(regions: &mut [Region], index: usize) -> usize {
+    let mut root = index;
+    while regions[root].parent != root {
+        root = regions[root].parent;
+    }
+
+    let mut index = index;
+    while regions[index].parent != root {
+        let parent = regions[index].parent;
+        regions[index].parent = root;
+        index = parent;
+    }
+
+    root
+}
+
+fn mean_coordinates(region: &Region) -> [f64; 3] {
+    region.coordinates.map(|sum| sum / region.area)
+}
+
+/// How many times distances in `color_space` are to be stretched to compare
+/// them with a ΔE: CIELAB lightness runs from 0 to 100, and the coordinates
+/// of the other spaces from about 0 to 1.
+fn delta_e_scale(color_space: ColorSpace) -> f64 {
+    match color_space {
+        ColorSpace::Cielab => 1f64,
+        _ => 100f64,
+    }
+}
+
+/// Merges neighbouring cells into regions while the mean colors of the
+/// regions differ by less than `threshold` ΔE, closest pairs first, and
+/// gives every cell the mean color of its region. Colors are averaged and
+/// compared in `color_space`, where [`delta_e_scale`] converts the distances.
+///
+/// Returns the region of every cell, as the index of one of its cells.
+pub fn merge_similar_cells(
+    cell_map: &CellMap,
+    colors: &mut [Rgba<u8>],
+    threshold: f64,
+    color_space: ColorSpace,
+) -> Vec<usize> {
+    merge_similar_neighbours(
+        &cell_areas(cell_map, colors.len()),
+        &neighbouring_cells(cell_map),
+        colors,
+        threshold,
+        color_space,
+    )
+}
+
+/// Like [`merge_similar_cells`], for cells of `areas` pixels touching as
+/// `neighbours` do, from [`neighbouring_cells`].
+pub(crate) fn merge_similar_neighbours(
+    areas: &[usize],
+    neighbours: &HashSet<(u32, u32)>,
+    colors: &mut [Rgba<u8>],
+    threshold: f64,
+    color_space: ColorSpace,
+) -> Vec<usize> {
+    let threshold = threshold / delta_e_scale(color_space);
+    let mut regions: Vec<Region> = colors
+        .iter()
+        .zip(areas)
+        .enumerate()
+        .map(|(index, (color, area))| {
+            // Cells without pixels weigh as one so their mean color stays defined.
+            let area = (*area).max(1) as f64;
+            Region {
+                parent: index,
+                area,
+                coordinates: color_space.encode(*color).map(|channel| channel * area),
+                alpha: (color.0[3] as f64) * area,
+            }
+        })
+        .collect();
+
+    let mut pairs: Vec<(f64, usize, usize)> = neighbours
+        .iter()
+        .map(|&(from, to)| {
+            let (from, to) = (from as usize, to as usize);
+            (
+                color_space.squared_distance(colors[from], colors[to]),
+                from,
+                to,
+            )
+        })
+        .filter(|(distance, _, _)| *distance < threshold * threshold)
+        .collect();
+    pairs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    for (_, from, to) in pairs {
+        let (from, to) = (find_root(&mut regions, from), find_root(&mut regions, to));
+        if from == to {
+            continue;
+        }
+
+        let (from_mean, to_mean) = (
+            mean_coordinates(&regions[from]),
+            mean_coordinates(&regions[to]),
+        );
+        let distance: f64 = from_mean
+            .iter()
+            .zip(&to_mean)
+            .map(|(from, to)| (from - to) * (from - to))
+            .sum();
+        if distance >= threshold * threshold {
+            continue;
+        }
+
+        regions[to].parent = from;
+        let (area, coordinates, alpha) =
+            (regions[to].area, regions[to].coordinates, regions[to].alpha);
+        let merged = &mut regions[from];
+        merged.area += area;
+        merged.alpha += alpha;
+        for (sum, channel) in merged.coordinates.iter_mut().zip(coordinates) {
+            *sum += channel;
+        }
+    }
+
+    let owners: Vec<usize> = (0..colors.len())
+        .map(|index| find_root(&mut regions, index))
+        .collect();
+    let mut members = vec![0usize; colors.len()];
+    for owner in &owners {
+        members[*owner] += 1;
+    }
+    for (color, owner) in colors.iter_mut().zip(&owners) {
+        if members[*owner] < 2 {
+            continue;
+        }
+        let region = &regions[*owner];
+        let [red, green, blue] = color_space.decode(mean_coordinates(region));
+        *color = Rgba([red, green, blue, (region.alpha / region.area).round() as u8]);
+    }
+
+    owners
+}
+
+/// Merges every cell of `cell_map` owning fewer than `minimum_area` pixels,
+/// smallest first, into the touching cell closest to it in `color_space`,
+/// which takes over its pixels and gives it its color. Cells touching no
+/// other are kept.
+pub fn merge_small_cells(
+    cell_map: &mut CellMap,
+    colors: &mut [Rgba<u8>],
+    minimum_area: u64,
+    color_space: ColorSpace,
+) {
+    let mut areas = vec![0u64; colors.len()];
+    for &label in &cell_map.labels {
+        if label != UNASSIGNED {
+            areas[label as usize] += 1;
+        }
+    }
+
+    // Ordered, so that ties go the same way on every run.
+    let mut touching = vec![BTreeSet::new(); colors.len()];
+    for (first, second) in neighbouring_cells(cell_map) {
+        touching[first as usize].insert(second as usize);
+        touching[second as usize].insert(first as usize);
+    }
+
+    let mut small: Vec<usize> = (0..colors.len())
+        .filter(|cell| (areas[*cell] > 0) && (areas[*cell] < minimum_area))
+        .collect();
+    small.sort_by_key(|cell| areas[*cell]);
+
+    let mut owners: Vec<usize> = (0..colors.len()).collect();
+    for cell in small {
+        // It may have grown past the minimum by taking over smaller cells.
+        if areas[cell] >= minimum_area {
+            continue;
+        }
+        let closest = touching[cell].iter().copied().min_by(|a, b| {
+            color_space
+                .squared_distance(colors[cell], colors[*a])
+                .total_cmp(&color_space.squared_distance(colors[cell], colors[*b]))
+        });
+        let Some(owner) = closest else {
+            continue;
+        };
+
+        owners[cell] = owner;
+        areas[owner] += areas[cell];
+        areas[cell] = 0;
+        for neighbour in std::mem::take(&mut touching[cell]) {
+            touching[neighbour].remove(&cell);
+            if neighbour != owner {
+                touching[neighbour].insert(owner);
+                touching[owner].insert(neighbour);
+            }
+        }
+    }
+
+    let root = |mut cell: usize| {
+        while owners[cell] != cell {
+            cell = owners[cell];
+        }
+        cell
+    };
+    for label in cell_map.labels.iter_mut() {
+        if *label != UNASSIGNED {
+            *label = root(*label as usize) as u32;
+        }
+    }
+    for cell in 0..colors.len() {
+        colors[cell] = colors[root(cell)];
+    }
+}
@@ -0,0 +1,98 @@
+//! How closely a tessellation reproduces the image it was painted from.
+
+use crate::analysis::luminance;
+use image::RgbaImage;
+
+/// Mean squared difference of the red, green and blue channels of two
+/// images of the same size, on the `0` to `255` scale.
+pub fn mean_squared_error(original: &RgbaImage, reconstruction: &RgbaImage) -> f64 {
+    let mut sum = 0f64;
+    for (original, reconstruction) in original.pixels().zip(reconstruction.pixels()) {
+        for channel in 0..3 {
+            let difference = (original.0[channel] as f64) - (reconstruction.0[channel] as f64);
+            sum += difference * difference;
+        }
+    }
+
+    sum / ((original.width() as f64) * (original.height() as f64) * 3f64).max(1f64)
+}
+
+/// Peak signal-to-noise ratio in decibels for a mean squared error, infinite
+/// for identical images.
+pub fn psnr_from_mse(mean_squared_error: f64) -> f64 {
+    if mean_squared_error <= 0f64 {
+        f64::INFINITY
+    } else {
+        10f64 * ((255f64 * 255f64) / mean_squared_error).log10()
+    }
+}
+
+/// Summed-area table of `values`, one row and column larger than the image
+/// so window sums need no bounds checks.
+fn integral(values: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let mut table = vec![0f64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row_sum = 0f64;
+        for x in 0..width {
+            row_sum += values[(y * width) + x];
+            table[((y + 1) * (width + 1)) + x + 1] = table[(y * (width + 1)) + x + 1] + row_sum;
+        }
+    }
+
+    table
+}
+
+/// Mean structural similarity (SSIM) of the luminance of two images of the
+/// same size, over every `7`×`7` window: `1` for identical images, lower as
+/// structure is lost.
+pub fn structural_similarity(original: &RgbaImage, reconstruction: &RgbaImage) -> f64 {
+    const STABILIZER_MEAN: f64 = 0.01f64 * 0.01f64;
+    const STABILIZER_VARIANCE: f64 = 0.03f64 * 0.03f64;
+
+    let (width, height) = (original.width() as usize, original.height() as usize);
+    let side = 7usize.min(width).min(height);
+    if side == 0 {
+        return 1f64;
+    }
+
+    let (first, second) = (luminance(original), luminance(reconstruction));
+    let products = |from: &[f64], to: &[f64]| -> Vec<f64> {
+        from.iter().zip(to).map(|(from, to)| from * to).collect()
+    };
+    let tables = [
+        integral(&first, width, height),
+        integral(&second, width, height),
+        integral(&products(&first, &first), width, height),
+        integral(&products(&second, &second), width, height),
+        integral(&products(&first, &second), width, height),
+    ];
+    let window_sum = |table: &[f64], left: usize, top: usize| {
+        let (right, bottom) = (left + side, top + side);
+        table[(bottom * (width + 1)) + right]
+            - table[(top * (width + 1)) + right]
+            - table[(bottom * (width + 1)) + left]
+            + table[(top * (width + 1)) + left]
+    };
+
+    let count = (side * side) as f64;
+    let mut total = 0f64;
+    let mut windows = 0usize;
+    for top in 0..=(height - side) {
+        for left in 0..=(width - side) {
+            let [mean_first, mean_second, square_first, square_second, product] = tables
+                .each_ref()
+                .map(|table| window_sum(table, left, top) / count);
+            let variance_first = square_first - (mean_first * mean_first);
+            let variance_second = square_second - (mean_second * mean_second);
+            let covariance = product - (mean_first * mean_second);
+
+            total += (((2f64 * mean_first * mean_second) + STABILIZER_MEAN)
+                * ((2f64 * covariance) + STABILIZER_VARIANCE))
+                / (((mean_first * mean_first) + (mean_second * mean_second) + STABILIZER_MEAN)
+                    * (variance_first + variance_second + STABILIZER_VARIANCE));
+            windows += 1;
+        }
+    }
+
+    total / (windows as f64)
+}
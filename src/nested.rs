@@ -0,0 +1,222 @@
+use crate::anchors::{color_anchor_points, Anchor};
+use crate::geometry::{Bounds, Point};
+use crate::projection::Projection;
+use crate::render::{
+    assign_projected, color_cells, finish_cell_colors, map_columns_on_target, paint_voronoi,
+    CellMap, RenderOptions, UNASSIGNED,
+};
+use crate::sampling::AnchorSampler;
+use image::{Rgba, RgbaImage};
+use rand::{Rng, RngCore};
+
+/// One level of a nested tessellation, from coarsest to finest.
+pub struct NestedLevel<'a> {
+    pub sampler: &'a dyn AnchorSampler,
+    pub minimum_distance: u32,
+}
+
+/// How the cells of a finer level are colored.
+#[derive(Clone, Copy)]
+pub enum NestedColoring {
+    /// Children take their parent's color, nudged slightly brighter or darker
+    /// so that the subdivision stays visible.
+    Inherit,
+    /// Children are colored from the source image with the colorizer.
+    Resample,
+}
+
+struct Level {
+    anchors: Vec<Anchor>,
+    cell_map: CellMap,
+    colors: Vec<Rgba<u8>>,
+}
+
+/// Like [`assign_cells`](crate::render::assign_cells), but only lets a pixel join a cell whose anchor lies
+/// in the same parent cell as the pixel itself.
+fn assign_cells_within_parents(
+    anchors: &[Anchor],
+    anchor_parents: &[u32],
+    parent_map: &CellMap,
+    options: &RenderOptions,
+) -> CellMap {
+    let image_height = parent_map.height;
+    let window = options.minimum_distance.saturating_mul(2) as f64;
+
+    let columns = map_columns_on_target(parent_map.width, |x| {
+        let candidates: Vec<usize> = (0..anchors.len())
+            .filter(|index| (anchors[*index].point.x - (x as f64)).abs() < window)
+            .collect();
+
+        (0..image_height)
+            .map(|y| {
+                let parent = parent_map.label(x, y);
+                let point = Point {
+                    x: x as f64,
+                    y: y as f64,
+                };
+
+                let mut closest: Option<(u32, f64)> = None;
+                for &index in &candidates {
+                    if anchor_parents[index] != parent {
+                        continue;
+                    }
+                    let distance = options.metric.distance(&point, &anchors[index].point);
+                    match closest {
+                        Some((_, closest_distance)) if closest_distance <= distance => {}
+                        _ => closest = Some((index as u32, distance)),
+                    }
+                }
+
+                closest.map(|(index, _)| index).unwrap_or(UNASSIGNED)
+            })
+            .collect::<Vec<u32>>()
+    });
+
+    let mut cell_map = CellMap::new(parent_map.width, image_height);
+    for (x, column_labels) in columns.into_iter().enumerate() {
+        cell_map.set_column(x as u32, column_labels);
+    }
+
+    cell_map
+}
+
+fn vary_brightness(color: Rgba<u8>, rng: &mut dyn RngCore) -> Rgba<u8> {
+    let factor = 1f64 + ((rng.gen::<f64>() - 0.5f64) * 0.3f64);
+    let [red, green, blue, alpha] = color.0;
+    let scale = |channel: u8| ((channel as f64) * factor).round().clamp(0f64, 255f64) as u8;
+
+    Rgba([scale(red), scale(green), scale(blue), alpha])
+}
+
+/// Renders a multi-level tessellation where every cell of a level is split
+/// into its own finer voronoi diagram by the next level.
+///
+/// Parent cells that receive no anchor of the finer level are kept whole.
+/// The finest cells are painted like [`paint_voronoi`] paints flat ones, in
+/// the style of `options`.
+pub fn render_nested(
+    source_image: &RgbaImage,
+    levels: &[NestedLevel],
+    coloring: NestedColoring,
+    rng: &mut dyn RngCore,
+    options: &RenderOptions,
+) -> RgbaImage {
+    let (image_width, image_height) = source_image.dimensions();
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+
+    let mut current: Option<Level> = None;
+    for nested_level in levels {
+        let level_options = RenderOptions {
+            minimum_distance: nested_level.minimum_distance,
+            smoothing: None,
+            antialias: None,
+            output_size: None,
+            observer: None,
+            mask: None,
+            projection: Projection::Flat,
+            radii: None,
+            order: None,
+            script: None,
+            round_corners: None,
+            min_cell_area: None,
+            relief: None,
+            ..*options
+        };
+
+        let anchor_points = nested_level.sampler.sample(&bounds, rng);
+        let anchors = color_anchor_points(source_image, anchor_points);
+
+        let level = match current {
+            None => {
+                let cell_map = assign_projected(
+                    &anchors,
+                    image_width,
+                    image_height,
+                    nested_level.minimum_distance,
+                    &level_options,
+                    None,
+                );
+                let colors = color_cells(&cell_map, &anchors, source_image, options.colorizer);
+
+                Level {
+                    anchors,
+                    cell_map,
+                    colors,
+                }
+            }
+            Some(parent) => {
+                let anchor_parents: Vec<u32> = anchors
+                    .iter()
+                    .map(|anchor| {
+                        parent
+                            .cell_map
+                            .label(anchor.point.x as u32, anchor.point.y as u32)
+                    })
+                    .collect();
+                let mut cell_map = assign_cells_within_parents(
+                    &anchors,
+                    &anchor_parents,
+                    &parent.cell_map,
+                    &level_options,
+                );
+
+                let child_count = anchors.len() as u32;
+                for (label, parent_label) in cell_map.labels.iter_mut().zip(&parent.cell_map.labels)
+                {
+                    if (*label == UNASSIGNED) && (*parent_label != UNASSIGNED) {
+                        *label = child_count + *parent_label;
+                    }
+                }
+
+                let mut combined_anchors = anchors;
+                combined_anchors.extend(parent.anchors);
+
+                let mut colors = match coloring {
+                    NestedColoring::Resample => color_cells(
+                        &cell_map,
+                        &combined_anchors,
+                        source_image,
+                        options.colorizer,
+                    ),
+                    NestedColoring::Inherit => {
+                        let mut colors: Vec<Rgba<u8>> = anchor_parents
+                            .iter()
+                            .map(|parent_label| match *parent_label {
+                                UNASSIGNED => Rgba([0, 0, 0, 0]),
+                                parent_label => {
+                                    vary_brightness(parent.colors[parent_label as usize], rng)
+                                }
+                            })
+                            .collect();
+                        colors.extend(parent.colors.iter().copied());
+                        colors
+                    }
+                };
+                colors.truncate(combined_anchors.len());
+
+                Level {
+                    anchors: combined_anchors,
+                    cell_map,
+                    colors,
+                }
+            }
+        };
+
+        current = Some(level);
+    }
+
+    match current {
+        None => RgbaImage::new(image_width, image_height),
+        Some(mut level) => {
+            if let Some(mask) = options.mask {
+                mask.apply(&mut level.cell_map);
+            }
+            finish_cell_colors(&level.cell_map, &mut level.colors, options);
+
+            paint_voronoi(&level.cell_map, &level.anchors, level.colors, options)
+        }
+    }
+}
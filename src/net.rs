@@ -0,0 +1,26 @@
+//! Downloading input images over HTTP(S), by handing the transfer to the
+//! system `curl` so no TLS stack has to be built in.
+
+use std::io;
+use std::process::Command;
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Downloads `url` into memory, following redirects and failing on HTTP
+/// error statuses.
+pub fn fetch(url: &str) -> io::Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", "--"])
+        .arg(url)
+        .output()?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
@@ -0,0 +1,102 @@
+use crate::geometry::Point;
+use crate::palette::Palette;
+use crate::render::map_columns_on_target;
+use image::RgbaImage;
+
+/// Which combination of the nearest anchor distances becomes the noise value.
+#[derive(Clone, Copy)]
+pub enum WorleyFeature {
+    /// Distance to the nearest anchor.
+    F1,
+    /// Distance to the second nearest anchor.
+    F2,
+    /// Difference between the second nearest and nearest distances, which
+    /// highlights cell borders.
+    F2MinusF1,
+}
+
+impl WorleyFeature {
+    pub fn from_name(name: &str) -> Option<WorleyFeature> {
+        match name {
+            "f1" => Some(WorleyFeature::F1),
+            "f2" => Some(WorleyFeature::F2),
+            "f2-f1" => Some(WorleyFeature::F2MinusF1),
+            _ => None,
+        }
+    }
+}
+
+/// Computes Worley (cellular) noise over a `width`×`height` canvas, normalized
+/// to `0..=1`, returned column by column.
+pub fn worley_noise(
+    anchor_points: &[Point],
+    width: u32,
+    height: u32,
+    minimum_distance: u32,
+    feature: WorleyFeature,
+) -> Vec<Vec<f64>> {
+    let window = (minimum_distance as f64) * 4f64;
+
+    let columns = map_columns_on_target(width, |x| {
+        let candidates: Vec<&Point> = anchor_points
+            .iter()
+            .filter(|point| (point.x - (x as f64)).abs() < window)
+            .collect();
+
+        (0..height)
+            .map(|y| {
+                let pixel = Point {
+                    x: x as f64,
+                    y: y as f64,
+                };
+
+                let mut nearest = f64::INFINITY;
+                let mut second_nearest = f64::INFINITY;
+                for candidate in &candidates {
+                    let distance = pixel.squared_distance_from(candidate);
+                    if distance < nearest {
+                        second_nearest = nearest;
+                        nearest = distance;
+                    } else if distance < second_nearest {
+                        second_nearest = distance;
+                    }
+                }
+
+                let nearest = nearest.sqrt();
+                let second_nearest = second_nearest.sqrt();
+                match feature {
+                    WorleyFeature::F1 => nearest,
+                    WorleyFeature::F2 => second_nearest,
+                    WorleyFeature::F2MinusF1 => second_nearest - nearest,
+                }
+            })
+            .map(|value| if value.is_finite() { value } else { 0f64 })
+            .collect::<Vec<f64>>()
+    });
+
+    let maximum = columns
+        .iter()
+        .flatten()
+        .fold(0f64, |maximum, value| maximum.max(*value));
+    if maximum > 0f64 {
+        columns
+            .into_iter()
+            .map(|column| column.into_iter().map(|value| value / maximum).collect())
+            .collect()
+    } else {
+        columns
+    }
+}
+
+/// Maps noise values onto colors of `palette` used as a gradient.
+pub fn paint_noise(noise: &[Vec<f64>], height: u32, palette: &Palette) -> RgbaImage {
+    let mut output_image_buffer = RgbaImage::new(noise.len() as u32, height);
+
+    for (x, column) in noise.iter().enumerate() {
+        for (y, value) in column.iter().enumerate() {
+            output_image_buffer.put_pixel(x as u32, y as u32, palette.gradient(*value));
+        }
+    }
+
+    output_image_buffer
+}
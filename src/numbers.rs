@@ -0,0 +1,272 @@
+//! Numbers drawn into cells: paint-by-numbers kits, with an outline sheet
+//! numbering the paint of every region and a legend of the paints, and cell
+//! index labels.
+
+use crate::color::ColorSpace;
+use crate::font::{draw_text, text_size, GLYPH_HEIGHT};
+use crate::geometry::Point;
+use crate::merge::merge_similar_cells;
+use crate::palette::{format_hex_color, Palette};
+use crate::render::{paint_cells, paint_outlines, CellMap, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+
+const PAPER: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const LINE: Rgba<u8> = Rgba([96, 96, 96, 255]);
+const INK: Rgba<u8> = Rgba([64, 64, 64, 255]);
+
+/// The images of a paint-by-numbers kit.
+pub struct PaintByNumbers {
+    /// Region outlines with the paint number inside every region.
+    pub sheet: RgbaImage,
+    /// Every paint as a swatch with its number and hex code.
+    pub legend: RgbaImage,
+    /// The regions filled with their paints, as the finished picture.
+    pub preview: RgbaImage,
+    pub palette: Palette,
+}
+
+/// Distance from every pixel to the closest pixel of another region or the
+/// image border, by a two pass chamfer transform.
+fn distances_to_boundary(regions: &CellMap) -> Vec<f64> {
+    let (width, height) = (regions.width as i64, regions.height as i64);
+    let index = |x: i64, y: i64| ((y * width) + x) as usize;
+    let mut distances: Vec<f64> = (0..(width * height))
+        .map(|position| {
+            let (x, y) = (position % width, position / width);
+            let label = regions.labels[position as usize];
+            let is_boundary = (label == UNASSIGNED)
+                || [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .iter()
+                    .any(|&(x, y)| {
+                        (x < 0)
+                            || (y < 0)
+                            || (x >= width)
+                            || (y >= height)
+                            || (regions.labels[index(x, y)] != label)
+                    });
+            if is_boundary {
+                0f64
+            } else {
+                f64::MAX
+            }
+        })
+        .collect();
+
+    let steps = [
+        (-1, 0, 1f64),
+        (0, -1, 1f64),
+        (-1, -1, 2f64.sqrt()),
+        (1, -1, 2f64.sqrt()),
+    ];
+    let mut relax = |x: i64, y: i64, sign: i64| {
+        for (dx, dy, cost) in steps {
+            let (neighbour_x, neighbour_y) = (x + (dx * sign), y + (dy * sign));
+            if (0..width).contains(&neighbour_x) && (0..height).contains(&neighbour_y) {
+                let candidate = distances[index(neighbour_x, neighbour_y)] + cost;
+                if candidate < distances[index(x, y)] {
+                    distances[index(x, y)] = candidate;
+                }
+            }
+        }
+    };
+    for y in 0..height {
+        for x in 0..width {
+            relax(x, y, 1);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            relax(x, y, -1);
+        }
+    }
+
+    distances
+}
+
+/// Draws every paint as a row of swatch, number and hex code.
+fn paint_legend(palette: &Palette) -> RgbaImage {
+    let scale = 3;
+    let (swatch, margin) = (GLYPH_HEIGHT * scale * 2, GLYPH_HEIGHT * scale);
+    let number_width = text_size(&palette.colors.len().to_string(), scale).0;
+    let hex_width = text_size("#000000", scale).0;
+    let width = margin + swatch + margin + number_width + margin + hex_width + margin;
+    let height = margin + ((palette.colors.len() as u32) * (swatch + margin));
+
+    let mut legend = RgbaImage::from_pixel(width, height, PAPER);
+    for (index, color) in palette.colors.iter().enumerate() {
+        let top = margin + ((index as u32) * (swatch + margin));
+        for y in top..(top + swatch) {
+            for x in margin..(margin + swatch) {
+                let is_edge = (y == top)
+                    || (y == top + swatch - 1)
+                    || (x == margin)
+                    || (x == margin + swatch - 1);
+                legend.put_pixel(x, y, if is_edge { LINE } else { *color });
+            }
+        }
+
+        let text_top = (top + ((swatch - (GLYPH_HEIGHT * scale)) / 2)) as i64;
+        let number = (index + 1).to_string();
+        let number_left = margin + swatch + margin + number_width - text_size(&number, scale).0;
+        draw_text(
+            &mut legend,
+            &number,
+            number_left as i64,
+            text_top,
+            scale,
+            INK,
+        );
+        draw_text(
+            &mut legend,
+            &format_hex_color(*color),
+            (width - margin - hex_width) as i64,
+            text_top,
+            scale,
+            INK,
+        );
+    }
+
+    legend
+}
+
+/// Turns a tessellation with cell `colors` into a kit of at most `paints`
+/// paints: the colors are quantized, neighbouring cells sharing a paint are
+/// joined into regions, and every region is numbered at the point deepest
+/// inside it, in the largest text that fits up to `maximum_scale` font
+/// pixels. Regions too small for the smallest text stay unnumbered.
+pub fn paint_by_numbers(
+    cell_map: &CellMap,
+    colors: &[Rgba<u8>],
+    paints: usize,
+    maximum_scale: u32,
+) -> PaintByNumbers {
+    let mut areas = vec![0f64; colors.len()];
+    for &label in &cell_map.labels {
+        if label != UNASSIGNED {
+            areas[label as usize] += 1f64;
+        }
+    }
+    let (palette, assignments) = Palette::quantize(colors, &areas, paints);
+    let mut paint_colors: Vec<Rgba<u8>> = assignments
+        .iter()
+        .map(|assignment| palette.colors[*assignment])
+        .collect();
+
+    // Cells of the same paint have the same color, so any threshold joins
+    // exactly them.
+    let owners = merge_similar_cells(cell_map, &mut paint_colors, 0.5f64, ColorSpace::Cielab);
+    let regions = CellMap {
+        width: cell_map.width,
+        height: cell_map.height,
+        labels: cell_map
+            .labels
+            .iter()
+            .map(|label| match *label {
+                UNASSIGNED => UNASSIGNED,
+                label => owners[label as usize] as u32,
+            })
+            .collect(),
+    };
+
+    let distances = distances_to_boundary(&regions);
+    let mut deepest: Vec<Option<(usize, f64)>> = vec![None; colors.len()];
+    for (position, (label, distance)) in regions.labels.iter().zip(&distances).enumerate() {
+        if *label == UNASSIGNED {
+            continue;
+        }
+        let entry = &mut deepest[*label as usize];
+        if entry
+            .map(|(_, deepest)| *distance > deepest)
+            .unwrap_or(true)
+        {
+            *entry = Some((position, *distance));
+        }
+    }
+
+    let mut sheet = paint_outlines(&regions, &paint_colors, false, 1f64, LINE, PAPER);
+    for (region, entry) in deepest.iter().enumerate() {
+        let (position, distance) = match entry {
+            None => continue,
+            Some(entry) => *entry,
+        };
+        let number = (assignments[region] + 1).to_string();
+        let (unit_width, unit_height) = text_size(&number, 1);
+        // The text box has to fit inside the circle free of boundaries.
+        let room = (2f64 * distance) / 2f64.sqrt();
+        let scale = (room / (unit_width.max(unit_height) as f64))
+            .floor()
+            .min(maximum_scale as f64);
+        if scale < 1f64 {
+            continue;
+        }
+
+        let scale = scale as u32;
+        let (text_width, text_height) = text_size(&number, scale);
+        let (x, y) = (
+            (position % (regions.width as usize)) as i64,
+            (position / (regions.width as usize)) as i64,
+        );
+        draw_text(
+            &mut sheet,
+            &number,
+            x - ((text_width / 2) as i64),
+            y - ((text_height / 2) as i64),
+            scale,
+            INK,
+        );
+    }
+
+    PaintByNumbers {
+        sheet,
+        legend: paint_legend(&palette),
+        preview: paint_cells(&regions, &paint_colors),
+        palette,
+    }
+}
+
+/// Writes the index of every cell at its `centroid`, the text a quarter as
+/// tall as a square of the cell's `area` is wide, and drawn in black or
+/// white, whichever stands out more from the pixel beneath. Cells without a
+/// centroid, or over transparent pixels, are skipped.
+pub fn label_cells(image: &mut RgbaImage, cells: &[Option<(Point, f64)>]) {
+    let (width, height) = image.dimensions();
+    for (index, cell) in cells.iter().enumerate() {
+        let (centroid, area) = match cell {
+            None => continue,
+            Some(cell) => cell,
+        };
+        let (x, y) = (centroid.x.floor() as i64, centroid.y.floor() as i64);
+        if (x < 0) || (y < 0) || (x >= width as i64) || (y >= height as i64) {
+            continue;
+        }
+        let [red, green, blue, alpha] = image.get_pixel(x as u32, y as u32).0;
+        if alpha == 0 {
+            continue;
+        }
+
+        let label = index.to_string();
+        let (unit_width, unit_height) = text_size(&label, 1);
+        let side = area.sqrt();
+        let scale = ((side / 4f64) / (unit_height as f64))
+            .min((side * 0.6f64) / (unit_width as f64))
+            .floor()
+            .clamp(1f64, 8f64) as u32;
+        let luminance =
+            (0.2126 * (red as f64)) + (0.7152 * (green as f64)) + (0.0722 * (blue as f64));
+        let color = if luminance > 128f64 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        };
+
+        let (text_width, text_height) = text_size(&label, scale);
+        draw_text(
+            image,
+            &label,
+            x - ((text_width / 2) as i64),
+            y - ((text_height / 2) as i64),
+            scale,
+            color,
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Energy-minimizing anchor placement: the squared error between the flat
+//! cells and the image they paint is the energy, and anchors are moved
+//! towards where their cells reproduce the image worst, with an annealed
+//! random shake, keeping only the moves that lower it.
+
+use crate::anchors::color_anchor_points;
+use crate::geometry::Point;
+use crate::render::{assign_projected, color_cells, paint_cells, RenderOptions, UNASSIGNED};
+use image::RgbaImage;
+use rand::{Rng, RngCore};
+
+/// Share of the way to the error-weighted centroid of its cell every anchor
+/// moves in the first iteration.
+const INITIAL_STEP: f64 = 0.5;
+/// Smallest step worth trying before the optimization settles.
+const MINIMUM_STEP: f64 = 1f64 / 64f64;
+
+/// How far the flat cells around `points` are from `source_image`.
+struct Evaluation {
+    /// Squared error of every pixel, summed over the channels, and zero for
+    /// pixels outside every cell.
+    pixel_errors: Vec<f64>,
+    labels: Vec<u32>,
+    /// Mean squared error on the `0` to `255` scale.
+    error: f64,
+}
+
+fn evaluate(source_image: &RgbaImage, points: &[Point], options: &RenderOptions) -> Evaluation {
+    let (image_width, image_height) = source_image.dimensions();
+    let anchors = color_anchor_points(source_image, points.to_vec());
+    let cell_map = assign_projected(
+        &anchors,
+        image_width,
+        image_height,
+        options.minimum_distance,
+        options,
+        None,
+    );
+    let colors = color_cells(&cell_map, &anchors, source_image, options.colorizer);
+    let reconstruction = paint_cells(&cell_map, &colors);
+
+    let (mut total, mut painted_pixels) = (0f64, 0usize);
+    let pixel_errors: Vec<f64> = source_image
+        .pixels()
+        .zip(reconstruction.pixels())
+        .zip(&cell_map.labels)
+        .map(|((original, painted), label)| {
+            if *label == UNASSIGNED {
+                return 0f64;
+            }
+            let error = (0..3)
+                .map(|channel| {
+                    let difference = (original.0[channel] as f64) - (painted.0[channel] as f64);
+                    difference * difference
+                })
+                .sum::<f64>();
+            total += error;
+            painted_pixels += 1;
+            error
+        })
+        .collect();
+
+    Evaluation {
+        pixel_errors,
+        labels: cell_map.labels,
+        error: total / ((painted_pixels * 3).max(1) as f64),
+    }
+}
+
+/// Moves every anchor `step` of the way to the centroid of its cell with
+/// every pixel weighted by its error, and shakes it by up to `shake` pixels.
+fn propose(
+    points: &[Point],
+    evaluation: &Evaluation,
+    (image_width, image_height): (u32, u32),
+    step: f64,
+    shake: f64,
+    rng: &mut dyn RngCore,
+) -> Vec<Point> {
+    let mut sums = vec![(0f64, 0f64, 0f64); points.len()];
+    for (index, (label, error)) in evaluation
+        .labels
+        .iter()
+        .zip(&evaluation.pixel_errors)
+        .enumerate()
+    {
+        if *label == UNASSIGNED {
+            continue;
+        }
+        let sum = &mut sums[*label as usize];
+        sum.0 += error * ((index % (image_width as usize)) as f64);
+        sum.1 += error * ((index / (image_width as usize)) as f64);
+        sum.2 += error;
+    }
+
+    points
+        .iter()
+        .zip(sums)
+        .map(|(point, (weighted_x, weighted_y, total_error))| {
+            let (mut x, mut y) = (point.x, point.y);
+            if total_error > 0f64 {
+                x += step * ((weighted_x / total_error) - x);
+                y += step * ((weighted_y / total_error) - y);
+            }
+            if shake > 0f64 {
+                x += rng.gen_range(-shake..=shake);
+                y += rng.gen_range(-shake..=shake);
+            }
+
+            Point {
+                x: x.clamp(0f64, (image_width - 1) as f64),
+                y: y.clamp(0f64, (image_height - 1) as f64),
+            }
+        })
+        .collect()
+}
+
+/// The moves of `proposed` that lower the error of the pixels the moved
+/// anchor's cell covers before or after it, with the other anchors where
+/// they are in `points`.
+fn keep_improving_moves(
+    points: &[Point],
+    evaluation: &Evaluation,
+    proposed: &[Point],
+    proposed_evaluation: &Evaluation,
+) -> Vec<Point> {
+    let mut changes = vec![0f64; points.len()];
+    for (((label, proposed_label), error), proposed_error) in evaluation
+        .labels
+        .iter()
+        .zip(&proposed_evaluation.labels)
+        .zip(&evaluation.pixel_errors)
+        .zip(&proposed_evaluation.pixel_errors)
+    {
+        let change = proposed_error - error;
+        if *label != UNASSIGNED {
+            changes[*label as usize] += change;
+        }
+        if (*proposed_label != UNASSIGNED) && (proposed_label != label) {
+            changes[*proposed_label as usize] += change;
+        }
+    }
+
+    points
+        .iter()
+        .zip(proposed)
+        .zip(changes)
+        .map(|((point, proposed), change)| match change < 0f64 {
+            true => proposed.clone(),
+            false => point.clone(),
+        })
+        .collect()
+}
+
+/// Treats the reconstruction error of the flat cells around `anchor_points`
+/// as an energy and lowers it for up to `iterations` rounds: every round
+/// moves all anchors towards the error-weighted centroid of their cell and
+/// shakes them by an amount that cools from half of `spacing`, keeps the
+/// moves that lower the error around the anchor, and takes them only when
+/// they lower the error of the whole image, otherwise trying again with half
+/// the step. The number of anchors never changes.
+///
+/// Returns the points, the mean squared error they started at and the one
+/// they reach, on the `0` to `255` scale.
+pub fn optimize_anchors(
+    source_image: &RgbaImage,
+    anchor_points: Vec<Point>,
+    iterations: u32,
+    spacing: f64,
+    options: &RenderOptions,
+    rng: &mut dyn RngCore,
+) -> (Vec<Point>, f64, f64) {
+    let dimensions = source_image.dimensions();
+    let mut points = anchor_points;
+    let mut evaluation = evaluate(source_image, &points, options);
+    let initial_error = evaluation.error;
+    if points.is_empty() {
+        return (points, initial_error, initial_error);
+    }
+
+    let mut step = INITIAL_STEP;
+    for iteration in 0..iterations {
+        let temperature = 1f64 - ((iteration as f64) / (iterations as f64));
+        let shake = temperature * step * spacing / 2f64;
+        let proposed = propose(&points, &evaluation, dimensions, step, shake, rng);
+        let proposed_evaluation = evaluate(source_image, &proposed, options);
+        let kept = keep_improving_moves(&points, &evaluation, &proposed, &proposed_evaluation);
+        let kept_evaluation = evaluate(source_image, &kept, options);
+
+        if kept_evaluation.error < evaluation.error {
+            points = kept;
+            evaluation = kept_evaluation;
+            step = (step * 1.5f64).min(1f64);
+        } else {
+            step /= 2f64;
+            if step < MINIMUM_STEP {
+                break;
+            }
+        }
+    }
+
+    (points, initial_error, evaluation.error)
+}
@@ -0,0 +1,140 @@
+//! Cells that elongate along the edges of the source image, like brush
+//! strokes following its structures.
+
+use crate::analysis::sobel;
+use crate::geometry::{DistanceMetric, Point};
+use image::RgbaImage;
+
+/// Direction of the local image structure at every pixel, from the smoothed
+/// structure tensor of the luminance.
+pub struct OrientationField {
+    pub width: u32,
+    pub height: u32,
+    /// Angle of the edge direction in radians, stored row by row.
+    angles: Vec<f64>,
+    /// How strongly the neighbourhood agrees on that direction, from `0` for
+    /// flat or noisy areas to `1` for a single clean edge.
+    coherence: Vec<f64>,
+}
+
+/// Averages `values` over a `(2 * radius + 1)` square window, clamped to the
+/// image.
+fn box_blur(values: &[f64], width: usize, height: usize, radius: usize) -> Vec<f64> {
+    let blur_lines = |values: &[f64], length: usize, lines: usize, stride: usize, step: usize| {
+        let mut blurred = vec![0f64; values.len()];
+        for line in 0..lines {
+            let at = |index: usize| values[(line * stride) + (index * step)];
+            let mut sum: f64 = (0..=radius.min(length - 1)).map(at).sum();
+            for index in 0..length {
+                let (start, end) = (
+                    index.saturating_sub(radius),
+                    (index + radius).min(length - 1),
+                );
+                blurred[(line * stride) + (index * step)] = sum / ((end - start + 1) as f64);
+
+                if index + radius + 1 < length {
+                    sum += at(index + radius + 1);
+                }
+                if index >= radius {
+                    sum -= at(index - radius);
+                }
+            }
+        }
+
+        blurred
+    };
+
+    let horizontal = blur_lines(values, width, height, width, 1);
+    blur_lines(&horizontal, height, width, 1, width)
+}
+
+impl OrientationField {
+    /// Estimates the orientation of `image`, averaging gradients over a
+    /// window of `radius` pixels around every pixel.
+    pub fn from_image(image: &RgbaImage, radius: u32) -> OrientationField {
+        let (width, height) = image.dimensions();
+        let (horizontal, vertical) = sobel(image);
+
+        let (columns, rows, radius) = (width as usize, height as usize, radius as usize);
+        let xx: Vec<f64> = horizontal.iter().map(|gx| gx * gx).collect();
+        let xy: Vec<f64> = horizontal
+            .iter()
+            .zip(&vertical)
+            .map(|(gx, gy)| gx * gy)
+            .collect();
+        let yy: Vec<f64> = vertical.iter().map(|gy| gy * gy).collect();
+        let (xx, xy, yy) = (
+            box_blur(&xx, columns, rows, radius),
+            box_blur(&xy, columns, rows, radius),
+            box_blur(&yy, columns, rows, radius),
+        );
+
+        let mut angles = Vec::with_capacity(xx.len());
+        let mut coherence = Vec::with_capacity(xx.len());
+        for index in 0..xx.len() {
+            let difference = xx[index] - yy[index];
+            let trace = xx[index] + yy[index];
+            let gradient_angle = 0.5f64 * (2f64 * xy[index]).atan2(difference);
+
+            angles.push(gradient_angle + std::f64::consts::FRAC_PI_2);
+            coherence.push(if trace > f64::EPSILON {
+                ((difference * difference) + (4f64 * xy[index] * xy[index])).sqrt() / trace
+            } else {
+                0f64
+            });
+        }
+
+        OrientationField {
+            width,
+            height,
+            angles,
+            coherence,
+        }
+    }
+
+    /// Edge angle and coherence at the pixel nearest to `point`, clamped to
+    /// the image.
+    pub fn at(&self, point: &Point) -> (f64, f64) {
+        let x = point.x.round().clamp(0f64, (self.width as f64) - 1f64) as usize;
+        let y = point.y.round().clamp(0f64, (self.height as f64) - 1f64) as usize;
+        let index = (y * (self.width as usize)) + x;
+
+        (self.angles[index], self.coherence[index])
+    }
+}
+
+/// Another metric measured in a frame turned to the edge direction at each
+/// anchor, where distances along the edge shrink by up to `stretch` where the
+/// orientation is coherent.
+pub struct Oriented<'a> {
+    pub metric: Box<dyn DistanceMetric>,
+    pub field: &'a OrientationField,
+    pub stretch: f64,
+}
+
+impl Oriented<'_> {
+    /// Where `from` lies in the turned and shrunk frame of the anchor at `to`.
+    fn turn(&self, from: &Point, to: &Point) -> Point {
+        let (angle, coherence) = self.field.at(to);
+        let (sin, cos) = angle.sin_cos();
+        let (dx, dy) = (from.x - to.x, from.y - to.y);
+        let stretch = 1f64 + ((self.stretch - 1f64) * coherence.clamp(0f64, 1f64));
+
+        Point {
+            x: ((dx * cos) + (dy * sin)) / stretch,
+            y: (dy * cos) - (dx * sin),
+        }
+    }
+}
+
+impl DistanceMetric for Oriented<'_> {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        self.metric
+            .distance(&self.turn(from, to), &Point { x: 0f64, y: 0f64 })
+    }
+
+    fn length(&self, from: &Point, to: &Point) -> f64 {
+        self.metric
+            .length(&self.turn(from, to), &Point { x: 0f64, y: 0f64 })
+    }
+}
@@ -0,0 +1,137 @@
+//! Multi-page TIFFs: tessellating one page of an input that has several,
+//! and writing the painting and its original as the pages of one file.
+
+use crate::geotiff::{
+    GeoReference, GEO_ASCII_PARAMS, GEO_DOUBLE_PARAMS, GEO_KEY_DIRECTORY, MODEL_PIXEL_SCALE,
+    MODEL_TIEPOINT, MODEL_TRANSFORMATION,
+};
+use image::{Rgba, RgbaImage};
+use std::io::{Read, Seek, Write};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+/// The `PageNumber` tag, the page and the count of pages.
+const PAGE_NUMBER: u16 = 297;
+const ICC_PROFILE: u16 = 34675;
+
+/// Decodes the page at `index` of the TIFF `reader`, the first page being
+/// `0`.
+pub fn read_tiff_page<R: Read + Seek>(reader: R, index: usize) -> Result<RgbaImage, String> {
+    let mut decoder = Decoder::new(reader).map_err(|error| error.to_string())?;
+    for page in 0..index {
+        if !decoder.more_images() {
+            return Err(format!(
+                "there is no page {}, the TIFF has {}",
+                index + 1,
+                page + 1
+            ));
+        }
+        decoder.next_image().map_err(|error| error.to_string())?;
+    }
+
+    let (width, height) = decoder.dimensions().map_err(|error| error.to_string())?;
+    let color_type = decoder.colortype().map_err(|error| error.to_string())?;
+    // Every sample scaled to eight bits.
+    let samples: Vec<u8> = match decoder.read_image().map_err(|error| error.to_string())? {
+        DecodingResult::U8(samples) => samples,
+        DecodingResult::U16(samples) => samples.iter().map(|sample| (sample >> 8) as u8).collect(),
+        _ => return Err(String::from("only 8 and 16 bit pages are supported")),
+    };
+
+    let channels = match color_type {
+        ColorType::Gray(8 | 16) => 1,
+        ColorType::GrayA(8 | 16) => 2,
+        ColorType::RGB(8 | 16) => 3,
+        ColorType::RGBA(8 | 16) | ColorType::CMYK(8) => 4,
+        _ => return Err(String::from("unsupported TIFF color type")),
+    };
+    if samples.len() < (width as usize) * (height as usize) * channels {
+        return Err(String::from("page is truncated"));
+    }
+    let is_cmyk = matches!(color_type, ColorType::CMYK(_));
+
+    Ok(RgbaImage::from_fn(width, height, |x, y| {
+        let start = (((y as usize) * (width as usize)) + (x as usize)) * channels;
+        let pixel = &samples[start..(start + channels)];
+        match pixel {
+            [gray] => Rgba([*gray, *gray, *gray, 255]),
+            [gray, alpha] => Rgba([*gray, *gray, *gray, *alpha]),
+            [red, green, blue] => Rgba([*red, *green, *blue, 255]),
+            [cyan, magenta, yellow, black] if is_cmyk => {
+                let ink =
+                    |channel: u8| ((255 - channel as u32) * (255 - *black as u32) / 255) as u8;
+                Rgba([ink(*cyan), ink(*magenta), ink(*yellow), 255])
+            }
+            _ => Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]),
+        }
+    }))
+}
+
+/// Writes every one of `pages` to `writer` as a page of one TIFF, in order,
+/// tagged with `icc_profile` if there is one. Pages covering the same area
+/// as the first are placed on the map with `georeference`, that of the
+/// first, if there is one.
+pub fn write_tiff_pages<W: Write + Seek>(
+    pages: &[&RgbaImage],
+    icc_profile: Option<&[u8]>,
+    georeference: Option<&GeoReference>,
+    writer: W,
+) -> Result<(), String> {
+    let mut encoder = TiffEncoder::new(writer).map_err(|error| error.to_string())?;
+    for (index, page) in pages.iter().enumerate() {
+        let mut image = encoder
+            .new_image::<colortype::RGBA8>(page.width(), page.height())
+            .map_err(|error| error.to_string())?;
+        image
+            .encoder()
+            .write_tag(
+                Tag::Unknown(PAGE_NUMBER),
+                &[index as u16, pages.len() as u16][..],
+            )
+            .map_err(|error| error.to_string())?;
+        if let Some(icc_profile) = icc_profile {
+            image
+                .encoder()
+                .write_tag(Tag::Unknown(ICC_PROFILE), icc_profile)
+                .map_err(|error| error.to_string())?;
+        }
+        if let Some(georeference) = georeference {
+            let georeference = georeference.scaled(pages[0].dimensions(), page.dimensions());
+            let encoder = image.encoder();
+            let doubles = [
+                (MODEL_PIXEL_SCALE, &georeference.pixel_scale),
+                (MODEL_TIEPOINT, &georeference.tie_points),
+                (MODEL_TRANSFORMATION, &georeference.transformation),
+                (GEO_DOUBLE_PARAMS, &georeference.double_params),
+            ];
+            for (tag, values) in doubles {
+                if let Some(values) = values {
+                    encoder
+                        .write_tag(Tag::from_u16_exhaustive(tag), values.as_slice())
+                        .map_err(|error| error.to_string())?;
+                }
+            }
+            encoder
+                .write_tag(
+                    Tag::from_u16_exhaustive(GEO_KEY_DIRECTORY),
+                    georeference.key_directory.as_slice(),
+                )
+                .map_err(|error| error.to_string())?;
+            if let Some(ascii_params) = &georeference.ascii_params {
+                encoder
+                    .write_tag(
+                        Tag::from_u16_exhaustive(GEO_ASCII_PARAMS),
+                        ascii_params.as_str(),
+                    )
+                    .map_err(|error| error.to_string())?;
+            }
+        }
+        image
+            .write_data(page.as_raw())
+            .map_err(|error| error.to_string())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,408 @@
+use crate::color::ColorSpace;
+use image::Rgba;
+use rand::{Rng, RngCore};
+use std::fs;
+use std::io;
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Opaque color of `hue` in degrees, with `saturation` and `value` in `0..=1`.
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Rgba<u8> {
+    let chroma = value * saturation;
+    let sector = hue / 60f64;
+    let second = chroma * (1f64 - ((sector % 2f64) - 1f64).abs());
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, second, 0f64),
+        1 => (second, chroma, 0f64),
+        2 => (0f64, chroma, second),
+        3 => (0f64, second, chroma),
+        4 => (second, 0f64, chroma),
+        _ => (chroma, 0f64, second),
+    };
+
+    let offset = value - chroma;
+    let to_channel = |channel: f64| (((channel + offset) * 255f64).round()) as u8;
+    Rgba([
+        to_channel(red),
+        to_channel(green),
+        to_channel(blue),
+        u8::MAX,
+    ])
+}
+
+/// Colors of a GIMP palette, whose entries are lines of `red green blue name`
+/// after a header of `Name:` and `Columns:` lines and `#` comments.
+fn parse_gpl(contents: &str) -> io::Result<Vec<Rgba<u8>>> {
+    let mut colors = Vec::new();
+    for (line_number, line) in contents.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+
+        let channels = line
+            .split_whitespace()
+            .take(3)
+            .map(|channel| channel.parse::<u8>().ok())
+            .collect::<Option<Vec<u8>>>()
+            .filter(|channels| channels.len() == 3)
+            .ok_or_else(|| {
+                invalid_data(format!(
+                    "Invalid color `{}` on line {}",
+                    line,
+                    line_number + 1
+                ))
+            })?;
+        colors.push(Rgba([channels[0], channels[1], channels[2], u8::MAX]));
+    }
+
+    Ok(colors)
+}
+
+/// Colors of an Adobe swatch exchange file. RGB, gray, CMYK and LAB swatches
+/// are converted to sRGB; groups are flattened.
+fn parse_ase(contents: &[u8]) -> io::Result<Vec<Rgba<u8>>> {
+    let truncated = || invalid_data(String::from("Swatch file is truncated"));
+    let bytes = |offset: usize, length: usize| contents.get(offset..(offset + length));
+    let read_u16 = |offset: usize| {
+        bytes(offset, 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(truncated)
+    };
+    let read_u32 = |offset: usize| {
+        bytes(offset, 4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or_else(truncated)
+    };
+    let read_f32 = |offset: usize| read_u32(offset).map(|bits| f32::from_bits(bits) as f64);
+    let to_channel = |value: f64| (value.clamp(0f64, 1f64) * 255f64).round() as u8;
+
+    let block_count = read_u32(8)?;
+    let mut colors = Vec::new();
+    let mut offset = 12;
+    for _ in 0..block_count {
+        let (block_type, length) = (read_u16(offset)?, read_u32(offset + 2)? as usize);
+        let body = offset + 6;
+        offset = body + length;
+        if block_type != 0x0001 {
+            continue;
+        }
+
+        // The name is a length-prefixed UTF-16 string.
+        let name_length = read_u16(body)? as usize;
+        let model = body + 2 + (name_length * 2);
+        let values = model + 4;
+        let color = match bytes(model, 4).ok_or_else(truncated)? {
+            b"RGB " => [
+                read_f32(values)?,
+                read_f32(values + 4)?,
+                read_f32(values + 8)?,
+            ]
+            .map(to_channel),
+            b"Gray" => [to_channel(read_f32(values)?); 3],
+            b"CMYK" => {
+                let key = read_f32(values + 12)?;
+                [
+                    read_f32(values)?,
+                    read_f32(values + 4)?,
+                    read_f32(values + 8)?,
+                ]
+                .map(|ink| to_channel((1f64 - ink) * (1f64 - key)))
+            }
+            b"LAB " => ColorSpace::Cielab.decode([
+                read_f32(values)? * 100f64,
+                read_f32(values + 4)?,
+                read_f32(values + 8)?,
+            ]),
+            model => {
+                return Err(invalid_data(format!(
+                    "Unsupported swatch color model `{}`",
+                    String::from_utf8_lossy(model).trim()
+                )))
+            }
+        };
+        colors.push(Rgba([color[0], color[1], color[2], u8::MAX]));
+    }
+
+    Ok(colors)
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA` (the leading `#` is optional).
+pub fn parse_hex_color(value: &str) -> Option<Rgba<u8>> {
+    let hex = value.trim().trim_start_matches('#');
+    if !hex.is_ascii() || ((hex.len() != 6) && (hex.len() != 8)) {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[(index * 2)..(index * 2 + 2)], 16).ok();
+    let alpha = if hex.len() == 8 { channel(3)? } else { u8::MAX };
+
+    Some(Rgba([channel(0)?, channel(1)?, channel(2)?, alpha]))
+}
+
+/// Formats a color as `#RRGGBB`, or `#RRGGBBAA` when it is not opaque.
+pub fn format_hex_color(color: Rgba<u8>) -> String {
+    let [red, green, blue, alpha] = color.0;
+    if alpha == u8::MAX {
+        format!("#{:02x}{:02x}{:02x}", red, green, blue)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", red, green, blue, alpha)
+    }
+}
+
+/// An ordered list of colors, usable as discrete entries or as a gradient.
+#[derive(Clone)]
+pub struct Palette {
+    pub colors: Vec<Rgba<u8>>,
+}
+
+impl Palette {
+    /// Parses a comma separated list of hex colors.
+    pub fn parse_list(value: &str) -> Option<Palette> {
+        let colors = value
+            .split(',')
+            .map(parse_hex_color)
+            .collect::<Option<Vec<Rgba<u8>>>>()?;
+
+        if colors.is_empty() {
+            None
+        } else {
+            Some(Palette { colors })
+        }
+    }
+
+    /// Reads a palette file: a GIMP `.gpl` palette, an Adobe `.ase` swatch
+    /// exchange file, or one hex color per line. Empty lines and lines starting
+    /// with `;` are ignored in the latter.
+    pub fn load(path: &str) -> io::Result<Palette> {
+        let contents = fs::read(path)?;
+        if contents.starts_with(b"ASEF") {
+            return Palette::from_colors(parse_ase(&contents)?);
+        }
+
+        let contents = String::from_utf8(contents)
+            .map_err(|_| invalid_data(String::from("Palette is not valid UTF-8 text")))?;
+        if contents.trim_start().starts_with("GIMP Palette") {
+            return Palette::from_colors(parse_gpl(&contents)?);
+        }
+
+        let mut colors = Vec::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            match parse_hex_color(line) {
+                None => {
+                    return Err(invalid_data(format!(
+                        "Invalid color `{}` on line {}",
+                        line,
+                        line_number + 1
+                    )));
+                }
+                Some(color) => colors.push(color),
+            }
+        }
+
+        Palette::from_colors(colors)
+    }
+
+    fn from_colors(colors: Vec<Rgba<u8>>) -> io::Result<Palette> {
+        if colors.is_empty() {
+            Err(invalid_data(String::from(
+                "Palette does not contain any colors",
+            )))
+        } else {
+            Ok(Palette { colors })
+        }
+    }
+
+    /// The entry closest to `color` in `color_space`, keeping the alpha of
+    /// `color`.
+    pub fn nearest(&self, color: Rgba<u8>, color_space: ColorSpace) -> Rgba<u8> {
+        let target = color_space.encode(color);
+        let closest = self
+            .colors
+            .iter()
+            .map(|entry| {
+                let distance: f64 = color_space
+                    .encode(*entry)
+                    .iter()
+                    .zip(&target)
+                    .map(|(from, to)| (from - to) * (from - to))
+                    .sum();
+                (distance, entry)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, entry)| *entry)
+            .unwrap_or(color);
+
+        Rgba([closest.0[0], closest.0[1], closest.0[2], color.0[3]])
+    }
+
+    /// `count` colors whose hues step through `hue_range`, in degrees, by the
+    /// golden angle from a random start so neighbours in the list stay apart.
+    /// A range whose end is below its start wraps around red.
+    pub fn harmonious(count: usize, hue_range: (f64, f64), rng: &mut dyn RngCore) -> Palette {
+        const GOLDEN_RATIO_CONJUGATE: f64 = 0.618033988749895;
+
+        let (from, to) = hue_range;
+        let span = if to < from {
+            (to + 360f64) - from
+        } else {
+            to - from
+        };
+        let start = rng.gen::<f64>();
+        let colors = (0..count)
+            .map(|index| {
+                let step = (start + ((index as f64) * GOLDEN_RATIO_CONJUGATE)).fract();
+                let hue = (from + (step * span)).rem_euclid(360f64);
+                let saturation = 0.5f64 + (rng.gen::<f64>() * 0.3f64);
+                let value = 0.75f64 + (rng.gen::<f64>() * 0.2f64);
+                hsv_to_rgb(hue, saturation, value)
+            })
+            .collect();
+
+        Palette { colors }
+    }
+
+    /// Reduces `colors` to at most `count` entries with k-means in CIELAB,
+    /// every color counting as much as its `weight`, such as the area of its
+    /// cell. Clusters are seeded farthest first from the heaviest color, so
+    /// the result does not depend on chance. Entries are ordered from dark to
+    /// light.
+    ///
+    /// Returns the palette and the entry every color was assigned to.
+    pub fn quantize(colors: &[Rgba<u8>], weights: &[f64], count: usize) -> (Palette, Vec<usize>) {
+        let points: Vec<[f64; 3]> = colors
+            .iter()
+            .map(|color| ColorSpace::Cielab.encode(*color))
+            .collect();
+        let squared_distance = |from: &[f64; 3], to: &[f64; 3]| -> f64 {
+            from.iter()
+                .zip(to)
+                .map(|(from, to)| (from - to) * (from - to))
+                .sum()
+        };
+        let nearest = |centers: &[[f64; 3]], point: &[f64; 3]| -> usize {
+            (0..centers.len())
+                .min_by(|a, b| {
+                    squared_distance(&centers[*a], point)
+                        .total_cmp(&squared_distance(&centers[*b], point))
+                })
+                .unwrap_or(0)
+        };
+
+        let mut centers: Vec<[f64; 3]> = Vec::with_capacity(count);
+        if let Some(heaviest) = (0..points.len()).max_by(|a, b| weights[*a].total_cmp(&weights[*b]))
+        {
+            centers.push(points[heaviest]);
+        }
+        while centers.len() < count.min(points.len()) {
+            let (farthest, distance) = points
+                .iter()
+                .enumerate()
+                .map(|(index, point)| {
+                    let distance = centers
+                        .iter()
+                        .map(|center| squared_distance(center, point))
+                        .fold(f64::MAX, f64::min);
+                    (index, distance)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0f64));
+            if distance <= 0f64 {
+                break;
+            }
+            centers.push(points[farthest]);
+        }
+
+        let mut assignments: Vec<usize> = points
+            .iter()
+            .map(|point| nearest(&centers, point))
+            .collect();
+        for _ in 0..32 {
+            let mut sums = vec![([0f64; 3], 0f64); centers.len()];
+            for ((point, weight), assignment) in points.iter().zip(weights).zip(&assignments) {
+                let (sum, total) = &mut sums[*assignment];
+                for (sum, channel) in sum.iter_mut().zip(point) {
+                    *sum += channel * weight;
+                }
+                *total += weight;
+            }
+            for (center, (sum, total)) in centers.iter_mut().zip(&sums) {
+                if *total > 0f64 {
+                    *center = sum.map(|sum| sum / total);
+                }
+            }
+
+            let updated: Vec<usize> = points
+                .iter()
+                .map(|point| nearest(&centers, point))
+                .collect();
+            if updated == assignments {
+                break;
+            }
+            assignments = updated;
+        }
+
+        // Clusters that lost all their colors are left out.
+        let mut order: Vec<usize> = (0..centers.len())
+            .filter(|index| assignments.contains(index))
+            .collect();
+        order.sort_by(|a, b| centers[*a][0].total_cmp(&centers[*b][0]));
+        let mut ranks = vec![0usize; centers.len()];
+        for (rank, index) in order.iter().enumerate() {
+            ranks[*index] = rank;
+        }
+
+        let colors = order
+            .iter()
+            .map(|index| {
+                let [red, green, blue] = ColorSpace::Cielab.decode(centers[*index]);
+                Rgba([red, green, blue, u8::MAX])
+            })
+            .collect();
+        let assignments = assignments.into_iter().map(|index| ranks[index]).collect();
+
+        (Palette { colors }, assignments)
+    }
+
+    pub fn grayscale() -> Palette {
+        Palette {
+            colors: vec![
+                Rgba([0, 0, 0, u8::MAX]),
+                Rgba([u8::MAX, u8::MAX, u8::MAX, u8::MAX]),
+            ],
+        }
+    }
+
+    /// Linearly interpolates between neighbouring colors, with `t` in `0..=1`.
+    pub fn gradient(&self, t: f64) -> Rgba<u8> {
+        if self.colors.len() == 1 {
+            return self.colors[0];
+        }
+
+        let position = t.clamp(0f64, 1f64) * ((self.colors.len() - 1) as f64);
+        let index = (position.floor() as usize).min(self.colors.len() - 2);
+        let fraction = position - (index as f64);
+
+        let from = self.colors[index].0;
+        let to = self.colors[index + 1].0;
+        let mut blended = [0u8; 4];
+        for channel in 0..4 {
+            blended[channel] = ((from[channel] as f64)
+                + (((to[channel] as f64) - (from[channel] as f64)) * fraction))
+                .round() as u8;
+        }
+
+        Rgba(blended)
+    }
+}
@@ -0,0 +1,140 @@
+//! Shaping the rings of vector exports: Douglas-Peucker simplification,
+//! which drops the stair-step vertices of outlines traced from pixels, and
+//! smoothing, which rounds every corner into a curve.
+
+use crate::geometry::Point;
+
+/// How the rings of vector exports are shaped, unchanged by default.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct PathShaping {
+    /// Drop vertices closer than this many pixels to the simplified ring.
+    pub simplify: Option<f64>,
+    /// Round every corner into a curve.
+    pub smooth: bool,
+}
+
+/// Line segments every rounded corner of [`smooth_ring`] is drawn with.
+const CORNER_SEGMENTS: usize = 8;
+
+impl PathShaping {
+    /// Shapes `ring`, simplifying it before smoothing it.
+    pub fn apply(&self, ring: &[Point]) -> Vec<Point> {
+        let ring = match self.simplify {
+            None => ring.to_vec(),
+            Some(tolerance) => simplify_ring(ring, tolerance),
+        };
+
+        match self.smooth {
+            true => smooth_ring(&ring),
+            false => ring,
+        }
+    }
+}
+
+/// Distance from `point` to the segment from `start` to `end`.
+fn distance_to_segment(point: &Point, start: &Point, end: &Point) -> f64 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length = (dx * dx) + (dy * dy);
+    let t = match length > 0f64 {
+        true => {
+            ((((point.x - start.x) * dx) + ((point.y - start.y) * dy)) / length).clamp(0f64, 1f64)
+        }
+        false => 0f64,
+    };
+    let closest = Point {
+        x: start.x + (t * dx),
+        y: start.y + (t * dy),
+    };
+
+    point.squared_distance_from(&closest).sqrt()
+}
+
+/// Marks the vertices of `points` from `first` to `last` the Douglas-Peucker
+/// algorithm keeps at `tolerance`.
+fn mark_kept(points: &[Point], first: usize, last: usize, tolerance: f64, kept: &mut [bool]) {
+    let farthest = ((first + 1)..last)
+        .map(|index| {
+            (
+                index,
+                distance_to_segment(&points[index], &points[first], &points[last]),
+            )
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((index, distance)) = farthest {
+        if distance > tolerance {
+            kept[index] = true;
+            mark_kept(points, first, index, tolerance, kept);
+            mark_kept(points, index, last, tolerance, kept);
+        }
+    }
+}
+
+/// The closed `ring` with the vertices Douglas-Peucker drops at `tolerance`
+/// pixels removed, split at its first vertex and the one farthest from it.
+/// Rings that would fall below a triangle are kept as they are.
+pub fn simplify_ring(ring: &[Point], tolerance: f64) -> Vec<Point> {
+    if ring.len() <= 3 {
+        return ring.to_vec();
+    }
+
+    let farthest = (1..ring.len())
+        .max_by(|a, b| {
+            ring[*a]
+                .squared_distance_from(&ring[0])
+                .total_cmp(&ring[*b].squared_distance_from(&ring[0]))
+        })
+        .unwrap_or(1);
+    // The ring closed back onto its first vertex.
+    let mut points = ring.to_vec();
+    points.push(ring[0].clone());
+    let mut kept = vec![false; points.len()];
+    kept[0] = true;
+    kept[farthest] = true;
+    mark_kept(&points, 0, farthest, tolerance, &mut kept);
+    mark_kept(&points, farthest, ring.len(), tolerance, &mut kept);
+
+    let simplified: Vec<Point> = ring
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| **kept)
+        .map(|(point, _)| point.clone())
+        .collect();
+    match simplified.len() >= 3 {
+        true => simplified,
+        false => ring.to_vec(),
+    }
+}
+
+/// The closed `ring` with every corner replaced by the quadratic Bézier
+/// curve from the middle of the edge before it to the middle of the edge
+/// after it, pulled towards the corner.
+///
+/// The middle of every edge stays on the ring with its tangent along the
+/// edge, so cells sharing an edge still touch there, and curve apart into
+/// small gaps towards the corners where more of them meet, like pebbles.
+pub fn smooth_ring(ring: &[Point]) -> Vec<Point> {
+    if ring.len() < 3 {
+        return ring.to_vec();
+    }
+
+    let middle = |a: &Point, b: &Point| Point {
+        x: (a.x + b.x) / 2f64,
+        y: (a.y + b.y) / 2f64,
+    };
+    let mut smoothed = Vec::with_capacity(ring.len() * CORNER_SEGMENTS);
+    for (index, corner) in ring.iter().enumerate() {
+        let before = &ring[(index + ring.len() - 1) % ring.len()];
+        let after = &ring[(index + 1) % ring.len()];
+        let (start, end) = (middle(before, corner), middle(corner, after));
+        for step in 0..CORNER_SEGMENTS {
+            let t = (step as f64) / (CORNER_SEGMENTS as f64);
+            let (a, b, c) = ((1f64 - t) * (1f64 - t), 2f64 * (1f64 - t) * t, t * t);
+            smoothed.push(Point {
+                x: (a * start.x) + (b * corner.x) + (c * end.x),
+                y: (a * start.y) + (b * corner.y) + (c * end.y),
+            });
+        }
+    }
+
+    smoothed
+}
@@ -0,0 +1,112 @@
+//! Pattern fills that shade cells with ink whose coverage follows how dark
+//! the cell color is, as in engravings and screen prints.
+
+use crate::render::{CellMap, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+
+/// How the inside of a cell is drawn.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FillPattern {
+    Solid,
+    /// Diagonal lines running from the bottom left to the top right.
+    Hatch,
+    /// Diagonal lines in both directions.
+    CrossHatch,
+    /// A grid of round dots.
+    Dots,
+}
+
+/// Rec. 709 luma of `color`, in `0..=1`.
+fn luminance(color: Rgba<u8>) -> f64 {
+    let [red, green, blue, _] = color.0;
+    ((0.2126 * (red as f64)) + (0.7152 * (green as f64)) + (0.0722 * (blue as f64))) / 255f64
+}
+
+/// Share of a pattern tile that is inked for `color`: black is covered
+/// completely and white not at all.
+pub fn ink_coverage(color: Rgba<u8>) -> f64 {
+    1f64 - luminance(color)
+}
+
+impl FillPattern {
+    pub fn from_name(name: &str) -> Option<FillPattern> {
+        match name {
+            "solid" => Some(FillPattern::Solid),
+            "hatch" => Some(FillPattern::Hatch),
+            "cross-hatch" => Some(FillPattern::CrossHatch),
+            "dots" => Some(FillPattern::Dots),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FillPattern::Solid => "solid",
+            FillPattern::Hatch => "hatch",
+            FillPattern::CrossHatch => "cross-hatch",
+            FillPattern::Dots => "dots",
+        }
+    }
+
+    /// Width of the hatch lines, or the radius of the dots, that ink
+    /// `coverage` of every `spacing` wide tile.
+    pub fn stroke_size(self, coverage: f64, spacing: f64) -> f64 {
+        let coverage = coverage.clamp(0f64, 1f64);
+        match self {
+            FillPattern::Solid => spacing,
+            FillPattern::Hatch => coverage * spacing,
+            // Two line sets overlap, so each covers less to reach the total.
+            FillPattern::CrossHatch => (1f64 - (1f64 - coverage).sqrt()) * spacing,
+            FillPattern::Dots => {
+                (spacing * (coverage / std::f64::consts::PI).sqrt()).min(spacing / 2f64.sqrt())
+            }
+        }
+    }
+
+    /// Whether the point `x`, `y` is inked in a pattern of `spacing` pixel
+    /// tiles covering `coverage` of the area.
+    pub fn is_inked(self, x: f64, y: f64, coverage: f64, spacing: f64) -> bool {
+        let size = self.stroke_size(coverage, spacing);
+        let offset = |position: f64| position.rem_euclid(spacing);
+        match self {
+            FillPattern::Solid => true,
+            FillPattern::Hatch => offset((x + y) / 2f64.sqrt()) < size,
+            FillPattern::CrossHatch => {
+                (offset((x + y) / 2f64.sqrt()) < size) || (offset((x - y) / 2f64.sqrt()) < size)
+            }
+            FillPattern::Dots => {
+                let (dx, dy) = (offset(x) - (spacing / 2f64), offset(y) - (spacing / 2f64));
+                ((dx * dx) + (dy * dy)) < (size * size)
+            }
+        }
+    }
+}
+
+/// Draws every cell as `pattern` in its own color over `background`, with
+/// denser ink for darker cells. Pixels outside every cell stay transparent.
+pub fn paint_patterns(
+    cell_map: &CellMap,
+    colors: &[Rgba<u8>],
+    pattern: FillPattern,
+    spacing: f64,
+    background: Rgba<u8>,
+) -> RgbaImage {
+    let coverage: Vec<f64> = colors.iter().map(|color| ink_coverage(*color)).collect();
+    let mut output_image_buffer = RgbaImage::new(cell_map.width, cell_map.height);
+
+    for (x, y, pixel) in output_image_buffer.enumerate_pixels_mut() {
+        let label = cell_map.label(x, y);
+        if label == UNASSIGNED {
+            continue;
+        }
+
+        let (center_x, center_y) = ((x as f64) + 0.5f64, (y as f64) + 0.5f64);
+        *pixel = if pattern.is_inked(center_x, center_y, coverage[label as usize], spacing) {
+            colors[label as usize]
+        } else {
+            background
+        };
+    }
+
+    output_image_buffer
+}
@@ -0,0 +1,436 @@
+//! PDF output of cell polygons as vector paths, for print shops that want
+//! resolution independent files.
+
+use crate::geometry::{Bounds, Point};
+use crate::pattern::{ink_coverage, FillPattern};
+use crate::render::CellStyle;
+use crate::stipple::stipple_radius;
+use crate::voronoi::{polygon_area, polygon_centroid};
+use image::Rgba;
+use std::fmt::Write;
+
+/// PDF user space units, points, per inch.
+pub const POINTS_PER_INCH: f64 = 72f64;
+/// Millimetres per inch.
+pub const MILLIMETRES_PER_INCH: f64 = 25.4f64;
+
+/// Splits a `mm`, `cm` or `in` suffix off `value`, with millimetres per unit.
+fn split_unit(value: &str) -> Option<(&str, f64)> {
+    if let Some(number) = value.strip_suffix("mm") {
+        Some((number, 1f64))
+    } else if let Some(number) = value.strip_suffix("cm") {
+        Some((number, 10f64))
+    } else {
+        value
+            .strip_suffix("in")
+            .map(|number| (number, MILLIMETRES_PER_INCH))
+    }
+}
+
+/// Parses a non-negative physical length, such as `10mm`, `1cm` or
+/// `0.5in`, in millimetres.
+pub fn parse_physical_length(value: &str) -> Option<f64> {
+    let value = value.trim().to_ascii_lowercase();
+    let (number, millimetres_per_unit) = split_unit(&value)?;
+    let length = number.trim().parse::<f64>().ok()?;
+
+    (length >= 0f64).then_some(length * millimetres_per_unit)
+}
+
+/// Parses a physical size as `A3`, `A4`, `A5`, `letter`, `legal` or
+/// `WIDTHxHEIGHT` followed by `mm`, `cm` or `in`, in millimetres.
+pub fn parse_physical_size(value: &str) -> Option<(f64, f64)> {
+    let value = value.trim().to_ascii_lowercase();
+    let named = match value.as_str() {
+        "a3" => Some((297f64, 420f64)),
+        "a4" => Some((210f64, 297f64)),
+        "a5" => Some((148f64, 210f64)),
+        "letter" => Some((215.9f64, 279.4f64)),
+        "legal" => Some((215.9f64, 355.6f64)),
+        _ => None,
+    };
+    if named.is_some() {
+        return named;
+    }
+
+    let (dimensions, millimetres_per_unit) = split_unit(&value)?;
+    let (width, height) = dimensions.split_once('x')?;
+    let (width, height) = (
+        width.trim().parse::<f64>().ok()?,
+        height.trim().parse::<f64>().ok()?,
+    );
+
+    ((width > 0f64) && (height > 0f64))
+        .then_some((width * millimetres_per_unit, height * millimetres_per_unit))
+}
+
+/// A PDF built page by page from content streams.
+#[derive(Default)]
+pub struct PdfDocument {
+    pages: Vec<(f64, f64, String)>,
+}
+
+impl PdfDocument {
+    pub fn new() -> PdfDocument {
+        PdfDocument::default()
+    }
+
+    /// Adds a page of `width` by `height` points drawn by the `content`
+    /// stream operators.
+    pub fn add_page(&mut self, width: f64, height: f64, content: String) {
+        self.pages.push((width, height, content));
+    }
+
+    /// The document as the bytes of a PDF file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut objects = vec![
+            String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..self.pages.len())
+                    .map(|index| format!("{} 0 R", 3 + (index * 2)))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                self.pages.len()
+            ),
+        ];
+        for (index, (width, height, content)) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << >> /Contents {} 0 R >>",
+                width,
+                height,
+                4 + (index * 2)
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                content.len() + 1,
+                content
+            ));
+        }
+
+        let mut bytes = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(bytes.len());
+            bytes.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).into_bytes());
+        }
+
+        let xref_offset = bytes.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        );
+        bytes.extend(trailer.into_bytes());
+
+        bytes
+    }
+}
+
+fn color_operands(color: Rgba<u8>) -> String {
+    let [red, green, blue, _] = color.0;
+    format!(
+        "{:.3} {:.3} {:.3}",
+        (red as f64) / 255f64,
+        (green as f64) / 255f64,
+        (blue as f64) / 255f64
+    )
+}
+
+/// Appends the rings of a cell as one closed path.
+fn append_path(content: &mut String, rings: &[Vec<Point>]) {
+    for ring in rings.iter().filter(|ring| ring.len() >= 3) {
+        for (index, point) in ring.iter().enumerate() {
+            let operator = if index == 0 { "m" } else { "l" };
+            let _ = writeln!(content, "{:.2} {:.2} {}", point.x, point.y, operator);
+        }
+        content.push_str("h\n");
+    }
+}
+
+/// Appends the lines or dots of `pattern` covering `(from, to)`, to be
+/// clipped to the cell.
+fn append_pattern(
+    content: &mut String,
+    pattern: FillPattern,
+    spacing: f64,
+    size: f64,
+    (from, to): (Point, Point),
+) {
+    // Hatch lines are `x + y = constant` diagonals, `spacing` apart measured
+    // across them, as in the raster patterns.
+    let step = spacing * 2f64.sqrt();
+    let diagonals = |content: &mut String, flipped: bool| {
+        let (low, high) = if flipped {
+            (
+                ((from.x - to.y) / step).floor() as i64 - 1,
+                ((to.x - from.y) / step).ceil() as i64 + 1,
+            )
+        } else {
+            (
+                ((from.x + from.y) / step).floor() as i64 - 1,
+                ((to.x + to.y) / step).ceil() as i64 + 1,
+            )
+        };
+        for line in low..=high {
+            let constant = ((line as f64) * step) + (size * 2f64.sqrt() / 2f64);
+            let (start_y, end_y) = (from.y, to.y);
+            let x_at = |y: f64| if flipped { constant + y } else { constant - y };
+            let _ = writeln!(
+                content,
+                "{:.2} {:.2} m {:.2} {:.2} l",
+                x_at(start_y),
+                start_y,
+                x_at(end_y),
+                end_y
+            );
+        }
+    };
+
+    match pattern {
+        FillPattern::Solid => {}
+        FillPattern::Hatch => diagonals(content, false),
+        FillPattern::CrossHatch => {
+            diagonals(content, false);
+            diagonals(content, true);
+        }
+        FillPattern::Dots => {
+            let mut y = (from.y / spacing).floor() * spacing;
+            while y <= to.y + spacing {
+                let mut x = (from.x / spacing).floor() * spacing;
+                while x <= to.x + spacing {
+                    let center = Point {
+                        x: x + (spacing / 2f64),
+                        y: y + (spacing / 2f64),
+                    };
+                    append_disc(content, &center, size);
+                    x += spacing;
+                }
+                y += spacing;
+            }
+        }
+    }
+}
+
+/// Appends a closed circle of `radius` around `center`, as four Bézier
+/// quarter circles.
+fn append_disc(content: &mut String, center: &Point, radius: f64) {
+    let (center_x, center_y) = (center.x, center.y);
+    let kappa = 0.5523f64 * radius;
+    let _ = writeln!(
+        content,
+        "{:.2} {:.2} m {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c h",
+        center_x + radius, center_y,
+        center_x + radius, center_y + kappa, center_x + kappa, center_y + radius, center_x, center_y + radius,
+        center_x - kappa, center_y + radius, center_x - radius, center_y + kappa, center_x - radius, center_y,
+        center_x - radius, center_y - kappa, center_x - kappa, center_y - radius, center_x, center_y - radius,
+        center_x + kappa, center_y - radius, center_x + radius, center_y - kappa, center_x + radius, center_y,
+    );
+}
+
+fn ring_bounds(rings: &[Vec<Point>]) -> (Point, Point) {
+    let mut from = Point {
+        x: f64::MAX,
+        y: f64::MAX,
+    };
+    let mut to = Point {
+        x: f64::MIN,
+        y: f64::MIN,
+    };
+    for point in rings.iter().flatten() {
+        from = Point {
+            x: from.x.min(point.x),
+            y: from.y.min(point.y),
+        };
+        to = Point {
+            x: to.x.max(point.x),
+            y: to.y.max(point.y),
+        };
+    }
+
+    (from, to)
+}
+
+/// Content stream drawing `cells`, given in pixel coordinates of a `bounds`
+/// sized image, as vector paths in `style`, scaled to fit a `width` by
+/// `height` point page and centered on it.
+///
+/// Every cell is its rings, as for [`crate::export::cells_to_geojson`]; holes
+/// are left out with the even-odd rule. Cells with transparent colors are
+/// skipped.
+pub fn cells_to_pdf_content(
+    cells: &[Vec<Vec<Point>>],
+    colors: &[Rgba<u8>],
+    bounds: &Bounds,
+    style: &CellStyle,
+    (width, height): (f64, f64),
+) -> String {
+    let (image_width, image_height) = (bounds.width as f64, bounds.height as f64);
+    let scale = (width / image_width).min(height / image_height);
+    let (offset_x, offset_y) = (
+        (width - (image_width * scale)) / 2f64,
+        (height - (image_height * scale)) / 2f64,
+    );
+
+    let mut content = String::new();
+    // Pixel coordinates, with `y` pointing down, from here on.
+    let _ = writeln!(
+        content,
+        "{:.4} 0 0 {:.4} {:.2} {:.2} cm",
+        scale,
+        -scale,
+        offset_x,
+        offset_y + (image_height * scale)
+    );
+    append_cells(&mut content, cells, colors, style);
+
+    content
+}
+
+/// Content stream of one page of a poster split over several pages: the
+/// part of `cells` from `origin` on, both in pixel coordinates, drawn
+/// `points_per_pixel` points per pixel onto a `page_height` point tall
+/// page, followed by the crop `marks`, lines in points from the top left
+/// corner of the page.
+pub fn cells_to_pdf_tile(
+    cells: &[Vec<Vec<Point>>],
+    colors: &[Rgba<u8>],
+    style: &CellStyle,
+    points_per_pixel: f64,
+    origin: Point,
+    page_height: f64,
+    marks: &[(Point, Point)],
+) -> String {
+    let mut content = String::new();
+    let _ = writeln!(content, "1 0 0 -1 0 {:.2} cm", page_height);
+    content.push_str("q\n");
+    let _ = writeln!(
+        content,
+        "{:.4} 0 0 {:.4} {:.2} {:.2} cm",
+        points_per_pixel,
+        points_per_pixel,
+        -origin.x * points_per_pixel,
+        -origin.y * points_per_pixel
+    );
+    append_cells(&mut content, cells, colors, style);
+    content.push_str("Q\n");
+
+    // Marks in black over a white halo, so they show on any cell.
+    for (halo, color) in [(2.25f64, "1 1 1"), (0.75f64, "0 0 0")] {
+        let _ = writeln!(content, "{} RG {:.2} w 0 J", color, halo);
+        for (from, to) in marks {
+            let _ = writeln!(
+                content,
+                "{:.2} {:.2} m {:.2} {:.2} l S",
+                from.x, from.y, to.x, to.y
+            );
+        }
+    }
+
+    content
+}
+
+/// Appends `cells`, in the current coordinates, in `style`.
+fn append_cells(
+    content: &mut String,
+    cells: &[Vec<Vec<Point>>],
+    colors: &[Rgba<u8>],
+    style: &CellStyle,
+) {
+    content.push_str("1 j 1 J\n");
+
+    for (rings, color) in cells.iter().zip(colors) {
+        if (color.0[3] == 0) || rings.iter().all(|ring| ring.len() < 3) {
+            continue;
+        }
+
+        match style {
+            CellStyle::Filled | CellStyle::StainedGlass { .. } => {
+                let _ = writeln!(content, "{} rg", color_operands(*color));
+                append_path(content, rings);
+                content.push_str("f*\n");
+            }
+            CellStyle::Outline {
+                width: stroke_width,
+                color: stroke_color,
+                ..
+            } => {
+                let _ = writeln!(
+                    content,
+                    "{} RG {:.2} w",
+                    color_operands(*stroke_color),
+                    stroke_width
+                );
+                append_path(content, rings);
+                content.push_str("S\n");
+            }
+            CellStyle::Patterned {
+                pattern,
+                spacing,
+                background,
+            } => {
+                content.push_str("q\n");
+                append_path(content, rings);
+                content.push_str("W* n\n");
+                if background.0[3] != 0 {
+                    let (from, to) = ring_bounds(rings);
+                    let _ = writeln!(
+                        content,
+                        "{} rg {:.2} {:.2} {:.2} {:.2} re f",
+                        color_operands(*background),
+                        from.x,
+                        from.y,
+                        to.x - from.x,
+                        to.y - from.y
+                    );
+                }
+
+                let size = pattern.stroke_size(ink_coverage(*color), *spacing);
+                if size > 0f64 {
+                    let ink = color_operands(*color);
+                    let _ = writeln!(content, "{} rg {} RG {:.2} w", ink, ink, size);
+                    append_pattern(content, *pattern, *spacing, size, ring_bounds(rings));
+                    let operator = if *pattern == FillPattern::Dots {
+                        "f"
+                    } else {
+                        "S"
+                    };
+                    let _ = writeln!(content, "{}", operator);
+                }
+                content.push_str("Q\n");
+            }
+            CellStyle::Stipple { background } => {
+                if background.0[3] != 0 {
+                    let _ = writeln!(content, "{} rg", color_operands(*background));
+                    append_path(content, rings);
+                    content.push_str("f*\n");
+                }
+
+                // Sized and placed by the outer ring, which holes barely move.
+                if let Some(ring) = rings.first() {
+                    if let Some(center) = polygon_centroid(ring) {
+                        let _ = writeln!(content, "{} rg", color_operands(*color));
+                        append_disc(content, &center, stipple_radius(polygon_area(ring)));
+                        content.push_str("f\n");
+                    }
+                }
+            }
+        }
+    }
+
+    // The leading goes over all the glass, so no cell covers half of it.
+    if let CellStyle::StainedGlass { width, color } = style {
+        let _ = writeln!(content, "{} RG {:.2} w", color_operands(*color), width);
+        for (rings, cell_color) in cells.iter().zip(colors) {
+            if (cell_color.0[3] != 0) && rings.iter().any(|ring| ring.len() >= 3) {
+                append_path(content, rings);
+                content.push_str("S\n");
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Named presets: the painting flags of a look bundled under one name, so
+//! `--preset stained-glass` stands for the metric, borders, color mode and
+//! palette that make it. The flags of a preset go before the ones given on
+//! the command line, which override them.
+
+/// A named combination of painting flags.
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The flags, with their values, as they would be typed.
+    pub arguments: &'static [&'static str],
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "mosaic",
+        description: "Hexagonal tiles of flat mean color set in dark grout",
+        arguments: &[
+            "--sampling",
+            "hex",
+            "--color-mode",
+            "mean",
+            "--style",
+            "stained-glass",
+            "--stroke-width",
+            "2",
+            "--stroke-color",
+            "#3a3a3a",
+        ],
+    },
+    Preset {
+        name: "stained-glass",
+        description: "Saturated panes of few colors in thick black leading",
+        arguments: &[
+            "--color-mode",
+            "mean",
+            "--saturation",
+            "1.4",
+            "--posterize",
+            "6",
+            "--style",
+            "stained-glass",
+            "--stroke-width",
+            "4",
+            "--stroke-color",
+            "#1a1a1a",
+        ],
+    },
+    Preset {
+        name: "crystal",
+        description: "Angular facets of crisp color edged in white, like cut glass",
+        arguments: &[
+            "--metric",
+            "manhattan",
+            "--color-mode",
+            "mean",
+            "--contrast",
+            "1.15",
+            "--style",
+            "stained-glass",
+            "--stroke-width",
+            "1",
+            "--stroke-color",
+            "#ffffff",
+        ],
+    },
+    Preset {
+        name: "low-poly",
+        description: "Flat shaded cells, small on the edges of the input and large elsewhere",
+        arguments: &[
+            "--auto-detail",
+            "1",
+            "--cvt-iterations",
+            "2",
+            "--color-mode",
+            "mean",
+            "--posterize",
+            "16",
+        ],
+    },
+    Preset {
+        name: "blueprint",
+        description: "Cells in shades of blueprint blue with pale lines between them",
+        arguments: &[
+            "--sampling",
+            "jittered-grid",
+            "--color-mode",
+            "mean",
+            "--duotone",
+            "dark=#0d2f64,light=#3f78c0",
+            "--style",
+            "stained-glass",
+            "--stroke-width",
+            "1",
+            "--stroke-color",
+            "#dfe9f5",
+        ],
+    },
+];
+
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
@@ -0,0 +1,230 @@
+//! Color profiles of outputs: paintings converted from sRGB to a wider
+//! gamut like Display P3, and tagged with the ICC profile of the space they
+//! are in so color managed viewers show them as painted.
+
+use crate::color::ColorSpace;
+use image::{Rgba, RgbaImage};
+
+/// Linear sRGB to linear Display P3, both under D65.
+const SRGB_TO_DISPLAY_P3: [[f64; 3]; 3] = [
+    [0.8224621, 0.1775380, 0f64],
+    [0.0331941, 0.9668058, 0f64],
+    [0.0170826, 0.0723974, 0.9105199],
+];
+
+/// The XYZ of the red, green and blue primaries adapted to D50, the white of
+/// the ICC profile connection space.
+const SRGB_COLORANTS: [[f64; 3]; 3] = [
+    [0.4360747, 0.2225045, 0.0139322],
+    [0.3850649, 0.7168786, 0.0971045],
+    [0.1430804, 0.0606169, 0.7141733],
+];
+const DISPLAY_P3_COLORANTS: [[f64; 3]; 3] = [
+    [0.5151100, 0.2411870, -0.0010480],
+    [0.2919680, 0.6922380, 0.0418820],
+    [0.1571580, 0.0665830, 0.7841670],
+];
+const D50_WHITE: [f64; 3] = [0.9642, 1f64, 0.8249];
+/// Samples of the tone curve in the profiles, which both spaces share.
+const CURVE_SAMPLES: usize = 1024;
+
+/// A color space outputs are written in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputProfile {
+    Srgb,
+    /// The P3 primaries of digital cinema with the white and tone curve of
+    /// sRGB, as wide gamut phone and tablet screens show.
+    DisplayP3,
+}
+
+impl OutputProfile {
+    pub fn from_name(name: &str) -> Option<OutputProfile> {
+        match name {
+            "srgb" => Some(OutputProfile::Srgb),
+            "display-p3" => Some(OutputProfile::DisplayP3),
+            _ => None,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            OutputProfile::Srgb => "sRGB",
+            OutputProfile::DisplayP3 => "Display P3",
+        }
+    }
+
+    /// Converts the sRGB `image` to this space, the same colors in the
+    /// channels of its primaries.
+    pub fn convert(self, image: &mut RgbaImage) {
+        if self == OutputProfile::Srgb {
+            return;
+        }
+
+        for pixel in image.pixels_mut() {
+            let linear = ColorSpace::Linear.encode(*pixel);
+            let converted = SRGB_TO_DISPLAY_P3
+                .map(|row| (row[0] * linear[0]) + (row[1] * linear[1]) + (row[2] * linear[2]));
+            let [red, green, blue] = ColorSpace::Linear.decode(converted);
+            *pixel = Rgba([red, green, blue, pixel.0[3]]);
+        }
+    }
+
+    /// An ICC version 2 display profile of this space.
+    pub fn icc_profile(self) -> Vec<u8> {
+        let colorants = match self {
+            OutputProfile::Srgb => SRGB_COLORANTS,
+            OutputProfile::DisplayP3 => DISPLAY_P3_COLORANTS,
+        };
+        let curve = curve_tag();
+        let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+            (b"desc", description_tag(self.description())),
+            (b"cprt", text_tag("No copyright, use freely")),
+            (b"wtpt", xyz_tag(D50_WHITE)),
+            (b"rXYZ", xyz_tag(colorants[0])),
+            (b"gXYZ", xyz_tag(colorants[1])),
+            (b"bXYZ", xyz_tag(colorants[2])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ];
+
+        let mut offset = 128 + 4 + (tags.len() * 12);
+        let mut table = Vec::new();
+        let mut data = Vec::new();
+        table.extend((tags.len() as u32).to_be_bytes());
+        for (signature, tag) in &tags {
+            table.extend(*signature);
+            table.extend((offset as u32).to_be_bytes());
+            table.extend((tag.len() as u32).to_be_bytes());
+            data.extend(tag);
+            // Every tag starts on four bytes.
+            while data.len() % 4 != 0 {
+                data.push(0);
+            }
+            offset = 128 + 4 + (tags.len() * 12) + data.len();
+        }
+
+        let size = 128 + table.len() + data.len();
+        let mut profile = Vec::with_capacity(size);
+        profile.extend((size as u32).to_be_bytes());
+        profile.extend([0; 4]);
+        profile.extend([2, 0x10, 0, 0]);
+        profile.extend(b"mntrRGB XYZ ");
+        // Created 2024-01-01.
+        for field in [2024u16, 1, 1, 0, 0, 0] {
+            profile.extend(field.to_be_bytes());
+        }
+        profile.extend(b"acsp");
+        profile.extend([0; 24]);
+        // Perceptual intent.
+        profile.extend([0; 4]);
+        for channel in D50_WHITE {
+            profile.extend(s15_fixed16(channel));
+        }
+        profile.extend([0; 48]);
+        profile.extend(table);
+        profile.extend(data);
+
+        profile
+    }
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536f64).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for channel in xyz {
+        tag.extend(s15_fixed16(channel));
+    }
+
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend(text.as_bytes());
+    tag.push(0);
+
+    tag
+}
+
+/// A `textDescriptionType` with only its ASCII description.
+fn description_tag(description: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend(((description.len() + 1) as u32).to_be_bytes());
+    tag.extend(description.as_bytes());
+    tag.push(0);
+    // No Unicode or ScriptCode descriptions.
+    tag.extend([0; 8]);
+    tag.extend([0; 3]);
+    tag.extend([0; 67]);
+
+    tag
+}
+
+/// The sRGB tone curve, sampled.
+fn curve_tag() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend((CURVE_SAMPLES as u32).to_be_bytes());
+    for index in 0..CURVE_SAMPLES {
+        let encoded = (index as f64) / ((CURVE_SAMPLES - 1) as f64);
+        let linear = if encoded <= 0.04045f64 {
+            encoded / 12.92f64
+        } else {
+            ((encoded + 0.055f64) / 1.055f64).powf(2.4f64)
+        };
+        tag.extend(((linear * 65535f64).round() as u16).to_be_bytes());
+    }
+
+    tag
+}
+
+/// `bytes` as a zlib stream of stored blocks, for embedding a profile where
+/// a format wants it deflated.
+pub fn zlib_stored(bytes: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = bytes.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        stream.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        stream.push(blocks.peek().is_none() as u8);
+        stream.extend((block.len() as u16).to_le_bytes());
+        stream.extend((!(block.len() as u16)).to_le_bytes());
+        stream.extend(block);
+    }
+
+    let (mut low, mut high) = (1u32, 0u32);
+    for byte in bytes {
+        low = (low + (*byte as u32)) % 65521;
+        high = (high + low) % 65521;
+    }
+    stream.extend(((high << 16) | low).to_be_bytes());
+
+    stream
+}
+
+/// Inserts `profile` into the JPEG `jpeg` as `ICC_PROFILE` segments, after
+/// its JFIF header.
+pub fn embed_jpeg_profile(jpeg: &[u8], profile: &[u8]) -> Vec<u8> {
+    const CHUNK: usize = 65519;
+    let mut position = 2;
+    if jpeg.get(2..4) == Some(&[0xFF, 0xE0]) {
+        position += 2 + (u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize);
+    }
+
+    let mut embedded = jpeg[..position].to_vec();
+    let count = profile.len().div_ceil(CHUNK);
+    for (index, chunk) in profile.chunks(CHUNK).enumerate() {
+        embedded.extend([0xFF, 0xE2]);
+        embedded.extend(((2 + 12 + 2 + chunk.len()) as u16).to_be_bytes());
+        embedded.extend(b"ICC_PROFILE\0");
+        embedded.extend([(index + 1) as u8, count as u8]);
+        embedded.extend(chunk);
+    }
+    embedded.extend(&jpeg[position..]);
+
+    embedded
+}
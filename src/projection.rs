@@ -0,0 +1,467 @@
+//! Tessellating images that wrap around: 360° equirectangular panoramas on
+//! the sphere, so cells stay seamless across the left and right edges and
+//! keep their size towards the poles, and tiles on a torus, so opposite edges
+//! line up.
+
+use crate::anchors::Anchor;
+use crate::geometry::{Bounds, DistanceMetric, Euclidean, Point};
+use crate::render::{
+    assign_cells_observed, map_columns_on_target, CellMap, RenderObserver, UNASSIGNED,
+};
+use crate::sampling::AnchorSampler;
+use rand::{Rng, RngCore};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// How image coordinates map onto the surface being tessellated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Flat,
+    /// Longitude runs along the width and latitude along the height.
+    Equirectangular,
+    /// Both pairs of opposite edges are glued together, so the output tiles.
+    Torus,
+}
+
+impl Projection {
+    pub fn from_name(name: &str) -> Option<Projection> {
+        match name {
+            "flat" => Some(Projection::Flat),
+            "equirectangular" => Some(Projection::Equirectangular),
+            "torus" => Some(Projection::Torus),
+            _ => None,
+        }
+    }
+}
+
+/// How the edges of a flat image bound the pattern of anchors.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Anchors are placed right up to the edges, which cut through cells.
+    Clamp,
+    /// The pattern is mirrored at the edges, so cells touching them are
+    /// shaped like the halves of cells across a mirror.
+    Mirror,
+}
+
+impl EdgeMode {
+    pub fn from_name(name: &str) -> Option<EdgeMode> {
+        match name {
+            "clamp" => Some(EdgeMode::Clamp),
+            "mirror" => Some(EdgeMode::Mirror),
+            _ => None,
+        }
+    }
+}
+
+fn latitude(y: f64, height: u32) -> f64 {
+    (PI / 2f64) - (PI * ((y + 0.5f64) / (height as f64)))
+}
+
+fn longitude(x: f64, width: u32) -> f64 {
+    2f64 * PI * (x / (width as f64))
+}
+
+fn unit_vector(latitude: f64, longitude: f64) -> [f64; 3] {
+    [
+        latitude.cos() * longitude.cos(),
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+    ]
+}
+
+fn squared_chord(from: &[f64; 3], to: &[f64; 3]) -> f64 {
+    from.iter()
+        .zip(to)
+        .map(|(from, to)| (from - to) * (from - to))
+        .sum()
+}
+
+/// Distance on the sphere an equirectangular image of `width`×`height`
+/// pixels wraps, as the squared chord length, which orders points the same
+/// way as the great-circle distance.
+pub struct Equirectangular {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Equirectangular {
+    fn project(&self, point: &Point) -> [f64; 3] {
+        unit_vector(
+            latitude(point.y, self.height),
+            longitude(point.x, self.width),
+        )
+    }
+}
+
+impl DistanceMetric for Equirectangular {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        squared_chord(&self.project(from), &self.project(to))
+    }
+}
+
+/// Thins another sampler so that anchors are evenly spread over the sphere
+/// rather than over the image, which would crowd them towards the poles.
+pub struct EquirectangularSampler<'a> {
+    pub sampler: Box<dyn AnchorSampler + 'a>,
+}
+
+impl EquirectangularSampler<'_> {
+    fn thin(&self, bounds: &Bounds, points: Vec<Point>, rng: &mut dyn RngCore) -> Vec<Point> {
+        let height = bounds.height as u32;
+        points
+            .into_iter()
+            .filter(|point| rng.gen::<f64>() < latitude(point.y, height).cos())
+            .collect()
+    }
+}
+
+impl AnchorSampler for EquirectangularSampler<'_> {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        let points = self.sampler.sample(bounds, rng);
+        self.thin(bounds, points, rng)
+    }
+
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        let points = self.sampler.try_sample(bounds, rng)?;
+        Ok(self.thin(bounds, points, rng))
+    }
+}
+
+/// Anchors bucketed on a regular grid in 3D around the unit sphere.
+struct SphereGrid {
+    cell_size: f64,
+    buckets: HashMap<(i64, i64, i64), Vec<usize>>,
+    reach: i64,
+}
+
+impl SphereGrid {
+    fn new(positions: &[[f64; 3]]) -> SphereGrid {
+        let cell_size = ((4f64 * PI) / (positions.len().max(1) as f64))
+            .sqrt()
+            .clamp(2f64 / 256f64, 2f64);
+
+        let mut grid = SphereGrid {
+            cell_size,
+            buckets: HashMap::new(),
+            reach: (2f64 / cell_size).ceil() as i64 + 1,
+        };
+        for (index, position) in positions.iter().enumerate() {
+            grid.buckets
+                .entry(grid.bucket_of(position))
+                .or_default()
+                .push(index);
+        }
+
+        grid
+    }
+
+    fn bucket_of(&self, position: &[f64; 3]) -> (i64, i64, i64) {
+        let bucket = |coordinate: f64| (coordinate / self.cell_size).floor() as i64;
+        (
+            bucket(position[0]),
+            bucket(position[1]),
+            bucket(position[2]),
+        )
+    }
+
+    fn closest(&self, positions: &[[f64; 3]], position: &[f64; 3]) -> Option<usize> {
+        let (center_x, center_y, center_z) = self.bucket_of(position);
+        let mut closest: Option<(usize, f64)> = None;
+
+        for ring in 0..=self.reach {
+            for x in (center_x - ring)..=(center_x + ring) {
+                for y in (center_y - ring)..=(center_y + ring) {
+                    for z in (center_z - ring)..=(center_z + ring) {
+                        let on_ring = ((x - center_x).abs() == ring)
+                            || ((y - center_y).abs() == ring)
+                            || ((z - center_z).abs() == ring);
+                        if !on_ring {
+                            continue;
+                        }
+                        for &index in self.buckets.get(&(x, y, z)).into_iter().flatten() {
+                            let distance = squared_chord(position, &positions[index]);
+                            match closest {
+                                Some((closest_index, closest_distance))
+                                    if (closest_distance < distance)
+                                        || ((closest_distance == distance)
+                                            && (closest_index < index)) => {}
+                                _ => closest = Some((index, distance)),
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Anything in a further ring is at least `ring` cells away.
+            if let Some((_, distance)) = closest {
+                let bound = (ring as f64) * self.cell_size;
+                if distance <= bound * bound {
+                    break;
+                }
+            }
+        }
+
+        closest.map(|(index, _)| index)
+    }
+}
+
+/// Assigns every pixel of an equirectangular image to the anchor closest to
+/// it on the sphere.
+pub fn assign_cells_on_sphere(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap {
+    let metric = Equirectangular {
+        width: image_width,
+        height: image_height,
+    };
+    let positions: Vec<[f64; 3]> = anchors
+        .iter()
+        .map(|anchor| metric.project(&anchor.point))
+        .collect();
+    let grid = SphereGrid::new(&positions);
+
+    let columns = map_columns_on_target(image_width, |x| {
+        if observer.is_some_and(|observer| observer.is_cancelled()) {
+            return vec![UNASSIGNED; image_height as usize];
+        }
+
+        let labels: Vec<u32> = (0..image_height)
+            .map(|y| {
+                let position = metric.project(&Point {
+                    x: x as f64,
+                    y: y as f64,
+                });
+                grid.closest(&positions, &position)
+                    .map(|index| index as u32)
+                    .unwrap_or(UNASSIGNED)
+            })
+            .collect();
+        if let Some(observer) = observer {
+            observer.column_assigned(x, &labels);
+        }
+
+        labels
+    });
+
+    let mut cell_map = CellMap::new(image_width, image_height);
+    for (x, column_labels) in columns.into_iter().enumerate() {
+        cell_map.set_column(x as u32, column_labels);
+    }
+
+    cell_map
+}
+
+/// Another metric measured to the closest copy of `to` when the plane is
+/// tiled with `width`×`height` images.
+pub struct Toroidal<'a> {
+    pub metric: &'a dyn DistanceMetric,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl DistanceMetric for Toroidal<'_> {
+    fn distance(&self, from: &Point, to: &Point) -> f64 {
+        let wrap = |from: f64, to: f64, size: u32| {
+            let size = size as f64;
+            to + (size * ((from - to) / size).round())
+        };
+
+        self.metric.distance(
+            from,
+            &Point {
+                x: wrap(from.x, to.x, self.width),
+                y: wrap(from.y, to.y, self.height),
+            },
+        )
+    }
+}
+
+/// Copies of the anchors shifted by the image size in every direction, kept
+/// when they land within `margin` of the image, along with the index of the
+/// anchor each copy came from.
+pub fn torus_copies(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    margin: u32,
+) -> (Vec<Anchor>, Vec<usize>) {
+    let (width, height) = (image_width as f64, image_height as f64);
+    let margin = margin as f64;
+
+    let mut copies = anchors.to_vec();
+    let mut owners: Vec<usize> = (0..anchors.len()).collect();
+    for shift_x in [-1f64, 0f64, 1f64] {
+        for shift_y in [-1f64, 0f64, 1f64] {
+            if (shift_x == 0f64) && (shift_y == 0f64) {
+                continue;
+            }
+            for (index, anchor) in anchors.iter().enumerate() {
+                let point = Point {
+                    x: anchor.point.x + (shift_x * width),
+                    y: anchor.point.y + (shift_y * height),
+                };
+                let is_near = (point.x > -margin)
+                    && (point.x < width + margin)
+                    && (point.y > -margin)
+                    && (point.y < height + margin);
+                if is_near {
+                    copies.push(Anchor {
+                        point,
+                        color: anchor.color,
+                    });
+                    owners.push(index);
+                }
+            }
+        }
+    }
+
+    (copies, owners)
+}
+
+/// Assigns every pixel to its closest anchor as if the image were tiled, so
+/// cells continue across opposite edges.
+pub fn assign_cells_on_torus<M>(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    metric: &M,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap
+where
+    M: DistanceMetric + ?Sized,
+{
+    let (copies, owners) = torus_copies(anchors, image_width, image_height, 2 * minimum_distance);
+    let mut cell_map = assign_cells_observed(
+        &copies,
+        image_width,
+        image_height,
+        minimum_distance,
+        metric,
+        observer,
+    );
+    for y in 0..image_height {
+        for x in 0..image_width {
+            let label = cell_map.label(x, y);
+            if label != UNASSIGNED {
+                cell_map.set_label(x, y, owners[label as usize] as u32);
+            }
+        }
+    }
+
+    cell_map
+}
+
+/// Drops anchors of another sampler that are closer than `minimum_distance`
+/// to an earlier anchor across an edge, so spacing stays even when the
+/// output is tiled.
+pub struct TileableSampler<'a> {
+    pub sampler: Box<dyn AnchorSampler + 'a>,
+    pub minimum_distance: f64,
+}
+
+impl TileableSampler<'_> {
+    fn thin(&self, bounds: &Bounds, points: Vec<Point>) -> Vec<Point> {
+        let wrapped = Toroidal {
+            metric: &Euclidean,
+            width: bounds.width as u32,
+            height: bounds.height as u32,
+        };
+        let (width, height) = (bounds.width as f64, bounds.height as f64);
+        let is_near_edge = |point: &Point| {
+            (point.x < self.minimum_distance)
+                || (point.y < self.minimum_distance)
+                || (point.x > width - self.minimum_distance)
+                || (point.y > height - self.minimum_distance)
+        };
+
+        let mut kept: Vec<Point> = Vec::new();
+        let mut kept_near_edge: Vec<Point> = Vec::new();
+        for point in points {
+            if !is_near_edge(&point) {
+                kept.push(point);
+                continue;
+            }
+            let is_crowded = kept_near_edge.iter().any(|other| {
+                wrapped.distance(&point, other) < (self.minimum_distance * self.minimum_distance)
+            });
+            if !is_crowded {
+                kept_near_edge.push(point.clone());
+                kept.push(point);
+            }
+        }
+
+        kept
+    }
+}
+
+impl AnchorSampler for TileableSampler<'_> {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        self.thin(bounds, self.sampler.sample(bounds, rng))
+    }
+
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        Ok(self.thin(bounds, self.sampler.try_sample(bounds, rng)?))
+    }
+}
+
+/// Samples with another sampler inside the bounds shrunk by half of
+/// `minimum_distance` on every side, so that every anchor is as far from its
+/// reflections across the edges as from its neighbours, and spacing stays
+/// even in the pattern mirrored at the edges.
+///
+/// The edge is halfway between every anchor and its reflection, so the
+/// reflections never take pixels of the image over and cells reach the
+/// edges as they would in the mirrored pattern. Without them anchors hug
+/// the edges, flattening their cells into slivers along them.
+pub struct MirroredSampler<'a> {
+    pub sampler: Box<dyn AnchorSampler + 'a>,
+    pub minimum_distance: f64,
+}
+
+impl MirroredSampler<'_> {
+    /// The bounds to sample in and how far they are in from `bounds`; bounds
+    /// too small to shrink are sampled as they are.
+    fn inner_bounds(&self, bounds: &Bounds) -> (Bounds, f64) {
+        let margin = (self.minimum_distance / 2f64).floor();
+        let inner = Bounds {
+            width: bounds.width.saturating_sub(2 * (margin as u64)),
+            height: bounds.height.saturating_sub(2 * (margin as u64)),
+        };
+        if (inner.width == 0) || (inner.height == 0) {
+            let whole = Bounds {
+                width: bounds.width,
+                height: bounds.height,
+            };
+            return (whole, 0f64);
+        }
+
+        (inner, margin)
+    }
+}
+
+fn shifted(points: Vec<Point>, margin: f64) -> Vec<Point> {
+    points
+        .into_iter()
+        .map(|point| Point {
+            x: point.x + margin,
+            y: point.y + margin,
+        })
+        .collect()
+}
+
+impl AnchorSampler for MirroredSampler<'_> {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        let (inner, margin) = self.inner_bounds(bounds);
+        shifted(self.sampler.sample(&inner, rng), margin)
+    }
+
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        let (inner, margin) = self.inner_bounds(bounds);
+        Ok(shifted(self.sampler.try_sample(&inner, rng)?, margin))
+    }
+}
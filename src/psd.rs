@@ -0,0 +1,458 @@
+//! Photoshop documents as inputs: the flattened composite a PSD or PSB saves
+//! next to its layers, or one of its layers by name, as RGBA. Grayscale and
+//! RGB documents of 8 or 16 bits stored raw or run length encoded are read;
+//! ZIP compressed channels are not.
+
+use image::{Rgba, RgbaImage};
+
+const GRAYSCALE: u16 = 1;
+const RGB: u16 = 3;
+const RAW: u16 = 0;
+const RUN_LENGTH: u16 = 1;
+/// The widest and tallest a PSD and a PSB can be.
+const LARGEST_PSD: i32 = 30_000;
+const LARGEST_PSB: i32 = 300_000;
+/// Channel id of the transparency of a layer.
+const TRANSPARENCY: i16 = -1;
+
+/// Reads big endian fields one after another.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, position: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .position
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or(String::from("truncated Photoshop document"))?;
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        self.u32().map(|value| value as i32)
+    }
+
+    /// A length, of eight bytes in a PSB where a PSD has four.
+    fn length(&mut self, large: bool) -> Result<usize, String> {
+        if large {
+            let high = self.u32()? as u64;
+            let low = self.u32()? as u64;
+            Ok(((high << 32) | low) as usize)
+        } else {
+            self.u32().map(|length| length as usize)
+        }
+    }
+}
+
+struct Document {
+    width: u32,
+    height: u32,
+    channels: usize,
+    depth: u16,
+    mode: u16,
+    /// Whether this is a PSB, whose lengths are larger.
+    large: bool,
+}
+
+impl Document {
+    /// The channels of a gray or RGB pixel.
+    fn colors(&self) -> usize {
+        if self.mode == GRAYSCALE {
+            1
+        } else {
+            3
+        }
+    }
+}
+
+struct Layer {
+    name: String,
+    top: i32,
+    left: i32,
+    bottom: i32,
+    right: i32,
+    opacity: u8,
+    /// The id and length of the data of every channel.
+    channels: Vec<(i16, usize)>,
+}
+
+/// Decodes the flattened composite of the PSD or PSB `bytes`, or the layer
+/// named `layer` placed on a transparent canvas of the document's size.
+pub fn decode_psd(bytes: &[u8], layer: Option<&str>) -> Result<RgbaImage, String> {
+    let mut reader = Reader::new(bytes);
+    if reader.take(4)? != b"8BPS" {
+        return Err(String::from("not a Photoshop document"));
+    }
+    let large = match reader.u16()? {
+        1 => false,
+        2 => true,
+        version => return Err(format!("unknown Photoshop version {}", version)),
+    };
+    reader.take(6)?;
+    let channels = reader.u16()? as usize;
+    let height = reader.u32()?;
+    let width = reader.u32()?;
+    let document = Document {
+        width,
+        height,
+        channels,
+        depth: reader.u16()?,
+        mode: reader.u16()?,
+        large,
+    };
+    if document.depth != 8 && document.depth != 16 {
+        return Err(format!(
+            "only 8 and 16 bit documents are supported, not {} bit",
+            document.depth
+        ));
+    }
+    if document.mode != GRAYSCALE && document.mode != RGB {
+        return Err(String::from(
+            "only grayscale and RGB documents are supported",
+        ));
+    }
+    if document.channels < document.colors() {
+        return Err(String::from("document has too few channels"));
+    }
+
+    // Color mode data and image resources.
+    for _ in 0..2 {
+        let length = reader.u32()? as usize;
+        reader.take(length)?;
+    }
+    let layer_section = reader.length(large)?;
+    let layer_section = reader.take(layer_section)?;
+    let layers = read_layers(layer_section, large)?;
+
+    match layer {
+        None => decode_composite(
+            &document,
+            &bytes[reader.position..],
+            layers.has_transparency,
+        ),
+        Some(name) => {
+            let index = layers
+                .records
+                .iter()
+                .position(|layer| layer.name == name)
+                .ok_or_else(|| {
+                    let names: Vec<&str> = layers
+                        .records
+                        .iter()
+                        .map(|layer| layer.name.as_str())
+                        .collect();
+                    format!(
+                        "no layer named `{}`, the layers are: {}",
+                        name,
+                        names.join(", ")
+                    )
+                })?;
+            decode_layer(&document, &layers, index)
+        }
+    }
+}
+
+struct Layers<'a> {
+    records: Vec<Layer>,
+    /// The channel data of every layer, one after the other.
+    data: &'a [u8],
+    /// Whether the first extra channel of the composite is its transparency.
+    has_transparency: bool,
+}
+
+fn read_layers(section: &[u8], large: bool) -> Result<Layers<'_>, String> {
+    let mut layers = Layers {
+        records: Vec::new(),
+        data: &[],
+        has_transparency: false,
+    };
+    if section.is_empty() {
+        return Ok(layers);
+    }
+    let mut reader = Reader::new(section);
+    let info = reader.length(large)?;
+    let info = reader.take(info)?;
+    if info.is_empty() {
+        return Ok(layers);
+    }
+
+    let mut reader = Reader::new(info);
+    let count = reader.u16()? as i16;
+    layers.has_transparency = count < 0;
+    for _ in 0..count.unsigned_abs() {
+        let top = reader.i32()?;
+        let left = reader.i32()?;
+        let bottom = reader.i32()?;
+        let right = reader.i32()?;
+        let channel_count = reader.u16()?;
+        let channels = (0..channel_count)
+            .map(|_| Ok((reader.u16()? as i16, reader.length(large)?)))
+            .collect::<Result<Vec<(i16, usize)>, String>>()?;
+        // Signature and blend mode.
+        reader.take(8)?;
+        let opacity = reader.u8()?;
+        // Clipping, flags and filler.
+        reader.take(3)?;
+        let extra = reader.u32()? as usize;
+        let extra = reader.take(extra)?;
+        layers.records.push(Layer {
+            name: layer_name(extra)?,
+            top,
+            left,
+            bottom,
+            right,
+            opacity,
+            channels,
+        });
+    }
+
+    layers.data = &info[reader.position..];
+
+    Ok(layers)
+}
+
+/// The Unicode name of a layer if it has one, or else its Pascal string
+/// name.
+fn layer_name(extra: &[u8]) -> Result<String, String> {
+    let mut reader = Reader::new(extra);
+    // Layer mask and blending ranges.
+    for _ in 0..2 {
+        let length = reader.u32()? as usize;
+        reader.take(length)?;
+    }
+    let length = reader.u8()? as usize;
+    let name = String::from_utf8_lossy(reader.take(length)?).into_owned();
+    // Padded to four bytes with its length.
+    reader.take((4 - ((length + 1) % 4)) % 4).ok();
+
+    while let (Ok(_signature), Ok(key), Ok(length)) = (reader.take(4), reader.take(4), reader.u32())
+    {
+        let Ok(data) = reader.take(length as usize) else {
+            break;
+        };
+        if key == b"luni" && data.len() >= 4 {
+            let count = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            let units: Vec<u16> = data[4..]
+                .chunks_exact(2)
+                .take(count)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect();
+            return Ok(String::from_utf16_lossy(&units));
+        }
+    }
+
+    Ok(name)
+}
+
+/// Expands a PackBits row.
+fn unpack_bits(data: &[u8], length: usize) -> Vec<u8> {
+    let mut row = Vec::with_capacity(length);
+    let mut position = 0;
+    while position < data.len() && row.len() < length {
+        let header = data[position] as i8;
+        position += 1;
+        match header {
+            -128 => {}
+            0..=127 => {
+                let end = (position + (header as usize) + 1).min(data.len());
+                row.extend_from_slice(&data[position..end]);
+                position = end;
+            }
+            _ => {
+                if let Some(value) = data.get(position) {
+                    row.extend(std::iter::repeat_n(*value, 1 + (-header as usize)));
+                }
+                position += 1;
+            }
+        }
+    }
+    row.resize(length, 0);
+
+    row
+}
+
+/// Decodes `rows` rows of `width` samples compressed as `compression`, one
+/// 8 bit sample each.
+fn decode_rows(
+    document: &Document,
+    compression: u16,
+    data: &[u8],
+    width: usize,
+    rows: usize,
+) -> Result<Vec<u8>, String> {
+    let bytes_per_sample = (document.depth / 8) as usize;
+    let row_length = width * bytes_per_sample;
+    let length = row_length
+        .checked_mul(rows)
+        .ok_or(String::from("Photoshop document larger than its data"))?;
+    let bytes = match compression {
+        RAW => data
+            .get(..length)
+            .ok_or(String::from("truncated Photoshop document"))?
+            .to_vec(),
+        // PackBits spends at least two bytes on every 128.
+        RUN_LENGTH if length / 64 > data.len() => {
+            return Err(String::from("Photoshop document larger than its data"))
+        }
+        RUN_LENGTH => {
+            let mut reader = Reader::new(data);
+            let counts = (0..rows)
+                .map(|_| {
+                    if document.large {
+                        reader.u32().map(|count| count as usize)
+                    } else {
+                        reader.u16().map(|count| count as usize)
+                    }
+                })
+                .collect::<Result<Vec<usize>, String>>()?;
+            let mut bytes = Vec::with_capacity(length);
+            for count in counts {
+                bytes.extend(unpack_bits(reader.take(count)?, row_length));
+            }
+            bytes
+        }
+        _ => return Err(String::from("ZIP compressed channels are not supported")),
+    };
+
+    // The high byte of 16 bit samples.
+    Ok(bytes.into_iter().step_by(bytes_per_sample).collect())
+}
+
+fn decode_composite(
+    document: &Document,
+    data: &[u8],
+    has_transparency: bool,
+) -> Result<RgbaImage, String> {
+    let mut reader = Reader::new(data);
+    let compression = reader.u16()?;
+    let (width, height) = (document.width as usize, document.height as usize);
+    let colors = document.colors();
+    let has_alpha = has_transparency && document.channels > colors;
+    // Every channel one after the other, as rows of the same width.
+    let samples = decode_rows(
+        document,
+        compression,
+        &data[reader.position..],
+        width,
+        document.channels * height,
+    )?;
+
+    let plane = width * height;
+    Ok(RgbaImage::from_fn(
+        document.width,
+        document.height,
+        |x, y| {
+            let index = ((y as usize) * width) + (x as usize);
+            let sample = |channel: usize| samples[(channel * plane) + index];
+            let alpha = if has_alpha { sample(colors) } else { 255 };
+            if colors == 1 {
+                Rgba([sample(0), sample(0), sample(0), alpha])
+            } else {
+                Rgba([sample(0), sample(1), sample(2), alpha])
+            }
+        },
+    ))
+}
+
+fn decode_layer(document: &Document, layers: &Layers, index: usize) -> Result<RgbaImage, String> {
+    // The channel data of the layers before it come first.
+    let start: usize = layers.records[..index]
+        .iter()
+        .flat_map(|layer| layer.channels.iter().map(|(_, length)| *length))
+        .sum();
+    let layer = &layers.records[index];
+    // Layers may reach past the canvas, but no further than a document of
+    // the format could be.
+    let largest = if document.large {
+        LARGEST_PSB
+    } else {
+        LARGEST_PSD
+    };
+    let extent = |from: i32, to: i32| {
+        to.checked_sub(from)
+            .filter(|extent| (0..=largest).contains(extent))
+            .map(|extent| extent as usize)
+            .ok_or_else(|| format!("layer `{}` has bounds no document can hold", layer.name))
+    };
+    let width = extent(layer.left, layer.right)?;
+    let height = extent(layer.top, layer.bottom)?;
+
+    let mut reader = Reader::new(layers.data);
+    reader.take(start)?;
+    let mut planes: [Option<Vec<u8>>; 4] = Default::default();
+    for (id, length) in &layer.channels {
+        let channel = reader.take(*length)?;
+        let plane = match *id {
+            TRANSPARENCY => 3,
+            id if (0..document.colors() as i16).contains(&id) => id as usize,
+            // Masks and spot colors.
+            _ => continue,
+        };
+        if channel.len() < 2 {
+            continue;
+        }
+        let compression = u16::from_be_bytes([channel[0], channel[1]]);
+        planes[plane] = Some(decode_rows(
+            document,
+            compression,
+            &channel[2..],
+            width,
+            height,
+        )?);
+    }
+
+    // Only the part of the layer on the canvas is drawn.
+    let on_canvas = |start: i32, extent: usize, canvas: u32| {
+        let start = start as i64;
+        let from = (-start).clamp(0, extent as i64) as usize;
+        let to = ((canvas as i64) - start).clamp(0, extent as i64) as usize;
+        from..to.max(from)
+    };
+    let mut image = RgbaImage::new(document.width, document.height);
+    for y in on_canvas(layer.top, height, document.height) {
+        for x in on_canvas(layer.left, width, document.width) {
+            let (canvas_x, canvas_y) = (
+                ((layer.left as i64) + (x as i64)) as u32,
+                ((layer.top as i64) + (y as i64)) as u32,
+            );
+            let index = (y * width) + x;
+            let sample = |plane: usize, missing: u8| {
+                planes[plane]
+                    .as_ref()
+                    .map_or(missing, |samples| samples[index])
+            };
+            let (red, green, blue) = if document.colors() == 1 {
+                (sample(0, 0), sample(0, 0), sample(0, 0))
+            } else {
+                (sample(0, 0), sample(1, 0), sample(2, 0))
+            };
+            let alpha = ((sample(3, 255) as u32) * (layer.opacity as u32) / 255) as u8;
+            image.put_pixel(canvas_x, canvas_y, Rgba([red, green, blue, alpha]));
+        }
+    }
+
+    Ok(image)
+}
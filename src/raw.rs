@@ -0,0 +1,925 @@
+//! Camera RAW inputs: the sensor values of a DNG, CR2, NEF or ARW file,
+//! which are all TIFF underneath, demosaiced and white balanced into an
+//! image that tessellates like any other. Sensor data stored uncompressed or
+//! as the lossless JPEG of DNG and CR2 is decoded; the proprietary
+//! compressions of NEF and ARW, and camera color matrices, are not.
+
+use crate::color::ColorSpace;
+use image::{imageops, Rgba, RgbaImage};
+use std::collections::{HashMap, HashSet};
+
+const SUBFILE_TYPE: u16 = 254;
+const WIDTH: u16 = 256;
+const HEIGHT: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC: u16 = 262;
+const STRIP_OFFSETS: u16 = 273;
+const ORIENTATION: u16 = 274;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const TILE_BYTE_COUNTS: u16 = 325;
+const SUB_IFDS: u16 = 330;
+const CFA_REPEAT_PATTERN_DIM: u16 = 33421;
+const CFA_PATTERN: u16 = 33422;
+const LINEARIZATION_TABLE: u16 = 50712;
+const BLACK_LEVEL: u16 = 50714;
+const WHITE_LEVEL: u16 = 50717;
+const AS_SHOT_NEUTRAL: u16 = 50728;
+const CR2_SLICES: u16 = 50752;
+const ACTIVE_AREA: u16 = 50829;
+
+/// `PHOTOMETRIC` of sensor data behind a color filter array.
+const PHOTOMETRIC_CFA: f64 = 32803f64;
+const UNCOMPRESSED: u32 = 1;
+const OLD_JPEG: u32 = 6;
+const LOSSLESS_JPEG: u32 = 7;
+
+/// IFDs deeper than this, or more of them than this, are not followed, so a
+/// malformed file cannot send the reader around in circles.
+const MAXIMUM_DEPTH: usize = 4;
+const MAXIMUM_IFDS: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    kind: u16,
+    count: usize,
+    /// Where in the file the values are, inline in the entry or not.
+    offset: usize,
+}
+
+type Ifd = HashMap<u16, Entry>;
+
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn slice(&self, offset: usize, length: usize) -> Result<&[u8], String> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or(String::from("truncated file"))
+    }
+
+    fn u16_at(&self, offset: usize) -> Result<u16, String> {
+        let bytes = self.slice(offset, 2)?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32, String> {
+        let bytes = self.slice(offset, 4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// The entries of the IFD at `offset`, and the offset of the next one.
+    fn ifd(&self, offset: usize) -> Result<(Ifd, usize), String> {
+        let count = self.u16_at(offset)? as usize;
+        let mut ifd = Ifd::new();
+        for index in 0..count {
+            let start = offset + 2 + (index * 12);
+            let kind = self.u16_at(start + 2)?;
+            let count = self.u32_at(start + 4)? as usize;
+            let size = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 | 13 => 4,
+                5 | 10 | 12 => 8,
+                _ => continue,
+            };
+            let offset = if count * size <= 4 {
+                start + 8
+            } else {
+                self.u32_at(start + 8)? as usize
+            };
+            ifd.insert(
+                self.u16_at(start)?,
+                Entry {
+                    kind,
+                    count,
+                    offset,
+                },
+            );
+        }
+
+        Ok((ifd, self.u32_at(offset + 2 + (count * 12))? as usize))
+    }
+
+    fn values(&self, entry: Entry) -> Result<Vec<f64>, String> {
+        (0..entry.count)
+            .map(|index| {
+                Ok(match entry.kind {
+                    1 | 7 => self.slice(entry.offset + index, 1)?[0] as f64,
+                    6 => self.slice(entry.offset + index, 1)?[0] as i8 as f64,
+                    3 => self.u16_at(entry.offset + (index * 2))? as f64,
+                    8 => self.u16_at(entry.offset + (index * 2))? as i16 as f64,
+                    4 | 13 => self.u32_at(entry.offset + (index * 4))? as f64,
+                    9 => self.u32_at(entry.offset + (index * 4))? as i32 as f64,
+                    11 => f32::from_bits(self.u32_at(entry.offset + (index * 4))?) as f64,
+                    5 | 10 => {
+                        let numerator = self.u32_at(entry.offset + (index * 8))?;
+                        let denominator = self.u32_at(entry.offset + (index * 8) + 4)?;
+                        let (numerator, denominator) = if entry.kind == 5 {
+                            (numerator as f64, denominator as f64)
+                        } else {
+                            (numerator as i32 as f64, denominator as i32 as f64)
+                        };
+                        numerator / denominator
+                    }
+                    12 => {
+                        let high = self.u32_at(entry.offset + (index * 8))? as u64;
+                        let low = self.u32_at(entry.offset + (index * 8) + 4)? as u64;
+                        f64::from_bits(if self.little_endian {
+                            (low << 32) | high
+                        } else {
+                            (high << 32) | low
+                        })
+                    }
+                    _ => return Err(String::from("text where numbers were expected")),
+                })
+            })
+            .collect()
+    }
+
+    fn numbers(&self, ifd: &Ifd, tag: u16) -> Result<Option<Vec<f64>>, String> {
+        ifd.get(&tag).map(|entry| self.values(*entry)).transpose()
+    }
+
+    fn number(&self, ifd: &Ifd, tag: u16) -> Result<Option<f64>, String> {
+        Ok(self
+            .numbers(ifd, tag)?
+            .and_then(|values| values.first().copied()))
+    }
+
+    fn required(&self, ifd: &Ifd, tag: u16, name: &str) -> Result<Vec<f64>, String> {
+        self.numbers(ifd, tag)?
+            .filter(|values| !values.is_empty())
+            .ok_or(format!("missing {}", name))
+    }
+
+    /// The IFDs chained from the header, and every IFD reachable from them
+    /// through sub IFDs.
+    fn ifds(&self, first: usize) -> Result<(Vec<Ifd>, Vec<Ifd>), String> {
+        let mut chain = Vec::new();
+        let mut all = Vec::new();
+        let mut visited = HashSet::new();
+        let mut offset = first;
+        while offset != 0 && chain.len() < MAXIMUM_IFDS && visited.insert(offset) {
+            let (ifd, next) = self.ifd(offset)?;
+            self.sub_ifds(&ifd, 1, &mut visited, &mut all)?;
+            all.push(ifd.clone());
+            chain.push(ifd);
+            offset = next;
+        }
+
+        Ok((chain, all))
+    }
+
+    fn sub_ifds(
+        &self,
+        ifd: &Ifd,
+        depth: usize,
+        visited: &mut HashSet<usize>,
+        all: &mut Vec<Ifd>,
+    ) -> Result<(), String> {
+        if depth > MAXIMUM_DEPTH {
+            return Ok(());
+        }
+        for offset in self.numbers(ifd, SUB_IFDS)?.unwrap_or_default() {
+            let offset = offset as usize;
+            if all.len() >= MAXIMUM_IFDS || !visited.insert(offset) {
+                continue;
+            }
+            let (sub_ifd, _) = self.ifd(offset)?;
+            self.sub_ifds(&sub_ifd, depth + 1, visited, all)?;
+            all.push(sub_ifd);
+        }
+
+        Ok(())
+    }
+}
+
+/// Sensor values of a color filter array, one per photosite.
+struct Mosaic {
+    width: usize,
+    height: usize,
+    values: Vec<u16>,
+    /// Which of red, green and blue (`0`, `1`, `2`) each site of the 2×2
+    /// repeat of the filter array sees, row by row.
+    pattern: [usize; 4],
+    black: f64,
+    white: f64,
+    /// Multipliers of red, green and blue that make neutral colors gray, if
+    /// the file says.
+    neutral: Option<[f64; 3]>,
+}
+
+impl Mosaic {
+    fn color(&self, x: usize, y: usize) -> usize {
+        self.pattern[((y % 2) * 2) + (x % 2)]
+    }
+}
+
+/// Decodes the camera RAW file `bytes` into an sRGB image, facing up.
+pub fn decode_raw(bytes: &[u8]) -> Result<RgbaImage, String> {
+    let little_endian = match bytes.get(0..4) {
+        Some([0x49, 0x49, 0x2A, 0x00]) => true,
+        Some([0x4D, 0x4D, 0x00, 0x2A]) => false,
+        _ => return Err(String::from("not a TIFF based camera RAW file")),
+    };
+    let tiff = Tiff {
+        bytes,
+        little_endian,
+    };
+    let (chain, all) = tiff.ifds(tiff.u32_at(4)? as usize)?;
+    let first = chain.first().ok_or(String::from("no images in file"))?;
+
+    let mosaic = if bytes.get(8..10) == Some(b"CR") {
+        // The sensor data of a CR2 is the fourth image, after the previews.
+        canon_mosaic(
+            &tiff,
+            chain
+                .get(3)
+                .ok_or(String::from("no sensor data in CR2 file"))?,
+        )?
+    } else {
+        let sensor = sensor_ifd(&tiff, &all)?;
+        cfa_mosaic(&tiff, sensor, first)?
+    };
+
+    let image = demosaic(&mosaic);
+    Ok(
+        match tiff.number(first, ORIENTATION)?.map(|value| value as u32) {
+            Some(3) => imageops::rotate180(&image),
+            Some(6) => imageops::rotate90(&image),
+            Some(8) => imageops::rotate270(&image),
+            _ => image,
+        },
+    )
+}
+
+/// The full resolution image behind a color filter array, the largest if a
+/// file has several.
+fn sensor_ifd<'a>(tiff: &Tiff, ifds: &'a [Ifd]) -> Result<&'a Ifd, String> {
+    let mut best: Option<(&Ifd, f64)> = None;
+    for ifd in ifds {
+        if tiff.number(ifd, PHOTOMETRIC)? != Some(PHOTOMETRIC_CFA)
+            || tiff.number(ifd, SUBFILE_TYPE)?.unwrap_or(0f64) != 0f64
+        {
+            continue;
+        }
+        let area =
+            tiff.number(ifd, WIDTH)?.unwrap_or(0f64) * tiff.number(ifd, HEIGHT)?.unwrap_or(0f64);
+        if best.is_none_or(|(_, best_area)| area > best_area) {
+            best = Some((ifd, area));
+        }
+    }
+
+    best.map(|(ifd, _)| ifd)
+        .ok_or(String::from("no color filter array image in file"))
+}
+
+/// The sensor data of `ifd`, the neutral color of a DNG being in its
+/// `first` IFD.
+fn cfa_mosaic(tiff: &Tiff, ifd: &Ifd, first: &Ifd) -> Result<Mosaic, String> {
+    let width = tiff.required(ifd, WIDTH, "image width")?[0] as usize;
+    let height = tiff.required(ifd, HEIGHT, "image height")?[0] as usize;
+    let bits = tiff.number(ifd, BITS_PER_SAMPLE)?.unwrap_or(16f64) as u32;
+    let compression = tiff.number(ifd, COMPRESSION)?.unwrap_or(1f64) as u32;
+    if !(1..=16).contains(&bits) {
+        return Err(format!("unsupported {} bit samples", bits));
+    }
+
+    // Strips are tiles as wide as the image.
+    let (chunk_width, chunk_height, offsets, byte_counts) = match tiff.numbers(ifd, TILE_OFFSETS)? {
+        Some(offsets) => (
+            tiff.required(ifd, TILE_WIDTH, "tile width")?[0] as usize,
+            tiff.required(ifd, TILE_LENGTH, "tile length")?[0] as usize,
+            offsets,
+            tiff.required(ifd, TILE_BYTE_COUNTS, "tile byte counts")?,
+        ),
+        None => (
+            width,
+            tiff.number(ifd, ROWS_PER_STRIP)?
+                .map_or(height, |rows| (rows as usize).min(height)),
+            tiff.required(ifd, STRIP_OFFSETS, "strip offsets")?,
+            tiff.required(ifd, STRIP_BYTE_COUNTS, "strip byte counts")?,
+        ),
+    };
+    if chunk_width == 0 || chunk_height == 0 || byte_counts.len() < offsets.len() {
+        return Err(String::from("malformed strips or tiles"));
+    }
+    // Every photosite takes at least a bit of the file.
+    let capacity = tiff.bytes.len().saturating_mul(8);
+    if [(width, height), (chunk_width, chunk_height)]
+        .iter()
+        .any(|(width, height)| {
+            width
+                .checked_mul(*height)
+                .is_none_or(|sites| sites > capacity)
+        })
+    {
+        return Err(String::from("image larger than the file holds"));
+    }
+
+    let across = width.div_ceil(chunk_width);
+    let mut values = vec![0u16; width * height];
+    for (index, (offset, byte_count)) in offsets.iter().zip(&byte_counts).enumerate() {
+        let (left, top) = (
+            (index % across) * chunk_width,
+            (index / across) * chunk_height,
+        );
+        if top >= height {
+            break;
+        }
+        let data = tiff.slice(*offset as usize, *byte_count as usize)?;
+        let chunk = match compression {
+            UNCOMPRESSED => unpack(data, bits, chunk_width, chunk_height, tiff.little_endian),
+            LOSSLESS_JPEG => {
+                let (jpeg_width, jpeg_height, samples) = decode_lossless_jpeg(data)?;
+                if jpeg_width != chunk_width || jpeg_height < chunk_height.min(height - top) {
+                    return Err(String::from(
+                        "lossless JPEG of a different size than its tile",
+                    ));
+                }
+                samples
+            }
+            _ => {
+                return Err(format!(
+                    "sensor data compressed with scheme {}, which is not supported",
+                    compression
+                ))
+            }
+        };
+        for y in 0..chunk_height.min(height - top) {
+            let columns = chunk_width.min(width - left);
+            let from = y * chunk_width;
+            let to = ((top + y) * width) + left;
+            if let Some(row) = chunk.get(from..(from + columns)) {
+                values[to..(to + columns)].copy_from_slice(row);
+            }
+        }
+    }
+
+    if let Some(table) = tiff.numbers(ifd, LINEARIZATION_TABLE)? {
+        if !table.is_empty() {
+            for value in &mut values {
+                *value = table[(*value as usize).min(table.len() - 1)] as u16;
+            }
+        }
+    }
+
+    let pattern = match (
+        tiff.numbers(ifd, CFA_REPEAT_PATTERN_DIM)?,
+        tiff.numbers(ifd, CFA_PATTERN)?,
+    ) {
+        (None, None) => [0, 1, 1, 2],
+        (dimensions, Some(pattern))
+            if dimensions
+                .as_ref()
+                .is_none_or(|dimensions| dimensions[..] == [2f64, 2f64])
+                && pattern.len() == 4
+                && pattern.iter().all(|color| *color <= 2f64) =>
+        {
+            [0, 1, 2, 3].map(|index| pattern[index] as usize)
+        }
+        _ => {
+            return Err(String::from(
+                "only 2×2 red, green and blue color filter arrays are supported",
+            ))
+        }
+    };
+
+    let black = tiff.numbers(ifd, BLACK_LEVEL)?;
+    let white = tiff.number(ifd, WHITE_LEVEL)?;
+    let neutral = match tiff.numbers(first, AS_SHOT_NEUTRAL)? {
+        Some(neutral) if neutral.len() == 3 && neutral.iter().all(|value| *value > 0f64) => {
+            Some([neutral[1] / neutral[0], 1f64, neutral[1] / neutral[2]])
+        }
+        _ => None,
+    };
+    let mosaic = Mosaic {
+        width,
+        height,
+        values,
+        pattern,
+        black: 0f64,
+        white: 0f64,
+        neutral,
+    };
+    let mosaic = match tiff.numbers(ifd, ACTIVE_AREA)? {
+        Some(area) if area.len() == 4 => crop(
+            mosaic,
+            area[1] as usize,
+            area[0] as usize,
+            area[3] as usize,
+            area[2] as usize,
+        )?,
+        _ => mosaic,
+    };
+
+    Ok(levels(mosaic, black, white))
+}
+
+/// The lossless JPEG of a CR2, split into vertical slices it is laid out in
+/// one after the other.
+fn canon_mosaic(tiff: &Tiff, ifd: &Ifd) -> Result<Mosaic, String> {
+    let compression = tiff.number(ifd, COMPRESSION)?.unwrap_or(0f64) as u32;
+    if compression != OLD_JPEG {
+        return Err(format!(
+            "CR2 sensor data compressed with scheme {}, which is not supported",
+            compression
+        ));
+    }
+    let offset = tiff.required(ifd, STRIP_OFFSETS, "strip offsets")?[0] as usize;
+    let byte_count = tiff.required(ifd, STRIP_BYTE_COUNTS, "strip byte counts")?[0] as usize;
+    let (jpeg_width, jpeg_height, samples) = decode_lossless_jpeg(tiff.slice(offset, byte_count)?)?;
+
+    let slices = tiff.numbers(ifd, CR2_SLICES)?.unwrap_or_default();
+    let (count, slice_width, last_width) = match slices[..] {
+        [count, slice_width, last_width] if slice_width > 0f64 || last_width > 0f64 => {
+            (count as usize, slice_width as usize, last_width as usize)
+        }
+        _ => (0, 0, jpeg_width),
+    };
+    let too_large = || String::from("CR2 slices larger than its sensor data");
+    let width = count
+        .checked_mul(slice_width)
+        .and_then(|width| width.checked_add(last_width))
+        .filter(|width| *width > 0)
+        .ok_or_else(too_large)?;
+    let height = (jpeg_width * jpeg_height) / width;
+    if width * height > samples.len() || height == 0 {
+        return Err(too_large());
+    }
+
+    let mut values = vec![0u16; width * height];
+    let mut next = 0;
+    let mut left = 0;
+    for slice in 0..=count {
+        let columns = if slice < count {
+            slice_width
+        } else {
+            last_width
+        };
+        for y in 0..height {
+            let to = (y * width) + left;
+            values[to..(to + columns)].copy_from_slice(&samples[next..(next + columns)]);
+            next += columns;
+        }
+        left += columns;
+    }
+
+    let mosaic = Mosaic {
+        width,
+        height,
+        values,
+        pattern: [0, 1, 1, 2],
+        black: 0f64,
+        white: 0f64,
+        neutral: None,
+    };
+    Ok(levels(mosaic, None, None))
+}
+
+/// Sets the black and white levels of `mosaic`, from the file's if it has
+/// them and otherwise from the darkest and brightest of its values.
+fn levels(mut mosaic: Mosaic, black: Option<Vec<f64>>, white: Option<f64>) -> Mosaic {
+    let darkest = mosaic.values.iter().min().copied().unwrap_or(0) as f64;
+    let brightest = mosaic.values.iter().max().copied().unwrap_or(0) as f64;
+    mosaic.black = match black {
+        Some(black) if !black.is_empty() => black.iter().sum::<f64>() / (black.len() as f64),
+        _ => darkest,
+    };
+    mosaic.white = white.unwrap_or(brightest).max(mosaic.black + 1f64);
+    mosaic
+}
+
+fn crop(
+    mosaic: Mosaic,
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+) -> Result<Mosaic, String> {
+    if left >= right || top >= bottom || right > mosaic.width || bottom > mosaic.height {
+        return Err(String::from("active area outside the image"));
+    }
+    let width = right - left;
+    let mut values = Vec::with_capacity(width * (bottom - top));
+    for y in top..bottom {
+        let from = (y * mosaic.width) + left;
+        values.extend_from_slice(&mosaic.values[from..(from + width)]);
+    }
+
+    Ok(Mosaic {
+        width,
+        height: bottom - top,
+        values,
+        ..mosaic
+    })
+}
+
+/// Samples of `bits` each, packed most significant bit first with every row
+/// starting on a new byte, or as whole 16 bit numbers in the file's byte
+/// order.
+fn unpack(data: &[u8], bits: u32, width: usize, height: usize, little_endian: bool) -> Vec<u16> {
+    let mut values = Vec::with_capacity(width * height);
+    if bits == 16 {
+        for pair in data.chunks_exact(2).take(width * height) {
+            let pair = [pair[0], pair[1]];
+            values.push(if little_endian {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            });
+        }
+    } else {
+        let row_bytes = (width * bits as usize).div_ceil(8);
+        for row in data.chunks(row_bytes).take(height) {
+            let mut reader = Bits::new(row);
+            for _ in 0..width {
+                values.push(reader.read(bits) as u16);
+            }
+        }
+    }
+    values.resize(width * height, 0);
+
+    values
+}
+
+/// Reads bits most significant first, past the byte stuffing of JPEG data
+/// if `stuffed`, and as zeros past the end or a marker.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+    stuffed: bool,
+}
+
+impl Bits<'_> {
+    fn new(bytes: &[u8]) -> Bits<'_> {
+        Bits {
+            bytes,
+            position: 0,
+            buffer: 0,
+            count: 0,
+            stuffed: false,
+        }
+    }
+
+    fn stuffed(bytes: &[u8]) -> Bits<'_> {
+        Bits {
+            stuffed: true,
+            ..Bits::new(bytes)
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let mut byte = 0;
+            if let Some(next) = self.bytes.get(self.position) {
+                byte = *next;
+                self.position += 1;
+                if self.stuffed && byte == 0xFF {
+                    if self.bytes.get(self.position) == Some(&0) {
+                        self.position += 1;
+                    } else {
+                        // A marker ends the data.
+                        byte = 0;
+                        self.position = self.bytes.len();
+                    }
+                }
+            }
+            self.buffer |= (byte as u64) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn read(&mut self, bits: u32) -> u32 {
+        if bits == 0 {
+            return 0;
+        }
+        if self.count < bits {
+            self.fill();
+        }
+        let value = (self.buffer >> (64 - bits)) as u32;
+        self.buffer <<= bits;
+        self.count -= bits;
+
+        value
+    }
+}
+
+/// A Huffman table of a JPEG, as the codes of every length.
+#[derive(Clone, Default)]
+struct Huffman {
+    /// The first and one past the last code of every length, and the index
+    /// of the first code's value.
+    lengths: [(u32, u32, usize); 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Huffman {
+        let mut lengths = [(0, 0, 0); 17];
+        let mut code = 0;
+        let mut index = 0;
+        for length in 1..=16 {
+            let count = counts[length - 1] as u32;
+            lengths[length] = (code, code + count, index);
+            code = (code + count) << 1;
+            index += count as usize;
+        }
+
+        Huffman {
+            lengths,
+            values: values.to_vec(),
+        }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u8, String> {
+        let mut code = 0;
+        for (first, end, index) in &self.lengths[1..] {
+            code = (code << 1) | bits.read(1);
+            if code < *end {
+                return self
+                    .values
+                    .get(index + (code - first) as usize)
+                    .copied()
+                    .ok_or(String::from("malformed Huffman table"));
+            }
+        }
+
+        Err(String::from("invalid Huffman code in lossless JPEG"))
+    }
+}
+
+/// Decodes a lossless JPEG into its width and height and the samples of
+/// its components, interleaved along the rows.
+fn decode_lossless_jpeg(data: &[u8]) -> Result<(usize, usize, Vec<u16>), String> {
+    if data.get(0..2) != Some(&[0xFF, 0xD8]) {
+        return Err(String::from("sensor data is not a JPEG"));
+    }
+    let malformed = || String::from("malformed lossless JPEG");
+    let mut tables: [Huffman; 4] = Default::default();
+    let mut frame: Option<(u32, usize, usize, Vec<u8>)> = None;
+    let mut position = 2;
+    loop {
+        while data.get(position) == Some(&0xFF) {
+            position += 1;
+        }
+        let marker = *data.get(position).ok_or_else(malformed)?;
+        let length = data
+            .get((position + 1)..(position + 3))
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+            .ok_or_else(malformed)?;
+        let segment = data
+            .get((position + 3)..(position + 1 + length))
+            .ok_or_else(malformed)?;
+        position += 1 + length;
+
+        match marker {
+            0xC4 => {
+                let mut rest = segment;
+                while rest.len() >= 17 {
+                    let total = rest[1..17]
+                        .iter()
+                        .map(|count| *count as usize)
+                        .sum::<usize>();
+                    let values = rest.get(17..(17 + total)).ok_or_else(malformed)?;
+                    tables[(rest[0] & 3) as usize] = Huffman::new(&rest[1..17], values);
+                    rest = &rest[(17 + total)..];
+                }
+            }
+            0xC3 => {
+                let [precision, high, low, high_width, low_width, components, ..] = segment[..]
+                else {
+                    return Err(malformed());
+                };
+                let components = components as usize;
+                let ids = (0..components)
+                    .map(|component| {
+                        let specification =
+                            segment.get((6 + (component * 3))..(9 + (component * 3)));
+                        match specification {
+                            Some([id, 0x11, _]) => Ok(*id),
+                            Some(_) => {
+                                Err(String::from("subsampled lossless JPEG is not supported"))
+                            }
+                            None => Err(malformed()),
+                        }
+                    })
+                    .collect::<Result<Vec<u8>, String>>()?;
+                frame = Some((
+                    precision as u32,
+                    u16::from_be_bytes([high_width, low_width]) as usize,
+                    u16::from_be_bytes([high, low]) as usize,
+                    ids,
+                ));
+            }
+            0xC0..=0xCF if marker != 0xC8 && marker != 0xCC => {
+                return Err(String::from("sensor data is not a lossless JPEG"))
+            }
+            0xDD if segment.get(0..2) != Some(&[0, 0]) => {
+                return Err(String::from(
+                    "lossless JPEG restart intervals are not supported",
+                ))
+            }
+            0xDA => {
+                let (precision, width, height, ids) = frame.ok_or_else(malformed)?;
+                let count = *segment.first().ok_or_else(malformed)? as usize;
+                let mut selected = Vec::with_capacity(ids.len());
+                for id in &ids {
+                    let table = (0..count)
+                        .find_map(
+                            |index| match segment.get((1 + (index * 2))..(3 + (index * 2))) {
+                                Some([selector, table]) if selector == id => {
+                                    Some((table >> 4) as usize)
+                                }
+                                _ => None,
+                            },
+                        )
+                        .ok_or_else(malformed)?;
+                    selected.push(&tables[table & 3]);
+                }
+                let predictor = *segment.get(1 + (count * 2)).ok_or_else(malformed)?;
+                let point_transform =
+                    (*segment.get(3 + (count * 2)).ok_or_else(malformed)? & 0xF) as u32;
+                if precision <= point_transform || precision > 16 {
+                    return Err(malformed());
+                }
+
+                let samples = decode_scan(
+                    &mut Bits::stuffed(&data[position..]),
+                    &selected,
+                    width,
+                    height,
+                    predictor,
+                    1 << (precision - point_transform - 1),
+                )?;
+                return Ok((
+                    width * ids.len(),
+                    height,
+                    samples
+                        .into_iter()
+                        .map(|sample| sample << point_transform)
+                        .collect(),
+                ));
+            }
+            0xD9 => return Err(malformed()),
+            _ => {}
+        }
+    }
+}
+
+fn decode_scan(
+    bits: &mut Bits,
+    tables: &[&Huffman],
+    width: usize,
+    height: usize,
+    predictor: u8,
+    initial: i32,
+) -> Result<Vec<u16>, String> {
+    let components = tables.len();
+    let columns = width * components;
+    // Every sample takes at least a bit of the scan.
+    if columns * height > bits.bytes.len().saturating_mul(8) {
+        return Err(String::from("lossless JPEG larger than its data"));
+    }
+    let mut samples = vec![0u16; columns * height];
+    for y in 0..height {
+        for x in 0..width {
+            for (component, table) in tables.iter().enumerate() {
+                let index = (y * columns) + (x * components) + component;
+                let length = table.decode(bits)? as u32;
+                let difference = match length {
+                    0 => 0,
+                    16 => 32768,
+                    17.. => return Err(String::from("malformed lossless JPEG")),
+                    _ => {
+                        let value = bits.read(length) as i32;
+                        if value < (1 << (length - 1)) {
+                            value - (1 << length) + 1
+                        } else {
+                            value
+                        }
+                    }
+                };
+
+                let left = || samples[index - components] as i32;
+                let above = || samples[index - columns] as i32;
+                let prediction = match (x, y) {
+                    (0, 0) => initial,
+                    (_, 0) => left(),
+                    (0, _) => above(),
+                    _ => {
+                        let (left, above, corner) = (
+                            left(),
+                            above(),
+                            samples[index - columns - components] as i32,
+                        );
+                        match predictor {
+                            1 => left,
+                            2 => above,
+                            3 => corner,
+                            4 => left + above - corner,
+                            5 => left + ((above - corner) >> 1),
+                            6 => above + ((left - corner) >> 1),
+                            7 => (left + above) / 2,
+                            _ => {
+                                return Err(format!(
+                                    "unknown lossless JPEG predictor {}",
+                                    predictor
+                                ))
+                            }
+                        }
+                    }
+                };
+                samples[index] = (prediction + difference) as u16;
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+/// White balances `mosaic`, then fills in the two colors every photosite
+/// misses with the average of its neighbours that see them.
+fn demosaic(mosaic: &Mosaic) -> RgbaImage {
+    let (width, height) = (mosaic.width, mosaic.height);
+    let range = mosaic.white - mosaic.black;
+    let mut linear: Vec<f32> = mosaic
+        .values
+        .iter()
+        .map(|value| (((*value as f64) - mosaic.black) / range).max(0f64) as f32)
+        .collect();
+
+    // Without a neutral from the file, the average color is taken as gray.
+    let multipliers = mosaic.neutral.unwrap_or_else(|| {
+        let mut sums = [0f64; 3];
+        let mut counts = [0f64; 3];
+        for y in 0..height {
+            for x in 0..width {
+                let color = mosaic.color(x, y);
+                sums[color] += linear[(y * width) + x] as f64;
+                counts[color] += 1f64;
+            }
+        }
+        let means = [0, 1, 2].map(|color| sums[color] / counts[color].max(1f64));
+        [0, 1, 2].map(|color| {
+            if means[color] > 0f64 {
+                means[1] / means[color]
+            } else {
+                1f64
+            }
+        })
+    });
+    for y in 0..height {
+        for x in 0..width {
+            let value = &mut linear[(y * width) + x];
+            *value = (*value * multipliers[mosaic.color(x, y)] as f32).min(1f32);
+        }
+    }
+
+    RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let mut sums = [0f32; 3];
+        let mut counts = [0f32; 3];
+        for neighbour_y in y.saturating_sub(1)..(y + 2).min(height) {
+            for neighbour_x in x.saturating_sub(1)..(x + 2).min(width) {
+                let color = mosaic.color(neighbour_x, neighbour_y);
+                sums[color] += linear[(neighbour_y * width) + neighbour_x];
+                counts[color] += 1f32;
+            }
+        }
+        let own = mosaic.color(x, y);
+        let channels = [0, 1, 2].map(|color| {
+            if color == own {
+                linear[(y * width) + x] as f64
+            } else if counts[color] > 0f32 {
+                (sums[color] / counts[color]) as f64
+            } else {
+                0f64
+            }
+        });
+        let [red, green, blue] = ColorSpace::Linear.decode(channels);
+
+        Rgba([red, green, blue, 255])
+    })
+}
@@ -0,0 +1,215 @@
+//! Recipes: pipelines of steps read from a TOML file, so a workflow of
+//! several tessellations and composites can be shared and repeated.
+//!
+//! Only the part of TOML recipes need is read: `key = value` pairs of
+//! strings, numbers, booleans and single line arrays of them, with `#`
+//! comments, at the top and in `[[step]]` tables.
+
+/// A value of a recipe setting.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecipeValue {
+    Text(String),
+    Number(f64),
+    Boolean(bool),
+    List(Vec<RecipeValue>),
+}
+
+impl RecipeValue {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            RecipeValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            RecipeValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// The value as command line text: lists are joined with commas.
+    pub fn to_argument(&self) -> String {
+        match self {
+            RecipeValue::Text(text) => text.clone(),
+            RecipeValue::Number(number) => number.to_string(),
+            RecipeValue::Boolean(boolean) => boolean.to_string(),
+            RecipeValue::List(values) => values
+                .iter()
+                .map(RecipeValue::to_argument)
+                .collect::<Vec<String>>()
+                .join(","),
+        }
+    }
+}
+
+/// Settings in the order they were written.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecipeTable {
+    /// Line of the recipe the table starts at, for error messages.
+    pub line: usize,
+    pub settings: Vec<(String, RecipeValue)>,
+}
+
+impl RecipeTable {
+    pub fn get(&self, key: &str) -> Option<&RecipeValue> {
+        self.settings
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recipe {
+    /// Settings before the first step.
+    pub settings: RecipeTable,
+    pub steps: Vec<RecipeTable>,
+}
+
+/// Reads a basic `"..."` or literal `'...'` string at the start of `text`,
+/// returning it and the rest of `text`.
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let quote = text.chars().next().ok_or("expected a string")?;
+    let mut value = String::new();
+    let mut characters = text[1..].char_indices();
+    while let Some((index, character)) = characters.next() {
+        match character {
+            character if character == quote => return Ok((value, &text[(index + 2)..])),
+            '\\' if quote == '"' => {
+                let escaped = match characters.next().map(|(_, escaped)| escaped) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    Some(escaped) => return Err(format!("unknown escape `\\{}`", escaped)),
+                    None => break,
+                };
+                value.push(escaped);
+            }
+            character => value.push(character),
+        }
+    }
+
+    Err(String::from("unterminated string"))
+}
+
+/// Reads the value at the start of `text`, returning it and the rest.
+fn parse_value(text: &str) -> Result<(RecipeValue, &str), String> {
+    let text = text.trim_start();
+    match text.chars().next() {
+        None => Err(String::from("expected a value")),
+        Some('"') | Some('\'') => {
+            let (value, rest) = parse_string(text)?;
+            Ok((RecipeValue::Text(value), rest))
+        }
+        Some('[') => {
+            let mut values = Vec::new();
+            let mut rest = text[1..].trim_start();
+            loop {
+                if let Some(after) = rest.strip_prefix(']') {
+                    return Ok((RecipeValue::List(values), after));
+                }
+                let (value, after) = parse_value(rest)?;
+                values.push(value);
+                rest = after.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after.trim_start();
+                } else if !rest.starts_with(']') {
+                    return Err(String::from("expected `,` or `]` in array"));
+                }
+            }
+        }
+        Some(_) => {
+            let end = text.find([',', ']']).unwrap_or(text.len());
+            let (word, rest) = (text[..end].trim_end(), &text[end..]);
+            let value = match word {
+                "true" => RecipeValue::Boolean(true),
+                "false" => RecipeValue::Boolean(false),
+                word => match word.replace('_', "").parse::<f64>() {
+                    Ok(number) => RecipeValue::Number(number),
+                    Err(_) => return Err(format!("`{}` is not a string, number or boolean", word)),
+                },
+            };
+            Ok((value, rest))
+        }
+    }
+}
+
+/// Drops a `#` comment that is not inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        match (quote, character) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), character) if (character == open) && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(character),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+
+    line
+}
+
+impl Recipe {
+    pub fn parse(text: &str) -> Result<Recipe, String> {
+        let mut recipe = Recipe::default();
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[step]]" {
+                recipe.steps.push(RecipeTable {
+                    line: number,
+                    settings: Vec::new(),
+                });
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(format!(
+                    "line {}: only `[[step]]` tables are supported",
+                    number
+                ));
+            }
+
+            // A quoted key may hold a `=` of its own.
+            let (key, value) = if line.starts_with('"') || line.starts_with('\'') {
+                let (key, rest) =
+                    parse_string(line).map_err(|error| format!("line {}: {}", number, error))?;
+                (key, rest.trim_start().strip_prefix('='))
+            } else {
+                match line.split_once('=') {
+                    Some((key, value)) => (key.trim().to_string(), Some(value)),
+                    None => (String::new(), None),
+                }
+            };
+            let value = value.ok_or(format!("line {}: expected `key = value`", number))?;
+            let (value, rest) =
+                parse_value(value).map_err(|error| format!("line {}: {}", number, error))?;
+            if !rest.trim().is_empty() {
+                return Err(format!(
+                    "line {}: unexpected `{}` after the value",
+                    number,
+                    rest.trim()
+                ));
+            }
+
+            let table = recipe.steps.last_mut().unwrap_or(&mut recipe.settings);
+            if table.get(&key).is_some() {
+                return Err(format!("line {}: `{}` is set twice", number, key));
+            }
+            table.settings.push((key, value));
+        }
+
+        Ok(recipe)
+    }
+}
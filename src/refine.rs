@@ -0,0 +1,255 @@
+use crate::anchors::{color_anchor_points, Anchor};
+use crate::geometry::Point;
+use crate::metrics::psnr_from_mse;
+use crate::render::{assign_projected, color_cells, paint_cells, RenderOptions, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+use rand::{Rng, RngCore};
+
+/// Limits for adaptive subdivision.
+pub struct RefinementOptions {
+    pub passes: u32,
+    /// Cells whose pixels vary more than this, as the color variance with
+    /// channels from `0` to `1`, get another anchor.
+    pub variance_threshold: f64,
+    /// Stop adding anchors once there are this many.
+    pub maximum_anchors: Option<usize>,
+    /// Closest an added anchor may be to the anchor of the cell it splits.
+    pub minimum_distance: f64,
+}
+
+/// Mean color of `pixels` and the variance around it, both with channels
+/// from `0` to `1`.
+fn color_statistics(pixels: &[(u32, u32)], source_image: &RgbaImage) -> ([f64; 3], f64) {
+    let channels = |x: u32, y: u32| {
+        let [red, green, blue, _] = source_image.get_pixel(x, y).0;
+        [red, green, blue].map(|channel| (channel as f64) / 255f64)
+    };
+
+    let mut sums = [0f64; 3];
+    let mut squared_sums = 0f64;
+    for &(x, y) in pixels {
+        for (sum, channel) in sums.iter_mut().zip(channels(x, y)) {
+            *sum += channel;
+            squared_sums += channel * channel;
+        }
+    }
+
+    let count = pixels.len() as f64;
+    let mean = sums.map(|sum| sum / count);
+    let variance = (squared_sums / count) - mean.iter().map(|mean| mean * mean).sum::<f64>();
+    (mean, variance / 3f64)
+}
+
+/// Where to split a cell: the centroid of its pixels that are closer in
+/// color to the pixel furthest from the mean color than to the mean.
+fn split_point(pixels: &[(u32, u32)], source_image: &RgbaImage, mean: [f64; 3]) -> Option<Point> {
+    let channels = |&(x, y): &(u32, u32)| {
+        let [red, green, blue, _] = source_image.get_pixel(x, y).0;
+        [red, green, blue].map(|channel| (channel as f64) / 255f64)
+    };
+    let squared_distance = |from: &[f64; 3], to: &[f64; 3]| -> f64 {
+        from.iter()
+            .zip(to)
+            .map(|(from, to)| (from - to) * (from - to))
+            .sum()
+    };
+
+    let colors: Vec<[f64; 3]> = pixels.iter().map(channels).collect();
+    let extreme = *colors
+        .iter()
+        .max_by(|a, b| squared_distance(a, &mean).total_cmp(&squared_distance(b, &mean)))?;
+
+    let (mut x_sum, mut y_sum, mut count) = (0f64, 0f64, 0f64);
+    for (&(x, y), color) in pixels.iter().zip(&colors) {
+        if squared_distance(color, &extreme) < squared_distance(color, &mean) {
+            x_sum += x as f64;
+            y_sum += y as f64;
+            count += 1f64;
+        }
+    }
+
+    (count > 0f64).then(|| Point {
+        x: x_sum / count,
+        y: y_sum / count,
+    })
+}
+
+/// Adds anchors inside cells whose pixels vary so much in color that the
+/// flat fill of the cell loses detail, re-tessellating after every pass,
+/// until no cell varies more than the threshold or the anchor budget is
+/// spent.
+///
+/// Returns the refined points and the number of passes that added anchors.
+pub fn refine_high_variance_cells(
+    source_image: &RgbaImage,
+    anchor_points: Vec<Point>,
+    refinement: &RefinementOptions,
+    options: &RenderOptions,
+) -> (Vec<Point>, u32) {
+    let (image_width, image_height) = source_image.dimensions();
+    let maximum_anchors = refinement.maximum_anchors.unwrap_or(usize::MAX);
+
+    let mut points = anchor_points;
+    for pass in 0..refinement.passes {
+        let anchors: Vec<Anchor> = points
+            .iter()
+            .map(|point| Anchor {
+                point: point.clone(),
+                color: Rgba([0, 0, 0, 0]),
+            })
+            .collect();
+        let cell_map = assign_projected(
+            &anchors,
+            image_width,
+            image_height,
+            options.minimum_distance,
+            options,
+            None,
+        );
+
+        let cell_pixels = cell_map.cell_pixels(anchors.len());
+        let mut candidates: Vec<(f64, usize, [f64; 3])> = cell_pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, pixels)| pixels.len() > 1)
+            .map(|(index, pixels)| {
+                let (mean, variance) = color_statistics(pixels, source_image);
+                (variance, index, mean)
+            })
+            .filter(|(variance, _, _)| *variance > refinement.variance_threshold)
+            .collect();
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut added = Vec::new();
+        for (_, index, mean) in candidates {
+            if points.len() + added.len() >= maximum_anchors {
+                break;
+            }
+
+            let split = match split_point(&cell_pixels[index], source_image, mean) {
+                None => continue,
+                Some(split) => split,
+            };
+            let spacing = refinement.minimum_distance;
+            if split.squared_distance_from(&points[index]) >= spacing * spacing {
+                added.push(split);
+            }
+        }
+
+        if added.is_empty() {
+            return (points, pass);
+        }
+        points.extend(added);
+    }
+
+    (points, refinement.passes)
+}
+
+/// Reconstruction error a tessellation should get within.
+#[derive(Clone, Copy)]
+pub enum ErrorTarget {
+    /// At least this peak signal-to-noise ratio, in decibels.
+    Psnr(f64),
+    /// At most this mean squared error, on the `0` to `255` scale.
+    Mse(f64),
+}
+
+impl ErrorTarget {
+    fn is_met(&self, mean_squared_error: f64) -> bool {
+        match self {
+            ErrorTarget::Psnr(psnr) => psnr_from_mse(mean_squared_error) >= *psnr,
+            ErrorTarget::Mse(mse) => mean_squared_error <= *mse,
+        }
+    }
+}
+
+/// Keeps adding anchors, drawn where the flat cells reproduce `source_image`
+/// worst, until the tessellation gets within `target` or `maximum_anchors`
+/// is reached. Anchors stay at least a pixel apart.
+///
+/// Returns the points and the mean squared error they reach.
+pub fn refine_to_target_error(
+    source_image: &RgbaImage,
+    anchor_points: Vec<Point>,
+    target: ErrorTarget,
+    maximum_anchors: Option<usize>,
+    options: &RenderOptions,
+    rng: &mut dyn RngCore,
+) -> (Vec<Point>, f64) {
+    let (image_width, image_height) = source_image.dimensions();
+    let maximum_anchors = maximum_anchors.unwrap_or(usize::MAX);
+
+    let mut points = anchor_points;
+    loop {
+        let anchors = color_anchor_points(source_image, points.clone());
+        let cell_map = assign_projected(
+            &anchors,
+            image_width,
+            image_height,
+            options.minimum_distance,
+            options,
+            None,
+        );
+        let colors = color_cells(&cell_map, &anchors, source_image, options.colorizer);
+        let reconstruction = paint_cells(&cell_map, &colors);
+
+        // Squared error of every pixel inside a cell, summed up so pixels can
+        // be drawn in proportion to it.
+        let mut cumulative = Vec::with_capacity(cell_map.labels.len());
+        let (mut total, mut painted_pixels) = (0f64, 0usize);
+        for ((original, painted), label) in source_image
+            .pixels()
+            .zip(reconstruction.pixels())
+            .zip(&cell_map.labels)
+        {
+            if *label != UNASSIGNED {
+                total += (0..3)
+                    .map(|channel| {
+                        let difference = (original.0[channel] as f64) - (painted.0[channel] as f64);
+                        difference * difference
+                    })
+                    .sum::<f64>();
+                painted_pixels += 1;
+            }
+            cumulative.push(total);
+        }
+
+        let error = total / ((painted_pixels * 3).max(1) as f64);
+        if target.is_met(error) || (points.len() >= maximum_anchors) {
+            return (points, error);
+        }
+
+        let batch = (points.len() / 8)
+            .max(8)
+            .min(maximum_anchors - points.len());
+        let mut added: Vec<Point> = Vec::with_capacity(batch);
+        for _ in 0..(batch * 4) {
+            if added.len() >= batch {
+                break;
+            }
+
+            let drawn = rng.gen::<f64>() * total;
+            let index = cumulative
+                .partition_point(|sum| *sum <= drawn)
+                .min(cumulative.len() - 1);
+            let label = cell_map.labels[index];
+            let point = Point {
+                x: (index % (image_width as usize)) as f64,
+                y: (index / (image_width as usize)) as f64,
+            };
+            let is_spaced = (label != UNASSIGNED)
+                && (point.squared_distance_from(&points[label as usize]) >= 1f64)
+                && added
+                    .iter()
+                    .all(|other| point.squared_distance_from(other) >= 1f64);
+            if is_spaced {
+                added.push(point);
+            }
+        }
+
+        if added.is_empty() {
+            return (points, error);
+        }
+        points.extend(added);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::analysis::gradient_magnitude;
+use crate::anchors::Anchor;
+use crate::geometry::Point;
+use crate::render::{assign_cells, RenderOptions, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+
+/// Limits for centroidal relaxation.
+pub struct RelaxationOptions {
+    pub iterations: u32,
+    /// Stop early once no anchor moves further than this many pixels.
+    pub convergence_threshold: f64,
+}
+
+/// Moves anchors towards the centroid of their cell, weighting every pixel by
+/// the gradient magnitude of `source_image`, so that cells gather along strong
+/// edges and their boundaries follow the structure of the photo.
+///
+/// Returns the relaxed points and the number of iterations that ran.
+pub fn gradient_weighted_relaxation(
+    source_image: &RgbaImage,
+    anchor_points: Vec<Point>,
+    relaxation: &RelaxationOptions,
+    options: &RenderOptions,
+) -> (Vec<Point>, u32) {
+    let (image_width, image_height) = source_image.dimensions();
+    let density: Vec<f64> = gradient_magnitude(source_image)
+        .into_iter()
+        .map(|magnitude| 0.05f64 + magnitude)
+        .collect();
+
+    let mut points = anchor_points;
+    for iteration in 0..relaxation.iterations {
+        let anchors: Vec<Anchor> = points
+            .iter()
+            .map(|point| Anchor {
+                point: point.clone(),
+                color: Rgba([0, 0, 0, 0]),
+            })
+            .collect();
+        let cell_map = assign_cells(
+            &anchors,
+            image_width,
+            image_height,
+            options.minimum_distance,
+            options.metric,
+        );
+
+        let mut sums = vec![(0f64, 0f64, 0f64); points.len()];
+        for (index, label) in cell_map.labels.iter().enumerate() {
+            if *label == UNASSIGNED {
+                continue;
+            }
+            let x = (index % (image_width as usize)) as f64;
+            let y = (index / (image_width as usize)) as f64;
+            let weight = density[index];
+
+            let sum = &mut sums[*label as usize];
+            sum.0 += weight * x;
+            sum.1 += weight * y;
+            sum.2 += weight;
+        }
+
+        let mut largest_move = 0f64;
+        for (point, (weighted_x, weighted_y, total_weight)) in points.iter_mut().zip(sums) {
+            if total_weight <= 0f64 {
+                continue;
+            }
+            let centroid = Point {
+                x: weighted_x / total_weight,
+                y: weighted_y / total_weight,
+            };
+            largest_move = largest_move.max(point.squared_distance_from(&centroid).sqrt());
+            *point = centroid;
+        }
+
+        if largest_move < relaxation.convergence_threshold {
+            return (points, iteration + 1);
+        }
+    }
+
+    (points, relaxation.iterations)
+}
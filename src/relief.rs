@@ -0,0 +1,140 @@
+//! Relief lighting for raster paintings: every cell raised as a tile whose
+//! edges slope down to the boundaries, lit from the top left, and casting a
+//! soft shadow into the gaps below it, like a ceramic mosaic.
+
+use crate::render::{CellMap, UNASSIGNED};
+use image::RgbaImage;
+
+/// How far lit slopes are pulled towards white, and those facing away
+/// towards black.
+const LIGHT_STRENGTH: f64 = 0.45;
+/// How far the deepest shadows are pulled towards black.
+const SHADOW_STRENGTH: f64 = 0.5;
+/// Slope depth of the tiles when only their shadows are asked for.
+pub const DEFAULT_DEPTH: f64 = 4f64;
+
+/// How the cells of a painting are raised and lit.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Relief {
+    /// Pixels over which the edges of every tile slope down to its boundary.
+    pub depth: f64,
+    /// Light the slopes, brighter facing the top left and darker facing the
+    /// bottom right.
+    pub bevel: bool,
+    /// Darken the gaps that the tiles above and to the left of them shade.
+    pub shadow: bool,
+}
+
+/// Distance of every pixel of `cell_map` to the nearest one of another cell,
+/// through the cell, as a two pass chamfer transform. The edges of the image
+/// do not count as boundaries.
+fn boundary_distances(cell_map: &CellMap) -> Vec<f64> {
+    let (width, height) = (cell_map.width as usize, cell_map.height as usize);
+    let label = |x: usize, y: usize| cell_map.labels[(y * width) + x];
+    let mut distances: Vec<f64> = (0..(width * height))
+        .map(|index| {
+            let (x, y) = (index % width, index / width);
+            let own = label(x, y);
+            let is_boundary = ((x > 0) && (label(x - 1, y) != own))
+                || ((x + 1 < width) && (label(x + 1, y) != own))
+                || ((y > 0) && (label(x, y - 1) != own))
+                || ((y + 1 < height) && (label(x, y + 1) != own));
+            match is_boundary {
+                true => 0.5f64,
+                false => f64::INFINITY,
+            }
+        })
+        .collect();
+
+    let diagonal = 2f64.sqrt();
+    let forward = [
+        (-1i64, 0i64, 1f64),
+        (0, -1, 1f64),
+        (-1, -1, diagonal),
+        (1, -1, diagonal),
+    ];
+    let backward = forward.map(|(dx, dy, step)| (-dx, -dy, step));
+    let mut relax = |x: usize, y: usize, steps: &[(i64, i64, f64)]| {
+        for (dx, dy, step) in steps {
+            let (from_x, from_y) = ((x as i64) + dx, (y as i64) + dy);
+            if (from_x < 0) || (from_y < 0) || (from_x >= width as i64) || (from_y >= height as i64)
+            {
+                continue;
+            }
+            let from = ((from_y as usize) * width) + (from_x as usize);
+            let index = (y * width) + x;
+            distances[index] = distances[index].min(distances[from] + step);
+        }
+    };
+    for y in 0..height {
+        for x in 0..width {
+            relax(x, y, &forward);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            relax(x, y, &backward);
+        }
+    }
+
+    distances
+}
+
+/// Raises the cells of `cell_map` painted on `image`, of its size, into
+/// tiles and lights them as `relief` asks. Pixels outside every cell are
+/// left as they are.
+pub fn light_cells(image: &mut RgbaImage, cell_map: &CellMap, relief: &Relief) {
+    let (width, height) = (cell_map.width as i64, cell_map.height as i64);
+    let heights: Vec<f64> = boundary_distances(cell_map)
+        .into_iter()
+        .map(|distance| distance.min(relief.depth) / relief.depth)
+        .collect();
+    let index =
+        |x: i64, y: i64| ((y.clamp(0, height - 1) * width) + x.clamp(0, width - 1)) as usize;
+    // Averaged over 3x3 pixels, so diagonal slopes do not light in steps.
+    let heights: Vec<f64> = (0..(width * height))
+        .map(|pixel| {
+            let (x, y) = (pixel % width, pixel / width);
+            (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| heights[index(x + dx, y + dy)])
+                .sum::<f64>()
+                / 9f64
+        })
+        .collect();
+    let height_at = |x: i64, y: i64| heights[index(x, y)];
+    // Tiles shade the gaps half their slope away from them.
+    let reach = ((relief.depth / 2f64).round() as i64).max(1);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if cell_map.label(x, y) == UNASSIGNED {
+            continue;
+        }
+        let (x, y) = (x as i64, y as i64);
+        let own = height_at(x, y);
+
+        // Slopes rise away from the boundary, so those rising towards the
+        // bottom right face the light.
+        let mut shade = 0f64;
+        if relief.bevel {
+            let slope_x = (height_at(x + 1, y) - height_at(x - 1, y)) / 2f64;
+            let slope_y = (height_at(x, y + 1) - height_at(x, y - 1)) / 2f64;
+            shade += ((slope_x + slope_y) / 2f64.sqrt() * relief.depth).clamp(-1f64, 1f64)
+                * LIGHT_STRENGTH;
+        }
+        if relief.shadow {
+            let shadow = (height_at(x - reach, y - reach) - own).clamp(0f64, 1f64);
+            shade -= shadow * SHADOW_STRENGTH;
+        }
+
+        let shade = shade.clamp(-1f64, 1f64);
+        for channel in pixel.0.iter_mut().take(3) {
+            let value = *channel as f64;
+            let shaded = match shade >= 0f64 {
+                true => value + ((255f64 - value) * shade),
+                false => value * (1f64 + shade),
+            };
+            *channel = shaded.round().clamp(0f64, 255f64) as u8;
+        }
+    }
+}
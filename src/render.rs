@@ -0,0 +1,1933 @@
+use crate::anchors::Anchor;
+use crate::color::ColorSpace;
+use crate::colorize::{AnchorColorizer, Cell, CellColorizer};
+use crate::geometry::{DistanceMetric, Euclidean, Point};
+use crate::mask::ShapeMask;
+use crate::merge::{cell_areas, merge_similar_neighbours, merge_small_cells, neighbouring_cells};
+use crate::pattern::{paint_patterns, FillPattern};
+use crate::projection::{
+    assign_cells_on_sphere, assign_cells_on_torus, torus_copies, Equirectangular, Projection,
+    Toroidal,
+};
+use crate::relief::{light_cells, Relief};
+use crate::rounding::round_cell_corners;
+use crate::script::{draw_cell_borders, CellScript};
+use crate::stipple::paint_stipples;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::timings::Timings;
+use crate::transform::assign_cells_by_distance_transform;
+use crate::vision::{separate_confused_neighbours, ColorVision};
+use image::{Rgba, RgbaImage};
+#[cfg(not(target_arch = "wasm32"))]
+use serde_json::json;
+use std::any::Any;
+use std::cell::{Cell as ThreadCell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicU32, AtomicUsize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+use std::sync::Once;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+pub const UNASSIGNED: u32 = u32::MAX;
+
+/// The index of the owning anchor for every pixel of the output image.
+#[derive(Clone)]
+pub struct CellMap {
+    pub width: u32,
+    pub height: u32,
+    pub labels: Vec<u32>,
+}
+
+impl CellMap {
+    pub fn new(width: u32, height: u32) -> CellMap {
+        CellMap {
+            width,
+            height,
+            labels: vec![UNASSIGNED; (width as usize) * (height as usize)],
+        }
+    }
+
+    pub fn label(&self, x: u32, y: u32) -> u32 {
+        self.labels[(y as usize) * (self.width as usize) + (x as usize)]
+    }
+
+    pub fn set_label(&mut self, x: u32, y: u32, label: u32) {
+        self.labels[(y as usize) * (self.width as usize) + (x as usize)] = label;
+    }
+
+    pub fn set_column(&mut self, x: u32, column_labels: Vec<u32>) {
+        for (y, label) in column_labels.into_iter().enumerate() {
+            self.set_label(x, y as u32, label);
+        }
+    }
+
+    /// Groups pixel coordinates by the anchor that owns them.
+    pub fn cell_pixels(&self, anchor_count: usize) -> Vec<Vec<(u32, u32)>> {
+        let mut cells = vec![Vec::new(); anchor_count];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let label = self.label(x, y);
+                if label != UNASSIGNED {
+                    cells[label as usize].push((x, y));
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+/// The anchors sorted by x once, so that the candidates of every column are
+/// found with a binary search instead of a scan over all of them.
+pub struct AnchorColumns {
+    /// Anchor indices, in order of their x coordinate.
+    order: Vec<usize>,
+    /// The x coordinate of every anchor of `order`.
+    xs: Vec<f64>,
+    /// The position of every anchor, by index.
+    positions: Vec<(f64, f64)>,
+}
+
+impl AnchorColumns {
+    pub fn new(anchors: &[Anchor]) -> AnchorColumns {
+        let mut order: Vec<usize> = (0..anchors.len()).collect();
+        order.sort_by(|a, b| anchors[*a].point.x.total_cmp(&anchors[*b].point.x));
+        let xs = order.iter().map(|index| anchors[*index].point.x).collect();
+        let positions = anchors
+            .iter()
+            .map(|anchor| (anchor.point.x, anchor.point.y))
+            .collect();
+
+        AnchorColumns {
+            order,
+            xs,
+            positions,
+        }
+    }
+
+    /// The anchors less than `window` pixels to the left or right of column
+    /// `x`, in the order of their index.
+    pub fn candidates(&self, x: u32, window: u32) -> Vec<usize> {
+        let left = ((x as i64) - (window as i64)) as f64;
+        let right = ((x as i64) + (window as i64)) as f64;
+        let start = self.xs.partition_point(|anchor_x| *anchor_x <= left);
+        let end = self.xs.partition_point(|anchor_x| *anchor_x < right);
+
+        let mut candidates = self.order[start..end.max(start)].to_vec();
+        candidates.sort_unstable();
+        candidates
+    }
+
+    /// Like [`AnchorColumns::candidates`], but with the window of
+    /// [`AnchorColumns::search_window`], so that no anchor left out is closer
+    /// to a pixel of the column in `rows` than its closest candidate.
+    pub fn exhaustive_candidates(&self, x: u32, rows: Range<u32>, window: u32) -> Vec<usize> {
+        self.widen(x, rows, window).0
+    }
+
+    /// `window`, widened until every pixel of column `x` in `rows` is within
+    /// it of one of the anchors less than it to the left or right. Any
+    /// anchor further to the side is then further from every pixel than its
+    /// closest candidate, even where the anchors are much sparser than
+    /// `window`.
+    pub fn search_window(&self, x: u32, rows: Range<u32>, window: u32) -> u32 {
+        self.widen(x, rows, window).1
+    }
+
+    fn widen(&self, x: u32, rows: Range<u32>, window: u32) -> (Vec<usize>, u32) {
+        let mut window = window.max(1);
+        loop {
+            let candidates = self.candidates(x, window);
+            if self.order.is_empty() || rows.is_empty() || (window == u32::MAX) {
+                return (candidates, window);
+            }
+            // Widening to the reach of the candidates takes in every anchor
+            // closer than them, so the window settles after the next round.
+            window = match self.reach(x, rows.clone(), &candidates) {
+                Some(reach) if reach <= window => return (candidates, window),
+                Some(reach) => reach,
+                None => window.saturating_mul(2),
+            };
+        }
+    }
+
+    /// The smallest distance every pixel of column `x` in `rows` is within
+    /// of one of `candidates`, or `None` without any.
+    fn reach(&self, x: u32, rows: Range<u32>, candidates: &[usize]) -> Option<u32> {
+        let (top, bottom) = (rows.start as f64, (rows.end - 1) as f64);
+        let mut high = candidates
+            .iter()
+            .map(|index| {
+                let (anchor_x, anchor_y) = self.positions[*index];
+                let horizontal_offset = (x as f64) - anchor_x;
+                let vertical_offset = (anchor_y - top).max(bottom - anchor_y);
+                ((horizontal_offset * horizontal_offset) + (vertical_offset * vertical_offset))
+                    .sqrt()
+                    .ceil()
+            })
+            .fold(None, |smallest: Option<f64>, distance| {
+                Some(smallest.map_or(distance, |smallest| smallest.min(distance)))
+            })?
+            .min(u32::MAX as f64) as u32;
+
+        let mut low = 0;
+        while low < high {
+            let middle = low + ((high - low) / 2);
+            if self.covers(x, rows.clone(), candidates, middle) {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        Some(high)
+    }
+
+    /// Whether every pixel of column `x` in `rows` is at most `window` pixels
+    /// from one of `candidates`.
+    fn covers(&self, x: u32, rows: Range<u32>, candidates: &[usize], window: u32) -> bool {
+        let reach = (window as f64) * (window as f64);
+        // The rows every candidate is within `window` of, as an interval.
+        let mut spans: Vec<(f64, f64)> = candidates
+            .iter()
+            .filter_map(|index| {
+                let (anchor_x, anchor_y) = self.positions[*index];
+                let horizontal_offset = (x as f64) - anchor_x;
+                let half_height = reach - (horizontal_offset * horizontal_offset);
+                (half_height >= 0f64).then(|| {
+                    let half_height = half_height.sqrt();
+                    (anchor_y - half_height, anchor_y + half_height)
+                })
+            })
+            .collect();
+        spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // The first row not covered yet.
+        let mut uncovered = rows.start as f64;
+        for (from, to) in spans {
+            if uncovered >= (rows.end as f64) {
+                break;
+            }
+            if from.ceil() > uncovered {
+                return false;
+            }
+            uncovered = uncovered.max(to.floor() + 1f64);
+        }
+
+        uncovered >= (rows.end as f64)
+    }
+}
+
+pub fn pixel_calculator<M>(
+    x: u32,
+    image_height: u32,
+    anchors: &[Anchor],
+    columns: &AnchorColumns,
+    minimum_distance_between_anchors: u32,
+    metric: &M,
+) -> Vec<u32>
+where
+    M: DistanceMetric + ?Sized,
+{
+    let filtered_anchors =
+        columns.exhaustive_candidates(x, 0..image_height, minimum_distance_between_anchors);
+
+    closest_in_column(x, 0..image_height, anchors, &filtered_anchors, metric)
+}
+
+/// Labels the pixels of column `x` in `rows` with the closest of the
+/// `candidates` among `anchors`, or [`UNASSIGNED`] without any.
+///
+/// The squared Euclidean distance is computed inline: the horizontal part is
+/// the same for the whole column, so it is worked out once per candidate and
+/// only the vertical part is left to every pixel.
+pub(crate) fn closest_in_column<M>(
+    x: u32,
+    rows: Range<u32>,
+    anchors: &[Anchor],
+    candidates: &[usize],
+    metric: &M,
+) -> Vec<u32>
+where
+    M: DistanceMetric + ?Sized,
+{
+    if !metric.is_squared_euclidean() {
+        return rows
+            .map(|y| {
+                let point = Point {
+                    x: x as f64,
+                    y: y as f64,
+                };
+                point
+                    .closest_anchor(anchors, candidates, metric)
+                    .map_or(UNASSIGNED, |index| index as u32)
+            })
+            .collect();
+    }
+
+    let column: Vec<(u32, f64, f64)> = candidates
+        .iter()
+        .map(|index| {
+            let point = &anchors[*index].point;
+            let horizontal_offset = (x as f64) - point.x;
+            (
+                *index as u32,
+                point.y,
+                horizontal_offset * horizontal_offset,
+            )
+        })
+        .collect();
+
+    rows.map(|y| {
+        let y = y as f64;
+        let mut closest = (UNASSIGNED, f64::INFINITY);
+        for (index, anchor_y, horizontal_distance) in &column {
+            let vertical_offset = y - anchor_y;
+            let distance = horizontal_distance + (vertical_offset * vertical_offset);
+            if (closest.0 == UNASSIGNED) || (closest.1 > distance) {
+                closest = (*index, distance);
+            }
+        }
+
+        closest.0
+    })
+    .collect()
+}
+
+/// A column whose calculation panicked, with what it panicked with and
+/// where.
+#[derive(Clone, Debug)]
+pub struct ColumnFailure {
+    pub column: u32,
+    pub message: String,
+}
+
+/// The columns of a render that failed. [`map_columns_on_target`] panics
+/// with it, once every column is done, instead of with the first panic of a
+/// worker.
+#[derive(Clone, Debug)]
+pub struct RenderFailure {
+    pub columns: Vec<ColumnFailure>,
+}
+
+/// Failed columns a [`RenderFailure`] lists before only counting the rest.
+const LISTED_FAILURES: usize = 5;
+
+impl RenderFailure {
+    /// A line per failed column, up to a few, then how many more failed.
+    fn column_lines(&self) -> String {
+        let mut lines: String = self
+            .columns
+            .iter()
+            .take(LISTED_FAILURES)
+            .map(|failure| format!("\n  column {}: {}", failure.column, failure.message))
+            .collect();
+        if self.columns.len() > LISTED_FAILURES {
+            lines.push_str(&format!(
+                "\n  and {} more",
+                self.columns.len() - LISTED_FAILURES
+            ));
+        }
+
+        lines
+    }
+}
+
+impl fmt::Display for RenderFailure {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.columns.len();
+        write!(
+            formatter,
+            "Rendering failed in {} column{}:{}",
+            count,
+            if count == 1 { "" } else { "s" },
+            self.column_lines()
+        )
+    }
+}
+
+thread_local! {
+    /// Whether a panic on this thread is being caught as a column failure,
+    /// which the panic hook then records instead of printing.
+    static CATCHING_COLUMN: ThreadCell<bool> = const { ThreadCell::new(false) };
+    static PANIC_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static PANIC_HOOK: Once = Once::new();
+
+/// Wraps the panic hook so that panics caught as column failures, and the
+/// [`RenderFailure`] they end in, are reported once with their columns
+/// instead of printed from every thread.
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING_COLUMN.with(ThreadCell::get) {
+                let location = info.location().map(ToString::to_string);
+                PANIC_LOCATION.with(|recorded| *recorded.borrow_mut() = location);
+            } else if !info.payload().is::<RenderFailure>() {
+                previous(info);
+            }
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(failure) = payload.downcast_ref::<RenderFailure>() {
+        failure.to_string()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+/// Runs `column_calculator` for column `x`, catching a panic as the failure
+/// of the column.
+fn catch_column<T, F>(x: u32, column_calculator: &F) -> Result<T, ColumnFailure>
+where
+    F: Fn(u32) -> T,
+{
+    install_panic_hook();
+    let was_catching = CATCHING_COLUMN.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| column_calculator(x)));
+    CATCHING_COLUMN.with(|catching| catching.set(was_catching));
+
+    result.map_err(|payload| {
+        let message = panic_message(payload.as_ref());
+        let location = PANIC_LOCATION.with(|recorded| recorded.borrow_mut().take());
+        ColumnFailure {
+            column: x,
+            message: match location {
+                None => message,
+                Some(location) => format!("{} (at {})", message, location),
+            },
+        }
+    })
+}
+
+/// The results of every column, or the failures of those that failed.
+fn collect_columns<T>(columns: Vec<Result<T, ColumnFailure>>) -> Result<Vec<T>, RenderFailure> {
+    let mut results = Vec::with_capacity(columns.len());
+    let mut failures = Vec::new();
+    for column in columns {
+        match column {
+            Ok(result) => results.push(result),
+            Err(failure) => failures.push(failure),
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(results),
+        false => Err(RenderFailure { columns: failures }),
+    }
+}
+
+/// Runs `column_calculator` for every column on the calling thread.
+///
+/// This is the portable path used on targets without `std::thread`, such as
+/// `wasm32-unknown-unknown`.
+pub fn map_columns<T, F>(image_width: u32, column_calculator: F) -> Vec<T>
+where
+    F: Fn(u32) -> T,
+{
+    (0..image_width).map(column_calculator).collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static WORKER_THREADS: AtomicUsize = AtomicUsize::new(10);
+
+/// Sets how many worker threads [`map_columns_in_threads`] runs at once, for
+/// every render in the process.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_worker_threads(count: usize) {
+    WORKER_THREADS.store(count.max(1), Ordering::Relaxed);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static WORKER_BUSY: Mutex<Vec<Duration>> = Mutex::new(Vec::new());
+
+/// How long every worker thread of [`map_columns_in_threads`] spent on
+/// columns since the last call, by its place in the batches.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn take_worker_busy_times() -> Vec<Duration> {
+    match WORKER_BUSY.lock() {
+        Ok(mut busy) => std::mem::take(&mut *busy),
+        Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn add_worker_busy_time(worker: usize, busy: Duration) {
+    let mut times = match WORKER_BUSY.lock() {
+        Ok(times) => times,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if times.len() <= worker {
+        times.resize(worker + 1, Duration::ZERO);
+    }
+    times[worker] += busy;
+}
+
+/// How many worker threads run at once, as set with [`set_worker_threads`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn worker_threads() -> usize {
+    WORKER_THREADS.load(Ordering::Relaxed)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static AUTO_TUNE: AtomicBool = AtomicBool::new(false);
+
+/// Makes [`map_columns_in_threads`] time its first columns to pick how many
+/// threads to run, up to the count set with [`set_worker_threads`], and how
+/// many columns every thread takes at once.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_auto_tune(enabled: bool) {
+    AUTO_TUNE.store(enabled, Ordering::Relaxed);
+}
+
+/// How [`map_columns_in_threads`] reports the columns it finishes.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// A line per column on stdout.
+    Text,
+    /// A JSON event on stderr every time another percent of the columns is
+    /// done, with the phase, the columns done and the seconds left, for
+    /// programs wrapping the painter.
+    Json,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+static PROGRESS_JSON: AtomicBool = AtomicBool::new(false);
+
+#[cfg(not(target_arch = "wasm32"))]
+static PROGRESS_PHASE: Mutex<&str> = Mutex::new("render");
+
+/// Sets how every render in the process reports its progress.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_progress_format(format: ProgressFormat) {
+    PROGRESS_JSON.store(format == ProgressFormat::Json, Ordering::Relaxed);
+}
+
+/// Names the phase the columns finished from now on belong to in
+/// [`ProgressFormat::Json`] events.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn set_progress_phase(phase: &'static str) {
+    match PROGRESS_PHASE.lock() {
+        Ok(mut current) => *current = phase,
+        Err(poisoned) => *poisoned.into_inner() = phase,
+    }
+}
+
+/// Columns finished so far by one [`map_columns_in_threads`] call.
+#[cfg(not(target_arch = "wasm32"))]
+struct ColumnProgress {
+    columns: u32,
+    done: AtomicU32,
+    started: Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ColumnProgress {
+    fn new(columns: u32) -> ColumnProgress {
+        ColumnProgress {
+            columns,
+            done: AtomicU32::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    fn finish_column(&self, x: u32) {
+        if !PROGRESS_JSON.load(Ordering::Relaxed) {
+            println!("Finished processing column: {}", x);
+            return;
+        }
+
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let percent = |done: u32| (u64::from(done) * 100) / u64::from(self.columns.max(1));
+        if (percent(done) == percent(done - 1)) && (done < self.columns) {
+            return;
+        }
+        let phase = match PROGRESS_PHASE.lock() {
+            Ok(phase) => *phase,
+            Err(poisoned) => *poisoned.into_inner(),
+        };
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let remaining = (elapsed / (done as f64)) * (self.columns.saturating_sub(done) as f64);
+        eprintln!(
+            "{}",
+            json!({
+                "phase": phase,
+                "percent": percent(done),
+                "columns_done": done,
+                "columns": self.columns,
+                "eta_seconds": (remaining * 10f64).round() / 10f64,
+            })
+        );
+    }
+}
+
+/// Runs batches of `threads` worker threads over `columns`, every thread
+/// taking `chunk` consecutive columns, and appends the results, or the
+/// failures of the columns that panicked, in order.
+#[cfg(not(target_arch = "wasm32"))]
+fn map_column_range_in_threads<T, F>(
+    columns: Range<u32>,
+    threads: u32,
+    chunk: u32,
+    column_calculator: &F,
+    progress: &ColumnProgress,
+    results: &mut Vec<Result<T, ColumnFailure>>,
+) where
+    T: Send,
+    F: Fn(u32) -> T + Sync,
+{
+    let batch = threads.saturating_mul(chunk).max(1);
+    for step in columns.clone().step_by(batch as usize) {
+        thread::scope(|scope| {
+            let mut thread_pool = Vec::with_capacity(threads as usize);
+            for worker in 0..threads {
+                let first = step.saturating_add(worker * chunk);
+                if first >= columns.end {
+                    break;
+                }
+                let last = first.saturating_add(chunk).min(columns.end);
+                let handle = scope.spawn(move || {
+                    let started = Instant::now();
+                    let mut chunk_columns = Vec::with_capacity((last - first) as usize);
+                    for x in first..last {
+                        chunk_columns.push(catch_column(x, column_calculator));
+                        progress.finish_column(x);
+                    }
+                    add_worker_busy_time(worker as usize, started.elapsed());
+
+                    chunk_columns
+                });
+
+                thread_pool.push((first..last, handle));
+            }
+
+            for (chunk_range, thread) in thread_pool {
+                match thread.join() {
+                    Ok(chunk_columns) => {
+                        results.extend(chunk_columns);
+                    }
+                    // Only reporting progress is left to panic outside a column.
+                    Err(payload) => {
+                        let message = panic_message(payload.as_ref());
+                        results.extend(chunk_range.map(|column| {
+                            Err(ColumnFailure {
+                                column,
+                                message: message.clone(),
+                            })
+                        }));
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Picks a thread count and chunk size for the columns left after the
+/// calibration, which renders the first columns of `image_width` into
+/// `results`: a few columns alone give the cost of a column, which against
+/// the cost of starting a thread gives the chunk size, then one batch of
+/// every candidate thread count shows which keeps up the most columns per
+/// second. Returns `None`, having rendered nothing, when the image is too
+/// narrow to spare the columns.
+#[cfg(not(target_arch = "wasm32"))]
+fn tune_workers<T, F>(
+    image_width: u32,
+    column_calculator: &F,
+    progress: &ColumnProgress,
+    results: &mut Vec<Result<T, ColumnFailure>>,
+) -> Option<(u32, u32)>
+where
+    T: Send,
+    F: Fn(u32) -> T + Sync,
+{
+    const SERIAL_COLUMNS: u32 = 4;
+    const MAXIMUM_CHUNK: u32 = 64;
+
+    let maximum_threads = worker_threads() as u32;
+    let mut candidates: Vec<u32> = (0..)
+        .map(|power| 1u32 << power)
+        .take_while(|threads| *threads < maximum_threads)
+        .collect();
+    candidates.push(maximum_threads);
+    // The calibration may take at most a quarter of the image.
+    let budget = image_width / 4;
+    if budget < SERIAL_COLUMNS + candidates.iter().sum::<u32>() {
+        return None;
+    }
+
+    let started = Instant::now();
+    for x in 0..SERIAL_COLUMNS {
+        results.push(catch_column(x, column_calculator));
+        progress.finish_column(x);
+    }
+    let column_cost = started.elapsed() / SERIAL_COLUMNS;
+
+    let started = Instant::now();
+    for _ in 0..SERIAL_COLUMNS {
+        thread::scope(|scope| {
+            scope.spawn(|| ());
+        });
+    }
+    let spawn_cost = started.elapsed() / SERIAL_COLUMNS;
+
+    // Chunks long enough that starting their thread costs a tenth of them.
+    let chunk = ((spawn_cost.as_secs_f64() * 10f64) / column_cost.as_secs_f64().max(1e-9))
+        .ceil()
+        .clamp(1f64, MAXIMUM_CHUNK as f64) as u32;
+    let chunk = chunk.min((budget - SERIAL_COLUMNS) / candidates.iter().sum::<u32>());
+
+    let mut next = SERIAL_COLUMNS;
+    let mut best: Option<(f64, u32)> = None;
+    for threads in candidates {
+        let columns = next..(next + (threads * chunk));
+        next = columns.end;
+        let started = Instant::now();
+        map_column_range_in_threads(
+            columns,
+            threads,
+            chunk,
+            column_calculator,
+            progress,
+            results,
+        );
+        let throughput = ((threads * chunk) as f64) / started.elapsed().as_secs_f64().max(1e-9);
+        match best {
+            Some((best_throughput, _)) if best_throughput >= throughput => {
+                // More threads only get slower from here.
+                break;
+            }
+            _ => best = Some((throughput, threads)),
+        }
+    }
+    let threads = best.map_or(1, |(_, threads)| threads);
+
+    println!(
+        "Auto-tuned to {} worker threads of {} columns each",
+        threads, chunk
+    );
+    Some((threads, chunk))
+}
+
+/// Runs `column_calculator` for every column with a batch of worker threads
+/// per group of as many columns, ten unless set with [`set_worker_threads`].
+/// With [`set_auto_tune`], the thread count and columns per thread are
+/// calibrated on the first columns instead.
+///
+/// Panics with a [`RenderFailure`] of every column that panicked.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn map_columns_in_threads<T, F>(image_width: u32, column_calculator: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(u32) -> T + Sync,
+{
+    collect_columns(map_columns_caught(image_width, column_calculator))
+        .unwrap_or_else(|failure| panic::panic_any(failure))
+}
+
+/// Runs `column_calculator` for every column as [`map_columns_on_target`]
+/// does, catching the panic of every column as its failure.
+fn map_columns_caught<T, F>(image_width: u32, column_calculator: F) -> Vec<Result<T, ColumnFailure>>
+where
+    T: Send,
+    F: Fn(u32) -> T + Sync,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut columns = Vec::with_capacity(image_width as usize);
+        let column_calculator = &column_calculator;
+        let progress = ColumnProgress::new(image_width);
+
+        let tuning = if AUTO_TUNE.load(Ordering::Relaxed) {
+            tune_workers(image_width, column_calculator, &progress, &mut columns)
+        } else {
+            None
+        };
+        let (threads, chunk) = tuning.unwrap_or((worker_threads() as u32, 1));
+        map_column_range_in_threads(
+            (columns.len() as u32)..image_width,
+            threads,
+            chunk,
+            column_calculator,
+            &progress,
+            &mut columns,
+        );
+
+        columns
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        map_columns(image_width, |x| catch_column(x, &column_calculator))
+    }
+}
+
+/// Runs `column_calculator` for every column using the fastest strategy
+/// available on the current target, or fails with every column that
+/// panicked.
+pub fn try_map_columns_on_target<T, F>(
+    image_width: u32,
+    column_calculator: F,
+) -> Result<Vec<T>, RenderFailure>
+where
+    T: Send,
+    F: Fn(u32) -> T + Sync,
+{
+    collect_columns(map_columns_caught(image_width, column_calculator))
+}
+
+/// Runs `column_calculator` for every column using the fastest strategy
+/// available on the current target.
+///
+/// Panics with a [`RenderFailure`] of every column that panicked.
+pub fn map_columns_on_target<T, F>(image_width: u32, column_calculator: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(u32) -> T + Sync,
+{
+    try_map_columns_on_target(image_width, column_calculator)
+        .unwrap_or_else(|failure| panic::panic_any(failure))
+}
+
+/// Runs `run`, turning a [`RenderFailure`] it panics with into its error.
+/// Other panics go on unwinding.
+pub fn catch_render_failure<T>(run: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(|payload| {
+        match payload.downcast::<RenderFailure>() {
+            Ok(failure) => Err(failure.to_string()),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    })
+}
+
+static BEST_EFFORT: AtomicBool = AtomicBool::new(false);
+
+/// Makes the cell assignments of every render leave the columns that fail
+/// unassigned, listing them on stderr, instead of failing the render.
+pub fn set_best_effort(enabled: bool) {
+    BEST_EFFORT.store(enabled, Ordering::Relaxed);
+}
+
+/// Assigns the pixels of every column with `calculate`, reporting them to
+/// `observer` and leaving them unassigned once it is cancelled, or with
+/// [`set_best_effort`] when they fail.
+fn assign_columns<F>(
+    image_width: u32,
+    image_height: u32,
+    observer: Option<&dyn RenderObserver>,
+    calculate: F,
+) -> CellMap
+where
+    F: Fn(u32) -> Vec<u32> + Sync,
+{
+    let columns = map_columns_caught(image_width, |x| match observer {
+        None => calculate(x),
+        Some(observer) if observer.is_cancelled() => vec![UNASSIGNED; image_height as usize],
+        Some(observer) => {
+            let labels = calculate(x);
+            observer.column_assigned(x, &labels);
+
+            labels
+        }
+    });
+
+    let mut cell_map = CellMap::new(image_width, image_height);
+    let mut failures = Vec::new();
+    for (x, column) in columns.into_iter().enumerate() {
+        match column {
+            Ok(column_labels) => cell_map.set_column(x as u32, column_labels),
+            Err(failure) => failures.push(failure),
+        }
+    }
+    if !failures.is_empty() {
+        let failure = RenderFailure { columns: failures };
+        if !BEST_EFFORT.load(Ordering::Relaxed) {
+            panic::panic_any(failure);
+        }
+        eprintln!(
+            "Left {} failed column{} unassigned:{}",
+            failure.columns.len(),
+            if failure.columns.len() == 1 { "" } else { "s" },
+            failure.column_lines()
+        );
+    }
+
+    cell_map
+}
+
+/// Receives progress from a render while it runs. Methods may be called from
+/// worker threads, in any column order.
+pub trait RenderObserver: Sync {
+    fn column_assigned(&self, _x: u32, _labels: &[u32]) {}
+
+    /// Checked before every column; once it returns `true` the remaining
+    /// columns are left unassigned.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Assigns every pixel to its closest anchor.
+pub fn assign_cells<M>(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    metric: &M,
+) -> CellMap
+where
+    M: DistanceMetric + ?Sized,
+{
+    assign_cells_observed(
+        anchors,
+        image_width,
+        image_height,
+        minimum_distance,
+        metric,
+        None,
+    )
+}
+
+/// Like [`assign_cells`], reporting every finished column to `observer`.
+pub fn assign_cells_observed<M>(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    metric: &M,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap
+where
+    M: DistanceMetric + ?Sized,
+{
+    let anchor_columns = AnchorColumns::new(anchors);
+    let calculate = |x| {
+        pixel_calculator(
+            x,
+            image_height,
+            anchors,
+            &anchor_columns,
+            minimum_distance,
+            metric,
+        )
+    };
+    assign_columns(image_width, image_height, observer, calculate)
+}
+
+/// Like [`assign_cells_observed`] with the Euclidean metric, measuring in
+/// `f32`: the anchors are copied once into separate arrays of their `x` and
+/// `y` coordinates, which halves the memory a column reads through for huge
+/// anchor sets.
+///
+/// Only pixels almost exactly between two anchors can go to the other one,
+/// a difference too small to see, but renders are not bit for bit those of
+/// [`assign_cells_observed`].
+pub fn assign_cells_single_precision(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap {
+    let anchor_columns = AnchorColumns::new(anchors);
+    let xs: Vec<f32> = anchors.iter().map(|anchor| anchor.point.x as f32).collect();
+    let ys: Vec<f32> = anchors.iter().map(|anchor| anchor.point.y as f32).collect();
+    let calculate = |x: u32| -> Vec<u32> {
+        let candidates = anchor_columns.exhaustive_candidates(x, 0..image_height, minimum_distance);
+        let candidate_ys: Vec<f32> = candidates.iter().map(|index| ys[*index]).collect();
+        let horizontal_distances: Vec<f32> = candidates
+            .iter()
+            .map(|index| {
+                let horizontal_offset = (x as f32) - xs[*index];
+                horizontal_offset * horizontal_offset
+            })
+            .collect();
+
+        (0..image_height)
+            .map(|y| {
+                let y = y as f32;
+                let mut closest = (UNASSIGNED, f32::INFINITY);
+                for (position, (anchor_y, horizontal_distance)) in
+                    candidate_ys.iter().zip(&horizontal_distances).enumerate()
+                {
+                    let vertical_offset = y - anchor_y;
+                    let distance = horizontal_distance + (vertical_offset * vertical_offset);
+                    if (closest.0 == UNASSIGNED) || (closest.1 > distance) {
+                        closest = (candidates[position] as u32, distance);
+                    }
+                }
+
+                closest.0
+            })
+            .collect()
+    };
+    assign_columns(image_width, image_height, observer, calculate)
+}
+
+/// Assigns every pixel to the anchor with the smallest distance minus its
+/// radius in `radii`, an additively weighted (Apollonius) diagram whose
+/// cells have curved boundaries, like soap bubbles.
+///
+/// A weighted anchor can claim pixels further away than `minimum_distance`
+/// by as much as its radius exceeds the smallest one, so the candidates of
+/// every column are taken from that much wider.
+pub fn assign_cells_additive<M>(
+    anchors: &[Anchor],
+    radii: &[f64],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    metric: &M,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap
+where
+    M: DistanceMetric + ?Sized,
+{
+    let smallest = radii.iter().copied().fold(f64::INFINITY, f64::min);
+    let largest = radii.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let spread = if radii.is_empty() {
+        0
+    } else {
+        (largest - smallest).ceil() as u32
+    };
+    let window = minimum_distance.saturating_add(spread);
+
+    let anchor_columns = AnchorColumns::new(anchors);
+    let calculate = |x: u32| -> Vec<u32> {
+        let candidates = anchor_columns.exhaustive_candidates(x, 0..image_height, window);
+        (0..image_height)
+            .map(|y| {
+                let point = Point {
+                    x: x as f64,
+                    y: y as f64,
+                };
+                let mut closest = (UNASSIGNED, f64::INFINITY);
+                for index in &candidates {
+                    let distance = metric.length(&point, &anchors[*index].point) - radii[*index];
+                    if (closest.0 == UNASSIGNED) || (closest.1 > distance) {
+                        closest = (*index as u32, distance);
+                    }
+                }
+
+                closest.0
+            })
+            .collect()
+    };
+    assign_columns(image_width, image_height, observer, calculate)
+}
+
+/// Assigns every pixel to one of its nearest anchors as `order` picks, for
+/// the intricate, overlapping cells of higher-order Voronoi diagrams.
+///
+/// The `k`-th nearest anchor may lie well beyond `minimum_distance`, so the
+/// candidates of every column are taken from `k + 1` times as wide.
+pub fn assign_cells_higher_order<M>(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    metric: &M,
+    order: HigherOrder,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap
+where
+    M: DistanceMetric + ?Sized,
+{
+    let k = match order {
+        HigherOrder::Nearest(k) | HigherOrder::Set(k) => k.max(1),
+    };
+    let window = minimum_distance.saturating_mul((k as u32).saturating_add(1));
+
+    let anchor_columns = AnchorColumns::new(anchors);
+    let calculate = |x: u32| -> Vec<u32> {
+        let candidates = anchor_columns.exhaustive_candidates(x, 0..image_height, window);
+        let mut nearest: Vec<(f64, usize)> = Vec::with_capacity(k + 1);
+        (0..image_height)
+            .map(|y| {
+                let point = Point {
+                    x: x as f64,
+                    y: y as f64,
+                };
+
+                nearest.clear();
+                for &index in &candidates {
+                    let distance = metric.distance(&point, &anchors[index].point);
+                    if nearest.len() < k || distance < nearest[nearest.len() - 1].0 {
+                        let position = nearest.partition_point(|(other, _)| *other <= distance);
+                        nearest.insert(position, (distance, index));
+                        nearest.truncate(k);
+                    }
+                }
+
+                match order {
+                    // Where fewer than k anchors are near, the furthest stands in.
+                    HigherOrder::Nearest(_) => nearest
+                        .last()
+                        .map_or(UNASSIGNED, |(_, index)| *index as u32),
+                    HigherOrder::Set(_) => {
+                        let mut set: Vec<usize> = nearest.iter().map(|(_, index)| *index).collect();
+                        set.sort_unstable();
+                        // FNV-1a of the set, so every pixel sharing it picks alike.
+                        let mut hash = 0xcbf29ce484222325u64;
+                        for index in &set {
+                            hash ^= *index as u64;
+                            hash = hash.wrapping_mul(0x100000001b3);
+                        }
+
+                        match set.len() {
+                            0 => UNASSIGNED,
+                            count => set[(hash % (count as u64)) as usize] as u32,
+                        }
+                    }
+                }
+            })
+            .collect()
+    };
+    assign_columns(image_width, image_height, observer, calculate)
+}
+
+/// Colors every pixel with a blend of the colors of its `k` nearest anchors,
+/// weighted by inverse distance, for soft transitions between cells.
+pub fn blend_nearest_cells<M>(
+    anchors: &[Anchor],
+    colors: &[Rgba<u8>],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    metric: &M,
+    k: usize,
+) -> RgbaImage
+where
+    M: DistanceMetric + ?Sized,
+{
+    let anchor_columns = AnchorColumns::new(anchors);
+    let columns = map_columns_on_target(image_width, |x| {
+        let candidates = anchor_columns.exhaustive_candidates(
+            x,
+            0..image_height,
+            minimum_distance.saturating_mul(2),
+        );
+
+        let mut column = Vec::with_capacity(image_height as usize);
+        let mut nearest: Vec<(f64, usize)> = Vec::with_capacity(k + 1);
+        for y in 0..image_height {
+            let point = Point {
+                x: x as f64,
+                y: y as f64,
+            };
+
+            nearest.clear();
+            for &index in &candidates {
+                let distance = metric.distance(&point, &anchors[index].point);
+                if nearest.len() < k || distance < nearest[nearest.len() - 1].0 {
+                    let position = nearest.partition_point(|(other, _)| *other <= distance);
+                    nearest.insert(position, (distance, index));
+                    nearest.truncate(k);
+                }
+            }
+
+            let mut sums = [0f64; 4];
+            let mut total_weight = 0f64;
+            for &(distance, index) in &nearest {
+                let weight = 1f64 / (distance + 1f64);
+                for (sum, channel) in sums.iter_mut().zip(colors[index].0) {
+                    *sum += weight * (channel as f64);
+                }
+                total_weight += weight;
+            }
+
+            column.push(if total_weight > 0f64 {
+                Rgba(sums.map(|sum| (sum / total_weight).round() as u8))
+            } else {
+                Rgba([0, 0, 0, 0])
+            });
+        }
+
+        column
+    });
+
+    let mut output_image_buffer = RgbaImage::new(image_width, image_height);
+    for (x, column) in columns.into_iter().enumerate() {
+        for (y, color) in column.into_iter().enumerate() {
+            output_image_buffer.put_pixel(x as u32, y as u32, color);
+        }
+    }
+
+    output_image_buffer
+}
+
+/// Computes the fill of every cell with the given colorizer.
+pub fn color_cells<C>(
+    cell_map: &CellMap,
+    anchors: &[Anchor],
+    source_image: &RgbaImage,
+    colorizer: &C,
+) -> Vec<Rgba<u8>>
+where
+    C: CellColorizer + ?Sized,
+{
+    cell_map
+        .cell_pixels(anchors.len())
+        .iter()
+        .zip(anchors)
+        .enumerate()
+        .map(|(index, (pixels, anchor))| {
+            colorizer.colorize(
+                &Cell {
+                    index,
+                    anchor,
+                    pixels,
+                },
+                source_image,
+            )
+        })
+        .collect()
+}
+
+/// Finishes the colors sampled for the cells of `cell_map` the way
+/// `options` asks: merging similar neighbours, then separating those
+/// `color_vision` would confuse.
+pub fn finish_cell_colors(cell_map: &CellMap, colors: &mut [Rgba<u8>], options: &RenderOptions) {
+    if options.merge_threshold.is_none() && options.color_vision.is_none() {
+        return;
+    }
+
+    finish_neighbour_colors(
+        &cell_areas(cell_map, colors.len()),
+        &neighbouring_cells(cell_map),
+        colors,
+        options,
+    );
+}
+
+/// Like [`finish_cell_colors`], for cells of `areas` pixels touching as
+/// `neighbours` do.
+pub(crate) fn finish_neighbour_colors(
+    areas: &[usize],
+    neighbours: &HashSet<(u32, u32)>,
+    colors: &mut [Rgba<u8>],
+    options: &RenderOptions,
+) {
+    if let Some(threshold) = options.merge_threshold {
+        merge_similar_neighbours(areas, neighbours, colors, threshold, options.match_space);
+    }
+    if let Some(vision) = options.color_vision {
+        separate_confused_neighbours(neighbours, colors, vision);
+    }
+}
+
+/// Draws only the boundaries between cells, `width` pixels wide, over a
+/// plain `background`. Boundaries between cells of the same color are left
+/// out when `joins_same_colors` is set, such as for merged regions.
+pub fn paint_outlines(
+    cell_map: &CellMap,
+    colors: &[Rgba<u8>],
+    joins_same_colors: bool,
+    width: f64,
+    color: Rgba<u8>,
+    background: Rgba<u8>,
+) -> RgbaImage {
+    let mut output_image_buffer =
+        RgbaImage::from_pixel(cell_map.width, cell_map.height, background);
+    draw_boundaries(
+        &mut output_image_buffer,
+        cell_map,
+        colors,
+        joins_same_colors,
+        width,
+        color,
+    );
+
+    output_image_buffer
+}
+
+/// Strokes the boundaries between cells onto `image`, as
+/// [`paint_outlines`] does.
+fn draw_boundaries(
+    output_image_buffer: &mut RgbaImage,
+    cell_map: &CellMap,
+    colors: &[Rgba<u8>],
+    joins_same_colors: bool,
+    width: f64,
+    color: Rgba<u8>,
+) {
+    let (image_width, image_height) = (cell_map.width, cell_map.height);
+    let color_of = |label: u32| (label != UNASSIGNED).then(|| colors[label as usize]);
+    let is_boundary = |from: u32, to: u32| {
+        (from != to) && !(joins_same_colors && (color_of(from) == color_of(to)))
+    };
+
+    let radius = ((width - 1f64) / 2f64).max(0f64);
+    let reach = radius.ceil() as i64;
+    for y in 0..image_height {
+        for x in 0..image_width {
+            let label = cell_map.label(x, y);
+            let right = (x + 1 < image_width) && is_boundary(label, cell_map.label(x + 1, y));
+            let below = (y + 1 < image_height) && is_boundary(label, cell_map.label(x, y + 1));
+            if !(right || below) {
+                continue;
+            }
+
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let (stroke_x, stroke_y) = ((x as i64) + dx, (y as i64) + dy);
+                    let is_inside = (stroke_x >= 0)
+                        && (stroke_y >= 0)
+                        && (stroke_x < image_width as i64)
+                        && (stroke_y < image_height as i64);
+                    if is_inside && (((dx * dx) + (dy * dy)) as f64) <= (radius * radius) + 0.5f64 {
+                        output_image_buffer.put_pixel(stroke_x as u32, stroke_y as u32, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn paint_cells(cell_map: &CellMap, colors: &[Rgba<u8>]) -> RgbaImage {
+    let mut output_image_buffer = RgbaImage::new(cell_map.width, cell_map.height);
+
+    for (x, y, pixel) in output_image_buffer.enumerate_pixels_mut() {
+        let label = cell_map.label(x, y);
+        if label != UNASSIGNED {
+            *pixel = colors[label as usize];
+        }
+    }
+
+    output_image_buffer
+}
+
+/// Re-renders the pixels that touch a cell boundary from `samples`×`samples`
+/// sub-pixel positions and blends the colors of the cells they fall in.
+///
+/// Interior pixels keep their single color, so the cost scales with the
+/// length of the cell edges rather than with the image area.
+pub fn antialias_cells<M>(
+    image: &mut RgbaImage,
+    cell_map: &CellMap,
+    anchors: &[Anchor],
+    colors: &[Rgba<u8>],
+    metric: &M,
+    samples: u32,
+) where
+    M: DistanceMetric + ?Sized,
+{
+    if samples < 2 {
+        return;
+    }
+
+    let width = cell_map.width as i64;
+    let height = cell_map.height as i64;
+    let mut neighbours: Vec<u32> = Vec::with_capacity(9);
+
+    for y in 0..height {
+        for x in 0..width {
+            neighbours.clear();
+            for (neighbour_x, neighbour_y) in (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+                .filter(|(nx, ny)| (0..width).contains(nx) && (0..height).contains(ny))
+            {
+                let label = cell_map.label(neighbour_x as u32, neighbour_y as u32);
+                if (label != UNASSIGNED) && !neighbours.contains(&label) {
+                    neighbours.push(label);
+                }
+            }
+            if neighbours.len() < 2 {
+                continue;
+            }
+
+            let mut sums = [0f64; 4];
+            for sample_y in 0..samples {
+                for sample_x in 0..samples {
+                    let point = Point {
+                        x: (x as f64) - 0.5f64 + (((sample_x as f64) + 0.5f64) / (samples as f64)),
+                        y: (y as f64) - 0.5f64 + (((sample_y as f64) + 0.5f64) / (samples as f64)),
+                    };
+                    let closest = neighbours
+                        .iter()
+                        .map(|label| {
+                            (
+                                *label,
+                                metric.distance(&point, &anchors[*label as usize].point),
+                            )
+                        })
+                        .fold(
+                            None,
+                            |closest: Option<(u32, f64)>, (label, distance)| match closest {
+                                Some((_, closest_distance)) if closest_distance <= distance => {
+                                    closest
+                                }
+                                _ => Some((label, distance)),
+                            },
+                        );
+                    if let Some((label, _)) = closest {
+                        for (sum, channel) in sums.iter_mut().zip(colors[label as usize].0) {
+                            *sum += channel as f64;
+                        }
+                    }
+                }
+            }
+
+            let sample_count = (samples * samples) as f64;
+            image.put_pixel(
+                x as u32,
+                y as u32,
+                Rgba(sums.map(|sum| (sum / sample_count).round() as u8)),
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderOptions<'a> {
+    pub minimum_distance: u32,
+    pub metric: &'a dyn DistanceMetric,
+    pub colorizer: &'a dyn CellColorizer,
+    /// Blend each pixel between this many nearest anchors instead of using
+    /// hard cell edges.
+    pub smoothing: Option<usize>,
+    /// Supersample boundary pixels on an N×N grid to smooth jagged edges.
+    pub antialias: Option<u32>,
+    /// Rasterize the cells at this size instead of the size of the source.
+    pub output_size: Option<(u32, u32)>,
+    pub observer: Option<&'a dyn RenderObserver>,
+    /// Only draw cells inside this shape and leave the rest transparent.
+    pub mask: Option<&'a ShapeMask>,
+    /// Surface the image is tessellated on. Anything but
+    /// [`Projection::Flat`] replaces `metric`.
+    pub projection: Projection,
+    /// Merge neighbouring cells whose colors differ by less than this ΔE.
+    pub merge_threshold: Option<f64>,
+    pub style: CellStyle,
+    /// How pixels find their closest anchor on a flat surface.
+    pub assignment: Assignment,
+    /// Nudge apart neighbouring colors this deficiency would confuse.
+    pub color_vision: Option<ColorVision>,
+    /// The additive weight of every anchor on a flat surface, in pixels:
+    /// pixels go to the anchor with the smallest distance minus it.
+    pub radii: Option<&'a [f64]>,
+    /// Which of its nearest anchors every pixel of a flat surface goes to,
+    /// instead of the closest.
+    pub order: Option<HigherOrder>,
+    /// Choose the fill and the border of every cell with this script.
+    pub script: Option<&'a CellScript>,
+    /// How precisely the Euclidean metric on a flat surface is measured.
+    pub precision: Precision,
+    /// Round the corners of the cells off within this many output pixels.
+    pub round_corners: Option<f64>,
+    /// Merge the cells owning fewer output pixels than this into their
+    /// neighbours.
+    pub min_cell_area: Option<u64>,
+    /// Raise the cells into lit tiles.
+    pub relief: Option<Relief>,
+    /// Space the colors of cells are compared in when merging them.
+    pub match_space: ColorSpace,
+}
+
+/// Which of the `k` nearest anchors of a pixel [`assign_cells_higher_order`]
+/// assigns it to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HigherOrder {
+    /// The `k`-th nearest.
+    Nearest(usize),
+    /// One of the `k` nearest picked by a hash of them, so every region
+    /// sharing the same `k` nearest anchors is one color.
+    Set(usize),
+}
+
+/// How [`assign_projected`] finds the closest anchor of every pixel.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Assignment {
+    /// Search the anchors near every column, with any metric.
+    Search,
+    /// Run a Euclidean distance transform over the whole image, whose cost
+    /// does not grow with the number of anchors.
+    DistanceTransform,
+}
+
+/// The floating point width [`assign_projected`] searches for the closest
+/// anchor of every pixel with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    /// `f64`, reproducing earlier renders exactly.
+    Double,
+    /// `f32`, with [`assign_cells_single_precision`], for huge anchor sets.
+    Single,
+}
+
+/// What is drawn of every cell.
+#[derive(Clone, Copy)]
+pub enum CellStyle {
+    /// Cells filled with their color.
+    Filled,
+    /// Cells shaded with `pattern` in their color on `background`, repeating
+    /// every `spacing` pixels.
+    Patterned {
+        pattern: FillPattern,
+        spacing: f64,
+        background: Rgba<u8>,
+    },
+    /// Only the boundaries between cells, as strokes of `width` pixels.
+    Outline {
+        width: f64,
+        color: Rgba<u8>,
+        background: Rgba<u8>,
+    },
+    /// Cells filled with their color and set in leading: strokes of `width`
+    /// pixels along the boundaries between them, like stained glass.
+    StainedGlass { width: f64, color: Rgba<u8> },
+    /// A disc in the color of every cell at its centroid, sized by its area,
+    /// on `background`, like pointillism.
+    Stipple { background: Rgba<u8> },
+}
+
+impl Default for RenderOptions<'_> {
+    fn default() -> Self {
+        RenderOptions {
+            minimum_distance: 10,
+            metric: &Euclidean,
+            colorizer: &AnchorColorizer,
+            smoothing: None,
+            antialias: None,
+            output_size: None,
+            observer: None,
+            mask: None,
+            projection: Projection::Flat,
+            merge_threshold: None,
+            style: CellStyle::Filled,
+            assignment: Assignment::Search,
+            color_vision: None,
+            radii: None,
+            order: None,
+            script: None,
+            precision: Precision::Double,
+            round_corners: None,
+            min_cell_area: None,
+            relief: None,
+            match_space: ColorSpace::Cielab,
+        }
+    }
+}
+
+/// Moves anchors placed on a `from` sized image to the same relative position
+/// on a `to` sized image, keeping their colors.
+pub fn scale_anchors(anchors: &[Anchor], from: (u32, u32), to: (u32, u32)) -> Vec<Anchor> {
+    let horizontal_scale = (to.0 as f64) / (from.0 as f64);
+    let vertical_scale = (to.1 as f64) / (from.1 as f64);
+
+    anchors
+        .iter()
+        .map(|anchor| Anchor {
+            point: Point {
+                x: anchor.point.x * horizontal_scale,
+                y: anchor.point.y * vertical_scale,
+            },
+            color: anchor.color,
+        })
+        .collect()
+}
+
+/// Assigns pixels to cells on the surface chosen by `options`, leaving
+/// pixels outside its mask unassigned.
+pub(crate) fn assign_projected(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+    minimum_distance: u32,
+    options: &RenderOptions,
+    observer: Option<&dyn RenderObserver>,
+) -> CellMap {
+    let mut cell_map = match options.projection {
+        Projection::Flat if options.radii.is_some() => assign_cells_additive(
+            anchors,
+            options.radii.unwrap_or_default(),
+            image_width,
+            image_height,
+            minimum_distance,
+            options.metric,
+            observer,
+        ),
+        Projection::Flat if options.order.is_some() => assign_cells_higher_order(
+            anchors,
+            image_width,
+            image_height,
+            minimum_distance,
+            options.metric,
+            options.order.unwrap_or(HigherOrder::Nearest(1)),
+            observer,
+        ),
+        Projection::Flat
+            if (options.assignment == Assignment::DistanceTransform)
+                && options.metric.is_squared_euclidean() =>
+        {
+            let cell_map = assign_cells_by_distance_transform(anchors, image_width, image_height);
+            if let Some(observer) = observer {
+                for x in 0..image_width {
+                    let labels: Vec<u32> =
+                        (0..image_height).map(|y| cell_map.label(x, y)).collect();
+                    observer.column_assigned(x, &labels);
+                }
+            }
+
+            cell_map
+        }
+        Projection::Flat
+            if (options.precision == Precision::Single)
+                && options.metric.is_squared_euclidean() =>
+        {
+            assign_cells_single_precision(
+                anchors,
+                image_width,
+                image_height,
+                minimum_distance,
+                observer,
+            )
+        }
+        Projection::Flat => assign_cells_observed(
+            anchors,
+            image_width,
+            image_height,
+            minimum_distance,
+            options.metric,
+            observer,
+        ),
+        Projection::Equirectangular => {
+            assign_cells_on_sphere(anchors, image_width, image_height, observer)
+        }
+        Projection::Torus => assign_cells_on_torus(
+            anchors,
+            image_width,
+            image_height,
+            minimum_distance,
+            options.metric,
+            observer,
+        ),
+    };
+    if let Some(mask) = options.mask {
+        mask.apply(&mut cell_map);
+    }
+
+    cell_map
+}
+
+/// Renders the voronoi diagram of `source_image`, assigning pixels to cells
+/// with the metric and filling each cell with the color chosen by the
+/// colorizer of `options`.
+pub fn render_voronoi(
+    source_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+) -> RgbaImage {
+    let (image_width, image_height) = source_image.dimensions();
+
+    let cell_map = assign_projected(
+        anchors,
+        image_width,
+        image_height,
+        options.minimum_distance,
+        options,
+        options.observer,
+    );
+    let mut colors = color_cells(&cell_map, anchors, source_image, options.colorizer);
+    finish_cell_colors(&cell_map, &mut colors, options);
+
+    paint_voronoi(&cell_map, anchors, colors, options)
+}
+
+/// Like [`render_voronoi`], recording how long assigning, coloring and
+/// painting the cells took in `timings`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_voronoi_timed(
+    source_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    timings: &mut Timings,
+) -> RgbaImage {
+    let mut paintings =
+        render_voronoi_styles(source_image, anchors, options, &[options.style], timings);
+
+    paintings.remove(0)
+}
+
+/// Like [`render_voronoi_timed`], painting the cells once in every one of
+/// `styles` from the same assignment instead of in `options.style`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_voronoi_styles(
+    source_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+    styles: &[CellStyle],
+    timings: &mut Timings,
+) -> Vec<RgbaImage> {
+    let (image_width, image_height) = source_image.dimensions();
+
+    let cell_map = timings.measure("assignment", || {
+        assign_projected(
+            anchors,
+            image_width,
+            image_height,
+            options.minimum_distance,
+            options,
+            options.observer,
+        )
+    });
+    let colors = timings.measure("color sampling", || {
+        let mut colors = color_cells(&cell_map, anchors, source_image, options.colorizer);
+        finish_cell_colors(&cell_map, &mut colors, options);
+
+        colors
+    });
+
+    timings.measure("painting", || {
+        styles
+            .iter()
+            .map(|style| {
+                let options = RenderOptions {
+                    style: *style,
+                    ..*options
+                };
+                paint_voronoi(&cell_map, anchors, colors.clone(), &options)
+            })
+            .collect()
+    })
+}
+
+/// `anchors` placed on a `source_size` image moved onto the output of
+/// `options`, with the minimum distance between them stretched as much.
+pub(crate) fn output_anchors(
+    anchors: &[Anchor],
+    source_size: (u32, u32),
+    options: &RenderOptions,
+) -> (Vec<Anchor>, u32) {
+    let (output_width, output_height) = options.output_size.unwrap_or(source_size);
+    let scale = ((output_width as f64) / (source_size.0 as f64))
+        .max((output_height as f64) / (source_size.1 as f64));
+
+    (
+        scale_anchors(anchors, source_size, (output_width, output_height)),
+        ((options.minimum_distance as f64) * scale).ceil() as u32,
+    )
+}
+
+/// Assigns every pixel of the output of `options` to the cells of `anchors`
+/// placed on a `source_size` image, the way [`paint_voronoi`] draws them.
+pub fn assign_output_cells(
+    anchors: &[Anchor],
+    source_size: (u32, u32),
+    options: &RenderOptions,
+) -> CellMap {
+    let (output_width, output_height) = options.output_size.unwrap_or(source_size);
+    let (anchors, minimum_distance) = output_anchors(anchors, source_size, options);
+
+    assign_projected(
+        &anchors,
+        output_width,
+        output_height,
+        minimum_distance,
+        options,
+        None,
+    )
+}
+
+/// Passes on only the cancellation of another observer, for assignments that
+/// should stop with a render without reporting their columns as its own.
+struct Cancellation<'a>(&'a dyn RenderObserver);
+
+impl RenderObserver for Cancellation<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+/// Draws cells already assigned on the source image and colored, at the
+/// output size and with the smoothing or anti-aliasing of `options`.
+pub fn paint_voronoi(
+    cell_map: &CellMap,
+    anchors: &[Anchor],
+    colors: Vec<Rgba<u8>>,
+    options: &RenderOptions,
+) -> RgbaImage {
+    let (image_width, image_height) = (cell_map.width, cell_map.height);
+
+    let (output_width, output_height) = options.output_size.unwrap_or((image_width, image_height));
+    let scaled_cell_map;
+    let (anchors, cell_map, minimum_distance) =
+        if (output_width, output_height) == (image_width, image_height) {
+            (anchors.to_vec(), cell_map, options.minimum_distance)
+        } else {
+            let (scaled_anchors, minimum_distance) =
+                output_anchors(anchors, (image_width, image_height), options);
+            let cancellation = options.observer.map(Cancellation);
+            scaled_cell_map = assign_projected(
+                &scaled_anchors,
+                output_width,
+                output_height,
+                minimum_distance,
+                options,
+                cancellation
+                    .as_ref()
+                    .map(|cancellation| cancellation as &dyn RenderObserver),
+            );
+
+            (scaled_anchors, &scaled_cell_map, minimum_distance)
+        };
+
+    let mut colors = colors;
+    let merged_cell_map = options.min_cell_area.map(|minimum_area| {
+        let mut merged_cell_map = cell_map.clone();
+        merge_small_cells(
+            &mut merged_cell_map,
+            &mut colors,
+            minimum_area,
+            options.match_space,
+        );
+        merged_cell_map
+    });
+    let cell_map = merged_cell_map.as_ref().unwrap_or(cell_map);
+
+    let rounded_cell_map = options
+        .round_corners
+        .map(|radius| round_cell_corners(cell_map, radius));
+    let cell_map = rounded_cell_map.as_ref().unwrap_or(cell_map);
+
+    let borders = options
+        .script
+        .map(|script| script.run(cell_map, &anchors, &mut colors));
+    let mut output_image_buffer =
+        paint_styled_cells(cell_map, anchors, colors, minimum_distance, options);
+    if let Some(relief) = options.relief {
+        light_cells(&mut output_image_buffer, cell_map, &relief);
+    }
+    if let Some(borders) = borders {
+        draw_cell_borders(&mut output_image_buffer, cell_map, &borders);
+    }
+
+    output_image_buffer
+}
+
+/// Draws the cells of `cell_map`, already at the output size, in the style
+/// of `options`.
+fn paint_styled_cells(
+    cell_map: &CellMap,
+    anchors: Vec<Anchor>,
+    colors: Vec<Rgba<u8>>,
+    minimum_distance: u32,
+    options: &RenderOptions,
+) -> RgbaImage {
+    let (output_width, output_height) = (cell_map.width, cell_map.height);
+
+    if let CellStyle::Outline {
+        width,
+        color,
+        background,
+    } = options.style
+    {
+        return paint_outlines(
+            cell_map,
+            &colors,
+            options.merge_threshold.is_some(),
+            width,
+            color,
+            background,
+        );
+    }
+
+    if let CellStyle::Patterned {
+        pattern,
+        spacing,
+        background,
+    } = options.style
+    {
+        return paint_patterns(cell_map, &colors, pattern, spacing, background);
+    }
+
+    if let CellStyle::Stipple { background } = options.style {
+        return paint_stipples(cell_map, &colors, background);
+    }
+
+    let mut output_image_buffer = match options.smoothing {
+        Some(k) if k > 1 => {
+            let (anchors, colors) = match options.projection {
+                Projection::Torus => {
+                    let (copies, owners) =
+                        torus_copies(&anchors, output_width, output_height, 2 * minimum_distance);
+                    let copy_colors = owners.iter().map(|owner| colors[*owner]).collect();
+
+                    (copies, copy_colors)
+                }
+                _ => (anchors, colors.clone()),
+            };
+            let mut output_image_buffer = blend_nearest_cells(
+                &anchors,
+                &colors,
+                output_width,
+                output_height,
+                minimum_distance,
+                options.metric,
+                k,
+            );
+            if let Some(mask) = options.mask {
+                mask.clear_outside(&mut output_image_buffer);
+            }
+
+            output_image_buffer
+        }
+        _ => {
+            let mut output_image_buffer = paint_cells(cell_map, &colors);
+            if let Some(samples) = options.antialias {
+                let sphere = Equirectangular {
+                    width: output_width,
+                    height: output_height,
+                };
+                let torus = Toroidal {
+                    metric: options.metric,
+                    width: output_width,
+                    height: output_height,
+                };
+                let metric: &dyn DistanceMetric = match options.projection {
+                    Projection::Flat => options.metric,
+                    Projection::Equirectangular => &sphere,
+                    Projection::Torus => &torus,
+                };
+                antialias_cells(
+                    &mut output_image_buffer,
+                    cell_map,
+                    &anchors,
+                    &colors,
+                    metric,
+                    samples,
+                );
+            }
+
+            output_image_buffer
+        }
+    };
+    if let CellStyle::StainedGlass { width, color } = options.style {
+        draw_boundaries(
+            &mut output_image_buffer,
+            cell_map,
+            &colors,
+            options.merge_threshold.is_some(),
+            width,
+            color,
+        );
+    }
+
+    output_image_buffer
+}
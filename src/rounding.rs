@@ -0,0 +1,58 @@
+//! Rounded cell boundaries: a majority filter over the label map, which
+//! leaves straight edges between two cells where they are but rounds off the
+//! corners where three meet, for a soft mosaic of pebbles instead of shards.
+
+use crate::render::{map_columns_on_target, CellMap};
+
+/// Gives every pixel of `cell_map` the label most of the pixels within
+/// `radius` of it have, keeping its own on a tie.
+pub fn round_cell_corners(cell_map: &CellMap, radius: f64) -> CellMap {
+    let reach = radius.floor() as i64;
+    let disc: Vec<(i64, i64)> = (-reach..=reach)
+        .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| (((dx * dx) + (dy * dy)) as f64) <= (radius * radius))
+        .collect();
+    let (width, height) = (cell_map.width as i64, cell_map.height as i64);
+
+    let columns = map_columns_on_target(cell_map.width, |x| {
+        let mut votes: Vec<(u32, usize)> = Vec::new();
+        (0..cell_map.height)
+            .map(|y| {
+                votes.clear();
+                for (dx, dy) in &disc {
+                    let (neighbor_x, neighbor_y) = ((x as i64) + dx, (y as i64) + dy);
+                    if (neighbor_x < 0)
+                        || (neighbor_y < 0)
+                        || (neighbor_x >= width)
+                        || (neighbor_y >= height)
+                    {
+                        continue;
+                    }
+                    let label = cell_map.label(neighbor_x as u32, neighbor_y as u32);
+                    match votes.iter_mut().find(|(voted, _)| *voted == label) {
+                        Some((_, count)) => *count += 1,
+                        None => votes.push((label, 1)),
+                    }
+                }
+
+                let own = cell_map.label(x, y);
+                let own_votes = votes
+                    .iter()
+                    .find(|(label, _)| *label == own)
+                    .map_or(0, |(_, count)| *count);
+                votes
+                    .iter()
+                    .filter(|(_, count)| *count > own_votes)
+                    .max_by_key(|(_, count)| *count)
+                    .map_or(own, |(label, _)| *label)
+            })
+            .collect::<Vec<u32>>()
+    });
+
+    let mut rounded = CellMap::new(cell_map.width, cell_map.height);
+    for (x, column_labels) in columns.into_iter().enumerate() {
+        rounded.set_column(x as u32, column_labels);
+    }
+
+    rounded
+}
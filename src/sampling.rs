@@ -0,0 +1,857 @@
+use crate::geometry::{Bounds, Distance, Point};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::worker_threads;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{panic, thread};
+
+/// Random tries at a point of the annulus around an anchor before falling
+/// back to sampling only the part of it inside the bounds.
+const ANNULUS_ATTEMPTS: usize = 32;
+
+fn is_inside(point: &Point, bounds: &Bounds) -> bool {
+    let is_point_in_horizontal_bounds = (point.x > 0f64) && (point.x < (bounds.width as f64));
+    let is_point_in_vertical_bounds = (point.y > 0f64) && (point.y < (bounds.height as f64));
+
+    is_point_in_horizontal_bounds && is_point_in_vertical_bounds
+}
+
+fn point_at(source_point: &Point, angle: f64, distance: f64) -> Point {
+    Point {
+        x: (distance * angle.cos()) + source_point.x,
+        y: (distance * angle.sin()) + source_point.y,
+    }
+}
+
+/// A random point between `distance.minimum` and `distance.maximum` away
+/// from `source_point` and inside `bounds`, or `None` if no part of that
+/// annulus is inside them.
+///
+/// Points are drawn from the whole annulus while few enough land outside;
+/// for anchors in corners of the canvas, where most of the annulus can be
+/// out of bounds, one of the positions of it checked to be inside is picked.
+fn random_point_at_certain_distance_from_given_point(
+    source_point: &Point,
+    distance: &Distance,
+    bounds: &Bounds,
+    rng: &mut dyn RngCore,
+) -> Option<Point> {
+    let spread = distance.maximum.saturating_sub(distance.minimum) as f64;
+    for _ in 0..ANNULUS_ATTEMPTS {
+        let angle = rng.gen::<f64>() * (2f64 * PI);
+        let actual_distance = (distance.minimum as f64) + (rng.gen::<f64>() * spread);
+
+        let point = point_at(source_point, angle, actual_distance);
+        if is_inside(&point, bounds) {
+            return Some(point);
+        }
+    }
+
+    const RINGS: usize = 8;
+    const STEPS: usize = 360;
+    let (ring_width, step) = (spread / (RINGS as f64), (2f64 * PI) / (STEPS as f64));
+    let inside: Vec<(f64, f64)> = (0..RINGS)
+        .flat_map(|ring| {
+            let ring_distance = (distance.minimum as f64) + (((ring as f64) + 0.5f64) * ring_width);
+            (0..STEPS).map(move |index| ((index as f64) * step, ring_distance))
+        })
+        .filter(|(angle, ring_distance)| {
+            is_inside(&point_at(source_point, *angle, *ring_distance), bounds)
+        })
+        .collect();
+    if inside.is_empty() {
+        return None;
+    }
+
+    let (angle, ring_distance) = inside[rng.gen_range(0..inside.len())];
+    let jittered = point_at(
+        source_point,
+        angle + ((rng.gen::<f64>() - 0.5f64) * step),
+        ring_distance + ((rng.gen::<f64>() - 0.5f64) * ring_width),
+    );
+    if is_inside(&jittered, bounds) {
+        Some(jittered)
+    } else {
+        Some(point_at(source_point, angle, ring_distance))
+    }
+}
+
+fn generate_anchor_candidates(
+    source_point: &Point,
+    distance: &Distance,
+    bounds: &Bounds,
+    rng: &mut dyn RngCore,
+) -> Vec<Point> {
+    let mut candidates = Vec::with_capacity(25);
+
+    for _ in 0..25 {
+        match random_point_at_certain_distance_from_given_point(source_point, distance, bounds, rng)
+        {
+            None => break,
+            Some(candidate) => candidates.push(candidate),
+        }
+    }
+
+    candidates
+}
+
+fn poisson_disk_points(bounds: &Bounds, distance: &Distance, rng: &mut dyn RngCore) -> Vec<Point> {
+    let minimum_distance = distance.minimum;
+
+    let squared_minimum_distance = minimum_distance * minimum_distance;
+
+    let mut final_anchors: Vec<Point> = Vec::new();
+    let mut anchor_candidates: VecDeque<Point> = VecDeque::new();
+
+    let mut first_anchor = Point {
+        x: rng.gen::<f64>() * (bounds.width as f64),
+        y: rng.gen::<f64>() * (bounds.height as f64),
+    };
+    let mut first_candidates = generate_anchor_candidates(&first_anchor, distance, bounds, rng);
+    // Around the middle of canvases not much wider than the minimum distance
+    // the whole annulus can be out of bounds while it still reaches in from
+    // the nearest corner.
+    if first_candidates.is_empty() {
+        first_anchor = Point {
+            x: ((first_anchor.x * 2f64) / (bounds.width as f64)).floor() * (bounds.width as f64),
+            y: ((first_anchor.y * 2f64) / (bounds.height as f64)).floor() * (bounds.height as f64),
+        };
+        first_candidates = generate_anchor_candidates(&first_anchor, distance, bounds, rng);
+    }
+
+    final_anchors.push(first_anchor);
+    anchor_candidates.extend(first_candidates);
+
+    while let Some(candidate) = anchor_candidates.pop_front() {
+        let mut is_valid_anchor = true;
+        for anchor in &final_anchors {
+            if anchor.squared_distance_from(&candidate) < (squared_minimum_distance as f64) {
+                is_valid_anchor = false;
+                break;
+            }
+        }
+
+        if is_valid_anchor {
+            final_anchors.push(candidate.clone());
+
+            match final_anchors.last() {
+                None => {}
+                Some(source) => {
+                    anchor_candidates
+                        .extend(generate_anchor_candidates(source, distance, bounds, rng));
+                }
+            }
+        }
+    }
+
+    final_anchors
+}
+
+/// Side of the tiles large canvases are split into for sampling in parallel,
+/// in maximum distances between neighbouring anchors.
+const TILE_DISTANCES: u32 = 16;
+
+/// Anchors bucketed by squares as wide as the minimum distance, so only the
+/// surrounding squares need checking for anchors that are too close.
+struct AnchorGrid {
+    origin: Point,
+    size: f64,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<Point>>,
+}
+
+impl AnchorGrid {
+    fn new(from: Point, to: Point, size: f64) -> AnchorGrid {
+        let columns = (((to.x - from.x) / size).ceil() as usize).max(1);
+        let rows = (((to.y - from.y) / size).ceil() as usize).max(1);
+        AnchorGrid {
+            origin: from,
+            size,
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+        }
+    }
+
+    fn cell_of(&self, point: &Point) -> (usize, usize) {
+        let column = ((point.x - self.origin.x) / self.size).floor().max(0f64) as usize;
+        let row = ((point.y - self.origin.y) / self.size).floor().max(0f64) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    fn insert(&mut self, point: Point) {
+        let (column, row) = self.cell_of(&point);
+        self.cells[(row * self.columns) + column].push(point);
+    }
+
+    fn has_anchor_within(&self, point: &Point, squared_distance: f64) -> bool {
+        let (column, row) = self.cell_of(point);
+        let (columns, rows) = (
+            column.saturating_sub(1)..(column + 2).min(self.columns),
+            row.saturating_sub(1)..(row + 2).min(self.rows),
+        );
+        rows.flat_map(|row| {
+            columns
+                .clone()
+                .map(move |column| (row * self.columns) + column)
+        })
+        .flat_map(|cell| &self.cells[cell])
+        .any(|anchor| anchor.squared_distance_from(point) < squared_distance)
+    }
+}
+
+/// Poisson-disk anchors inside the tile from `from` to `to`, grown from the
+/// `neighbours` already placed in surrounding tiles and from a random first
+/// anchor, and kept away from all of them.
+fn poisson_tile_points(
+    bounds: &Bounds,
+    distance: &Distance,
+    (from, to): (Point, Point),
+    neighbours: &[Point],
+    rng: &mut dyn RngCore,
+) -> Vec<Point> {
+    let minimum_distance = (distance.minimum as f64).max(1f64);
+    let squared_minimum_distance = (distance.minimum as f64) * (distance.minimum as f64);
+    let margin = distance.maximum as f64;
+    let mut grid = AnchorGrid::new(
+        Point {
+            x: from.x - margin,
+            y: from.y - margin,
+        },
+        Point {
+            x: to.x + margin,
+            y: to.y + margin,
+        },
+        minimum_distance,
+    );
+    let is_in_tile = |point: &Point| {
+        (point.x >= from.x) && (point.x < to.x) && (point.y >= from.y) && (point.y < to.y)
+    };
+
+    let mut anchor_candidates: VecDeque<Point> = VecDeque::new();
+    for neighbour in neighbours {
+        grid.insert(neighbour.clone());
+        anchor_candidates.extend(
+            generate_anchor_candidates(neighbour, distance, bounds, rng)
+                .into_iter()
+                .filter(is_in_tile),
+        );
+    }
+
+    let first_anchor = Point {
+        x: from.x + (rng.gen::<f64>() * (to.x - from.x)),
+        y: from.y + (rng.gen::<f64>() * (to.y - from.y)),
+    };
+    anchor_candidates.push_front(first_anchor);
+
+    let mut tile_anchors = Vec::new();
+    while let Some(candidate) = anchor_candidates.pop_front() {
+        if grid.has_anchor_within(&candidate, squared_minimum_distance) {
+            continue;
+        }
+
+        grid.insert(candidate.clone());
+        anchor_candidates.extend(
+            generate_anchor_candidates(&candidate, distance, bounds, rng)
+                .into_iter()
+                .filter(is_in_tile),
+        );
+        tile_anchors.push(candidate);
+    }
+
+    tile_anchors
+}
+
+/// Poisson-disk sampling of large canvases split into tiles sampled on
+/// worker threads.
+///
+/// Tiles run in four phases, each a checkerboard of every other column and
+/// row, so the tiles running at once are at least a tile apart and cannot
+/// place conflicting anchors; later phases grow from and reconcile with the
+/// anchors of the tiles around them. Every tile has its own generator seeded
+/// from `rng` in a fixed order, so the anchors depend only on `rng` and not
+/// on the number of threads.
+fn tiled_poisson_disk_points(
+    bounds: &Bounds,
+    distance: &Distance,
+    tile_size: f64,
+    rng: &mut dyn RngCore,
+) -> Vec<Point> {
+    let columns = ((bounds.width as f64) / tile_size).ceil() as usize;
+    let rows = ((bounds.height as f64) / tile_size).ceil() as usize;
+    let seeds: Vec<u64> = (0..(columns * rows)).map(|_| rng.gen::<u64>()).collect();
+    let mut tiles: Vec<Vec<Point>> = vec![Vec::new(); columns * rows];
+
+    for phase in 0..4 {
+        let jobs: Vec<usize> = (0..(columns * rows))
+            .filter(|tile| {
+                let (column, row) = (tile % columns, tile / columns);
+                ((column % 2), (row % 2)) == ((phase % 2), (phase / 2))
+            })
+            .collect();
+
+        let sample_tile = |tile: usize| {
+            let (column, row) = (tile % columns, tile / columns);
+            let from = Point {
+                x: (column as f64) * tile_size,
+                y: (row as f64) * tile_size,
+            };
+            let to = Point {
+                x: (from.x + tile_size).min(bounds.width as f64),
+                y: (from.y + tile_size).min(bounds.height as f64),
+            };
+            let neighbours: Vec<Point> = (row.saturating_sub(1)..(row + 2).min(rows))
+                .flat_map(|row| {
+                    (column.saturating_sub(1)..(column + 2).min(columns))
+                        .map(move |column| (row * columns) + column)
+                })
+                .flat_map(|neighbour| &tiles[neighbour])
+                .cloned()
+                .collect();
+
+            let mut tile_rng = StdRng::seed_from_u64(seeds[tile]);
+            poisson_tile_points(bounds, distance, (from, to), &neighbours, &mut tile_rng)
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let sampled: Vec<Vec<Point>> = jobs
+            .chunks(worker_threads())
+            .flat_map(|chunk| {
+                thread::scope(|scope| {
+                    let handles: Vec<_> = chunk
+                        .iter()
+                        .map(|tile| scope.spawn(|| sample_tile(*tile)))
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|handle| match handle.join() {
+                            Ok(points) => points,
+                            Err(message) => panic::resume_unwind(message),
+                        })
+                        .collect::<Vec<Vec<Point>>>()
+                })
+            })
+            .collect();
+        #[cfg(target_arch = "wasm32")]
+        let sampled: Vec<Vec<Point>> = jobs.iter().map(|tile| sample_tile(*tile)).collect();
+
+        for (tile, points) in jobs.into_iter().zip(sampled) {
+            tiles[tile] = points;
+        }
+    }
+
+    tiles.into_iter().flatten().collect()
+}
+
+/// Places the initial anchors of a tessellation inside the given bounds.
+pub trait AnchorSampler: Send + Sync {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point>;
+
+    /// Like [`AnchorSampler::sample`], but fails instead of returning
+    /// anchors that cannot be spaced as asked in `bounds`.
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        Ok(self.sample(bounds, rng))
+    }
+}
+
+/// Poisson-disk sampling: every anchor is at least `distance.minimum` away from
+/// all others, and new anchors are proposed within `distance.maximum` of
+/// existing ones.
+pub struct PoissonDiskSampler {
+    pub distance: Distance,
+}
+
+impl AnchorSampler for PoissonDiskSampler {
+    /// Canvases larger than a tile of [`TILE_DISTANCES`] maximum distances
+    /// are sampled tile by tile in parallel.
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        let tile_size = ((self.distance.maximum.max(1) * TILE_DISTANCES) as f64).max(64f64);
+        if ((bounds.width as f64) <= tile_size) && ((bounds.height as f64) <= tile_size) {
+            poisson_disk_points(bounds, &self.distance, rng)
+        } else {
+            tiled_poisson_disk_points(bounds, &self.distance, tile_size, rng)
+        }
+    }
+
+    /// Fails when no two anchors fit `distance.minimum` apart in `bounds`,
+    /// where [`AnchorSampler::sample`] returns a single one.
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        let points = self.sample(bounds, rng);
+        if points.len() == 1 {
+            return Err(format!(
+                "No two anchors fit {} to {} pixels apart inside {}x{} pixels",
+                self.distance.minimum, self.distance.maximum, bounds.width, bounds.height
+            ));
+        }
+
+        Ok(points)
+    }
+}
+
+/// Independent uniformly distributed anchors.
+pub struct UniformRandomSampler {
+    pub count: usize,
+}
+
+impl AnchorSampler for UniformRandomSampler {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        (0..self.count)
+            .map(|_| Point {
+                x: rng.gen::<f64>() * (bounds.width as f64),
+                y: rng.gen::<f64>() * (bounds.height as f64),
+            })
+            .collect()
+    }
+}
+
+/// One anchor per square grid cell, displaced from the cell center by up to
+/// `jitter` (a fraction of the cell size between 0 and 1).
+pub struct JitteredGridSampler {
+    pub spacing: f64,
+    pub jitter: f64,
+}
+
+impl AnchorSampler for JitteredGridSampler {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        let width = bounds.width as f64;
+        let height = bounds.height as f64;
+        let jitter = self.jitter.clamp(0f64, 1f64);
+
+        let columns = (width / self.spacing).ceil() as u64;
+        let rows = (height / self.spacing).ceil() as u64;
+
+        let mut points = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            let top = (row as f64) * self.spacing;
+            let bottom = (top + self.spacing).min(height);
+            for column in 0..columns {
+                let left = (column as f64) * self.spacing;
+                let right = (left + self.spacing).min(width);
+
+                let horizontal_offset = 0.5f64 + ((rng.gen::<f64>() - 0.5f64) * jitter);
+                let vertical_offset = 0.5f64 + ((rng.gen::<f64>() - 0.5f64) * jitter);
+                points.push(Point {
+                    x: left + ((right - left) * horizontal_offset),
+                    y: top + ((bottom - top) * vertical_offset),
+                });
+            }
+        }
+
+        points
+    }
+}
+
+/// A regular hexagonal lattice with `spacing` between neighbouring anchors.
+pub struct HexGridSampler {
+    pub spacing: f64,
+}
+
+impl AnchorSampler for HexGridSampler {
+    fn sample(&self, bounds: &Bounds, _rng: &mut dyn RngCore) -> Vec<Point> {
+        let width = bounds.width as f64;
+        let height = bounds.height as f64;
+        let row_height = self.spacing * 3f64.sqrt() / 2f64;
+
+        let mut points = Vec::new();
+        let mut row = 0u64;
+        loop {
+            let y = ((row as f64) + 0.5f64) * row_height;
+            if y >= height {
+                break;
+            }
+
+            let row_offset = if row.is_multiple_of(2) { 0.5f64 } else { 1f64 };
+            let mut column = 0u64;
+            loop {
+                let x = ((column as f64) + row_offset) * self.spacing;
+                if x >= width {
+                    break;
+                }
+                points.push(Point { x, y });
+                column += 1;
+            }
+
+            row += 1;
+        }
+
+        points
+    }
+}
+
+/// Stamps one toroidal Poisson-disk tile across the bounds, which keeps the
+/// blue-noise spacing of [`PoissonDiskSampler`] at a cost linear in the area
+/// instead of a rejection loop over the whole canvas.
+pub struct BlueNoiseTileSampler {
+    pub minimum_distance: f64,
+    /// Side of the square tile, in multiples of `minimum_distance`.
+    pub tile_cells: u32,
+}
+
+impl BlueNoiseTileSampler {
+    /// Dart throwing on a torus of side `size`, accelerated by a grid of
+    /// cells so small that each holds at most one point.
+    fn tile(&self, size: f64, rng: &mut dyn RngCore) -> Vec<Point> {
+        let minimum_distance = self.minimum_distance;
+        let grid_size = ((size / (minimum_distance / 2f64.sqrt())).floor() as usize).max(1);
+        let cell_size = size / (grid_size as f64);
+        let mut grid: Vec<Option<usize>> = vec![None; grid_size * grid_size];
+        let cell_of = |coordinate: f64| ((coordinate / cell_size) as usize).min(grid_size - 1);
+        let wrapped_distance = |from: f64, to: f64| {
+            let difference = (from - to).abs();
+            difference.min(size - difference)
+        };
+
+        let first = Point {
+            x: rng.gen::<f64>() * size,
+            y: rng.gen::<f64>() * size,
+        };
+        grid[(cell_of(first.y) * grid_size) + cell_of(first.x)] = Some(0);
+        let mut points: Vec<Point> = vec![first];
+        let mut active: Vec<usize> = vec![0];
+
+        let reach = ((minimum_distance / cell_size).ceil() as i64).max(1);
+        while !active.is_empty() {
+            let active_index = rng.gen_range(0..active.len());
+            let source = points[active[active_index]].clone();
+
+            let mut found = None;
+            for _ in 0..30 {
+                let angle = rng.gen::<f64>() * (2f64 * PI);
+                let distance = minimum_distance * (1f64 + rng.gen::<f64>());
+                let candidate = Point {
+                    x: (source.x + (distance * angle.cos())).rem_euclid(size),
+                    y: (source.y + (distance * angle.sin())).rem_euclid(size),
+                };
+
+                let (column, row) = (cell_of(candidate.x) as i64, cell_of(candidate.y) as i64);
+                let is_clear = (-reach..=reach).all(|row_offset| {
+                    (-reach..=reach).all(|column_offset| {
+                        let neighbour_row = (row + row_offset).rem_euclid(grid_size as i64);
+                        let neighbour_column =
+                            (column + column_offset).rem_euclid(grid_size as i64);
+                        match grid
+                            [((neighbour_row as usize) * grid_size) + (neighbour_column as usize)]
+                        {
+                            None => true,
+                            Some(index) => {
+                                let dx = wrapped_distance(points[index].x, candidate.x);
+                                let dy = wrapped_distance(points[index].y, candidate.y);
+                                ((dx * dx) + (dy * dy)) >= (minimum_distance * minimum_distance)
+                            }
+                        }
+                    })
+                });
+                if is_clear {
+                    found = Some(candidate);
+                    break;
+                }
+            }
+
+            match found {
+                Some(candidate) => {
+                    grid[(cell_of(candidate.y) * grid_size) + cell_of(candidate.x)] =
+                        Some(points.len());
+                    active.push(points.len());
+                    points.push(candidate);
+                }
+                None => {
+                    active.swap_remove(active_index);
+                }
+            }
+        }
+
+        points
+    }
+}
+
+impl AnchorSampler for BlueNoiseTileSampler {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        let width = bounds.width as f64;
+        let height = bounds.height as f64;
+        let size = (self.minimum_distance * (self.tile_cells.max(4) as f64))
+            .min(width.max(height))
+            .max(self.minimum_distance * 4f64);
+        let tile = self.tile(size, rng);
+
+        let columns = (width / size).ceil() as u64;
+        let rows = (height / size).ceil() as u64;
+        let mut points = Vec::with_capacity(tile.len() * ((columns * rows) as usize));
+        for row in 0..rows {
+            for column in 0..columns {
+                let (left, top) = ((column as f64) * size, (row as f64) * size);
+                points.extend(
+                    tile.iter()
+                        .map(|point| Point {
+                            x: left + point.x,
+                            y: top + point.y,
+                        })
+                        .filter(|point| (point.x < width) && (point.y < height)),
+                );
+            }
+        }
+
+        points
+    }
+}
+
+/// Poisson-disk sampling where the spacing varies across the bounds, such as
+/// small cells up close and large ones far away with a depth map.
+///
+/// `spacing` holds the distance kept around every pixel of a `width`×`height`
+/// raster, which is stretched over the bounds being sampled.
+pub struct VariablePoissonSampler {
+    pub width: u32,
+    pub height: u32,
+    pub spacing: Vec<f64>,
+}
+
+impl VariablePoissonSampler {
+    fn spacing_at(&self, point: &Point, bounds: &Bounds) -> f64 {
+        let x = ((point.x / (bounds.width as f64)) * (self.width as f64)) as i64;
+        let y = ((point.y / (bounds.height as f64)) * (self.height as f64)) as i64;
+        let x = x.clamp(0, (self.width as i64) - 1) as usize;
+        let y = y.clamp(0, (self.height as i64) - 1) as usize;
+
+        self.spacing[(y * (self.width as usize)) + x]
+    }
+}
+
+impl AnchorSampler for VariablePoissonSampler {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        let (width, height) = (bounds.width as f64, bounds.height as f64);
+        let smallest = self
+            .spacing
+            .iter()
+            .copied()
+            .fold(f64::MAX, f64::min)
+            .max(1f64);
+        let largest = self.spacing.iter().copied().fold(smallest, f64::max);
+
+        // No two points are closer than the smallest spacing, so every grid
+        // cell holds at most one.
+        let cell_size = smallest / 2f64.sqrt();
+        let columns = ((width / cell_size).ceil() as usize).max(1);
+        let rows = ((height / cell_size).ceil() as usize).max(1);
+        let reach = (largest / cell_size).ceil() as i64;
+        let mut grid: Vec<Option<usize>> = vec![None; columns * rows];
+        let cell_of = |point: &Point| {
+            (
+                ((point.x / cell_size) as usize).min(columns - 1),
+                ((point.y / cell_size) as usize).min(rows - 1),
+            )
+        };
+
+        let first = Point {
+            x: rng.gen::<f64>() * width,
+            y: rng.gen::<f64>() * height,
+        };
+        let (column, row) = cell_of(&first);
+        grid[(row * columns) + column] = Some(0);
+        let mut points = vec![first];
+        let mut active = vec![0usize];
+
+        while !active.is_empty() {
+            let active_index = rng.gen_range(0..active.len());
+            let source = points[active[active_index]].clone();
+            let source_spacing = self.spacing_at(&source, bounds);
+
+            let mut found = None;
+            for _ in 0..30 {
+                let angle = rng.gen::<f64>() * (2f64 * PI);
+                let distance = source_spacing * (1f64 + rng.gen::<f64>());
+                let candidate = Point {
+                    x: source.x + (distance * angle.cos()),
+                    y: source.y + (distance * angle.sin()),
+                };
+                if (candidate.x < 0f64)
+                    || (candidate.y < 0f64)
+                    || (candidate.x >= width)
+                    || (candidate.y >= height)
+                {
+                    continue;
+                }
+
+                let candidate_spacing = self.spacing_at(&candidate, bounds);
+                let (column, row) = cell_of(&candidate);
+                let is_clear = (-reach..=reach).all(|row_offset| {
+                    (-reach..=reach).all(|column_offset| {
+                        let (neighbour_column, neighbour_row) =
+                            ((column as i64) + column_offset, (row as i64) + row_offset);
+                        if (neighbour_column < 0)
+                            || (neighbour_row < 0)
+                            || (neighbour_column >= columns as i64)
+                            || (neighbour_row >= rows as i64)
+                        {
+                            return true;
+                        }
+                        match grid
+                            [((neighbour_row as usize) * columns) + (neighbour_column as usize)]
+                        {
+                            None => true,
+                            Some(index) => {
+                                let spacing = (candidate_spacing
+                                    + self.spacing_at(&points[index], bounds))
+                                    / 2f64;
+                                points[index].squared_distance_from(&candidate)
+                                    >= (spacing * spacing)
+                            }
+                        }
+                    })
+                });
+                if is_clear {
+                    found = Some(candidate);
+                    break;
+                }
+            }
+
+            match found {
+                Some(candidate) => {
+                    let (column, row) = cell_of(&candidate);
+                    grid[(row * columns) + column] = Some(points.len());
+                    active.push(points.len());
+                    points.push(candidate);
+                }
+                None => {
+                    active.swap_remove(active_index);
+                }
+            }
+        }
+
+        points
+    }
+}
+
+/// Anchors evenly spaced along the edges of `bounds`, about `spacing` apart
+/// and one in every corner, half of `spacing` in from the edges so that the
+/// cells around them reach out to the edges as wide as they are long.
+pub fn border_points(bounds: &Bounds, spacing: f64) -> Vec<Point> {
+    let (width, height) = (bounds.width as f64, bounds.height as f64);
+    let from = (spacing / 2f64).min(width / 2f64).min(height / 2f64);
+    let (to_x, to_y) = (width - from, height - from);
+    // Positions along one edge from `from` to `to`, both included.
+    let along = |to: f64| {
+        let steps = (((to - from) / spacing).round() as usize).max(1);
+        (0..=steps).map(move |step| from + ((to - from) * (step as f64) / (steps as f64)))
+    };
+
+    let mut points: Vec<Point> = along(to_x)
+        .flat_map(|x| [Point { x, y: from }, Point { x, y: to_y }])
+        .collect();
+    let steps = along(to_y).count();
+    points.extend(
+        along(to_y)
+            .skip(1)
+            .take(steps.saturating_sub(2))
+            .flat_map(|y| [Point { x: from, y }, Point { x: to_x, y }]),
+    );
+
+    points
+}
+
+/// Seeds anchors along the borders and in the corners of the bounds before
+/// `sampler` fills the rest, so cells at the edges are as large as those of
+/// the interior instead of stretching out to the edge from wherever the
+/// nearest anchor happened to land.
+///
+/// Anchors of `sampler` closer than `spacing` to a border anchor are dropped.
+pub struct BorderSeededSampler<'a> {
+    pub sampler: Box<dyn AnchorSampler + 'a>,
+    pub spacing: f64,
+}
+
+impl BorderSeededSampler<'_> {
+    fn seed(&self, bounds: &Bounds, sampled: Vec<Point>) -> Vec<Point> {
+        let border = border_points(bounds, self.spacing);
+        let mut grid = AnchorGrid::new(
+            Point { x: 0f64, y: 0f64 },
+            Point {
+                x: bounds.width as f64,
+                y: bounds.height as f64,
+            },
+            self.spacing.max(1f64),
+        );
+        for point in &border {
+            grid.insert(point.clone());
+        }
+
+        let squared_spacing = self.spacing * self.spacing;
+        let inner: Vec<Point> = sampled
+            .into_iter()
+            .filter(|point| !grid.has_anchor_within(point, squared_spacing))
+            .collect();
+
+        border.into_iter().chain(inner).collect()
+    }
+}
+
+impl AnchorSampler for BorderSeededSampler<'_> {
+    fn sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Vec<Point> {
+        self.seed(bounds, self.sampler.sample(bounds, rng))
+    }
+
+    fn try_sample(&self, bounds: &Bounds, rng: &mut dyn RngCore) -> Result<Vec<Point>, String> {
+        Ok(self.seed(bounds, self.sampler.try_sample(bounds, rng)?))
+    }
+}
+
+pub const SAMPLER_NAMES: [&str; 6] = [
+    "poisson",
+    "uniform",
+    "stratified",
+    "jittered-grid",
+    "hex",
+    "blue-noise",
+];
+
+/// Builds one of the built-in samplers with cells sized around
+/// `minimum_distance`.
+pub fn sampler_from_name(
+    name: &str,
+    bounds: &Bounds,
+    minimum_distance: u32,
+) -> Option<Box<dyn AnchorSampler>> {
+    let spacing = minimum_distance as f64;
+    match name {
+        "poisson" => Some(Box::new(PoissonDiskSampler {
+            distance: Distance {
+                minimum: minimum_distance,
+                maximum: minimum_distance * 2,
+            },
+        })),
+        "uniform" => Some(Box::new(UniformRandomSampler {
+            count: (((bounds.width * bounds.height) as f64) / (spacing * spacing))
+                .round()
+                .max(1f64) as usize,
+        })),
+        // A grid fully jittered places one uniform anchor per cell.
+        "stratified" => Some(Box::new(JitteredGridSampler {
+            spacing,
+            jitter: 1f64,
+        })),
+        "jittered-grid" => Some(Box::new(JitteredGridSampler {
+            spacing,
+            jitter: 0.5f64,
+        })),
+        "hex" => Some(Box::new(HexGridSampler { spacing })),
+        "blue-noise" => Some(Box::new(BlueNoiseTileSampler {
+            minimum_distance: spacing,
+            tile_cells: 32,
+        })),
+        _ => None,
+    }
+}
+
+pub fn generate_anchor_points(bounds: &Bounds, minimum_distance: u32) -> Vec<Point> {
+    let sampler = PoissonDiskSampler {
+        distance: Distance {
+            minimum: minimum_distance,
+            maximum: minimum_distance * 2,
+        },
+    };
+
+    sampler.sample(bounds, &mut rand::thread_rng())
+}
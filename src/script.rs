@@ -0,0 +1,1087 @@
+//! Cell scripts: small programs choosing the fill and the border of every
+//! cell from its anchor, area, color and neighbours, for effects no flag
+//! anticipates.
+//!
+//! Scripts are written in a small language in the manner of Rhai: `let`
+//! bindings, assignments, `if`/`else` and `for i in 0..n` loops over
+//! numbers, booleans, colors like `"#ff8800"` and `//` comments. The type of
+//! every variable is fixed when the script is read, so a script that reads
+//! can only fail halfway through a painting by looping more than
+//! [`MAXIMUM_STEPS`] times for a cell.
+//!
+//! A script reads `x`, `y`, `index`, `area`, `color`, `neighbors`,
+//! `neighbor_mean`, `width` and `height`, and sets `fill`, which starts as
+//! `color`, and `stroke_color` and `stroke_width`, the border drawn inside
+//! the cell, which start as black and `0`.
+
+use crate::anchors::Anchor;
+use crate::merge::neighbouring_cells;
+use crate::palette::parse_hex_color;
+use crate::render::{CellMap, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+use std::sync::Mutex;
+
+/// Loop iterations a script may take for every cell, nested loops counting
+/// every iteration of the inner one.
+pub const MAXIMUM_STEPS: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    Number,
+    Boolean,
+    Color,
+}
+
+impl Type {
+    fn name(self) -> &'static str {
+        match self {
+            Type::Number => "a number",
+            Type::Boolean => "a boolean",
+            Type::Color => "a color",
+        }
+    }
+}
+
+/// A value of a script, colors being channels from 0 to 255.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+    Number(f64),
+    Boolean(bool),
+    Color([f64; 4]),
+}
+
+impl Value {
+    fn number(self) -> f64 {
+        match self {
+            Value::Number(number) => number,
+            _ => f64::NAN,
+        }
+    }
+
+    fn color(self) -> [f64; 4] {
+        match self {
+            Value::Color(color) => color,
+            _ => [0f64; 4],
+        }
+    }
+
+    fn from_pixel(pixel: Rgba<u8>) -> Value {
+        Value::Color(pixel.0.map(|channel| channel as f64))
+    }
+
+    fn to_pixel(self) -> Rgba<u8> {
+        Rgba(
+            self.color()
+                .map(|channel| channel.clamp(0f64, 255f64).round() as u8),
+        )
+    }
+}
+
+/// The red, green and blue channels of `color` mapped by `map`, keeping
+/// its alpha.
+fn map_channels(color: [f64; 4], map: impl Fn(usize, f64) -> f64) -> Value {
+    Value::Color([
+        map(0, color[0]),
+        map(1, color[1]),
+        map(2, color[2]),
+        color[3],
+    ])
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl Operator {
+    fn from_symbol(symbol: &str) -> Option<Operator> {
+        Some(match symbol {
+            "+" => Operator::Add,
+            "-" => Operator::Subtract,
+            "*" => Operator::Multiply,
+            "/" => Operator::Divide,
+            "%" => Operator::Remainder,
+            "<" => Operator::Less,
+            "<=" => Operator::LessEqual,
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterEqual,
+            "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            "&&" => Operator::And,
+            "||" => Operator::Or,
+            _ => return None,
+        })
+    }
+
+    /// The type of the operation on `left` and `right`, if they can be
+    /// combined.
+    fn result_type(self, left: Type, right: Type) -> Option<Type> {
+        use Type::{Boolean, Color, Number};
+        match (self, left, right) {
+            (Operator::Add | Operator::Subtract, Number, Number) => Some(Number),
+            (Operator::Add | Operator::Subtract, Color, Color) => Some(Color),
+            (Operator::Multiply, Number, Number) => Some(Number),
+            (Operator::Multiply, Color, Number | Color) | (Operator::Multiply, Number, Color) => {
+                Some(Color)
+            }
+            (Operator::Divide, Number, Number) | (Operator::Remainder, Number, Number) => {
+                Some(Number)
+            }
+            (Operator::Divide, Color, Number) => Some(Color),
+            (
+                Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual,
+                Number,
+                Number,
+            ) => Some(Boolean),
+            (Operator::Equal | Operator::NotEqual, left, right) if left == right => Some(Boolean),
+            (Operator::And | Operator::Or, Boolean, Boolean) => Some(Boolean),
+            _ => None,
+        }
+    }
+
+    fn apply(self, left: Value, right: Value) -> Value {
+        use Value::{Boolean, Color, Number};
+        match (self, left, right) {
+            (Operator::Add, Number(left), Number(right)) => Number(left + right),
+            (Operator::Add, Color(left), Color(right)) => {
+                map_channels(left, |index, channel| channel + right[index])
+            }
+            (Operator::Subtract, Number(left), Number(right)) => Number(left - right),
+            (Operator::Subtract, Color(left), Color(right)) => {
+                map_channels(left, |index, channel| channel - right[index])
+            }
+            (Operator::Multiply, Number(left), Number(right)) => Number(left * right),
+            (Operator::Multiply, Color(color), Number(factor))
+            | (Operator::Multiply, Number(factor), Color(color)) => {
+                map_channels(color, |_, channel| channel * factor)
+            }
+            // Multiplying two colors darkens one by the other, as a multiply
+            // blend does.
+            (Operator::Multiply, Color(left), Color(right)) => {
+                map_channels(left, |index, channel| channel * right[index] / 255f64)
+            }
+            (Operator::Divide, Number(left), Number(right)) => Number(left / right),
+            (Operator::Divide, Color(color), Number(divisor)) => {
+                map_channels(color, |_, channel| channel / divisor)
+            }
+            (Operator::Remainder, Number(left), Number(right)) => Number(left.rem_euclid(right)),
+            (Operator::Less, Number(left), Number(right)) => Boolean(left < right),
+            (Operator::LessEqual, Number(left), Number(right)) => Boolean(left <= right),
+            (Operator::Greater, Number(left), Number(right)) => Boolean(left > right),
+            (Operator::GreaterEqual, Number(left), Number(right)) => Boolean(left >= right),
+            (Operator::Equal, left, right) => Boolean(left == right),
+            (Operator::NotEqual, left, right) => Boolean(left != right),
+            (Operator::And, Boolean(left), Boolean(right)) => Boolean(left && right),
+            (Operator::Or, Boolean(left), Boolean(right)) => Boolean(left || right),
+            _ => Number(f64::NAN),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Function {
+    Rgb,
+    Rgba,
+    Gray,
+    MixColors,
+    MixNumbers,
+    Min,
+    Max,
+    Abs,
+    Floor,
+    Round,
+    Sqrt,
+    Sin,
+    Cos,
+    Pow,
+    Clamp,
+    Lightness,
+    Difference,
+    Random,
+    Neighbor,
+    NeighborArea,
+}
+
+/// Every function with the types of its parameters and of its result. A
+/// name may be listed for several parameter types.
+const FUNCTIONS: &[(&str, Function, &[Type], Type)] = {
+    use Type::{Color, Number};
+    &[
+        ("rgb", Function::Rgb, &[Number, Number, Number], Color),
+        (
+            "rgba",
+            Function::Rgba,
+            &[Number, Number, Number, Number],
+            Color,
+        ),
+        ("gray", Function::Gray, &[Number], Color),
+        ("mix", Function::MixColors, &[Color, Color, Number], Color),
+        (
+            "mix",
+            Function::MixNumbers,
+            &[Number, Number, Number],
+            Number,
+        ),
+        ("min", Function::Min, &[Number, Number], Number),
+        ("max", Function::Max, &[Number, Number], Number),
+        ("abs", Function::Abs, &[Number], Number),
+        ("floor", Function::Floor, &[Number], Number),
+        ("round", Function::Round, &[Number], Number),
+        ("sqrt", Function::Sqrt, &[Number], Number),
+        ("sin", Function::Sin, &[Number], Number),
+        ("cos", Function::Cos, &[Number], Number),
+        ("pow", Function::Pow, &[Number, Number], Number),
+        ("clamp", Function::Clamp, &[Number, Number, Number], Number),
+        ("lightness", Function::Lightness, &[Color], Number),
+        ("difference", Function::Difference, &[Color, Color], Number),
+        ("random", Function::Random, &[Number], Number),
+        ("neighbor", Function::Neighbor, &[Number], Color),
+        ("neighbor_area", Function::NeighborArea, &[Number], Number),
+    ]
+};
+
+#[derive(Debug)]
+enum Expression {
+    Constant(Value),
+    Variable(usize),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+    Channel(Box<Expression>, usize),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Assign(usize, Expression),
+    If(Expression, Vec<Statement>, Vec<Statement>),
+    For(usize, Expression, Expression, Vec<Statement>),
+}
+
+const FILL: usize = 0;
+const STROKE_COLOR: usize = 1;
+const STROKE_WIDTH: usize = 2;
+const X: usize = 3;
+const Y: usize = 4;
+const INDEX: usize = 5;
+const AREA: usize = 6;
+const COLOR: usize = 7;
+const NEIGHBORS: usize = 8;
+const NEIGHBOR_MEAN: usize = 9;
+const WIDTH: usize = 10;
+const HEIGHT: usize = 11;
+
+/// The variables every script starts with, in the order of their slots,
+/// and whether it may set them.
+const VARIABLES: [(&str, Type, bool); 12] = [
+    ("fill", Type::Color, true),
+    ("stroke_color", Type::Color, true),
+    ("stroke_width", Type::Number, true),
+    ("x", Type::Number, false),
+    ("y", Type::Number, false),
+    ("index", Type::Number, false),
+    ("area", Type::Number, false),
+    ("color", Type::Color, false),
+    ("neighbors", Type::Number, false),
+    ("neighbor_mean", Type::Color, false),
+    ("width", Type::Number, false),
+    ("height", Type::Number, false),
+];
+
+const KEYWORDS: [&str; 7] = ["let", "if", "else", "for", "in", "true", "false"];
+
+/// Symbols, longer ones first so they are not read as their prefixes.
+const SYMBOLS: [&str; 23] = [
+    "..", "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "=", "(",
+    ")", "{", "}", ",", ";", ".",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Text(String),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(number) => format!("`{}`", number),
+            Token::Name(name) => format!("`{}`", name),
+            Token::Text(text) => format!("`\"{}\"`", text),
+            Token::Symbol(symbol) => format!("`{}`", symbol),
+        }
+    }
+}
+
+/// Splits `source` into tokens, each with the line it is on.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let number = line_index + 1;
+        let mut rest = line;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with("//") {
+                break;
+            }
+
+            let first = rest.chars().next().unwrap_or_default();
+            if first.is_ascii_digit() {
+                let mut end = rest
+                    .find(|character: char| !character.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                // A point only continues the number if digits follow, so
+                // `0..n` is a range.
+                if rest[end..].starts_with('.')
+                    && rest[(end + 1)..].starts_with(|character: char| character.is_ascii_digit())
+                {
+                    end += 1 + rest[(end + 1)..]
+                        .find(|character: char| !character.is_ascii_digit())
+                        .unwrap_or(rest.len() - end - 1);
+                }
+                let value = rest[..end]
+                    .parse::<f64>()
+                    .map_err(|_| format!("line {}: `{}` is not a number", number, &rest[..end]))?;
+                tokens.push((Token::Number(value), number));
+                rest = &rest[end..];
+            } else if first.is_ascii_alphabetic() || first == '_' {
+                let end = rest
+                    .find(|character: char| {
+                        !(character.is_ascii_alphanumeric() || character == '_')
+                    })
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Name(rest[..end].to_string()), number));
+                rest = &rest[end..];
+            } else if first == '"' {
+                let end = rest[1..]
+                    .find('"')
+                    .ok_or_else(|| format!("line {}: the string is not closed", number))?;
+                tokens.push((Token::Text(rest[1..(end + 1)].to_string()), number));
+                rest = &rest[(end + 2)..];
+            } else {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| rest.starts_with(*symbol))
+                    .ok_or_else(|| format!("line {}: unexpected `{}`", number, first))?;
+                tokens.push((Token::Symbol(symbol), number));
+                rest = &rest[symbol.len()..];
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// The names visible in every block, innermost last, with their slots.
+    scopes: Vec<Vec<(String, usize)>>,
+    /// The type of every slot and whether the script may set it.
+    slots: Vec<(Type, bool)>,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or_else(|| self.tokens.last())
+            .map(|(_, line)| *line)
+            .unwrap_or(1)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line(), message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+
+        token
+    }
+
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        let is_next = self.peek() == Some(&Token::Symbol(symbol)) || self.is_keyword(symbol);
+        if is_next {
+            self.position += 1;
+        }
+
+        is_next
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(name)) if name == keyword)
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+
+        Err(match self.peek() {
+            Some(token) => self.error(&format!(
+                "expected `{}`, found {}",
+                symbol,
+                token.describe()
+            )),
+            None => self.error(&format!("expected `{}` at the end of the script", symbol)),
+        })
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Name(name)) if !KEYWORDS.contains(&name.as_str()) => Ok(name),
+            Some(token) => {
+                self.position -= 1;
+                Err(self.error(&format!("expected a name, found {}", token.describe())))
+            }
+            None => Err(self.error("expected a name at the end of the script")),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(variable, _)| variable == name)
+            .map(|(_, slot)| *slot)
+    }
+
+    fn declare(&mut self, name: String, kind: Type, is_writable: bool) -> usize {
+        self.slots.push((kind, is_writable));
+        let slot = self.slots.len() - 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name, slot));
+        }
+
+        slot
+    }
+
+    fn statements(&mut self, is_block: bool) -> Result<Vec<Statement>, String> {
+        let mut statements = Vec::new();
+        loop {
+            match self.peek() {
+                None if is_block => return Err(self.error("expected `}` at the end of the script")),
+                None => return Ok(statements),
+                Some(Token::Symbol("}")) if is_block => {
+                    self.position += 1;
+                    return Ok(statements);
+                }
+                _ => statements.push(self.statement()?),
+            }
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, String> {
+        self.expect("{")?;
+        self.scopes.push(Vec::new());
+        let statements = self.statements(true);
+        self.scopes.pop();
+
+        statements
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.eat("let") {
+            let name = self.name()?;
+            self.expect("=")?;
+            let (value, kind) = self.expression()?;
+            self.expect(";")?;
+            let slot = self.declare(name, kind, true);
+
+            return Ok(Statement::Assign(slot, value));
+        }
+
+        if self.eat("if") {
+            return self.if_statement();
+        }
+
+        if self.eat("for") {
+            let name = self.name()?;
+            self.expect("in")?;
+            let start = self.typed_expression(Type::Number, "the start of a range")?;
+            self.expect("..")?;
+            let end = self.typed_expression(Type::Number, "the end of a range")?;
+            if is_constant(&start) && is_constant(&end) {
+                let count = iterations(&start, &end, &[], &[]);
+                if count > (MAXIMUM_STEPS as f64) {
+                    return Err(self.error(&format!(
+                        "the range runs {} times, more than the {} a cell may loop",
+                        count, MAXIMUM_STEPS
+                    )));
+                }
+            }
+            self.scopes.push(Vec::new());
+            let counter = self.declare(name, Type::Number, false);
+            let body = self.block();
+            self.scopes.pop();
+
+            return Ok(Statement::For(counter, start, end, body?));
+        }
+
+        let name = self.name()?;
+        let slot = self
+            .lookup(&name)
+            .ok_or_else(|| self.error(&format!("unknown variable `{}`", name)))?;
+        let (kind, is_writable) = self.slots[slot];
+        if !is_writable {
+            return Err(self.error(&format!("`{}` cannot be set", name)));
+        }
+        self.expect("=")?;
+        let value = self.typed_expression(kind, &format!("`{}`", name))?;
+        self.expect(";")?;
+
+        Ok(Statement::Assign(slot, value))
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, String> {
+        let condition = self.typed_expression(Type::Boolean, "a condition")?;
+        let then = self.block()?;
+        let otherwise = if !self.eat("else") {
+            Vec::new()
+        } else if self.eat("if") {
+            vec![self.if_statement()?]
+        } else {
+            self.block()?
+        };
+
+        Ok(Statement::If(condition, then, otherwise))
+    }
+
+    /// An expression that must be of type `kind`, `what` naming it in the
+    /// error otherwise.
+    fn typed_expression(&mut self, kind: Type, what: &str) -> Result<Expression, String> {
+        let line = self.line();
+        let (expression, found) = self.expression()?;
+        if found != kind {
+            return Err(format!(
+                "line {}: {} must be {}, not {}",
+                line,
+                what,
+                kind.name(),
+                found.name()
+            ));
+        }
+
+        Ok(expression)
+    }
+
+    fn expression(&mut self) -> Result<(Expression, Type), String> {
+        self.binary(0)
+    }
+
+    /// Operators by how tightly they bind, loosest first.
+    const PRECEDENCE: [&'static [&'static str]; 5] = [
+        &["||"],
+        &["&&"],
+        &["==", "!=", "<", "<=", ">", ">="],
+        &["+", "-"],
+        &["*", "/", "%"],
+    ];
+
+    fn binary(&mut self, level: usize) -> Result<(Expression, Type), String> {
+        if level == Self::PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let (mut left, mut left_type) = self.binary(level + 1)?;
+        while let Some(Token::Symbol(symbol)) = self.peek().cloned() {
+            if !Self::PRECEDENCE[level].contains(&symbol) {
+                break;
+            }
+            let operator = Operator::from_symbol(symbol).unwrap_or(Operator::Add);
+            let line = self.line();
+            self.position += 1;
+            let (right, right_type) = self.binary(level + 1)?;
+            left_type = operator.result_type(left_type, right_type).ok_or_else(|| {
+                format!(
+                    "line {}: `{}` cannot combine {} and {}",
+                    line,
+                    symbol,
+                    left_type.name(),
+                    right_type.name()
+                )
+            })?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        Ok((left, left_type))
+    }
+
+    fn unary(&mut self) -> Result<(Expression, Type), String> {
+        if self.eat("-") {
+            let operand = self.typed_expression(Type::Number, "a negated value")?;
+            return Ok((Expression::Negate(Box::new(operand)), Type::Number));
+        }
+        if self.eat("!") {
+            let operand = self.typed_expression(Type::Boolean, "a negated value")?;
+            return Ok((Expression::Not(Box::new(operand)), Type::Boolean));
+        }
+
+        let (mut expression, mut kind) = self.primary()?;
+        while self.eat(".") {
+            let channel = match self.name()?.as_str() {
+                "r" => 0,
+                "g" => 1,
+                "b" => 2,
+                "a" => 3,
+                name => return Err(self.error(&format!("colors have no channel `{}`", name))),
+            };
+            if kind != Type::Color {
+                return Err(self.error(&format!("{} has no channels", kind.name())));
+            }
+            expression = Expression::Channel(Box::new(expression), channel);
+            kind = Type::Number;
+        }
+
+        Ok((expression, kind))
+    }
+
+    fn primary(&mut self) -> Result<(Expression, Type), String> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Number(number)) => {
+                Ok((Expression::Constant(Value::Number(number)), Type::Number))
+            }
+            Some(Token::Text(text)) => match parse_hex_color(&text) {
+                Some(color) => Ok((Expression::Constant(Value::from_pixel(color)), Type::Color)),
+                None => Err(format!("line {}: `\"{}\"` is not a hex color", line, text)),
+            },
+            Some(Token::Symbol("(")) => {
+                let expression = self.expression()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Some(Token::Name(name)) if (name == "true") || (name == "false") => Ok((
+                Expression::Constant(Value::Boolean(name == "true")),
+                Type::Boolean,
+            )),
+            Some(Token::Name(name)) if self.eat("(") => self.call(&name),
+            Some(Token::Name(name)) if !KEYWORDS.contains(&name.as_str()) => {
+                let slot = self
+                    .lookup(&name)
+                    .ok_or_else(|| format!("line {}: unknown variable `{}`", line, name))?;
+                Ok((Expression::Variable(slot), self.slots[slot].0))
+            }
+            Some(token) => Err(format!(
+                "line {}: expected a value, found {}",
+                line,
+                token.describe()
+            )),
+            None => Err(self.error("expected a value at the end of the script")),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<(Expression, Type), String> {
+        let line = self.line();
+        let mut arguments = Vec::new();
+        let mut types = Vec::new();
+        if !self.eat(")") {
+            loop {
+                let (argument, kind) = self.expression()?;
+                arguments.push(argument);
+                types.push(kind);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        let mut overloads = FUNCTIONS
+            .iter()
+            .filter(|(function_name, ..)| *function_name == name)
+            .peekable();
+        if overloads.peek().is_none() {
+            return Err(format!("line {}: unknown function `{}`", line, name));
+        }
+        let (_, function, _, result) = overloads
+            .find(|(_, _, parameters, _)| *parameters == types.as_slice())
+            .ok_or_else(|| {
+                let types: Vec<&str> = types.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "line {}: `{}` cannot be called with {}",
+                    line,
+                    name,
+                    match types.is_empty() {
+                        true => String::from("nothing"),
+                        false => types.join(", "),
+                    }
+                )
+            })?;
+
+        Ok((Expression::Call(*function, arguments), *result))
+    }
+}
+
+/// What a script knows of the neighbours of the cell it runs for.
+struct Neighbor {
+    color: [f64; 4],
+    area: f64,
+}
+
+fn evaluate(expression: &Expression, slots: &[Value], neighbors: &[Neighbor]) -> Value {
+    match expression {
+        Expression::Constant(value) => *value,
+        Expression::Variable(slot) => slots[*slot],
+        Expression::Negate(operand) => Value::Number(-evaluate(operand, slots, neighbors).number()),
+        Expression::Not(operand) => match evaluate(operand, slots, neighbors) {
+            Value::Boolean(value) => Value::Boolean(!value),
+            value => value,
+        },
+        // `&&` and `||` only evaluate their right side if it decides them.
+        Expression::Binary(operator @ (Operator::And | Operator::Or), left, right) => {
+            match (operator, evaluate(left, slots, neighbors)) {
+                (Operator::And, Value::Boolean(false)) => Value::Boolean(false),
+                (Operator::Or, Value::Boolean(true)) => Value::Boolean(true),
+                (_, _) => evaluate(right, slots, neighbors),
+            }
+        }
+        Expression::Binary(operator, left, right) => operator.apply(
+            evaluate(left, slots, neighbors),
+            evaluate(right, slots, neighbors),
+        ),
+        Expression::Channel(color, channel) => {
+            Value::Number(evaluate(color, slots, neighbors).color()[*channel])
+        }
+        Expression::Call(function, arguments) => {
+            let values: Vec<Value> = arguments
+                .iter()
+                .map(|argument| evaluate(argument, slots, neighbors))
+                .collect();
+            call(*function, &values, slots, neighbors)
+        }
+    }
+}
+
+fn call(function: Function, arguments: &[Value], slots: &[Value], neighbors: &[Neighbor]) -> Value {
+    let number = |index: usize| {
+        arguments
+            .get(index)
+            .map_or(f64::NAN, |value| value.number())
+    };
+    let color = |index: usize| {
+        arguments
+            .get(index)
+            .map_or([0f64; 4], |value| value.color())
+    };
+    // The neighbour at a number argument, counting from 0.
+    let neighbor = || {
+        let index = number(0).floor();
+        (index >= 0f64)
+            .then(|| neighbors.get(index as usize))
+            .flatten()
+    };
+    match function {
+        Function::Rgb => Value::Color([number(0), number(1), number(2), 255f64]),
+        Function::Rgba => Value::Color([number(0), number(1), number(2), number(3)]),
+        Function::Gray => Value::Color([number(0), number(0), number(0), 255f64]),
+        Function::MixColors => {
+            let (from, to, amount) = (color(0), color(1), number(2));
+            Value::Color(
+                [0, 1, 2, 3].map(|index| from[index] + ((to[index] - from[index]) * amount)),
+            )
+        }
+        Function::MixNumbers => Value::Number(number(0) + ((number(1) - number(0)) * number(2))),
+        Function::Min => Value::Number(number(0).min(number(1))),
+        Function::Max => Value::Number(number(0).max(number(1))),
+        Function::Abs => Value::Number(number(0).abs()),
+        Function::Floor => Value::Number(number(0).floor()),
+        Function::Round => Value::Number(number(0).round()),
+        Function::Sqrt => Value::Number(number(0).sqrt()),
+        Function::Sin => Value::Number(number(0).sin()),
+        Function::Cos => Value::Number(number(0).cos()),
+        Function::Pow => Value::Number(number(0).powf(number(1))),
+        Function::Clamp => Value::Number(number(0).max(number(1)).min(number(2))),
+        Function::Lightness => {
+            let [red, green, blue, _] = color(0);
+            Value::Number(((0.2126f64 * red) + (0.7152f64 * green) + (0.0722f64 * blue)) / 255f64)
+        }
+        Function::Difference => {
+            let (left, right) = (color(0), color(1));
+            Value::Number(
+                (0..3)
+                    .map(|index| (left[index] - right[index]).powi(2))
+                    .sum::<f64>()
+                    .sqrt(),
+            )
+        }
+        Function::Random => {
+            // SplitMix64 of the number, so the same cell always draws the
+            // same value.
+            let mut state = number(0).to_bits().wrapping_add(0x9E3779B97F4A7C15);
+            state = (state ^ (state >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            state = (state ^ (state >> 27)).wrapping_mul(0x94D049BB133111EB);
+            state ^= state >> 31;
+            Value::Number(((state >> 11) as f64) / ((1u64 << 53) as f64))
+        }
+        Function::Neighbor => match neighbor() {
+            Some(neighbor) => Value::Color(neighbor.color),
+            None => slots[COLOR],
+        },
+        Function::NeighborArea => Value::Number(neighbor().map_or(0f64, |neighbor| neighbor.area)),
+    }
+}
+
+/// Whether `expression` comes out the same for every cell.
+fn is_constant(expression: &Expression) -> bool {
+    match expression {
+        Expression::Constant(_) => true,
+        Expression::Variable(_) => false,
+        Expression::Negate(operand)
+        | Expression::Not(operand)
+        | Expression::Channel(operand, _) => is_constant(operand),
+        Expression::Binary(_, left, right) => is_constant(left) && is_constant(right),
+        Expression::Call(Function::Neighbor | Function::NeighborArea, _) => false,
+        Expression::Call(_, arguments) => arguments.iter().all(is_constant),
+    }
+}
+
+/// How many times a loop over `start..end` runs, counted rather than
+/// stepped, as adding one to numbers past 2^53 leaves them the same.
+fn iterations(
+    start: &Expression,
+    end: &Expression,
+    slots: &[Value],
+    neighbors: &[Neighbor],
+) -> f64 {
+    let start = evaluate(start, slots, neighbors).number().ceil();
+    let end = evaluate(end, slots, neighbors).number();
+
+    (end - start).ceil()
+}
+
+/// Runs `statements`, taking a step from `steps` for every loop iteration
+/// and failing once there are none left.
+fn execute(
+    statements: &[Statement],
+    slots: &mut [Value],
+    neighbors: &[Neighbor],
+    steps: &mut usize,
+) -> Result<(), String> {
+    for statement in statements {
+        match statement {
+            Statement::Assign(slot, value) => slots[*slot] = evaluate(value, slots, neighbors),
+            Statement::If(condition, then, otherwise) => {
+                match evaluate(condition, slots, neighbors) {
+                    Value::Boolean(true) => execute(then, slots, neighbors, steps)?,
+                    _ => execute(otherwise, slots, neighbors, steps)?,
+                }
+            }
+            Statement::For(counter, start, end, body) => {
+                let count = iterations(start, end, slots, neighbors);
+                let start = evaluate(start, slots, neighbors).number().ceil();
+                if count > (*steps as f64) {
+                    return Err(format!(
+                        "loops ran more than {} times for one cell",
+                        MAXIMUM_STEPS
+                    ));
+                }
+                for step in 0..(count.max(0f64) as usize) {
+                    *steps -= 1;
+                    slots[*counter] = Value::Number(start + (step as f64));
+                    execute(body, slots, neighbors, steps)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The border a script gave a cell: a stroke `width` pixels wide inside
+/// its edges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellBorder {
+    pub width: f64,
+    pub color: Rgba<u8>,
+}
+
+/// The border of the cells a script stopped before.
+const NO_BORDER: CellBorder = CellBorder {
+    width: 0f64,
+    color: Rgba([0, 0, 0, 255]),
+};
+
+/// A script read and checked, ready to run for every cell.
+#[derive(Debug)]
+pub struct CellScript {
+    statements: Vec<Statement>,
+    slot_count: usize,
+    /// Why the script stopped in a painting, if it did.
+    failure: Mutex<Option<String>>,
+}
+
+impl CellScript {
+    pub fn parse(source: &str) -> Result<CellScript, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            scopes: vec![Vec::new()],
+            slots: Vec::new(),
+        };
+        for (name, kind, is_writable) in VARIABLES {
+            parser.declare(name.to_string(), kind, is_writable);
+        }
+        let statements = parser.statements(false)?;
+
+        Ok(CellScript {
+            statements,
+            slot_count: parser.slots.len(),
+            failure: Mutex::new(None),
+        })
+    }
+
+    /// Why the script stopped in a painting, if it did since it was read.
+    pub fn failure(&self) -> Option<String> {
+        self.failure
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Runs the script for every cell of `cell_map`, replacing `colors` with
+    /// the fills it sets and returning the borders. A cell whose loops run
+    /// out of [`MAXIMUM_STEPS`] keeps its color and gets no border, and the
+    /// script stops there, leaving the rest as they are and the reason in
+    /// [`CellScript::failure`].
+    pub fn run(
+        &self,
+        cell_map: &CellMap,
+        anchors: &[Anchor],
+        colors: &mut [Rgba<u8>],
+    ) -> Vec<CellBorder> {
+        let areas: Vec<f64> = cell_map
+            .cell_pixels(anchors.len())
+            .iter()
+            .map(|pixels| pixels.len() as f64)
+            .collect();
+        let mut neighbors = vec![Vec::new(); anchors.len()];
+        for (first, second) in neighbouring_cells(cell_map) {
+            neighbors[first as usize].push(second as usize);
+            neighbors[second as usize].push(first as usize);
+        }
+
+        let mut slots = vec![Value::Number(0f64); self.slot_count];
+        let mut borders = Vec::with_capacity(anchors.len());
+        let mut stopped = false;
+        for (index, anchor) in anchors.iter().enumerate() {
+            if stopped {
+                borders.push(NO_BORDER);
+                continue;
+            }
+            let cell_neighbors = &mut neighbors[index];
+            cell_neighbors.sort_unstable();
+            let cell_neighbors: Vec<Neighbor> = cell_neighbors
+                .iter()
+                .map(|neighbor| Neighbor {
+                    color: colors[*neighbor].0.map(|channel| channel as f64),
+                    area: areas[*neighbor],
+                })
+                .collect();
+            let color = Value::from_pixel(colors[index]);
+            let neighbor_mean = match cell_neighbors.len() {
+                0 => color,
+                count => {
+                    let mut sums = [0f64; 4];
+                    for neighbor in &cell_neighbors {
+                        for (sum, channel) in sums.iter_mut().zip(neighbor.color) {
+                            *sum += channel;
+                        }
+                    }
+                    Value::Color(sums.map(|sum| sum / (count as f64)))
+                }
+            };
+
+            slots[FILL] = color;
+            slots[STROKE_COLOR] = Value::Color([0f64, 0f64, 0f64, 255f64]);
+            slots[STROKE_WIDTH] = Value::Number(0f64);
+            slots[X] = Value::Number(anchor.point.x);
+            slots[Y] = Value::Number(anchor.point.y);
+            slots[INDEX] = Value::Number(index as f64);
+            slots[AREA] = Value::Number(areas[index]);
+            slots[COLOR] = color;
+            slots[NEIGHBORS] = Value::Number(cell_neighbors.len() as f64);
+            slots[NEIGHBOR_MEAN] = neighbor_mean;
+            slots[WIDTH] = Value::Number(cell_map.width as f64);
+            slots[HEIGHT] = Value::Number(cell_map.height as f64);
+            let mut steps = MAXIMUM_STEPS;
+            if let Err(message) = execute(&self.statements, &mut slots, &cell_neighbors, &mut steps)
+            {
+                *self
+                    .failure
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+                    Some(format!("cell {}: {}", index, message));
+                stopped = true;
+                borders.push(NO_BORDER);
+                continue;
+            }
+
+            colors[index] = slots[FILL].to_pixel();
+            borders.push(CellBorder {
+                width: slots[STROKE_WIDTH].number(),
+                color: slots[STROKE_COLOR].to_pixel(),
+            });
+        }
+
+        borders
+    }
+}
+
+/// Strokes the border of every cell onto `image`, inside the cell along its
+/// edges with other cells.
+pub fn draw_cell_borders(image: &mut RgbaImage, cell_map: &CellMap, borders: &[CellBorder]) {
+    let (width, height) = (cell_map.width as i64, cell_map.height as i64);
+    let label_at = |x: i64, y: i64| {
+        ((0..width).contains(&x) && (0..height).contains(&y))
+            .then(|| cell_map.label(x as u32, y as u32))
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let label = cell_map.label(x as u32, y as u32);
+            let border = match borders.get(label as usize) {
+                Some(border) if (label != UNASSIGNED) && (border.width > 0f64) => border,
+                _ => continue,
+            };
+            let is_edge = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .any(|(dx, dy)| label_at(x + dx, y + dy).is_some_and(|other| other != label));
+            if !is_edge {
+                continue;
+            }
+
+            let radius = border.width - 1f64;
+            let reach = radius.max(0f64).ceil() as i64;
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    let is_within = (((dx * dx) + (dy * dy)) as f64) <= (radius * radius) + 0.5f64;
+                    if is_within && (label_at(x + dx, y + dy) == Some(label)) {
+                        image.put_pixel((x + dx) as u32, (y + dy) as u32, border.color);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! A self test of the build: synthetic inputs painted with fixed seeds and
+//! compared by hash with what a known good build painted of them, so a user
+//! can check their build draws the cells it should on their machine.
+
+use crate::anchors::color_anchor_points;
+use crate::cache::image_hash;
+use crate::colorize::MeanColorizer;
+use crate::geometry::{Bounds, Distance, Manhattan};
+use crate::projection::Projection;
+use crate::render::{render_voronoi, Assignment, CellStyle, RenderOptions};
+use crate::sampling::{AnchorSampler, JitteredGridSampler, PoissonDiskSampler};
+use image::{Rgba, RgbaImage};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 96;
+const MINIMUM_DISTANCE: u32 = 8;
+
+/// A painting the self test makes, and the hash a known good build's
+/// painting of it has.
+struct Case {
+    name: &'static str,
+    golden: u64,
+    paint: fn() -> RgbaImage,
+}
+
+/// How one painting of the self test came out.
+pub struct Outcome {
+    pub name: &'static str,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// Hue and lightness sweeping across the canvas, so every cell is colored
+/// differently.
+fn gradient() -> RgbaImage {
+    RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgba([
+            ((x * 255) / (WIDTH - 1)) as u8,
+            ((y * 255) / (HEIGHT - 1)) as u8,
+            (((x + y) * 255) / (WIDTH + HEIGHT - 2)) as u8,
+            255,
+        ])
+    })
+}
+
+/// Squares of two colors, so the cells average across hard edges.
+fn checkerboard() -> RgbaImage {
+    RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        if ((x / 12) + (y / 12)) % 2 == 0 {
+            Rgba([230, 60, 40, 255])
+        } else {
+            Rgba([30, 90, 200, 255])
+        }
+    })
+}
+
+fn paint(
+    input_image: &RgbaImage,
+    sampler: &dyn AnchorSampler,
+    seed: u64,
+    options: &RenderOptions,
+) -> RgbaImage {
+    let bounds = Bounds {
+        width: WIDTH as u64,
+        height: HEIGHT as u64,
+    };
+    let anchor_points = sampler.sample(&bounds, &mut StdRng::seed_from_u64(seed));
+
+    render_voronoi(
+        input_image,
+        &color_anchor_points(input_image, anchor_points),
+        options,
+    )
+}
+
+fn poisson() -> PoissonDiskSampler {
+    PoissonDiskSampler {
+        distance: Distance {
+            minimum: MINIMUM_DISTANCE,
+            maximum: MINIMUM_DISTANCE * 2,
+        },
+    }
+}
+
+fn jittered_grid() -> JitteredGridSampler {
+    JitteredGridSampler {
+        spacing: MINIMUM_DISTANCE as f64,
+        jitter: 0.5f64,
+    }
+}
+
+/// Options searching far enough for the closest anchor of every pixel.
+fn options<'a>() -> RenderOptions<'a> {
+    RenderOptions {
+        minimum_distance: MINIMUM_DISTANCE * 2,
+        ..RenderOptions::default()
+    }
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "poisson anchor colors",
+        golden: 0x6fa93f47f9937ecf,
+        paint: || paint(&gradient(), &poisson(), 1, &options()),
+    },
+    Case {
+        name: "distance transform",
+        golden: 0xf9c4eb1626d35688,
+        paint: || {
+            let options = RenderOptions {
+                assignment: Assignment::DistanceTransform,
+                ..options()
+            };
+            paint(&gradient(), &poisson(), 1, &options)
+        },
+    },
+    Case {
+        name: "manhattan mean colors",
+        golden: 0x56a6c1f0beb4ec08,
+        paint: || {
+            let colorizer = MeanColorizer::default();
+            let options = RenderOptions {
+                metric: &Manhattan,
+                colorizer: &colorizer,
+                ..options()
+            };
+            paint(&checkerboard(), &jittered_grid(), 2, &options)
+        },
+    },
+    Case {
+        name: "smoothing",
+        golden: 0xb64f0279c4c20f37,
+        paint: || {
+            let options = RenderOptions {
+                smoothing: Some(3),
+                ..options()
+            };
+            paint(&gradient(), &poisson(), 3, &options)
+        },
+    },
+    Case {
+        name: "antialiasing",
+        golden: 0x490cb9afcafc689d,
+        paint: || {
+            let options = RenderOptions {
+                antialias: Some(3),
+                ..options()
+            };
+            paint(&checkerboard(), &poisson(), 4, &options)
+        },
+    },
+    Case {
+        name: "tileable",
+        golden: 0x4e27add02bd4d5ee,
+        paint: || {
+            let options = RenderOptions {
+                projection: Projection::Torus,
+                ..options()
+            };
+            paint(&gradient(), &poisson(), 5, &options)
+        },
+    },
+    Case {
+        name: "outlines",
+        golden: 0x339f097120c7ea4d,
+        paint: || {
+            let options = RenderOptions {
+                style: CellStyle::Outline {
+                    width: 1.5f64,
+                    color: Rgba([0, 0, 0, 255]),
+                    background: Rgba([255, 255, 255, 255]),
+                },
+                ..options()
+            };
+            paint(&gradient(), &jittered_grid(), 6, &options)
+        },
+    },
+];
+
+/// Paints every case of the self test and compares it with its golden hash.
+pub fn run_self_test() -> Vec<Outcome> {
+    CASES
+        .iter()
+        .map(|case| Outcome {
+            name: case.name,
+            expected: case.golden,
+            actual: image_hash(&(case.paint)()),
+        })
+        .collect()
+}
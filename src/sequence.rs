@@ -0,0 +1,193 @@
+use crate::anchors::{color_anchor_points, Anchor};
+use crate::geometry::Point;
+use crate::render::{
+    assign_projected, color_cells, finish_cell_colors, paint_voronoi, CellMap, RenderOptions,
+};
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// Renders aligned frames of a burst or timelapse with the same anchors, so
+/// cells stay put instead of flickering from frame to frame.
+pub struct FrameSequence<'a> {
+    anchors: Vec<Anchor>,
+    options: RenderOptions<'a>,
+    cell_map: CellMap,
+    /// Share of the previous frame's color kept in every cell, from `0` (none)
+    /// towards `1` (colors barely change).
+    color_smoothing: f64,
+    /// Color of every cell in the previous frame, if it existed then.
+    colors: Vec<Option<Rgba<u8>>>,
+}
+
+/// Points bucketed by `spacing`, to check that new points keep their
+/// distance from all earlier ones.
+struct SpacingGrid {
+    spacing: f64,
+    buckets: HashMap<(i64, i64), Vec<Point>>,
+}
+
+impl SpacingGrid {
+    fn bucket_of(&self, point: &Point) -> (i64, i64) {
+        (
+            (point.x / self.spacing).floor() as i64,
+            (point.y / self.spacing).floor() as i64,
+        )
+    }
+
+    /// Adds `point` unless it is closer than the spacing to an earlier point.
+    fn insert(&mut self, point: &Point) -> bool {
+        let (column, row) = self.bucket_of(point);
+        let is_crowded = (-1..=1).any(|row_offset| {
+            (-1..=1).any(|column_offset| {
+                self.buckets
+                    .get(&(column + column_offset, row + row_offset))
+                    .into_iter()
+                    .flatten()
+                    .any(|other| other.squared_distance_from(point) < (self.spacing * self.spacing))
+            })
+        });
+        if !is_crowded {
+            self.buckets
+                .entry((column, row))
+                .or_default()
+                .push(point.clone());
+        }
+
+        !is_crowded
+    }
+}
+
+impl<'a> FrameSequence<'a> {
+    /// Prepares to render `width`×`height` frames. The cells are assigned
+    /// once, up front, and only change when anchors are moved.
+    pub fn new(
+        anchors: Vec<Anchor>,
+        width: u32,
+        height: u32,
+        color_smoothing: f64,
+        options: &RenderOptions<'a>,
+    ) -> FrameSequence<'a> {
+        let cell_map = assign_projected(
+            &anchors,
+            width,
+            height,
+            options.minimum_distance,
+            options,
+            options.observer,
+        );
+
+        FrameSequence {
+            colors: vec![None; anchors.len()],
+            anchors,
+            options: *options,
+            cell_map,
+            color_smoothing: color_smoothing.clamp(0f64, 1f64),
+        }
+    }
+
+    pub fn anchors(&self) -> &[Anchor] {
+        &self.anchors
+    }
+
+    /// Renders the next frame, which must have the size given to
+    /// [`FrameSequence::new`].
+    pub fn render_frame(&mut self, frame: &RgbaImage) -> RgbaImage {
+        let points = self
+            .anchors
+            .iter()
+            .map(|anchor| anchor.point.clone())
+            .collect();
+        self.anchors = color_anchor_points(frame, points);
+
+        let mut sampled = color_cells(&self.cell_map, &self.anchors, frame, self.options.colorizer);
+        finish_cell_colors(&self.cell_map, &mut sampled, &self.options);
+        let colors: Vec<Rgba<u8>> = sampled
+            .iter()
+            .zip(&self.colors)
+            .map(|(current, previous)| match previous {
+                None => *current,
+                Some(previous) => Rgba(std::array::from_fn(|channel| {
+                    let (previous, current) =
+                        (previous.0[channel] as f64, current.0[channel] as f64);
+                    (current + ((previous - current) * self.color_smoothing)).round() as u8
+                })),
+            })
+            .collect();
+        self.colors = colors.iter().copied().map(Some).collect();
+
+        paint_voronoi(&self.cell_map, &self.anchors, colors, &self.options)
+    }
+
+    /// Moves every anchor by its displacement, such as the optical flow from
+    /// [`crate::flow::track_points`], and reassigns the cells.
+    ///
+    /// Anchors that leave the frame or come closer than half of
+    /// `minimum_distance` to another are dropped, and `fill_points` that are
+    /// at least `minimum_distance` from every anchor are added to cover the
+    /// gaps left behind.
+    pub fn move_anchors(
+        &mut self,
+        displacements: &[Point],
+        fill_points: Vec<Point>,
+        minimum_distance: f64,
+    ) {
+        let (width, height) = (self.cell_map.width as f64, self.cell_map.height as f64);
+        let is_inside = |point: &Point| {
+            (point.x >= 0f64) && (point.y >= 0f64) && (point.x < width) && (point.y < height)
+        };
+
+        let mut merged = SpacingGrid {
+            spacing: minimum_distance / 2f64,
+            buckets: HashMap::new(),
+        };
+        let mut anchors = Vec::with_capacity(self.anchors.len());
+        let mut colors = Vec::with_capacity(self.anchors.len());
+        for ((anchor, displacement), color) in
+            self.anchors.iter().zip(displacements).zip(&self.colors)
+        {
+            let point = Point {
+                x: anchor.point.x + displacement.x,
+                y: anchor.point.y + displacement.y,
+            };
+            if is_inside(&point) && merged.insert(&point) {
+                anchors.push(Anchor {
+                    point,
+                    color: anchor.color,
+                });
+                colors.push(*color);
+            }
+        }
+
+        let mut spaced = SpacingGrid {
+            spacing: minimum_distance,
+            buckets: HashMap::new(),
+        };
+        for anchor in &anchors {
+            spaced
+                .buckets
+                .entry(spaced.bucket_of(&anchor.point))
+                .or_default()
+                .push(anchor.point.clone());
+        }
+        for point in fill_points {
+            if is_inside(&point) && spaced.insert(&point) {
+                anchors.push(Anchor {
+                    point,
+                    color: Rgba([0, 0, 0, 0]),
+                });
+                colors.push(None);
+            }
+        }
+
+        self.cell_map = assign_projected(
+            &anchors,
+            self.cell_map.width,
+            self.cell_map.height,
+            self.options.minimum_distance,
+            &self.options,
+            None,
+        );
+        self.anchors = anchors;
+        self.colors = colors;
+    }
+}
@@ -0,0 +1,589 @@
+//! A small HTTP/1.1 server exposing the painter over `POST /render`.
+//!
+//! The request body is either the raw bytes of an image, with options passed
+//! as query parameters (`/render?minimum_distance=12&format=jpeg`), or a JSON
+//! object with a base64 encoded `image` field next to the same options.
+//!
+//! `POST /sessions` takes the same body but keeps the tessellation, replying
+//! with its id and anchors. `POST /sessions/<id>/edits` then adds, moves or
+//! removes anchors, as a JSON `edits` list like
+//! `[{"add": [x, y]}, {"move": [index, x, y]}, {"remove": index}]`, and
+//! replies with the painting, recomputing only the cells the edits reach.
+//! `GET /sessions/<id>` paints it as it is, and `DELETE` closes it.
+
+use crate::anchors::{color_anchor_points, Anchor};
+use crate::base64;
+use crate::geometry::{Bounds, Point};
+use crate::incremental::IncrementalRenderer;
+use crate::render::{render_voronoi, RenderOptions};
+use crate::sampling::generate_anchor_points;
+use image::{DynamicImage, GenericImageView, ImageOutputFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Cursor, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const MAXIMUM_BODY_SIZE: usize = 64 * 1024 * 1024;
+/// Sessions kept open at once, each holding its image and cell map.
+const MAXIMUM_SESSIONS: usize = 32;
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+struct HttpResponse {
+    status: u16,
+    reason: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn error(status: u16, reason: &'static str, message: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            reason,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message).into_bytes(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonRenderRequest {
+    image: String,
+    minimum_distance: Option<u32>,
+    format: Option<String>,
+}
+
+struct RequestOptions {
+    minimum_distance: u32,
+    format: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AnchorEdit {
+    Add(f64, f64),
+    Move(usize, f64, f64),
+    Remove(usize),
+}
+
+#[derive(Deserialize)]
+struct EditRequest {
+    edits: Vec<AnchorEdit>,
+    format: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SessionCreated {
+    session: u64,
+    width: u32,
+    height: u32,
+    anchors: Vec<[f64; 2]>,
+}
+
+/// Tessellations kept between requests, so that edits to their anchors only
+/// repaint the cells they reach.
+#[derive(Default)]
+struct Sessions {
+    next_id: u64,
+    renderers: HashMap<u64, Arc<Mutex<IncrementalRenderer<'static>>>>,
+}
+
+type SharedSessions = Arc<Mutex<Sessions>>;
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        RequestOptions {
+            minimum_distance: 10,
+            format: String::from("png"),
+        }
+    }
+}
+
+pub fn serve(address: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("Listening on http://{}", listener.local_addr()?);
+
+    let sessions = SharedSessions::default();
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let sessions = Arc::clone(&sessions);
+                thread::spawn(move || handle_connection(stream, &sessions));
+            }
+            Err(error) => {
+                eprintln!("Failed to accept connection: {}", error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, sessions: &SharedSessions) {
+    let mut reader = BufReader::new(&stream);
+    let response = match read_request(&mut reader) {
+        Ok(request) => route(request, sessions),
+        Err(response) => response,
+    };
+
+    let mut writer = &stream;
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason,
+        response.content_type,
+        response.body.len()
+    );
+    if let Err(error) = writer
+        .write_all(header.as_bytes())
+        .and_then(|_| writer.write_all(&response.body))
+        .and_then(|_| writer.flush())
+    {
+        eprintln!("Failed to write response: {}", error);
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<HttpRequest, HttpResponse> {
+    let bad_request = |message: &str| HttpResponse::error(400, "Bad Request", message);
+
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .map_err(|_| bad_request("Could not read request line"))?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(bad_request("Malformed request line")),
+    };
+
+    let mut content_length = 0usize;
+    let mut content_type = None;
+    loop {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|_| bad_request("Could not read headers"))?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .parse()
+                    .map_err(|_| bad_request("Invalid Content-Length header"))?;
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.to_ascii_lowercase());
+            }
+        }
+    }
+
+    if content_length > MAXIMUM_BODY_SIZE {
+        return Err(HttpResponse::error(
+            413,
+            "Payload Too Large",
+            "Uploaded image is too large",
+        ));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad_request("Request body is shorter than Content-Length"))?;
+
+    let (path, query) = match target.split_once('?') {
+        None => (target.clone(), Vec::new()),
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+    };
+
+    Ok(HttpRequest {
+        method,
+        path,
+        query,
+        content_type,
+        body,
+    })
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            None => (percent_decode(pair), String::new()),
+            Some((key, value)) => (percent_decode(key), percent_decode(value)),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' if index + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[(index + 1)..(index + 3)]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        index += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn route(request: HttpRequest, sessions: &SharedSessions) -> HttpResponse {
+    if let Some(session_path) = request.path.strip_prefix("/sessions") {
+        let session_path = session_path.to_string();
+        return route_session(request, &session_path, sessions);
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/render") => render(request),
+        (_, "/render") => HttpResponse::error(405, "Method Not Allowed", "Use POST /render"),
+        _ => HttpResponse::error(404, "Not Found", "Unknown path, use POST /render"),
+    }
+}
+
+fn route_session(
+    request: HttpRequest,
+    session_path: &str,
+    sessions: &SharedSessions,
+) -> HttpResponse {
+    let not_found = || HttpResponse::error(404, "Not Found", "Unknown session");
+
+    if session_path.is_empty() || (session_path == "/") {
+        return match request.method.as_str() {
+            "POST" => open_session(request, sessions),
+            _ => HttpResponse::error(405, "Method Not Allowed", "Use POST /sessions"),
+        };
+    }
+
+    let session_path = match session_path.strip_prefix('/') {
+        None => return not_found(),
+        Some(session_path) => session_path,
+    };
+    let (id, action) = match session_path.split_once('/') {
+        None => (session_path, None),
+        Some((id, action)) => (id, Some(action)),
+    };
+    let id = match id.parse::<u64>() {
+        Ok(id) => id,
+        Err(_) => return not_found(),
+    };
+    let mut sessions = sessions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let renderer = match (request.method.as_str(), action) {
+        ("DELETE", None) => {
+            return match sessions.renderers.remove(&id) {
+                None => not_found(),
+                Some(_) => HttpResponse {
+                    status: 200,
+                    reason: "OK",
+                    content_type: "text/plain; charset=utf-8",
+                    body: format!("Closed session {}\n", id).into_bytes(),
+                },
+            }
+        }
+        ("GET", None) | ("POST", Some("edits")) => match sessions.renderers.get(&id) {
+            None => return not_found(),
+            Some(renderer) => Arc::clone(renderer),
+        },
+        (_, None) => return HttpResponse::error(405, "Method Not Allowed", "Use GET or DELETE"),
+        (_, Some("edits")) => {
+            return HttpResponse::error(405, "Method Not Allowed", "Use POST for edits")
+        }
+        _ => return HttpResponse::error(404, "Not Found", "Unknown session path"),
+    };
+    // Other sessions are not held up while this one repaints.
+    drop(sessions);
+    let mut renderer = renderer
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    match action {
+        None => {
+            let format = request
+                .query
+                .iter()
+                .find(|(key, _)| key == "format")
+                .map_or("png", |(_, format)| format.as_str());
+            match output_format(format) {
+                Err(response) => response,
+                Ok(output) => encoded_response(renderer.image().clone(), output),
+            }
+        }
+        Some(_) => edit_session(request, &mut renderer),
+    }
+}
+
+/// Reads the image and options of a `/render` or `/sessions` request.
+fn read_render_request(request: HttpRequest) -> Result<(RequestOptions, Vec<u8>), HttpResponse> {
+    let bad_request = |message: &str| HttpResponse::error(400, "Bad Request", message);
+
+    let mut options = RequestOptions::default();
+    let image_bytes = match request.content_type.as_deref() {
+        Some(content_type) if content_type.starts_with("application/json") => {
+            let json_request: JsonRenderRequest = match serde_json::from_slice(&request.body) {
+                Ok(json_request) => json_request,
+                Err(error) => return Err(bad_request(&format!("Invalid JSON body: {}", error))),
+            };
+            if let Some(minimum_distance) = json_request.minimum_distance {
+                options.minimum_distance = minimum_distance;
+            }
+            if let Some(format) = json_request.format {
+                options.format = format;
+            }
+            match base64::decode(&json_request.image) {
+                None => return Err(bad_request("`image` is not valid base64")),
+                Some(image_bytes) => image_bytes,
+            }
+        }
+        _ => {
+            for (key, value) in &request.query {
+                match key.as_str() {
+                    "minimum_distance" => match value.parse() {
+                        Ok(minimum_distance) => options.minimum_distance = minimum_distance,
+                        Err(_) => return Err(bad_request("`minimum_distance` must be an integer")),
+                    },
+                    "format" => options.format = value.clone(),
+                    _ => return Err(bad_request(&format!("Unknown option `{}`", key))),
+                }
+            }
+            request.body
+        }
+    };
+
+    if options.minimum_distance == 0 {
+        return Err(bad_request("`minimum_distance` must be greater than 0"));
+    }
+
+    Ok((options, image_bytes))
+}
+
+fn output_format(format: &str) -> Result<(ImageOutputFormat, &'static str), HttpResponse> {
+    match format.to_ascii_lowercase().as_str() {
+        "png" => Ok((ImageOutputFormat::Png, "image/png")),
+        "jpeg" | "jpg" => Ok((ImageOutputFormat::Jpeg(90), "image/jpeg")),
+        "bmp" => Ok((ImageOutputFormat::Bmp, "image/bmp")),
+        "gif" => Ok((ImageOutputFormat::Gif, "image/gif")),
+        _ => Err(HttpResponse::error(
+            400,
+            "Bad Request",
+            "`format` must be one of png, jpeg, bmp, gif",
+        )),
+    }
+}
+
+/// The input image and the anchors generated for it.
+fn tessellate(
+    image_bytes: &[u8],
+    minimum_distance: u32,
+) -> Result<(RgbaImage, Vec<Anchor>), HttpResponse> {
+    let input_image = image::load_from_memory(image_bytes).map_err(|error| {
+        HttpResponse::error(
+            400,
+            "Bad Request",
+            &format!("Could not decode image: {}", error),
+        )
+    })?;
+
+    let (image_width, image_height) = input_image.dimensions();
+    let bounds = Bounds {
+        width: image_width as u64,
+        height: image_height as u64,
+    };
+    let anchor_points = generate_anchor_points(&bounds, minimum_distance);
+    let anchors = color_anchor_points(&input_image, anchor_points);
+
+    Ok((input_image.to_rgba8(), anchors))
+}
+
+fn encoded_response(
+    image: RgbaImage,
+    (output_format, content_type): (ImageOutputFormat, &'static str),
+) -> HttpResponse {
+    let output_image = DynamicImage::ImageRgba8(image);
+    let output_image = match output_format {
+        ImageOutputFormat::Jpeg(_) => DynamicImage::ImageRgb8(output_image.to_rgb8()),
+        _ => output_image,
+    };
+
+    let mut encoded = Cursor::new(Vec::new());
+    match output_image.write_to(&mut encoded, output_format) {
+        Ok(_) => HttpResponse {
+            status: 200,
+            reason: "OK",
+            content_type,
+            body: encoded.into_inner(),
+        },
+        Err(error) => HttpResponse::error(
+            500,
+            "Internal Server Error",
+            &format!("Could not encode image: {}", error),
+        ),
+    }
+}
+
+fn render(request: HttpRequest) -> HttpResponse {
+    let (options, image_bytes) = match read_render_request(request) {
+        Ok(parsed) => parsed,
+        Err(response) => return response,
+    };
+    let output = match output_format(&options.format) {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+    let (input_image, anchors) = match tessellate(&image_bytes, options.minimum_distance) {
+        Ok(tessellation) => tessellation,
+        Err(response) => return response,
+    };
+
+    let painting = render_voronoi(
+        &input_image,
+        &anchors,
+        &RenderOptions {
+            minimum_distance: options.minimum_distance,
+            ..RenderOptions::default()
+        },
+    );
+    encoded_response(painting, output)
+}
+
+fn open_session(request: HttpRequest, sessions: &SharedSessions) -> HttpResponse {
+    let (options, image_bytes) = match read_render_request(request) {
+        Ok(parsed) => parsed,
+        Err(response) => return response,
+    };
+    let (input_image, anchors) = match tessellate(&image_bytes, options.minimum_distance) {
+        Ok(tessellation) => tessellation,
+        Err(response) => return response,
+    };
+    if sessions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .renderers
+        .len()
+        >= MAXIMUM_SESSIONS
+    {
+        return HttpResponse::error(
+            503,
+            "Service Unavailable",
+            "Too many open sessions, close one with DELETE /sessions/<id>",
+        );
+    }
+
+    let (width, height) = input_image.dimensions();
+    let renderer = IncrementalRenderer::with_owned_source(
+        input_image,
+        anchors,
+        &RenderOptions {
+            minimum_distance: options.minimum_distance,
+            ..RenderOptions::default()
+        },
+    );
+    let anchors = renderer
+        .anchors()
+        .iter()
+        .map(|anchor| [anchor.point.x, anchor.point.y])
+        .collect();
+
+    let mut sessions = sessions
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let session = sessions.next_id;
+    sessions.next_id += 1;
+    sessions
+        .renderers
+        .insert(session, Arc::new(Mutex::new(renderer)));
+
+    let created = SessionCreated {
+        session,
+        width,
+        height,
+        anchors,
+    };
+    match serde_json::to_vec(&created) {
+        Ok(body) => HttpResponse {
+            status: 201,
+            reason: "Created",
+            content_type: "application/json",
+            body,
+        },
+        Err(error) => HttpResponse::error(
+            500,
+            "Internal Server Error",
+            &format!("Could not describe session: {}", error),
+        ),
+    }
+}
+
+/// Applies the edits of `request` to the anchors of a session in order and
+/// paints the result. Edits after an invalid one are not applied.
+fn edit_session(request: HttpRequest, renderer: &mut IncrementalRenderer) -> HttpResponse {
+    let bad_request = |message: &str| HttpResponse::error(400, "Bad Request", message);
+
+    let edit_request: EditRequest = match serde_json::from_slice(&request.body) {
+        Ok(edit_request) => edit_request,
+        Err(error) => return bad_request(&format!("Invalid JSON body: {}", error)),
+    };
+    let output = match output_format(edit_request.format.as_deref().unwrap_or("png")) {
+        Ok(output) => output,
+        Err(response) => return response,
+    };
+
+    let (width, height) = renderer.image().dimensions();
+    let is_inside = |x: f64, y: f64| {
+        (0f64..(width as f64)).contains(&x) && (0f64..(height as f64)).contains(&y)
+    };
+    for edit in edit_request.edits {
+        match edit {
+            AnchorEdit::Add(x, y) | AnchorEdit::Move(_, x, y) if !is_inside(x, y) => {
+                return bad_request(&format!(
+                    "({}, {}) is outside the {}x{} image",
+                    x, y, width, height
+                ))
+            }
+            AnchorEdit::Move(index, _, _) | AnchorEdit::Remove(index)
+                if index >= renderer.anchors().len() =>
+            {
+                return bad_request(&format!(
+                    "There is no anchor {}, the session has {}",
+                    index,
+                    renderer.anchors().len()
+                ))
+            }
+            AnchorEdit::Add(x, y) => {
+                renderer.add_anchor(Point { x, y });
+            }
+            AnchorEdit::Move(index, x, y) => renderer.move_anchor(index, Point { x, y }),
+            AnchorEdit::Remove(index) => {
+                renderer.remove_anchor(index);
+            }
+        }
+    }
+
+    encoded_response(renderer.image().clone(), output)
+}
@@ -0,0 +1,131 @@
+//! Snapshots of a render in progress: the columns assigned so far written
+//! to a preview image every so often, to check on a long render before it
+//! ends.
+
+use crate::interrupt::interrupted;
+use crate::render::{RenderObserver, UNASSIGNED};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often a [`SnapshotObserver`] writes its preview.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SnapshotInterval {
+    /// Every this long, at the first column assigned after it.
+    Every(Duration),
+    /// Every this many assigned columns.
+    Columns(u32),
+}
+
+impl SnapshotInterval {
+    /// Parses a number of seconds like `30s`, or a number of columns like
+    /// `500`.
+    pub fn parse(value: &str) -> Option<SnapshotInterval> {
+        let value = value.trim();
+        match value.strip_suffix('s') {
+            Some(seconds) => match seconds.trim().parse::<f64>() {
+                Ok(seconds) if seconds > 0f64 && seconds.is_finite() => {
+                    Some(SnapshotInterval::Every(Duration::from_secs_f64(seconds)))
+                }
+                _ => None,
+            },
+            None => match value.parse::<u32>() {
+                Ok(columns) if columns > 0 => Some(SnapshotInterval::Columns(columns)),
+                _ => None,
+            },
+        }
+    }
+}
+
+struct Progress {
+    preview: RgbaImage,
+    columns: u32,
+    written: Instant,
+}
+
+/// Paints every assigned column in the color of its anchor, and writes the
+/// preview to `path` as a PNG every `interval`. Cancels the render once
+/// [`interrupted`].
+pub struct SnapshotObserver<'a> {
+    interval: SnapshotInterval,
+    path: PathBuf,
+    colors: &'a [Rgba<u8>],
+    progress: Mutex<Progress>,
+}
+
+impl SnapshotObserver<'_> {
+    pub fn new(
+        interval: SnapshotInterval,
+        path: PathBuf,
+        colors: &[Rgba<u8>],
+        width: u32,
+        height: u32,
+    ) -> SnapshotObserver<'_> {
+        SnapshotObserver {
+            interval,
+            path,
+            colors,
+            progress: Mutex::new(Progress {
+                preview: RgbaImage::new(width, height),
+                columns: 0,
+                written: Instant::now(),
+            }),
+        }
+    }
+
+    /// Writes through a temporary file, so viewers never see half a preview.
+    fn write(&self, preview: &RgbaImage) {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let written = preview
+            .save_with_format(&temporary, ImageFormat::Png)
+            .map_err(|error| error.to_string())
+            .and_then(|_| fs::rename(&temporary, &self.path).map_err(|error| error.to_string()));
+        if let Err(error) = written {
+            eprintln!(
+                "Could not write snapshot {}: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+impl RenderObserver for SnapshotObserver<'_> {
+    fn column_assigned(&self, x: u32, labels: &[u32]) {
+        let due = {
+            let mut progress = match self.progress.lock() {
+                Ok(progress) => progress,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            for (y, label) in labels.iter().enumerate() {
+                if *label != UNASSIGNED {
+                    progress
+                        .preview
+                        .put_pixel(x, y as u32, self.colors[*label as usize]);
+                }
+            }
+            progress.columns += 1;
+            let due = match self.interval {
+                SnapshotInterval::Every(interval) => progress.written.elapsed() >= interval,
+                SnapshotInterval::Columns(columns) => progress.columns % columns == 0,
+            };
+            if due {
+                progress.written = Instant::now();
+            }
+
+            // Cloned so the other columns are not held up by the encoding.
+            due.then(|| progress.preview.clone())
+        };
+        if let Some(preview) = due {
+            self.write(&preview);
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        interrupted()
+    }
+}
@@ -0,0 +1,105 @@
+//! Sprite sheets: the frames of an animation packed row by row into one grid
+//! image, with a JSON descriptor of where every frame is and how long it
+//! shows, the way game engines load animations.
+
+use image::{imageops, Delay, RgbaImage};
+use serde::Serialize;
+
+/// Where one frame is on the sheet, and how long it shows.
+#[derive(Clone, Debug, Serialize)]
+pub struct SpriteFrame {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub duration_ms: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SpriteSheetDescriptor {
+    /// File name of the sheet image, next to the descriptor.
+    pub image: String,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub frame_count: usize,
+    /// Frames per second, from the mean duration of the frames.
+    pub fps: f64,
+    pub frames: Vec<SpriteFrame>,
+}
+
+fn delay_ms(delay: &Delay) -> f64 {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    (numerator as f64) / (denominator.max(1) as f64)
+}
+
+/// Packs `frames` left to right and top to bottom into a grid of `columns`
+/// by `rows` slots as large as the largest frame, describing it as the
+/// `image` file. Fails when the grid has fewer slots than there are frames.
+pub fn pack_sprite_sheet(
+    frames: &[(RgbaImage, Delay)],
+    (columns, rows): (u32, u32),
+    image: &str,
+) -> Result<(RgbaImage, SpriteSheetDescriptor), String> {
+    if frames.is_empty() {
+        return Err(String::from("There are no frames to pack"));
+    }
+    if (frames.len() as u64) > (columns as u64) * (rows as u64) {
+        return Err(format!(
+            "A {}x{} sprite sheet holds {} frames, not all {}",
+            columns,
+            rows,
+            (columns as u64) * (rows as u64),
+            frames.len()
+        ));
+    }
+
+    let frame_width = frames
+        .iter()
+        .map(|(frame, _)| frame.width())
+        .max()
+        .unwrap_or(0);
+    let frame_height = frames
+        .iter()
+        .map(|(frame, _)| frame.height())
+        .max()
+        .unwrap_or(0);
+    let mut sheet = RgbaImage::new(frame_width * columns, frame_height * rows);
+    let mut placed = Vec::with_capacity(frames.len());
+    for (index, (frame, delay)) in frames.iter().enumerate() {
+        let (x, y) = (
+            ((index as u32) % columns) * frame_width,
+            ((index as u32) / columns) * frame_height,
+        );
+        imageops::replace(&mut sheet, frame, x as i64, y as i64);
+        placed.push(SpriteFrame {
+            x,
+            y,
+            width: frame.width(),
+            height: frame.height(),
+            duration_ms: delay_ms(delay),
+        });
+    }
+
+    let total_ms: f64 = placed.iter().map(|frame| frame.duration_ms).sum();
+    let fps = match total_ms > 0f64 {
+        // Rounded, so that 1000 / 6 ms frames are 6 and not 6.000000000000001.
+        true => ((placed.len() as f64) * 1000000f64 / total_ms).round() / 1000f64,
+        false => 0f64,
+    };
+
+    Ok((
+        sheet,
+        SpriteSheetDescriptor {
+            image: image.to_string(),
+            frame_width,
+            frame_height,
+            columns,
+            rows,
+            frame_count: placed.len(),
+            fps,
+            frames: placed,
+        },
+    ))
+}
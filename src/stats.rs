@@ -0,0 +1,136 @@
+//! Statistics of every cell of a tessellation, as CSV rows for analyzing
+//! outside the tool how the parameters shape the cells.
+
+use crate::anchors::Anchor;
+use crate::merge::neighbouring_cells;
+use crate::palette::format_hex_color;
+use crate::render::{CellMap, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+
+/// What one cell covers of the image it was painted from.
+pub struct CellStatistics {
+    pub index: usize,
+    pub x: f64,
+    pub y: f64,
+    /// Pixels owned by the cell.
+    pub area: u64,
+    /// Pixel edges between the cell and other cells or the edge of the
+    /// image.
+    pub perimeter: u64,
+    /// Mean red, green, blue and alpha of the pixels owned, if any.
+    pub mean: Option<[f64; 4]>,
+    /// Variance of the red, green and blue of the pixels owned, averaged
+    /// over the three.
+    pub variance: Option<f64>,
+    /// Cells sharing an edge with the cell.
+    pub neighbors: usize,
+}
+
+/// The statistics of every cell of `anchors`, in their order, over the
+/// pixels of `image` that `cell_map`, of its size, assigns.
+pub fn cell_statistics(
+    cell_map: &CellMap,
+    anchors: &[Anchor],
+    image: &RgbaImage,
+) -> Vec<CellStatistics> {
+    let mut areas = vec![0u64; anchors.len()];
+    let mut perimeters = vec![0u64; anchors.len()];
+    let mut sums = vec![[0f64; 4]; anchors.len()];
+    let mut squares = vec![[0f64; 3]; anchors.len()];
+    for y in 0..cell_map.height {
+        for x in 0..cell_map.width {
+            let label = cell_map.label(x, y);
+            if (label == UNASSIGNED) || ((label as usize) >= anchors.len()) {
+                continue;
+            }
+            let cell = label as usize;
+
+            areas[cell] += 1;
+            let sides = [
+                (x > 0).then(|| cell_map.label(x - 1, y)),
+                (x + 1 < cell_map.width).then(|| cell_map.label(x + 1, y)),
+                (y > 0).then(|| cell_map.label(x, y - 1)),
+                (y + 1 < cell_map.height).then(|| cell_map.label(x, y + 1)),
+            ];
+            perimeters[cell] += sides.iter().filter(|side| **side != Some(label)).count() as u64;
+
+            let Rgba(channels) = image.get_pixel(x, y);
+            for (channel, value) in channels.iter().enumerate() {
+                sums[cell][channel] += *value as f64;
+                if channel < 3 {
+                    squares[cell][channel] += (*value as f64) * (*value as f64);
+                }
+            }
+        }
+    }
+
+    let mut neighbors = vec![0usize; anchors.len()];
+    for (first, second) in neighbouring_cells(cell_map) {
+        for cell in [first, second] {
+            if let Some(count) = neighbors.get_mut(cell as usize) {
+                *count += 1;
+            }
+        }
+    }
+
+    anchors
+        .iter()
+        .enumerate()
+        .map(|(index, anchor)| {
+            let area = areas[index];
+            let mean = (area > 0).then(|| sums[index].map(|sum| sum / (area as f64)));
+            let variance = mean.map(|mean| {
+                (0..3)
+                    .map(|channel| {
+                        (squares[index][channel] / (area as f64)) - (mean[channel] * mean[channel])
+                    })
+                    .sum::<f64>()
+                    .max(0f64)
+                    / 3f64
+            });
+
+            CellStatistics {
+                index,
+                x: anchor.point.x,
+                y: anchor.point.y,
+                area,
+                perimeter: perimeters[index],
+                mean,
+                variance,
+                neighbors: neighbors[index],
+            }
+        })
+        .collect()
+}
+
+/// `statistics` as CSV with a header row, the empty cells without colors.
+pub fn cell_statistics_csv(statistics: &[CellStatistics]) -> String {
+    let mut csv = String::from(
+        "index,x,y,area,perimeter,color,red,green,blue,alpha,color_variance,neighbors\n",
+    );
+    for cell in statistics {
+        let color = match cell.mean {
+            None => String::from(",,,,"),
+            Some(mean) => {
+                let rounded = mean.map(|channel| channel.round().clamp(0f64, 255f64) as u8);
+                format!(
+                    "{},{:.2},{:.2},{:.2},{:.2}",
+                    format_hex_color(Rgba(rounded)),
+                    mean[0],
+                    mean[1],
+                    mean[2],
+                    mean[3]
+                )
+            }
+        };
+        let variance = cell
+            .variance
+            .map_or(String::new(), |variance| format!("{:.2}", variance));
+        csv.push_str(&format!(
+            "{},{:.2},{:.2},{},{},{},{},{}\n",
+            cell.index, cell.x, cell.y, cell.area, cell.perimeter, color, variance, cell.neighbors
+        ));
+    }
+
+    csv
+}
@@ -0,0 +1,82 @@
+//! Pointillist stippling: instead of filling the cells, a disc in the color
+//! of every cell at its centroid, sized by its area, over a plain background.
+
+use crate::render::{CellMap, UNASSIGNED};
+use image::{Rgba, RgbaImage};
+
+/// Share of the radius of the circle as large as a cell that its disc gets,
+/// so that the discs of neighbouring cells rarely touch.
+const DISC_SCALE: f64 = 0.8;
+
+/// Radius of the disc stippled for a cell of `area` square pixels.
+pub fn stipple_radius(area: f64) -> f64 {
+    (area / std::f64::consts::PI).sqrt() * DISC_SCALE
+}
+
+/// `color` laid over `background` with `coverage` of its opacity.
+fn blend(background: Rgba<u8>, color: Rgba<u8>, coverage: f64) -> Rgba<u8> {
+    let mut blended = background;
+    for (channel, (from, to)) in blended
+        .0
+        .iter_mut()
+        .zip(background.0.into_iter().zip(color.0))
+    {
+        let mixed = (from as f64) + (((to as f64) - (from as f64)) * coverage);
+        *channel = mixed.round().clamp(0f64, 255f64) as u8;
+    }
+
+    blended
+}
+
+/// Draws a disc for every cell of `cell_map` in its color at its centroid,
+/// with [`stipple_radius`] of its area and anti-aliased edges, over
+/// `background`. Pixels outside every cell stay transparent.
+pub fn paint_stipples(cell_map: &CellMap, colors: &[Rgba<u8>], background: Rgba<u8>) -> RgbaImage {
+    let mut sums = vec![(0f64, 0f64, 0u64); colors.len()];
+    let mut output_image_buffer = RgbaImage::new(cell_map.width, cell_map.height);
+    for (x, y, pixel) in output_image_buffer.enumerate_pixels_mut() {
+        let label = cell_map.label(x, y);
+        if label == UNASSIGNED {
+            continue;
+        }
+
+        let (sum_x, sum_y, area) = &mut sums[label as usize];
+        *sum_x += (x as f64) + 0.5f64;
+        *sum_y += (y as f64) + 0.5f64;
+        *area += 1;
+        *pixel = background;
+    }
+
+    for ((sum_x, sum_y, area), color) in sums.into_iter().zip(colors) {
+        if area == 0 {
+            continue;
+        }
+
+        let (center_x, center_y) = (sum_x / (area as f64), sum_y / (area as f64));
+        let radius = stipple_radius(area as f64);
+        let from_x = (center_x - radius - 1f64).floor().max(0f64) as u32;
+        let from_y = (center_y - radius - 1f64).floor().max(0f64) as u32;
+        let to_x = ((center_x + radius + 1f64).ceil() as u32).min(cell_map.width);
+        let to_y = ((center_y + radius + 1f64).ceil() as u32).min(cell_map.height);
+        for y in from_y..to_y {
+            for x in from_x..to_x {
+                if cell_map.label(x, y) == UNASSIGNED {
+                    continue;
+                }
+
+                let (dx, dy) = (
+                    (x as f64) + 0.5f64 - center_x,
+                    (y as f64) + 0.5f64 - center_y,
+                );
+                // Pixels half in the disc are half covered.
+                let coverage = (radius + 0.5f64 - ((dx * dx) + (dy * dy)).sqrt()).clamp(0f64, 1f64);
+                if coverage > 0f64 {
+                    let pixel = output_image_buffer.get_pixel_mut(x, y);
+                    *pixel = blend(*pixel, *color, coverage);
+                }
+            }
+        }
+    }
+
+    output_image_buffer
+}
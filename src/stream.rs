@@ -0,0 +1,167 @@
+//! Painting cells straight into a PNG a strip of rows at a time, so outputs
+//! far larger than memory allows as one image can still be written.
+
+use crate::anchors::Anchor;
+use crate::colorize::PixelSums;
+use crate::render::{
+    closest_in_column, finish_neighbour_colors, map_columns_on_target, output_anchors,
+    AnchorColumns, RenderOptions, UNASSIGNED,
+};
+use image::{Rgba, RgbaImage};
+use png::{BitDepth, ColorType, Compression, Encoder, EncodingError};
+use std::collections::HashSet;
+use std::io::Write;
+
+/// How many rows of the output are assigned and painted at once.
+const STRIP_ROWS: u32 = 256;
+
+/// Colors the cells of `anchors` on `source_image` with the colorizer of
+/// `options`, and merges or separates them as it asks, from the sums of
+/// their pixels and the cells they touch gathered a strip of rows at a time
+/// the way [`stream_cells_png`] paints them, so no label of the whole image
+/// is held. `None` if the colorizer needs more of a cell than the mean of
+/// its pixels.
+///
+/// Once the observer of `options` is cancelled the remaining strips are
+/// left out.
+pub fn stream_cell_colors(
+    source_image: &RgbaImage,
+    anchors: &[Anchor],
+    options: &RenderOptions,
+) -> Option<Vec<Rgba<u8>>> {
+    let color_space = options.colorizer.mean_space()?;
+    let (width, height) = source_image.dimensions();
+    let anchor_columns = AnchorColumns::new(anchors);
+    let finishes = options.merge_threshold.is_some() || options.color_vision.is_some();
+
+    let mut sums = vec![PixelSums::new(color_space); anchors.len()];
+    let mut areas = vec![0usize; anchors.len()];
+    let mut neighbours = HashSet::new();
+    let mut touch = |label: u32, neighbour: u32| {
+        if (label != UNASSIGNED) && (neighbour != UNASSIGNED) && (label != neighbour) {
+            neighbours.insert((label.min(neighbour), label.max(neighbour)));
+        }
+    };
+    // The labels of the last row of the strip before, which touches the
+    // first of the next.
+    let mut previous_row: Option<Vec<u32>> = None;
+    for top in (0..height).step_by(STRIP_ROWS as usize) {
+        if options
+            .observer
+            .is_some_and(|observer| observer.is_cancelled())
+        {
+            break;
+        }
+        let bottom = (top + STRIP_ROWS).min(height);
+        let columns = map_columns_on_target(width, |x| {
+            let candidates =
+                anchor_columns.exhaustive_candidates(x, top..bottom, options.minimum_distance);
+            closest_in_column(x, top..bottom, anchors, &candidates, options.metric)
+        });
+
+        for (x, column) in columns.iter().enumerate() {
+            for (y, label) in (top..bottom).zip(column) {
+                if *label != UNASSIGNED {
+                    sums[*label as usize].add(*source_image.get_pixel(x as u32, y));
+                    areas[*label as usize] += 1;
+                }
+            }
+        }
+        if finishes {
+            for (x, column) in columns.iter().enumerate() {
+                if let Some(previous_row) = &previous_row {
+                    touch(previous_row[x], column[0]);
+                }
+                for (row, label) in column.iter().enumerate() {
+                    if let Some(below) = column.get(row + 1) {
+                        touch(*label, *below);
+                    }
+                    if let Some(right) = columns.get(x + 1) {
+                        touch(*label, right[row]);
+                    }
+                }
+            }
+            previous_row = Some(
+                columns
+                    .iter()
+                    .map(|column| column[column.len() - 1])
+                    .collect(),
+            );
+        }
+    }
+
+    let mut colors: Vec<Rgba<u8>> = anchors
+        .iter()
+        .zip(&sums)
+        .map(|(anchor, sums)| options.colorizer.colorize_sums(anchor, sums))
+        .collect();
+    finish_neighbour_colors(&areas, &neighbours, &mut colors, options);
+
+    Some(colors)
+}
+
+/// Paints the cells of `anchors`, placed on a `source_size` image and filled
+/// with `colors`, at the output size of `options` and encodes them as a PNG
+/// to `writer`, a strip of rows at a time. Pixels no cell reaches are
+/// filled with `background`.
+///
+/// Only the labels and pixels of one strip are held at once, so peak memory
+/// grows with the width of the output rather than its area. Once the
+/// observer of `options` is cancelled the remaining strips are left
+/// `background`.
+pub fn stream_cells_png<W: Write>(
+    writer: W,
+    anchors: &[Anchor],
+    colors: &[Rgba<u8>],
+    source_size: (u32, u32),
+    options: &RenderOptions,
+    background: Rgba<u8>,
+    compression: Compression,
+) -> Result<(), EncodingError> {
+    let (width, height) = options.output_size.unwrap_or(source_size);
+    let (anchors, minimum_distance) = output_anchors(anchors, source_size, options);
+    let anchor_columns = AnchorColumns::new(&anchors);
+
+    let mut encoder = Encoder::new(writer, width, height);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.set_compression(compression);
+    let mut png_writer = encoder.write_header()?;
+    let mut stream = png_writer.stream_writer()?;
+
+    let row_bytes = (width as usize) * 4;
+    for top in (0..height).step_by(STRIP_ROWS as usize) {
+        let bottom = (top + STRIP_ROWS).min(height);
+        // A cancelled render still finishes the file, with background rows.
+        if options
+            .observer
+            .is_some_and(|observer| observer.is_cancelled())
+        {
+            let strip: Vec<u8> = background
+                .0
+                .repeat((width as usize) * ((bottom - top) as usize));
+            stream.write_all(&strip)?;
+            continue;
+        }
+        let columns = map_columns_on_target(width, |x| {
+            let candidates = anchor_columns.exhaustive_candidates(x, top..bottom, minimum_distance);
+            closest_in_column(x, top..bottom, &anchors, &candidates, options.metric)
+        });
+
+        let mut strip = vec![0u8; row_bytes * ((bottom - top) as usize)];
+        for (x, column) in columns.iter().enumerate() {
+            for (row, label) in column.iter().enumerate() {
+                let color = match *label {
+                    UNASSIGNED => background,
+                    label => colors[label as usize],
+                };
+                let offset = row * row_bytes + x * 4;
+                strip[offset..(offset + 4)].copy_from_slice(&color.0);
+            }
+        }
+        stream.write_all(&strip)?;
+    }
+
+    stream.finish()?;
+    png_writer.finish()
+}
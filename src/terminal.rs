@@ -0,0 +1,196 @@
+//! Inline previews of rendered images in terminals that understand one of the
+//! common graphics protocols.
+
+use crate::base64;
+use image::{imageops, DynamicImage, ImageOutputFormat, RgbaImage};
+use std::env;
+use std::io::{Cursor, Write};
+
+const MAXIMUM_PREVIEW_WIDTH: u32 = 800;
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TerminalGraphics {
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+impl TerminalGraphics {
+    pub fn from_name(name: &str) -> Option<TerminalGraphics> {
+        match name {
+            "kitty" => Some(TerminalGraphics::Kitty),
+            "iterm" => Some(TerminalGraphics::Iterm),
+            "sixel" => Some(TerminalGraphics::Sixel),
+            _ => None,
+        }
+    }
+
+    /// Guesses the protocol of the current terminal from its environment,
+    /// falling back to sixel, which has the widest support.
+    pub fn detect() -> TerminalGraphics {
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        if env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") {
+            TerminalGraphics::Kitty
+        } else if (term_program == "iTerm.app") || (term_program == "WezTerm") {
+            TerminalGraphics::Iterm
+        } else {
+            TerminalGraphics::Sixel
+        }
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> std::io::Result<Vec<u8>> {
+    let mut encoded = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(image.clone())
+        .write_to(&mut encoded, ImageOutputFormat::Png)
+        .map_err(std::io::Error::other)?;
+
+    Ok(encoded.into_inner())
+}
+
+fn write_kitty<W: Write>(image: &RgbaImage, writer: &mut W) -> std::io::Result<()> {
+    let encoded = base64::encode(&encode_png(image)?);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = if index + 1 < chunks.len() { 1 } else { 0 };
+        if index == 0 {
+            write!(writer, "\x1b_Gf=100,a=T,m={};", more)?;
+        } else {
+            write!(writer, "\x1b_Gm={};", more)?;
+        }
+        writer.write_all(chunk)?;
+        writer.write_all(b"\x1b\\")?;
+    }
+
+    writeln!(writer)
+}
+
+fn write_iterm<W: Write>(image: &RgbaImage, writer: &mut W) -> std::io::Result<()> {
+    let png = encode_png(image)?;
+    write!(
+        writer,
+        "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07",
+        png.len(),
+        base64::encode(&png)
+    )?;
+
+    writeln!(writer)
+}
+
+/// Index of the closest color of a 6×6×6 cube, flattening transparency onto
+/// black.
+fn sixel_color_index(pixel: &image::Rgba<u8>) -> usize {
+    let [red, green, blue, alpha] = pixel.0;
+    let level = |channel: u8| {
+        let channel = ((channel as u32) * (alpha as u32)) / 255;
+        ((channel * 5) + 127) / 255
+    };
+
+    ((level(red) * 36) + (level(green) * 6) + level(blue)) as usize
+}
+
+fn write_sixel_run<W: Write>(writer: &mut W, sixel: u8, count: usize) -> std::io::Result<()> {
+    match count {
+        0 => Ok(()),
+        1..=3 => writer.write_all(&vec![sixel; count]),
+        _ => write!(writer, "!{}{}", count, sixel as char),
+    }
+}
+
+fn write_sixel<W: Write>(image: &RgbaImage, writer: &mut W) -> std::io::Result<()> {
+    let (width, height) = image.dimensions();
+    write!(writer, "\x1bPq\"1;1;{};{}", width, height)?;
+
+    for index in 0..216u32 {
+        let scale = |level: u32| (level * 100) / 5;
+        write!(
+            writer,
+            "#{};2;{};{};{}",
+            index,
+            scale(index / 36),
+            scale((index / 6) % 6),
+            scale(index % 6)
+        )?;
+    }
+
+    let indices: Vec<usize> = image.pixels().map(sixel_color_index).collect();
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+
+        let mut used = [false; 216];
+        for y in band..(band + rows) {
+            for x in 0..width {
+                used[indices[((y * width) + x) as usize]] = true;
+            }
+        }
+
+        let mut first_color = true;
+        for (color, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            if !first_color {
+                writer.write_all(b"$")?;
+            }
+            first_color = false;
+            write!(writer, "#{}", color)?;
+
+            let mut run_sixel = 0u8;
+            let mut run_length = 0usize;
+            for x in 0..width {
+                let mut bits = 0u8;
+                for row in 0..rows {
+                    if indices[(((band + row) * width) + x) as usize] == color {
+                        bits |= 1 << row;
+                    }
+                }
+                let sixel = 63 + bits;
+
+                if (sixel == run_sixel) && (run_length > 0) {
+                    run_length += 1;
+                } else {
+                    write_sixel_run(writer, run_sixel, run_length)?;
+                    run_sixel = sixel;
+                    run_length = 1;
+                }
+            }
+            write_sixel_run(writer, run_sixel, run_length)?;
+        }
+
+        writer.write_all(b"-")?;
+    }
+
+    writer.write_all(b"\x1b\\")?;
+    writeln!(writer)
+}
+
+/// Writes `image` to `writer` as an inline graphic, shrinking it first so that
+/// it fits comfortably in a terminal window.
+pub fn write_preview<W: Write>(
+    image: &RgbaImage,
+    graphics: TerminalGraphics,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let (width, height) = image.dimensions();
+    let preview = if width > MAXIMUM_PREVIEW_WIDTH {
+        let preview_height =
+            (((height as u64) * (MAXIMUM_PREVIEW_WIDTH as u64)) / (width as u64)).max(1);
+        imageops::resize(
+            image,
+            MAXIMUM_PREVIEW_WIDTH,
+            preview_height as u32,
+            imageops::FilterType::Triangle,
+        )
+    } else {
+        image.clone()
+    };
+
+    match graphics {
+        TerminalGraphics::Kitty => write_kitty(&preview, writer),
+        TerminalGraphics::Iterm => write_iterm(&preview, writer),
+        TerminalGraphics::Sixel => write_sixel(&preview, writer),
+    }?;
+
+    writer.flush()
+}
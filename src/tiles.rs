@@ -0,0 +1,177 @@
+//! Splitting a large painting over overlapping pages, so a poster can be
+//! printed on a home printer and assembled.
+
+use crate::geometry::Point;
+use image::{Rgba, RgbaImage};
+
+const PAPER: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const MARK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// A grid of pages covering an image, in pixels of the image. Neighbouring
+/// pages share `overlap` pixels.
+pub struct PageLayout {
+    pub page_width: f64,
+    pub page_height: f64,
+    pub overlap: f64,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+fn pages_along(length: f64, page: f64, overlap: f64) -> u32 {
+    if length <= page {
+        1
+    } else {
+        1 + ((length - page) / (page - overlap)).ceil() as u32
+    }
+}
+
+impl PageLayout {
+    /// Lays `page_width` by `page_height` pages over an image, turned
+    /// whichever way needs fewer pages. Pages must be larger than `overlap`.
+    pub fn new(
+        image_width: u32,
+        image_height: u32,
+        (page_width, page_height): (f64, f64),
+        overlap: f64,
+    ) -> PageLayout {
+        let (image_width, image_height) = (image_width as f64, image_height as f64);
+        let layout = |page_width: f64, page_height: f64| PageLayout {
+            page_width,
+            page_height,
+            overlap,
+            columns: pages_along(image_width, page_width, overlap),
+            rows: pages_along(image_height, page_height, overlap),
+        };
+
+        let upright = layout(page_width, page_height);
+        let turned = layout(page_height, page_width);
+        if turned.page_count() < upright.page_count() {
+            turned
+        } else {
+            upright
+        }
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// Position in the image of the top left corner of a page.
+    pub fn origin(&self, column: u32, row: u32) -> Point {
+        Point {
+            x: (column as f64) * (self.page_width - self.overlap),
+            y: (row as f64) * (self.page_height - self.overlap),
+        }
+    }
+
+    /// Crop marks of a page, as lines in page pixels. They sit in the middle
+    /// of every overlap with a neighbouring page, at the same spot of the
+    /// image on both pages: trim one page along its marks and lay it over
+    /// the other with the marks lined up.
+    pub fn crop_marks(&self, column: u32, row: u32) -> Vec<(Point, Point)> {
+        let length = self.page_width.min(self.page_height) / 30f64;
+        let (width, height) = (self.page_width, self.page_height);
+        let half = self.overlap / 2f64;
+
+        let mut verticals = Vec::new();
+        if column > 0 {
+            verticals.push(half);
+        }
+        if column + 1 < self.columns {
+            verticals.push(width - half);
+        }
+        let mut horizontals = Vec::new();
+        if row > 0 {
+            horizontals.push(half);
+        }
+        if row + 1 < self.rows {
+            horizontals.push(height - half);
+        }
+
+        let mut marks = Vec::new();
+        for x in verticals {
+            marks.push((Point { x, y: 0f64 }, Point { x, y: length }));
+            marks.push((
+                Point {
+                    x,
+                    y: height - length,
+                },
+                Point { x, y: height },
+            ));
+        }
+        for y in horizontals {
+            marks.push((Point { x: 0f64, y }, Point { x: length, y }));
+            marks.push((
+                Point {
+                    x: width - length,
+                    y,
+                },
+                Point { x: width, y },
+            ));
+        }
+
+        marks
+    }
+
+    /// The part of `image` on a page, on white paper where the page runs
+    /// past the image, with its crop marks drawn `line_width` pixels wide
+    /// in black over a white halo.
+    pub fn paint_page(
+        &self,
+        image: &RgbaImage,
+        column: u32,
+        row: u32,
+        line_width: u32,
+    ) -> RgbaImage {
+        let origin = self.origin(column, row);
+        let (left, top) = (origin.x.round() as i64, origin.y.round() as i64);
+        let mut page = RgbaImage::from_fn(
+            self.page_width.round() as u32,
+            self.page_height.round() as u32,
+            |x, y| {
+                let (image_x, image_y) = (left + (x as i64), top + (y as i64));
+                let is_inside =
+                    (image_x < image.width() as i64) && (image_y < image.height() as i64);
+                if !is_inside {
+                    return PAPER;
+                }
+                // Transparent parts of the painting print as paper.
+                let [red, green, blue, alpha] = image.get_pixel(image_x as u32, image_y as u32).0;
+                let alpha = alpha as u32;
+                let blend =
+                    |channel: u8| (((channel as u32) * alpha) + (255 * (255 - alpha))) / 255;
+                Rgba([blend(red) as u8, blend(green) as u8, blend(blue) as u8, 255])
+            },
+        );
+
+        let line_width = line_width.max(1) as f64;
+        let marks = self.crop_marks(column, row);
+        for (radius, color) in [(line_width * 1.5f64, PAPER), (line_width / 2f64, MARK)] {
+            for (from, to) in &marks {
+                draw_line(&mut page, from, to, radius, color);
+            }
+        }
+
+        page
+    }
+}
+
+/// Draws an axis aligned line as a rectangle reaching `radius` pixels on
+/// either side of it.
+fn draw_line(image: &mut RgbaImage, from: &Point, to: &Point, radius: f64, color: Rgba<u8>) {
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let clamp = |value: f64, limit: f64| value.round().clamp(0f64, limit) as u32;
+    let (x_from, x_to) = (
+        clamp(from.x.min(to.x) - radius, width),
+        clamp(from.x.max(to.x) + radius, width),
+    );
+    let (y_from, y_to) = (
+        clamp(from.y.min(to.y) - radius, height),
+        clamp(from.y.max(to.y) + radius, height),
+    );
+    for y in y_from..y_to {
+        for x in x_from..x_to {
+            image.put_pixel(x, y, color);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Wall-clock timings of the phases of a render, to see where a slow render
+//! spends its time.
+
+use crate::render::{set_progress_phase, take_worker_busy_times};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// How long a phase took, and how long each worker thread of
+/// [`crate::render::map_columns_in_threads`] was busy during it.
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub elapsed: Duration,
+    pub worker_busy: Vec<Duration>,
+}
+
+pub struct Timings {
+    started: Instant,
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Timings::new()
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{:.1} ms", duration.as_secs_f64() * 1000f64)
+    } else {
+        format!("{:.2} s", duration.as_secs_f64())
+    }
+}
+
+impl Timings {
+    /// Starts the clock of the whole run.
+    pub fn new() -> Timings {
+        Timings {
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Runs `run` as `phase`, which also names it in progress events.
+    pub fn measure<T>(&mut self, phase: &'static str, run: impl FnOnce() -> T) -> T {
+        take_worker_busy_times();
+        set_progress_phase(phase);
+        let started = Instant::now();
+        let result = run();
+        self.record(phase, started.elapsed());
+
+        result
+    }
+
+    /// Adds `elapsed` to `phase`, with the time worker threads were busy
+    /// since the previous phase. Phases recorded more than once add up.
+    pub fn record(&mut self, phase: &'static str, elapsed: Duration) {
+        let worker_busy = take_worker_busy_times();
+        match self.phases.iter_mut().find(|timing| timing.phase == phase) {
+            None => self.phases.push(PhaseTiming {
+                phase,
+                elapsed,
+                worker_busy,
+            }),
+            Some(timing) => {
+                timing.elapsed += elapsed;
+                if timing.worker_busy.len() < worker_busy.len() {
+                    timing.worker_busy.resize(worker_busy.len(), Duration::ZERO);
+                }
+                for (total, busy) in timing.worker_busy.iter_mut().zip(worker_busy) {
+                    *total += busy;
+                }
+            }
+        }
+    }
+
+    /// One line per phase with its share of the run, and for phases run on
+    /// worker threads how busy every thread was, then the total.
+    pub fn report(&self) -> String {
+        let total = self.started.elapsed();
+        let mut report = String::from("Timings:\n");
+        for timing in &self.phases {
+            let share = timing.elapsed.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON);
+            let _ = write!(
+                report,
+                "  {:<16} {:>10} {:>5.1}%",
+                timing.phase,
+                format_duration(timing.elapsed),
+                share * 100f64
+            );
+            if !timing.worker_busy.is_empty() {
+                let elapsed = timing.elapsed.as_secs_f64().max(f64::EPSILON);
+                let utilization: Vec<String> = timing
+                    .worker_busy
+                    .iter()
+                    .map(|busy| format!("{:.0}%", (busy.as_secs_f64() / elapsed) * 100f64))
+                    .collect();
+                let _ = write!(
+                    report,
+                    "  {} worker threads busy {}",
+                    timing.worker_busy.len(),
+                    utilization.join(" ")
+                );
+            }
+            report.push('\n');
+        }
+        let _ = write!(report, "  {:<16} {:>10}", "total", format_duration(total));
+
+        report
+    }
+}
@@ -0,0 +1,112 @@
+//! Assigning pixels to cells with an exact Euclidean distance transform
+//! (Felzenszwalb and Huttenlocher) that carries along the nearest anchor,
+//! in two passes over the image whatever the number of anchors.
+
+use crate::anchors::Anchor;
+use crate::render::{map_columns_on_target, CellMap, UNASSIGNED};
+
+/// Squared distances and labels of the lower envelope of the parabolas
+/// rooted at every finite entry of `distances`, for every position of it.
+fn transform_line(distances: &[f64], labels: &[u32]) -> (Vec<f64>, Vec<u32>) {
+    let length = distances.len();
+    let mut roots: Vec<usize> = Vec::with_capacity(length);
+    // Where the parabola of every root starts being the lowest.
+    let mut starts: Vec<f64> = Vec::with_capacity(length);
+    for (position, distance) in distances.iter().enumerate() {
+        if !distance.is_finite() {
+            continue;
+        }
+        let position_value = distance + (position * position) as f64;
+        let mut start = f64::NEG_INFINITY;
+        while let Some(&root) = roots.last() {
+            let root_value = distances[root] + (root * root) as f64;
+            start = (position_value - root_value) / (2f64 * ((position - root) as f64));
+            if start <= *starts.last().unwrap_or(&f64::NEG_INFINITY) {
+                roots.pop();
+                starts.pop();
+                start = f64::NEG_INFINITY;
+            } else {
+                break;
+            }
+        }
+        roots.push(position);
+        starts.push(start);
+    }
+
+    let mut transformed = vec![f64::INFINITY; length];
+    let mut transformed_labels = vec![UNASSIGNED; length];
+    let mut segment = 0;
+    for position in 0..length {
+        if roots.is_empty() {
+            break;
+        }
+        while (segment + 1 < roots.len()) && (starts[segment + 1] < position as f64) {
+            segment += 1;
+        }
+        let root = roots[segment];
+        let offset = (position as f64) - (root as f64);
+        transformed[position] = (offset * offset) + distances[root];
+        transformed_labels[position] = labels[root];
+    }
+
+    (transformed, transformed_labels)
+}
+
+/// Assigns every pixel to its closest anchor by the squared Euclidean
+/// distance, with a pass along the rows and one along the columns.
+///
+/// Anchors are snapped to the pixel they fall on first, so boundaries can
+/// differ from an exact search by a pixel; where several anchors share a
+/// pixel the first keeps it.
+pub fn assign_cells_by_distance_transform(
+    anchors: &[Anchor],
+    image_width: u32,
+    image_height: u32,
+) -> CellMap {
+    let (width, height) = (image_width as usize, image_height as usize);
+    if (width == 0) || (height == 0) {
+        return CellMap::new(image_width, image_height);
+    }
+    let mut seeds = vec![UNASSIGNED; width * height];
+    for (index, anchor) in anchors.iter().enumerate() {
+        let x = anchor.point.x.round().clamp(0f64, (width - 1) as f64) as usize;
+        let y = anchor.point.y.round().clamp(0f64, (height - 1) as f64) as usize;
+        if seeds[(y * width) + x] == UNASSIGNED {
+            seeds[(y * width) + x] = index as u32;
+        }
+    }
+
+    // Along every row, the distance to the closest anchor snapped to it.
+    let rows = map_columns_on_target(image_height, |y| {
+        let labels = &seeds[(y as usize * width)..((y as usize + 1) * width)];
+        let distances: Vec<f64> = labels
+            .iter()
+            .map(|label| {
+                if *label == UNASSIGNED {
+                    f64::INFINITY
+                } else {
+                    0f64
+                }
+            })
+            .collect();
+
+        transform_line(&distances, labels)
+    });
+
+    // Down every column, the closest of those row distances.
+    let columns = map_columns_on_target(image_width, |x| {
+        let (distances, labels): (Vec<f64>, Vec<u32>) = rows
+            .iter()
+            .map(|(distances, labels)| (distances[x as usize], labels[x as usize]))
+            .unzip();
+
+        transform_line(&distances, &labels).1
+    });
+
+    let mut cell_map = CellMap::new(image_width, image_height);
+    for (x, column_labels) in columns.into_iter().enumerate() {
+        cell_map.set_column(x as u32, column_labels);
+    }
+
+    cell_map
+}
@@ -0,0 +1,167 @@
+use crate::geometry::{Bounds, Point};
+use crate::palette::Palette;
+use crate::render::map_columns_on_target;
+use image::{Rgba, RgbaImage};
+use rand::{Rng, RngCore};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// How many points of the canvas the layout measures cell areas on, at most.
+const LAYOUT_SAMPLES: f64 = 40_000f64;
+
+/// A cell of a power diagram: pixels belong to the site with the smallest
+/// squared distance minus `weight`, so heavier sites claim more of the canvas.
+#[derive(Clone)]
+pub struct PowerSite {
+    pub point: Point,
+    pub weight: f64,
+}
+
+/// Reduces `image` to at most `count` colors and measures the share of its
+/// opaque pixels every one of them covers, from `0` to `1`.
+///
+/// Pixels are binned to 5 bits per channel before quantizing, so large
+/// photographs cluster as fast as small ones.
+pub fn color_shares(image: &RgbaImage, count: usize) -> (Palette, Vec<f64>) {
+    let mut bins: HashMap<[u8; 3], (f64, [f64; 3])> = HashMap::new();
+    for pixel in image.pixels().filter(|pixel| pixel[3] > 0) {
+        let key = [pixel[0] >> 3, pixel[1] >> 3, pixel[2] >> 3];
+        let (total, sums) = bins.entry(key).or_insert((0f64, [0f64; 3]));
+        *total += 1f64;
+        for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+            *sum += channel as f64;
+        }
+    }
+
+    let (colors, weights): (Vec<Rgba<u8>>, Vec<f64>) = bins
+        .values()
+        .map(|(total, sums)| {
+            let [red, green, blue] = sums.map(|sum| (sum / total).round() as u8);
+            (Rgba([red, green, blue, u8::MAX]), *total)
+        })
+        .unzip();
+    let (palette, assignments) = Palette::quantize(&colors, &weights, count);
+
+    let mut shares = vec![0f64; palette.colors.len()];
+    for (assignment, weight) in assignments.iter().zip(&weights) {
+        shares[*assignment] += weight;
+    }
+    let total: f64 = shares.iter().sum();
+    if total > 0f64 {
+        for share in shares.iter_mut() {
+            *share /= total;
+        }
+    }
+
+    (palette, shares)
+}
+
+fn nearest_site(sites: &[PowerSite], point: &Point) -> usize {
+    (0..sites.len())
+        .min_by(|a, b| {
+            let a = point.squared_distance_from(&sites[*a].point) - sites[*a].weight;
+            let b = point.squared_distance_from(&sites[*b].point) - sites[*b].weight;
+            a.total_cmp(&b)
+        })
+        .unwrap_or(0)
+}
+
+/// Lays out one power diagram cell per entry of `shares` over `bounds`, with
+/// areas in proportion to the shares, like a Voronoi treemap.
+///
+/// Every iteration moves the sites to the centroid of their cell and grows or
+/// shrinks their weights by how far their area is from its target, stopping
+/// early once every cell is within 1% of it. Returns the sites and the largest
+/// area error left, as a fraction of the targets.
+pub fn area_weighted_layout(
+    bounds: &Bounds,
+    shares: &[f64],
+    iterations: u32,
+    rng: &mut dyn RngCore,
+) -> (Vec<PowerSite>, f64) {
+    let (width, height) = (bounds.width as f64, bounds.height as f64);
+    let step = ((width * height) / LAYOUT_SAMPLES).sqrt().max(1f64);
+    let samples: Vec<Point> = (0..((width / step).ceil() as u64))
+        .flat_map(|column| {
+            (0..((height / step).ceil() as u64)).map(move |row| Point {
+                x: ((column as f64) + 0.5f64) * step,
+                y: ((row as f64) + 0.5f64) * step,
+            })
+        })
+        .filter(|sample| sample.x < width && sample.y < height)
+        .collect();
+    let sample_area = (width * height) / (samples.len().max(1) as f64);
+    let targets: Vec<f64> = shares.iter().map(|share| share * width * height).collect();
+
+    let mut sites: Vec<PowerSite> = targets
+        .iter()
+        .map(|target| PowerSite {
+            point: Point {
+                x: rng.gen_range(0f64..width),
+                y: rng.gen_range(0f64..height),
+            },
+            weight: (target / PI) * 0.1f64,
+        })
+        .collect();
+
+    let mut error = f64::INFINITY;
+    for _ in 0..iterations {
+        let mut sums = vec![(0f64, 0f64, 0f64); sites.len()];
+        for sample in &samples {
+            let sum = &mut sums[nearest_site(&sites, sample)];
+            sum.0 += sample.x;
+            sum.1 += sample.y;
+            sum.2 += 1f64;
+        }
+
+        error = 0f64;
+        for ((site, (x, y, count)), target) in sites.iter_mut().zip(&sums).zip(&targets) {
+            let area = count * sample_area;
+            if *count > 0f64 {
+                site.point = Point {
+                    x: x / count,
+                    y: y / count,
+                };
+            }
+            if *target > 0f64 {
+                error = error.max((area - target).abs() / target);
+            }
+            site.weight += (target - area) / PI;
+        }
+        if error < 0.01f64 {
+            break;
+        }
+    }
+
+    (sites, error)
+}
+
+/// Paints the power diagram of `sites` over a `width`×`height` canvas, every
+/// cell filled with the color of its site.
+pub fn paint_power_diagram(
+    width: u32,
+    height: u32,
+    sites: &[PowerSite],
+    colors: &[Rgba<u8>],
+) -> RgbaImage {
+    let columns = map_columns_on_target(width, |x| {
+        (0..height)
+            .map(|y| {
+                let pixel = Point {
+                    x: (x as f64) + 0.5f64,
+                    y: (y as f64) + 0.5f64,
+                };
+                colors[nearest_site(sites, &pixel)]
+            })
+            .collect::<Vec<Rgba<u8>>>()
+    });
+
+    let mut output_image_buffer = RgbaImage::new(width, height);
+    for (x, column) in columns.iter().enumerate() {
+        for (y, color) in column.iter().enumerate() {
+            output_image_buffer.put_pixel(x as u32, y as u32, *color);
+        }
+    }
+
+    output_image_buffer
+}
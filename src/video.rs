@@ -0,0 +1,226 @@
+//! Reading and writing video by streaming raw RGBA frames through the system
+//! `ffmpeg`, so no codecs have to be built in. `ffprobe` tells the size and
+//! frame rate of a clip.
+
+use image::RgbaImage;
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// What `ffprobe` tells about the first video stream of a clip.
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second as `ffmpeg` writes them, such as `30000/1001`.
+    pub frame_rate: String,
+}
+
+fn ffmpeg_error(tool: &str, error: io::Error) -> io::Error {
+    match error.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "`{}` was not found; install ffmpeg to read and write video",
+                tool
+            ),
+        ),
+        _ => error,
+    }
+}
+
+pub fn probe(path: &str) -> io::Result<VideoInfo> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,r_frame_rate"])
+        .args(["-of", "json", "--"])
+        .arg(path)
+        .output()
+        .map_err(|error| ffmpeg_error("ffprobe", error))?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "no video stream found");
+    let probed: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|_| invalid())?;
+    let stream = probed["streams"].get(0).ok_or_else(invalid)?;
+    let dimension = |key: &str| {
+        stream[key]
+            .as_u64()
+            .filter(|dimension| *dimension > 0)
+            .map(|dimension| dimension as u32)
+    };
+
+    Ok(VideoInfo {
+        width: dimension("width").ok_or_else(invalid)?,
+        height: dimension("height").ok_or_else(invalid)?,
+        frame_rate: stream["r_frame_rate"]
+            .as_str()
+            .filter(|rate| !rate.starts_with('0'))
+            .unwrap_or("25")
+            .to_string(),
+    })
+}
+
+/// Waits for `ffmpeg` to end, failing with what it wrote to stderr if it
+/// did not succeed.
+fn wait(child: Child) -> io::Result<()> {
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// The frames of a clip, decoded one at a time.
+pub struct FrameReader {
+    child: Option<Child>,
+    stdout: ChildStdout,
+    width: u32,
+    height: u32,
+}
+
+impl FrameReader {
+    /// Decodes the clip at `path`, resampled to `frame_rate` frames per
+    /// second if given, at the size `info` was probed with.
+    pub fn open(path: &str, info: &VideoInfo, frame_rate: Option<&str>) -> io::Result<FrameReader> {
+        let mut command = Command::new("ffmpeg");
+        command.args(["-v", "error", "-nostdin", "-i"]).arg(path);
+        if let Some(frame_rate) = frame_rate {
+            command.arg("-vf").arg(format!("fps={}", frame_rate));
+        }
+        let mut child = command
+            .args(["-map", "0:v:0", "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| ffmpeg_error("ffmpeg", error))?;
+        let stdout = child.stdout.take().unwrap();
+
+        Ok(FrameReader {
+            child: Some(child),
+            stdout,
+            width: info.width,
+            height: info.height,
+        })
+    }
+}
+
+impl Iterator for FrameReader {
+    type Item = io::Result<RgbaImage>;
+
+    fn next(&mut self) -> Option<io::Result<RgbaImage>> {
+        let child = self.child.take()?;
+        let mut pixels = vec![0u8; (self.width as usize) * (self.height as usize) * 4];
+        let mut filled = 0;
+        while filled < pixels.len() {
+            match self.stdout.read(&mut pixels[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Some(Err(error)),
+            }
+        }
+
+        if filled < pixels.len() {
+            // The clip ended; a trailing partial frame is dropped.
+            return wait(child).err().map(Err);
+        }
+        self.child = Some(child);
+        RgbaImage::from_raw(self.width, self.height, pixels).map(Ok)
+    }
+}
+
+/// A clip being encoded from frames written one at a time.
+pub struct FrameWriter {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    width: u32,
+    height: u32,
+}
+
+impl FrameWriter {
+    /// Encodes `width`×`height` frames at `frame_rate` into `path`, in
+    /// `yuv420p` so common players can show it, padding odd sizes by a
+    /// pixel. The audio of `audio_from` is copied in, if it has any.
+    pub fn create(
+        path: &str,
+        width: u32,
+        height: u32,
+        frame_rate: &str,
+        audio_from: Option<&str>,
+    ) -> io::Result<FrameWriter> {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-v", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s")
+            .arg(format!("{}x{}", width, height))
+            .arg("-r")
+            .arg(frame_rate)
+            .args(["-i", "-"]);
+        if let Some(audio_from) = audio_from {
+            command.arg("-i").arg(audio_from).args([
+                "-map",
+                "0:v",
+                "-map",
+                "1:a?",
+                "-c:a",
+                "copy",
+                "-shortest",
+            ]);
+        }
+        let mut child = command
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| ffmpeg_error("ffmpeg", error))?;
+        let stdin = child.stdin.take();
+
+        Ok(FrameWriter {
+            child,
+            stdin,
+            width,
+            height,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &RgbaImage) -> io::Result<()> {
+        if frame.dimensions() != (self.width, self.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame is {}x{}, but the video is {}x{}",
+                    frame.width(),
+                    frame.height(),
+                    self.width,
+                    self.height
+                ),
+            ));
+        }
+
+        match &mut self.stdin {
+            Some(stdin) => stdin.write_all(frame.as_raw()),
+            None => Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+        }
+    }
+
+    /// Ends the clip and waits for `ffmpeg` to finish writing it.
+    pub fn finish(mut self) -> io::Result<()> {
+        drop(self.stdin.take());
+        wait(self.child)
+    }
+}
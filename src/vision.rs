@@ -0,0 +1,138 @@
+//! Keeping neighbouring cells apart for colorblind viewers: colors that a
+//! color vision deficiency would make look the same are nudged apart in
+//! lightness, which every deficiency still sees.
+
+use crate::color::ColorSpace;
+use crate::merge::neighbouring_cells;
+use crate::render::CellMap;
+use image::Rgba;
+use std::collections::HashSet;
+
+/// OKLab distance below which two colors count as hard to tell apart.
+const DISTINGUISHABLE: f64 = 0.08;
+/// Rounds of nudging, since moving apart one pair can bring another closer.
+const ROUNDS: usize = 32;
+
+/// A color vision deficiency [`separate_confused_cells`] keeps cells
+/// readable for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorVision {
+    /// No working green cones.
+    Deuteranopia,
+    /// No working red cones.
+    Protanopia,
+    /// No working blue cones.
+    Tritanopia,
+}
+
+impl ColorVision {
+    pub fn from_name(name: &str) -> Option<ColorVision> {
+        match name {
+            "deuteranopia" => Some(ColorVision::Deuteranopia),
+            "protanopia" => Some(ColorVision::Protanopia),
+            "tritanopia" => Some(ColorVision::Tritanopia),
+            _ => None,
+        }
+    }
+
+    /// The linear RGB transform of Machado, Oliveira and Fernandes (2009)
+    /// simulating the deficiency at full severity.
+    fn simulation(self) -> [[f64; 3]; 3] {
+        match self {
+            ColorVision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    /// How `color` looks with this deficiency.
+    pub fn simulate(self, color: Rgba<u8>) -> Rgba<u8> {
+        let linear = ColorSpace::Linear.encode(color);
+        let simulated = self
+            .simulation()
+            .map(|row| (row[0] * linear[0]) + (row[1] * linear[1]) + (row[2] * linear[2]));
+        let [red, green, blue] = ColorSpace::Linear.decode(simulated);
+
+        Rgba([red, green, blue, color.0[3]])
+    }
+}
+
+/// Nudges apart the lightness of neighbouring cells whose colors are told
+/// apart with normal vision but not with `vision`, in OKLab, until they are
+/// or the rounds run out.
+pub fn separate_confused_cells(cell_map: &CellMap, colors: &mut [Rgba<u8>], vision: ColorVision) {
+    separate_confused_neighbours(&neighbouring_cells(cell_map), colors, vision);
+}
+
+/// Like [`separate_confused_cells`], for cells touching as `neighbours` do,
+/// from [`neighbouring_cells`].
+pub(crate) fn separate_confused_neighbours(
+    neighbours: &HashSet<(u32, u32)>,
+    colors: &mut [Rgba<u8>],
+    vision: ColorVision,
+) {
+    let mut pairs: Vec<(usize, usize)> = neighbours
+        .iter()
+        .map(|&(from, to)| (from as usize, to as usize))
+        .filter(|(from, to)| {
+            ColorSpace::Oklab.squared_distance(colors[*from], colors[*to])
+                >= DISTINGUISHABLE * DISTINGUISHABLE
+        })
+        .collect();
+    // Sorted so the nudges do not depend on the order of the hash set.
+    pairs.sort_unstable();
+
+    let mut coordinates: Vec<[f64; 3]> = colors
+        .iter()
+        .map(|color| ColorSpace::Oklab.encode(*color))
+        .collect();
+    let color_of = |coordinates: [f64; 3], alpha: u8| {
+        let [red, green, blue] = ColorSpace::Oklab.decode(coordinates);
+        Rgba([red, green, blue, alpha])
+    };
+    for _ in 0..ROUNDS {
+        let mut nudged = false;
+        for (from, to) in &pairs {
+            let (from, to) = (*from, *to);
+            let seen_from = ColorSpace::Oklab.encode(vision.simulate(colors[from]));
+            let seen_to = ColorSpace::Oklab.encode(vision.simulate(colors[to]));
+            let distance = seen_from
+                .iter()
+                .zip(&seen_to)
+                .map(|(from, to)| (from - to) * (from - to))
+                .sum::<f64>()
+                .sqrt();
+            if distance >= DISTINGUISHABLE {
+                continue;
+            }
+
+            // The cell that looks lighter gets lighter still, the other darker.
+            let (lighter, darker) = if seen_from[0] >= seen_to[0] {
+                (from, to)
+            } else {
+                (to, from)
+            };
+            let step = (DISTINGUISHABLE - distance) / 2f64;
+            coordinates[lighter][0] = (coordinates[lighter][0] + step).min(1f64);
+            coordinates[darker][0] = (coordinates[darker][0] - step).max(0f64);
+            colors[lighter] = color_of(coordinates[lighter], colors[lighter].0[3]);
+            colors[darker] = color_of(coordinates[darker], colors[darker].0[3]);
+            nudged = true;
+        }
+        if !nudged {
+            break;
+        }
+    }
+}
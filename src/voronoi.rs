@@ -0,0 +1,362 @@
+//! Exact euclidean voronoi cells as polygons.
+//!
+//! Every cell is the intersection of the image rectangle with the half-planes
+//! closer to its anchor than to each neighbour. Neighbours are visited ring by
+//! ring on a uniform grid, so only anchors that can still cut the cell are
+//! considered.
+
+use crate::anchors::Anchor;
+use crate::geometry::{Bounds, Point};
+
+struct AnchorGrid {
+    cell_size: f64,
+    columns: i64,
+    rows: i64,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl AnchorGrid {
+    fn new(anchors: &[Anchor], bounds: &Bounds) -> AnchorGrid {
+        let area = ((bounds.width * bounds.height) as f64).max(1f64);
+        let cell_size = (area / (anchors.len().max(1) as f64)).sqrt().max(1f64);
+        let columns = (((bounds.width as f64) / cell_size).ceil() as i64).max(1);
+        let rows = (((bounds.height as f64) / cell_size).ceil() as i64).max(1);
+
+        let mut grid = AnchorGrid {
+            cell_size,
+            columns,
+            rows,
+            buckets: vec![Vec::new(); (columns * rows) as usize],
+        };
+        for (index, anchor) in anchors.iter().enumerate() {
+            let (column, row) = grid.bucket_of(&anchor.point);
+            grid.buckets[((row * columns) + column) as usize].push(index);
+        }
+
+        grid
+    }
+
+    fn bucket_of(&self, point: &Point) -> (i64, i64) {
+        (
+            ((point.x / self.cell_size).floor() as i64).clamp(0, self.columns - 1),
+            ((point.y / self.cell_size).floor() as i64).clamp(0, self.rows - 1),
+        )
+    }
+
+    /// Anchors in the buckets at exactly `ring` steps (Chebyshev distance)
+    /// from `(column, row)`.
+    fn ring(&self, column: i64, row: i64, ring: i64) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for bucket_row in (row - ring)..=(row + ring) {
+            for bucket_column in (column - ring)..=(column + ring) {
+                let on_ring =
+                    ((bucket_row - row).abs() == ring) || ((bucket_column - column).abs() == ring);
+                if !on_ring
+                    || !(0..self.columns).contains(&bucket_column)
+                    || !(0..self.rows).contains(&bucket_row)
+                {
+                    continue;
+                }
+                indices
+                    .extend(&self.buckets[((bucket_row * self.columns) + bucket_column) as usize]);
+            }
+        }
+
+        indices
+    }
+}
+
+/// Keeps the part of `polygon` where `side` is not positive.
+fn clip_by_half_plane<F>(polygon: &[Point], side: F) -> Vec<Point>
+where
+    F: Fn(&Point) -> f64,
+{
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (index, current) in polygon.iter().enumerate() {
+        let next = &polygon[(index + 1) % polygon.len()];
+        let (current_side, next_side) = (side(current), side(next));
+
+        if current_side <= 0f64 {
+            clipped.push(current.clone());
+        }
+        if (current_side <= 0f64) != (next_side <= 0f64) {
+            let t = current_side / (current_side - next_side);
+            clipped.push(Point {
+                x: current.x + ((next.x - current.x) * t),
+                y: current.y + ((next.y - current.y) * t),
+            });
+        }
+    }
+
+    clipped
+}
+
+/// Keeps the part of `polygon` closer to `anchor` than to `neighbour`.
+fn clip_by_bisector(polygon: &[Point], anchor: &Point, neighbour: &Point) -> Vec<Point> {
+    let normal = Point {
+        x: neighbour.x - anchor.x,
+        y: neighbour.y - anchor.y,
+    };
+    let offset = ((neighbour.x * neighbour.x) + (neighbour.y * neighbour.y)
+        - (anchor.x * anchor.x)
+        - (anchor.y * anchor.y))
+        / 2f64;
+
+    clip_by_half_plane(polygon, |point| {
+        ((normal.x * point.x) + (normal.y * point.y)) - offset
+    })
+}
+
+/// Clips any polygon to a convex one running the same way as the cells of
+/// [`cell_polygons`].
+///
+/// A subject that the convex polygon cuts into several pieces comes back as
+/// one ring whose pieces are joined by zero-width seams.
+pub fn clip_to_convex(subject: &[Point], convex: &[Point]) -> Vec<Point> {
+    let mut clipped = subject.to_vec();
+    for (index, start) in convex.iter().enumerate() {
+        if clipped.is_empty() {
+            break;
+        }
+        let end = &convex[(index + 1) % convex.len()];
+        clipped = clip_by_half_plane(&clipped, |point| {
+            ((start.x - end.x) * (point.y - start.y)) - ((start.y - end.y) * (point.x - start.x))
+        });
+    }
+
+    clipped
+}
+
+/// Intersects every cell with a shape given as rings, such as
+/// [`crate::mask::ShapeMask::outline`], dropping pieces without area.
+pub fn clip_cells_to_rings(cells: &[Vec<Point>], rings: &[Vec<Point>]) -> Vec<Vec<Vec<Point>>> {
+    cells
+        .iter()
+        .map(|cell| {
+            if cell.len() < 3 {
+                return Vec::new();
+            }
+            rings
+                .iter()
+                .map(|ring| clip_to_convex(ring, cell))
+                .filter(|ring| (ring.len() >= 3) && (polygon_area(ring) > f64::EPSILON))
+                .collect()
+        })
+        .collect()
+}
+
+/// Groups rings into polygons: every outer ring followed by the holes (rings
+/// running the other way) that lie inside it.
+pub fn group_rings(rings: &[Vec<Point>]) -> Vec<Vec<Vec<Point>>> {
+    let (outers, holes): (Vec<&Vec<Point>>, Vec<&Vec<Point>>) = rings
+        .iter()
+        .partition(|ring| signed_double_area(ring) > 0f64);
+
+    let mut polygons: Vec<Vec<Vec<Point>>> =
+        outers.iter().map(|outer| vec![(*outer).clone()]).collect();
+    for hole in holes {
+        // Clipped holes often start on the edge of their outer ring, so test
+        // the average of their vertices instead.
+        let count = hole.len() as f64;
+        let probe = Point {
+            x: hole.iter().map(|point| point.x).sum::<f64>() / count,
+            y: hole.iter().map(|point| point.y).sum::<f64>() / count,
+        };
+        let container = match polygons.len() {
+            1 => polygons.first_mut(),
+            _ => polygons
+                .iter_mut()
+                .find(|polygon| contains_point(&polygon[0], &probe)),
+        };
+        if let Some(polygon) = container {
+            polygon.push(hole.clone());
+        }
+    }
+
+    polygons
+}
+
+/// Even-odd test of whether `point` lies inside `ring`.
+fn contains_point(ring: &[Point], point: &Point) -> bool {
+    let mut is_inside = false;
+    for (index, current) in ring.iter().enumerate() {
+        let next = &ring[(index + 1) % ring.len()];
+        if (current.y > point.y) != (next.y > point.y) {
+            let crossing_x =
+                current.x + (((point.y - current.y) / (next.y - current.y)) * (next.x - current.x));
+            if point.x < crossing_x {
+                is_inside = !is_inside;
+            }
+        }
+    }
+
+    is_inside
+}
+
+/// Computes the polygon of every anchor's cell, clipped to `bounds`, in the
+/// same order as `anchors`.
+///
+/// Vertices run clockwise on screen (with `y` pointing down). A cell is empty
+/// when its anchor lies outside the bounds or shares its position with an
+/// earlier anchor.
+pub fn cell_polygons(anchors: &[Anchor], bounds: &Bounds) -> Vec<Vec<Point>> {
+    let grid = AnchorGrid::new(anchors, bounds);
+    let (width, height) = (bounds.width as f64, bounds.height as f64);
+    let rectangle = vec![
+        Point { x: 0f64, y: 0f64 },
+        Point { x: width, y: 0f64 },
+        Point {
+            x: width,
+            y: height,
+        },
+        Point { x: 0f64, y: height },
+    ];
+
+    anchors
+        .iter()
+        .enumerate()
+        .map(|(index, anchor)| {
+            let point = &anchor.point;
+            if (point.x < 0f64) || (point.y < 0f64) || (point.x > width) || (point.y > height) {
+                return Vec::new();
+            }
+
+            let (column, row) = grid.bucket_of(point);
+            let mut polygon = rectangle.clone();
+            let mut ring = 0i64;
+            loop {
+                let reach = polygon
+                    .iter()
+                    .map(|vertex| vertex.squared_distance_from(point))
+                    .fold(0f64, f64::max)
+                    .sqrt();
+                let ring_distance = ((ring - 1).max(0) as f64) * grid.cell_size;
+                if (polygon.is_empty())
+                    || (ring_distance > 2f64 * reach)
+                    || (ring > grid.columns.max(grid.rows))
+                {
+                    break;
+                }
+
+                for neighbour in grid.ring(column, row, ring) {
+                    if neighbour == index {
+                        continue;
+                    }
+                    let neighbour_point = &anchors[neighbour].point;
+                    if neighbour_point.squared_distance_from(point) == 0f64 {
+                        if neighbour < index {
+                            polygon.clear();
+                        }
+                        continue;
+                    }
+                    polygon = clip_by_bisector(&polygon, point, neighbour_point);
+                    if polygon.is_empty() {
+                        break;
+                    }
+                }
+                ring += 1;
+            }
+
+            polygon
+        })
+        .collect()
+}
+
+/// Area enclosed by `polygon`, whichever way its vertices run.
+pub fn polygon_area(polygon: &[Point]) -> f64 {
+    (signed_double_area(polygon) / 2f64).abs()
+}
+
+fn signed_double_area(polygon: &[Point]) -> f64 {
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(current, next)| (current.x * next.y) - (next.x * current.y))
+        .sum()
+}
+
+/// Moves every edge of `ring` `distance` towards the inside of the shape it
+/// bounds, which for holes widens the hole, and joins the shifted edges at
+/// their intersections. Returns `None` when the ring vanishes.
+pub fn inset_ring(ring: &[Point], distance: f64) -> Option<Vec<Point>> {
+    let mut points: Vec<&Point> = Vec::with_capacity(ring.len());
+    for point in ring {
+        let is_repeated = points
+            .last()
+            .map(|last| last.squared_distance_from(point) < 1e-12)
+            .unwrap_or(false);
+        if !is_repeated {
+            points.push(point);
+        }
+    }
+    while (points.len() > 1) && (points[0].squared_distance_from(points[points.len() - 1]) < 1e-12)
+    {
+        points.pop();
+    }
+    if points.len() < 3 {
+        return None;
+    }
+
+    // The shape lies to the left of every edge, for outer rings and holes.
+    let left_normal = |from: &Point, to: &Point| {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        let length = ((dx * dx) + (dy * dy)).sqrt();
+        (-dy / length, dx / length)
+    };
+
+    let count = points.len();
+    let inset: Vec<Point> = (0..count)
+        .map(|index| {
+            let previous = points[(index + count - 1) % count];
+            let current = points[index];
+            let next = points[(index + 1) % count];
+            let (first, second) = (left_normal(previous, current), left_normal(current, next));
+
+            // The shifted edges meet `distance / cos(half the turn)` away
+            // along the bisector; very sharp corners are capped.
+            let bisector = (first.0 + second.0, first.1 + second.1);
+            let cosine = ((first.0 * second.0) + (first.1 * second.1)).max(-1f64);
+            let half_cosine = ((1f64 + cosine) / 2f64).sqrt().max(0.25f64);
+            let length = ((bisector.0 * bisector.0) + (bisector.1 * bisector.1)).sqrt();
+            let (x, y) = if length < 1e-9 {
+                (first.0, first.1)
+            } else {
+                (bisector.0 / length, bisector.1 / length)
+            };
+
+            Point {
+                x: current.x + ((x * distance) / half_cosine),
+                y: current.y + ((y * distance) / half_cosine),
+            }
+        })
+        .collect();
+
+    let original: Vec<Point> = points.into_iter().cloned().collect();
+    let (before, after) = (signed_double_area(&original), signed_double_area(&inset));
+    // Insetting a ring past its middle turns it inside out.
+    ((before.signum() == after.signum()) && (after.abs() > f64::EPSILON)).then_some(inset)
+}
+
+/// Center of mass of `polygon`, or `None` when it has no area.
+pub fn polygon_centroid(polygon: &[Point]) -> Option<Point> {
+    let double_area = signed_double_area(polygon);
+    if double_area.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let (x, y) = polygon.iter().zip(polygon.iter().cycle().skip(1)).fold(
+        (0f64, 0f64),
+        |(x, y), (current, next)| {
+            let cross = (current.x * next.y) - (next.x * current.y);
+            (
+                x + ((current.x + next.x) * cross),
+                y + ((current.y + next.y) * cross),
+            )
+        },
+    );
+
+    Some(Point {
+        x: x / (3f64 * double_area),
+        y: y / (3f64 * double_area),
+    })
+}
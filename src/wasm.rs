@@ -0,0 +1,73 @@
+//! JavaScript bindings for running the painter in the browser.
+//!
+//! Build with `cargo build --lib --target wasm32-unknown-unknown --features wasm`
+//! and generate the glue code with `wasm-bindgen`.
+
+use crate::anchors::color_anchor_points;
+use crate::geometry::Bounds;
+use crate::render::{render_voronoi, RenderOptions};
+use crate::sampling::generate_anchor_points;
+use image::RgbaImage;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct PaintOptions {
+    pub minimum_distance: u32,
+}
+
+#[wasm_bindgen]
+impl PaintOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PaintOptions {
+        PaintOptions {
+            minimum_distance: 10,
+        }
+    }
+}
+
+impl Default for PaintOptions {
+    fn default() -> Self {
+        PaintOptions::new()
+    }
+}
+
+/// Converts RGBA bytes of a `width`×`height` image into the RGBA bytes of its
+/// voronoi diagram.
+#[wasm_bindgen]
+pub fn paint(
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+    options: &PaintOptions,
+) -> Result<Vec<u8>, JsValue> {
+    if options.minimum_distance == 0 {
+        return Err(JsValue::from_str("minimum_distance must be greater than 0"));
+    }
+
+    let input_image = match RgbaImage::from_raw(width, height, rgba) {
+        None => {
+            return Err(JsValue::from_str(
+                "Length of the RGBA buffer does not match width * height * 4",
+            ));
+        }
+        Some(input_image) => input_image,
+    };
+
+    let bounds = Bounds {
+        width: width as u64,
+        height: height as u64,
+    };
+    let anchor_points = generate_anchor_points(&bounds, options.minimum_distance);
+    let anchors = color_anchor_points(&input_image, anchor_points);
+
+    let output_image = render_voronoi(
+        &input_image,
+        &anchors,
+        &RenderOptions {
+            minimum_distance: options.minimum_distance,
+            ..RenderOptions::default()
+        },
+    );
+
+    Ok(output_image.into_raw())
+}
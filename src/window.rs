@@ -0,0 +1,328 @@
+//! Desktop windows: a live preview that fills in columns as the render
+//! assigns them, and an interactive anchor editor.
+
+use crate::anchors::color_anchor_points;
+use crate::geometry::Point;
+use crate::incremental::IncrementalRenderer;
+use crate::render::{RenderObserver, RenderOptions, UNASSIGNED};
+use image::{imageops, Rgba, RgbaImage};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, ScaleMode, Window, WindowOptions};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+const MAXIMUM_WINDOW_SIZE: (u32, u32) = (1280, 800);
+const ANCHOR_MARKER_RADIUS: i64 = 3;
+const ANCHOR_PICK_RADIUS: f64 = 6f64;
+
+struct ChannelObserver {
+    columns: Sender<(u32, Vec<u32>)>,
+    cancelled: AtomicBool,
+}
+
+impl RenderObserver for ChannelObserver {
+    fn column_assigned(&self, x: u32, labels: &[u32]) {
+        self.columns.send((x, labels.to_vec())).ok();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+fn pack_pixel(color: Rgba<u8>) -> u32 {
+    let [red, green, blue, _] = color.0;
+    ((red as u32) << 16) | ((green as u32) << 8) | (blue as u32)
+}
+
+fn window_size(width: u32, height: u32) -> (usize, usize) {
+    let scale = ((MAXIMUM_WINDOW_SIZE.0 as f64) / (width as f64))
+        .min((MAXIMUM_WINDOW_SIZE.1 as f64) / (height as f64))
+        .min(1f64);
+
+    (
+        ((width as f64) * scale).round().max(1f64) as usize,
+        ((height as f64) * scale).round().max(1f64) as usize,
+    )
+}
+
+/// Runs `render` on a worker thread while a window on the calling thread
+/// shows every assigned column, colored with `preview_colors` by cell, and
+/// finally the finished image until the window is closed.
+///
+/// Closing the window before the render completes cancels it and returns
+/// `None`.
+pub fn watch_render<F>(
+    width: u32,
+    height: u32,
+    preview_colors: &[Rgba<u8>],
+    render: F,
+) -> minifb::Result<Option<RgbaImage>>
+where
+    F: FnOnce(&dyn RenderObserver) -> RgbaImage + Send,
+{
+    let (window_width, window_height) = window_size(width, height);
+    let mut window = Window::new(
+        "voronoi-painter",
+        window_width,
+        window_height,
+        WindowOptions {
+            resize: true,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        },
+    )?;
+    window.limit_update_rate(Some(Duration::from_millis(30)));
+
+    let (buffer_width, buffer_height) = (width as usize, height as usize);
+    let mut buffer = vec![0u32; buffer_width * buffer_height];
+
+    let (sender, receiver) = mpsc::channel();
+    let observer = ChannelObserver {
+        columns: sender,
+        cancelled: AtomicBool::new(false),
+    };
+
+    let mut failure = None;
+    let output_image = thread::scope(|scope| {
+        let observer = &observer;
+        let handle = scope.spawn(move || render(observer));
+
+        while !handle.is_finished() {
+            for (x, labels) in receiver.try_iter() {
+                for (y, label) in labels.into_iter().enumerate() {
+                    if label != UNASSIGNED {
+                        buffer[(y * buffer_width) + (x as usize)] =
+                            pack_pixel(preview_colors[label as usize]);
+                    }
+                }
+            }
+
+            if observer.is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            if !window.is_open() || window.is_key_down(Key::Escape) {
+                observer.cancelled.store(true, Ordering::Relaxed);
+            } else if let Err(error) =
+                window.update_with_buffer(&buffer, buffer_width, buffer_height)
+            {
+                observer.cancelled.store(true, Ordering::Relaxed);
+                failure = Some(error);
+            }
+        }
+
+        match handle.join() {
+            Ok(output_image) => output_image,
+            Err(message) => panic::resume_unwind(message),
+        }
+    });
+
+    if let Some(error) = failure {
+        return Err(error);
+    }
+    if observer.is_cancelled() {
+        return Ok(None);
+    }
+
+    let (output_width, output_height) = output_image.dimensions();
+    let buffer: Vec<u32> = output_image
+        .pixels()
+        .map(|color| pack_pixel(*color))
+        .collect();
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        window.update_with_buffer(&buffer, output_width as usize, output_height as usize)?;
+    }
+
+    Ok(Some(output_image))
+}
+
+fn draw_marker(buffer: &mut [u32], width: usize, height: usize, x: f64, y: f64, fill: u32) {
+    let (center_x, center_y) = (x.round() as i64, y.round() as i64);
+    for dy in -ANCHOR_MARKER_RADIUS..=ANCHOR_MARKER_RADIUS {
+        for dx in -ANCHOR_MARKER_RADIUS..=ANCHOR_MARKER_RADIUS {
+            let (pixel_x, pixel_y) = (center_x + dx, center_y + dy);
+            if (pixel_x < 0)
+                || (pixel_y < 0)
+                || (pixel_x >= width as i64)
+                || (pixel_y >= height as i64)
+            {
+                continue;
+            }
+
+            let is_outline =
+                (dx.abs() == ANCHOR_MARKER_RADIUS) || (dy.abs() == ANCHOR_MARKER_RADIUS);
+            buffer[((pixel_y as usize) * width) + (pixel_x as usize)] =
+                if is_outline { 0x000000 } else { fill };
+        }
+    }
+}
+
+fn closest_marker(anchor_points: &[Point], cursor: &Point, scale: f64) -> Option<usize> {
+    anchor_points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| {
+            let on_screen = Point {
+                x: point.x * scale,
+                y: point.y * scale,
+            };
+            (index, on_screen.squared_distance_from(cursor))
+        })
+        .filter(|(_, squared_distance)| *squared_distance <= ANCHOR_PICK_RADIUS.powi(2))
+        .min_by(|(_, left), (_, right)| left.total_cmp(right))
+        .map(|(index, _)| index)
+}
+
+/// Opens an editor showing `image` with its anchors drawn on top.
+///
+/// Left click on empty space adds an anchor, dragging an anchor moves it and
+/// right click (or Delete over an anchor) removes it. Tab toggles a live view
+/// of the tessellation, rendered with `options`, which is updated
+/// incrementally as anchors change. `S` calls `on_save` with the current
+/// anchors. The edited anchors are returned when the window is closed.
+pub fn edit_anchors<F>(
+    image: &RgbaImage,
+    anchor_points: Vec<Point>,
+    options: &RenderOptions,
+    mut on_save: F,
+) -> minifb::Result<Vec<Point>>
+where
+    F: FnMut(&[Point]),
+{
+    let (image_width, image_height) = image.dimensions();
+    let (window_width, window_height) = window_size(image_width, image_height);
+    let scale = (window_width as f64) / (image_width as f64);
+
+    let preview_image = imageops::resize(
+        image,
+        window_width as u32,
+        window_height as u32,
+        imageops::FilterType::Triangle,
+    );
+    let background: Vec<u32> = preview_image
+        .pixels()
+        .map(|color| pack_pixel(*color))
+        .collect();
+
+    let to_preview = |point: &Point| Point {
+        x: (point.x * scale).min((window_width as f64) - 1f64),
+        y: (point.y * scale).min((window_height as f64) - 1f64),
+    };
+    let preview_options = RenderOptions {
+        minimum_distance: (((options.minimum_distance as f64) * scale).ceil() as u32).max(1),
+        ..*options
+    };
+    let mut cells = IncrementalRenderer::new(
+        &preview_image,
+        color_anchor_points(
+            &preview_image,
+            anchor_points.iter().map(to_preview).collect(),
+        ),
+        &preview_options,
+    );
+    let mut shows_cells = false;
+
+    let mut window = Window::new(
+        "voronoi-painter anchors",
+        window_width,
+        window_height,
+        WindowOptions::default(),
+    )?;
+    window.limit_update_rate(Some(Duration::from_millis(16)));
+
+    let mut anchor_points = anchor_points;
+    let mut dragged: Option<usize> = None;
+    let mut was_left_down = false;
+    let mut was_right_down = false;
+    let mut buffer = background.clone();
+
+    while window.is_open() {
+        let cursor = window
+            .get_mouse_pos(MouseMode::Discard)
+            .map(|(x, y)| Point {
+                x: x as f64,
+                y: y as f64,
+            });
+        let is_left_down = window.get_mouse_down(MouseButton::Left);
+        let is_right_down = window.get_mouse_down(MouseButton::Right);
+        let hovered = cursor
+            .as_ref()
+            .and_then(|cursor| closest_marker(&anchor_points, cursor, scale));
+
+        if let Some(cursor) = &cursor {
+            let image_point = Point {
+                x: (cursor.x / scale).clamp(0f64, (image_width as f64) - 1f64),
+                y: (cursor.y / scale).clamp(0f64, (image_height as f64) - 1f64),
+            };
+
+            match (is_left_down, was_left_down, dragged) {
+                (true, false, _) => match hovered {
+                    Some(index) => dragged = Some(index),
+                    None => {
+                        cells.add_anchor(to_preview(&image_point));
+                        anchor_points.push(image_point);
+                        dragged = Some(anchor_points.len() - 1);
+                    }
+                },
+                (true, true, Some(index)) => {
+                    cells.move_anchor(index, to_preview(&image_point));
+                    anchor_points[index] = image_point;
+                }
+                _ => {}
+            }
+
+            let delete_requested = (is_right_down && !was_right_down)
+                || window.is_key_pressed(Key::Delete, KeyRepeat::No)
+                || window.is_key_pressed(Key::Backspace, KeyRepeat::No);
+            if let (true, Some(index), None) = (delete_requested, hovered, dragged) {
+                cells.remove_anchor(index);
+                anchor_points.swap_remove(index);
+            }
+        }
+        if !is_left_down {
+            dragged = None;
+        }
+        was_left_down = is_left_down;
+        was_right_down = is_right_down;
+
+        if window.is_key_pressed(Key::S, KeyRepeat::No) {
+            on_save(&anchor_points);
+        }
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            shows_cells = !shows_cells;
+        }
+
+        if shows_cells {
+            for (pixel, color) in buffer.iter_mut().zip(cells.image().pixels()) {
+                *pixel = pack_pixel(*color);
+            }
+        } else {
+            buffer.copy_from_slice(&background);
+        }
+        for (index, point) in anchor_points.iter().enumerate() {
+            let fill = if (Some(index) == dragged) || (Some(index) == hovered) {
+                0xff3030
+            } else {
+                0xffffff
+            };
+            draw_marker(
+                &mut buffer,
+                window_width,
+                window_height,
+                point.x * scale,
+                point.y * scale,
+                fill,
+            );
+        }
+        window.set_title(&format!(
+            "voronoi-painter anchors ({}) - Tab to toggle cells, S to save",
+            anchor_points.len()
+        ));
+        window.update_with_buffer(&buffer, window_width, window_height)?;
+    }
+
+    Ok(anchor_points)
+}
@@ -0,0 +1,70 @@
+use image::{Rgba, RgbaImage};
+use voronoi_painter::anchors::color_anchor_points;
+use voronoi_painter::geometry::{Euclidean, Point};
+use voronoi_painter::render::{assign_cells, render_voronoi, RenderOptions, UNASSIGNED};
+
+/// An image whose two anchors sit at opposite ends, much further apart than
+/// the minimum distance, which used to leave the columns between them out
+/// of every cell.
+fn sparse_anchors() -> (RgbaImage, Vec<Point>) {
+    let image = RgbaImage::from_fn(120, 40, |x, y| {
+        Rgba([(x * 2) as u8, (y * 6) as u8, 128, 255])
+    });
+    let points = vec![Point { x: 2.5, y: 20.5 }, Point { x: 117.5, y: 20.5 }];
+
+    (image, points)
+}
+
+#[test]
+fn every_pixel_is_assigned_to_a_cell() {
+    let (image, points) = sparse_anchors();
+    let anchors = color_anchor_points(&image, points);
+
+    let cell_map = assign_cells(&anchors, image.width(), image.height(), 10, &Euclidean);
+
+    assert!(cell_map.labels.iter().all(|label| *label != UNASSIGNED));
+    assert_eq!(cell_map.label(30, 5), 0);
+    assert_eq!(cell_map.label(90, 35), 1);
+}
+
+#[test]
+fn rendering_leaves_no_holes() {
+    let (image, points) = sparse_anchors();
+    let anchors = color_anchor_points(&image, points);
+    let options = RenderOptions {
+        minimum_distance: 10,
+        ..RenderOptions::default()
+    };
+
+    let output = render_voronoi(&image, &anchors, &options);
+
+    assert!(output.pixels().all(|pixel| pixel.0[3] == 255));
+    assert_eq!(*output.get_pixel(59, 0), anchors[0].color);
+    assert_eq!(*output.get_pixel(61, 39), anchors[1].color);
+}
+
+#[test]
+fn sparse_pixels_go_to_the_closest_anchor() {
+    let image = RgbaImage::from_pixel(40, 1000, Rgba([0, 0, 0, 255]));
+    let points = vec![Point { x: 0.0, y: 0.0 }, Point { x: 25.0, y: 999.0 }];
+    let anchors = color_anchor_points(&image, points);
+
+    let cell_map = assign_cells(&anchors, image.width(), image.height(), 10, &Euclidean);
+
+    assert_eq!(cell_map.label(16, 0), 0);
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let pixel = Point {
+                x: x as f64,
+                y: y as f64,
+            };
+            let closest = match pixel.squared_distance_from(&anchors[0].point)
+                <= pixel.squared_distance_from(&anchors[1].point)
+            {
+                true => 0,
+                false => 1,
+            };
+            assert_eq!(cell_map.label(x, y), closest, "pixel {},{}", x, y);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use voronoi_painter::base64::{decode, encode};
+
+#[test]
+fn the_rfc_examples_encode_and_decode() {
+    let examples = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    for (text, encoded) in examples {
+        assert_eq!(encode(text.as_bytes()), encoded);
+        assert_eq!(decode(encoded), Some(text.as_bytes().to_vec()));
+    }
+}
+
+#[test]
+fn every_byte_round_trips() {
+    let bytes: Vec<u8> = (0..=255).collect();
+
+    for length in 0..bytes.len() {
+        assert_eq!(
+            decode(&encode(&bytes[..length])),
+            Some(bytes[..length].to_vec())
+        );
+    }
+}
+
+#[test]
+fn unpadded_wrapped_and_url_safe_text_decodes() {
+    assert_eq!(decode("Zm9vYg"), Some(b"foob".to_vec()));
+    assert_eq!(decode("Zm9v\r\nYmFy\n"), Some(b"foobar".to_vec()));
+    assert_eq!(decode("-_-_"), decode("+/+/"));
+}
+
+#[test]
+fn other_text_is_not_base64() {
+    for text in ["Zm9v!", "Zm9v YmF*", "Z", "Zm9vY", "é"] {
+        assert_eq!(decode(text), None, "{:?}", text);
+    }
+}
@@ -0,0 +1,154 @@
+use image::Rgba;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use voronoi_painter::cache::{read_anchor_cache, write_anchor_cache, AnchorCache};
+use voronoi_painter::geometry::Point;
+
+/// A path named `name` in a directory of this test process.
+fn scratch_path(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("voronoi-painter-cache-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    directory.join(name)
+}
+
+/// Writes `bytes` to a file named `name` and reads it as an anchor cache.
+fn read_bytes(name: &str, bytes: &[u8]) -> std::io::Result<AnchorCache> {
+    let path = scratch_path(name);
+    fs::write(&path, bytes).unwrap();
+
+    read_anchor_cache(path.to_str().unwrap())
+}
+
+/// An `.npy` file of version 1 with the header `header` padded to 64 bytes,
+/// followed by `data`.
+fn npy(header: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = header.to_string();
+    header.push_str(&" ".repeat((64 - ((10 + header.len() + 1) % 64)) % 64));
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+
+    bytes
+}
+
+fn points(cache: &AnchorCache) -> Vec<(f64, f64)> {
+    cache
+        .points
+        .iter()
+        .map(|point| (point.x, point.y))
+        .collect()
+}
+
+#[test]
+fn npy_caches_round_trip() {
+    let path = scratch_path("round-trip.npy");
+    let path = path.to_str().unwrap();
+    let cache = AnchorCache {
+        points: vec![Point { x: 1.5, y: 2.25 }, Point { x: 300.0, y: 0.125 }],
+        colors: Some(vec![Rgba([255, 128, 0, 255]), Rgba([1, 2, 3, 4])]),
+        ..AnchorCache::default()
+    };
+
+    write_anchor_cache(&cache, path).unwrap();
+    let read = read_anchor_cache(path).unwrap();
+
+    assert_eq!(points(&read), points(&cache));
+    assert_eq!(read.colors, cache.colors);
+    // The data starts 64 byte aligned, as NumPy writes it.
+    let bytes = fs::read(path).unwrap();
+    assert_eq!(bytes.len() - (2 * 6 * 8), 128);
+}
+
+#[test]
+fn npy_caches_hold_no_radii() {
+    let path = scratch_path("radii.npy");
+    let cache = AnchorCache {
+        points: vec![Point { x: 1.0, y: 2.0 }],
+        radii: Some(vec![3.0]),
+        ..AnchorCache::default()
+    };
+
+    assert!(write_anchor_cache(&cache, path.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn fortran_order_npy_arrays_are_read_by_column() {
+    // The x of both anchors, then their y.
+    let data: Vec<u8> = [1f64, 2f64, 10f64, 20f64]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let bytes = npy(
+        "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 2), }",
+        &data,
+    );
+
+    let cache = read_bytes("fortran.npy", &bytes).unwrap();
+
+    assert_eq!(points(&cache), vec![(1.0, 10.0), (2.0, 20.0)]);
+}
+
+#[test]
+fn big_endian_float32_npy_arrays_are_read() {
+    let data: Vec<u8> = [0.5f32, 1.5f32, 12f32, 34f32, 56f32, 300f32]
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect();
+    let bytes = npy(
+        "{'descr': '>f4', 'fortran_order': False, 'shape': (1, 6), }",
+        &data,
+    );
+
+    let cache = read_bytes("big-endian.npy", &bytes).unwrap();
+
+    assert_eq!(points(&cache), vec![(0.5, 1.5)]);
+    // Channels are clamped to 0 to 255.
+    assert_eq!(cache.colors, Some(vec![Rgba([12, 34, 56, 255])]));
+}
+
+#[test]
+fn truncated_npy_arrays_are_errors() {
+    let data: Vec<u8> = [1f64, 2f64, 3f64, 4f64]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let bytes = npy(
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (2, 2), }",
+        &data,
+    );
+
+    for length in 0..bytes.len() {
+        assert!(
+            read_bytes("truncated.npy", &bytes[..length]).is_err(),
+            "length {}",
+            length
+        );
+    }
+    assert!(read_bytes("truncated.npy", &bytes).is_ok());
+}
+
+#[test]
+fn malformed_npy_headers_are_errors() {
+    let headers = [
+        "{'descr': '<i8', 'fortran_order': False, 'shape': (1, 2), }",
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }",
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (1, 3), }",
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (x, 2), }",
+        "{'descr': '<f8', 'fortran_order': False, }",
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (18446744073709551615, 6), }",
+    ];
+
+    for header in headers {
+        assert!(
+            read_bytes("malformed.npy", &npy(header, &[0; 16])).is_err(),
+            "{}",
+            header
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use image::{Rgba, RgbaImage};
+use voronoi_painter::anchors::color_anchor_points;
+use voronoi_painter::geometry::Point;
+use voronoi_painter::incremental::IncrementalRenderer;
+use voronoi_painter::render::{render_voronoi, RenderOptions, UNASSIGNED};
+
+#[test]
+fn edits_of_sparse_anchors_match_a_full_render() {
+    let image = RgbaImage::from_fn(120, 40, |x, y| {
+        Rgba([(x * 2) as u8, (y * 6) as u8, 128, 255])
+    });
+    let points = vec![Point { x: 2.5, y: 20.5 }, Point { x: 117.5, y: 20.5 }];
+    let options = RenderOptions {
+        minimum_distance: 10,
+        ..RenderOptions::default()
+    };
+
+    let mut renderer =
+        IncrementalRenderer::new(&image, color_anchor_points(&image, points), &options);
+    renderer.add_anchor(Point { x: 60.5, y: 5.5 });
+    renderer.move_anchor(2, Point { x: 40.5, y: 30.5 });
+    renderer.remove_anchor(0);
+
+    let full = render_voronoi(&image, renderer.anchors(), &options);
+    assert!(renderer
+        .cell_map()
+        .labels
+        .iter()
+        .all(|label| *label != UNASSIGNED));
+    assert_eq!(renderer.image().as_raw(), full.as_raw());
+}
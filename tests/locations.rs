@@ -0,0 +1,113 @@
+use voronoi_painter::locations::{parse_gpx, parse_location_csv, Location};
+
+fn coordinates(locations: &[Location]) -> Vec<(f64, f64)> {
+    locations
+        .iter()
+        .map(|location| (location.latitude, location.longitude))
+        .collect()
+}
+
+#[test]
+fn gpx_points_are_read_in_order() {
+    let gpx = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test">
+  <wpt lat="48.85" lon="2.35"><name>Paris</name></wpt>
+  <trk><trkseg>
+    <trkpt lon='-0.12' lat='51.5'/>
+  </trkseg></trk>
+  <rte><rtept lat=" 40.4 " lon="-3.7"></rtept></rte>
+</gpx>"#;
+
+    let locations = parse_gpx(gpx).unwrap();
+
+    assert_eq!(
+        coordinates(&locations),
+        vec![(48.85, 2.35), (51.5, -0.12), (40.4, -3.7)]
+    );
+}
+
+#[test]
+fn namespaced_gpx_points_are_read() {
+    let gpx = r#"<gpx:gpx xmlns:gpx="http://www.topografix.com/GPX/1/1">
+  <gpx:trk><gpx:trkseg>
+    <gpx:trkpt lat="1.5" lon="2.5"></gpx:trkpt>
+    <gpx:trkpt lat="3.5" lon="4.5"/>
+  </gpx:trkseg></gpx:trk>
+</gpx:gpx>"#;
+
+    let locations = parse_gpx(gpx).unwrap();
+
+    assert_eq!(coordinates(&locations), vec![(1.5, 2.5), (3.5, 4.5)]);
+}
+
+#[test]
+fn attributes_must_be_whole_names() {
+    // `plat` and `long` are not `lat` and `lon`.
+    let gpx = r#"<wpt plat="9" lat="1" long="9" lon="2"/>"#;
+
+    assert_eq!(coordinates(&parse_gpx(gpx).unwrap()), vec![(1.0, 2.0)]);
+}
+
+#[test]
+fn malformed_gpx_points_are_errors() {
+    assert_eq!(
+        parse_gpx(r#"<wpt lat="1""#).unwrap_err(),
+        "a tag is not closed"
+    );
+    assert_eq!(
+        parse_gpx(r#"<trkpt lon="2"/>"#).unwrap_err(),
+        "a `trkpt` has no `lat`"
+    );
+    assert_eq!(
+        parse_gpx(r#"<wpt lat="north" lon="2"/>"#).unwrap_err(),
+        "a `wpt` has a `lat` that is not a number"
+    );
+    // The unclosed quote runs into the next attribute.
+    assert_eq!(
+        parse_gpx(r#"<wpt lat="1 lon="2"/>"#).unwrap_err(),
+        "a `wpt` has a `lat` that is not a number"
+    );
+}
+
+#[test]
+fn csv_locations_are_read_by_their_header() {
+    let csv = "name,Longitude,LAT\nParis,2.35,48.85\n\nLondon, -0.12 ,51.5\n";
+
+    let locations = parse_location_csv(csv).unwrap();
+
+    assert_eq!(coordinates(&locations), vec![(48.85, 2.35), (51.5, -0.12)]);
+}
+
+#[test]
+fn quoted_csv_fields_may_hold_commas_and_quotes() {
+    let csv =
+        "\"name, in full\",lat,lng\n\"Paris, \"\"the city\"\"\",48.85,2.35\n\"x\",\"1.5\",\"2.5\"";
+
+    let locations = parse_location_csv(csv).unwrap();
+
+    assert_eq!(coordinates(&locations), vec![(48.85, 2.35), (1.5, 2.5)]);
+}
+
+#[test]
+fn empty_csvs_have_no_locations() {
+    assert_eq!(parse_location_csv("").unwrap(), Vec::new());
+    assert_eq!(parse_location_csv("lat,lon\n").unwrap(), Vec::new());
+}
+
+#[test]
+fn malformed_csvs_are_errors() {
+    assert!(parse_location_csv("name,x,y\na,1,2").is_err());
+    assert_eq!(
+        parse_location_csv("lat,lon\n1,2\n1,east").unwrap_err(),
+        "line 3: the longitude is not a number"
+    );
+    assert_eq!(
+        parse_location_csv("lat,lon\n1").unwrap_err(),
+        "line 2: the longitude is not a number"
+    );
+    // A comma inside quotes does not split the field.
+    assert_eq!(
+        parse_location_csv("lat,lon\n\"1,2\",3").unwrap_err(),
+        "line 2: the latitude is not a number"
+    );
+}
@@ -0,0 +1,376 @@
+use image::Rgba;
+use voronoi_painter::psd::decode_psd;
+
+const GRAYSCALE: u16 = 1;
+const RGB: u16 = 3;
+
+/// A layer record: its name, its bounds as top, left, bottom and right, and
+/// the id and stored data of every channel.
+struct Layer {
+    name: &'static str,
+    bounds: [i32; 4],
+    channels: Vec<(i16, Vec<u8>)>,
+}
+
+/// A Photoshop document of `channels` channels of `depth` bits, PSB if
+/// `large`, with `layers` and the composite stored as `composite`. A
+/// negative layer count marks the first extra channel as transparency.
+struct Document {
+    large: bool,
+    width: u32,
+    height: u32,
+    channels: u16,
+    depth: u16,
+    mode: u16,
+    layers: Vec<Layer>,
+    transparency: bool,
+    composite: Vec<u8>,
+}
+
+impl Document {
+    fn rgb(width: u32, height: u32, composite: Vec<u8>) -> Document {
+        Document {
+            large: false,
+            width,
+            height,
+            channels: 3,
+            depth: 8,
+            mode: RGB,
+            layers: Vec::new(),
+            transparency: false,
+            composite,
+        }
+    }
+
+    fn length(&self, bytes: &mut Vec<u8>, length: usize) {
+        if self.large {
+            bytes.extend_from_slice(&(length as u64).to_be_bytes());
+        } else {
+            bytes.extend_from_slice(&(length as u32).to_be_bytes());
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = b"8BPS".to_vec();
+        bytes.extend_from_slice(&(if self.large { 2u16 } else { 1u16 }).to_be_bytes());
+        bytes.extend_from_slice(&[0; 6]);
+        bytes.extend_from_slice(&self.channels.to_be_bytes());
+        bytes.extend_from_slice(&self.height.to_be_bytes());
+        bytes.extend_from_slice(&self.width.to_be_bytes());
+        bytes.extend_from_slice(&self.depth.to_be_bytes());
+        bytes.extend_from_slice(&self.mode.to_be_bytes());
+        // No color mode data, and image resources of four bytes.
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes.extend_from_slice(&4u32.to_be_bytes());
+        bytes.extend_from_slice(b"8BIM");
+
+        let mut info = Vec::new();
+        if !self.layers.is_empty() {
+            let count = self.layers.len() as i16;
+            let count = if self.transparency { -count } else { count };
+            info.extend_from_slice(&count.to_be_bytes());
+            for layer in &self.layers {
+                for bound in layer.bounds {
+                    info.extend_from_slice(&bound.to_be_bytes());
+                }
+                info.extend_from_slice(&(layer.channels.len() as u16).to_be_bytes());
+                for (id, data) in &layer.channels {
+                    info.extend_from_slice(&id.to_be_bytes());
+                    self.length(&mut info, data.len());
+                }
+                info.extend_from_slice(b"8BIMnorm");
+                info.extend_from_slice(&[255, 0, 0, 0]);
+                let mut extra = vec![0u8; 8];
+                extra.push(layer.name.len() as u8);
+                extra.extend_from_slice(layer.name.as_bytes());
+                extra.resize(8 + (layer.name.len() + 1).div_ceil(4) * 4, 0);
+                info.extend_from_slice(&(extra.len() as u32).to_be_bytes());
+                info.extend_from_slice(&extra);
+            }
+            for layer in &self.layers {
+                for (_, data) in &layer.channels {
+                    info.extend_from_slice(data);
+                }
+            }
+        }
+        let mut section = Vec::new();
+        if !info.is_empty() {
+            self.length(&mut section, info.len());
+            section.extend_from_slice(&info);
+        }
+        self.length(&mut bytes, section.len());
+        bytes.extend_from_slice(&section);
+        bytes.extend_from_slice(&self.composite);
+
+        bytes
+    }
+}
+
+/// Raw channel data: the compression and then `samples`.
+fn raw(samples: &[u8]) -> Vec<u8> {
+    let mut data = vec![0, 0];
+    data.extend_from_slice(samples);
+
+    data
+}
+
+/// Run length encoded `rows` rows, each one run of `value` repeated `width`
+/// times.
+fn run_length(rows: usize, width: usize, value: u8, large: bool) -> Vec<u8> {
+    let row = [(1i8 - (width as i8)) as u8, value];
+    let mut data = vec![0, 1];
+    for _ in 0..rows {
+        if large {
+            data.extend_from_slice(&2u32.to_be_bytes());
+        } else {
+            data.extend_from_slice(&2u16.to_be_bytes());
+        }
+    }
+    for _ in 0..rows {
+        data.extend_from_slice(&row);
+    }
+
+    data
+}
+
+fn pixels(image: &image::RgbaImage) -> Vec<[u8; 4]> {
+    image.pixels().map(|pixel| pixel.0).collect()
+}
+
+#[test]
+fn raw_rgb_composites_decode() {
+    // Every channel as a plane of rows.
+    let document = Document::rgb(2, 1, raw(&[10, 20, 30, 40, 50, 60]));
+
+    let image = decode_psd(&document.encode(), None).unwrap();
+
+    assert_eq!(image.dimensions(), (2, 1));
+    assert_eq!(pixels(&image), vec![[10, 30, 50, 255], [20, 40, 60, 255]]);
+}
+
+#[test]
+fn run_length_grayscale_composites_decode() {
+    let document = Document {
+        channels: 1,
+        mode: GRAYSCALE,
+        ..Document::rgb(3, 2, run_length(2, 3, 77, false))
+    };
+
+    let image = decode_psd(&document.encode(), None).unwrap();
+
+    assert!(pixels(&image)
+        .iter()
+        .all(|pixel| *pixel == [77, 77, 77, 255]));
+}
+
+#[test]
+fn psb_composites_decode() {
+    let document = Document {
+        large: true,
+        ..Document::rgb(4, 1, run_length(3, 4, 9, true))
+    };
+
+    let image = decode_psd(&document.encode(), None).unwrap();
+
+    assert!(pixels(&image).iter().all(|pixel| *pixel == [9, 9, 9, 255]));
+}
+
+#[test]
+fn sixteen_bit_composites_keep_their_high_bytes() {
+    let document = Document {
+        depth: 16,
+        ..Document::rgb(1, 1, raw(&[0x12, 0xFF, 0x34, 0xFF, 0x56, 0xFF]))
+    };
+
+    let image = decode_psd(&document.encode(), None).unwrap();
+
+    assert_eq!(pixels(&image), vec![[0x12, 0x34, 0x56, 255]]);
+}
+
+#[test]
+fn composites_read_the_transparency_of_their_layers() {
+    let layer = Layer {
+        name: "a",
+        bounds: [0, 0, 1, 1],
+        channels: vec![(0, raw(&[1]))],
+    };
+    let document = Document {
+        channels: 4,
+        layers: vec![layer],
+        transparency: true,
+        ..Document::rgb(1, 1, raw(&[1, 2, 3, 128]))
+    };
+
+    let image = decode_psd(&document.encode(), None).unwrap();
+
+    assert_eq!(pixels(&image), vec![[1, 2, 3, 128]]);
+}
+
+#[test]
+fn layers_decode_by_name_onto_the_canvas() {
+    let background = Layer {
+        name: "background",
+        bounds: [0, 0, 2, 3],
+        channels: vec![(0, raw(&[0; 6])), (1, raw(&[0; 6])), (2, raw(&[0; 6]))],
+    };
+    // One pixel wide and two high at the second column, half transparent.
+    let mark = Layer {
+        name: "mark",
+        bounds: [0, 1, 2, 2],
+        channels: vec![
+            (-1, raw(&[128, 255])),
+            (0, raw(&[200, 201])),
+            (1, raw(&[100, 101])),
+            (2, raw(&[50, 51])),
+        ],
+    };
+    let document = Document {
+        layers: vec![background, mark],
+        ..Document::rgb(3, 2, raw(&[0; 18]))
+    };
+
+    let image = decode_psd(&document.encode(), Some("mark")).unwrap();
+
+    assert_eq!(image.dimensions(), (3, 2));
+    assert_eq!(*image.get_pixel(1, 0), Rgba([200, 100, 50, 128]));
+    assert_eq!(*image.get_pixel(1, 1), Rgba([201, 101, 51, 255]));
+    assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+    assert_eq!(
+        decode_psd(&document.encode(), Some("missing")).unwrap_err(),
+        "no layer named `missing`, the layers are: background, mark"
+    );
+}
+
+#[test]
+fn truncated_documents_are_errors() {
+    let layer = Layer {
+        name: "layer",
+        bounds: [0, 0, 1, 2],
+        channels: vec![(0, raw(&[1, 2])), (1, run_length(1, 2, 3, false))],
+    };
+    let document = Document {
+        layers: vec![layer],
+        ..Document::rgb(2, 1, raw(&[1, 2, 3, 4, 5, 6]))
+    };
+    let bytes = document.encode();
+
+    for length in 0..bytes.len() {
+        assert!(
+            decode_psd(&bytes[..length], None).is_err(),
+            "length {}",
+            length
+        );
+    }
+    assert!(decode_psd(&bytes, None).is_ok());
+}
+
+#[test]
+fn unsupported_documents_are_errors() {
+    let errors = [
+        (
+            Document {
+                depth: 32,
+                ..Document::rgb(1, 1, raw(&[0; 12]))
+            },
+            "only 8 and 16 bit documents are supported, not 32 bit",
+        ),
+        (
+            Document {
+                mode: 4,
+                ..Document::rgb(1, 1, raw(&[0; 3]))
+            },
+            "only grayscale and RGB documents are supported",
+        ),
+        (
+            Document {
+                channels: 2,
+                ..Document::rgb(1, 1, raw(&[0; 2]))
+            },
+            "document has too few channels",
+        ),
+        (
+            Document::rgb(1, 1, vec![0, 2, 0, 0, 0]),
+            "ZIP compressed channels are not supported",
+        ),
+    ];
+
+    for (document, error) in errors {
+        assert_eq!(decode_psd(&document.encode(), None).unwrap_err(), error);
+    }
+    assert_eq!(
+        decode_psd(b"8BPS\x00\x03", None).unwrap_err(),
+        "unknown Photoshop version 3"
+    );
+    assert_eq!(
+        decode_psd(b"GIF89a", None).unwrap_err(),
+        "not a Photoshop document"
+    );
+}
+
+#[test]
+fn documents_larger_than_their_data_are_errors() {
+    for composite in [raw(&[0; 16]), run_length(4, 4, 0, false)] {
+        let document = Document::rgb(u32::MAX, u32::MAX, composite);
+
+        assert!(decode_psd(&document.encode(), None).is_err());
+    }
+    let document = Document::rgb(30_000, 30_000, run_length(4, 4, 0, false));
+    assert_eq!(
+        decode_psd(&document.encode(), None).unwrap_err(),
+        "Photoshop document larger than its data"
+    );
+}
+
+/// A document of one `layer`, three pixels wide and two high.
+fn one_layer(layer: Layer) -> Vec<u8> {
+    Document {
+        layers: vec![layer],
+        ..Document::rgb(3, 2, raw(&[0; 18]))
+    }
+    .encode()
+}
+
+#[test]
+fn layers_past_the_canvas_are_clipped() {
+    // Two by two from one pixel left and above the canvas.
+    let layer = Layer {
+        name: "corner",
+        bounds: [-1, -1, 1, 1],
+        channels: vec![
+            (0, raw(&[1, 2, 3, 4])),
+            (1, raw(&[1, 2, 3, 4])),
+            (2, raw(&[1, 2, 3, 4])),
+        ],
+    };
+
+    let image = decode_psd(&one_layer(layer), Some("corner")).unwrap();
+
+    assert_eq!(*image.get_pixel(0, 0), Rgba([4, 4, 4, 255]));
+    assert_eq!(*image.get_pixel(1, 0), Rgba([0, 0, 0, 0]));
+    assert_eq!(*image.get_pixel(0, 1), Rgba([0, 0, 0, 0]));
+}
+
+#[test]
+fn layers_no_document_can_hold_are_errors() {
+    let bounds = [
+        [i32::MIN, i32::MIN, i32::MAX, i32::MAX],
+        [0, i32::MIN, 1, 1],
+        [1, 0, 0, 1],
+        [0, 0, 1, 30_001],
+    ];
+
+    for bounds in bounds {
+        let layer = Layer {
+            name: "huge",
+            bounds,
+            channels: vec![(0, raw(&[0]))],
+        };
+
+        assert_eq!(
+            decode_psd(&one_layer(layer), Some("huge")).unwrap_err(),
+            "layer `huge` has bounds no document can hold",
+            "{:?}",
+            bounds
+        );
+    }
+}
@@ -0,0 +1,206 @@
+#![cfg(feature = "raw")]
+
+use voronoi_painter::raw::decode_raw;
+
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+
+/// An IFD entry: its tag, its type and its values.
+type Entry = (u16, u16, Vec<u32>);
+
+/// A little endian TIFF with `data` right after the header and the chain of
+/// `ifds` after it, the header marked as a CR2 if `cr2`.
+fn tiff(ifds: &[Vec<Entry>], data: &[u8], cr2: bool) -> Vec<u8> {
+    let mut bytes = vec![0x49, 0x49, 0x2A, 0x00, 0, 0, 0, 0];
+    if cr2 {
+        bytes.extend_from_slice(b"CR\x02\x00\x00\x00\x00\x00");
+    }
+    bytes.extend_from_slice(data);
+    if bytes.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    let first = bytes.len() as u32;
+    bytes[4..8].copy_from_slice(&first.to_le_bytes());
+
+    for (index, entries) in ifds.iter().enumerate() {
+        let start = bytes.len();
+        let end = start + 2 + (entries.len() * 12) + 4;
+        let mut values_area: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, kind, values) in entries {
+            let encoded: Vec<u8> = values
+                .iter()
+                .flat_map(|value| match *kind {
+                    SHORT => (*value as u16).to_le_bytes().to_vec(),
+                    _ => value.to_le_bytes().to_vec(),
+                })
+                .collect();
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&kind.to_le_bytes());
+            bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            if encoded.len() <= 4 {
+                let mut inline = encoded.clone();
+                inline.resize(4, 0);
+                bytes.extend_from_slice(&inline);
+            } else {
+                let offset = (end + values_area.len()) as u32;
+                bytes.extend_from_slice(&offset.to_le_bytes());
+                values_area.extend_from_slice(&encoded);
+            }
+        }
+        let next = match index + 1 < ifds.len() {
+            true => (end + values_area.len()) as u32,
+            false => 0,
+        };
+        bytes.extend_from_slice(&next.to_le_bytes());
+        bytes.extend_from_slice(&values_area);
+    }
+
+    bytes
+}
+
+/// Where [`tiff`] puts the data of a file that is not a CR2.
+const DATA_OFFSET: u32 = 8;
+
+/// A DNG of a `width` by `height` color filter array, its sensor data
+/// stored as `data` with `compression`.
+fn dng(width: u32, height: u32, compression: u32, data: &[u8]) -> Vec<u8> {
+    let ifd = vec![
+        (256, LONG, vec![width]),
+        (257, LONG, vec![height]),
+        (258, SHORT, vec![16]),
+        (259, SHORT, vec![compression]),
+        (262, SHORT, vec![32803]),
+        (273, LONG, vec![DATA_OFFSET]),
+        (279, LONG, vec![data.len() as u32]),
+    ];
+
+    tiff(&[ifd], data, false)
+}
+
+/// A lossless JPEG of one `width` by `height` component of `precision`
+/// bits, whose single Huffman code `0` stands for `value`, followed by
+/// `scan`.
+fn lossless_jpeg(width: u16, height: u16, precision: u8, value: u8, scan: &[u8]) -> Vec<u8> {
+    let mut jpeg = vec![0xFF, 0xD8];
+    // One code of one bit.
+    jpeg.extend_from_slice(&[0xFF, 0xC4, 0x00, 20, 0x00, 1]);
+    jpeg.extend_from_slice(&[0; 15]);
+    jpeg.push(value);
+    jpeg.extend_from_slice(&[0xFF, 0xC3, 0x00, 11, precision]);
+    jpeg.extend_from_slice(&height.to_be_bytes());
+    jpeg.extend_from_slice(&width.to_be_bytes());
+    jpeg.extend_from_slice(&[1, 1, 0x11, 0]);
+    jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 8, 1, 1, 0x00, 1, 0, 0]);
+    jpeg.extend_from_slice(scan);
+    jpeg.extend_from_slice(&[0xFF, 0xD9]);
+
+    jpeg
+}
+
+#[test]
+fn uncompressed_sensor_data_decodes() {
+    let data: Vec<u8> = [100u16, 2000, 3000, 4000]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+
+    let image = decode_raw(&dng(2, 2, 1, &data)).unwrap();
+
+    assert_eq!(image.dimensions(), (2, 2));
+    assert!(image.pixels().all(|pixel| pixel.0[3] == 255));
+}
+
+#[test]
+fn lossless_jpeg_sensor_data_decodes() {
+    // Every difference is zero, so every sample is the initial prediction.
+    let image = decode_raw(&dng(2, 2, 7, &lossless_jpeg(2, 2, 8, 0, &[0x00]))).unwrap();
+
+    assert_eq!(image.dimensions(), (2, 2));
+}
+
+#[test]
+fn other_files_are_not_raw() {
+    assert!(decode_raw(b"").is_err());
+    assert!(decode_raw(b"\x89PNG\r\n\x1a\n").is_err());
+}
+
+#[test]
+fn truncated_files_are_errors() {
+    let data: Vec<u8> = (0..32).collect();
+    let bytes = dng(4, 4, 1, &data);
+
+    for length in 0..bytes.len() {
+        assert!(decode_raw(&bytes[..length]).is_err(), "length {}", length);
+    }
+}
+
+#[test]
+fn images_larger_than_the_file_are_errors() {
+    let error = decode_raw(&dng(100_000, 100_000, 1, &[0; 8])).unwrap_err();
+
+    assert_eq!(error, "image larger than the file holds");
+}
+
+#[test]
+fn truncated_lossless_jpegs_are_errors() {
+    let jpeg = lossless_jpeg(2, 2, 8, 0, &[0x00]);
+
+    // Cut anywhere before the scan.
+    for length in 0..(jpeg.len() - 3) {
+        assert!(
+            decode_raw(&dng(2, 2, 7, &jpeg[..length])).is_err(),
+            "length {}",
+            length
+        );
+    }
+}
+
+#[test]
+fn huffman_values_past_16_bits_are_errors() {
+    for value in [17, 32, 40, 65, 255] {
+        let jpeg = lossless_jpeg(2, 2, 8, value, &[0x00; 8]);
+
+        let error = decode_raw(&dng(2, 2, 7, &jpeg)).unwrap_err();
+
+        assert_eq!(error, "malformed lossless JPEG", "value {}", value);
+    }
+}
+
+/// A CR2 whose sensor data is `jpeg`, in `slices`.
+fn cr2(jpeg: &[u8], slices: Vec<u32>) -> Vec<u8> {
+    let preview = vec![(256, LONG, vec![1])];
+    // The JPEG starts after the header and its CR2 marker.
+    let sensor = vec![
+        (259, SHORT, vec![6]),
+        (273, LONG, vec![16]),
+        (279, LONG, vec![jpeg.len() as u32]),
+        (50752, LONG, slices),
+    ];
+
+    tiff(
+        &[preview.clone(), preview.clone(), preview, sensor],
+        jpeg,
+        true,
+    )
+}
+
+#[test]
+fn lossless_jpegs_larger_than_their_scan_are_errors() {
+    let jpeg = lossless_jpeg(60_000, 60_000, 8, 0, &[0x00]);
+
+    let error = decode_raw(&cr2(&jpeg, vec![0, 0, 60_000])).unwrap_err();
+
+    assert_eq!(error, "lossless JPEG larger than its data");
+}
+
+#[test]
+fn malformed_cr2_slices_are_errors() {
+    for (jpeg_width, slices) in [(2, vec![u32::MAX, u32::MAX, 1]), (0, vec![0, 0, 0])] {
+        let jpeg = lossless_jpeg(jpeg_width, 2, 8, 0, &[0x00]);
+
+        let error = decode_raw(&cr2(&jpeg, slices)).unwrap_err();
+
+        assert_eq!(error, "CR2 slices larger than its sensor data");
+    }
+}
@@ -0,0 +1,112 @@
+use voronoi_painter::recipe::{Recipe, RecipeValue};
+
+fn text(value: &str) -> RecipeValue {
+    RecipeValue::Text(value.to_string())
+}
+
+#[test]
+fn settings_and_steps_are_read_in_order() {
+    let recipe = Recipe::parse(
+        "# A recipe
+input = \"in.png\"
+seed = 1_000
+
+[[step]]
+kind = 'painting' # the first
+distances = [10, 20.5, -3]
+smooth = true
+
+[[step]]
+kind = \"composite\"
+names = [\"a\", 'b', [false]]
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        recipe.settings.settings,
+        vec![
+            (String::from("input"), text("in.png")),
+            (String::from("seed"), RecipeValue::Number(1000f64)),
+        ]
+    );
+    assert_eq!(recipe.steps.len(), 2);
+    assert_eq!(recipe.steps[0].line, 5);
+    assert_eq!(recipe.steps[0].get("kind"), Some(&text("painting")));
+    assert_eq!(
+        recipe.steps[0].get("distances").unwrap().to_argument(),
+        "10,20.5,-3"
+    );
+    assert_eq!(
+        recipe.steps[0].get("smooth"),
+        Some(&RecipeValue::Boolean(true))
+    );
+    assert_eq!(
+        recipe.steps[1].get("names"),
+        Some(&RecipeValue::List(vec![
+            text("a"),
+            text("b"),
+            RecipeValue::List(vec![RecipeValue::Boolean(false)]),
+        ]))
+    );
+}
+
+#[test]
+fn strings_keep_their_escapes_and_comment_signs() {
+    let recipe = Recipe::parse(
+        r##"basic = "a \"#\" b\tc\\"
+literal = 'C:\path # not a comment'
+"quoted = key" = 1
+"##,
+    )
+    .unwrap();
+
+    assert_eq!(recipe.settings.get("basic"), Some(&text("a \"#\" b\tc\\")));
+    assert_eq!(
+        recipe.settings.get("literal"),
+        Some(&text("C:\\path # not a comment"))
+    );
+    assert_eq!(
+        recipe.settings.get("quoted = key"),
+        Some(&RecipeValue::Number(1f64))
+    );
+}
+
+#[test]
+fn empty_recipes_have_no_steps() {
+    assert_eq!(Recipe::parse("").unwrap(), Recipe::default());
+    assert_eq!(
+        Recipe::parse("\n  # only a comment\n").unwrap(),
+        Recipe::default()
+    );
+}
+
+#[test]
+fn malformed_recipes_are_errors_naming_their_line() {
+    let errors = [
+        ("[step]", "line 1: only `[[step]]` tables are supported"),
+        ("\nkey", "line 2: expected `key = value`"),
+        ("key =", "line 1: expected a value"),
+        ("key = \"open", "line 1: unterminated string"),
+        ("key = 'open", "line 1: unterminated string"),
+        ("key = \"\\q\"", "line 1: unknown escape `\\q`"),
+        ("key = [1, 2", "line 1: expected `,` or `]` in array"),
+        (
+            "key = [\"a\" \"b\"]",
+            "line 1: expected `,` or `]` in array",
+        ),
+        (
+            "key = maybe",
+            "line 1: `maybe` is not a string, number or boolean",
+        ),
+        ("key = 1, 2", "line 1: unexpected `, 2` after the value"),
+        ("key = \"a\" b", "line 1: unexpected `b` after the value"),
+        ("key = 1\nkey = 2", "line 2: `key` is set twice"),
+        ("\"key = 1", "line 1: unterminated string"),
+        ("\"key\" 1", "line 1: expected `key = value`"),
+    ];
+
+    for (recipe, error) in errors {
+        assert_eq!(Recipe::parse(recipe).unwrap_err(), error, "{:?}", recipe);
+    }
+}
@@ -0,0 +1,190 @@
+use image::Rgba;
+use voronoi_painter::anchors::Anchor;
+use voronoi_painter::geometry::{Euclidean, Point};
+use voronoi_painter::render::assign_cells;
+use voronoi_painter::script::{CellBorder, CellScript, MAXIMUM_STEPS};
+
+/// Three cells side by side on a 30 by 10 canvas, colored red, green and
+/// blue from the left.
+fn anchors() -> Vec<Anchor> {
+    [
+        (5.5, Rgba([255, 0, 0, 255])),
+        (15.5, Rgba([0, 255, 0, 255])),
+        (25.5, Rgba([0, 0, 255, 255])),
+    ]
+    .iter()
+    .map(|(x, color)| Anchor {
+        point: Point { x: *x, y: 5.5 },
+        color: *color,
+    })
+    .collect()
+}
+
+/// Runs `source` over the cells of [`anchors`], returning their fills and
+/// borders.
+fn run(source: &str) -> (Vec<Rgba<u8>>, Vec<CellBorder>) {
+    let script = CellScript::parse(source).unwrap();
+    let anchors = anchors();
+    let cell_map = assign_cells(&anchors, 30, 10, 5, &Euclidean);
+    let mut colors: Vec<Rgba<u8>> = anchors.iter().map(|anchor| anchor.color).collect();
+
+    let borders = script.run(&cell_map, &anchors, &mut colors);
+    assert_eq!(script.failure(), None);
+
+    (colors, borders)
+}
+
+fn parse_error(source: &str) -> String {
+    CellScript::parse(source).unwrap_err()
+}
+
+#[test]
+fn tokenizer_errors_name_their_line() {
+    assert_eq!(parse_error("fill = color # 2;"), "line 1: unexpected `#`");
+    assert_eq!(
+        parse_error("\nfill = \"#ff0000;"),
+        "line 2: the string is not closed"
+    );
+    assert_eq!(parse_error("fill = color @ 2;"), "line 1: unexpected `@`");
+}
+
+#[test]
+fn type_errors_are_found_when_reading() {
+    assert_eq!(
+        parse_error("fill = 3;"),
+        "line 1: `fill` must be a color, not a number"
+    );
+    assert_eq!(
+        parse_error("if area { fill = color; }"),
+        "line 1: a condition must be a boolean, not a number"
+    );
+    assert!(parse_error("stroke_width = color + 1;").contains("cannot combine"));
+    assert_eq!(parse_error("x = 1;"), "line 1: `x` cannot be set");
+    assert_eq!(
+        parse_error("fill = missing;"),
+        "line 1: unknown variable `missing`"
+    );
+    assert_eq!(
+        parse_error("fill = shade(1);"),
+        "line 1: unknown function `shade`"
+    );
+    assert_eq!(
+        parse_error("fill = \"#zz0000\";"),
+        "line 1: `\"#zz0000\"` is not a hex color"
+    );
+}
+
+#[test]
+fn variables_declared_in_loops_end_with_them() {
+    assert_eq!(
+        parse_error("for i in 0..2 { let a = i; }\nstroke_width = a;"),
+        "line 2: unknown variable `a`"
+    );
+}
+
+#[test]
+fn scripts_set_fills_and_borders() {
+    let (colors, borders) = run("
+        // The middle cell turns white, the others keep their color.
+        if index == 1 {
+            fill = \"#ffffff\";
+        } else {
+            fill = color;
+        }
+        stroke_width = index + 1;
+        stroke_color = gray(10);
+    ");
+
+    assert_eq!(
+        colors,
+        vec![
+            Rgba([255, 0, 0, 255]),
+            Rgba([255, 255, 255, 255]),
+            Rgba([0, 0, 255, 255]),
+        ]
+    );
+    assert_eq!(
+        borders
+            .iter()
+            .map(|border| border.width)
+            .collect::<Vec<_>>(),
+        vec![1f64, 2f64, 3f64]
+    );
+    assert!(borders
+        .iter()
+        .all(|border| border.color == Rgba([10, 10, 10, 255])));
+}
+
+#[test]
+fn loops_and_functions_evaluate() {
+    let (colors, borders) = run("
+        let sum = 0;
+        for i in 0..neighbors {
+            sum = sum + neighbor_area(i);
+        }
+        stroke_width = sum;
+        fill = mix(color, rgb(0, 0, 0), 0.5);
+    ");
+
+    // The cells are 10 pixels wide and 10 high.
+    assert_eq!(
+        borders
+            .iter()
+            .map(|border| border.width)
+            .collect::<Vec<_>>(),
+        vec![100f64, 200f64, 100f64]
+    );
+    assert_eq!(colors[0], Rgba([128, 0, 0, 255]));
+}
+
+#[test]
+fn ranges_past_the_limit_are_errors_when_reading() {
+    assert_eq!(
+        parse_error("for i in 0..1000000000000 { }"),
+        format!(
+            "line 1: the range runs 1000000000000 times, more than the {} a cell may loop",
+            MAXIMUM_STEPS
+        )
+    );
+}
+
+#[test]
+fn ranges_past_2_to_the_53_end() {
+    // Adding one to 2^53 leaves it the same.
+    let (_, borders) = run("
+        for i in 9007199254740992..9007199254740992 + 2 {
+            stroke_width = stroke_width + 1;
+        }
+    ");
+
+    assert!(borders.iter().all(|border| border.width == 2f64));
+}
+
+#[test]
+fn loops_past_the_limit_stop_the_script() {
+    let script = CellScript::parse(
+        "
+        for i in 0..2000 {
+            for j in 0..area {
+                stroke_width = 1;
+            }
+        }
+    ",
+    )
+    .unwrap();
+    let anchors = anchors();
+    let cell_map = assign_cells(&anchors, 30, 10, 5, &Euclidean);
+    let mut colors: Vec<Rgba<u8>> = anchors.iter().map(|anchor| anchor.color).collect();
+
+    let borders = script.run(&cell_map, &anchors, &mut colors);
+
+    assert_eq!(
+        script.failure(),
+        Some(format!(
+            "cell 0: loops ran more than {} times for one cell",
+            MAXIMUM_STEPS
+        ))
+    );
+    assert_eq!(borders.len(), anchors.len());
+    assert!(borders.iter().all(|border| border.width == 0f64));
+}
@@ -0,0 +1,88 @@
+use image::{Rgba, RgbaImage};
+use voronoi_painter::anchors::color_anchor_points;
+use voronoi_painter::color::ColorSpace;
+use voronoi_painter::colorize::{CellColorizer, MeanColorizer, MedianColorizer};
+use voronoi_painter::geometry::{Euclidean, Point};
+use voronoi_painter::render::{assign_cells, color_cells, finish_cell_colors, RenderOptions};
+use voronoi_painter::stream::stream_cell_colors;
+
+/// An image taller than a strip, with anchors scattered across it.
+fn scattered_anchors() -> (RgbaImage, Vec<Point>) {
+    let image = RgbaImage::from_fn(90, 700, |x, y| {
+        Rgba([
+            (x * 2) as u8,
+            (y % 256) as u8,
+            ((x + y) % 7 * 30) as u8,
+            255,
+        ])
+    });
+    let points = (0..60u32)
+        .map(|index| Point {
+            x: ((index * 37) % 90) as f64 + 0.5,
+            y: ((index * 113) % 700) as f64 + 0.5,
+        })
+        .collect();
+
+    (image, points)
+}
+
+/// The colors of the cells of `points` on `image` the way a painting
+/// without streaming chooses them.
+fn whole_image_colors(
+    image: &RgbaImage,
+    points: Vec<Point>,
+    options: &RenderOptions,
+) -> Vec<Rgba<u8>> {
+    let anchors = color_anchor_points(image, points);
+    let cell_map = assign_cells(
+        &anchors,
+        image.width(),
+        image.height(),
+        options.minimum_distance,
+        &Euclidean,
+    );
+    let mut colors = color_cells(&cell_map, &anchors, image, options.colorizer);
+    finish_cell_colors(&cell_map, &mut colors, options);
+
+    colors
+}
+
+#[test]
+fn streamed_colors_match_the_whole_image() {
+    let (image, points) = scattered_anchors();
+    let anchors = color_anchor_points(&image, points.clone());
+    let colorizers = [
+        MeanColorizer::default(),
+        MeanColorizer {
+            color_space: ColorSpace::Oklab,
+        },
+    ];
+
+    for colorizer in &colorizers {
+        for merge_threshold in [None, Some(20f64)] {
+            let options = RenderOptions {
+                minimum_distance: 12,
+                colorizer: colorizer as &dyn CellColorizer,
+                merge_threshold,
+                ..RenderOptions::default()
+            };
+
+            assert_eq!(
+                stream_cell_colors(&image, &anchors, &options),
+                Some(whole_image_colors(&image, points.clone(), &options))
+            );
+        }
+    }
+}
+
+#[test]
+fn colorizers_needing_every_pixel_are_not_streamed() {
+    let (image, points) = scattered_anchors();
+    let anchors = color_anchor_points(&image, points);
+    let options = RenderOptions {
+        colorizer: &MedianColorizer,
+        ..RenderOptions::default()
+    };
+
+    assert_eq!(stream_cell_colors(&image, &anchors, &options), None);
+}